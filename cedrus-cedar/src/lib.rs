@@ -7,11 +7,12 @@ pub mod proto {
 
 use std::{
     borrow::Borrow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     hash::Hash,
     str::{self, FromStr},
 };
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -69,12 +70,14 @@ impl From<cedar_policy::EntityUid> for EntityUid {
     }
 }
 
-impl From<EntityUid> for cedar_policy::EntityUid {
-    fn from(val: EntityUid) -> Self {
-        cedar_policy::EntityUid::from_type_name_and_id(
-            cedar_policy::EntityTypeName::from_str(&val.r#type).unwrap(),
-            cedar_policy::EntityId::from_str(&val.id).unwrap(),
-        )
+impl TryFrom<EntityUid> for cedar_policy::EntityUid {
+    type Error = cedar_policy::ParseErrors;
+
+    fn try_from(val: EntityUid) -> Result<Self, Self::Error> {
+        Ok(cedar_policy::EntityUid::from_type_name_and_id(
+            cedar_policy::EntityTypeName::from_str(&val.r#type)?,
+            cedar_policy::EntityId::new(val.id),
+        ))
     }
 }
 
@@ -109,6 +112,12 @@ pub struct ExtensionFn {
     arg: String,
 }
 
+impl ExtensionFn {
+    pub fn new(r#fn: String, arg: String) -> Self {
+        Self { r#fn, arg }
+    }
+}
+
 impl From<proto::ExtensionFn> for ExtensionFn {
     fn from(value: proto::ExtensionFn) -> Self {
         Self {
@@ -133,6 +142,12 @@ pub struct EntityUidEscape {
     entity: EntityUid,
 }
 
+impl EntityUidEscape {
+    pub fn uid(&self) -> &EntityUid {
+        &self.entity
+    }
+}
+
 impl From<cedar_policy::EntityUid> for EntityUidEscape {
     fn from(value: cedar_policy::EntityUid) -> Self {
         let entity = EntityUid {
@@ -143,12 +158,11 @@ impl From<cedar_policy::EntityUid> for EntityUidEscape {
     }
 }
 
-impl From<EntityUidEscape> for cedar_policy::EntityUid {
-    fn from(val: EntityUidEscape) -> Self {
-        cedar_policy::EntityUid::from_type_name_and_id(
-            cedar_policy::EntityTypeName::from_str(&val.entity.r#type).unwrap(),
-            cedar_policy::EntityId::from_str(&val.entity.id).unwrap(),
-        )
+impl TryFrom<EntityUidEscape> for cedar_policy::EntityUid {
+    type Error = cedar_policy::ParseErrors;
+
+    fn try_from(val: EntityUidEscape) -> Result<Self, Self::Error> {
+        val.entity.try_into()
     }
 }
 
@@ -240,6 +254,8 @@ pub mod entity {
     pub enum EntityAttr {
         String(String),
         Number(i64),
+        /// Fractional number, rendered as a Cedar `decimal` escape
+        Decimal(#[serde(serialize_with = "serialize_decimal")] f64),
         Boolean(bool),
         #[schema(no_recursion)]
         Set(Vec<EntityAttr>),
@@ -257,9 +273,103 @@ pub mod entity {
         }
     }
 
+    impl EntityAttr {
+        /// An `__extn` escape calling the extension function `name`, e.g. `ip` or `decimal`.
+        pub fn extension(name: &str, arg: String) -> Self {
+            EntityAttr::FunctionEscape(ExtensionFn::new(name.to_string(), arg).into())
+        }
+
+        /// Cedar type of the value, named as in context errors, e.g. `Long` or `Entity<User>`.
+        pub fn type_name(&self) -> String {
+            match self {
+                EntityAttr::String(_) => "String".to_owned(),
+                EntityAttr::Number(_) => "Long".to_owned(),
+                EntityAttr::Boolean(_) => "Boolean".to_owned(),
+                EntityAttr::Set(_) => "Set".to_owned(),
+                EntityAttr::Record(_) => "Record".to_owned(),
+                EntityAttr::EntityUid(uid)
+                | EntityAttr::EntityUidEscape(EntityUidEscape { entity: uid }) => {
+                    format!("Entity<{}>", uid.type_name())
+                }
+                EntityAttr::Decimal(_)
+                | EntityAttr::Function(_)
+                | EntityAttr::FunctionEscape(_) => "Extension".to_owned(),
+            }
+        }
+
+        /// Reports the decimals in the value, at `path` or nested below it, that a Cedar
+        /// `decimal` cannot hold without rounding.
+        pub fn decimal_errors(&self, path: &str, errors: &mut Vec<String>) {
+            match self {
+                EntityAttr::Decimal(value) => {
+                    if let Err(e) = decimal_check(*value) {
+                        errors.push(format!("{path}: {e}"));
+                    }
+                }
+                EntityAttr::Set(items) => {
+                    for (i, item) in items.iter().enumerate() {
+                        item.decimal_errors(&format!("{path}[{i}]"), errors);
+                    }
+                }
+                EntityAttr::Record(map) => {
+                    for (name, item) in map {
+                        item.decimal_errors(&format!("{path}.{name}"), errors);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    impl From<std::net::IpAddr> for EntityAttr {
+        fn from(value: std::net::IpAddr) -> Self {
+            EntityAttr::extension("ip", value.to_string())
+        }
+    }
+
+    /// Formats a number as a Cedar decimal literal, which has one to four fractional digits.
+    pub fn decimal_arg(value: f64) -> String {
+        let arg = format!("{value:.4}");
+        match arg.trim_end_matches('0') {
+            trimmed if trimmed.ends_with('.') => format!("{trimmed}0"),
+            trimmed => trimmed.to_string(),
+        }
+    }
+
+    /// Checks a number is a Cedar decimal: a count of ten-thousandths that fits in an `i64`,
+    /// so within about ±922337203685477 and with at most four fractional digits.
+    pub fn decimal_check(value: f64) -> Result<(), String> {
+        if !value.is_finite() || (value * 10_000.0).abs() >= i64::MAX as f64 {
+            return Err(format!("decimal {value} is out of range"));
+        }
+        // The shortest representation that reads back as the same number
+        let digits = value
+            .to_string()
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.len());
+        if digits > 4 {
+            return Err(format!(
+                "decimal {value} has more than four fractional digits"
+            ));
+        }
+        Ok(())
+    }
+
+    fn serialize_decimal<S: serde::Serializer>(
+        value: &f64,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        ExtensionFnEscape::from(ExtensionFn::new("decimal".to_string(), decimal_arg(*value)))
+            .serialize(serializer)
+    }
+
     impl From<proto::entity::EntityAttr> for EntityAttr {
         fn from(value: proto::entity::EntityAttr) -> Self {
-            match value.value.unwrap() {
+            // A value missing from the message decodes as its default, an empty string
+            let Some(value) = value.value else {
+                return Self::String(String::new());
+            };
+            match value {
                 proto::entity::entity_attr::Value::S(s) => Self::String(s),
                 proto::entity::entity_attr::Value::I(n) => Self::Number(n),
                 proto::entity::entity_attr::Value::B(b) => Self::Boolean(b),
@@ -292,6 +402,13 @@ pub mod entity {
             let value = match val {
                 EntityAttr::String(s) => proto::entity::entity_attr::Value::S(s),
                 EntityAttr::Number(n) => proto::entity::entity_attr::Value::I(n),
+                EntityAttr::Decimal(d) => proto::entity::entity_attr::Value::Efne(
+                    ExtensionFnEscape::from(ExtensionFn::new(
+                        "decimal".to_string(),
+                        decimal_arg(d),
+                    ))
+                    .into(),
+                ),
                 EntityAttr::Boolean(b) => proto::entity::entity_attr::Value::B(b),
                 EntityAttr::EntityUid(e) => proto::entity::entity_attr::Value::Euid(e.into()),
                 EntityAttr::Function(f) => proto::entity::entity_attr::Value::Efn(f.into()),
@@ -327,6 +444,9 @@ pub struct Entity {
     attrs: HashMap<String, entity::EntityAttr>,
     parents: HashSet<EntityUid>,
     tags: HashMap<String, entity::EntityAttr>,
+    /// Removed once past, e.g. for session or device entities
+    #[serde(rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
 }
 
 impl Entity {
@@ -340,6 +460,7 @@ impl Entity {
             attrs,
             parents,
             tags: HashMap::new(),
+            expires_at: None,
         }
     }
 
@@ -349,6 +470,7 @@ impl Entity {
             attrs: HashMap::new(),
             parents,
             tags: HashMap::new(),
+            expires_at: None,
         }
     }
 
@@ -363,9 +485,15 @@ impl Entity {
             attrs,
             parents,
             tags,
+            expires_at: None,
         }
     }
 
+    pub fn with_expires_at(mut self, expires_at: Option<DateTime<Utc>>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
     pub fn uid(&self) -> &EntityUid {
         &self.uid
     }
@@ -378,16 +506,84 @@ impl Entity {
         &self.attrs
     }
 
+    pub fn attrs_mut(&mut self) -> &mut HashMap<String, entity::EntityAttr> {
+        &mut self.attrs
+    }
+
     pub fn tags(&self) -> &HashMap<String, entity::EntityAttr> {
         &self.tags
     }
 
+    pub fn expires_at(&self) -> Option<&DateTime<Utc>> {
+        self.expires_at.as_ref()
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Overlays `other` on this entity: its attributes and tags replace the ones with the
+    /// same name, its parents are added and its expiry, if any, replaces this one.
+    pub fn merge(&mut self, other: Entity) {
+        self.attrs.extend(other.attrs);
+        self.tags.extend(other.tags);
+        self.parents.extend(other.parents);
+        if other.expires_at.is_some() {
+            self.expires_at = other.expires_at;
+        }
+    }
+
+    /// Rewrites attribute and tag values the schema declares as extension types into `__extn`
+    /// escapes, e.g. `"10.0.0.1"` for an `ipaddr` or `1.5` for a `decimal`.
+    pub fn coerce(&mut self, schema: &Schema) {
+        let Some((namespace, entity_type)) = schema.entity_type(self.uid.type_name()) else {
+            return;
+        };
+        if let Some(shape) = &entity_type.shape {
+            let shape = schema.resolve(namespace, shape);
+            if let schema::TypeJson::Record { attributes, .. } = shape {
+                for (name, attr) in self.attrs.iter_mut() {
+                    if let Some(expected) = attributes.get(name) {
+                        coerce_attr(schema, namespace, attr, expected);
+                    }
+                }
+            }
+        }
+        if let Some(tags) = &entity_type.tags {
+            for attr in self.tags.values_mut() {
+                coerce_attr(schema, namespace, attr, tags);
+            }
+        }
+    }
+
+    /// Checks the decimal attributes and tags fit a Cedar `decimal`, returning one message per
+    /// value out of range or with more than four fractional digits.
+    pub fn decimal_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (name, attr) in &self.attrs {
+            attr.decimal_errors(&format!("{} {name}", self.uid), &mut errors);
+        }
+        for (name, tag) in &self.tags {
+            tag.decimal_errors(&format!("{} tag {name}", self.uid), &mut errors);
+        }
+        errors.sort();
+        errors
+    }
+
+    /// Cedar JSON format of the entity, which has no expiry.
+    fn to_cedar_json(&self) -> serde_json::Value {
+        let mut json = serde_json::to_value(self).unwrap();
+        if let Some(json) = json.as_object_mut() {
+            json.remove("expiresAt");
+        }
+        json
+    }
+
     pub fn to_cedar_entity(
         &self,
         cedar_schema: Option<&cedar_policy::Schema>,
     ) -> Result<cedar_policy::Entity, cedar_policy::entities_errors::EntitiesError> {
-        let json = serde_json::to_value(self).unwrap();
-        cedar_policy::Entity::from_json_value(json, cedar_schema)
+        cedar_policy::Entity::from_json_value(self.to_cedar_json(), cedar_schema)
     }
 }
 
@@ -420,13 +616,13 @@ impl TryInto<cedar_policy::Entity> for Entity {
     type Error = cedar_policy::entities_errors::EntitiesError;
 
     fn try_into(self) -> Result<cedar_policy::Entity, Self::Error> {
-        cedar_policy::Entity::from_json_value(serde_json::to_value(self).unwrap(), None)
+        cedar_policy::Entity::from_json_value(self.to_cedar_json(), None)
     }
 }
 
 impl From<proto::Entity> for Entity {
     fn from(value: proto::Entity) -> Self {
-        let uid = value.uid.unwrap().into();
+        let uid = value.uid.unwrap_or_default().into();
         let attrs = value
             .attrs
             .into_iter()
@@ -442,12 +638,14 @@ impl From<proto::Entity> for Entity {
             .into_iter()
             .map(|(k, v)| (k, v.into()))
             .collect::<HashMap<String, entity::EntityAttr>>();
+        let expires_at = value.expires_at.and_then(DateTime::from_timestamp_millis);
 
         Self {
             uid,
             attrs,
             parents,
             tags,
+            expires_at,
         }
     }
 }
@@ -470,13 +668,86 @@ impl From<Entity> for proto::Entity {
             .into_iter()
             .map(|(k, v)| (k, v.into()))
             .collect::<HashMap<String, proto::entity::EntityAttr>>();
+        let expires_at = val
+            .expires_at
+            .map(|expires_at| expires_at.timestamp_millis());
 
         proto::Entity {
             uid,
             attrs,
             parents,
             tags,
+            expires_at,
+        }
+    }
+}
+
+/// A resource at the end of a path of containers, e.g. the document `/docs/2024/report.pdf`
+/// in the folders `/docs` and `/docs/2024`. Each entity is identified by its full path and
+/// is a member of the container before it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcePath {
+    /// Entity type of the containers along the path, e.g. `App::Folder`
+    pub container_type: String,
+    /// Entity type of the resource at the end of the path, the container type when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_type: Option<String>,
+    /// Segments separated by `/`, empty segments ignored, e.g. `/docs/2024/report.pdf`
+    pub path: String,
+    /// Attributes of the resource at the end of the path
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attrs: HashMap<String, entity::EntityAttr>,
+}
+
+impl ResourcePath {
+    /// Full paths of the containers then of the resource, e.g. `/docs`, `/docs/2024` and
+    /// `/docs/2024/report.pdf`.
+    fn prefixes(&self) -> Vec<String> {
+        let mut prefix = String::new();
+        self.path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                prefix.push('/');
+                prefix.push_str(segment);
+                prefix.clone()
+            })
+            .collect()
+    }
+
+    /// Uid of the resource, `None` when the path has no segment.
+    pub fn uid(&self) -> Option<EntityUid> {
+        let resource_type = self.resource_type.as_ref().unwrap_or(&self.container_type);
+        let id = self.prefixes().pop()?;
+        Some(EntityUid::new(resource_type.clone(), id))
+    }
+
+    /// The containers, outermost first, then the resource carrying the attributes.
+    pub fn entities(&self) -> Vec<Entity> {
+        let prefixes = self.prefixes();
+        let len = prefixes.len();
+        let mut entities: Vec<Entity> = Vec::with_capacity(len);
+        let mut parent: Option<EntityUid> = None;
+        for (i, prefix) in prefixes.into_iter().enumerate() {
+            let is_resource = i + 1 == len;
+            let entity_type = match (is_resource, &self.resource_type) {
+                (true, Some(resource_type)) => resource_type,
+                _ => &self.container_type,
+            };
+            let uid = EntityUid::new(entity_type.clone(), prefix);
+            let attrs = match is_resource {
+                true => self.attrs.clone(),
+                false => HashMap::new(),
+            };
+            entities.push(Entity::new(
+                uid.clone(),
+                attrs,
+                parent.into_iter().collect(),
+            ));
+            parent = Some(uid);
         }
+        entities
     }
 }
 
@@ -534,9 +805,56 @@ pub mod schema {
         }
     }
 
+    impl TypeJson {
+        pub fn is_required(&self) -> bool {
+            let required = match self {
+                TypeJson::Long { required }
+                | TypeJson::String { required }
+                | TypeJson::Boolean { required }
+                | TypeJson::Set { required, .. }
+                | TypeJson::Entity { required, .. }
+                | TypeJson::Record { required, .. }
+                | TypeJson::Extension { required, .. }
+                | TypeJson::EntityOrCommon { required, .. } => required,
+            };
+            required.unwrap_or(true)
+        }
+
+        /// Collects the names of the `EntityOrCommon` types this type refers to, nested ones
+        /// included.
+        pub fn references(&self, names: &mut Vec<String>) {
+            match self {
+                TypeJson::Set { element, .. } => element.references(names),
+                TypeJson::Record { attributes, .. } => {
+                    attributes.values().for_each(|t| t.references(names))
+                }
+                TypeJson::EntityOrCommon { name, .. } => names.push(name.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    impl std::fmt::Display for TypeJson {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                TypeJson::Long { .. } => write!(f, "Long"),
+                TypeJson::String { .. } => write!(f, "String"),
+                TypeJson::Boolean { .. } => write!(f, "Boolean"),
+                TypeJson::Set { element, .. } => write!(f, "Set<{element}>"),
+                TypeJson::Entity { name, .. } => write!(f, "Entity<{name}>"),
+                TypeJson::Record { .. } => write!(f, "Record"),
+                TypeJson::Extension { name, .. } => write!(f, "Extension<{name}>"),
+                TypeJson::EntityOrCommon { name, .. } => write!(f, "{name}"),
+            }
+        }
+    }
+
     impl From<proto::schema::TypeJson> for TypeJson {
         fn from(value: proto::schema::TypeJson) -> Self {
-            match value.value.unwrap() {
+            let Some(value) = value.value else {
+                return Self::String { required: None };
+            };
+            match value {
                 proto::schema::type_json::Value::L(long) => Self::Long {
                     required: match long.required {
                         true => None,
@@ -556,7 +874,12 @@ pub mod schema {
                     },
                 },
                 proto::schema::type_json::Value::Set(set) => Self::Set {
-                    element: Box::new(TypeJson::from(*set.element.unwrap())),
+                    element: Box::new(
+                        set.element
+                            .map_or(TypeJson::String { required: None }, |element| {
+                                TypeJson::from(*element)
+                            }),
+                    ),
                     required: match set.required {
                         true => None,
                         false => Some(false),
@@ -742,6 +1065,29 @@ pub mod schema {
         annotations: HashMap<String, String>,
     }
 
+    impl Action {
+        /// Type of the context of requests for this action, when declared.
+        pub fn context(&self) -> Option<&TypeJson> {
+            self.applies_to.as_ref().and_then(|a| a.context.as_ref())
+        }
+
+        /// Principal types the action applies to, as written in its namespace.
+        pub fn principal_types(&self) -> &[String] {
+            self.applies_to
+                .as_ref()
+                .map(|a| a.principal_types.as_slice())
+                .unwrap_or_default()
+        }
+
+        /// Resource types the action applies to, as written in its namespace.
+        pub fn resource_types(&self) -> &[String] {
+            self.applies_to
+                .as_ref()
+                .map(|a| a.resource_types.as_slice())
+                .unwrap_or_default()
+        }
+    }
+
     impl From<proto::schema::Action> for Action {
         fn from(value: proto::schema::Action) -> Self {
             Self {
@@ -841,6 +1187,179 @@ pub mod schema {
 #[serde(default)]
 pub struct Schema(pub HashMap<String, schema::Namespace>);
 
+impl Schema {
+    /// Namespace and context type declared for an action, e.g. `NS::Action::"view"`.
+    pub fn action_context(&self, action: &EntityUid) -> Option<(&str, &schema::TypeJson)> {
+        let namespace = action
+            .type_name()
+            .strip_suffix("Action")
+            .map(|ns| ns.trim_end_matches("::"))?;
+        let (namespace, ns) = self.0.get_key_value(namespace)?;
+        let context = ns.actions.get(action.id())?.context()?;
+        Some((namespace.as_str(), context))
+    }
+
+    /// Checks that the schema declares `action` and that it applies to the types of
+    /// `principal` and `resource`.
+    pub fn validate_request(
+        &self,
+        principal: &EntityUid,
+        action: &EntityUid,
+        resource: &EntityUid,
+    ) -> Vec<RequestError> {
+        let action_name = format!("{}::\"{}\"", action.type_name(), action.id());
+        let declared = action
+            .type_name()
+            .strip_suffix("Action")
+            .map(|ns| ns.trim_end_matches("::"))
+            .and_then(|namespace| self.0.get_key_value(namespace))
+            .and_then(|(namespace, ns)| Some((namespace, ns.actions.get(action.id())?)));
+        let Some((namespace, declared)) = declared else {
+            return vec![RequestError {
+                action: action_name,
+                kind: RequestErrorKind::UndeclaredAction,
+                found: None,
+                expected: Vec::new(),
+            }];
+        };
+
+        let qualify = |name: &String| match namespace.is_empty() || name.contains("::") {
+            true => name.clone(),
+            false => format!("{namespace}::{name}"),
+        };
+        let mut errors = Vec::new();
+        for (kind, uid, types) in [
+            (
+                RequestErrorKind::UnexpectedPrincipalType,
+                principal,
+                declared.principal_types(),
+            ),
+            (
+                RequestErrorKind::UnexpectedResourceType,
+                resource,
+                declared.resource_types(),
+            ),
+        ] {
+            let expected: Vec<String> = types.iter().map(qualify).collect();
+            if !expected.iter().any(|t| t == uid.type_name()) {
+                errors.push(RequestError {
+                    action: action_name.clone(),
+                    kind,
+                    found: Some(uid.type_name().to_owned()),
+                    expected,
+                });
+            }
+        }
+        errors
+    }
+
+    /// Namespace and definition of an entity type, e.g. `NS::User`.
+    pub fn entity_type(&self, type_name: &str) -> Option<(&str, &schema::EntityType)> {
+        let (namespace, name) = type_name.rsplit_once("::").unwrap_or(("", type_name));
+        let (namespace, ns) = self.0.get_key_value(namespace)?;
+        Some((namespace.as_str(), ns.entity_types.get(name)?))
+    }
+
+    /// Copies into each namespace the common types of `library` it refers to without declaring
+    /// a common or entity type of that name, along with the library types those refer to.
+    pub fn with_common_types(mut self, library: &HashMap<String, schema::TypeJson>) -> Self {
+        if library.is_empty() {
+            return self;
+        }
+        for ns in self.0.values_mut() {
+            let mut pending = Vec::new();
+            for entity_type in ns.entity_types.values() {
+                for type_json in entity_type.shape.iter().chain(entity_type.tags.iter()) {
+                    type_json.references(&mut pending);
+                }
+            }
+            for action in ns.actions.values() {
+                if let Some(context) = action.context() {
+                    context.references(&mut pending);
+                }
+            }
+            for type_json in ns.common_types.iter().flat_map(|types| types.values()) {
+                type_json.references(&mut pending);
+            }
+
+            while let Some(name) = pending.pop() {
+                let declared = ns
+                    .common_types
+                    .as_ref()
+                    .is_some_and(|types| types.contains_key(&name))
+                    || ns.entity_types.contains_key(&name);
+                if declared {
+                    continue;
+                }
+                if let Some(type_json) = library.get(&name) {
+                    type_json.references(&mut pending);
+                    ns.common_types
+                        .get_or_insert_with(HashMap::new)
+                        .insert(name, type_json.clone());
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Resolves `EntityOrCommon` types, leaving any other type as is.
+    pub fn resolve(&self, namespace: &str, type_json: &schema::TypeJson) -> schema::TypeJson {
+        match type_json {
+            schema::TypeJson::EntityOrCommon { name, .. } => self.resolve_type(namespace, name),
+            type_json => type_json.clone(),
+        }
+    }
+
+    /// Names of the context attributes the schema declares for `action`, or `None` when the
+    /// action declares no context record.
+    pub fn action_context_attributes(&self, action: &EntityUid) -> Option<Vec<String>> {
+        let (namespace, context) = self.action_context(action)?;
+        match self.resolve(namespace, context) {
+            schema::TypeJson::Record { attributes, .. } => Some(attributes.into_keys().collect()),
+            _ => None,
+        }
+    }
+
+    /// Names of the attributes the schema declares for the entity type, or `None` when it does
+    /// not declare the type or its shape.
+    pub fn entity_attributes(&self, type_name: &str) -> Option<Vec<String>> {
+        let (namespace, entity_type) = self.entity_type(type_name)?;
+        match self.resolve(namespace, entity_type.shape.as_ref()?) {
+            schema::TypeJson::Record { attributes, .. } => Some(attributes.into_keys().collect()),
+            _ => None,
+        }
+    }
+
+    /// Resolves a `EntityOrCommon` type name to a common type, a builtin type or an entity.
+    fn resolve_type(&self, namespace: &str, name: &str) -> schema::TypeJson {
+        let name = name.strip_prefix("__cedar::").unwrap_or(name);
+        let (ns, short) = match name.rsplit_once("::") {
+            Some((ns, short)) => (ns, short),
+            None => (namespace, name),
+        };
+        let common = self
+            .0
+            .get(ns)
+            .and_then(|ns| ns.common_types.as_ref())
+            .and_then(|types| types.get(short));
+        match (common, short) {
+            (Some(common), _) => common.clone(),
+            (None, "Long") => schema::TypeJson::Long { required: None },
+            (None, "String") => schema::TypeJson::String { required: None },
+            (None, "Bool" | "Boolean") => schema::TypeJson::Boolean { required: None },
+            (None, "ipaddr" | "decimal" | "datetime" | "duration") => schema::TypeJson::Extension {
+                name: short.to_owned(),
+                required: None,
+            },
+            _ => schema::TypeJson::Entity {
+                name: name.to_owned(),
+                required: None,
+            },
+        }
+    }
+}
+
 impl TryInto<cedar_policy::Schema> for Schema {
     type Error = cedar_policy::SchemaError;
 
@@ -962,7 +1481,7 @@ impl From<EntityOrSlot> for proto::EntityOrSlot {
                 slot: 0,
             }
         } else {
-            let slot: proto::SlotId = val.slot.unwrap().into();
+            let slot: proto::SlotId = val.slot.unwrap_or_default().into();
             proto::EntityOrSlot {
                 entity: None,
                 slot: slot.into(),
@@ -1084,10 +1603,26 @@ pub struct PrincipalOp {
     r#in: Option<EntityOrSlot>,
 }
 
+impl PrincipalOp {
+    pub fn new_eq(entity: EntityUid) -> Self {
+        Self {
+            op: PrincipalOperator::Eq,
+            entity: Some(entity),
+            ..Default::default()
+        }
+    }
+
+    /// Entities the constraint names, slots excluded.
+    pub fn entities(&self) -> Vec<&EntityUid> {
+        let r#in = self.r#in.as_ref().and_then(|e| e.entity.as_ref());
+        self.entity.iter().chain(r#in).collect()
+    }
+}
+
 impl From<proto::PrincipalOp> for PrincipalOp {
     fn from(value: proto::PrincipalOp) -> Self {
         let op = proto::principal_op::Operator::try_from(value.op)
-            .unwrap()
+            .unwrap_or_default()
             .into();
 
         match op {
@@ -1109,7 +1644,7 @@ impl From<proto::PrincipalOp> for PrincipalOp {
                         ..Default::default()
                     }
                 } else {
-                    let slot_id = proto::SlotId::try_from(value.slot).unwrap();
+                    let slot_id = proto::SlotId::try_from(value.slot).unwrap_or_default();
                     Self {
                         op,
                         slot: Some(slot_id.into()),
@@ -1144,7 +1679,7 @@ impl From<PrincipalOp> for proto::PrincipalOp {
                         ..Default::default()
                     }
                 } else {
-                    let slot: proto::SlotId = val.slot.unwrap().into();
+                    let slot: proto::SlotId = val.slot.unwrap_or_default().into();
                     proto::PrincipalOp {
                         op: op.into(),
                         slot: slot.into(),
@@ -1171,10 +1706,26 @@ pub struct ResourceOp {
     r#in: Option<EntityOrSlot>,
 }
 
+impl ResourceOp {
+    pub fn new_eq(entity: EntityUid) -> Self {
+        Self {
+            op: ResourceOperator::Eq,
+            entity: Some(entity),
+            ..Default::default()
+        }
+    }
+
+    /// Entities the constraint names, slots excluded.
+    pub fn entities(&self) -> Vec<&EntityUid> {
+        let r#in = self.r#in.as_ref().and_then(|e| e.entity.as_ref());
+        self.entity.iter().chain(r#in).collect()
+    }
+}
+
 impl From<proto::ResourceOp> for ResourceOp {
     fn from(value: proto::ResourceOp) -> Self {
         let op = proto::resource_op::Operator::try_from(value.op)
-            .unwrap()
+            .unwrap_or_default()
             .into();
 
         match op {
@@ -1196,7 +1747,7 @@ impl From<proto::ResourceOp> for ResourceOp {
                         ..Default::default()
                     }
                 } else {
-                    let slot_id = proto::SlotId::try_from(value.slot).unwrap();
+                    let slot_id = proto::SlotId::try_from(value.slot).unwrap_or_default();
                     Self {
                         op,
                         slot: Some(slot_id.into()),
@@ -1231,7 +1782,7 @@ impl From<ResourceOp> for proto::ResourceOp {
                         ..Default::default()
                     }
                 } else {
-                    let slot: proto::SlotId = val.slot.unwrap().into();
+                    let slot: proto::SlotId = val.slot.unwrap_or_default().into();
                     proto::ResourceOp {
                         op: op.into(),
                         slot: slot.into(),
@@ -1253,10 +1804,28 @@ pub struct ActionOp {
     entities: Option<Vec<EntityUid>>,
 }
 
+impl ActionOp {
+    pub fn new_in(entities: Vec<EntityUid>) -> Self {
+        Self {
+            op: ActionOperator::In,
+            entities: Some(entities),
+            ..Default::default()
+        }
+    }
+
+    /// Actions the constraint names.
+    pub fn entities(&self) -> Vec<&EntityUid> {
+        self.entity
+            .iter()
+            .chain(self.entities.iter().flatten())
+            .collect()
+    }
+}
+
 impl From<proto::ActionOp> for ActionOp {
     fn from(value: proto::ActionOp) -> Self {
         let op = proto::action_op::Operator::try_from(value.op)
-            .unwrap()
+            .unwrap_or_default()
             .into();
 
         match op {
@@ -1304,7 +1873,7 @@ impl From<ActionOp> for proto::ActionOp {
                         op: op.into(),
                         entities: val
                             .entities
-                            .unwrap()
+                            .unwrap_or_default()
                             .into_iter()
                             .map(|e| e.into())
                             .collect(),
@@ -1385,7 +1954,10 @@ impl Default for ValueExpr {
 
 impl From<proto::json_expr::ValueExpr> for ValueExpr {
     fn from(value: proto::json_expr::ValueExpr) -> Self {
-        match value.value.unwrap() {
+        let Some(value) = value.value else {
+            return ValueExpr::default();
+        };
+        match value {
             proto::json_expr::value_expr::Value::S(s) => ValueExpr::String(s),
             proto::json_expr::value_expr::Value::I(n) => ValueExpr::Number(n),
             proto::json_expr::value_expr::Value::B(b) => ValueExpr::Boolean(b),
@@ -1476,7 +2048,7 @@ pub struct HasExpr {
 impl From<proto::json_expr::HasExpr> for HasExpr {
     fn from(value: proto::json_expr::HasExpr) -> Self {
         Self {
-            left: JsonExpr::from(*value.left.unwrap()),
+            left: value.left.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
             attr: value.attr,
         }
     }
@@ -1502,8 +2074,8 @@ pub struct BinaryExpr {
 impl From<proto::json_expr::BinaryExpr> for BinaryExpr {
     fn from(value: proto::json_expr::BinaryExpr) -> Self {
         Self {
-            left: JsonExpr::from(*value.left.unwrap()),
-            right: JsonExpr::from(*value.right.unwrap()),
+            left: value.left.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
+            right: value.right.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
         }
     }
 }
@@ -1526,7 +2098,7 @@ pub struct NegExpr {
 impl From<proto::json_expr::NegExpr> for NegExpr {
     fn from(value: proto::json_expr::NegExpr) -> Self {
         Self {
-            arg: JsonExpr::from(*value.arg.unwrap()),
+            arg: value.arg.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
         }
     }
 }
@@ -1549,7 +2121,7 @@ pub struct IsExpr {
 impl From<proto::json_expr::IsExpr> for IsExpr {
     fn from(value: proto::json_expr::IsExpr) -> Self {
         Self {
-            left: JsonExpr::from(*value.left.unwrap()),
+            left: value.left.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
             entity_type: value.entity_type,
         }
     }
@@ -1580,11 +2152,12 @@ pub struct LikeExpr {
 impl From<proto::json_expr::LikeExpr> for LikeExpr {
     fn from(value: proto::json_expr::LikeExpr) -> Self {
         Self {
-            left: JsonExpr::from(*value.left.unwrap()),
+            left: value.left.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
             pattern: value
                 .pattern
                 .into_iter()
-                .map(|e| match e.value.unwrap() {
+                .filter_map(|e| e.value)
+                .map(|e| match e {
                     proto::json_expr::pattern_elem::Value::Literal(s) => PatternElem::Literal(s),
                     proto::json_expr::pattern_elem::Value::Wildcard(_) => PatternElem::Wildcard,
                 })
@@ -1629,9 +2202,9 @@ pub struct IfThenElseExpr {
 impl From<proto::json_expr::IfThenElseExpr> for IfThenElseExpr {
     fn from(value: proto::json_expr::IfThenElseExpr) -> Self {
         Self {
-            r#if: JsonExpr::from(*value.r#if.unwrap()),
-            then: JsonExpr::from(*value.then.unwrap()),
-            r#else: JsonExpr::from(*value.r#else.unwrap()),
+            r#if: value.r#if.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
+            then: value.then.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
+            r#else: value.r#else.map(|e| JsonExpr::from(*e)).unwrap_or_default(),
         }
     }
 }
@@ -1727,10 +2300,10 @@ pub enum JsonExpr {
     Ip(Vec<JsonExpr>),
 
     //IP address functions
-    #[serde(rename = "isIpV4")]
+    #[serde(rename = "isIpv4", alias = "isIpV4")]
     #[schema(no_recursion)]
     IsIpV4(Vec<JsonExpr>),
-    #[serde(rename = "isIpV6")]
+    #[serde(rename = "isIpv6", alias = "isIpV6")]
     #[schema(no_recursion)]
     IsIpV6(Vec<JsonExpr>),
     #[serde(rename = "isLoopback")]
@@ -1794,16 +2367,19 @@ impl Default for JsonExpr {
 
 impl From<proto::JsonExpr> for JsonExpr {
     fn from(value: proto::JsonExpr) -> Self {
-        match value.expr.unwrap() {
+        let Some(expr) = value.expr else {
+            return JsonExpr::default();
+        };
+        match expr {
             proto::json_expr::Expr::Value(expr) => JsonExpr::Value(expr.into()),
             proto::json_expr::Expr::Var(var) => JsonExpr::Var(VarValue::from(
-                proto::json_expr::VarValue::try_from(var).unwrap(),
+                proto::json_expr::VarValue::try_from(var).unwrap_or_default(),
+            )),
+            proto::json_expr::Expr::Slot(slot_id) => JsonExpr::Slot(SlotId::from(
+                proto::SlotId::try_from(slot_id).unwrap_or_default(),
             )),
-            proto::json_expr::Expr::Slot(slot_id) => {
-                JsonExpr::Slot(SlotId::from(proto::SlotId::try_from(slot_id).unwrap()))
-            }
             proto::json_expr::Expr::Neg(expr) => JsonExpr::Neg(Box::new((*expr).into())),
-            proto::json_expr::Expr::Bang(expr) => JsonExpr::Neg(Box::new((*expr).into())),
+            proto::json_expr::Expr::Bang(expr) => JsonExpr::Bang(Box::new((*expr).into())),
             proto::json_expr::Expr::IsEmpty(expr) => JsonExpr::IsEmpty(Box::new((*expr).into())),
             proto::json_expr::Expr::Eq(expr) => JsonExpr::Eq(Box::new((*expr).into())),
             proto::json_expr::Expr::Neq(expr) => JsonExpr::Neq(Box::new((*expr).into())),
@@ -1925,7 +2501,7 @@ impl From<JsonExpr> for proto::JsonExpr {
                 )),
             },
             JsonExpr::Slot(slot_id) => proto::JsonExpr {
-                expr: Some(proto::json_expr::Expr::Var(
+                expr: Some(proto::json_expr::Expr::Slot(
                     Into::<proto::SlotId>::into(slot_id).into(),
                 )),
             },
@@ -2223,7 +2799,7 @@ impl From<proto::Condition> for Condition {
     fn from(value: proto::Condition) -> Self {
         Self {
             kind: value.kind().into(),
-            body: value.body.unwrap().into(),
+            body: value.body.unwrap_or_default().into(),
         }
     }
 }
@@ -2277,6 +2853,19 @@ pub struct Policy {
 }
 
 impl Policy {
+    /// Builder of a policy, see `builder::PolicyBuilder`.
+    pub fn builder() -> builder::PolicyBuilder<Policy> {
+        builder::PolicyBuilder::default()
+    }
+
+    /// Entities the principal, action and resource constraints name.
+    pub fn scope_entities(&self) -> Vec<&EntityUid> {
+        let mut entities = self.principal.entities();
+        entities.extend(self.action.entities());
+        entities.extend(self.resource.entities());
+        entities
+    }
+
     pub fn to_cedar(
         &self,
         policy_id: PolicyId,
@@ -2290,9 +2879,9 @@ impl From<proto::Policy> for Policy {
     fn from(value: proto::Policy) -> Self {
         Self {
             effect: value.effect().into(),
-            principal: value.principal.unwrap().into(),
-            action: value.action.unwrap().into(),
-            resource: value.resource.unwrap().into(),
+            principal: value.principal.unwrap_or_default().into(),
+            action: value.action.unwrap_or_default().into(),
+            resource: value.resource.unwrap_or_default().into(),
             conditions: value
                 .conditions
                 .into_iter()
@@ -2301,7 +2890,7 @@ impl From<proto::Policy> for Policy {
             annotations: value
                 .annotations
                 .into_iter()
-                .map(|(k, v)| (k, Some(v)))
+                .map(|(k, v)| (k, v.value))
                 .collect(),
         }
     }
@@ -2318,7 +2907,7 @@ impl From<Policy> for proto::Policy {
             annotations: val
                 .annotations
                 .into_iter()
-                .map(|(k, v)| (k, v.unwrap_or_default()))
+                .map(|(k, v)| (k, proto::Annotation { value: v }))
                 .collect(),
         }
     }
@@ -2357,6 +2946,19 @@ pub struct Template {
 }
 
 impl Template {
+    /// Builder of a template, see `builder::PolicyBuilder`.
+    pub fn builder() -> builder::PolicyBuilder<Template> {
+        builder::PolicyBuilder::default()
+    }
+
+    /// Entities the principal, action and resource constraints name, slots excluded.
+    pub fn scope_entities(&self) -> Vec<&EntityUid> {
+        let mut entities = self.principal.entities();
+        entities.extend(self.action.entities());
+        entities.extend(self.resource.entities());
+        entities
+    }
+
     pub fn to_cedar(
         &self,
         policy_id: PolicyId,
@@ -2370,9 +2972,9 @@ impl From<proto::Template> for Template {
     fn from(value: proto::Template) -> Self {
         Self {
             effect: value.effect().into(),
-            principal: value.principal.unwrap().into(),
-            action: value.action.unwrap().into(),
-            resource: value.resource.unwrap().into(),
+            principal: value.principal.unwrap_or_default().into(),
+            action: value.action.unwrap_or_default().into(),
+            resource: value.resource.unwrap_or_default().into(),
             conditions: value
                 .conditions
                 .into_iter()
@@ -2381,7 +2983,7 @@ impl From<proto::Template> for Template {
             annotations: value
                 .annotations
                 .into_iter()
-                .map(|(k, v)| (k, Some(v)))
+                .map(|(k, v)| (k, v.value))
                 .collect(),
         }
     }
@@ -2398,7 +3000,7 @@ impl From<Template> for proto::Template {
             annotations: val
                 .annotations
                 .into_iter()
-                .map(|(k, v)| (k, v.unwrap_or_default()))
+                .map(|(k, v)| (k, proto::Annotation { value: v }))
                 .collect(),
         }
     }
@@ -2466,6 +3068,15 @@ pub enum EntityValue {
     EntityEscape(EntityUidEscape),
 }
 
+impl EntityValue {
+    pub fn uid(&self) -> &EntityUid {
+        match self {
+            EntityValue::EntityUid(uid) => uid,
+            EntityValue::EntityEscape(escape) => &escape.entity,
+        }
+    }
+}
+
 impl Default for EntityValue {
     fn default() -> Self {
         Self::EntityEscape(EntityUidEscape::default())
@@ -2478,18 +3089,23 @@ impl From<cedar_policy::EntityUid> for EntityValue {
     }
 }
 
-impl From<EntityValue> for cedar_policy::EntityUid {
-    fn from(val: EntityValue) -> Self {
+impl TryFrom<EntityValue> for cedar_policy::EntityUid {
+    type Error = cedar_policy::ParseErrors;
+
+    fn try_from(val: EntityValue) -> Result<Self, Self::Error> {
         match val {
-            EntityValue::EntityUid(e) => e.into(),
-            EntityValue::EntityEscape(e) => e.into(),
+            EntityValue::EntityUid(e) => e.try_into(),
+            EntityValue::EntityEscape(e) => e.try_into(),
         }
     }
 }
 
 impl From<proto::EntityValue> for EntityValue {
     fn from(value: proto::EntityValue) -> Self {
-        match value.value.unwrap() {
+        let Some(value) = value.value else {
+            return EntityValue::EntityUid(EntityUid::default());
+        };
+        match value {
             proto::entity_value::Value::Ee(e) => EntityValue::EntityEscape(e.into()),
             proto::entity_value::Value::Euid(e) => EntityValue::EntityUid(e.into()),
         }
@@ -2530,10 +3146,18 @@ impl TemplateLink {
         }
     }
 
-    pub fn to_cedar_vals(&self) -> HashMap<cedar_policy::SlotId, cedar_policy::EntityUid> {
+    /// Entities the slots are linked to.
+    pub fn entities(&self) -> Vec<&EntityUid> {
+        self.values.values().map(EntityValue::uid).collect()
+    }
+
+    pub fn to_cedar_vals(
+        &self,
+    ) -> Result<HashMap<cedar_policy::SlotId, cedar_policy::EntityUid>, cedar_policy::ParseErrors>
+    {
         self.values
             .iter()
-            .map(|(k, v)| (k.clone().into(), v.clone().into()))
+            .map(|(k, v)| Ok((k.clone().into(), v.clone().try_into()?)))
             .collect()
     }
 }
@@ -2646,7 +3270,43 @@ impl TryInto<cedar_policy::PolicySet> for PolicySet {
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Context(HashMap<String, entity::EntityAttr>);
 
+impl From<HashMap<String, entity::EntityAttr>> for Context {
+    fn from(value: HashMap<String, entity::EntityAttr>) -> Self {
+        Self(value)
+    }
+}
+
 impl Context {
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &entity::EntityAttr)> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn insert(&mut self, key: String, value: entity::EntityAttr) -> Option<entity::EntityAttr> {
+        self.0.insert(key, value)
+    }
+
+    /// Checks the decimal attributes fit a Cedar `decimal`, as [`Entity::decimal_errors`].
+    pub fn decimal_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (name, attr) in &self.0 {
+            attr.decimal_errors(name, &mut errors);
+        }
+        errors.sort();
+        errors
+    }
+
     pub fn to_cedar_context(
         &self,
         schema: Option<(&cedar_policy::Schema, &cedar_policy::EntityUid)>,
@@ -2654,43 +3314,298 @@ impl Context {
         let json = serde_json::to_value(self).unwrap();
         cedar_policy::Context::from_json_value(json, schema)
     }
+
+    /// Rewrites the attributes the schema declares as extension types for `action` into
+    /// `__extn` escapes.
+    pub fn coerce(&mut self, schema: &Schema, action: &EntityUid) {
+        let Some((namespace, context_type)) = schema.action_context(action) else {
+            return;
+        };
+        if let schema::TypeJson::Record { attributes, .. } = schema.resolve(namespace, context_type)
+        {
+            for (name, attr) in self.0.iter_mut() {
+                if let Some(expected) = attributes.get(name) {
+                    coerce_attr(schema, namespace, attr, expected);
+                }
+            }
+        }
+    }
+
+    /// Checks the context against the type the schema declares for `action`, reporting every
+    /// attribute that is missing, unexpected or of the wrong type. Returns no errors when the
+    /// schema declares no context for the action.
+    pub fn validate(&self, schema: &Schema, action: &EntityUid) -> Vec<ContextError> {
+        let mut errors = Vec::new();
+        if let Some((namespace, context_type)) = schema.action_context(action) {
+            let value = serde_json::to_value(self).unwrap_or_default();
+            validate_value(
+                schema,
+                namespace,
+                "context",
+                &value,
+                context_type,
+                &mut errors,
+            );
+        }
+        errors
+    }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
-pub enum Decision {
-    Allow,
-    #[default]
-    Deny,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ContextErrorKind {
+    MissingAttribute,
+    UnexpectedAttribute,
+    TypeMismatch,
 }
 
-impl From<cedar_policy::Decision> for Decision {
-    fn from(value: cedar_policy::Decision) -> Self {
-        match value {
-            cedar_policy::Decision::Allow => Self::Allow,
-            cedar_policy::Decision::Deny => Self::Deny,
+/// Attribute of a request context that does not conform to the schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextError {
+    pub path: String,
+    pub kind: ContextErrorKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub found: Option<String>,
+}
+
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ContextErrorKind::MissingAttribute => write!(
+                f,
+                "{}: missing required attribute of type {}",
+                self.path,
+                self.expected.as_deref().unwrap_or_default()
+            ),
+            ContextErrorKind::UnexpectedAttribute => {
+                write!(f, "{}: attribute not declared in schema", self.path)
+            }
+            ContextErrorKind::TypeMismatch => write!(
+                f,
+                "{}: expected {}, found {}",
+                self.path,
+                self.expected.as_deref().unwrap_or_default(),
+                self.found.as_deref().unwrap_or_default()
+            ),
         }
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
-#[serde(default)]
-pub struct Response {
-    pub decision: Decision,
-    pub reason: Vec<String>,
-    pub errors: Vec<String>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum RequestErrorKind {
+    UndeclaredAction,
+    UnexpectedPrincipalType,
+    UnexpectedResourceType,
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
-pub struct Request {
-    pub principal: EntityUid,
-    pub action: EntityUid,
-    pub resource: EntityUid,
+/// Action or entity type of a request that does not conform to the schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestError {
+    pub action: String,
+    pub kind: RequestErrorKind,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub context: Option<Context>,
+    pub found: Option<String>,
+    /// Types the action applies to
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expected: Vec<String>,
 }
 
-impl From<cedar_policy::Response> for Response {
-    fn from(value: cedar_policy::Response) -> Self {
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let role = match self.kind {
+            RequestErrorKind::UndeclaredAction => {
+                return write!(f, "{}: action not declared in schema", self.action);
+            }
+            RequestErrorKind::UnexpectedPrincipalType => "principal",
+            RequestErrorKind::UnexpectedResourceType => "resource",
+        };
+        write!(
+            f,
+            "{}: {role} type {} not in appliesTo, expected one of [{}]",
+            self.action,
+            self.found.as_deref().unwrap_or_default(),
+            self.expected.join(", ")
+        )
+    }
+}
+
+fn json_type_name(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "null".to_owned(),
+        serde_json::Value::Bool(_) => "Boolean".to_owned(),
+        serde_json::Value::Number(_) => "Long".to_owned(),
+        serde_json::Value::String(_) => "String".to_owned(),
+        serde_json::Value::Array(_) => "Set".to_owned(),
+        serde_json::Value::Object(map) => {
+            if let Some(uid) = json_entity_uid(value) {
+                format!("Entity<{}>", uid.type_name())
+            } else if map.contains_key("__extn") || map.contains_key("fn") {
+                "Extension".to_owned()
+            } else {
+                "Record".to_owned()
+            }
+        }
+    }
+}
+
+fn json_entity_uid(value: &serde_json::Value) -> Option<EntityUid> {
+    let value = value.get("__entity").unwrap_or(value);
+    serde_json::from_value(value.clone()).ok()
+}
+
+fn coerce_attr(
+    schema: &Schema,
+    namespace: &str,
+    attr: &mut entity::EntityAttr,
+    expected: &schema::TypeJson,
+) {
+    use entity::EntityAttr;
+    use schema::TypeJson;
+
+    match (schema.resolve(namespace, expected), &mut *attr) {
+        (TypeJson::Extension { name, .. }, EntityAttr::String(arg)) => {
+            // Extension types are constructed by a function of the same name, except `ipaddr`
+            let constructor = match name.as_str() {
+                "ipaddr" => "ip",
+                name => name,
+            };
+            *attr = EntityAttr::extension(constructor, std::mem::take(arg));
+        }
+        (TypeJson::Extension { name, .. }, EntityAttr::Number(n)) if name == "decimal" => {
+            *attr = EntityAttr::extension(&name, entity::decimal_arg(*n as f64));
+        }
+        (TypeJson::Extension { name, .. }, EntityAttr::Decimal(d)) if name == "decimal" => {
+            *attr = EntityAttr::extension(&name, entity::decimal_arg(*d));
+        }
+        (TypeJson::Set { element, .. }, EntityAttr::Set(items)) => {
+            for item in items {
+                coerce_attr(schema, namespace, item, &element);
+            }
+        }
+        (TypeJson::Record { attributes, .. }, EntityAttr::Record(map)) => {
+            for (name, item) in map.iter_mut() {
+                if let Some(expected) = attributes.get(name) {
+                    coerce_attr(schema, namespace, item, expected);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn validate_value(
+    schema: &Schema,
+    namespace: &str,
+    path: &str,
+    value: &serde_json::Value,
+    expected: &schema::TypeJson,
+    errors: &mut Vec<ContextError>,
+) {
+    use schema::TypeJson;
+    use serde_json::Value;
+
+    let mismatch = |errors: &mut Vec<ContextError>| {
+        errors.push(ContextError {
+            path: path.to_owned(),
+            kind: ContextErrorKind::TypeMismatch,
+            expected: Some(expected.to_string()),
+            found: Some(json_type_name(value)),
+        })
+    };
+
+    match (expected, value) {
+        (TypeJson::Long { .. }, Value::Number(n)) if n.is_i64() => {}
+        (TypeJson::String { .. }, Value::String(_)) => {}
+        (TypeJson::Boolean { .. }, Value::Bool(_)) => {}
+        (TypeJson::Set { element, .. }, Value::Array(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                let path = format!("{path}[{i}]");
+                validate_value(schema, namespace, &path, item, element, errors);
+            }
+        }
+        (TypeJson::Entity { .. }, value) if json_entity_uid(value).is_some() => {}
+        (TypeJson::Extension { .. }, Value::String(_)) => {}
+        (TypeJson::Extension { .. }, Value::Object(map))
+            if map.contains_key("__extn") || map.contains_key("fn") => {}
+        (TypeJson::Record { attributes, .. }, Value::Object(map)) => {
+            let mut names: Vec<&String> = attributes.keys().chain(map.keys()).collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                let path = format!("{path}.{name}");
+                match (attributes.get(name), map.get(name)) {
+                    (Some(attr_type), Some(attr)) => {
+                        validate_value(schema, namespace, &path, attr, attr_type, errors)
+                    }
+                    (Some(attr_type), None) if attr_type.is_required() => {
+                        errors.push(ContextError {
+                            path,
+                            kind: ContextErrorKind::MissingAttribute,
+                            expected: Some(attr_type.to_string()),
+                            found: None,
+                        })
+                    }
+                    (None, Some(attr)) => errors.push(ContextError {
+                        path,
+                        kind: ContextErrorKind::UnexpectedAttribute,
+                        expected: None,
+                        found: Some(json_type_name(attr)),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        (TypeJson::EntityOrCommon { name, .. }, value) => {
+            let resolved = schema.resolve_type(namespace, name);
+            validate_value(schema, namespace, path, value, &resolved, errors);
+        }
+        _ => mismatch(errors),
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum Decision {
+    Allow,
+    #[default]
+    Deny,
+}
+
+impl From<cedar_policy::Decision> for Decision {
+    fn from(value: cedar_policy::Decision) -> Self {
+        match value {
+            cedar_policy::Decision::Allow => Self::Allow,
+            cedar_policy::Decision::Deny => Self::Deny,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct Response {
+    pub decision: Decision,
+    pub reason: Vec<String>,
+    pub errors: Vec<String>,
+    /// Time spent evaluating the request, in microseconds, when asked for
+    #[serde(rename = "evaluationMicros", skip_serializing_if = "Option::is_none")]
+    pub evaluation_micros: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Request {
+    pub principal: EntityUid,
+    pub action: EntityUid,
+    pub resource: EntityUid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<Context>,
+}
+
+impl From<cedar_policy::Response> for Response {
+    fn from(value: cedar_policy::Response) -> Self {
         let decision = match value.decision() {
             cedar_policy::Decision::Allow => Decision::Allow,
             cedar_policy::Decision::Deny => Decision::Deny,
@@ -2710,11 +3625,1500 @@ impl From<cedar_policy::Response> for Response {
             decision,
             reason,
             errors,
+            evaluation_micros: None,
+        }
+    }
+}
+
+impl From<proto::Context> for Context {
+    fn from(value: proto::Context) -> Self {
+        Self(
+            value
+                .attrs
+                .into_iter()
+                .map(|(k, v)| (k, v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl From<Context> for proto::Context {
+    fn from(val: Context) -> Self {
+        proto::Context {
+            attrs: val.0.into_iter().map(|(k, v)| (k, v.into())).collect(),
+        }
+    }
+}
+
+impl From<proto::Decision> for Decision {
+    fn from(value: proto::Decision) -> Self {
+        match value {
+            proto::Decision::Allow => Self::Allow,
+            proto::Decision::Deny => Self::Deny,
+        }
+    }
+}
+
+impl From<Decision> for proto::Decision {
+    fn from(val: Decision) -> Self {
+        match val {
+            Decision::Allow => proto::Decision::Allow,
+            Decision::Deny => proto::Decision::Deny,
+        }
+    }
+}
+
+impl From<proto::Request> for Request {
+    fn from(value: proto::Request) -> Self {
+        Self {
+            principal: value.principal.unwrap_or_default().into(),
+            action: value.action.unwrap_or_default().into(),
+            resource: value.resource.unwrap_or_default().into(),
+            context: value.context.map(|c| c.into()),
+        }
+    }
+}
+
+impl From<Request> for proto::Request {
+    fn from(val: Request) -> Self {
+        proto::Request {
+            principal: Some(val.principal.into()),
+            action: Some(val.action.into()),
+            resource: Some(val.resource.into()),
+            context: val.context.map(|c| c.into()),
+        }
+    }
+}
+
+impl From<proto::Response> for Response {
+    fn from(value: proto::Response) -> Self {
+        Self {
+            decision: value.decision().into(),
+            reason: value.reason,
+            errors: value.errors,
+            evaluation_micros: None,
+        }
+    }
+}
+
+impl From<Response> for proto::Response {
+    fn from(val: Response) -> Self {
+        proto::Response {
+            decision: Into::<proto::Decision>::into(val.decision) as i32,
+            reason: val.reason,
+            errors: val.errors,
+        }
+    }
+}
+
+/// Builders of policies and templates, e.g.
+/// `Policy::builder().permit().principal_eq(uid).action_in([view, edit]).when(expr).build()`.
+/// Constraints left out match any principal, action or resource, and a later constraint on
+/// the same element replaces an earlier one.
+pub mod builder {
+    use std::marker::PhantomData;
+
+    use super::*;
+
+    /// Builds a `Policy`, or a `Template` whose principal and resource may also be slots.
+    #[derive(Debug, Clone)]
+    pub struct PolicyBuilder<T> {
+        effect: PolicyEffect,
+        principal: PrincipalOp,
+        action: ActionOp,
+        resource: ResourceOp,
+        conditions: Vec<Condition>,
+        annotations: HashMap<String, Option<String>>,
+        kind: PhantomData<T>,
+    }
+
+    impl<T> Default for PolicyBuilder<T> {
+        fn default() -> Self {
+            Self {
+                effect: PolicyEffect::default(),
+                principal: PrincipalOp::default(),
+                action: ActionOp::default(),
+                resource: ResourceOp::default(),
+                conditions: Vec::new(),
+                annotations: HashMap::new(),
+                kind: PhantomData,
+            }
+        }
+    }
+
+    impl<T> PolicyBuilder<T> {
+        pub fn permit(mut self) -> Self {
+            self.effect = PolicyEffect::Permit;
+            self
+        }
+
+        pub fn forbid(mut self) -> Self {
+            self.effect = PolicyEffect::Forbid;
+            self
+        }
+
+        /// Sets the `@id` annotation, the id policies are stored under from Cedar text.
+        pub fn id(self, id: impl Into<String>) -> Self {
+            self.annotation("id", id)
+        }
+
+        pub fn annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+            self.annotations.insert(key.into(), Some(value.into()));
+            self
+        }
+
+        /// `principal == uid`
+        pub fn principal_eq(mut self, uid: EntityUid) -> Self {
+            self.principal = PrincipalOp::new_eq(uid);
+            self
+        }
+
+        /// `principal in uid`
+        pub fn principal_in(mut self, uid: EntityUid) -> Self {
+            self.principal = PrincipalOp {
+                op: PrincipalOperator::In,
+                entity: Some(uid),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `principal is type`
+        pub fn principal_is(mut self, entity_type: impl Into<String>) -> Self {
+            self.principal = PrincipalOp {
+                op: PrincipalOperator::Is,
+                entity_type: Some(entity_type.into()),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `principal is type in uid`
+        pub fn principal_is_in(mut self, entity_type: impl Into<String>, uid: EntityUid) -> Self {
+            self.principal = PrincipalOp {
+                op: PrincipalOperator::Is,
+                entity_type: Some(entity_type.into()),
+                r#in: Some(EntityOrSlot {
+                    entity: Some(uid),
+                    slot: None,
+                }),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `action == uid`
+        pub fn action_eq(mut self, uid: EntityUid) -> Self {
+            self.action = ActionOp {
+                op: ActionOperator::Eq,
+                entity: Some(uid),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `action in [uids]`
+        pub fn action_in(mut self, uids: impl IntoIterator<Item = EntityUid>) -> Self {
+            self.action = ActionOp::new_in(uids.into_iter().collect());
+            self
+        }
+
+        /// `resource == uid`
+        pub fn resource_eq(mut self, uid: EntityUid) -> Self {
+            self.resource = ResourceOp::new_eq(uid);
+            self
+        }
+
+        /// `resource in uid`
+        pub fn resource_in(mut self, uid: EntityUid) -> Self {
+            self.resource = ResourceOp {
+                op: ResourceOperator::In,
+                entity: Some(uid),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `resource is type`
+        pub fn resource_is(mut self, entity_type: impl Into<String>) -> Self {
+            self.resource = ResourceOp {
+                op: ResourceOperator::Is,
+                entity_type: Some(entity_type.into()),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `resource is type in uid`
+        pub fn resource_is_in(mut self, entity_type: impl Into<String>, uid: EntityUid) -> Self {
+            self.resource = ResourceOp {
+                op: ResourceOperator::Is,
+                entity_type: Some(entity_type.into()),
+                r#in: Some(EntityOrSlot {
+                    entity: Some(uid),
+                    slot: None,
+                }),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// Adds a `when { expr }` condition, after the conditions already added.
+        pub fn when(mut self, expr: JsonExpr) -> Self {
+            self.conditions.push(Condition {
+                kind: ConditionKind::When,
+                body: expr,
+            });
+            self
+        }
+
+        /// Adds an `unless { expr }` condition, after the conditions already added.
+        pub fn unless(mut self, expr: JsonExpr) -> Self {
+            self.conditions.push(Condition {
+                kind: ConditionKind::Unless,
+                body: expr,
+            });
+            self
+        }
+    }
+
+    impl PolicyBuilder<Policy> {
+        pub fn build(self) -> Policy {
+            Policy {
+                effect: self.effect,
+                principal: self.principal,
+                action: self.action,
+                resource: self.resource,
+                conditions: self.conditions,
+                annotations: self.annotations,
+            }
+        }
+    }
+
+    impl PolicyBuilder<Template> {
+        /// `principal == ?principal`
+        pub fn principal_eq_slot(mut self) -> Self {
+            self.principal = PrincipalOp {
+                op: PrincipalOperator::Eq,
+                slot: Some(SlotId::Principal),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `principal in ?principal`
+        pub fn principal_in_slot(mut self) -> Self {
+            self.principal = PrincipalOp {
+                op: PrincipalOperator::In,
+                slot: Some(SlotId::Principal),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `principal is type in ?principal`
+        pub fn principal_is_in_slot(mut self, entity_type: impl Into<String>) -> Self {
+            self.principal = PrincipalOp {
+                op: PrincipalOperator::Is,
+                entity_type: Some(entity_type.into()),
+                r#in: Some(EntityOrSlot {
+                    entity: None,
+                    slot: Some(SlotId::Principal),
+                }),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `resource == ?resource`
+        pub fn resource_eq_slot(mut self) -> Self {
+            self.resource = ResourceOp {
+                op: ResourceOperator::Eq,
+                slot: Some(SlotId::Resource),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `resource in ?resource`
+        pub fn resource_in_slot(mut self) -> Self {
+            self.resource = ResourceOp {
+                op: ResourceOperator::In,
+                slot: Some(SlotId::Resource),
+                ..Default::default()
+            };
+            self
+        }
+
+        /// `resource is type in ?resource`
+        pub fn resource_is_in_slot(mut self, entity_type: impl Into<String>) -> Self {
+            self.resource = ResourceOp {
+                op: ResourceOperator::Is,
+                entity_type: Some(entity_type.into()),
+                r#in: Some(EntityOrSlot {
+                    entity: None,
+                    slot: Some(SlotId::Resource),
+                }),
+                ..Default::default()
+            };
+            self
+        }
+
+        pub fn build(self) -> Template {
+            Template {
+                effect: self.effect,
+                principal: self.principal,
+                action: self.action,
+                resource: self.resource,
+                conditions: self.conditions,
+                annotations: self.annotations,
+            }
+        }
+    }
+}
+
+/// Constructors of expressions, e.g. `JsonExpr::resource().attr("owner").equals(JsonExpr::principal())`,
+/// and traversals visiting or rewriting every subexpression of a condition.
+pub mod expr {
+    use super::*;
+
+    fn binary(left: JsonExpr, right: JsonExpr) -> Box<BinaryExpr> {
+        Box::new(BinaryExpr { left, right })
+    }
+
+    impl JsonExpr {
+        pub fn principal() -> Self {
+            JsonExpr::Var(VarValue::Principal)
+        }
+
+        pub fn action() -> Self {
+            JsonExpr::Var(VarValue::Action)
+        }
+
+        pub fn resource() -> Self {
+            JsonExpr::Var(VarValue::Resource)
+        }
+
+        pub fn context() -> Self {
+            JsonExpr::Var(VarValue::Context)
+        }
+
+        pub fn string(value: impl Into<String>) -> Self {
+            JsonExpr::Value(ValueExpr::String(value.into()))
+        }
+
+        pub fn long(value: i64) -> Self {
+            JsonExpr::Value(ValueExpr::Number(value))
+        }
+
+        pub fn boolean(value: bool) -> Self {
+            JsonExpr::Value(ValueExpr::Boolean(value))
+        }
+
+        pub fn entity(uid: EntityUid) -> Self {
+            JsonExpr::Value(ValueExpr::EntityUidEscape(EntityUidEscape { entity: uid }))
+        }
+
+        pub fn set(items: impl IntoIterator<Item = JsonExpr>) -> Self {
+            JsonExpr::Set(items.into_iter().collect())
+        }
+
+        pub fn record(attrs: impl IntoIterator<Item = (String, JsonExpr)>) -> Self {
+            JsonExpr::Record(attrs.into_iter().collect())
+        }
+
+        pub fn if_then_else(r#if: JsonExpr, then: JsonExpr, r#else: JsonExpr) -> Self {
+            JsonExpr::IfThenElse(Box::new(IfThenElseExpr { r#if, then, r#else }))
+        }
+
+        /// `self.attr`
+        pub fn attr(self, attr: impl Into<String>) -> Self {
+            JsonExpr::Dot(Box::new(HasExpr {
+                left: self,
+                attr: attr.into(),
+            }))
+        }
+
+        /// `self has attr`
+        pub fn has(self, attr: impl Into<String>) -> Self {
+            JsonExpr::Has(Box::new(HasExpr {
+                left: self,
+                attr: attr.into(),
+            }))
+        }
+
+        /// `self is entity_type`
+        pub fn is(self, entity_type: impl Into<String>) -> Self {
+            JsonExpr::Is(Box::new(IsExpr {
+                left: self,
+                entity_type: entity_type.into(),
+            }))
+        }
+
+        /// `self like "pattern"`, where `*` is a wildcard.
+        pub fn like(self, pattern: &str) -> Self {
+            let mut elems = Vec::new();
+            for (index, literal) in pattern.split('*').enumerate() {
+                if index > 0 {
+                    elems.push(PatternElem::Wildcard);
+                }
+                if !literal.is_empty() {
+                    elems.push(PatternElem::Literal(literal.to_string()));
+                }
+            }
+            JsonExpr::Like(Box::new(LikeExpr {
+                left: self,
+                pattern: elems,
+            }))
+        }
+
+        /// `!self`
+        pub fn negate(self) -> Self {
+            JsonExpr::Bang(Box::new(NegExpr { arg: self }))
+        }
+
+        pub fn equals(self, right: JsonExpr) -> Self {
+            JsonExpr::Eq(binary(self, right))
+        }
+
+        pub fn not_equals(self, right: JsonExpr) -> Self {
+            JsonExpr::Neq(binary(self, right))
+        }
+
+        /// `self in right`
+        pub fn is_in(self, right: JsonExpr) -> Self {
+            JsonExpr::In(binary(self, right))
+        }
+
+        pub fn less_than(self, right: JsonExpr) -> Self {
+            JsonExpr::Lt(binary(self, right))
+        }
+
+        pub fn less_or_equal(self, right: JsonExpr) -> Self {
+            JsonExpr::Lte(binary(self, right))
+        }
+
+        pub fn greater_than(self, right: JsonExpr) -> Self {
+            JsonExpr::Gt(binary(self, right))
+        }
+
+        pub fn greater_or_equal(self, right: JsonExpr) -> Self {
+            JsonExpr::Gte(binary(self, right))
+        }
+
+        pub fn and(self, right: JsonExpr) -> Self {
+            JsonExpr::And(binary(self, right))
+        }
+
+        pub fn or(self, right: JsonExpr) -> Self {
+            JsonExpr::Or(binary(self, right))
+        }
+
+        pub fn plus(self, right: JsonExpr) -> Self {
+            JsonExpr::Plus(binary(self, right))
+        }
+
+        pub fn minus(self, right: JsonExpr) -> Self {
+            JsonExpr::Minus(binary(self, right))
+        }
+
+        pub fn times(self, right: JsonExpr) -> Self {
+            JsonExpr::Mul(binary(self, right))
+        }
+
+        pub fn contains(self, right: JsonExpr) -> Self {
+            JsonExpr::Contains(binary(self, right))
+        }
+
+        pub fn contains_all(self, right: JsonExpr) -> Self {
+            JsonExpr::ContainsAll(binary(self, right))
+        }
+
+        pub fn contains_any(self, right: JsonExpr) -> Self {
+            JsonExpr::ContainsAny(binary(self, right))
+        }
+
+        pub fn has_tag(self, tag: JsonExpr) -> Self {
+            JsonExpr::HasTag(binary(self, tag))
+        }
+
+        pub fn get_tag(self, tag: JsonExpr) -> Self {
+            JsonExpr::GetTag(binary(self, tag))
+        }
+
+        /// Immediate subexpressions, operands and arguments in order, those of a record in
+        /// no particular order.
+        pub fn children(&self) -> Vec<&JsonExpr> {
+            match self {
+                JsonExpr::Value(value) => match value {
+                    ValueExpr::Set(set) => set.set.iter().collect(),
+                    ValueExpr::Record(record) => record.record.values().collect(),
+                    _ => Vec::new(),
+                },
+                JsonExpr::Var(_) | JsonExpr::Slot(_) => Vec::new(),
+                JsonExpr::Bang(expr) | JsonExpr::Neg(expr) | JsonExpr::IsEmpty(expr) => {
+                    vec![&expr.arg]
+                }
+                JsonExpr::Eq(expr)
+                | JsonExpr::Neq(expr)
+                | JsonExpr::In(expr)
+                | JsonExpr::Lt(expr)
+                | JsonExpr::Lte(expr)
+                | JsonExpr::Gt(expr)
+                | JsonExpr::Gte(expr)
+                | JsonExpr::And(expr)
+                | JsonExpr::Or(expr)
+                | JsonExpr::Plus(expr)
+                | JsonExpr::Minus(expr)
+                | JsonExpr::Mul(expr)
+                | JsonExpr::Contains(expr)
+                | JsonExpr::ContainsAll(expr)
+                | JsonExpr::ContainsAny(expr)
+                | JsonExpr::HasTag(expr)
+                | JsonExpr::GetTag(expr) => vec![&expr.left, &expr.right],
+                JsonExpr::Dot(expr) | JsonExpr::Has(expr) => vec![&expr.left],
+                JsonExpr::Is(expr) => vec![&expr.left],
+                JsonExpr::Like(expr) => vec![&expr.left],
+                JsonExpr::IfThenElse(expr) => vec![&expr.r#if, &expr.then, &expr.r#else],
+                JsonExpr::Record(record) => record.values().collect(),
+                JsonExpr::Set(args)
+                | JsonExpr::Datetime(args)
+                | JsonExpr::Decimal(args)
+                | JsonExpr::Duration(args)
+                | JsonExpr::Ip(args)
+                | JsonExpr::IsIpV4(args)
+                | JsonExpr::IsIpV6(args)
+                | JsonExpr::IsLoopback(args)
+                | JsonExpr::IsMulticast(args)
+                | JsonExpr::IsInRange(args)
+                | JsonExpr::Offset(args)
+                | JsonExpr::DurationSince(args)
+                | JsonExpr::ToDate(args)
+                | JsonExpr::ToTime(args)
+                | JsonExpr::ToMilliseconds(args)
+                | JsonExpr::ToSeconds(args)
+                | JsonExpr::ToMinutes(args)
+                | JsonExpr::ToHours(args)
+                | JsonExpr::ToDays(args)
+                | JsonExpr::LessThan(args)
+                | JsonExpr::LessThanOrEqual(args)
+                | JsonExpr::GreaterThan(args)
+                | JsonExpr::GreaterThanOrEqual(args) => args.iter().collect(),
+            }
+        }
+
+        /// Immediate subexpressions, in the order of `children`.
+        pub fn children_mut(&mut self) -> Vec<&mut JsonExpr> {
+            match self {
+                JsonExpr::Value(value) => match value {
+                    ValueExpr::Set(set) => set.set.iter_mut().collect(),
+                    ValueExpr::Record(record) => record.record.values_mut().collect(),
+                    _ => Vec::new(),
+                },
+                JsonExpr::Var(_) | JsonExpr::Slot(_) => Vec::new(),
+                JsonExpr::Bang(expr) | JsonExpr::Neg(expr) | JsonExpr::IsEmpty(expr) => {
+                    vec![&mut expr.arg]
+                }
+                JsonExpr::Eq(expr)
+                | JsonExpr::Neq(expr)
+                | JsonExpr::In(expr)
+                | JsonExpr::Lt(expr)
+                | JsonExpr::Lte(expr)
+                | JsonExpr::Gt(expr)
+                | JsonExpr::Gte(expr)
+                | JsonExpr::And(expr)
+                | JsonExpr::Or(expr)
+                | JsonExpr::Plus(expr)
+                | JsonExpr::Minus(expr)
+                | JsonExpr::Mul(expr)
+                | JsonExpr::Contains(expr)
+                | JsonExpr::ContainsAll(expr)
+                | JsonExpr::ContainsAny(expr)
+                | JsonExpr::HasTag(expr)
+                | JsonExpr::GetTag(expr) => vec![&mut expr.left, &mut expr.right],
+                JsonExpr::Dot(expr) | JsonExpr::Has(expr) => vec![&mut expr.left],
+                JsonExpr::Is(expr) => vec![&mut expr.left],
+                JsonExpr::Like(expr) => vec![&mut expr.left],
+                JsonExpr::IfThenElse(expr) => {
+                    vec![&mut expr.r#if, &mut expr.then, &mut expr.r#else]
+                }
+                JsonExpr::Record(record) => record.values_mut().collect(),
+                JsonExpr::Set(args)
+                | JsonExpr::Datetime(args)
+                | JsonExpr::Decimal(args)
+                | JsonExpr::Duration(args)
+                | JsonExpr::Ip(args)
+                | JsonExpr::IsIpV4(args)
+                | JsonExpr::IsIpV6(args)
+                | JsonExpr::IsLoopback(args)
+                | JsonExpr::IsMulticast(args)
+                | JsonExpr::IsInRange(args)
+                | JsonExpr::Offset(args)
+                | JsonExpr::DurationSince(args)
+                | JsonExpr::ToDate(args)
+                | JsonExpr::ToTime(args)
+                | JsonExpr::ToMilliseconds(args)
+                | JsonExpr::ToSeconds(args)
+                | JsonExpr::ToMinutes(args)
+                | JsonExpr::ToHours(args)
+                | JsonExpr::ToDays(args)
+                | JsonExpr::LessThan(args)
+                | JsonExpr::LessThanOrEqual(args)
+                | JsonExpr::GreaterThan(args)
+                | JsonExpr::GreaterThanOrEqual(args) => args.iter_mut().collect(),
+            }
+        }
+
+        /// Calls `f` on this expression then on each of its subexpressions, depth first.
+        pub fn walk(&self, f: &mut impl FnMut(&JsonExpr)) {
+            f(self);
+            for child in self.children() {
+                child.walk(f);
+            }
+        }
+
+        pub fn accept<V: ExprVisitor + ?Sized>(&self, visitor: &mut V) {
+            visitor.visit_expr(self);
+        }
+
+        pub fn fold<F: ExprFolder + ?Sized>(self, folder: &mut F) -> JsonExpr {
+            folder.fold_expr(self)
+        }
+    }
+
+    /// Analysis of expressions. `visit_expr` visits every subexpression by default, an
+    /// implementation overriding it calls `walk_children` to keep descending.
+    pub trait ExprVisitor {
+        fn visit_expr(&mut self, expr: &JsonExpr) {
+            walk_children(self, expr);
+        }
+    }
+
+    pub fn walk_children<V: ExprVisitor + ?Sized>(visitor: &mut V, expr: &JsonExpr) {
+        for child in expr.children() {
+            visitor.visit_expr(child);
+        }
+    }
+
+    /// Rewriting of expressions. `fold_expr` rebuilds every subexpression by default, an
+    /// implementation overriding it calls `fold_children` to rewrite below the expressions it
+    /// keeps.
+    pub trait ExprFolder {
+        fn fold_expr(&mut self, expr: JsonExpr) -> JsonExpr {
+            fold_children(self, expr)
+        }
+    }
+
+    pub fn fold_children<F: ExprFolder + ?Sized>(folder: &mut F, mut expr: JsonExpr) -> JsonExpr {
+        for child in expr.children_mut() {
+            *child = folder.fold_expr(std::mem::take(child));
+        }
+        expr
+    }
+
+    impl Condition {
+        pub fn new(kind: ConditionKind, body: JsonExpr) -> Self {
+            Self { kind, body }
+        }
+
+        pub fn kind(&self) -> &ConditionKind {
+            &self.kind
+        }
+
+        pub fn body(&self) -> &JsonExpr {
+            &self.body
+        }
+
+        pub fn body_mut(&mut self) -> &mut JsonExpr {
+            &mut self.body
+        }
+    }
+
+    /// Entity attributes, tags and context keys the conditions of a policy or template read,
+    /// e.g. for the impact of a schema change. Attributes are paths from the variable or entity
+    /// they are read on, `resource.owner.team` also listing `resource.owner`. Tags are the key
+    /// they are read with on a path, `*` when the key is not a literal. Context keys are paths
+    /// below `context`.
+    #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AttributeReferences {
+        pub attributes: BTreeSet<String>,
+        pub tags: BTreeSet<String>,
+        pub context: BTreeSet<String>,
+    }
+
+    impl AttributeReferences {
+        pub fn of(conditions: &[Condition]) -> Self {
+            let mut references = Self::default();
+            for condition in conditions {
+                condition.body().accept(&mut references);
+            }
+            references
+        }
+
+        fn add(&mut self, path: String) {
+            match path.strip_prefix("context.") {
+                Some(key) => self.context.insert(key.to_string()),
+                None => self.attributes.insert(path),
+            };
+        }
+    }
+
+    // Path of an expression read from, e.g. `resource.owner`
+    fn path(expr: &JsonExpr) -> Option<String> {
+        match expr {
+            JsonExpr::Var(var) => Some(
+                match var {
+                    VarValue::Principal => "principal",
+                    VarValue::Action => "action",
+                    VarValue::Resource => "resource",
+                    VarValue::Context => "context",
+                }
+                .to_string(),
+            ),
+            JsonExpr::Value(ValueExpr::EntityUidEscape(escape)) => Some(format!(
+                "{}::\"{}\"",
+                escape.entity.type_name(),
+                escape.entity.id()
+            )),
+            JsonExpr::Dot(dot) => Some(format!("{}.{}", path(&dot.left)?, dot.attr)),
+            _ => None,
+        }
+    }
+
+    impl ExprVisitor for AttributeReferences {
+        fn visit_expr(&mut self, expr: &JsonExpr) {
+            match expr {
+                JsonExpr::Dot(dot) | JsonExpr::Has(dot) => {
+                    if let Some(left) = path(&dot.left) {
+                        self.add(format!("{}.{}", left, dot.attr));
+                    }
+                }
+                JsonExpr::HasTag(tag) | JsonExpr::GetTag(tag) => {
+                    if let Some(left) = path(&tag.left) {
+                        let key = match &tag.right {
+                            JsonExpr::Value(ValueExpr::String(key)) => key.as_str(),
+                            _ => "*",
+                        };
+                        self.tags.insert(format!("{}.{}", left, key));
+                    }
+                }
+                _ => {}
+            }
+            walk_children(self, expr);
+        }
+    }
+
+    impl Policy {
+        /// Entity attributes, tags and context keys the conditions read.
+        pub fn references(&self) -> AttributeReferences {
+            AttributeReferences::of(&self.conditions)
+        }
+    }
+
+    impl Template {
+        /// Entity attributes, tags and context keys the conditions read.
+        pub fn references(&self) -> AttributeReferences {
+            AttributeReferences::of(&self.conditions)
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // TODO: Create tests
+    use prost::Message;
+
+    use super::*;
+
+    fn round_trip<T, P>(value: T) -> T
+    where
+        T: Into<P>,
+        P: Message + Default + Into<T>,
+    {
+        let buf = value.into().encode_to_vec();
+        P::decode(buf.as_slice()).unwrap().into()
+    }
+
+    #[test]
+    fn test_policy_annotations_round_trip() {
+        let policy = Policy {
+            annotations: HashMap::from([
+                ("id".to_string(), Some("policy0".to_string())),
+                ("empty".to_string(), Some(String::new())),
+                ("flag".to_string(), None),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(round_trip::<Policy, proto::Policy>(policy.clone()), policy);
+    }
+
+    #[test]
+    fn test_template_annotations_round_trip() {
+        let template = Template {
+            annotations: HashMap::from([("flag".to_string(), None)]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            round_trip::<Template, proto::Template>(template.clone()),
+            template
+        );
+    }
+
+    #[test]
+    fn test_extension_functions_round_trip() {
+        let sources = [
+            r#"permit(principal, action, resource) when {
+                context.now > datetime("2024-10-15T11:35:00Z") &&
+                context.now.toDate() == datetime("2024-10-15") &&
+                context.now.offset(duration("1h")).durationSince(datetime("2024-01-01")) < duration("2d") &&
+                context.now.toTime().toHours() >= 9 &&
+                duration("90m").toMinutes() == 90 &&
+                duration("1s").toMilliseconds() == 1000 &&
+                duration("1m").toSeconds() == 60 &&
+                duration("1d").toDays() == 1
+            };"#,
+            r#"permit(principal, action, resource) when {
+                ip("1.2.3.4").isIpv4() && ip("::1").isIpv6() &&
+                ip("127.0.0.1").isLoopback() && !ip("224.0.0.1").isMulticast() &&
+                ip("10.0.0.1").isInRange(ip("10.0.0.0/8"))
+            };"#,
+            r#"permit(principal, action, resource) when {
+                decimal("1.0").lessThan(decimal("2.0")) &&
+                decimal("1.0").lessThanOrEqual(decimal("2.0")) &&
+                decimal("3.0").greaterThan(decimal("2.0")) &&
+                decimal("3.0").greaterThanOrEqual(decimal("2.0"))
+            };"#,
+        ];
+
+        for source in sources {
+            let json = cedar_policy::Policy::parse(None, source)
+                .unwrap()
+                .to_json()
+                .unwrap();
+            let policy: Policy = serde_json::from_value(json.clone()).unwrap();
+
+            assert_eq!(serde_json::to_value(&policy).unwrap(), json);
+            assert_eq!(round_trip::<Policy, proto::Policy>(policy.clone()), policy);
+        }
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        let context = Context(HashMap::from([
+            ("mfa".to_string(), entity::EntityAttr::Boolean(true)),
+            ("age".to_string(), entity::EntityAttr::Number(42)),
+            (
+                "tags".to_string(),
+                entity::EntityAttr::Set(vec![entity::EntityAttr::String("a".to_string())]),
+            ),
+        ]));
+        let request = Request {
+            principal: EntityUid::from("User::alice"),
+            action: EntityUid::from("Action::view"),
+            resource: EntityUid::from("Photo::vacation"),
+            context: Some(context),
+        };
+
+        assert_eq!(
+            round_trip::<Request, proto::Request>(request.clone()),
+            request
+        );
+
+        let request = Request {
+            context: None,
+            ..request
+        };
+        assert_eq!(
+            round_trip::<Request, proto::Request>(request.clone()),
+            request
+        );
+    }
+
+    #[test]
+    fn test_entity_uid_invalid_type_name() {
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "principal": { "type": "1 bad", "id": "x" },
+            "action": { "type": "Action", "id": "view" },
+            "resource": { "type": "Photo", "id": "a \"quoted\" id" },
+        }))
+        .unwrap();
+
+        assert!(cedar_policy::EntityUid::try_from(request.principal).is_err());
+        let escape = EntityUidEscape::from(EntityUid::new("".to_string(), "x".to_string()));
+        assert!(cedar_policy::EntityUid::try_from(escape).is_err());
+
+        let resource = cedar_policy::EntityUid::try_from(request.resource).unwrap();
+        assert_eq!(resource.id().unescaped(), "a \"quoted\" id");
+        let link = TemplateLink::new(
+            PolicyId::from("t".to_string()),
+            PolicyId::from("l".to_string()),
+            HashMap::from([(
+                SlotId::Principal,
+                EntityValue::EntityUid(EntityUid::new("Bad Type".to_string(), "x".to_string())),
+            )]),
+        );
+        assert!(link.to_cedar_vals().is_err());
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let response = Response {
+            decision: Decision::Allow,
+            reason: vec!["policy0".to_string()],
+            errors: vec!["error".to_string()],
+            evaluation_micros: None,
+        };
+
+        assert_eq!(
+            round_trip::<Response, proto::Response>(response.clone()),
+            response
+        );
+    }
+
+    #[test]
+    fn test_context_validate() {
+        let schema: Schema = serde_json::from_value(serde_json::json!({
+            "App": {
+                "entityTypes": { "User": {} },
+                "actions": {
+                    "view": {
+                        "appliesTo": {
+                            "principalTypes": ["User"],
+                            "resourceTypes": ["User"],
+                            "context": { "type": "EntityOrCommon", "name": "Ctx" }
+                        }
+                    }
+                },
+                "commonTypes": {
+                    "Ctx": {
+                        "type": "Record",
+                        "attributes": {
+                            "ip": { "type": "Extension", "name": "ipaddr" },
+                            "owner": { "type": "Entity", "name": "User" },
+                            "tags": { "type": "Set", "element": { "type": "String" } },
+                            "level": { "type": "Long" },
+                            "note": { "type": "String", "required": false }
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let action = EntityUid::new("App::Action".to_string(), "view".to_string());
+
+        let context: Context = serde_json::from_value(serde_json::json!({
+            "ip": "10.0.0.1",
+            "owner": { "__entity": { "type": "App::User", "id": "alice" } },
+            "tags": ["a", 1],
+            "extra": true
+        }))
+        .unwrap();
+
+        let errors = context.validate(&schema, &action);
+        assert_eq!(
+            errors,
+            vec![
+                ContextError {
+                    path: "context.extra".to_string(),
+                    kind: ContextErrorKind::UnexpectedAttribute,
+                    expected: None,
+                    found: Some("Boolean".to_string()),
+                },
+                ContextError {
+                    path: "context.level".to_string(),
+                    kind: ContextErrorKind::MissingAttribute,
+                    expected: Some("Long".to_string()),
+                    found: None,
+                },
+                ContextError {
+                    path: "context.tags[1]".to_string(),
+                    kind: ContextErrorKind::TypeMismatch,
+                    expected: Some("String".to_string()),
+                    found: Some("Long".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            errors[2].to_string(),
+            "context.tags[1]: expected String, found Long"
+        );
+    }
+
+    #[test]
+    fn test_request_validate() {
+        let schema: Schema = serde_json::from_value(serde_json::json!({
+            "App": {
+                "entityTypes": { "User": {}, "Document": {} },
+                "actions": {
+                    "view": {
+                        "appliesTo": {
+                            "principalTypes": ["User"],
+                            "resourceTypes": ["Document"]
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let uid = |t: &str, id: &str| EntityUid::new(t.to_string(), id.to_string());
+        let user = uid("App::User", "alice");
+        let document = uid("App::Document", "d1");
+
+        let errors = schema.validate_request(&user, &uid("App::Action", "view"), &document);
+        assert!(errors.is_empty());
+
+        let errors = schema.validate_request(&user, &uid("App::Action", "edit"), &document);
+        assert_eq!(errors[0].kind, RequestErrorKind::UndeclaredAction);
+
+        let errors = schema.validate_request(&document, &uid("App::Action", "view"), &user);
+        assert_eq!(
+            errors.iter().map(|e| e.kind).collect::<Vec<_>>(),
+            vec![
+                RequestErrorKind::UnexpectedPrincipalType,
+                RequestErrorKind::UnexpectedResourceType
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "App::Action::\"view\": principal type App::Document not in appliesTo, expected one of [App::User]"
+        );
+    }
+
+    #[test]
+    fn test_entity_coerce() {
+        let schema_json = serde_json::json!({
+            "App": {
+                "entityTypes": {
+                    "Host": {
+                        "shape": {
+                            "type": "Record",
+                            "attributes": {
+                                "addr": { "type": "Extension", "name": "ipaddr" },
+                                "load": { "type": "Extension", "name": "decimal" },
+                                "limit": { "type": "Extension", "name": "decimal" },
+                                "ranges": {
+                                    "type": "Set",
+                                    "element": { "type": "Extension", "name": "ipaddr" }
+                                },
+                                "name": { "type": "String" }
+                            }
+                        }
+                    }
+                },
+                "actions": {}
+            }
+        });
+        let schema: Schema = serde_json::from_value(schema_json.clone()).unwrap();
+        let cedar_schema = cedar_policy::Schema::from_json_value(schema_json).unwrap();
+
+        let mut entity: Entity = serde_json::from_value(serde_json::json!({
+            "uid": { "type": "App::Host", "id": "web" },
+            "attrs": {
+                "addr": "10.0.0.1",
+                "load": 0.75,
+                "limit": 2,
+                "ranges": ["10.0.0.0/8"],
+                "name": "10.0.0.1"
+            }
+        }))
+        .unwrap();
+        assert_eq!(entity.attrs()["load"], entity::EntityAttr::Decimal(0.75));
+        assert!(entity.to_cedar_entity(Some(&cedar_schema)).is_err());
+
+        entity.coerce(&schema);
+        assert_eq!(
+            entity.attrs()["addr"],
+            entity::EntityAttr::from("10.0.0.1".parse::<std::net::IpAddr>().unwrap())
+        );
+        assert_eq!(
+            entity.attrs()["limit"],
+            entity::EntityAttr::extension("decimal", "2.0".to_string())
+        );
+        assert_eq!(
+            entity.attrs()["name"],
+            entity::EntityAttr::String("10.0.0.1".to_string())
+        );
+        assert!(entity.to_cedar_entity(Some(&cedar_schema)).is_ok());
+
+        assert_eq!(
+            serde_json::to_value(entity::EntityAttr::Decimal(1.5)).unwrap(),
+            serde_json::json!({ "__extn": { "fn": "decimal", "arg": "1.5" } })
+        );
+    }
+
+    #[test]
+    fn test_entity_decimal_errors() {
+        assert!(entity::decimal_check(0.75).is_ok());
+        assert!(entity::decimal_check(-12.3456).is_ok());
+        assert!(entity::decimal_check(922_337_203_685_477.0).is_ok());
+        assert!(entity::decimal_check(0.12345).is_err());
+        assert!(entity::decimal_check(1e15).is_err());
+        assert!(entity::decimal_check(f64::NAN).is_err());
+
+        let entity: Entity = serde_json::from_value(serde_json::json!({
+            "uid": { "type": "Host", "id": "web" },
+            "attrs": { "load": 0.75, "limits": { "cpu": 0.00001 } },
+            "tags": { "weight": 1e16 }
+        }))
+        .unwrap();
+        assert_eq!(
+            entity.decimal_errors(),
+            vec![
+                "Host::web limits.cpu: decimal 0.00001 has more than four fractional digits",
+                "Host::web tag weight: decimal 10000000000000000 is out of range",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entity_merge() {
+        let mut stored: Entity = serde_json::from_value(serde_json::json!({
+            "uid": { "type": "User", "id": "alice" },
+            "attrs": { "department": "sales", "level": 1 },
+            "parents": [{ "type": "Group", "id": "staff" }]
+        }))
+        .unwrap();
+        let mapped: Entity = serde_json::from_value(serde_json::json!({
+            "uid": { "type": "User", "id": "alice" },
+            "attrs": { "level": 2, "email": "alice@example.com" },
+            "parents": [{ "type": "Group", "id": "admins" }]
+        }))
+        .unwrap();
+
+        stored.merge(mapped);
+        assert_eq!(
+            stored.attrs()["department"],
+            entity::EntityAttr::String("sales".to_string())
+        );
+        assert_eq!(stored.attrs()["level"], entity::EntityAttr::Number(2));
+        assert!(stored.attrs().contains_key("email"));
+        assert_eq!(stored.parents().len(), 2);
+    }
+
+    #[test]
+    fn test_resource_path() {
+        let path: ResourcePath = serde_json::from_value(serde_json::json!({
+            "containerType": "App::Folder",
+            "resourceType": "App::Document",
+            "path": "/docs//2024/report.pdf",
+            "attrs": { "owner": "alice" }
+        }))
+        .unwrap();
+        let entities = path.entities();
+
+        let uids: Vec<String> = entities.iter().map(|e| e.uid().to_string()).collect();
+        assert_eq!(
+            uids,
+            vec![
+                "App::Folder::/docs",
+                "App::Folder::/docs/2024",
+                "App::Document::/docs/2024/report.pdf"
+            ]
+        );
+        assert!(entities[0].parents().is_empty());
+        assert!(entities[2].parents().contains(entities[1].uid()));
+        assert!(entities[1].attrs().is_empty());
+        assert_eq!(entities[2].attrs().len(), 1);
+        assert_eq!(path.uid().as_ref(), Some(entities[2].uid()));
+
+        let empty = ResourcePath {
+            container_type: "App::Folder".to_string(),
+            path: "/".to_string(),
+            ..Default::default()
+        };
+        assert!(empty.entities().is_empty());
+        assert!(empty.uid().is_none());
+    }
+
+    #[test]
+    fn test_entity_expires_at() {
+        let entity: Entity = serde_json::from_value(serde_json::json!({
+            "uid": { "type": "Session", "id": "s1" },
+            "expiresAt": "2025-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        let expires_at = *entity.expires_at().unwrap();
+
+        assert!(entity.is_expired(expires_at));
+        assert!(!entity.is_expired(expires_at - chrono::TimeDelta::seconds(1)));
+
+        let entity = round_trip::<Entity, proto::Entity>(entity);
+        assert_eq!(entity.expires_at(), Some(&expires_at));
+
+        let cedar_entity = entity.to_cedar_entity(None).unwrap();
+        assert_eq!(cedar_entity.uid().to_string(), "Session::\"s1\"");
+    }
+
+    #[test]
+    fn test_schema_with_common_types() {
+        let schema: Schema = serde_json::from_value(serde_json::json!({
+            "App": {
+                "entityTypes": {
+                    "User": {
+                        "shape": {
+                            "type": "Record",
+                            "attributes": {
+                                "home": { "type": "EntityOrCommon", "name": "Address" },
+                                "manager": { "type": "EntityOrCommon", "name": "User" }
+                            }
+                        }
+                    }
+                },
+                "actions": {}
+            }
+        }))
+        .unwrap();
+        let library: HashMap<String, schema::TypeJson> =
+            serde_json::from_value(serde_json::json!({
+                "Address": {
+                    "type": "Record",
+                    "attributes": {
+                        "street": { "type": "String" },
+                        "country": { "type": "EntityOrCommon", "name": "Country" }
+                    }
+                },
+                "Country": { "type": "String" },
+                "User": { "type": "Long" },
+                "Unused": { "type": "Boolean" }
+            }))
+            .unwrap();
+
+        let schema = schema.with_common_types(&library);
+        let common_types = schema.0["App"].common_types.as_ref().unwrap();
+        let mut names: Vec<&String> = common_types.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["Address", "Country"]);
+
+        let _: cedar_policy::Schema = schema.try_into().unwrap();
+    }
+
+    fn assert_cedar(json: serde_json::Value, cedar: &str) {
+        let expected = cedar_policy::Policy::parse(None, cedar)
+            .unwrap()
+            .to_json()
+            .unwrap();
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn test_policy_builder() {
+        let user = |id: &str| EntityUid::new("App::User".to_string(), id.to_string());
+        let action = |id: &str| EntityUid::new("App::Action".to_string(), id.to_string());
+        let condition: JsonExpr = serde_json::from_value(serde_json::json!({
+            "==": {
+                "left": { ".": { "left": { "Var": "resource" }, "attr": "owner" } },
+                "right": { "Var": "principal" }
+            }
+        }))
+        .unwrap();
+
+        let policy = Policy::builder()
+            .permit()
+            .id("owners")
+            .principal_eq(user("alice"))
+            .action_in([action("view"), action("edit")])
+            .resource_is_in(
+                "App::Document",
+                EntityUid::new("App::Folder".to_string(), "shared".to_string()),
+            )
+            .when(condition.clone())
+            .build();
+        let cedar_policy = policy
+            .to_cedar(PolicyId::from("owners".to_string()))
+            .unwrap();
+        assert_cedar(
+            cedar_policy.to_json().unwrap(),
+            r#"@id("owners")
+            permit(
+                principal == App::User::"alice",
+                action in [App::Action::"view", App::Action::"edit"],
+                resource is App::Document in App::Folder::"shared"
+            ) when { resource.owner == principal };"#,
+        );
+        assert_eq!(Policy::try_from(cedar_policy).unwrap(), policy);
+
+        let policy = Policy::builder()
+            .forbid()
+            .principal_is("App::User")
+            .action_eq(action("delete"))
+            .unless(condition)
+            .build();
+        assert_cedar(
+            policy
+                .to_cedar(PolicyId::from("p".to_string()))
+                .unwrap()
+                .to_json()
+                .unwrap(),
+            r#"forbid(principal is App::User, action == App::Action::"delete", resource)
+            unless { resource.owner == principal };"#,
+        );
+    }
+
+    #[test]
+    fn test_template_builder() {
+        let template = Template::builder()
+            .permit()
+            .principal_eq_slot()
+            .action_eq(EntityUid::new(
+                "App::Action".to_string(),
+                "view".to_string(),
+            ))
+            .resource_is_in_slot("App::Document")
+            .build();
+        let cedar_template = template.to_cedar(PolicyId::from("t".to_string())).unwrap();
+        let expected = cedar_policy::Template::parse(
+            None,
+            r#"permit(principal == ?principal, action == App::Action::"view", resource is App::Document in ?resource);"#,
+        )
+        .unwrap();
+        assert_eq!(
+            cedar_template.to_json().unwrap(),
+            expected.to_json().unwrap()
+        );
+        assert_eq!(Template::try_from(cedar_template).unwrap(), template);
+    }
+
+    #[test]
+    fn test_expr_constructors() {
+        let admins = EntityUid::new("App::Group".to_string(), "admins".to_string());
+        let policy = Policy::builder()
+            .permit()
+            .when(
+                JsonExpr::resource()
+                    .attr("owner")
+                    .equals(JsonExpr::principal())
+                    .or(JsonExpr::principal().is_in(JsonExpr::entity(admins)))
+                    .and(
+                        JsonExpr::context()
+                            .has("level")
+                            .negate()
+                            .or(JsonExpr::context()
+                                .attr("level")
+                                .greater_or_equal(JsonExpr::long(2))),
+                    ),
+            )
+            .unless(JsonExpr::resource().attr("name").like("*.secret"))
+            .build();
+
+        // Cedar parses patterns into one literal per character, the built policy is compared
+        // once printed and parsed again
+        let expected = cedar_policy::Policy::parse(
+            None,
+            r#"permit(principal, action, resource)
+            when {
+                (resource.owner == principal || principal in App::Group::"admins") &&
+                (!(context has level) || context.level >= 2)
+            }
+            unless { resource.name like "*.secret" };"#,
+        )
+        .unwrap();
+        let cedar = policy
+            .to_cedar(PolicyId::from("p".to_string()))
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            cedar_policy::Policy::parse(None, cedar)
+                .unwrap()
+                .to_json()
+                .unwrap(),
+            expected.to_json().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expr_visitor_and_folder() {
+        struct Attributes(Vec<String>);
+
+        impl expr::ExprVisitor for Attributes {
+            fn visit_expr(&mut self, expr: &JsonExpr) {
+                if let JsonExpr::Dot(dot) | JsonExpr::Has(dot) = expr {
+                    self.0.push(dot.attr.clone());
+                }
+                expr::walk_children(self, expr);
+            }
+        }
+
+        // Renames `owner` to `creator`
+        struct Rename;
+
+        impl expr::ExprFolder for Rename {
+            fn fold_expr(&mut self, expr: JsonExpr) -> JsonExpr {
+                match expr::fold_children(self, expr) {
+                    JsonExpr::Dot(mut dot) if dot.attr == "owner" => {
+                        dot.attr = "creator".to_string();
+                        JsonExpr::Dot(dot)
+                    }
+                    expr => expr,
+                }
+            }
+        }
+
+        let json = cedar_policy::Policy::parse(
+            None,
+            r#"permit(principal, action, resource) when {
+                resource has owner && resource.owner == principal &&
+                [context.a, context.b].contains(principal.dept) &&
+                (if context.c then resource.tags.containsAll([1]) else false)
+            };"#,
+        )
+        .unwrap()
+        .to_json()
+        .unwrap();
+        let policy: Policy = serde_json::from_value(json).unwrap();
+        let body = policy.conditions[0].body();
+
+        let mut attributes = Attributes(Vec::new());
+        body.accept(&mut attributes);
+        attributes.0.sort();
+        assert_eq!(
+            attributes.0,
+            vec!["a", "b", "c", "dept", "owner", "owner", "tags"]
+        );
+
+        let mut count = 0;
+        body.walk(&mut |_| count += 1);
+        assert!(count > attributes.0.len());
+
+        let renamed = body.clone().fold(&mut Rename);
+        let mut attributes = Attributes(Vec::new());
+        renamed.accept(&mut attributes);
+        assert_eq!(attributes.0.iter().filter(|a| *a == "creator").count(), 1);
+        assert_eq!(attributes.0.iter().filter(|a| *a == "owner").count(), 1);
+    }
+
+    #[test]
+    fn test_attribute_references() {
+        let json = cedar_policy::Policy::parse(
+            None,
+            r#"permit(principal, action, resource) when {
+                resource.owner.team == principal.team &&
+                context has location && context.location.country == "FR" &&
+                resource.hasTag("env") && resource.getTag(context.key) == "x" &&
+                App::User::"admin".enabled
+            };"#,
+        )
+        .unwrap()
+        .to_json()
+        .unwrap();
+        let policy: Policy = serde_json::from_value(json).unwrap();
+
+        let references = policy.references();
+        let set = |items: &[&str]| items.iter().map(|i| i.to_string()).collect::<BTreeSet<_>>();
+        assert_eq!(
+            references.attributes,
+            set(&[
+                "App::User::\"admin\".enabled",
+                "principal.team",
+                "resource.owner",
+                "resource.owner.team"
+            ])
+        );
+        assert_eq!(references.tags, set(&["resource.*", "resource.env"]));
+        assert_eq!(
+            references.context,
+            set(&["key", "location", "location.country"])
+        );
+    }
 }
@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+
+use cedrus_cedar::{
+    Entity, EntityUid, EntityUidEscape, ExtensionFn, JsonExpr, Policy, Schema, entity::EntityAttr,
+    proto,
+};
+use proptest::prelude::*;
+use prost::Message;
+use serde_json::{Value, json};
+
+fn round_trip<T, P>(value: T) -> T
+where
+    T: Into<P>,
+    P: Message + Default + Into<T>,
+{
+    let buf = value.into().encode_to_vec();
+    P::decode(buf.as_slice()).unwrap().into()
+}
+
+fn ident() -> impl Strategy<Value = String> {
+    "[a-zA-Z_][a-zA-Z0-9_]{0,7}"
+}
+
+fn entity_uid() -> impl Strategy<Value = EntityUid> {
+    (ident(), ident()).prop_map(|(t, id)| EntityUid::new(t, id))
+}
+
+fn entity_attr() -> impl Strategy<Value = EntityAttr> {
+    let leaf = prop_oneof![
+        any::<String>().prop_map(EntityAttr::String),
+        any::<i64>().prop_map(EntityAttr::Number),
+        any::<bool>().prop_map(EntityAttr::Boolean),
+        entity_uid().prop_map(EntityAttr::EntityUid),
+        entity_uid().prop_map(|uid| EntityAttr::EntityUidEscape(EntityUidEscape::from(uid))),
+        (ident(), any::<String>()).prop_map(|(r#fn, arg)| {
+            EntityAttr::Function(ExtensionFn::from(proto::ExtensionFn { r#fn, arg }))
+        }),
+    ];
+    leaf.prop_recursive(4, 32, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(EntityAttr::Set),
+            prop::collection::hash_map(ident(), inner, 0..4).prop_map(EntityAttr::Record),
+        ]
+    })
+}
+
+fn entity_uid_json() -> impl Strategy<Value = Value> {
+    (ident(), ident()).prop_map(|(t, id)| json!({ "type": t, "id": id }))
+}
+
+fn json_expr() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        any::<String>().prop_map(|s| json!({ "Value": s })),
+        any::<i64>().prop_map(|n| json!({ "Value": n })),
+        any::<bool>().prop_map(|b| json!({ "Value": b })),
+        entity_uid_json().prop_map(|uid| json!({ "Value": { "__entity": uid } })),
+        prop_oneof![
+            Just("principal"),
+            Just("action"),
+            Just("resource"),
+            Just("context")
+        ]
+        .prop_map(|v| json!({ "Var": v })),
+        prop_oneof![Just("?principal"), Just("?resource")].prop_map(|s| json!({ "Slot": s })),
+    ];
+    leaf.prop_recursive(4, 48, 4, |inner| {
+        let unary = prop_oneof![Just("!"), Just("neg"), Just("isEmpty")];
+        let binary = prop_oneof![
+            Just("=="),
+            Just("!="),
+            Just("in"),
+            Just("<"),
+            Just("<="),
+            Just(">"),
+            Just(">="),
+            Just("&&"),
+            Just("||"),
+            Just("+"),
+            Just("-"),
+            Just("*"),
+            Just("contains"),
+            Just("containsAll"),
+            Just("containsAny"),
+            Just("hasTag"),
+            Just("getTag"),
+        ];
+        let attr = prop_oneof![Just("."), Just("has")];
+        let function = prop_oneof![
+            Just("datetime"),
+            Just("decimal"),
+            Just("duration"),
+            Just("ip"),
+            Just("isIpv4"),
+            Just("isIpv6"),
+            Just("isLoopback"),
+            Just("isMulticast"),
+            Just("isInRange"),
+            Just("offset"),
+            Just("durationSince"),
+            Just("toDate"),
+            Just("toTime"),
+            Just("toMilliseconds"),
+            Just("toSeconds"),
+            Just("toMinutes"),
+            Just("toHours"),
+            Just("toDays"),
+            Just("lessThan"),
+            Just("lessThanOrEqual"),
+            Just("greaterThan"),
+            Just("greaterThanOrEqual"),
+        ];
+        let pattern = prop::collection::vec(
+            prop_oneof![
+                ident().prop_map(|s| json!({ "Literal": s })),
+                Just(json!("Wildcard")),
+            ],
+            0..4,
+        );
+        prop_oneof![
+            (unary, inner.clone()).prop_map(|(op, arg)| json!({ op: { "arg": arg } })),
+            (binary, inner.clone(), inner.clone())
+                .prop_map(|(op, left, right)| json!({ op: { "left": left, "right": right } })),
+            (attr, inner.clone(), ident())
+                .prop_map(|(op, left, attr)| json!({ op: { "left": left, "attr": attr } })),
+            (inner.clone(), ident())
+                .prop_map(|(left, t)| json!({ "is": { "left": left, "entity_type": t } })),
+            (inner.clone(), pattern)
+                .prop_map(|(left, p)| json!({ "like": { "left": left, "pattern": p } })),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(i, t, e)| {
+                json!({ "if-then-else": { "if": i, "then": t, "else": e } })
+            }),
+            prop::collection::vec(inner.clone(), 0..4).prop_map(|set| json!({ "Set": set })),
+            prop::collection::hash_map(ident(), inner.clone(), 0..4)
+                .prop_map(|record| json!({ "Record": record })),
+            prop::collection::vec(inner.clone(), 0..4)
+                .prop_map(|set| json!({ "Value": { "Set": set } })),
+            prop::collection::hash_map(ident(), inner.clone(), 0..4)
+                .prop_map(|record| json!({ "Value": { "Record": record } })),
+            (function, prop::collection::vec(inner, 1..3))
+                .prop_map(|(op, args)| json!({ op: args })),
+        ]
+    })
+}
+
+fn with_required(mut value: Value, required: Option<bool>) -> Value {
+    if let Some(required) = required {
+        value["required"] = json!(required);
+    }
+    value
+}
+
+// `required: true` is the default and is normalized to an absent field by the proto mapping.
+fn required() -> impl Strategy<Value = Option<bool>> {
+    prop_oneof![Just(None), Just(Some(false))]
+}
+
+fn type_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        required().prop_map(|r| with_required(json!({ "type": "Long" }), r)),
+        required().prop_map(|r| with_required(json!({ "type": "String" }), r)),
+        required().prop_map(|r| with_required(json!({ "type": "Boolean" }), r)),
+        (ident(), required())
+            .prop_map(|(n, r)| with_required(json!({ "type": "Entity", "name": n }), r)),
+        (ident(), required())
+            .prop_map(|(n, r)| with_required(json!({ "type": "Extension", "name": n }), r)),
+        (ident(), required())
+            .prop_map(|(n, r)| with_required(json!({ "type": "EntityOrCommon", "name": n }), r)),
+    ];
+    leaf.prop_recursive(3, 24, 4, |inner| {
+        prop_oneof![
+            (inner.clone(), required())
+                .prop_map(|(e, r)| with_required(json!({ "type": "Set", "element": e }), r)),
+            (prop::collection::hash_map(ident(), inner, 0..4), required()).prop_map(|(a, r)| {
+                with_required(json!({ "type": "Record", "attributes": a }), r)
+            }),
+        ]
+    })
+}
+
+// Empty lists and maps are normalized to absent fields by the proto mapping.
+fn non_empty_idents() -> impl Strategy<Value = Option<Vec<String>>> {
+    prop::option::of(prop::collection::vec(ident(), 1..3))
+}
+
+fn annotations() -> impl Strategy<Value = HashMap<String, String>> {
+    prop::collection::hash_map(ident(), any::<String>(), 0..3)
+}
+
+fn insert_some(value: &mut Value, key: &str, item: Option<impl serde::Serialize>) {
+    if let Some(item) = item {
+        value[key] = json!(item);
+    }
+}
+
+fn entity_type() -> impl Strategy<Value = Value> {
+    (
+        non_empty_idents(),
+        prop::option::of(type_json()),
+        prop::option::of(type_json()),
+        non_empty_idents(),
+        annotations(),
+    )
+        .prop_map(|(member_of_types, shape, tags, r#enum, annotations)| {
+            let mut value = json!({ "annotations": annotations });
+            insert_some(&mut value, "memberOfTypes", member_of_types);
+            insert_some(&mut value, "shape", shape);
+            insert_some(&mut value, "tags", tags);
+            insert_some(&mut value, "enum", r#enum);
+            value
+        })
+}
+
+fn action() -> impl Strategy<Value = Value> {
+    (
+        non_empty_idents(),
+        prop::option::of((
+            prop::collection::vec(ident(), 0..3),
+            prop::collection::vec(ident(), 0..3),
+            prop::option::of(type_json()),
+        )),
+        annotations(),
+    )
+        .prop_map(|(member_of, applies_to, annotations)| {
+            let mut value = json!({ "annotations": annotations });
+            insert_some(&mut value, "memberOf", member_of);
+            insert_some(
+                &mut value,
+                "appliesTo",
+                applies_to.map(|(principal_types, resource_types, context)| {
+                    let mut value = json!({
+                        "principalTypes": principal_types,
+                        "resourceTypes": resource_types,
+                    });
+                    insert_some(&mut value, "context", context);
+                    value
+                }),
+            );
+            value
+        })
+}
+
+fn schema() -> impl Strategy<Value = Value> {
+    let namespace = (
+        prop::collection::hash_map(ident(), entity_type(), 0..3),
+        prop::collection::hash_map(ident(), action(), 0..3),
+        prop::option::of(prop::collection::hash_map(ident(), type_json(), 1..3)),
+    )
+        .prop_map(|(entity_types, actions, common_types)| {
+            let mut value = json!({ "entityTypes": entity_types, "actions": actions });
+            insert_some(&mut value, "commonTypes", common_types);
+            value
+        });
+    prop::collection::hash_map("[a-zA-Z0-9_]{0,8}", namespace, 0..3)
+        .prop_map(|namespaces| json!(namespaces))
+}
+
+#[test]
+fn test_decode_missing_fields() {
+    // Fields missing from untrusted messages decode as their defaults
+    let policy = Policy::from(proto::Policy::default());
+    assert_eq!(policy.conditions, Vec::new());
+
+    let entity = Entity::from(proto::Entity::default());
+    assert_eq!(entity.uid(), &EntityUid::default());
+
+    let expr = JsonExpr::from(proto::JsonExpr {
+        expr: Some(proto::json_expr::Expr::Eq(Box::default())),
+    });
+    assert_eq!(
+        serde_json::to_value(&expr).unwrap(),
+        json!({ "==": { "left": { "Value": "" }, "right": { "Value": "" } } })
+    );
+    assert_eq!(
+        EntityAttr::from(proto::entity::EntityAttr::default()),
+        EntityAttr::String(String::new())
+    );
+}
+
+proptest! {
+    #[test]
+    fn test_decode_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+        if let Ok(policy) = proto::Policy::decode(bytes.as_slice()) {
+            let _ = Policy::from(policy);
+        }
+        if let Ok(entity) = proto::Entity::decode(bytes.as_slice()) {
+            let _ = Entity::from(entity);
+        }
+        if let Ok(schema) = proto::Schema::decode(bytes.as_slice()) {
+            let _ = Schema::from(schema);
+        }
+    }
+
+    #[test]
+    fn test_entity_attr_round_trip(attr in entity_attr()) {
+        let decoded = round_trip::<EntityAttr, proto::entity::EntityAttr>(attr.clone());
+        prop_assert_eq!(decoded, attr);
+    }
+
+    #[test]
+    fn test_json_expr_round_trip(value in json_expr()) {
+        let expr: JsonExpr = serde_json::from_value(value).unwrap();
+        let decoded = round_trip::<JsonExpr, proto::JsonExpr>(expr.clone());
+        prop_assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&expr).unwrap()
+        );
+        prop_assert_eq!(decoded, expr);
+    }
+
+    #[test]
+    fn test_schema_round_trip(value in schema()) {
+        let schema: Schema = serde_json::from_value(value).unwrap();
+        let decoded = round_trip::<Schema, proto::Schema>(schema.clone());
+        prop_assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&schema).unwrap()
+        );
+        prop_assert_eq!(decoded, schema);
+    }
+}
@@ -1,17 +1,34 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use cedrus_cedar::{
     Entity, EntityUid, Policy, PolicyId, PolicySet, Schema, Template, TemplateLink,
 };
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet, mapref::entry::Entry};
 use uuid::Uuid;
 
 use crate::core::{
-    IdentitySource,
+    DashMapCacheConfig, IdentitySource,
     project::{ApiKey, Project},
 };
 
-use super::{Cache, CacheError};
+use super::{Cache, CacheError, CacheLock, CacheUsage, CompiledEntities};
+
+/// Share of `max_size` an eviction frees the Cache down to when no target is configured.
+const DEFAULT_EVICTION_TARGET_PERCENT: usize = 90;
+
+/// Approximate bytes held by the entities of a project, the unit of eviction.
+#[derive(Debug, Default)]
+struct ProjectUsage {
+    size: usize,
+    last_access: u64,
+}
 
 pub struct DashMapCache {
     projects: DashMap<Uuid, Project>,
@@ -19,9 +36,25 @@ pub struct DashMapCache {
     identity_sources: DashMap<Uuid, IdentitySource>,
     schemas: DashMap<Uuid, Schema>,
     entities: DashMap<(Uuid, EntityUid), Entity>,
+    compiled_entities: DashMap<Uuid, CompiledEntities>,
     policies: DashMap<(Uuid, PolicyId), Policy>,
     templates: DashMap<(Uuid, PolicyId), Template>,
     template_links: DashMap<(Uuid, PolicyId), TemplateLink>,
+    versions: DashMap<Uuid, u64>,
+    max_size: Option<usize>,
+    eviction_target_size: usize,
+    usage: DashMap<Uuid, ProjectUsage>,
+    size: AtomicUsize,
+    clock: AtomicU64,
+    evicted: DashSet<Uuid>,
+    /// Bumped by every write to the entities of a project and by their eviction, its entry
+    /// held while they are written so a stale replacement can't slip in between
+    generations: DashMap<Uuid, u64>,
+    /// Projects evicted since the last `take_evictions`
+    pending_evictions: DashSet<Uuid>,
+    evictions: AtomicU64,
+    /// Holder and expiry of the policy set lock of each project
+    locks: Arc<DashMap<Uuid, (Uuid, Instant)>>,
 }
 
 impl Default for DashMapCache {
@@ -32,15 +65,186 @@ impl Default for DashMapCache {
 
 impl DashMapCache {
     pub fn new() -> Self {
+        Self::with_config(&DashMapCacheConfig::default())
+    }
+
+    pub fn with_config(conf: &DashMapCacheConfig) -> Self {
+        let eviction_target_size = match (conf.max_size, conf.eviction_target_size) {
+            (Some(max_size), Some(target_size)) => target_size.min(max_size),
+            (Some(max_size), None) => max_size / 100 * DEFAULT_EVICTION_TARGET_PERCENT,
+            (None, _) => 0,
+        };
+
         Self {
             projects: DashMap::new(),
             apikeys: DashMap::new(),
             identity_sources: DashMap::new(),
             schemas: DashMap::new(),
             entities: DashMap::new(),
+            compiled_entities: DashMap::new(),
             policies: DashMap::new(),
             templates: DashMap::new(),
             template_links: DashMap::new(),
+            versions: DashMap::new(),
+            max_size: conf.max_size,
+            eviction_target_size,
+            usage: DashMap::new(),
+            size: AtomicUsize::new(0),
+            clock: AtomicU64::new(0),
+            evicted: DashSet::new(),
+            generations: DashMap::new(),
+            pending_evictions: DashSet::new(),
+            evictions: AtomicU64::new(0),
+            locks: Arc::new(DashMap::new()),
+        }
+    }
+
+    fn entity_size(entity: &Entity) -> usize {
+        serde_json::to_vec(entity)
+            .map(|data| data.len())
+            .unwrap_or_default()
+    }
+
+    fn compiled_size(compiled: &CompiledEntities) -> usize {
+        compiled.fingerprint.len() + compiled.data.len()
+    }
+
+    // Advances the version of the project, once a change to its schema, entities or policies
+    // is written
+    fn advance(&self, project_id: &Uuid) {
+        *self.versions.entry(*project_id).or_default() += 1;
+    }
+
+    // Takes the lock under `key` until its holder drops it, renewed meanwhile
+    fn lock(&self, key: Uuid, ttl: Duration) -> Option<CacheLock> {
+        let token = Uuid::now_v7();
+        let now = Instant::now();
+        match self.locks.entry(key) {
+            Entry::Occupied(entry) if entry.get().1 > now => return None,
+            Entry::Occupied(mut entry) => {
+                entry.insert((token, now + ttl));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((token, now + ttl));
+            }
+        }
+
+        let locks = self.locks.clone();
+        let renew = move || {
+            let held = match locks.get_mut(&key) {
+                Some(mut lock) if lock.0 == token => {
+                    lock.1 = Instant::now() + ttl;
+                    true
+                }
+                _ => false,
+            };
+            std::future::ready(held)
+        };
+        let locks = self.locks.clone();
+        Some(CacheLock::renewed(ttl, renew, move || {
+            locks.remove_if(&key, |_, (holder, _)| *holder == token);
+        }))
+    }
+
+    // Marks the project as the most recently used
+    fn touch(&self, project_id: &Uuid) {
+        let tick = self.clock.fetch_add(1, Ordering::Relaxed);
+        self.usage.entry(*project_id).or_default().last_access = tick;
+    }
+
+    fn account(&self, project_id: &Uuid, added: usize, removed: usize) {
+        if added == removed {
+            return;
+        }
+        let mut usage = self.usage.entry(*project_id).or_default();
+        usage.size = (usage.size + added).saturating_sub(removed);
+        drop(usage);
+
+        if added > removed {
+            self.size.fetch_add(added - removed, Ordering::Relaxed);
+        } else {
+            let _ = self
+                .size
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |size| {
+                    Some(size.saturating_sub(removed - added))
+                });
+        }
+    }
+
+    // Drops the accounting of a project, returning the bytes it held
+    fn forget(&self, project_id: &Uuid) -> usize {
+        let Some((_, usage)) = self.usage.remove(project_id) else {
+            return 0;
+        };
+        let _ = self
+            .size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |size| {
+                Some(size.saturating_sub(usage.size))
+            });
+        usage.size
+    }
+
+    fn remove_project_entities(&self, project_id: &Uuid) {
+        self.entities.retain(|(pid, _), _| pid != project_id);
+        self.compiled_entities.remove(project_id);
+        self.forget(project_id);
+    }
+
+    // Inserts entities of a project, returning the bytes added and those they replaced
+    fn store_entities(&self, project_id: &Uuid, entities: &[Entity]) -> (usize, usize) {
+        let (mut added, mut removed) = (0, 0);
+        for entity in entities {
+            added += Self::entity_size(entity);
+            if let Some(old) = self
+                .entities
+                .insert((*project_id, entity.uid().clone()), entity.clone())
+            {
+                removed += Self::entity_size(&old);
+            }
+        }
+        (added, removed)
+    }
+
+    fn evict_project(&self, project_id: &Uuid) {
+        *self.generations.entry(*project_id).or_default() += 1;
+        self.evicted.insert(*project_id);
+        self.pending_evictions.insert(*project_id);
+        self.remove_project_entities(project_id);
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+        tracing::info!(
+            "dashmap cache: evicted the entities of project {}",
+            project_id
+        );
+    }
+
+    /// Evicts the entities of the least recently used projects while the Cache holds more
+    /// than its maximum size, `keep`, the project just written to, last: only when it alone
+    /// is over the maximum size.
+    fn evict(&self, keep: &Uuid) {
+        let Some(max_size) = self.max_size else {
+            return;
+        };
+        if self.size.load(Ordering::Relaxed) <= max_size {
+            return;
+        }
+
+        let mut candidates = self
+            .usage
+            .iter()
+            .filter(|r| r.key() != keep)
+            .map(|r| (*r.key(), r.value().last_access))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(_, last_access)| *last_access);
+
+        for (project_id, _) in candidates {
+            if self.size.load(Ordering::Relaxed) <= self.eviction_target_size {
+                break;
+            }
+            self.evict_project(&project_id);
+        }
+
+        if self.size.load(Ordering::Relaxed) > max_size && self.usage.contains_key(keep) {
+            self.evict_project(keep);
         }
     }
 }
@@ -65,7 +269,10 @@ impl Cache for DashMapCache {
         self.apikeys.retain(|(pid, _), _| pid != project_id);
         self.identity_sources.remove(project_id);
         self.schemas.remove(project_id);
-        self.entities.retain(|(pid, _), _| pid != project_id);
+        *self.generations.entry(*project_id).or_default() += 1;
+        self.remove_project_entities(project_id);
+        self.evicted.remove(project_id);
+        self.pending_evictions.remove(project_id);
         self.policies.retain(|(pid, _), _| pid != project_id);
         self.templates.retain(|(pid, _), _| pid != project_id);
         self.template_links.retain(|(pid, _), _| pid != project_id);
@@ -139,11 +346,13 @@ impl Cache for DashMapCache {
         schema: &Schema,
     ) -> Result<(), CacheError> {
         self.schemas.insert(*project_id, schema.clone());
+        self.advance(project_id);
         Ok(())
     }
 
     async fn project_del_schema(&self, project_id: &Uuid) -> Result<(), CacheError> {
         self.schemas.remove(project_id);
+        self.advance(project_id);
         Ok(())
     }
 
@@ -152,6 +361,11 @@ impl Cache for DashMapCache {
         project_id: &Uuid,
         entity_uids: &[EntityUid],
     ) -> Result<Vec<Entity>, CacheError> {
+        if self.evicted.contains(project_id) {
+            return Err(CacheError::Evicted);
+        }
+        self.touch(project_id);
+
         if entity_uids.is_empty() {
             Ok(self
                 .entities
@@ -176,10 +390,20 @@ impl Cache for DashMapCache {
         project_id: &Uuid,
         entities: &[Entity],
     ) -> Result<(), CacheError> {
-        for entity in entities {
-            self.entities
-                .insert((*project_id, entity.uid().clone()), entity.clone());
+        let mut generation = self.generations.entry(*project_id).or_default();
+        *generation += 1;
+        // Partial entities are not kept, the project is restored whole on its next read
+        if self.evicted.contains(project_id) {
+            return Ok(());
         }
+
+        let (added, removed) = self.store_entities(project_id, entities);
+        drop(generation);
+        self.account(project_id, added, removed);
+        self.touch(project_id);
+        self.evict(project_id);
+
+        self.advance(project_id);
         Ok(())
     }
 
@@ -188,9 +412,80 @@ impl Cache for DashMapCache {
         project_id: &Uuid,
         entity_uids: &[EntityUid],
     ) -> Result<(), CacheError> {
+        let mut generation = self.generations.entry(*project_id).or_default();
+        *generation += 1;
+        let mut removed = 0;
         for uid in entity_uids {
-            self.entities.remove(&(*project_id, uid.clone()));
+            if let Some((_, old)) = self.entities.remove(&(*project_id, uid.clone())) {
+                removed += Self::entity_size(&old);
+            }
         }
+        drop(generation);
+        self.account(project_id, 0, removed);
+
+        self.advance(project_id);
+        Ok(())
+    }
+
+    async fn project_replace_entities(
+        &self,
+        project_id: &Uuid,
+        entities: &[Entity],
+        generation: u64,
+    ) -> Result<bool, CacheError> {
+        let mut current = self.generations.entry(*project_id).or_default();
+        if *current != generation {
+            return Ok(false);
+        }
+        *current += 1;
+        self.remove_project_entities(project_id);
+        self.evicted.remove(project_id);
+        let (added, removed) = self.store_entities(project_id, entities);
+        drop(current);
+
+        self.account(project_id, added, removed);
+        self.touch(project_id);
+        self.evict(project_id);
+        self.advance(project_id);
+        Ok(true)
+    }
+
+    fn entities_generation(&self, project_id: &Uuid) -> u64 {
+        self.generations
+            .get(project_id)
+            .map_or(0, |generation| *generation)
+    }
+
+    async fn project_get_compiled_entities(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<CompiledEntities>, CacheError> {
+        if self.evicted.contains(project_id) {
+            return Ok(None);
+        }
+        Ok(self
+            .compiled_entities
+            .get(project_id)
+            .map(|r| r.value().clone()))
+    }
+
+    async fn project_set_compiled_entities(
+        &self,
+        project_id: &Uuid,
+        compiled: &CompiledEntities,
+    ) -> Result<(), CacheError> {
+        if self.evicted.contains(project_id) {
+            return Ok(());
+        }
+
+        let removed = self
+            .compiled_entities
+            .insert(*project_id, compiled.clone())
+            .map(|old| Self::compiled_size(&old))
+            .unwrap_or_default();
+        self.account(project_id, Self::compiled_size(compiled), removed);
+        self.evict(project_id);
+
         Ok(())
     }
 
@@ -215,6 +510,7 @@ impl Cache for DashMapCache {
             self.policies
                 .insert((*project_id, policy_id.clone()), policy.clone());
         }
+        self.advance(project_id);
         Ok(())
     }
 
@@ -226,6 +522,7 @@ impl Cache for DashMapCache {
         for policy_id in policy_ids {
             self.policies.remove(&(*project_id, policy_id.clone()));
         }
+        self.advance(project_id);
         Ok(())
     }
 
@@ -250,6 +547,7 @@ impl Cache for DashMapCache {
             self.templates
                 .insert((*project_id, policy_id.clone()), template.clone());
         }
+        self.advance(project_id);
         Ok(())
     }
 
@@ -261,6 +559,7 @@ impl Cache for DashMapCache {
         for policy_id in policy_ids {
             self.templates.remove(&(*project_id, policy_id.clone()));
         }
+        self.advance(project_id);
         Ok(())
     }
 
@@ -285,6 +584,7 @@ impl Cache for DashMapCache {
             self.template_links
                 .insert((*project_id, link.new_id.clone()), link.clone());
         }
+        self.advance(project_id);
         Ok(())
     }
 
@@ -297,6 +597,7 @@ impl Cache for DashMapCache {
             self.template_links
                 .remove(&(*project_id, policy_id.clone()));
         }
+        self.advance(project_id);
         Ok(())
     }
 
@@ -325,4 +626,199 @@ impl Cache for DashMapCache {
             .await?;
         Ok(())
     }
+
+    async fn project_get_version(&self, project_id: &Uuid) -> Result<u64, CacheError> {
+        Ok(self
+            .versions
+            .get(project_id)
+            .map(|r| *r.value())
+            .unwrap_or_default())
+    }
+
+    async fn project_lock_policy_set(
+        &self,
+        project_id: &Uuid,
+        ttl: Duration,
+    ) -> Result<Option<CacheLock>, CacheError> {
+        Ok(self.lock(*project_id, ttl))
+    }
+
+    async fn lock_entity_expiry(&self, ttl: Duration) -> Result<Option<CacheLock>, CacheError> {
+        // Keyed apart from every project
+        Ok(self.lock(Uuid::max(), ttl))
+    }
+
+    async fn lock_job_recovery(&self, ttl: Duration) -> Result<Option<CacheLock>, CacheError> {
+        Ok(self.lock(Uuid::from_u128(u128::MAX - 1), ttl))
+    }
+
+    fn usage(&self) -> Option<CacheUsage> {
+        Some(CacheUsage {
+            size: self.size.load(Ordering::Relaxed),
+            evicted_projects: self.evicted.len(),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        })
+    }
+
+    fn take_evictions(&self) -> Vec<Uuid> {
+        let project_ids: Vec<Uuid> = self.pending_evictions.iter().map(|r| *r).collect();
+        for project_id in &project_ids {
+            self.pending_evictions.remove(project_id);
+        }
+        project_ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_policy_set_lock() {
+        let cache = DashMapCache::default();
+        let project_id = Uuid::now_v7();
+        let ttl = Duration::from_secs(30);
+
+        let lock = cache
+            .project_lock_policy_set(&project_id, ttl)
+            .await
+            .unwrap();
+        assert!(lock.is_some());
+        assert!(
+            cache
+                .project_lock_policy_set(&project_id, ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        drop(lock);
+        assert!(
+            cache
+                .project_lock_policy_set(&project_id, ttl)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // An expired lock is taken over, and releasing it no longer frees the new holder
+        let expired = cache
+            .project_lock_policy_set(&Uuid::nil(), Duration::ZERO)
+            .await
+            .unwrap();
+        let held = cache
+            .project_lock_policy_set(&Uuid::nil(), ttl)
+            .await
+            .unwrap();
+        assert!(held.is_some());
+        drop(expired);
+        assert!(
+            cache
+                .project_lock_policy_set(&Uuid::nil(), ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_policy_set_lock_renewal() {
+        let cache = DashMapCache::default();
+        let project_id = Uuid::now_v7();
+        let ttl = Duration::from_millis(90);
+
+        // Held past its time to live, the lock is renewed rather than taken over
+        let lock = cache
+            .project_lock_policy_set(&project_id, ttl)
+            .await
+            .unwrap();
+        assert!(lock.is_some());
+        tokio::time::sleep(ttl * 3).await;
+        assert!(
+            cache
+                .project_lock_policy_set(&project_id, ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        drop(lock);
+        assert!(
+            cache
+                .project_lock_policy_set(&project_id, ttl)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    fn users(project: &str, n: usize) -> Vec<Entity> {
+        (0..n)
+            .map(|i| {
+                let uid = EntityUid::from(format!("App::User::{project}{i}").as_str());
+                Entity::new_no_attrs(uid, Default::default())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stale_replacement() {
+        let cache = DashMapCache::with_config(&DashMapCacheConfig {
+            max_size: Some(1),
+            eviction_target_size: None,
+        });
+        let project_id = Uuid::now_v7();
+
+        // Alone over the maximum size, the project written to is evicted too
+        cache
+            .project_set_entities(&project_id, &users("a", 2))
+            .await
+            .unwrap();
+        assert!(matches!(
+            cache.project_get_entities(&project_id, &[]).await,
+            Err(CacheError::Evicted)
+        ));
+        assert_eq!(cache.usage().unwrap().size, 0);
+
+        // A write while the entities are reloaded discards the stale replacement
+        let cache = DashMapCache::default();
+        cache
+            .project_set_entities(&project_id, &users("a", 2))
+            .await
+            .unwrap();
+        let generation = cache.entities_generation(&project_id);
+        cache
+            .project_set_entities(&project_id, &users("b", 1))
+            .await
+            .unwrap();
+        assert!(
+            !cache
+                .project_replace_entities(&project_id, &users("a", 2), generation)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            cache
+                .project_get_entities(&project_id, &[])
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+
+        let generation = cache.entities_generation(&project_id);
+        assert!(
+            cache
+                .project_replace_entities(&project_id, &users("c", 1), generation)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            cache
+                .project_get_entities(&project_id, &[])
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
 }
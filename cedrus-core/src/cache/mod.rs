@@ -1,4 +1,4 @@
-use std::{collections::HashMap, error::Error};
+use std::{collections::HashMap, error::Error, time::Duration};
 
 use cedrus_cedar::{
     Entity, EntityUid, Policy, PolicyId, PolicySet, Schema, Template, TemplateLink,
@@ -12,15 +12,18 @@ use crate::core::{
 };
 
 pub mod dashmap;
+pub mod regional;
 pub mod valkey;
 
 #[derive(Debug)]
 pub enum CacheError {
     Connection,
     NotFound,
+    Evicted,
     DecodeError(String),
     JsonError(String),
     RedisError(RedisError),
+    Config(String),
 }
 
 impl std::fmt::Display for CacheError {
@@ -28,9 +31,11 @@ impl std::fmt::Display for CacheError {
         match self {
             CacheError::Connection => write!(f, "Connection error"),
             CacheError::NotFound => write!(f, "Not found"),
+            CacheError::Evicted => write!(f, "Evicted"),
             CacheError::DecodeError(err) => write!(f, "Decode error: {}", err),
             CacheError::JsonError(err) => write!(f, "Json error: {}", err),
             CacheError::RedisError(err) => write!(f, "Redis error: {}", err),
+            CacheError::Config(err) => write!(f, "Configuration error: {}", err),
         }
     }
 }
@@ -43,6 +48,79 @@ impl From<RedisError> for CacheError {
     }
 }
 
+/// Cedar entities of a project already validated against its schema, encoded with Cedar's
+/// protobuf representation. `fingerprint` identifies the entities and schema they were built
+/// from, so a stale encoding is never reused.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompiledEntities {
+    pub fingerprint: String,
+    pub data: Vec<u8>,
+}
+
+/// Lock taken through the Cache, released when dropped, or once its time to live ends when
+/// its node stops while holding it.
+pub struct CacheLock {
+    release: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl CacheLock {
+    pub fn new(release: impl FnOnce() + Send + Sync + 'static) -> Self {
+        Self {
+            release: Some(Box::new(release)),
+        }
+    }
+
+    /// Lock whose time to live is renewed every third of `ttl` while it is held, so a holder
+    /// slower than `ttl` keeps it, by `renew` returning whether the lock was still held.
+    pub fn renewed<F, R>(
+        ttl: Duration,
+        renew: F,
+        release: impl FnOnce() + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Fn() -> R + Send + 'static,
+        R: Future<Output = bool> + Send,
+    {
+        let (stop, mut stopped) = tokio::sync::oneshot::channel::<()>();
+        if let (false, Ok(runtime)) = (ttl.is_zero(), tokio::runtime::Handle::try_current()) {
+            runtime.spawn(async move {
+                loop {
+                    tokio::select! {
+                        _ = &mut stopped => return,
+                        _ = tokio::time::sleep(ttl / 3) => {
+                            if !renew().await {
+                                tracing::warn!("cache: lock lost before it was released");
+                                return;
+                            }
+                        }
+                    }
+                }
+            });
+        }
+
+        Self::new(move || {
+            let _ = stop.send(());
+            release();
+        })
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        if let Some(release) = self.release.take() {
+            release();
+        }
+    }
+}
+
+/// Approximate memory held by an in-memory Cache, and the evictions done to bound it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub size: usize,
+    pub evicted_projects: usize,
+    pub evictions: u64,
+}
+
 #[async_trait::async_trait]
 pub trait Cache: Send + Sync {
     async fn projects_get(&self) -> Result<Vec<Project>, CacheError>;
@@ -96,6 +174,30 @@ pub trait Cache: Send + Sync {
         project_id: &Uuid,
         entity_uids: &[EntityUid],
     ) -> Result<(), CacheError>;
+    /// Replaces every entity of a project, restoring a project whose entities were evicted,
+    /// unless they were written to or evicted since `generation`, as the entities loaded
+    /// before would be stale. Returns whether they were stored.
+    async fn project_replace_entities(
+        &self,
+        project_id: &Uuid,
+        entities: &[Entity],
+        generation: u64,
+    ) -> Result<bool, CacheError>;
+    /// Generation of the entities of a project, changed by every write to them and by their
+    /// eviction, read before loading the entities a replacement restores.
+    fn entities_generation(&self, _project_id: &Uuid) -> u64 {
+        0
+    }
+
+    async fn project_get_compiled_entities(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<CompiledEntities>, CacheError>;
+    async fn project_set_compiled_entities(
+        &self,
+        project_id: &Uuid,
+        compiled: &CompiledEntities,
+    ) -> Result<(), CacheError>;
 
     async fn project_get_policies(
         &self,
@@ -148,6 +250,37 @@ pub trait Cache: Send + Sync {
         project_id: &Uuid,
         policy_set: &PolicySet,
     ) -> Result<(), CacheError>;
+
+    /// Version of the schema, entities and policies of a project, advanced by every change
+    /// to them and never reset, `0` before the first one.
+    async fn project_get_version(&self, project_id: &Uuid) -> Result<u64, CacheError>;
+
+    /// Takes the lock of the policy set of a project for at most `ttl`, `None` while another
+    /// node, or another task of this one, holds it.
+    async fn project_lock_policy_set(
+        &self,
+        project_id: &Uuid,
+        ttl: Duration,
+    ) -> Result<Option<CacheLock>, CacheError>;
+
+    /// Takes the lock of the entity expiry sweep for at most `ttl`, so a single node sweeps
+    /// at a time, `None` while another one holds it.
+    async fn lock_entity_expiry(&self, ttl: Duration) -> Result<Option<CacheLock>, CacheError>;
+
+    /// Takes the lock of the recovery of the jobs whose lease expired for at most `ttl`, so a
+    /// single node takes each of them over, `None` while another one holds it.
+    async fn lock_job_recovery(&self, ttl: Duration) -> Result<Option<CacheLock>, CacheError>;
+
+    /// Memory accounting of the Cache, when it holds its entries in this process.
+    fn usage(&self) -> Option<CacheUsage> {
+        None
+    }
+
+    /// Projects whose entities were evicted since the last call, for the compiled structures
+    /// built from them to be dropped too.
+    fn take_evictions(&self) -> Vec<Uuid> {
+        Vec::new()
+    }
 }
 
 pub async fn cache_factory(
@@ -157,7 +290,9 @@ pub async fn cache_factory(
         crate::core::CacheConfig::ValKeyConfig(conf) => {
             Box::new(valkey::ValKeyCache::new(conf).await?)
         }
-        crate::core::CacheConfig::DashMapConfig(_) => Box::new(dashmap::DashMapCache::new()),
+        crate::core::CacheConfig::DashMapConfig(conf) => {
+            Box::new(dashmap::DashMapCache::with_config(conf))
+        }
     };
     Ok(cache)
 }
@@ -0,0 +1,503 @@
+use std::{collections::HashMap, time::Duration};
+
+use cedrus_cedar::{
+    Entity, EntityUid, Policy, PolicyId, PolicySet, Schema, Template, TemplateLink,
+};
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::core::{
+    IdentitySource,
+    project::{ApiKey, Project},
+};
+
+use super::{Cache, CacheError, CacheLock, CacheUsage, CompiledEntities};
+
+/// Cache keeping the data of each project in the Cache of its `region`, as `RegionalDb` does
+/// for the database, while the projects themselves and the entity expiry lock stay in the
+/// default Cache shared by every region. Projects without a region, the admin project
+/// included, are entirely in the default Cache.
+pub struct RegionalCache {
+    cache: Box<dyn Cache + Send + Sync>,
+    regions: HashMap<String, Box<dyn Cache + Send + Sync>>,
+    /// Region of each project cached so far, set on creation and never changed
+    project_regions: DashMap<Uuid, Option<String>>,
+}
+
+impl RegionalCache {
+    pub fn new(
+        cache: Box<dyn Cache + Send + Sync>,
+        regions: HashMap<String, Box<dyn Cache + Send + Sync>>,
+    ) -> Self {
+        Self {
+            cache,
+            regions,
+            project_regions: DashMap::new(),
+        }
+    }
+
+    // Every backend, the default one first
+    fn backends(&self) -> impl Iterator<Item = &(dyn Cache + Send + Sync)> {
+        std::iter::once(self.cache.as_ref())
+            .chain(self.regions.values().map(|cache| cache.as_ref()))
+    }
+
+    fn region_cache(
+        &self,
+        region: Option<&String>,
+    ) -> Result<&(dyn Cache + Send + Sync), CacheError> {
+        match region {
+            Some(region) => self
+                .regions
+                .get(region)
+                .map(|cache| cache.as_ref())
+                .ok_or_else(|| CacheError::Config(format!("region {}", region))),
+            None => Ok(self.cache.as_ref()),
+        }
+    }
+
+    // Backend of the data of a project, the default one until the project is cached
+    async fn project_cache(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<&(dyn Cache + Send + Sync), CacheError> {
+        if project_id.is_nil() {
+            return Ok(self.cache.as_ref());
+        }
+        let region = match self.project_regions.get(project_id) {
+            Some(region) => region.clone(),
+            None => match self.cache.project_get(project_id).await? {
+                Some(project) => {
+                    self.project_regions
+                        .insert(*project_id, project.region.clone());
+                    project.region
+                }
+                None => None,
+            },
+        };
+        self.region_cache(region.as_ref())
+    }
+}
+
+#[async_trait::async_trait]
+impl Cache for RegionalCache {
+    async fn projects_get(&self) -> Result<Vec<Project>, CacheError> {
+        self.cache.projects_get().await
+    }
+
+    async fn project_get(&self, project_id: &Uuid) -> Result<Option<Project>, CacheError> {
+        self.cache.project_get(project_id).await
+    }
+
+    async fn project_set(&self, project: &Project) -> Result<(), CacheError> {
+        // Checked before the project is cached, so its data never falls back to the default
+        self.region_cache(project.region.as_ref())?;
+        self.cache.project_set(project).await?;
+        self.project_regions
+            .insert(project.id, project.region.clone());
+        Ok(())
+    }
+
+    async fn project_del(&self, project_id: &Uuid) -> Result<(), CacheError> {
+        let cache = self.project_cache(project_id).await?;
+        if !std::ptr::addr_eq(cache, self.cache.as_ref()) {
+            cache.project_del(project_id).await?;
+        }
+        self.cache.project_del(project_id).await
+    }
+
+    async fn project_get_apikeys(&self, project_id: &Uuid) -> Result<Vec<ApiKey>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_apikeys(project_id)
+            .await
+    }
+
+    async fn project_set_apikeys(
+        &self,
+        project_id: &Uuid,
+        apikeys: &Vec<ApiKey>,
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_apikeys(project_id, apikeys)
+            .await
+    }
+
+    async fn project_del_apikeys(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<Uuid>,
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_del_apikeys(project_id, ids)
+            .await
+    }
+
+    async fn project_get_identity_source(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<IdentitySource>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_identity_source(project_id)
+            .await
+    }
+
+    async fn project_set_identity_source(
+        &self,
+        project_id: &Uuid,
+        identity_source: &IdentitySource,
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_identity_source(project_id, identity_source)
+            .await
+    }
+
+    async fn project_del_identity_source(&self, project_id: &Uuid) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_del_identity_source(project_id)
+            .await
+    }
+
+    async fn project_get_schema(&self, project_id: &Uuid) -> Result<Option<Schema>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_schema(project_id)
+            .await
+    }
+
+    async fn project_set_schema(
+        &self,
+        project_id: &Uuid,
+        schema: &Schema,
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_schema(project_id, schema)
+            .await
+    }
+
+    async fn project_del_schema(&self, project_id: &Uuid) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_del_schema(project_id)
+            .await
+    }
+
+    async fn project_get_entities(
+        &self,
+        project_id: &Uuid,
+        entity_uids: &[EntityUid],
+    ) -> Result<Vec<Entity>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_entities(project_id, entity_uids)
+            .await
+    }
+
+    async fn project_set_entities(
+        &self,
+        project_id: &Uuid,
+        entities: &[Entity],
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_entities(project_id, entities)
+            .await
+    }
+
+    async fn project_del_entities(
+        &self,
+        project_id: &Uuid,
+        entity_uids: &[EntityUid],
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_del_entities(project_id, entity_uids)
+            .await
+    }
+
+    async fn project_replace_entities(
+        &self,
+        project_id: &Uuid,
+        entities: &[Entity],
+        generation: u64,
+    ) -> Result<bool, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_replace_entities(project_id, entities, generation)
+            .await
+    }
+
+    fn entities_generation(&self, project_id: &Uuid) -> u64 {
+        // Read before a load, by which the project is cached and its region known
+        let region = self
+            .project_regions
+            .get(project_id)
+            .and_then(|region| region.clone());
+        match self.region_cache(region.as_ref()) {
+            Ok(cache) => cache.entities_generation(project_id),
+            Err(_) => 0,
+        }
+    }
+
+    async fn project_get_compiled_entities(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<CompiledEntities>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_compiled_entities(project_id)
+            .await
+    }
+
+    async fn project_set_compiled_entities(
+        &self,
+        project_id: &Uuid,
+        compiled: &CompiledEntities,
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_compiled_entities(project_id, compiled)
+            .await
+    }
+
+    async fn project_get_policies(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<HashMap<PolicyId, Policy>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_policies(project_id)
+            .await
+    }
+
+    async fn project_set_policies(
+        &self,
+        project_id: &Uuid,
+        policies: &HashMap<PolicyId, Policy>,
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_policies(project_id, policies)
+            .await
+    }
+
+    async fn project_del_policies(
+        &self,
+        project_id: &Uuid,
+        policy_ids: &[PolicyId],
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_del_policies(project_id, policy_ids)
+            .await
+    }
+
+    async fn project_get_templates(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<HashMap<PolicyId, Template>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_templates(project_id)
+            .await
+    }
+
+    async fn project_set_templates(
+        &self,
+        project_id: &Uuid,
+        templates: &HashMap<PolicyId, Template>,
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_templates(project_id, templates)
+            .await
+    }
+
+    async fn project_del_templates(
+        &self,
+        project_id: &Uuid,
+        policy_ids: &[PolicyId],
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_del_templates(project_id, policy_ids)
+            .await
+    }
+
+    async fn project_get_template_links(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Vec<TemplateLink>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_template_links(project_id)
+            .await
+    }
+
+    async fn project_set_template_links(
+        &self,
+        project_id: &Uuid,
+        template_links: &[TemplateLink],
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_template_links(project_id, template_links)
+            .await
+    }
+
+    async fn project_del_template_links(
+        &self,
+        project_id: &Uuid,
+        policy_ids: &[PolicyId],
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_del_template_links(project_id, policy_ids)
+            .await
+    }
+
+    async fn project_get_policy_set(&self, project_id: &Uuid) -> Result<PolicySet, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_policy_set(project_id)
+            .await
+    }
+
+    async fn project_set_policy_set(
+        &self,
+        project_id: &Uuid,
+        policy_set: &PolicySet,
+    ) -> Result<(), CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_set_policy_set(project_id, policy_set)
+            .await
+    }
+
+    async fn project_get_version(&self, project_id: &Uuid) -> Result<u64, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_get_version(project_id)
+            .await
+    }
+
+    async fn project_lock_policy_set(
+        &self,
+        project_id: &Uuid,
+        ttl: Duration,
+    ) -> Result<Option<CacheLock>, CacheError> {
+        self.project_cache(project_id)
+            .await?
+            .project_lock_policy_set(project_id, ttl)
+            .await
+    }
+
+    async fn lock_entity_expiry(&self, ttl: Duration) -> Result<Option<CacheLock>, CacheError> {
+        self.cache.lock_entity_expiry(ttl).await
+    }
+
+    async fn lock_job_recovery(&self, ttl: Duration) -> Result<Option<CacheLock>, CacheError> {
+        self.cache.lock_job_recovery(ttl).await
+    }
+
+    fn usage(&self) -> Option<CacheUsage> {
+        self.backends()
+            .filter_map(|cache| cache.usage())
+            .reduce(|total, usage| CacheUsage {
+                size: total.size + usage.size,
+                evicted_projects: total.evicted_projects + usage.evicted_projects,
+                evictions: total.evictions + usage.evictions,
+            })
+    }
+
+    fn take_evictions(&self) -> Vec<Uuid> {
+        self.backends()
+            .flat_map(|cache| cache.take_evictions())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::dashmap::DashMapCache;
+
+    use super::*;
+
+    fn project(region: Option<&str>) -> Project {
+        let mut project = Project::new(
+            Uuid::now_v7(),
+            "project".to_string(),
+            EntityUid::from("App::User::owner"),
+        );
+        project.region = region.map(|region| region.to_string());
+        project
+    }
+
+    #[tokio::test]
+    async fn test_regional_cache() {
+        let cache = RegionalCache::new(
+            Box::new(DashMapCache::new()),
+            HashMap::from([(
+                "eu".to_string(),
+                Box::new(DashMapCache::new()) as Box<dyn Cache + Send + Sync>,
+            )]),
+        );
+        let global = project(None);
+        let eu = project(Some("eu"));
+        cache.project_set(&global).await.unwrap();
+        cache.project_set(&eu).await.unwrap();
+
+        let schema = Schema::default();
+        cache.project_set_schema(&global.id, &schema).await.unwrap();
+        cache.project_set_schema(&eu.id, &schema).await.unwrap();
+
+        // The projects stay in the default Cache, the data of a regional one in its region
+        assert_eq!(cache.cache.projects_get().await.unwrap().len(), 2);
+        assert!(cache.regions["eu"].projects_get().await.unwrap().is_empty());
+        assert!(
+            cache
+                .cache
+                .project_get_schema(&global.id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            cache
+                .cache
+                .project_get_schema(&eu.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            cache.regions["eu"]
+                .project_get_schema(&eu.id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // A project of an unknown region is refused rather than cached in the default one
+        assert!(matches!(
+            cache.project_set(&project(Some("us"))).await,
+            Err(CacheError::Config(_))
+        ));
+
+        cache.project_del(&eu.id).await.unwrap();
+        assert!(cache.project_get(&eu.id).await.unwrap().is_none());
+        assert!(
+            cache.regions["eu"]
+                .project_get_schema(&eu.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
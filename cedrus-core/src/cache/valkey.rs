@@ -1,4 +1,7 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use base64::{Engine, prelude::BASE64_STANDARD};
 use cedrus_cedar::{
@@ -15,8 +18,9 @@ use crate::core::{
     project::{ApiKey, Project},
 };
 
-use super::{Cache, CacheError};
+use super::{Cache, CacheError, CacheLock, CompiledEntities};
 
+#[derive(Clone)]
 pub enum CacheConnectionType {
     Multiplexed(MultiplexedConnection),
     Cluster(ClusterConnection),
@@ -140,6 +144,108 @@ impl CacheConnectionType {
         }
     }
 
+    /// Sets the key unless it exists, expiring after `ttl`, returning whether it was set.
+    pub async fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, RedisError> {
+        let cmd = redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .to_owned();
+        let set: Option<String> = match self {
+            CacheConnectionType::Multiplexed(conn) => cmd.query_async(&mut conn.clone()).await?,
+            CacheConnectionType::Cluster(conn) => cmd.query_async(&mut conn.clone()).await?,
+        };
+        Ok(set.is_some())
+    }
+
+    /// Deletes the key only while it still holds `value`.
+    pub async fn del_if_eq(&self, key: &str, value: &str) -> Result<(), RedisError> {
+        let script = redis::Script::new(
+            r"if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) end return 0",
+        );
+        let mut invocation = script.key(key);
+        invocation.arg(value);
+        let _: i64 = match self {
+            CacheConnectionType::Multiplexed(conn) => {
+                invocation.invoke_async(&mut conn.clone()).await?
+            }
+            CacheConnectionType::Cluster(conn) => {
+                invocation.invoke_async(&mut conn.clone()).await?
+            }
+        };
+        Ok(())
+    }
+
+    /// Serialized value of the key with its time to live in milliseconds, 0 when it does not
+    /// expire, `None` once the key is gone.
+    pub async fn dump(&self, key: &str) -> Result<Option<(Vec<u8>, u64)>, RedisError> {
+        let dump = redis::cmd("DUMP").arg(key).to_owned();
+        let pttl = redis::cmd("PTTL").arg(key).to_owned();
+        let (data, ttl): (Option<Vec<u8>>, i64) = match self {
+            CacheConnectionType::Multiplexed(conn) => {
+                let mut conn = conn.clone();
+                (
+                    dump.query_async(&mut conn).await?,
+                    pttl.query_async(&mut conn).await?,
+                )
+            }
+            CacheConnectionType::Cluster(conn) => {
+                let mut conn = conn.clone();
+                (
+                    dump.query_async(&mut conn).await?,
+                    pttl.query_async(&mut conn).await?,
+                )
+            }
+        };
+        // -2 when the key expired between both commands, -1 without expiry
+        Ok(match (data, ttl) {
+            (Some(data), ttl) if ttl != -2 => Some((data, ttl.max(0) as u64)),
+            _ => None,
+        })
+    }
+
+    /// Writes a value serialized by `dump` under the key, replacing it, expiring after `ttl`
+    /// milliseconds unless 0.
+    pub async fn restore(&self, key: &str, ttl: u64, data: &[u8]) -> Result<(), RedisError> {
+        let cmd = redis::cmd("RESTORE")
+            .arg(key)
+            .arg(ttl)
+            .arg(data)
+            .arg("REPLACE")
+            .to_owned();
+        let _: String = match self {
+            CacheConnectionType::Multiplexed(conn) => cmd.query_async(&mut conn.clone()).await?,
+            CacheConnectionType::Cluster(conn) => cmd.query_async(&mut conn.clone()).await?,
+        };
+        Ok(())
+    }
+
+    /// Extends the expiry of the key to `ttl` only while it still holds `value`, returning
+    /// whether it did.
+    pub async fn pexpire_if_eq(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: Duration,
+    ) -> Result<bool, RedisError> {
+        let script = redis::Script::new(
+            r"if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('PEXPIRE', KEYS[1], ARGV[2]) end return 0",
+        );
+        let mut invocation = script.key(key);
+        invocation.arg(value).arg(ttl.as_millis() as u64);
+        let renewed: i64 = match self {
+            CacheConnectionType::Multiplexed(conn) => {
+                invocation.invoke_async(&mut conn.clone()).await?
+            }
+            CacheConnectionType::Cluster(conn) => {
+                invocation.invoke_async(&mut conn.clone()).await?
+            }
+        };
+        Ok(renewed == 1)
+    }
+
     pub async fn incr(&self, key: &str, num: usize) -> Result<(), RedisError> {
         match self {
             CacheConnectionType::Multiplexed(conn) => {
@@ -154,65 +260,190 @@ impl CacheConnectionType {
     }
 }
 
+/// Keys of the entity expiry and job recovery locks, and prefix of the policy set lock keys,
+/// after the namespace.
+const ENTITY_EXPIRY_LOCK_KEY: &str = "c:eel";
+const JOB_RECOVERY_LOCK_KEY: &str = "c:jrl";
+const POLICY_SET_LOCK_PREFIX: &str = "c:psl:";
+
+/// Whether a key, without its namespace, is a lock, which is left to expire where its
+/// holder took it rather than migrated.
+fn is_lock_key(key: &str) -> bool {
+    key == ENTITY_EXPIRY_LOCK_KEY
+        || key == JOB_RECOVERY_LOCK_KEY
+        || key.starts_with(POLICY_SET_LOCK_PREFIX)
+}
+
+/// Prefix of the keys of `namespace`, empty without one. A namespace is rejected when it
+/// could be mistaken for a key segment or a scan pattern.
+pub fn namespace_prefix(namespace: Option<&str>) -> Result<String, CacheError> {
+    let Some(namespace) = namespace else {
+        return Ok(String::new());
+    };
+    if namespace.is_empty()
+        || namespace == "c"
+        || namespace
+            .chars()
+            .any(|c| matches!(c, ':' | '*' | '?' | '[' | ']' | '\\'))
+    {
+        return Err(CacheError::Config(format!(
+            "invalid cache namespace: {}",
+            namespace
+        )));
+    }
+
+    Ok(format!("{}:", namespace))
+}
+
 pub struct ValKeyCache {
     conn: CacheConnectionType,
+    prefix: String,
 }
 
 impl ValKeyCache {
     pub async fn new(conf: &core::ValKeyCacheConfig) -> Result<Self, CacheError> {
+        let prefix = namespace_prefix(conf.namespace.as_deref())?;
         let conn = CacheConnectionType::new(conf).await?;
 
-        Ok(Self { conn })
+        Ok(Self { conn, prefix })
+    }
+
+    /// Moves the keys of the `from` namespace, the keys without namespace when `None`, into
+    /// the namespace of this cache, returning the number of keys moved. Each key is dumped and
+    /// restored with its time to live, whatever its type and even across cluster slots, and
+    /// the locks are left behind.
+    pub async fn migrate_namespace(&self, from: Option<&str>) -> Result<usize, CacheError> {
+        let from_prefix = namespace_prefix(from)?;
+        if from_prefix == self.prefix {
+            return Ok(0);
+        }
+
+        let keys = self
+            .keys_from_pattern(&format!("{}c:*", from_prefix))
+            .await?;
+        let mut moved = 0;
+        for key in keys {
+            let Some(suffix) = key.strip_prefix(&from_prefix) else {
+                continue;
+            };
+            if is_lock_key(suffix) {
+                continue;
+            }
+            let Some((data, ttl)) = self.conn.dump(&key).await? else {
+                continue;
+            };
+            self.conn
+                .restore(&format!("{}{}", self.prefix, suffix), ttl, &data)
+                .await?;
+            self.conn.del(&vec![key.clone()]).await?;
+            moved += 1;
+        }
+
+        Ok(moved)
     }
 
     fn apikeys_pattern(&self, project_id: &Uuid) -> String {
-        format!("c:pak:{}:*", project_id)
+        format!("{}c:pak:{}:*", self.prefix, project_id)
     }
     fn apikeys_key(&self, project_id: &Uuid, apikey_id: &Uuid) -> String {
-        format!("c:pak:{}:{}", project_id, apikey_id)
+        format!("{}c:pak:{}:{}", self.prefix, project_id, apikey_id)
     }
 
     fn project_identity_source_key(&self, project_id: &Uuid) -> String {
-        format!("c:pis:{}", project_id)
+        format!("{}c:pis:{}", self.prefix, project_id)
     }
 
     fn project_schema_key(&self, project_id: &Uuid) -> String {
-        format!("c:ps:{}", project_id)
+        format!("{}c:ps:{}", self.prefix, project_id)
     }
 
     fn entities_pattern(&self, project_id: &Uuid) -> String {
-        format!("c:pe:{}:*", project_id)
+        format!("{}c:pe:{}:*", self.prefix, project_id)
     }
     fn entities_key(&self, project_id: &Uuid, entity_uid: &EntityUid) -> String {
-        format!("c:pe:{}:{}", project_id, entity_uid)
+        format!("{}c:pe:{}:{}", self.prefix, project_id, entity_uid)
+    }
+
+    fn compiled_entities_key(&self, project_id: &Uuid) -> String {
+        format!("{}c:pce:{}", self.prefix, project_id)
     }
 
     fn policies_pattern(&self, project_id: &Uuid) -> String {
-        format!("c:pp:{}:*", project_id)
+        format!("{}c:pp:{}:*", self.prefix, project_id)
     }
     fn policies_key(&self, project_id: &Uuid, policy_id: &PolicyId) -> String {
-        format!("c:pp:{}:{}", project_id, policy_id)
+        format!("{}c:pp:{}:{}", self.prefix, project_id, policy_id)
     }
 
     fn templates_pattern(&self, project_id: &Uuid) -> String {
-        format!("c:pt:{}:*", project_id)
+        format!("{}c:pt:{}:*", self.prefix, project_id)
     }
     fn templates_key(&self, project_id: &Uuid, policy_id: &PolicyId) -> String {
-        format!("c:pt:{}:{}", project_id, policy_id)
+        format!("{}c:pt:{}:{}", self.prefix, project_id, policy_id)
     }
 
     fn template_links_pattern(&self, project_id: &Uuid) -> String {
-        format!("c:ptl:{}:*", project_id)
+        format!("{}c:ptl:{}:*", self.prefix, project_id)
     }
     fn template_links_key(&self, project_id: &Uuid, policy_id: &PolicyId) -> String {
-        format!("c:ptl:{}:{}", project_id, policy_id)
+        format!("{}c:ptl:{}:{}", self.prefix, project_id, policy_id)
+    }
+
+    fn version_key(&self, project_id: &Uuid) -> String {
+        format!("{}c:pv:{}", self.prefix, project_id)
+    }
+
+    // Advances the version of the project, once a change to its schema, entities or policies
+    // is written
+    async fn advance(&self, project_id: &Uuid) -> Result<(), CacheError> {
+        self.conn.incr(&self.version_key(project_id), 1).await?;
+        Ok(())
+    }
+
+    // Takes the lock under `key` until its holder drops it, renewed meanwhile, or `ttl`
+    // elapses once its node stopped
+    async fn lock(&self, key: String, ttl: Duration) -> Result<Option<CacheLock>, CacheError> {
+        let token = Uuid::now_v7().to_string();
+        if !self.conn.set_nx(&key, &token, ttl).await? {
+            return Ok(None);
+        }
+
+        let renewal = (self.conn.clone(), key.clone(), token.clone());
+        let renew = move || {
+            let (conn, key, token) = renewal.clone();
+            async move {
+                match conn.pexpire_if_eq(&key, &token, ttl).await {
+                    Ok(renewed) => renewed,
+                    // Retried on the next renewal, before the lock expires
+                    Err(e) => {
+                        tracing::warn!("valkey cache: failed to renew lock {}: {}", key, e);
+                        true
+                    }
+                }
+            }
+        };
+        // Released in the background, the lock expiring anyway if it never runs
+        let conn = self.conn.clone();
+        Ok(Some(CacheLock::renewed(ttl, renew, move || {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                runtime.spawn(async move {
+                    if let Err(e) = conn.del_if_eq(&key, &token).await {
+                        tracing::warn!("valkey cache: failed to release lock {}: {}", key, e);
+                    }
+                });
+            }
+        })))
+    }
+
+    fn policy_set_lock_key(&self, project_id: &Uuid) -> String {
+        format!("{}{}{}", self.prefix, POLICY_SET_LOCK_PREFIX, project_id)
     }
 
     fn project_pattern(&self) -> String {
-        "c:p:*".to_string()
+        format!("{}c:p:*", self.prefix)
     }
     fn project_key(&self, project_id: &Uuid) -> String {
-        format!("c:p:{}", project_id)
+        format!("{}c:p:{}", self.prefix, project_id)
     }
 
     fn entity_to_val(&self, entity: &Entity) -> String {
@@ -294,6 +525,8 @@ impl Cache for ValKeyCache {
         let pattern_prefix = self.entities_pattern(project_id);
         keys.extend(self.keys_from_pattern(&pattern_prefix).await?);
 
+        keys.push(self.compiled_entities_key(project_id));
+
         let pattern_prefix = self.policies_pattern(project_id);
         keys.extend(self.keys_from_pattern(&pattern_prefix).await?);
 
@@ -431,6 +664,7 @@ impl Cache for ValKeyCache {
             serde_json::to_string(schema).map_err(|e| CacheError::JsonError(e.to_string()))?;
         let _: () = self.conn.set(&key, &val).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
 
@@ -440,6 +674,7 @@ impl Cache for ValKeyCache {
 
         let _: () = self.conn.del(&keys).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
 
@@ -494,6 +729,7 @@ impl Cache for ValKeyCache {
 
         let _: () = self.conn.mset(&vec_tuples).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
 
@@ -514,6 +750,83 @@ impl Cache for ValKeyCache {
 
         let _: () = self.conn.del(&keys).await?;
 
+        self.advance(project_id).await?;
+        Ok(())
+    }
+
+    // Entities are never evicted from Valkey, a replacement is never stale
+    async fn project_replace_entities(
+        &self,
+        project_id: &Uuid,
+        entities: &[Entity],
+        _generation: u64,
+    ) -> Result<bool, CacheError> {
+        let pattern = self.entities_pattern(project_id);
+        let keys = self.keys_from_pattern(&pattern).await?;
+        let vec_tuples = entities
+            .iter()
+            .map(|entity| {
+                let key = self.entities_key(project_id, entity.uid());
+                (key, self.entity_to_val(entity))
+            })
+            .collect::<Vec<(String, String)>>();
+
+        // The new entities are written before the stale ones go, so readers never find the
+        // project without its entities
+        if !vec_tuples.is_empty() {
+            let _: () = self.conn.mset(&vec_tuples).await?;
+        }
+        let current = vec_tuples
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect::<HashSet<&str>>();
+        let stale = keys
+            .into_iter()
+            .filter(|key| !current.contains(key.as_str()))
+            .collect::<Vec<String>>();
+        if !stale.is_empty() {
+            let _: () = self.conn.del(&stale).await?;
+        }
+
+        self.advance(project_id).await?;
+        Ok(true)
+    }
+
+    async fn project_get_compiled_entities(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<CompiledEntities>, CacheError> {
+        let key = self.compiled_entities_key(project_id);
+        let Some(val) = self.conn.get(&key).await? else {
+            return Ok(None);
+        };
+
+        let (fingerprint, data) = val
+            .split_once(':')
+            .ok_or_else(|| CacheError::DecodeError("compiled entities".to_string()))?;
+        let data = BASE64_STANDARD
+            .decode(data)
+            .map_err(|e| CacheError::DecodeError(e.to_string()))?;
+
+        Ok(Some(CompiledEntities {
+            fingerprint: fingerprint.to_string(),
+            data,
+        }))
+    }
+
+    async fn project_set_compiled_entities(
+        &self,
+        project_id: &Uuid,
+        compiled: &CompiledEntities,
+    ) -> Result<(), CacheError> {
+        let key = self.compiled_entities_key(project_id);
+        let val = format!(
+            "{}:{}",
+            compiled.fingerprint,
+            BASE64_STANDARD.encode(&compiled.data)
+        );
+        let _: () = self.conn.set(&key, &val).await?;
+
         Ok(())
     }
 
@@ -563,6 +876,7 @@ impl Cache for ValKeyCache {
         let vec_tuples = map.into_iter().collect::<Vec<(String, String)>>();
         let _: () = self.conn.mset(&vec_tuples).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
     async fn project_del_policies(
@@ -582,6 +896,7 @@ impl Cache for ValKeyCache {
 
         let _: () = self.conn.del(&keys).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
 
@@ -631,6 +946,7 @@ impl Cache for ValKeyCache {
         let vec_tuples = map.into_iter().collect::<Vec<(String, String)>>();
         let _: () = self.conn.mset(&vec_tuples).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
 
@@ -651,6 +967,7 @@ impl Cache for ValKeyCache {
 
         let _: () = self.conn.del(&keys).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
 
@@ -695,6 +1012,7 @@ impl Cache for ValKeyCache {
         let vec_tuples = map.into_iter().collect::<Vec<(String, String)>>();
         let _: () = self.conn.mset(&vec_tuples).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
     async fn project_del_template_links(
@@ -714,6 +1032,7 @@ impl Cache for ValKeyCache {
 
         let _: () = self.conn.del(&keys).await?;
 
+        self.advance(project_id).await?;
         Ok(())
     }
 
@@ -747,4 +1066,58 @@ impl Cache for ValKeyCache {
 
         Ok(())
     }
+
+    async fn project_get_version(&self, project_id: &Uuid) -> Result<u64, CacheError> {
+        let Some(val) = self.conn.get(&self.version_key(project_id)).await? else {
+            return Ok(0);
+        };
+        val.parse()
+            .map_err(|_| CacheError::DecodeError("project version".to_string()))
+    }
+
+    async fn project_lock_policy_set(
+        &self,
+        project_id: &Uuid,
+        ttl: Duration,
+    ) -> Result<Option<CacheLock>, CacheError> {
+        self.lock(self.policy_set_lock_key(project_id), ttl).await
+    }
+
+    async fn lock_entity_expiry(&self, ttl: Duration) -> Result<Option<CacheLock>, CacheError> {
+        self.lock(format!("{}{}", self.prefix, ENTITY_EXPIRY_LOCK_KEY), ttl)
+            .await
+    }
+
+    async fn lock_job_recovery(&self, ttl: Duration) -> Result<Option<CacheLock>, CacheError> {
+        self.lock(format!("{}{}", self.prefix, JOB_RECOVERY_LOCK_KEY), ttl)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_prefix() {
+        assert_eq!(namespace_prefix(None).unwrap(), "");
+        assert_eq!(namespace_prefix(Some("staging")).unwrap(), "staging:");
+        for namespace in ["", "c", "a:b", "a*", "a?", "[a]", "a\\b"] {
+            assert!(
+                namespace_prefix(Some(namespace)).is_err(),
+                "{namespace:?} accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_lock_key() {
+        let project_id = Uuid::now_v7();
+        assert!(is_lock_key("c:eel"));
+        assert!(is_lock_key("c:jrl"));
+        assert!(is_lock_key(&format!("c:psl:{project_id}")));
+        assert!(!is_lock_key(&format!("c:pv:{project_id}")));
+        assert!(!is_lock_key(&format!("c:pp:{project_id}:p1")));
+        assert!(!is_lock_key("c:eelx"));
+    }
 }
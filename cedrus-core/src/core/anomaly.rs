@@ -0,0 +1,347 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use cedrus_cedar::EntityUid;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::DenyRateAnomalyConfig;
+
+/// Buckets the rolling window of an action is split into, expiring one at a time.
+const WINDOW_BUCKETS: u64 = 10;
+
+/// Rolling Deny rate of an action, and the one it had when the policies of its project
+/// last changed.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionDenyRate {
+    pub action: EntityUid,
+    pub allowed: u64,
+    pub denied: u64,
+    /// Share of Deny decisions, from 0 to 1
+    pub rate: f64,
+    /// Rate before the last policy change, while the change is watched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<f64>,
+}
+
+// Rates are counts of decisions, never NaN
+impl Eq for ActionDenyRate {}
+
+/// Deny rate of an action past its baseline after a policy change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenyRateSpike {
+    pub action: EntityUid,
+    pub baseline: f64,
+    /// Deny rate of the decisions since the change
+    pub rate: f64,
+    pub decisions: u64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    slot: u64,
+    allowed: u64,
+    denied: u64,
+}
+
+#[derive(Debug, Default)]
+struct ActionRates {
+    buckets: VecDeque<Bucket>,
+    baseline: Option<f64>,
+    changed_at: Option<Instant>,
+    since_allowed: u64,
+    since_denied: u64,
+}
+
+impl ActionRates {
+    fn counts(&self) -> (u64, u64) {
+        self.buckets
+            .iter()
+            .fold((0, 0), |(allowed, denied), bucket| {
+                (allowed + bucket.allowed, denied + bucket.denied)
+            })
+    }
+}
+
+fn rate(allowed: u64, denied: u64) -> f64 {
+    denied as f64 / (allowed + denied).max(1) as f64
+}
+
+/// Rolling Allow and Deny counts of the authorizations of each project per action, served by
+/// this node. When the policies of a project change, the Deny rate of each action becomes its
+/// baseline, and the decisions of the next `watch` seconds are checked against it.
+#[derive(Debug)]
+pub struct DenyRates {
+    enabled: bool,
+    started: Instant,
+    bucket: u64,
+    min_requests: u64,
+    spike: f64,
+    watch: Duration,
+    projects: DashMap<Uuid, HashMap<EntityUid, ActionRates>>,
+    /// Spikes found for each project since the node started
+    spikes: DashMap<Uuid, u64>,
+}
+
+impl Default for DenyRates {
+    fn default() -> Self {
+        Self::new(&DenyRateAnomalyConfig::default(), false)
+    }
+}
+
+impl DenyRates {
+    pub fn new(conf: &DenyRateAnomalyConfig, enabled: bool) -> Self {
+        Self {
+            enabled,
+            started: Instant::now(),
+            bucket: (conf.window / WINDOW_BUCKETS).max(1),
+            min_requests: conf.min_requests.max(1),
+            spike: conf.spike,
+            watch: Duration::from_secs(conf.watch),
+            projects: DashMap::new(),
+            spikes: DashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Counts decisions of a project, `true` for Deny, returning the actions whose Deny rate
+    /// spiked since the last policy change. An action spikes at most once per change.
+    pub fn record<'a>(
+        &self,
+        project_id: &Uuid,
+        decisions: impl IntoIterator<Item = (&'a EntityUid, bool)>,
+    ) -> Vec<DenyRateSpike> {
+        self.record_at(project_id, decisions, Instant::now())
+    }
+
+    fn record_at<'a>(
+        &self,
+        project_id: &Uuid,
+        decisions: impl IntoIterator<Item = (&'a EntityUid, bool)>,
+        now: Instant,
+    ) -> Vec<DenyRateSpike> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        let slot = now.duration_since(self.started).as_secs() / self.bucket;
+
+        let mut actions = self.projects.entry(*project_id).or_default();
+        let mut recorded: Vec<&EntityUid> = Vec::new();
+        for (action, denied) in decisions {
+            if !actions.contains_key(action) {
+                actions.insert(action.clone(), ActionRates::default());
+            }
+            let Some(rates) = actions.get_mut(action) else {
+                continue;
+            };
+
+            while rates
+                .buckets
+                .front()
+                .is_some_and(|bucket| bucket.slot + WINDOW_BUCKETS <= slot)
+            {
+                rates.buckets.pop_front();
+            }
+            if rates
+                .buckets
+                .back()
+                .is_none_or(|bucket| bucket.slot != slot)
+            {
+                rates.buckets.push_back(Bucket {
+                    slot,
+                    allowed: 0,
+                    denied: 0,
+                });
+            }
+            if let Some(bucket) = rates.buckets.back_mut() {
+                match denied {
+                    true => bucket.denied += 1,
+                    false => bucket.allowed += 1,
+                }
+            }
+
+            if rates.changed_at.is_some() {
+                match denied {
+                    true => rates.since_denied += 1,
+                    false => rates.since_allowed += 1,
+                }
+                if !recorded.contains(&action) {
+                    recorded.push(action);
+                }
+            }
+        }
+
+        let mut spikes = Vec::new();
+        for action in recorded {
+            let Some(rates) = actions.get_mut(action) else {
+                continue;
+            };
+            let Some(changed_at) = rates.changed_at else {
+                continue;
+            };
+            if now.duration_since(changed_at) > self.watch {
+                rates.changed_at = None;
+                rates.baseline = None;
+                continue;
+            }
+            let Some(baseline) = rates.baseline else {
+                continue;
+            };
+            let decisions = rates.since_allowed + rates.since_denied;
+            let since = rate(rates.since_allowed, rates.since_denied);
+            if decisions < self.min_requests || since - baseline < self.spike {
+                continue;
+            }
+
+            rates.changed_at = None;
+            rates.baseline = None;
+            spikes.push(DenyRateSpike {
+                action: action.clone(),
+                baseline,
+                rate: since,
+                decisions,
+            });
+        }
+        drop(actions);
+
+        if !spikes.is_empty() {
+            *self.spikes.entry(*project_id).or_default() += spikes.len() as u64;
+        }
+        spikes
+    }
+
+    /// Takes the Deny rate of each action of a project as its baseline, the actions without
+    /// `minRequests` decisions in their window having none.
+    pub fn policy_changed(&self, project_id: &Uuid) {
+        self.policy_changed_at(project_id, Instant::now());
+    }
+
+    fn policy_changed_at(&self, project_id: &Uuid, now: Instant) {
+        if !self.enabled {
+            return;
+        }
+        let Some(mut actions) = self.projects.get_mut(project_id) else {
+            return;
+        };
+        for rates in actions.values_mut() {
+            let (allowed, denied) = rates.counts();
+            rates.baseline = (allowed + denied >= self.min_requests).then(|| rate(allowed, denied));
+            rates.changed_at = Some(now);
+            rates.since_allowed = 0;
+            rates.since_denied = 0;
+        }
+    }
+
+    /// Deny rates of the actions of a project, sorted by action.
+    pub fn report(&self, project_id: &Uuid) -> Vec<ActionDenyRate> {
+        let Some(actions) = self.projects.get(project_id) else {
+            return Vec::new();
+        };
+        let mut report: Vec<ActionDenyRate> = actions
+            .iter()
+            .map(|(action, rates)| {
+                let (allowed, denied) = rates.counts();
+                ActionDenyRate {
+                    action: action.clone(),
+                    allowed,
+                    denied,
+                    rate: rate(allowed, denied),
+                    baseline: rates.baseline,
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| a.action.cmp(&b.action));
+        report
+    }
+
+    /// Deny rates of the actions of every project.
+    pub fn reports(&self) -> Vec<(Uuid, Vec<ActionDenyRate>)> {
+        let project_ids: Vec<Uuid> = self.projects.iter().map(|entry| *entry.key()).collect();
+        project_ids
+            .into_iter()
+            .map(|project_id| (project_id, self.report(&project_id)))
+            .collect()
+    }
+
+    /// Spikes found for each project since the node started.
+    pub fn spike_counts(&self) -> Vec<(Uuid, u64)> {
+        self.spikes
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect()
+    }
+
+    pub fn remove(&self, project_id: &Uuid) {
+        self.projects.remove(project_id);
+        self.spikes.remove(project_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deny_rate_spike() {
+        let rates = DenyRates::new(
+            &DenyRateAnomalyConfig {
+                window: 100,
+                min_requests: 10,
+                spike: 0.3,
+                watch: 600,
+            },
+            true,
+        );
+        let project_id = Uuid::now_v7();
+        let view = EntityUid::from("Action::view");
+        let edit = EntityUid::from("Action::edit");
+        let now = Instant::now();
+
+        // 10% of views denied, edits too few for a baseline
+        let decisions = (0..20).map(|i| (&view, i % 10 == 0)).chain([(&edit, true)]);
+        assert!(rates.record_at(&project_id, decisions, now).is_empty());
+        rates.policy_changed_at(&project_id, now);
+
+        let report = rates.report(&project_id);
+        assert_eq!(report[1].action, view);
+        assert_eq!((report[1].allowed, report[1].denied), (18, 2));
+        assert_eq!(report[1].baseline, Some(0.1));
+        assert_eq!(report[0].baseline, None);
+
+        // Half of the views denied since the change, once enough of them were made
+        let later = now + Duration::from_secs(5);
+        let decisions = (0..9).map(|i| (&view, i % 2 == 0));
+        assert!(rates.record_at(&project_id, decisions, later).is_empty());
+        let spikes = rates.record_at(&project_id, [(&view, false), (&edit, true)], later);
+        assert_eq!(spikes.len(), 1);
+        assert_eq!(spikes[0].action, view);
+        assert_eq!((spikes[0].rate, spikes[0].decisions), (0.5, 10));
+
+        // Alerted once per change
+        assert!(
+            rates
+                .record_at(&project_id, [(&view, true)], later)
+                .is_empty()
+        );
+        assert_eq!(rates.spike_counts(), vec![(project_id, 1)]);
+
+        // The window rolls over
+        let much_later = now + Duration::from_secs(200);
+        rates.record_at(&project_id, [(&view, true)], much_later);
+        assert_eq!(rates.report(&project_id)[1].denied, 1);
+
+        assert!(
+            DenyRates::default()
+                .record_at(&project_id, [(&view, true)], now)
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use cedrus_cedar::EntityUid;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Management change of a project, appended once it succeeded. The digests cover the
+/// settings, schema, entities, policies, API keys and identity source of the project before
+/// and after the change, `None` while the project did not exist.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuditRecord {
+    /// Time ordered, records sort by id in the order they were made
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub principal: EntityUid,
+    pub method: String,
+    pub route: String,
+    pub path: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl AuditRecord {
+    pub fn new(project_id: Uuid, principal: EntityUid) -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            project_id,
+            principal,
+            created_at: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use cedrus_cedar::{EntityUid, EntityValue, PolicyId, SlotId, Template, TemplateLink};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::Selector;
+
+/// Outcome of removing a single item of a batch delete.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchDeleteStatus {
+    Deleted,
+    NotFound,
+    /// The item is still referenced and `force` was not requested
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchDeleteResult<T> {
+    pub id: T,
+    pub status: BatchDeleteStatus,
+}
+
+impl<T> BatchDeleteResult<T> {
+    pub fn new(id: T, status: BatchDeleteStatus) -> Self {
+        Self { id, status }
+    }
+}
+
+/// Links of a single template generated for many principals at once, for bulk role
+/// assignment. The principals listed and those among the stored entities matching `selector`
+/// each get one link.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TemplateLinkBatch {
+    pub template_id: PolicyId,
+    pub principals: Vec<EntityUid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<Selector>,
+    /// Bound to `?resource` in every link, required by templates declaring that slot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<EntityUid>,
+}
+
+impl TemplateLinkBatch {
+    /// Builds one link per principal, binding the slots the template declares. `None` when
+    /// the template declares `?resource` and the batch names none, or is not valid Cedar.
+    pub fn template_links(
+        &self,
+        template: &Template,
+        principals: &[EntityUid],
+    ) -> Option<Vec<TemplateLink>> {
+        let cedar = template.to_cedar(self.template_id.clone()).ok()?;
+        let slots = cedar
+            .slots()
+            .map(|slot| SlotId::from(slot.clone()))
+            .collect::<Vec<_>>();
+        if slots.contains(&SlotId::Resource) && self.resource.is_none() {
+            return None;
+        }
+
+        let mut links = principals
+            .iter()
+            .map(|principal| {
+                let values = slots
+                    .iter()
+                    .map(|slot| match slot {
+                        SlotId::Principal => {
+                            (SlotId::Principal, EntityValue::EntityUid(principal.clone()))
+                        }
+                        SlotId::Resource => (
+                            SlotId::Resource,
+                            EntityValue::EntityUid(self.resource.clone().unwrap_or_default()),
+                        ),
+                    })
+                    .collect::<HashMap<SlotId, EntityValue>>();
+                let new_id = Self::link_id(&self.template_id, principal, self.resource.as_ref());
+                TemplateLink::new(self.template_id.clone(), new_id, values)
+            })
+            .collect::<Vec<_>>();
+        links.sort_by(|a, b| a.new_id.cmp(&b.new_id));
+        links.dedup_by(|a, b| a.new_id == b.new_id);
+        Some(links)
+    }
+
+    /// Deterministic id of the link of `template_id` for `principal`, so generating the same
+    /// batch again overwrites the links instead of duplicating them.
+    pub fn link_id(
+        template_id: &PolicyId,
+        principal: &EntityUid,
+        resource: Option<&EntityUid>,
+    ) -> PolicyId {
+        match resource {
+            Some(resource) => PolicyId::from(format!("{template_id}:{principal}:{resource}")),
+            None => PolicyId::from(format!("{template_id}:{principal}")),
+        }
+    }
+
+    /// Principals of a CSV document, one `type,id` row each. Blank rows and a leading
+    /// `type,id` header are skipped, values may be double quoted. `None` on a malformed row.
+    pub fn principals_from_csv(csv: &str) -> Option<Vec<EntityUid>> {
+        let unquote = |value: &str| {
+            let value = value.trim();
+            value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .map_or_else(|| value.to_string(), |v| v.replace("\"\"", "\""))
+        };
+
+        let mut principals = Vec::new();
+        for (index, row) in csv.lines().enumerate() {
+            if row.trim().is_empty() {
+                continue;
+            }
+            let (entity_type, id) = row.split_once(',')?;
+            let (entity_type, id) = (unquote(entity_type), unquote(id));
+            if index == 0
+                && entity_type.eq_ignore_ascii_case("type")
+                && id.eq_ignore_ascii_case("id")
+            {
+                continue;
+            }
+            if entity_type.is_empty() || id.is_empty() {
+                return None;
+            }
+            principals.push(EntityUid::new(entity_type, id));
+        }
+        Some(principals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_principals_from_csv() {
+        let csv = "type,id\nUser,alice\n\n\"User\",\"bob, jr\"\n";
+        let principals = TemplateLinkBatch::principals_from_csv(csv).unwrap();
+        assert_eq!(
+            principals,
+            vec![
+                EntityUid::new("User".to_string(), "alice".to_string()),
+                EntityUid::new("User".to_string(), "bob, jr".to_string()),
+            ]
+        );
+
+        assert!(TemplateLinkBatch::principals_from_csv("User").is_none());
+        assert!(TemplateLinkBatch::principals_from_csv("User,").is_none());
+    }
+
+    #[test]
+    fn test_template_links() {
+        let template: Template = serde_json::from_value(serde_json::json!({
+            "effect": "permit",
+            "principal": { "op": "==", "slot": "?principal" },
+            "action": { "op": "All" },
+            "resource": { "op": "in", "slot": "?resource" },
+            "conditions": []
+        }))
+        .unwrap();
+        let alice = EntityUid::new("User".to_string(), "alice".to_string());
+        let mut batch = TemplateLinkBatch {
+            template_id: PolicyId::from("editor".to_string()),
+            ..Default::default()
+        };
+
+        assert!(
+            batch
+                .template_links(&template, std::slice::from_ref(&alice))
+                .is_none()
+        );
+
+        batch.resource = Some(EntityUid::new("Folder".to_string(), "docs".to_string()));
+        let links = batch
+            .template_links(&template, &[alice.clone(), alice.clone()])
+            .unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(
+            links[0].new_id,
+            TemplateLinkBatch::link_id(&batch.template_id, &alice, batch.resource.as_ref())
+        );
+        assert_eq!(links[0].values.len(), 2);
+    }
+}
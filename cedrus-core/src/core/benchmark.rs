@@ -0,0 +1,96 @@
+use cedrus_cedar::Request;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Authorizations a benchmark runs when not told otherwise.
+pub const DEFAULT_BENCHMARK_ITERATIONS: usize = 1000;
+/// Authorizations a benchmark runs at most, keeping it from holding a worker for long.
+pub const MAX_BENCHMARK_ITERATIONS: usize = 100_000;
+
+/// Requests generated from the schema for a benchmark not given any.
+pub const SYNTHETIC_REQUESTS: usize = 100;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Benchmark {
+    /// Authorizations to run, 1000 by default and 100000 at most
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<usize>,
+    /// Requests evaluated in turn, generated from the schema and the entities when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requests: Vec<Request>,
+}
+
+/// Latencies of the authorizations of a benchmark, in microseconds.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyPercentiles {
+    pub min: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl LatencyPercentiles {
+    /// Nearest-rank percentiles of the samples, all zero without samples.
+    pub fn new(mut samples: Vec<u64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+
+        let rank = |percentile: usize| {
+            let index = (percentile * samples.len()).div_ceil(100).max(1) - 1;
+            samples[index.min(samples.len() - 1)]
+        };
+        Self {
+            min: samples[0],
+            mean: samples.iter().sum::<u64>() as f64 / samples.len() as f64,
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkReport {
+    pub iterations: usize,
+    /// Distinct requests the iterations took turns with
+    pub requests: usize,
+    pub allowed: usize,
+    pub denied: usize,
+    /// Requests the project rejected, e.g. outside its schema, which are not timed
+    pub errors: usize,
+    pub total_micros: u64,
+    /// Authorizations per second
+    pub throughput: f64,
+    pub latency: LatencyPercentiles,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_percentiles() {
+        let latency = LatencyPercentiles::new((1..=100).rev().collect());
+        assert_eq!(latency.min, 1);
+        assert_eq!(latency.p50, 50);
+        assert_eq!(latency.p90, 90);
+        assert_eq!(latency.p99, 99);
+        assert_eq!(latency.max, 100);
+        assert_eq!(latency.mean, 50.5);
+
+        let latency = LatencyPercentiles::new(vec![7]);
+        assert_eq!((latency.p50, latency.p99), (7, 7));
+        assert_eq!(
+            LatencyPercentiles::new(Vec::new()),
+            LatencyPercentiles::default()
+        );
+    }
+}
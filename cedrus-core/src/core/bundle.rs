@@ -0,0 +1,283 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use cedrus_cedar::PolicySet;
+use openssl::{
+    pkey::{Id, PKey, Private, Public},
+    sign::{Signer, Verifier},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::CedrusError;
+
+use super::{
+    BundleConfig,
+    cedrus::{policy_set_version, to_hex},
+    crypto::{HMAC_ALGORITHM, HmacKeys},
+};
+
+pub const BUNDLE_ALGORITHM: &str = "ed25519";
+
+/// Metadata of a policy bundle. The signature covers the project, the creation time and
+/// the version of the policy set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleMetadata {
+    pub project_id: Uuid,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub version: String,
+    pub algorithm: String,
+    pub key_id: String,
+    pub signature: String,
+}
+
+/// The policy set of a project, signed by the node that exported it so its origin can be
+/// verified when it is imported in another environment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyBundle {
+    pub metadata: BundleMetadata,
+    pub policy_set: PolicySet,
+}
+
+fn bundle_error(message: impl std::fmt::Display) -> CedrusError {
+    CedrusError::BundleError(message.to_string())
+}
+
+fn key_id(key: &PKey<Public>) -> Result<String, CedrusError> {
+    let raw = key.raw_public_key().map_err(bundle_error)?;
+    Ok(to_hex(&Sha256::digest(raw))[..16].to_string())
+}
+
+fn signed_payload(project_id: &Uuid, created_at: i64, version: &str) -> String {
+    format!("cedrus-bundle:v1\n{project_id}\n{created_at}\n{version}")
+}
+
+/// Keys signing the exported bundles and verifying the imported ones. Without an ed25519
+/// private key, bundles are signed with the HMAC key of their project, which only the nodes
+/// sharing the signing secrets can verify.
+#[derive(Default)]
+pub struct BundleKeys {
+    signing_key: Option<(String, PKey<Private>)>,
+    trusted_keys: Vec<(String, PKey<Public>)>,
+    hmac_keys: HmacKeys,
+}
+
+impl BundleKeys {
+    pub fn new(conf: &BundleConfig) -> Result<Self, CedrusError> {
+        let mut keys = Self::default();
+
+        if let Some(pem) = &conf.private_key {
+            let private_key = PKey::private_key_from_pem(pem.as_bytes()).map_err(bundle_error)?;
+            if private_key.id() != Id::ED25519 {
+                return Err(bundle_error("the bundle private key is not an ed25519 key"));
+            }
+            let raw = private_key.raw_public_key().map_err(bundle_error)?;
+            let public_key =
+                PKey::public_key_from_raw_bytes(&raw, Id::ED25519).map_err(bundle_error)?;
+            let key_id = key_id(&public_key)?;
+            keys.trusted_keys.push((key_id.clone(), public_key));
+            keys.signing_key = Some((key_id, private_key));
+        }
+
+        for pem in &conf.trusted_keys {
+            let public_key = PKey::public_key_from_pem(pem.as_bytes()).map_err(bundle_error)?;
+            if public_key.id() != Id::ED25519 {
+                return Err(bundle_error("a trusted bundle key is not an ed25519 key"));
+            }
+            keys.trusted_keys.push((key_id(&public_key)?, public_key));
+        }
+
+        Ok(keys)
+    }
+
+    pub fn with_hmac(mut self, hmac_keys: HmacKeys) -> Self {
+        self.hmac_keys = hmac_keys;
+        self
+    }
+
+    pub fn sign(
+        &self,
+        project_id: Uuid,
+        policy_set: PolicySet,
+    ) -> Result<PolicyBundle, CedrusError> {
+        let created_at = chrono::Utc::now();
+        let version = policy_set_version(&policy_set)?;
+        let payload = signed_payload(&project_id, created_at.timestamp(), &version);
+        let (algorithm, key_id, signature) = match &self.signing_key {
+            Some((key_id, private_key)) => {
+                let signature = Signer::new_without_digest(private_key)
+                    .and_then(|mut signer| signer.sign_oneshot_to_vec(payload.as_bytes()))
+                    .map_err(bundle_error)?;
+                (BUNDLE_ALGORITHM, key_id.clone(), signature)
+            }
+            None if !self.hmac_keys.is_empty() => {
+                let (key_id, mac) = self.hmac_keys.sign(&project_id, payload.as_bytes())?;
+                (HMAC_ALGORITHM, key_id, mac)
+            }
+            None => return Err(bundle_error("no bundle private key is configured")),
+        };
+
+        Ok(PolicyBundle {
+            metadata: BundleMetadata {
+                project_id,
+                created_at,
+                version,
+                algorithm: algorithm.to_string(),
+                key_id,
+                signature: BASE64_STANDARD.encode(signature),
+            },
+            policy_set,
+        })
+    }
+
+    /// Checks that the policy set is the one signed, by a trusted key.
+    pub fn verify(&self, bundle: &PolicyBundle) -> Result<(), CedrusError> {
+        let metadata = &bundle.metadata;
+        if metadata.algorithm != BUNDLE_ALGORITHM && metadata.algorithm != HMAC_ALGORITHM {
+            return Err(bundle_error(format!(
+                "unsupported bundle algorithm: {}",
+                metadata.algorithm
+            )));
+        }
+        if policy_set_version(&bundle.policy_set)? != metadata.version {
+            return Err(bundle_error(
+                "the policy set does not match the bundle version",
+            ));
+        }
+        let signature = BASE64_STANDARD
+            .decode(&metadata.signature)
+            .map_err(bundle_error)?;
+        let payload = signed_payload(
+            &metadata.project_id,
+            metadata.created_at.timestamp(),
+            &metadata.version,
+        );
+
+        if metadata.algorithm == HMAC_ALGORITHM {
+            return self
+                .hmac_keys
+                .verify(
+                    &metadata.project_id,
+                    &metadata.key_id,
+                    payload.as_bytes(),
+                    &signature,
+                )
+                .map_err(|_| bundle_error("the bundle signature is invalid"));
+        }
+        let Some((_, public_key)) = self
+            .trusted_keys
+            .iter()
+            .find(|(key_id, _)| *key_id == metadata.key_id)
+        else {
+            return Err(bundle_error(format!(
+                "the bundle key {} is not trusted",
+                metadata.key_id
+            )));
+        };
+
+        let verified = Verifier::new_without_digest(public_key)
+            .and_then(|mut verifier| verifier.verify_oneshot(&signature, payload.as_bytes()))
+            .map_err(bundle_error)?;
+        if !verified {
+            return Err(bundle_error("the bundle signature is invalid"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cedrus_cedar::{Policy, PolicyId};
+
+    use super::*;
+
+    fn bundle_config() -> BundleConfig {
+        let key = PKey::generate_ed25519().unwrap();
+        BundleConfig {
+            private_key: Some(String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap()),
+            trusted_keys: Vec::new(),
+        }
+    }
+
+    fn policy_set() -> PolicySet {
+        let policy: Policy = serde_json::from_value(serde_json::json!({
+            "effect": "permit",
+            "principal": {"op": "All"},
+            "action": {"op": "All"},
+            "resource": {"op": "All"},
+            "conditions": []
+        }))
+        .unwrap();
+        PolicySet {
+            static_policies: [(PolicyId::from("p1".to_string()), policy)].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let keys = BundleKeys::new(&bundle_config()).unwrap();
+        let bundle = keys.sign(Uuid::nil(), policy_set()).unwrap();
+        assert!(keys.verify(&bundle).is_ok());
+
+        let mut tampered = bundle.clone();
+        tampered.policy_set.static_policies.clear();
+        assert!(keys.verify(&tampered).is_err());
+
+        let mut tampered = bundle.clone();
+        tampered.policy_set.static_policies.clear();
+        tampered.metadata.version = policy_set_version(&tampered.policy_set).unwrap();
+        assert!(keys.verify(&tampered).is_err());
+
+        let other = BundleKeys::new(&bundle_config()).unwrap();
+        assert!(other.verify(&bundle).is_err());
+    }
+
+    #[test]
+    fn test_trusted_key() {
+        let conf = bundle_config();
+        let signer = BundleKeys::new(&conf).unwrap();
+        let private_key = PKey::private_key_from_pem(conf.private_key.unwrap().as_bytes()).unwrap();
+        let public_key = String::from_utf8(private_key.public_key_to_pem().unwrap()).unwrap();
+        let verifier = BundleKeys::new(&BundleConfig {
+            private_key: None,
+            trusted_keys: vec![public_key],
+        })
+        .unwrap();
+
+        let bundle = signer.sign(Uuid::nil(), policy_set()).unwrap();
+        assert!(verifier.verify(&bundle).is_ok());
+        assert!(verifier.sign(Uuid::nil(), policy_set()).is_err());
+    }
+
+    #[test]
+    fn test_hmac_bundle() {
+        let hmac_keys = HmacKeys::new(&crate::core::SigningConfig {
+            keys: vec![crate::core::SigningKeyConfig {
+                id: "k1".to_string(),
+                secret: "0123456789abcdef0123456789abcdef".to_string(),
+            }],
+        })
+        .unwrap();
+        let keys = BundleKeys::default().with_hmac(hmac_keys);
+        let project_id = Uuid::now_v7();
+        let bundle = keys.sign(project_id, policy_set()).unwrap();
+        assert_eq!(bundle.metadata.algorithm, HMAC_ALGORITHM);
+        assert!(keys.verify(&bundle).is_ok());
+
+        let mut moved = bundle.clone();
+        moved.metadata.project_id = Uuid::now_v7();
+        assert!(keys.verify(&moved).is_err());
+        assert!(BundleKeys::default().verify(&bundle).is_err());
+
+        // The ed25519 key takes precedence
+        let keys = BundleKeys::new(&bundle_config())
+            .unwrap()
+            .with_hmac(keys.hmac_keys.clone());
+        let bundle = keys.sign(project_id, policy_set()).unwrap();
+        assert_eq!(bundle.metadata.algorithm, BUNDLE_ALGORITHM);
+    }
+}
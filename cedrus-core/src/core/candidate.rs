@@ -0,0 +1,231 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use cedrus_cedar::{Decision, PolicySet, Request, Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Divergent requests kept for each candidate, the oldest dropped first.
+pub const MAX_DIVERGENT_SAMPLES: usize = 100;
+
+/// Policy set evaluated alongside the live one of a project on its authorization traffic,
+/// its decisions compared to the live ones but never enforced, until it is promoted.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectCandidate {
+    pub policy_set: PolicySet,
+    /// Share of the divergent requests kept and logged, from 0 to 1, all of them when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<f64>,
+    /// Set by the server when the candidate is attached
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attached_at: Option<DateTime<Utc>>,
+}
+
+// The sample rate is read from JSON, which has no NaN
+impl Eq for ProjectCandidate {}
+
+impl ProjectCandidate {
+    pub fn is_valid(&self) -> bool {
+        self.sample_rate
+            .is_none_or(|rate| (0.0..=1.0).contains(&rate))
+    }
+}
+
+/// Request the candidate decided otherwise than the live policies.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DivergentRequest {
+    pub request: Request,
+    pub live: Response,
+    pub candidate: Response,
+    pub at: DateTime<Utc>,
+}
+
+/// Decisions of the candidate of a project compared to the live ones, on this node since
+/// the candidate was attached.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateReport {
+    /// Policy version of the candidate
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attached_at: Option<DateTime<Utc>>,
+    pub evaluations: u64,
+    /// Requests the live policies allow and the candidate denies
+    pub allow_to_deny: u64,
+    /// Requests the live policies deny and the candidate allows
+    pub deny_to_allow: u64,
+    /// Requests not evaluated against the candidate, no evaluation slot being free
+    pub skipped: u64,
+    /// Latest divergent requests sampled
+    pub samples: Vec<DivergentRequest>,
+}
+
+/// Compiled candidate of a project, and how its decisions compared to the live ones.
+#[derive(Debug)]
+pub struct CandidateShadow {
+    pub candidate: ProjectCandidate,
+    pub policies: cedar_policy::PolicySet,
+    pub version: String,
+    evaluations: AtomicU64,
+    allow_to_deny: AtomicU64,
+    deny_to_allow: AtomicU64,
+    skipped: AtomicU64,
+    samples: Mutex<VecDeque<DivergentRequest>>,
+}
+
+impl CandidateShadow {
+    pub fn new(
+        candidate: ProjectCandidate,
+        policies: cedar_policy::PolicySet,
+        version: String,
+    ) -> Self {
+        Self {
+            candidate,
+            policies,
+            version,
+            evaluations: AtomicU64::new(0),
+            allow_to_deny: AtomicU64::new(0),
+            deny_to_allow: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Counts a decision of the candidate, returning whether the request was sampled as
+    /// divergent.
+    pub fn record(&self, request: &Request, live: &Response, candidate: &Response) -> bool {
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        match (&live.decision, &candidate.decision) {
+            (Decision::Allow, Decision::Deny) => self.allow_to_deny.fetch_add(1, Ordering::Relaxed),
+            (Decision::Deny, Decision::Allow) => self.deny_to_allow.fetch_add(1, Ordering::Relaxed),
+            _ => return false,
+        };
+
+        let rate = self.candidate.sample_rate.unwrap_or(1.0);
+        if rate < 1.0 && (rate <= 0.0 || rand::random::<f64>() >= rate) {
+            return false;
+        }
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == MAX_DIVERGENT_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(DivergentRequest {
+            request: request.clone(),
+            live: live.clone(),
+            candidate: candidate.clone(),
+            at: Utc::now(),
+        });
+        true
+    }
+
+    /// Counts requests left unevaluated, the node being too busy.
+    pub fn skip(&self, requests: usize) {
+        self.skipped.fetch_add(requests as u64, Ordering::Relaxed);
+    }
+
+    /// Evaluations, and divergences from Allow to Deny and from Deny to Allow.
+    pub fn counts(&self) -> (u64, u64, u64) {
+        (
+            self.evaluations.load(Ordering::Relaxed),
+            self.allow_to_deny.load(Ordering::Relaxed),
+            self.deny_to_allow.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn report(&self) -> CandidateReport {
+        let (evaluations, allow_to_deny, deny_to_allow) = self.counts();
+        CandidateReport {
+            version: self.version.clone(),
+            attached_at: self.candidate.attached_at,
+            evaluations,
+            allow_to_deny,
+            deny_to_allow,
+            skipped: self.skipped.load(Ordering::Relaxed),
+            samples: self.samples.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cedrus_cedar::EntityUid;
+
+    use super::*;
+
+    fn response(decision: Decision) -> Response {
+        Response {
+            decision,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_candidate_record() {
+        let request = Request {
+            principal: EntityUid::from("User::alice"),
+            action: EntityUid::from("Action::view"),
+            resource: EntityUid::from("Document::d1"),
+            context: None,
+        };
+        let shadow = CandidateShadow::new(
+            ProjectCandidate::default(),
+            cedar_policy::PolicySet::new(),
+            String::new(),
+        );
+
+        let (allow, deny) = (response(Decision::Allow), response(Decision::Deny));
+        assert!(!shadow.record(&request, &allow, &allow));
+        assert!(shadow.record(&request, &allow, &deny));
+        assert!(shadow.record(&request, &deny, &allow));
+        for _ in 0..MAX_DIVERGENT_SAMPLES {
+            shadow.record(&request, &allow, &deny);
+        }
+
+        let report = shadow.report();
+        assert_eq!(
+            (
+                report.evaluations,
+                report.allow_to_deny,
+                report.deny_to_allow
+            ),
+            (
+                MAX_DIVERGENT_SAMPLES as u64 + 3,
+                MAX_DIVERGENT_SAMPLES as u64 + 1,
+                1
+            )
+        );
+        assert_eq!(report.samples.len(), MAX_DIVERGENT_SAMPLES);
+        assert!(
+            report
+                .samples
+                .iter()
+                .all(|sample| sample.candidate.decision == Decision::Deny)
+        );
+
+        let unsampled = CandidateShadow::new(
+            ProjectCandidate {
+                sample_rate: Some(0.0),
+                ..Default::default()
+            },
+            cedar_policy::PolicySet::new(),
+            String::new(),
+        );
+        assert!(!unsampled.record(&request, &allow, &deny));
+        assert_eq!(unsampled.report().allow_to_deny, 1);
+        assert!(
+            !ProjectCandidate {
+                sample_rate: Some(2.0),
+                ..Default::default()
+            }
+            .is_valid()
+        );
+    }
+}
@@ -1,29 +1,255 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 use base64::{Engine, prelude::BASE64_STANDARD};
-use dashmap::DashMap;
-use jwt_authorizer::{JwtAuthorizer, Validation};
+use cedar_policy::proto::traits::Protobuf;
+use dashmap::{DashMap, DashSet};
+use jwt_authorizer::{JwtAuthorizer, Refresh, RefreshStrategy, Validation};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::JoinSet,
+};
 use uuid::Uuid;
 
 use cedrus_cedar::{
-    Context, Entity, EntityUid, Policy, PolicyId, PolicySet, Request, Response, Schema, Template,
-    TemplateLink,
+    Context, Decision, Entity, EntityUid, Policy, PolicyEffect, PolicyId, PolicySet, Request,
+    ResourcePath, Response, Schema, Template, TemplateLink, schema::TypeJson,
 };
 
 use crate::{
-    Authorizer, CedrusError, Event, EventType, PageHash, PageList, Query, cache::Cache,
-    db::Database, pubsub::PubSub,
+    Authorizer, CedrusError, DEFAULT_LIMIT, Event, EventType, PageHash, PageList, Query,
+    cache::{Cache, CacheError, CacheLock, CompiledEntities},
+    db::{Database, ENTITY_TAG_PREFIX, sort_entities},
+    pubsub::PubSub,
 };
 
 use super::{
-    CedrusConfig, IdentitySource,
+    BootstrapConfig, CedrusConfig, GitOpsConfig, IdentitySource,
+    anomaly::DenyRates,
+    audit::AuditRecord,
+    batch::{BatchDeleteResult, BatchDeleteStatus, TemplateLinkBatch},
+    benchmark::{
+        Benchmark, BenchmarkReport, DEFAULT_BENCHMARK_ITERATIONS, LatencyPercentiles,
+        MAX_BENCHMARK_ITERATIONS, SYNTHETIC_REQUESTS,
+    },
+    bundle::{BundleKeys, PolicyBundle},
+    candidate::{CandidateReport, CandidateShadow, ProjectCandidate},
+    cluster::{ClusterMembership, ClusterStatus},
+    combine::{CombinedResponse, DecisionStrategy, ProjectDecision},
+    consistency::{ConsistencyDrift, ConsistencyReport},
+    coverage::PolicyCoverageReport,
+    crypto::HmacKeys,
+    diff::{DiffFormat, RevisionDiff},
+    dry_run,
+    environment::{self, ProjectEnvironment, Promotion, PromotionReport},
+    epoch::ProjectEpochs,
+    generator::{
+        DEFAULT_ENTITIES_PER_TYPE, DEFAULT_GENERATED_REQUESTS, DataGenerator, GeneratedData,
+        GeneratorOptions, MAX_ENTITIES_PER_TYPE, MAX_GENERATED_REQUESTS,
+    },
+    gitops::{self, GitOpsReport, GitOpsSource, GitOpsState},
+    history::{Revision, RevisionKind},
     is::Configuration,
-    project::{ApiKey, Project},
+    isolation::EvaluationSlots,
+    job::{JOB_CHUNK_SIZE, Job, JobTask, ProjectCleanup, ProjectData},
+    lint::SchemaLintReport,
+    modified::{ModifiedTimes, ProjectResource},
+    notification::{Alert, AlertKind, Notifications, ProjectNotifications},
+    project::{
+        ANNOTATION_DELEGATION_PROJECT, ANNOTATION_GUARDRAIL, ApiKey, GUARDRAIL_ID_PREFIX,
+        LinkedEntityRemoval, PROJECT_SORT_FIELDS, Project, ProjectHydration, ProjectStats,
+        RequestValidation, Role, RoleAssignment, TimeContext,
+    },
+    references::{self, EntityReferences, ReferenceIndex},
+    relation::{Relation, RelationIndex},
+    sdk::{self, SdkLang},
+    shard::ShardOwnership,
+    staleness::EventStaleness,
+    state::{ProjectState, StatePlan},
+    sync::{EntitiesSync, EntitiesSyncReport},
+    telemetry::ContextTelemetry,
+    token::TokenCache,
+    write_behind::{WriteBehindQueue, WriteOp},
 };
 
+/// Number of entities converted to Cedar per blocking task when rebuilding a project.
+const ENTITY_CONVERSION_CHUNK_SIZE: usize = 1000;
+/// Smallest number of requests of a batch evaluated per thread.
+const AUTHORIZATION_CHUNK_SIZE: usize = 64;
+/// Number of entities loaded per page from the database when streaming entities.
+const ENTITIES_STREAM_PAGE_SIZE: usize = 500;
+
+/// JSON value with object keys sorted recursively, so equal values serialize identically.
+pub fn canonical_json(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map
+                .into_iter()
+                .map(|(k, v)| (k, canonical_json(v)))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonical_json).collect()),
+        value => value,
+    }
+}
+
+/// SHA-256 of a JSON value with object keys sorted, the same for equal values on every node.
+pub fn json_digest(value: Value) -> String {
+    to_hex(&Sha256::digest(
+        canonical_json(value).to_string().as_bytes(),
+    ))
+}
+
+pub(crate) fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Common type names are Cedar identifiers, referred to unqualified from any namespace.
+fn is_common_type_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Deterministic version of a policy set: the SHA-256 of its JSON form with object keys and
+/// template links sorted, so every node derives the same version for the same policies.
+pub fn policy_set_version(policy_set: &PolicySet) -> Result<String, CedrusError> {
+    let mut value = canonical_json(serde_json::to_value(policy_set)?);
+    if let Some(Value::Array(links)) = value.get_mut("templateLinks") {
+        links.sort_by_cached_key(|link| link.to_string());
+    }
+
+    Ok(to_hex(&Sha256::digest(value.to_string().as_bytes())))
+}
+
+/// Compiled policy set of a project along with its version, replaced as one so an evaluation
+/// always reports the version of the policies it used.
+#[derive(Debug)]
+pub struct CompiledPolicies {
+    pub policy_set: cedar_policy::PolicySet,
+    pub version: String,
+}
+
+impl CompiledPolicies {
+    pub fn new(policy_set: cedar_policy::PolicySet, version: String) -> Self {
+        Self {
+            policy_set,
+            version,
+        }
+    }
+}
+
+impl std::ops::Deref for CompiledPolicies {
+    type Target = cedar_policy::PolicySet;
+
+    fn deref(&self) -> &Self::Target {
+        &self.policy_set
+    }
+}
+
+/// Fingerprint of the entities and schema a compiled Cedar entity store is built from.
+fn entities_fingerprint(
+    entities: &[Entity],
+    schema: Option<&Schema>,
+) -> Result<String, CedrusError> {
+    let mut values = entities
+        .iter()
+        .map(|entity| Ok(canonical_json(serde_json::to_value(entity)?).to_string()))
+        .collect::<Result<Vec<String>, serde_json::Error>>()?;
+    values.sort();
+
+    let mut hasher = Sha256::new();
+    if let Some(schema) = schema {
+        hasher.update(canonical_json(serde_json::to_value(schema)?).to_string());
+    }
+    for value in values {
+        hasher.update(b"\n");
+        hasher.update(value);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Attempts made to build a JWT authorizer before giving up.
+const AUTHORIZER_RETRIES: u32 = 3;
+/// Delay before the first retry, doubled after each failed attempt.
+const AUTHORIZER_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Attempts made to export a project snapshot while writes overlap the export.
+const EXPORT_SNAPSHOT_ATTEMPTS: u32 = 5;
+/// Delay after a failed snapshot attempt, multiplied by the number of attempts.
+const EXPORT_SNAPSHOT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Time the policy set lock of a project outlives a node stopping without releasing it, the
+/// lock being renewed while held.
+const POLICY_SET_LOCK_TTL: Duration = Duration::from_secs(30);
+/// Time spent waiting for the policy set lock of a project before reporting a conflict.
+const POLICY_SET_LOCK_WAIT: Duration = Duration::from_secs(5);
+/// Delay between attempts to take the policy set lock of a project.
+const POLICY_SET_LOCK_RETRY: Duration = Duration::from_millis(50);
+/// Time the entity expiry lock outlives a node stopping during its sweep, the lock being
+/// renewed while held.
+const ENTITY_EXPIRY_LOCK_TTL: Duration = Duration::from_secs(300);
+/// Time a job is left to the node running it without a heartbeat before another node takes
+/// it over.
+const JOB_LEASE: Duration = Duration::from_secs(60);
+/// Delay between the renewals of the lease of a running job.
+const JOB_HEARTBEAT: Duration = Duration::from_secs(20);
+/// Times a job is started before it fails, so a job stopping every node running it is not
+/// taken over forever.
+const JOB_MAX_ATTEMPTS: u32 = 3;
+/// Time the job recovery lock outlives a node stopping while taking jobs over.
+const JOB_RECOVERY_LOCK_TTL: Duration = Duration::from_secs(60);
+
+fn job_lease() -> chrono::Duration {
+    chrono::Duration::seconds(JOB_LEASE.as_secs() as i64)
+}
+
+/// Builds the JWT authorizer of an identity source, retrying with exponential backoff when the
+/// identity provider (JWKS or OpenID Connect discovery) is unreachable.
 pub async fn authorizer_factory(
     conf: &Configuration,
+) -> Result<jwt_authorizer::Authorizer<Value>, CedrusError> {
+    let mut backoff = AUTHORIZER_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match authorizer_build(conf).await {
+            Ok(authorizer) => return Ok(authorizer),
+            Err(e) if attempt < AUTHORIZER_RETRIES => {
+                tracing::warn!(
+                    "cedrus: authorizer_factory: attempt {attempt} failed, retrying in {backoff:?}: {e}"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// JWKS are cached by the authorizer and refreshed periodically, keeping the last known keys
+// while the identity provider is unreachable.
+fn jwks_refresh() -> Refresh {
+    Refresh {
+        strategy: RefreshStrategy::Interval,
+        refresh_interval: Duration::from_secs(600),
+        retry_interval: Duration::from_secs(10),
+    }
+}
+
+async fn authorizer_build(
+    conf: &Configuration,
 ) -> Result<jwt_authorizer::Authorizer<Value>, CedrusError> {
     match conf {
         Configuration::CognitoUserPoolConfiguration(conf) => {
@@ -32,6 +258,7 @@ pub async fn authorizer_factory(
             let validation = Validation::new().iss(&iss).aud(&conf.client_ids);
             JwtAuthorizer::from_jwks_url(&url)
                 .validation(validation)
+                .refresh(jwks_refresh())
                 .build()
                 .await
                 .map_err(|e| CedrusError::AuthorizerError(e.to_string()))
@@ -47,6 +274,7 @@ pub async fn authorizer_factory(
             };
             JwtAuthorizer::from_oidc(&conf.issuer)
                 .validation(validation)
+                .refresh(jwks_refresh())
                 .build()
                 .await
                 .map_err(|e| CedrusError::AuthorizerError(e.to_string()))
@@ -58,17 +286,95 @@ pub struct Cedrus {
     pub id: Uuid, // Container Identity, used for cluster comunictaion
 
     pub db: Box<dyn Database + Send + Sync>,
+    /// Read replica the cache warm-up reads from, the primary when unset
+    pub read_db: Option<Box<dyn Database + Send + Sync>>,
     pub cache: Box<dyn Cache + Send + Sync>,
     pub pubsub: Box<dyn PubSub + Send + Sync>,
 
     pub exclude_policy_annotation: Option<String>,
+    pub compiled_entities: bool,
+    pub read_only: bool,
+    /// Warm up only the admin project at startup, the others being loaded by `project_reload`
+    /// on their requests
+    pub lazy_load: bool,
 
-    pub api_keys: DashMap<String, EntityUid>,
+    pub api_keys: DashMap<String, ApiKey>,
 
     pub project_authorizers: DashMap<Uuid, Option<Authorizer>>,
+    /// Principals of the tokens verified by the project authorizers
+    pub token_cache: TokenCache,
+    pub pending_identity_sources: DashMap<Uuid, IdentitySource>,
+    pub project_schemas: DashMap<Uuid, Schema>,
     pub project_cedar_schemas: DashMap<Uuid, Option<cedar_policy::Schema>>,
-    pub project_cedar_entities: DashMap<Uuid, cedar_policy::Entities>,
-    pub project_cedar_policies: DashMap<Uuid, cedar_policy::PolicySet>,
+    /// Shared with the evaluations in flight, which keep their snapshot once it is replaced
+    pub project_cedar_entities: DashMap<Uuid, Arc<cedar_policy::Entities>>,
+    /// Fingerprint of the entities and schema each project's Cedar entities are built from
+    pub project_entities_fingerprints: DashMap<Uuid, String>,
+    /// Held by the rebuild of the Cedar entities of each project, so the last one started is
+    /// the last one stored
+    entities_locks: DashMap<Uuid, Arc<tokio::sync::Mutex<()>>>,
+    /// Projects whose Cedar entities changed since they were last persisted to the cache
+    pub pending_compiled_entities: DashSet<Uuid>,
+    pub project_cedar_policies: DashMap<Uuid, Arc<CompiledPolicies>>,
+    /// Cache versions of each project and of the admin project it was last synced at
+    pub project_synced_versions: DashMap<Uuid, (u64, u64)>,
+    pub project_time_contexts: DashMap<Uuid, TimeContext>,
+    /// Earliest `expiresAt` among the entities of each project having one
+    pub project_entity_expiries: DashMap<Uuid, chrono::DateTime<chrono::Utc>>,
+    pub project_relations: DashMap<Uuid, RelationIndex>,
+    pub project_references: DashMap<Uuid, ReferenceIndex>,
+    pub read_only_projects: DashSet<Uuid>,
+    pub write_behind_projects: DashSet<Uuid>,
+    pub write_behind: WriteBehindQueue,
+    pub history_projects: DashSet<Uuid>,
+    pub permissive_projects: DashSet<Uuid>,
+    /// Projects removing the template links naming an entity along with it
+    pub cascade_link_projects: DashSet<Uuid>,
+    pub project_epochs: ProjectEpochs,
+    /// When the schema, entities and policies of each project last changed on this node
+    pub project_modified: ModifiedTimes,
+    pub context_telemetry: ContextTelemetry,
+    pub bundle_keys: BundleKeys,
+    /// Per project HMAC keys of webhook and event payloads and stream tokens
+    pub signing_keys: HmacKeys,
+    pub anonymous_principals: DashMap<Uuid, EntityUid>,
+    pub project_evaluation_timeouts: DashMap<Uuid, Duration>,
+    /// Authorizations of each project given up on once their evaluation timeout elapsed
+    pub evaluation_timeouts: DashMap<Uuid, u64>,
+    pub gitops_projects: DashMap<Uuid, GitOpsSource>,
+    /// Environment each project is of, for those created as one
+    pub project_environments: DashMap<Uuid, ProjectEnvironment>,
+    pub project_notifications: DashMap<Uuid, ProjectNotifications>,
+    /// Candidate policy set of each project having one, evaluated in the shadow of the live one
+    pub project_candidates: DashMap<Uuid, CandidateShadow>,
+    /// Candidate evaluations running at once on this node, one per available core
+    candidate_evaluations: Arc<Semaphore>,
+    /// Repositories the GitOps projects may be reconciled from
+    pub gitops: GitOpsConfig,
+    /// Held by the sync of each GitOps project, over its checkout
+    gitops_locks: DashMap<Uuid, Arc<tokio::sync::Mutex<()>>>,
+    common_types: RwLock<HashMap<String, TypeJson>>,
+    /// Roles of the admin project by id, as stored
+    roles: RwLock<BTreeMap<String, Role>>,
+    /// Forbid policies of the admin project merged into the policy set of every other project
+    guardrails: RwLock<HashMap<PolicyId, Policy>>,
+    common_types_lock: tokio::sync::Mutex<()>,
+    /// Serve the admin project from its bootstrap schema, entities and policy set, the stored
+    /// ones failing the startup integrity check
+    pub admin_safe_mode: bool,
+    /// Files replacing the bundled schema, entities and policy set of the admin project
+    pub bootstrap: BootstrapConfig,
+    /// Data residency regions projects can be created in
+    pub regions: HashSet<String>,
+    /// Projects this node rebuilds on events, the others being rebuilt on their next request
+    pub shards: ShardOwnership,
+    pub evaluation_slots: EvaluationSlots,
+    pub notifications: Notifications,
+    pub deny_rates: DenyRates,
+    /// Other nodes known from their heartbeats, and the events of each received
+    pub cluster: ClusterMembership,
+    /// Last event applied to each project
+    pub event_staleness: EventStaleness,
 }
 
 impl Cedrus {
@@ -77,22 +383,75 @@ impl Cedrus {
         cache: Box<dyn Cache + Send + Sync>,
         pubsub: Box<dyn PubSub + Send + Sync>,
         exclude_policy_annotation: Option<String>,
+        compiled_entities: bool,
+        read_only: bool,
     ) -> Self {
         Self {
             id: Uuid::now_v7(),
 
             db,
+            read_db: None,
             cache,
             pubsub,
 
             exclude_policy_annotation,
+            compiled_entities,
+            read_only,
+            lazy_load: false,
 
             api_keys: DashMap::new(),
 
             project_authorizers: DashMap::new(),
+            token_cache: TokenCache::default(),
+            pending_identity_sources: DashMap::new(),
+            project_schemas: DashMap::new(),
             project_cedar_schemas: DashMap::new(),
             project_cedar_entities: DashMap::new(),
+            project_entities_fingerprints: DashMap::new(),
+            entities_locks: DashMap::new(),
+            pending_compiled_entities: DashSet::new(),
             project_cedar_policies: DashMap::new(),
+            project_synced_versions: DashMap::new(),
+            project_time_contexts: DashMap::new(),
+            project_entity_expiries: DashMap::new(),
+            project_relations: DashMap::new(),
+            project_references: DashMap::new(),
+            read_only_projects: DashSet::new(),
+            write_behind_projects: DashSet::new(),
+            write_behind: WriteBehindQueue::default(),
+            history_projects: DashSet::new(),
+            permissive_projects: DashSet::new(),
+            cascade_link_projects: DashSet::new(),
+            project_epochs: ProjectEpochs::default(),
+            project_modified: ModifiedTimes::default(),
+            context_telemetry: ContextTelemetry::default(),
+            bundle_keys: BundleKeys::default(),
+            signing_keys: HmacKeys::default(),
+            anonymous_principals: DashMap::new(),
+            project_evaluation_timeouts: DashMap::new(),
+            evaluation_timeouts: DashMap::new(),
+            gitops_projects: DashMap::new(),
+            project_environments: DashMap::new(),
+            project_notifications: DashMap::new(),
+            project_candidates: DashMap::new(),
+            candidate_evaluations: Arc::new(Semaphore::new(
+                std::thread::available_parallelism().map_or(1, |n| n.get()),
+            )),
+            gitops: GitOpsConfig::default(),
+            gitops_locks: DashMap::new(),
+            common_types: RwLock::new(HashMap::new()),
+            roles: RwLock::new(BTreeMap::new()),
+            guardrails: RwLock::new(HashMap::new()),
+            common_types_lock: tokio::sync::Mutex::new(()),
+            admin_safe_mode: false,
+            bootstrap: BootstrapConfig::default(),
+            regions: HashSet::new(),
+            shards: ShardOwnership::default(),
+            evaluation_slots: EvaluationSlots::default(),
+            notifications: Notifications::default(),
+            deny_rates: DenyRates::default(),
+            cluster: ClusterMembership::default(),
+            event_staleness: EventStaleness::default(),
         }
     }
 
@@ -131,12 +490,7 @@ impl Cedrus {
                     .await?;
             }
         } else {
-            let schema_str = include_str!("../../config/cedrus.cedarschema.json");
-            let entities_str = include_str!("../../config/cedrus.cedarentities.json");
-            let policy_set_str = include_str!("../../config/cedrus.cedar.json");
-            let schema: Schema = serde_json::from_str(schema_str)?;
-            let entities: Vec<Entity> = serde_json::from_str(entities_str)?;
-            let policy_set: PolicySet = serde_json::from_str(policy_set_str)?;
+            let (schema, entities, policy_set) = self.admin_project_bundle()?;
 
             let now = chrono::Utc::now();
             let owner = EntityUid::new("User".to_string(), Uuid::nil().to_string());
@@ -187,96 +541,624 @@ impl Cedrus {
         Ok(())
     }
 
-    pub async fn init_cache(&mut self) -> Result<(), CedrusError> {
+    /// Schema, entities and policy set the admin project is created with, read from the
+    /// bootstrap files when configured, else bundled with the server.
+    fn admin_project_bundle(&self) -> Result<(Schema, Vec<Entity>, PolicySet), CedrusError> {
+        let read = |path: &Option<String>, bundled: &str| match path {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|e| CedrusError::BootstrapError(format!("{path}: {e}"))),
+            None => Ok(bundled.to_string()),
+        };
+        let schema_str = read(
+            &self.bootstrap.schema,
+            include_str!("../../config/cedrus.cedarschema.json"),
+        )?;
+        let entities_str = read(
+            &self.bootstrap.entities,
+            include_str!("../../config/cedrus.cedarentities.json"),
+        )?;
+        let policy_set_str = read(
+            &self.bootstrap.policy_set,
+            include_str!("../../config/cedrus.cedar.json"),
+        )?;
+        Ok((
+            serde_json::from_str(schema_str.as_str())?,
+            serde_json::from_str(entities_str.as_str())?,
+            serde_json::from_str(policy_set_str.as_str())?,
+        ))
+    }
+
+    /// Checks that the bootstrap admin project and the one stored in the Database parse and
+    /// compile, before the Cache is built from them, returning every problem found.
+    pub async fn admin_project_check(&self) -> Result<(), CedrusError> {
+        *self.common_types.write().unwrap() = self.db.common_types_load().await?;
+
+        let mut diagnostics = Vec::new();
+        match self.admin_project_bundle() {
+            Ok((schema, entities, policy_set)) => diagnostics.extend(self.compile_diagnostics(
+                "bootstrap",
+                Some(&schema),
+                &entities,
+                policy_set,
+            )),
+            Err(e) => diagnostics.push(format!("bootstrap: {e}")),
+        }
+
+        let project_id = Uuid::nil();
         let query = Query::new();
-        let projects = self.db.projects_load(&query).await?;
+        let schema = self.db.project_schema_load(&project_id).await;
+        let entities = self.db.project_entities_load(&project_id, &query).await;
+        let static_policies = self.db.project_policies_load(&project_id, &query).await;
+        let templates = self.db.project_templates_load(&project_id, &query).await;
+        let template_links = self
+            .db
+            .project_template_links_load(&project_id, &query)
+            .await;
+        match (schema, entities, static_policies, templates, template_links) {
+            (Ok(schema), Ok(entities), Ok(static_policies), Ok(templates), Ok(template_links)) => {
+                let policy_set = PolicySet {
+                    static_policies: static_policies.items,
+                    templates: templates.items,
+                    template_links: template_links.items,
+                };
+                diagnostics.extend(self.compile_diagnostics(
+                    "stored",
+                    schema.as_ref(),
+                    &entities.items,
+                    policy_set,
+                ));
+            }
+            (schema, entities, static_policies, templates, template_links) => {
+                let errors = [
+                    ("schema", schema.err()),
+                    ("entities", entities.err()),
+                    ("policies", static_policies.err()),
+                    ("templates", templates.err()),
+                    ("template links", template_links.err()),
+                ];
+                for (name, error) in errors {
+                    if let Some(e) = error {
+                        diagnostics.push(format!("stored {name}: {e}"));
+                    }
+                }
+            }
+        }
+
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(CedrusError::IntegrityError(diagnostics))
+        }
+    }
+
+    // Problems compiling the schema, entities and policy set of the admin project
+    fn compile_diagnostics(
+        &self,
+        source: &str,
+        schema: Option<&Schema>,
+        entities: &[Entity],
+        policy_set: PolicySet,
+    ) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+
+        let cedar_schema: Option<cedar_policy::Schema> =
+            match schema.map(|s| self.with_common_types(s.clone()).try_into()) {
+                Some(Ok(cedar_schema)) => Some(cedar_schema),
+                Some(Err(e)) => {
+                    diagnostics.push(format!("{source} schema: {e}"));
+                    None
+                }
+                None => None,
+            };
+
+        let mut cedar_entities = Vec::new();
+        let enum_entities = schema.map(Self::schema_enum_entities).unwrap_or_default();
+        for entity in entities.iter().chain(&enum_entities) {
+            match entity.to_cedar_entity(cedar_schema.as_ref()) {
+                Ok(cedar_entity) => cedar_entities.push(cedar_entity),
+                Err(e) => diagnostics.push(format!("{source} entity {}: {e}", entity.uid())),
+            }
+        }
+        if let Err(e) = cedar_policy::Entities::from_entities(cedar_entities, cedar_schema.as_ref())
+        {
+            diagnostics.push(format!("{source} entities: {e}"));
+        }
+
+        if let Err(e) = TryInto::<cedar_policy::PolicySet>::try_into(policy_set) {
+            diagnostics.push(format!("{source} policy set: {e}"));
+        }
+
+        diagnostics
+    }
 
+    pub async fn init_cache(&mut self) -> Result<(), CedrusError> {
         let query = Query::new();
-        for project in projects.items {
-            self.cache.project_del(&project.id).await?;
+        let read_db = self.read_db.as_deref().unwrap_or(self.db.as_ref());
+        let mut projects = match self.lazy_load {
+            true => Vec::new(),
+            false => read_db.projects_load(&query).await?.items,
+        };
 
-            let apikeys = self.db.project_apikeys_load(&project.id, &query).await?;
-            let entities = self.db.project_entities_load(&project.id, &query).await?;
-            let static_policies = self.db.project_policies_load(&project.id, &query).await?;
-            let templates = self.db.project_templates_load(&project.id, &query).await?;
-            let template_links = self
-                .db
-                .project_template_links_load(&project.id, &query)
-                .await?;
+        // The admin project may have just been bootstrapped, and not be replicated yet
+        projects.retain(|project| !project.id.is_nil());
+        if let Some(admin_project) = self.db.project_load(&Uuid::nil()).await? {
+            projects.insert(0, admin_project);
+        }
 
-            self.cache.project_set(&project).await?;
+        for project in projects {
+            if project.id.is_nil() && self.admin_safe_mode {
+                self.admin_project_cache_bundle(&project).await?;
+            } else if project.id.is_nil() {
+                self.project_cache_init(self.db.as_ref(), &project).await?;
+            } else {
+                self.project_cache_init(read_db, &project).await?;
+            }
+        }
 
-            if let Some(identity_source) = self.db.project_identity_source_load(&project.id).await?
-            {
+        Ok(())
+    }
+
+    // Fill the Cache entries of the admin project with its bootstrap schema, entities and
+    // policy set in place of the stored ones, keeping its API keys and identity source
+    async fn admin_project_cache_bundle(&self, project: &Project) -> Result<(), CedrusError> {
+        let (schema, entities, policy_set) = self.admin_project_bundle()?;
+        let apikeys = self
+            .db
+            .project_apikeys_load(&project.id, &Query::new())
+            .await?;
+
+        self.cache.project_del(&project.id).await?;
+        self.cache.project_set(project).await?;
+        match self.db.project_identity_source_load(&project.id).await {
+            Ok(Some(identity_source)) => {
                 self.cache
                     .project_set_identity_source(&project.id, &identity_source)
-                    .await?;
+                    .await?
             }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("cedrus: safe mode: admin identity source: {e}"),
+        }
+        self.cache
+            .project_set_apikeys(&project.id, &apikeys.items)
+            .await?;
+        self.cache.project_set_schema(&project.id, &schema).await?;
+        self.cache
+            .project_set_entities(&project.id, &entities)
+            .await?;
+        self.cache
+            .project_set_policy_set(&project.id, &policy_set)
+            .await?;
 
-            if let Some(schema) = self.db.project_schema_load(&project.id).await? {
-                self.cache.project_set_schema(&project.id, &schema).await?;
-            }
+        Ok(())
+    }
 
+    // Rewrite the Cache entries of a project from the Database `db`, the primary or a replica
+    async fn project_cache_init(
+        &self,
+        db: &(dyn Database + Send + Sync),
+        project: &Project,
+    ) -> Result<(), CedrusError> {
+        let query = Query::new();
+
+        self.cache.project_del(&project.id).await?;
+
+        let apikeys = db.project_apikeys_load(&project.id, &query).await?;
+        let entities = db.project_entities_load(&project.id, &query).await?;
+        let static_policies = db.project_policies_load(&project.id, &query).await?;
+        let templates = db.project_templates_load(&project.id, &query).await?;
+        let template_links = db.project_template_links_load(&project.id, &query).await?;
+
+        self.cache.project_set(project).await?;
+
+        if let Some(identity_source) = db.project_identity_source_load(&project.id).await? {
             self.cache
-                .project_set_apikeys(&project.id, &apikeys.items)
-                .await?;
-            self.cache
-                .project_set_entities(&project.id, &entities.items)
-                .await?;
-            self.cache
-                .project_set_policies(&project.id, &static_policies.items)
-                .await?;
-            self.cache
-                .project_set_templates(&project.id, &templates.items)
-                .await?;
-            self.cache
-                .project_set_template_links(&project.id, &template_links.items)
+                .project_set_identity_source(&project.id, &identity_source)
                 .await?;
         }
 
+        if let Some(schema) = db.project_schema_load(&project.id).await? {
+            self.cache.project_set_schema(&project.id, &schema).await?;
+        }
+
+        self.cache
+            .project_set_apikeys(&project.id, &apikeys.items)
+            .await?;
+        self.cache
+            .project_set_entities(&project.id, &entities.items)
+            .await?;
+        self.cache
+            .project_set_policies(&project.id, &static_policies.items)
+            .await?;
+        self.cache
+            .project_set_templates(&project.id, &templates.items)
+            .await?;
+        self.cache
+            .project_set_template_links(&project.id, &template_links.items)
+            .await?;
+
         Ok(())
     }
 
     pub async fn load_cache(&self) -> Result<(), CedrusError> {
+        *self.common_types.write().unwrap() = self.db.common_types_load().await?;
+        self.roles_reload().await?;
+
         let projects = self.cache.projects_get().await?;
         for project in projects {
-            self.on_project_set(&project)?;
+            self.project_cache_load(&project, true).await?;
+        }
+
+        Ok(())
+    }
+
+    // Rebuild the in-memory Cedar structures of a project from the Cache, and its JWT
+    // authorizer unless `authorizer` is false
+    async fn project_cache_load(
+        &self,
+        project: &Project,
+        authorizer: bool,
+    ) -> Result<(), CedrusError> {
+        self.on_project_set(project)?;
+
+        let apikeys = self.cache.project_get_apikeys(&project.id).await?;
+        self.on_project_apikeys_set(&apikeys)?;
+
+        let cache_identity_source = self.cache.project_get_identity_source(&project.id).await?;
+        if authorizer && let Some(identity_source) = cache_identity_source {
+            self.on_project_identity_source_set(&project.id, &identity_source)
+                .await?;
+        }
+
+        let cache_schema = self.cache.project_get_schema(&project.id).await?;
+        if let Some(schema) = &cache_schema {
+            self.on_project_schema_set(&project.id, schema)?;
+        }
+
+        self.on_project_entities(&project.id).await?;
+        self.on_project_policy_set(&project.id).await?;
+        self.on_project_references(&project.id, None).await?;
+        self.event_staleness.applied(&project.id, None);
+
+        Ok(())
+    }
+
+    /// Reloads a single project from the Database into the Cache and the in-memory Cedar
+    /// structures, then asks the other nodes to reload it from the Cache.
+    pub async fn project_resync(&self, project_id: Uuid) -> Result<(), CedrusError> {
+        let Some(project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
 
-            let apikeys = self.cache.project_get_apikeys(&project.id).await?;
-            self.on_project_apikeys_set(&apikeys)?;
+        let api_keys: HashSet<String> = self
+            .cache
+            .project_get_apikeys(&project_id)
+            .await?
+            .into_iter()
+            .map(|api_key| api_key.key)
+            .collect();
+
+        self.project_cache_init(self.db.as_ref(), &project).await?;
+
+        self.publish(Event::project_resync(self.id, project_id, api_keys))
+            .await;
+
+        Ok(())
+    }
+
+    /// Reloads a project from the Database into the Cache and the in-memory Cedar structures of
+    /// this node only, for nodes warming projects up lazily without events, e.g. on every
+    /// serverless invocation. The JWT authorizer is kept while the identity source is unchanged,
+    /// and a project removed from the Database is dropped.
+    pub async fn project_reload(&self, project_id: &Uuid) -> Result<(), CedrusError> {
+        let api_keys: Vec<String> = self
+            .cache
+            .project_get_apikeys(project_id)
+            .await?
+            .into_iter()
+            .map(|api_key| api_key.key)
+            .collect();
+        let Some(project) = self.db.project_load(project_id).await? else {
+            self.cache.project_del(project_id).await?;
+            return self.on_project_del(project_id, &api_keys);
+        };
+
+        let identity_source = self.cache.project_get_identity_source(project_id).await?;
+        if project.id.is_nil() && self.admin_safe_mode {
+            self.admin_project_cache_bundle(&project).await?;
+        } else {
+            self.project_cache_init(self.db.as_ref(), &project).await?;
+        }
+        let reloaded = self.cache.project_get_identity_source(project_id).await?;
+        let authorizer = !self.project_authorizers.contains_key(project_id)
+            || serde_json::to_value(&identity_source)? != serde_json::to_value(&reloaded)?;
+        if authorizer {
+            self.on_project_identity_source_del(project_id)?;
+        }
+
+        self.on_project_apikeys_del(&api_keys)?;
+        self.project_cache_load(&project, authorizer).await
+    }
 
-            let cache_identity_source = self.cache.project_get_identity_source(&project.id).await?;
-            if let Some(identity_source) = cache_identity_source {
-                self.on_project_identity_source_set(&project.id, &identity_source)
+    /// Rebuilds the compiled entities and policies of a project another node owns, when events
+    /// changed them since its last request, or of a project the Cache evicted. The project
+    /// stays stale until rebuilt, concurrent requests waiting for the rebuild in progress, and
+    /// an event landing during the rebuild leaves it stale for the next one.
+    pub async fn project_refresh(&self, project_id: &Uuid) -> Result<(), CedrusError> {
+        if !self.shards.is_stale(project_id) {
+            return Ok(());
+        }
+        let lock = self.shards.rebuild_lock(project_id);
+        let _lock = lock.lock().await;
+        // Rebuilt by the request holding the lock before
+        let Some(generation) = self.shards.stale_generation(project_id) else {
+            return Ok(());
+        };
+
+        let rebuilt = async {
+            self.on_project_entities(project_id).await?;
+            self.on_project_policy_set(project_id).await?;
+            self.on_project_references(project_id, None).await?;
+            // Dropped along with the entities of a project the Cache evicted
+            if !self.project_authorizers.contains_key(project_id)
+                && let Some(identity_source) =
+                    self.cache.project_get_identity_source(project_id).await?
+            {
+                self.on_project_identity_source_set(project_id, &identity_source)
                     .await?;
             }
+            Ok::<(), CedrusError>(())
+        };
+        // Left stale on failure, for the next request to retry
+        rebuilt.await?;
+        self.shards.clear_stale(project_id, generation);
+
+        Ok(())
+    }
+
+    /// Catches a project up with the Cache before a decision needing read-your-writes right
+    /// after a change: its settings are reapplied, and its schema, entities and policies
+    /// rebuilt when they differ from the Cache, this node having missed or not yet applied
+    /// their events. Returns whether any was rebuilt.
+    ///
+    /// The versions of the project and of the admin project, whose guardrails it merges, are
+    /// read from the Cache first: while neither advanced since the last sync nothing is
+    /// compared, so a sync only loads the project again after a change.
+    pub async fn project_sync(&self, project_id: &Uuid) -> Result<bool, CedrusError> {
+        self.project_refresh(project_id).await?;
+        // Read before the project itself, a change landing in between is caught next time
+        let versions = (
+            self.cache.project_get_version(project_id).await?,
+            self.cache.project_get_version(&Uuid::nil()).await?,
+        );
+        let Some(project) = self.cache.project_get(project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        if !self.project_cedar_policies.contains_key(project_id) {
+            self.project_cache_load(&project, true).await?;
+            self.project_synced_versions.insert(*project_id, versions);
+            return Ok(true);
+        }
+        self.on_project_update(&project);
+
+        if self
+            .project_synced_versions
+            .get(project_id)
+            .is_some_and(|synced| *synced == versions)
+        {
+            self.event_staleness.applied(project_id, None);
+            return Ok(false);
+        }
 
-            let cache_schema = self.cache.project_get_schema(&project.id).await?;
-            if let Some(schema) = &cache_schema {
-                self.on_project_schema_set(&project.id, schema)?;
+        let mut synced = false;
+        let cache_schema = self.cache.project_get_schema(project_id).await?;
+        let schema = cache_schema
+            .clone()
+            .map(|schema| self.with_common_types(schema));
+        let memory_schema = self
+            .project_schemas
+            .get(project_id)
+            .map(|schema| schema.clone());
+        if serde_json::to_value(&schema)? != serde_json::to_value(&memory_schema)? {
+            match &cache_schema {
+                Some(schema) => self.on_project_schema_set(project_id, schema)?,
+                None => self.on_project_schema_del(project_id)?,
             }
+            synced = true;
+        }
+
+        let mut entities = self.cache_entities(project_id, &[]).await?;
+        if let Some(schema) = &cache_schema {
+            entities.extend(Self::schema_enum_entities(schema));
+        }
+        let schema = cache_schema.map(|s| self.with_common_types(s));
+        let fingerprint = entities_fingerprint(&entities, schema.as_ref())?;
+        if self
+            .project_entities_fingerprints
+            .get(project_id)
+            .is_none_or(|current| *current != fingerprint)
+        {
+            self.on_project_entities(project_id).await?;
+            synced = true;
+        }
 
-            self.on_project_entities(&project.id).await?;
-            self.on_project_policy_set(&project.id).await?;
+        let cache_policy_set = self.cache.project_get_policy_set(project_id).await?;
+        let (policy_set, _) = self.live_policy_set(project_id, cache_policy_set);
+        let version = policy_set_version(&policy_set)?;
+        if self
+            .project_cedar_policies
+            .get(project_id)
+            .is_none_or(|current| current.version != version)
+        {
+            self.on_project_policy_set(project_id).await?;
+            self.on_project_references(project_id, None).await?;
+            synced = true;
         }
 
-        Ok(())
+        self.project_synced_versions.insert(*project_id, versions);
+        self.event_staleness.applied(project_id, None);
+        Ok(synced)
+    }
+
+    // Whether to leave the rebuild of a project changed by an event to its owner, marking it
+    // stale on this node
+    fn is_rebuild_deferred(&self, project_id: &Uuid) -> bool {
+        if self.shards.owns(project_id) {
+            return false;
+        }
+        self.shards.mark_stale(project_id);
+        true
     }
 
     fn on_project_set(&self, project: &Project) -> Result<(), CedrusError> {
+        self.project_schemas.remove(&project.id);
         self.project_cedar_schemas.insert(project.id, None);
         self.project_cedar_entities
-            .insert(project.id, cedar_policy::Entities::empty());
-        self.project_cedar_policies
-            .insert(project.id, cedar_policy::PolicySet::new());
+            .insert(project.id, Arc::new(cedar_policy::Entities::empty()));
+        self.project_entities_fingerprints
+            .insert(project.id, entities_fingerprint(&[], None)?);
+        self.project_cedar_policies.insert(
+            project.id,
+            Arc::new(CompiledPolicies::new(
+                cedar_policy::PolicySet::new(),
+                policy_set_version(&PolicySet::default())?,
+            )),
+        );
+        for resource in [
+            ProjectResource::Schema,
+            ProjectResource::Entities,
+            ProjectResource::Policies,
+        ] {
+            self.project_modified.touch(&project.id, resource);
+        }
+        self.on_project_update(project);
 
         Ok(())
     }
 
+    // Refresh the project settings without resetting its compiled Cedar structures
+    fn on_project_update(&self, project: &Project) {
+        if let Some(time_context) = &project.time_context {
+            self.project_time_contexts
+                .insert(project.id, time_context.clone());
+        } else {
+            self.project_time_contexts.remove(&project.id);
+        }
+        if project.read_only {
+            self.read_only_projects.insert(project.id);
+        } else {
+            self.read_only_projects.remove(&project.id);
+        }
+        if project.write_behind {
+            self.write_behind_projects.insert(project.id);
+        } else {
+            self.write_behind_projects.remove(&project.id);
+        }
+        if project.history {
+            self.history_projects.insert(project.id);
+        } else {
+            self.history_projects.remove(&project.id);
+        }
+        if project.request_validation == RequestValidation::Permissive {
+            self.permissive_projects.insert(project.id);
+        } else {
+            self.permissive_projects.remove(&project.id);
+        }
+        if project.linked_entity_removal == LinkedEntityRemoval::Cascade {
+            self.cascade_link_projects.insert(project.id);
+        } else {
+            self.cascade_link_projects.remove(&project.id);
+        }
+        if let Some(principal) = &project.anonymous_principal {
+            self.anonymous_principals
+                .insert(project.id, principal.clone());
+        } else {
+            self.anonymous_principals.remove(&project.id);
+        }
+        if let Some(millis) = project.evaluation_timeout_millis {
+            self.project_evaluation_timeouts
+                .insert(project.id, Duration::from_millis(millis));
+        } else {
+            self.project_evaluation_timeouts.remove(&project.id);
+        }
+        if let Some(source) = &project.gitops {
+            self.gitops_projects.insert(project.id, source.clone());
+        } else {
+            self.gitops_projects.remove(&project.id);
+        }
+        if let Some(environment) = &project.environment {
+            self.project_environments
+                .insert(project.id, environment.clone());
+        } else {
+            self.project_environments.remove(&project.id);
+        }
+        if let Some(notifications) = &project.notifications {
+            self.project_notifications
+                .insert(project.id, notifications.clone());
+        } else {
+            self.project_notifications.remove(&project.id);
+        }
+        match &project.candidate {
+            Some(candidate) => {
+                let compiled = self
+                    .project_candidates
+                    .get(&project.id)
+                    .is_some_and(|shadow| shadow.candidate == *candidate);
+                if !compiled {
+                    match self.candidate_compile(candidate) {
+                        Ok(shadow) => {
+                            self.project_candidates.insert(project.id, shadow);
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                "cedrus: on_project_update: candidate of {}: {e}",
+                                project.id
+                            );
+                            self.project_candidates.remove(&project.id);
+                        }
+                    }
+                }
+            }
+            None => {
+                self.project_candidates.remove(&project.id);
+            }
+        }
+    }
+
     fn on_project_del(&self, project_id: &Uuid, api_keys: &[String]) -> Result<(), CedrusError> {
+        self.token_cache.invalidate(project_id);
+        self.project_schemas.remove(project_id);
         self.project_cedar_schemas.remove(project_id);
         self.project_cedar_entities.remove(project_id);
+        self.project_entities_fingerprints.remove(project_id);
+        self.pending_compiled_entities.remove(project_id);
         self.project_cedar_policies.remove(project_id);
+        self.project_synced_versions.remove(project_id);
+        self.project_time_contexts.remove(project_id);
+        self.project_entity_expiries.remove(project_id);
+        self.project_relations.remove(project_id);
+        self.project_references.remove(project_id);
+        self.read_only_projects.remove(project_id);
+        self.write_behind_projects.remove(project_id);
+        self.history_projects.remove(project_id);
+        self.permissive_projects.remove(project_id);
+        self.cascade_link_projects.remove(project_id);
+        self.anonymous_principals.remove(project_id);
+        self.project_evaluation_timeouts.remove(project_id);
+        self.evaluation_timeouts.remove(project_id);
+        self.evaluation_slots.remove(project_id);
+        self.gitops_projects.remove(project_id);
+        self.gitops_locks.remove(project_id);
+        self.entities_locks.remove(project_id);
+        self.shards.remove(project_id);
+        self.project_environments.remove(project_id);
+        self.project_notifications.remove(project_id);
+        self.project_candidates.remove(project_id);
+        self.notifications.remove(project_id);
+        self.deny_rates.remove(project_id);
+        self.project_epochs.remove(project_id);
+        self.project_modified.remove(project_id);
+        self.event_staleness.remove(project_id);
+        self.context_telemetry.remove(project_id);
 
         for api_key in api_keys {
             self.api_keys.remove(api_key);
@@ -287,13 +1169,26 @@ impl Cedrus {
 
     fn on_project_apikeys_set(&self, api_keys: &[ApiKey]) -> Result<(), CedrusError> {
         for api_key in api_keys {
-            self.api_keys
-                .insert(api_key.key.clone(), api_key.owner.clone());
+            self.api_keys.insert(api_key.key.clone(), api_key.clone());
         }
 
         Ok(())
     }
 
+    /// Whether the admin project schema declares `type_name`. Until it is loaded, every
+    /// type is considered declared.
+    pub fn is_admin_entity_type(&self, type_name: &str) -> bool {
+        self.project_schemas
+            .get(&Uuid::nil())
+            .is_none_or(|schema| schema.entity_type(type_name).is_some())
+    }
+
+    /// Whether credential-less requests may act as `principal`, never one of the admin project
+    /// types, such as a user, whose rights extend beyond the project.
+    fn is_anonymous_principal_valid(&self, principal: Option<&EntityUid>) -> bool {
+        principal.is_none_or(|principal| !self.is_admin_entity_type(principal.type_name()))
+    }
+
     fn on_project_apikeys_del(&self, api_keys: &[String]) -> Result<(), CedrusError> {
         for api_key in api_keys {
             self.api_keys.remove(api_key);
@@ -307,103 +1202,563 @@ impl Cedrus {
         project_id: &Uuid,
         identity_source: &IdentitySource,
     ) -> Result<(), CedrusError> {
-        let authorizer = authorizer_factory(&identity_source.configuration).await?;
-        let authorizer = Authorizer::new(identity_source.clone(), authorizer);
-        self.project_authorizers
-            .insert(*project_id, Some(authorizer));
+        self.token_cache.invalidate(project_id);
+        match authorizer_factory(&identity_source.configuration).await {
+            Ok(authorizer) => {
+                let authorizer = Authorizer::new(identity_source.clone(), authorizer);
+                self.project_authorizers
+                    .insert(*project_id, Some(authorizer));
+                self.pending_identity_sources.remove(project_id);
+            }
+            Err(e) => {
+                // Degraded mode: JWT authentication is unavailable for the project until
+                // `authorizers_rebuild` succeeds, API keys keep working.
+                tracing::error!(
+                    "cedrus: on_project_identity_source_set: project {project_id}: {e}"
+                );
+                self.project_authorizers.insert(*project_id, None);
+                self.pending_identity_sources
+                    .insert(*project_id, identity_source.clone());
+            }
+        }
 
         Ok(())
     }
 
-    fn on_project_identity_source_del(&self, project_id: &Uuid) -> Result<(), CedrusError> {
+    /// Retries building the JWT authorizers that failed, returning how many are still pending.
+    pub async fn authorizers_rebuild(&self) -> usize {
+        let pending: Vec<(Uuid, IdentitySource)> = self
+            .pending_identity_sources
+            .iter()
+            .map(|r| (*r.key(), r.value().clone()))
+            .collect();
+
+        for (project_id, identity_source) in pending {
+            let _ = self
+                .on_project_identity_source_set(&project_id, &identity_source)
+                .await;
+        }
+
+        self.pending_identity_sources.len()
+    }
+
+    fn on_project_identity_source_del(&self, project_id: &Uuid) -> Result<(), CedrusError> {
+        self.token_cache.invalidate(project_id);
         self.project_authorizers.remove(project_id);
+        self.pending_identity_sources.remove(project_id);
 
         Ok(())
     }
 
     fn on_project_schema_set(&self, project_id: &Uuid, schema: &Schema) -> Result<(), CedrusError> {
+        let schema = self.with_common_types(schema.clone());
         let cedar_schema: Option<cedar_policy::Schema> = Some(schema.clone().try_into()?);
         self.project_cedar_schemas.insert(*project_id, cedar_schema);
+        self.project_schemas.insert(*project_id, schema);
+        self.project_modified
+            .touch(project_id, ProjectResource::Schema);
 
         Ok(())
     }
 
+    /// Schema with the common types it refers to from the shared library, as Cedar builds it.
+    pub fn with_common_types(&self, schema: Schema) -> Schema {
+        schema.with_common_types(&self.common_types.read().unwrap())
+    }
+
+    /// Shared library of common types, referenced by name from the schemas of any project.
+    pub fn common_types(&self) -> HashMap<String, TypeJson> {
+        self.common_types.read().unwrap().clone()
+    }
+
     fn on_project_schema_del(&self, project_id: &Uuid) -> Result<(), CedrusError> {
         self.project_cedar_schemas.insert(*project_id, None);
+        self.project_schemas.remove(project_id);
+        self.project_modified
+            .touch(project_id, ProjectResource::Schema);
 
         Ok(())
     }
 
+    /// Entities of the enumerated entity types of a schema, one per value.
+    pub fn schema_enum_entities(schema: &Schema) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        for (ns_name, ns) in &schema.0 {
+            for (entity_type_name, entity_type) in &ns.entity_types {
+                if let Some(r#enum) = &entity_type.r#enum {
+                    let type_name = if ns_name.is_empty() {
+                        entity_type_name.to_string()
+                    } else {
+                        format!("{}::{}", ns_name, entity_type_name)
+                    };
+                    for enum_value in r#enum {
+                        let uid = EntityUid::from(format!("{}::{}", type_name, enum_value));
+                        entities.push(Entity::new(uid, HashMap::new(), HashSet::new()));
+                    }
+                }
+            }
+        }
+        entities
+    }
+
+    /// Converts every entity to Cedar up front, as an evaluation may reach any of them through
+    /// the hierarchy, in chunks of `ENTITY_CONVERSION_CHUNK_SIZE` on the blocking pool with at
+    /// most one chunk per available core converting at a time. Entities that fail to convert
+    /// are logged and skipped.
+    async fn to_cedar_entities(
+        mut entities: Vec<Entity>,
+        cedar_schema: Option<Arc<cedar_policy::Schema>>,
+    ) -> Vec<cedar_policy::Entity> {
+        let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let semaphore = Arc::new(Semaphore::new(workers));
+        let mut tasks = JoinSet::new();
+        while !entities.is_empty() {
+            let rest = entities.split_off(entities.len().min(ENTITY_CONVERSION_CHUNK_SIZE));
+            let chunk = std::mem::replace(&mut entities, rest);
+            let cedar_schema = cedar_schema.clone();
+            let semaphore = semaphore.clone();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                tokio::task::spawn_blocking(move || {
+                    chunk
+                        .iter()
+                        .filter_map(|entity| {
+                            match entity.to_cedar_entity(cedar_schema.as_deref()) {
+                                Ok(cedar_entity) => Some(cedar_entity),
+                                Err(e) => {
+                                    tracing::error!(
+                                        "cedrus: on_project_entities: Entity: {:#?}",
+                                        entity
+                                    );
+                                    tracing::error!("cedrus: on_project_entities: error: {:?}", e);
+                                    None
+                                }
+                            }
+                        })
+                        .collect::<Vec<cedar_policy::Entity>>()
+                })
+                .await
+            });
+        }
+
+        let mut cedar_entities = Vec::new();
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok(Ok(converted)) => cedar_entities.extend(converted),
+                Ok(Err(e)) | Err(e) => {
+                    tracing::error!("cedrus: to_cedar_entities: task failed: {:?}", e)
+                }
+            }
+        }
+
+        cedar_entities
+    }
+
+    /// Entities of a project from the Cache, all of them when `entity_uids` is empty. When
+    /// the Cache evicted them, they are reloaded from the Database after flushing the writes
+    /// deferred for the project.
+    async fn cache_entities(
+        &self,
+        project_id: &Uuid,
+        entity_uids: &[EntityUid],
+    ) -> Result<Vec<Entity>, CedrusError> {
+        match self
+            .cache
+            .project_get_entities(project_id, entity_uids)
+            .await
+        {
+            Err(CacheError::Evicted) => {}
+            result => return Ok(result?),
+        }
+
+        let generation = self.cache.entities_generation(project_id);
+        if self.write_behind.is_pending(project_id) {
+            self.write_behind_flush().await?;
+        }
+        let entities = self
+            .db
+            .project_entities_load(project_id, &Query::new())
+            .await?
+            .items;
+        // Written to or evicted again meanwhile, the project is restored on a later read
+        if !self
+            .cache
+            .project_replace_entities(project_id, &entities, generation)
+            .await?
+        {
+            tracing::debug!(
+                "cedrus: entities of project {} changed while reloaded, not restored",
+                project_id
+            );
+        }
+
+        if entity_uids.is_empty() {
+            return Ok(entities);
+        }
+        let entity_uids = entity_uids.iter().collect::<HashSet<_>>();
+        Ok(entities
+            .into_iter()
+            .filter(|e| entity_uids.contains(e.uid()))
+            .collect())
+    }
+
+    fn entities_lock(&self, project_id: &Uuid) -> Arc<tokio::sync::Mutex<()>> {
+        self.entities_locks
+            .entry(*project_id)
+            .or_default()
+            .value()
+            .clone()
+    }
+
     // Genarate Cedar Entities from cache
     async fn on_project_entities(&self, project_id: &Uuid) -> Result<(), CedrusError> {
-        let mut cache_entities = self.cache.project_get_entities(project_id, &[]).await?;
+        let lock = self.entities_lock(project_id);
+        let _lock = lock.lock().await;
+
+        let mut cache_entities = self.cache_entities(project_id, &[]).await?;
+        self.project_modified
+            .touch(project_id, ProjectResource::Entities);
+
+        match cache_entities.iter().filter_map(|e| e.expires_at()).min() {
+            Some(expires_at) => {
+                self.project_entity_expiries
+                    .insert(*project_id, *expires_at);
+            }
+            None => {
+                self.project_entity_expiries.remove(project_id);
+            }
+        }
+        self.project_relations
+            .insert(*project_id, RelationIndex::new(&cache_entities));
 
         // Add enum entities if has schema
         let cache_schema: Option<Schema> = self.cache.project_get_schema(project_id).await?;
-        if let Some(schema) = cache_schema.clone() {
-            for ns_name in schema.0.keys() {
-                let ns = schema.0.get(ns_name).unwrap();
-                for entity_type_name in ns.entity_types.keys() {
-                    let entity_type = ns.entity_types.get(entity_type_name).unwrap();
-                    if let Some(r#enum) = &entity_type.r#enum {
-                        let type_name = if ns_name.is_empty() {
-                            entity_type_name.to_string()
-                        } else {
-                            format!("{}::{}", ns_name, entity_type_name)
-                        };
-                        for enum_value in r#enum {
-                            let uid = EntityUid::from(format!("{}::{}", type_name, enum_value));
-                            let entity = Entity::new(uid, HashMap::new(), HashSet::new());
-                            cache_entities.push(entity);
-                        }
-                    }
+        if let Some(schema) = &cache_schema {
+            cache_entities.extend(Self::schema_enum_entities(schema));
+        }
+
+        // Common types take part in validation, so they change the fingerprint too
+        let schema = cache_schema.map(|s| self.with_common_types(s));
+        let fingerprint = entities_fingerprint(&cache_entities, schema.as_ref())?;
+        let unchanged = self
+            .project_entities_fingerprints
+            .get(project_id)
+            .is_some_and(|current| *current == fingerprint);
+        if unchanged && self.project_cedar_entities.contains_key(project_id) {
+            return Ok(());
+        }
+
+        if self.compiled_entities
+            && let Some(compiled) = self.cache.project_get_compiled_entities(project_id).await?
+            && compiled.fingerprint == fingerprint
+        {
+            match cedar_policy::Entities::decode(compiled.data.as_slice()) {
+                Ok(cedar_entities) => {
+                    self.project_cedar_entities
+                        .insert(*project_id, Arc::new(cedar_entities));
+                    self.project_entities_fingerprints
+                        .insert(*project_id, fingerprint);
+                    return Ok(());
                 }
+                Err(e) => tracing::warn!("cedrus: on_project_entities: compiled entities: {e}"),
             }
         }
 
         let cedar_schema: Option<cedar_policy::Schema> =
-            cache_schema.map(|s| s.try_into()).transpose()?;
+            schema.map(|s| s.try_into()).transpose()?;
 
-        let mut cedar_entities_list: Vec<cedar_policy::Entity> = Vec::new();
-        for entity in cache_entities.iter() {
-            match entity.to_cedar_entity(cedar_schema.as_ref()) {
-                Ok(cedar_entity) => cedar_entities_list.push(cedar_entity),
+        let cedar_schema = cedar_schema.map(Arc::new);
+        let cedar_entities_list =
+            Self::to_cedar_entities(cache_entities, cedar_schema.clone()).await;
+
+        let cedar_entities: cedar_policy::Entities =
+            cedar_policy::Entities::from_entities(cedar_entities_list, cedar_schema.as_deref())?;
+
+        // Only once stored, a failed build leaves the fingerprint of the entities it replaces
+        self.project_cedar_entities
+            .insert(*project_id, Arc::new(cedar_entities));
+        self.project_entities_fingerprints
+            .insert(*project_id, fingerprint);
+        if self.compiled_entities {
+            self.pending_compiled_entities.insert(*project_id);
+        }
+        self.on_cache_evictions();
+
+        Ok(())
+    }
+
+    // Drops the compiled entities, policies and JWT authorizer of the projects whose entities
+    // the Cache evicted, so its memory bound holds for this node too. They are marked stale
+    // and rebuilt by `project_refresh` on their next request. The admin project authorizes
+    // every request and stays resident.
+    fn on_cache_evictions(&self) {
+        for project_id in self.cache.take_evictions() {
+            if project_id.is_nil() {
+                continue;
+            }
+            self.project_cedar_entities.remove(&project_id);
+            self.project_entities_fingerprints.remove(&project_id);
+            self.pending_compiled_entities.remove(&project_id);
+            self.project_relations.remove(&project_id);
+            self.project_cedar_policies.remove(&project_id);
+            self.project_synced_versions.remove(&project_id);
+            if self.project_authorizers.remove(&project_id).is_some() {
+                self.token_cache.invalidate(&project_id);
+            }
+            self.shards.mark_stale(&project_id);
+        }
+    }
+
+    /// Persists to the cache the Cedar entities of the projects rebuilt since the last
+    /// snapshot, so a burst of mutations encodes each project once instead of on every change.
+    /// Returns the number of projects persisted; those failing are retried on the next one.
+    pub async fn compiled_entities_snapshot(&self) -> usize {
+        let project_ids: Vec<Uuid> = self.pending_compiled_entities.iter().map(|r| *r).collect();
+
+        let mut persisted = 0;
+        for project_id in project_ids {
+            self.pending_compiled_entities.remove(&project_id);
+
+            // Both read between rebuilds, so the fingerprint is the one of the entities
+            let lock = self.entities_lock(&project_id);
+            let guard = lock.lock().await;
+            let Some(fingerprint) = self
+                .project_entities_fingerprints
+                .get(&project_id)
+                .map(|r| r.value().clone())
+            else {
+                continue;
+            };
+            let Some(data) = self
+                .project_cedar_entities
+                .get(&project_id)
+                .map(|r| r.value().encode())
+            else {
+                continue;
+            };
+            drop(guard);
+
+            let compiled = CompiledEntities { fingerprint, data };
+            match self
+                .cache
+                .project_set_compiled_entities(&project_id, &compiled)
+                .await
+            {
+                Ok(()) => persisted += 1,
                 Err(e) => {
-                    tracing::error!("cedrus: on_project_entities: Entity: {:#?}", entity);
-                    tracing::error!("cedrus: on_project_entities: error: {:?}", e);
+                    tracing::warn!("cedrus: compiled_entities_snapshot: {project_id}: {e}");
+                    self.pending_compiled_entities.insert(project_id);
                 }
             }
         }
+        self.on_cache_evictions();
 
-        let cedar_entities: cedar_policy::Entities =
-            cedar_policy::Entities::from_entities(cedar_entities_list, cedar_schema.as_ref())?;
+        persisted
+    }
 
-        {
-            self.project_cedar_entities
-                .insert(*project_id, cedar_entities);
+    fn is_guardrail(policy: &Policy) -> bool {
+        policy.effect == PolicyEffect::Forbid
+            && policy.annotations.contains_key(ANNOTATION_GUARDRAIL)
+    }
+
+    async fn on_project_policy_set(&self, project_id: &Uuid) -> Result<(), CedrusError> {
+        let guardrails_changed = self.compile_project_policy_set(project_id).await?;
+        if !guardrails_changed {
+            return Ok(());
+        }
+
+        let project_ids: Vec<Uuid> = self
+            .project_cedar_policies
+            .iter()
+            .map(|entry| *entry.key())
+            .filter(|id| !id.is_nil())
+            .collect();
+        for id in project_ids {
+            if let Err(e) = self.compile_project_policy_set(&id).await {
+                tracing::warn!("cedrus: on_project_policy_set: guardrails of {id}: {e}");
+            }
+        }
+
+        let candidates: Vec<(Uuid, ProjectCandidate)> = self
+            .project_candidates
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().candidate.clone()))
+            .collect();
+        for (id, candidate) in candidates {
+            match self.candidate_compile(&candidate) {
+                Ok(shadow) => {
+                    self.project_candidates.insert(id, shadow);
+                }
+                Err(e) => tracing::warn!("cedrus: on_project_policy_set: candidate of {id}: {e}"),
+            }
         }
 
         Ok(())
     }
 
-    async fn on_project_policy_set(&self, project_id: &Uuid) -> Result<(), CedrusError> {
-        let cache_policy_set = self.cache.project_get_policy_set(project_id).await?;
+    /// Updates the index of the entities the policies of a project name with the policies,
+    /// templates or links a policy event changed, or rebuilds it without event.
+    async fn on_project_references(
+        &self,
+        project_id: &Uuid,
+        event: Option<&EventType>,
+    ) -> Result<(), CedrusError> {
+        let event = match event {
+            Some(event) if self.project_references.contains_key(project_id) => event,
+            _ => {
+                let policy_set = self.cache.project_get_policy_set(project_id).await?;
+                self.project_references
+                    .insert(*project_id, ReferenceIndex::new(&policy_set));
+                return Ok(());
+            }
+        };
 
-        let exclude = self.exclude_policy_annotation.clone().unwrap_or_default();
+        let (policies, templates, template_links) = match event {
+            EventType::ProjectAddPolicies(_, _) => {
+                let policies = self.cache.project_get_policies(project_id).await?;
+                (policies, HashMap::new(), Vec::new())
+            }
+            EventType::ProjectAddTemplates(_, _) | EventType::ProjectAddTemplateLinks(_, _) => {
+                let templates = self.cache.project_get_templates(project_id).await?;
+                let links = self.cache.project_get_template_links(project_id).await?;
+                (HashMap::new(), templates, links)
+            }
+            _ => (HashMap::new(), HashMap::new(), Vec::new()),
+        };
+
+        let Some(mut index) = self.project_references.get_mut(project_id) else {
+            return Ok(());
+        };
+        match event {
+            EventType::ProjectAddPolicies(_, policy_ids) => {
+                for policy_id in policy_ids {
+                    match policies.get(policy_id) {
+                        Some(policy) => index.set_policy(policy_id, policy),
+                        None => index.remove_policy(policy_id),
+                    }
+                }
+            }
+            EventType::ProjectRemovePolicies(_, policy_ids) => {
+                policy_ids.iter().for_each(|id| index.remove_policy(id));
+            }
+            EventType::ProjectAddTemplates(_, template_ids) => {
+                for link in template_links
+                    .iter()
+                    .filter(|link| template_ids.contains(&link.template_id))
+                {
+                    index.set_template_link(link, templates.get(&link.template_id));
+                }
+            }
+            EventType::ProjectRemoveTemplates(_, template_ids) => {
+                for template_id in template_ids {
+                    for link_id in index.template_links_of(template_id) {
+                        index.remove_template_link(&link_id);
+                    }
+                }
+            }
+            EventType::ProjectAddTemplateLinks(_, link_ids) => {
+                for link in template_links
+                    .iter()
+                    .filter(|link| link_ids.contains(&link.new_id))
+                {
+                    index.set_template_link(link, templates.get(&link.template_id));
+                }
+            }
+            EventType::ProjectRemoveTemplateLinks(_, link_ids) => {
+                link_ids
+                    .iter()
+                    .for_each(|id| index.remove_template_link(id));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    // Adds the guardrails of the admin project to the static policies of another project
+    fn with_guardrails(
+        static_policies: &mut HashMap<PolicyId, Policy>,
+        guardrails: &HashMap<PolicyId, Policy>,
+    ) {
+        for (policy_id, policy) in guardrails {
+            static_policies.insert(
+                format!("{GUARDRAIL_ID_PREFIX}{policy_id}").into(),
+                policy.clone(),
+            );
+        }
+    }
+
+    /// Compiles the candidate policy set of a project as its live one would be, excluded
+    /// policies left out and guardrails included.
+    fn candidate_compile(
+        &self,
+        candidate: &ProjectCandidate,
+    ) -> Result<CandidateShadow, CedrusError> {
+        let mut policy_set = candidate.policy_set.clone();
+        policy_set
+            .static_policies
+            .retain(|_key, policy| !self.is_policy_excluded(&policy.annotations));
+        policy_set
+            .templates
+            .retain(|_key, template| !self.is_policy_excluded(&template.annotations));
+        let templates = &policy_set.templates;
+        policy_set
+            .template_links
+            .retain(|link| templates.contains_key(&link.template_id));
+        Self::with_guardrails(
+            &mut policy_set.static_policies,
+            &self.guardrails.read().unwrap(),
+        );
+
+        let version = policy_set_version(&policy_set)?;
+        let policies: cedar_policy::PolicySet = policy_set.try_into()?;
+        Ok(CandidateShadow::new(candidate.clone(), policies, version))
+    }
+
+    // Policy set of a project as compiled from its Cache entries, without the excluded
+    // policies, and with the guardrails unless it is the admin project, whose guardrails are
+    // returned apart
+    fn live_policy_set(
+        &self,
+        project_id: &Uuid,
+        cache_policy_set: PolicySet,
+    ) -> (PolicySet, Option<HashMap<PolicyId, Policy>>) {
+        let guardrails = self.guardrails.read().unwrap();
+        self.effective_policy_set(project_id, cache_policy_set, &guardrails)
+    }
 
-        let static_policies: HashMap<PolicyId, Policy> = cache_policy_set
+    // Policy set of a project as evaluated, given the guardrails of the admin project to
+    // merge into it
+    fn effective_policy_set(
+        &self,
+        project_id: &Uuid,
+        policy_set: PolicySet,
+        admin_guardrails: &HashMap<PolicyId, Policy>,
+    ) -> (PolicySet, Option<HashMap<PolicyId, Policy>>) {
+        let mut static_policies: HashMap<PolicyId, Policy> = policy_set
             .static_policies
             .into_iter()
-            .filter(|(_key, policy)| !policy.annotations.contains_key(&exclude))
+            .filter(|(_key, policy)| !self.is_policy_excluded(&policy.annotations))
             .collect();
 
-        let templates: HashMap<PolicyId, Template> = cache_policy_set
+        let guardrails = if project_id.is_nil() {
+            Some(
+                static_policies
+                    .extract_if(|_key, policy| Self::is_guardrail(policy))
+                    .collect(),
+            )
+        } else {
+            Self::with_guardrails(&mut static_policies, admin_guardrails);
+            None
+        };
+
+        let templates: HashMap<PolicyId, Template> = policy_set
             .templates
             .into_iter()
-            .filter(|(_key, policy)| !policy.annotations.contains_key(&exclude))
+            .filter(|(_key, policy)| !self.is_policy_excluded(&policy.annotations))
             .collect();
 
-        let template_links: Vec<TemplateLink> = cache_policy_set
+        let template_links: Vec<TemplateLink> = policy_set
             .template_links
             .into_iter()
             .filter(|link| templates.contains_key(&link.template_id))
@@ -414,29 +1769,189 @@ impl Cedrus {
             templates,
             template_links,
         };
+        (policy_set, guardrails)
+    }
 
+    /// Compiles the policy set of a project, the guardrails of the admin project included.
+    /// Compiling the admin project collects them instead, and tells whether they changed.
+    async fn compile_project_policy_set(&self, project_id: &Uuid) -> Result<bool, CedrusError> {
+        let cache_policy_set = self.cache.project_get_policy_set(project_id).await?;
+        let (policy_set, guardrails) = self.live_policy_set(project_id, cache_policy_set);
+
+        let mut guardrails_changed = false;
+        if let Some(guardrails) = guardrails {
+            let mut current = self.guardrails.write().unwrap();
+            if *current != guardrails {
+                *current = guardrails;
+                guardrails_changed = true;
+            }
+        }
+
+        let version = policy_set_version(&policy_set)?;
         let cedar_policy_set: cedar_policy::PolicySet = policy_set.try_into()?;
-        self.project_cedar_policies
-            .insert(*project_id, cedar_policy_set);
+        let previous = self.project_cedar_policies.insert(
+            *project_id,
+            Arc::new(CompiledPolicies::new(cedar_policy_set, version.clone())),
+        );
+        if previous.is_some_and(|previous| previous.version != version) {
+            self.deny_rates.policy_changed(project_id);
+        }
+        self.project_modified
+            .touch(project_id, ProjectResource::Policies);
 
-        Ok(())
+        Ok(guardrails_changed)
     }
 
     async fn publish(&self, message: Event) {
+        if !dry_run::allows_side_effect("publish") {
+            return;
+        }
+        self.cluster.published();
         self.update(&message, true).await;
+        self.policy_change_notify(message.msg());
         let _ = self.pubsub.publish(message).await;
     }
 
+    /// Publishes the heartbeat of this node to the others.
+    pub async fn cluster_heartbeat(&self) {
+        let heartbeat = self
+            .cluster
+            .heartbeat(self.id, self.project_cedar_policies.len());
+        if let Err(e) = self
+            .pubsub
+            .publish(Event::node_heartbeat(self.id, heartbeat))
+            .await
+        {
+            tracing::warn!("cedrus: cluster_heartbeat: {e}");
+        }
+    }
+
+    /// How long the data of a project may have changed on other nodes without this one
+    /// knowing, `None` for a project it has not loaded.
+    pub fn project_staleness(&self, project_id: &Uuid) -> Option<chrono::Duration> {
+        self.event_staleness
+            .staleness(project_id, self.cluster.synced_at())
+    }
+
+    /// Nodes of the cluster as this one sees them.
+    pub fn cluster_status(&self) -> ClusterStatus {
+        let heartbeat = self
+            .cluster
+            .heartbeat(self.id, self.project_cedar_policies.len());
+        self.cluster.status(heartbeat)
+    }
+
+    /// Queues an alert of a project for the channels of the configuration and its own.
+    pub fn notify(&self, kind: AlertKind, project_id: &Uuid, message: String) {
+        if !self.notifications.is_enabled() {
+            return;
+        }
+        self.notifications.notify(Alert {
+            kind,
+            project_id: *project_id,
+            node_id: self.id,
+            message,
+            at: chrono::Utc::now(),
+            project: self
+                .project_notifications
+                .get(project_id)
+                .map(|notifications| notifications.clone()),
+        });
+    }
+
+    // Only the node making the change alerts on it
+    fn policy_change_notify(&self, msg: &EventType) {
+        let (project_id, change, ids) = match msg {
+            EventType::ProjectAddPolicies(id, ids) => (id, "Policies added", ids),
+            EventType::ProjectRemovePolicies(id, ids) => (id, "Policies removed", ids),
+            EventType::ProjectAddTemplates(id, ids) => (id, "Templates added", ids),
+            EventType::ProjectRemoveTemplates(id, ids) => (id, "Templates removed", ids),
+            EventType::ProjectAddTemplateLinks(id, ids) => (id, "Template links added", ids),
+            EventType::ProjectRemoveTemplateLinks(id, ids) => (id, "Template links removed", ids),
+            _ => return,
+        };
+        let mut ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        ids.sort();
+        self.notify(
+            AlertKind::PolicyChange,
+            project_id,
+            format!("{}: {}", change, ids.join(", ")),
+        );
+    }
+
+    /// Counts the decisions served for a project, alerting when its Deny rate spikes, or
+    /// when that of one of the `actions` requested rises past its baseline after a policy
+    /// change. `actions` is only needed while `deny_rates` is enabled.
+    pub fn decisions_record(
+        &self,
+        project_id: &Uuid,
+        actions: &[EntityUid],
+        responses: &[Response],
+    ) {
+        if self.deny_rates.is_enabled() {
+            let decisions = actions
+                .iter()
+                .zip(responses)
+                .map(|(action, response)| (action, response.decision == Decision::Deny));
+            for spike in self.deny_rates.record(project_id, decisions) {
+                let version = self
+                    .project_cedar_policies
+                    .get(project_id)
+                    .map(|policies| policies.version.clone())
+                    .unwrap_or_default();
+                let message = format!(
+                    "Deny rate of {} rose from {:.0}% to {:.0}% over {} decisions since the policies changed to version {}",
+                    spike.action,
+                    spike.baseline * 100.0,
+                    spike.rate * 100.0,
+                    spike.decisions,
+                    version
+                );
+                tracing::warn!(target: "cedrus::anomaly", "Project {}: {}", project_id, message);
+                self.notify(AlertKind::DenyRateSpike, project_id, message);
+            }
+        }
+        if !self.notifications.is_enabled() {
+            return;
+        }
+        let denied = responses
+            .iter()
+            .filter(|response| response.decision == Decision::Deny)
+            .count() as u64;
+        let threshold = self
+            .project_notifications
+            .get(project_id)
+            .and_then(|notifications| notifications.deny_rate_threshold);
+        if let Some(rate) = self.notifications.deny_rate_record(
+            project_id,
+            responses.len() as u64 - denied,
+            denied,
+            threshold,
+        ) {
+            self.notify(
+                AlertKind::DenyRate,
+                project_id,
+                format!("{:.0}% of the recent decisions are Deny", rate * 100.0),
+            );
+        }
+    }
+
     pub fn is_allow(&self, principal: EntityUid, action: EntityUid, resource: EntityUid) -> bool {
-        let cedar_principal: cedar_policy::EntityUid = principal.into();
-        let cedar_action: cedar_policy::EntityUid = action.into();
-        let cedar_resource: cedar_policy::EntityUid = resource.into();
+        let context = self.with_time_context(&Uuid::nil(), &action, None, chrono::Utc::now());
+        let (Ok(cedar_principal), Ok(cedar_action), Ok(cedar_resource)) =
+            (principal.try_into(), action.try_into(), resource.try_into())
+        else {
+            return false;
+        };
+        let cedar_context = context
+            .and_then(|context| context.to_cedar_context(None).ok())
+            .unwrap_or_else(cedar_policy::Context::empty);
 
         let cedar_request_result = cedar_policy::Request::new(
             cedar_principal,
             cedar_action,
             cedar_resource,
-            cedar_policy::Context::empty(),
+            cedar_context,
             None,
         );
 
@@ -461,124 +1976,735 @@ impl Cedrus {
         }
     }
 
-    pub fn is_authorized(
+    /// Version of the policy set currently used to evaluate requests of a project.
+    pub fn project_policy_version(&self, project_id: &Uuid) -> Result<String, CedrusError> {
+        Ok(self.project_policies(project_id)?.version.clone())
+    }
+
+    /// Policy set currently used to evaluate requests of a project, with its version, to
+    /// evaluate requests against a version checked beforehand.
+    pub fn project_policies(
         &self,
         project_id: &Uuid,
-        principal: EntityUid,
-        action: EntityUid,
-        resource: EntityUid,
-        context: Option<Context>,
-    ) -> Result<Response, CedrusError> {
-        let cedar_request = {
-            let cedar_principal = principal.into();
-            let cedar_action = action.into();
-            let cedar_resource = resource.into();
+    ) -> Result<Arc<CompiledPolicies>, CedrusError> {
+        self.project_cedar_policies
+            .get(project_id)
+            .map(|policies| policies.value().clone())
+            .ok_or(CedrusError::NotFound)
+    }
 
-            let cedar_schema = self
-                .project_cedar_schemas
-                .get(project_id)
-                .ok_or(CedrusError::NotFound)?;
+    /// Digest of the slice of stored entities a request reads: the given entities and all
+    /// their ancestors, so samples evaluated against the same data share a digest.
+    pub fn project_entity_slice_digest(
+        &self,
+        project_id: &Uuid,
+        uids: &[EntityUid],
+    ) -> Result<String, CedrusError> {
+        let cedar_entities = self
+            .project_cedar_entities
+            .get(project_id)
+            .ok_or(CedrusError::NotFound)?;
 
-            let cedar_context = match context {
-                Some(value) => {
-                    let context_schema =
-                        cedar_schema.as_ref().map(|schema| (schema, &cedar_action));
-                    value.to_cedar_context(context_schema)?
+        let mut seen = HashSet::new();
+        let mut values = Vec::new();
+        for uid in uids {
+            let cedar_uid: cedar_policy::EntityUid = uid.clone().try_into()?;
+            let ancestors = cedar_entities
+                .ancestors(&cedar_uid)
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+            for cedar_uid in std::iter::once(cedar_uid).chain(ancestors) {
+                if !seen.insert(cedar_uid.to_string()) {
+                    continue;
                 }
-                _ => cedar_policy::Context::empty(),
-            };
+                if let Some(entity) = cedar_entities.get(&cedar_uid) {
+                    values.push(canonical_json(entity.to_json_value()?).to_string());
+                }
+            }
+        }
+        values.sort();
 
-            cedar_policy::Request::new(
-                cedar_principal,
-                cedar_action,
-                cedar_resource,
-                cedar_context,
-                cedar_schema.as_ref(),
-            )?
-        };
+        let mut hasher = Sha256::new();
+        for value in values {
+            hasher.update(b"\n");
+            hasher.update(value);
+        }
 
-        let authorizer = cedar_policy::Authorizer::new();
+        Ok(to_hex(&hasher.finalize()))
+    }
 
-        let answer = {
-            let cedar_entities = self
-                .project_cedar_entities
-                .get(project_id)
-                .ok_or(CedrusError::NotFound)?;
+    /// Explains a context rejected by Cedar in terms of the attribute paths and types of the
+    /// stored schema, falling back to the Cedar error when no specific attribute is at fault.
+    fn context_error(
+        &self,
+        project_id: &Uuid,
+        action: &EntityUid,
+        context: &Context,
+        error: cedar_policy::ContextJsonError,
+    ) -> CedrusError {
+        let errors = self
+            .project_schemas
+            .get(project_id)
+            .map(|schema| context.validate(&schema, action))
+            .unwrap_or_default();
 
-            let cedar_policies = self
-                .project_cedar_policies
-                .get(project_id)
-                .ok_or(CedrusError::NotFound)?;
+        match errors.is_empty() {
+            true => CedrusError::ContextJsonError(error),
+            false => CedrusError::ContextValidationError(errors),
+        }
+    }
 
-            authorizer.is_authorized(&cedar_request, &cedar_policies, &cedar_entities)
+    /// Rewrites the context attributes the schema declares as extension types into `__extn`
+    /// escapes, so callers can send plain strings and numbers. Decimals a Cedar `decimal`
+    /// cannot hold are rejected rather than rounded.
+    fn coerce_context(
+        &self,
+        project_id: &Uuid,
+        action: &EntityUid,
+        context: Option<Context>,
+    ) -> Result<Option<Context>, CedrusError> {
+        let Some(mut context) = context else {
+            return Ok(None);
         };
-
-        Ok(answer.into())
+        let errors = context.decimal_errors();
+        if !errors.is_empty() {
+            return Err(CedrusError::ValidationError(errors));
+        }
+        if let Some(schema) = self.project_schemas.get(project_id) {
+            context.coerce(&schema, action);
+        }
+        Ok(Some(context))
     }
 
-    pub fn is_authorized_batch(
+    /// Adds the project time attributes at `at` the caller did not supply. With a schema only
+    /// the attributes the action context declares are added, so requests keep validating.
+    fn with_time_context(
         &self,
         project_id: &Uuid,
-        requests: Vec<Request>,
-    ) -> Result<Vec<Response>, CedrusError> {
+        action: &EntityUid,
+        context: Option<Context>,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Option<Context> {
+        let Some(time_context) = self.project_time_contexts.get(project_id) else {
+            return context;
+        };
+        let declared = self
+            .project_schemas
+            .get(project_id)
+            .map(|schema| schema.action_context_attributes(action).unwrap_or_default());
+
+        let mut inserted = false;
+        let mut time_attributes = context.clone().unwrap_or_default();
+        for (name, value) in time_context.attributes(at) {
+            let is_declared = declared
+                .as_ref()
+                .is_none_or(|declared| declared.iter().any(|attr| attr == name));
+            if is_declared && !time_attributes.contains_key(name) {
+                time_attributes.insert(name.to_string(), value);
+                inserted = true;
+            }
+        }
+
+        match inserted {
+            true => Some(time_attributes),
+            false => context,
+        }
+    }
+
+    /// Checks the action and entity types of a request against the `appliesTo` of the project
+    /// schema. A strict project rejects a request not conforming, a permissive one has it
+    /// evaluated without the schema, so this returns whether the schema applies.
+    fn request_conforms(
+        &self,
+        project_id: &Uuid,
+        principal: &EntityUid,
+        action: &EntityUid,
+        resource: &EntityUid,
+    ) -> Result<bool, CedrusError> {
+        let Some(schema) = self.project_schemas.get(project_id) else {
+            return Ok(true);
+        };
+        let errors = schema.validate_request(principal, action, resource);
+        if errors.is_empty() {
+            return Ok(true);
+        }
+
+        match self.permissive_projects.contains(project_id) {
+            true => Ok(false),
+            false => Err(CedrusError::RequestSchemaError(errors)),
+        }
+    }
+
+    /// Runs an evaluation of the given projects on a blocking thread, once it holds an
+    /// evaluation slot of each, giving up with `EvaluationTimeout` once the shortest of their
+    /// evaluation timeouts elapsed. Cedar can't interrupt an evaluation, so the thread
+    /// completes it unobserved, keeping the slots until then and counted as abandoned by its
+    /// projects, while batches stop at their next request. Without slots nor timeouts it runs
+    /// in place.
+    pub async fn evaluate<T, F>(
+        &self,
+        project_ids: &[Uuid],
+        evaluation: F,
+    ) -> Result<T, CedrusError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, CedrusError> + Send + 'static,
+    {
+        let timeout = project_ids
+            .iter()
+            .filter_map(|project_id| self.project_evaluation_timeouts.get(project_id))
+            .map(|timeout| *timeout)
+            .min();
+        if timeout.is_none() && !self.evaluation_slots.is_bounded() {
+            return evaluation();
+        }
+
+        let permits = self.evaluation_slots.acquire(project_ids).await?;
+        let tracked = self.evaluation_slots.track(project_ids);
+        let running = tracked.running();
+        let task = tokio::task::spawn_blocking(move || {
+            let _permits = permits;
+            let _running = running;
+            evaluation()
+        });
+        let joined = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, task).await,
+            None => Ok(task.await),
+        };
+        let result = match joined {
+            Ok(joined) => joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic())),
+            Err(_) => {
+                tracked.abandon();
+                Err(CedrusError::EvaluationTimeout)
+            }
+        };
+        if let (Err(CedrusError::EvaluationTimeout), Some(timeout)) = (&result, timeout) {
+            self.evaluation_timed_out(project_ids, timeout);
+        }
+        result
+    }
+
+    fn evaluation_timed_out(&self, project_ids: &[Uuid], timeout: Duration) {
+        for project_id in project_ids {
+            *self.evaluation_timeouts.entry(*project_id).or_default() += 1;
+        }
+        tracing::warn!(
+            "Evaluation of projects {:?} timed out after {:?}",
+            project_ids,
+            timeout
+        );
+    }
+
+    /// Evaluates a request against `policies`, the current policies of the project when
+    /// `None`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn is_authorized(
+        &self,
+        project_id: &Uuid,
+        principal: EntityUid,
+        action: EntityUid,
+        resource: EntityUid,
+        context: Option<Context>,
+        principal_entity: Option<Entity>,
+        policies: Option<Arc<CompiledPolicies>>,
+    ) -> Result<Response, CedrusError> {
         let cedar_schema = self
             .project_cedar_schemas
             .get(project_id)
             .ok_or(CedrusError::NotFound)?;
+        let conforms = self.request_conforms(project_id, &principal, &action, &resource)?;
+        let cedar_schema = cedar_schema.as_ref().filter(|_| conforms);
 
-        let mut answers = Vec::new();
+        self.context_telemetry
+            .record(project_id, &action, context.as_ref());
+        let context = self.coerce_context(project_id, &action, context)?;
+        let context = self.with_time_context(project_id, &action, context, chrono::Utc::now());
+        let cedar_request = {
+            let cedar_principal = principal.try_into()?;
+            let cedar_action = action.clone().try_into()?;
+            let cedar_resource = resource.try_into()?;
 
-        let mut entity_uids = HashSet::new();
-        for request in &requests {
-            entity_uids.insert(request.principal.clone());
-            entity_uids.insert(request.resource.clone());
-        }
+            let cedar_context = match context {
+                Some(value) => {
+                    let context_schema = cedar_schema.map(|schema| (schema, &cedar_action));
+                    value
+                        .to_cedar_context(context_schema)
+                        .map_err(|e| self.context_error(project_id, &action, &value, e))?
+                }
+                _ => cedar_policy::Context::empty(),
+            };
+
+            cedar_policy::Request::new(
+                cedar_principal,
+                cedar_action,
+                cedar_resource,
+                cedar_context,
+                cedar_schema,
+            )?
+        };
+
+        let authorizer = cedar_policy::Authorizer::new();
 
-        let cedar_entities = {
-            self.project_cedar_entities
+        let answer = {
+            let cedar_entities = self
+                .project_cedar_entities
                 .get(project_id)
-                .ok_or(CedrusError::NotFound)?
+                .ok_or(CedrusError::NotFound)?;
+            let cedar_entities =
+                Self::with_principal(&cedar_entities, cedar_schema, principal_entity)?;
+
+            let cedar_policies = match policies {
+                Some(policies) => policies,
+                None => self.project_policies(project_id)?,
+            };
+
+            authorizer.is_authorized(&cedar_request, &cedar_policies, &cedar_entities)
+        };
+
+        Ok(answer.into())
+    }
+
+    /// Runs the authorizations of a benchmark against the live policies and entities of a
+    /// project, the way `is_authorized` serves them, and reports their latencies.
+    pub fn project_benchmark(
+        &self,
+        project_id: &Uuid,
+        benchmark: Benchmark,
+    ) -> Result<BenchmarkReport, CedrusError> {
+        let iterations = benchmark.iterations.unwrap_or(DEFAULT_BENCHMARK_ITERATIONS);
+        if iterations == 0 || iterations > MAX_BENCHMARK_ITERATIONS {
+            return Err(CedrusError::BadRequest);
+        }
+        if !self.project_cedar_policies.contains_key(project_id) {
+            return Err(CedrusError::NotFound);
+        }
+
+        let requests = match benchmark.requests.is_empty() {
+            true => {
+                let schema = self
+                    .project_schemas
+                    .get(project_id)
+                    .ok_or(CedrusError::BadRequest)?;
+                let entities: Vec<EntityUid> = self
+                    .project_cedar_entities
+                    .get(project_id)
+                    .map(|entities| entities.iter().map(|e| e.uid().into()).collect())
+                    .unwrap_or_default();
+                DataGenerator::new(&schema, None)
+                    .with_entities(entities)
+                    .requests(SYNTHETIC_REQUESTS)
+            }
+            false => benchmark.requests,
+        };
+        if requests.is_empty() {
+            return Err(CedrusError::BadRequest);
+        }
+
+        let mut report = BenchmarkReport {
+            iterations,
+            requests: requests.len(),
+            ..Default::default()
+        };
+        let mut samples = Vec::with_capacity(iterations);
+        let started = std::time::Instant::now();
+        for request in requests.iter().cycle().take(iterations) {
+            let request = request.clone();
+            let start = std::time::Instant::now();
+            let answer = self.is_authorized(
+                project_id,
+                request.principal,
+                request.action,
+                request.resource,
+                request.context,
+                None,
+                None,
+            );
+            let elapsed = start.elapsed().as_micros() as u64;
+            match answer {
+                Ok(response) => {
+                    match response.decision {
+                        Decision::Allow => report.allowed += 1,
+                        Decision::Deny => report.denied += 1,
+                    }
+                    samples.push(elapsed);
+                }
+                Err(_) => report.errors += 1,
+            }
+        }
+        report.total_micros = started.elapsed().as_micros() as u64;
+        report.throughput = iterations as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON);
+        report.latency = LatencyPercentiles::new(samples);
+
+        Ok(report)
+    }
+
+    /// Random entities and requests conforming to the schema of a project, for tests. The
+    /// entities are made up, not those of the project.
+    pub fn project_generate(
+        &self,
+        project_id: &Uuid,
+        options: GeneratorOptions,
+    ) -> Result<GeneratedData, CedrusError> {
+        let per_type = options
+            .entities_per_type
+            .unwrap_or(DEFAULT_ENTITIES_PER_TYPE);
+        let requests = options.requests.unwrap_or(DEFAULT_GENERATED_REQUESTS);
+        if per_type > MAX_ENTITIES_PER_TYPE || requests > MAX_GENERATED_REQUESTS {
+            return Err(CedrusError::BadRequest);
+        }
+        if !self.project_cedar_schemas.contains_key(project_id) {
+            return Err(CedrusError::NotFound);
+        }
+        let schema = self
+            .project_schemas
+            .get(project_id)
+            .ok_or(CedrusError::BadRequest)?;
+
+        let mut generator = DataGenerator::new(&schema, options.seed);
+        let entities = generator.entities(per_type);
+        let requests = generator.requests(requests);
+
+        Ok(GeneratedData { entities, requests })
+    }
+
+    /// Evaluates `request` against each of the projects, in order, and combines their
+    /// decisions with `strategy`.
+    pub fn is_authorized_combined(
+        &self,
+        project_ids: &[Uuid],
+        request: Request,
+        strategy: DecisionStrategy,
+    ) -> Result<CombinedResponse, CedrusError> {
+        if project_ids.is_empty() {
+            return Err(CedrusError::BadRequest);
+        }
+
+        let projects = project_ids
+            .iter()
+            .map(|project_id| {
+                let response = self.is_authorized(
+                    project_id,
+                    request.principal.clone(),
+                    request.action.clone(),
+                    request.resource.clone(),
+                    request.context.clone(),
+                    None,
+                    None,
+                )?;
+                Ok(ProjectDecision {
+                    project_id: *project_id,
+                    response,
+                })
+            })
+            .collect::<Result<Vec<_>, CedrusError>>()?;
+
+        Ok(CombinedResponse::new(strategy, projects))
+    }
+
+    /// Evaluates `request` against the policy set and entities of a project as they were at
+    /// `as_of`, replayed from its history, with the time context of that instant. The history
+    /// holds no schema, so the current one only coerces the context and nothing is validated
+    /// against it.
+    pub async fn project_replay(
+        &self,
+        project_id: Uuid,
+        as_of: chrono::DateTime<chrono::Utc>,
+        request: Request,
+    ) -> Result<Response, CedrusError> {
+        let policy_set = self.project_policy_set_as_of(project_id, as_of).await?;
+        let cedar_policies: cedar_policy::PolicySet = policy_set.try_into()?;
+
+        let entities = self.project_entities_as_of(project_id, as_of).await?;
+        let cedar_entities = cedar_policy::Entities::from_entities(
+            Self::to_cedar_entities(entities, None).await,
+            None,
+        )?;
+
+        let context = self.coerce_context(&project_id, &request.action, request.context)?;
+        let context = self.with_time_context(&project_id, &request.action, context, as_of);
+        let cedar_context = match context {
+            Some(value) => value
+                .to_cedar_context(None)
+                .map_err(|e| self.context_error(&project_id, &request.action, &value, e))?,
+            None => cedar_policy::Context::empty(),
+        };
+        let cedar_request = cedar_policy::Request::new(
+            request.principal.try_into()?,
+            request.action.try_into()?,
+            request.resource.try_into()?,
+            cedar_context,
+            None,
+        )?;
+
+        let authorizer = cedar_policy::Authorizer::new();
+        Ok(authorizer
+            .is_authorized(&cedar_request, &cedar_policies, &cedar_entities)
+            .into())
+    }
+
+    // The project entities with the principal materialized from a token in place of the
+    // stored one. Upserting rebuilds the entity hierarchy, so it is only done when needed.
+    fn with_principal<'a>(
+        cedar_entities: &'a cedar_policy::Entities,
+        cedar_schema: Option<&cedar_policy::Schema>,
+        principal_entity: Option<Entity>,
+    ) -> Result<Cow<'a, cedar_policy::Entities>, CedrusError> {
+        let Some(principal_entity) = principal_entity else {
+            return Ok(Cow::Borrowed(cedar_entities));
+        };
+        let cedar_entity = principal_entity.to_cedar_entity(cedar_schema)?;
+        Ok(Cow::Owned(
+            cedar_entities
+                .clone()
+                .upsert_entities([cedar_entity], cedar_schema)?,
+        ))
+    }
+
+    /// Verifies `token` with the identity source of the project and returns the principal
+    /// it identifies, built from its claims over the stored entity.
+    pub async fn project_token_principal(
+        &self,
+        project_id: &Uuid,
+        token: &str,
+    ) -> Result<Entity, CedrusError> {
+        let mut entity = match self.token_cache.get(project_id, token) {
+            Some(entity) => entity,
+            None => {
+                let generation = self.token_cache.generation(project_id);
+                let authorizer = self
+                    .project_authorizers
+                    .get(project_id)
+                    .ok_or(CedrusError::BadRequest)?;
+                let authorizer = authorizer.as_ref().ok_or(CedrusError::BadRequest)?;
+
+                let token_data = authorizer
+                    .jwt
+                    .check_auth(token)
+                    .await
+                    .map_err(|_| CedrusError::Unauthorized)?;
+                if !authorizer.identity_source.assert_claims(&token_data.claims) {
+                    return Err(CedrusError::Unauthorized);
+                }
+                let entity = authorizer.get_entity(&token_data.claims)?;
+
+                let exp = token_data.claims.get("exp").and_then(Value::as_u64);
+                self.token_cache
+                    .insert(project_id, token, entity.clone(), exp, generation);
+                entity
+            }
         };
+        if let Some(schema) = self.project_schemas.get(project_id) {
+            entity.coerce(&schema);
+        }
+
+        let stored = self
+            .cache_entities(project_id, std::slice::from_ref(entity.uid()))
+            .await?;
+        Ok(match stored.into_iter().next() {
+            Some(mut stored) => {
+                stored.merge(entity);
+                stored
+            }
+            None => entity,
+        })
+    }
 
-        let cedar_policies = {
-            self.project_cedar_policies
+    /// Evaluates every request against one snapshot of the project, in parallel chunks on
+    /// blocking threads once it holds an evaluation slot of the project, one thread per slot
+    /// it holds when slots are bounded. With a `principal_entity`, each request is made by it, and with `timings` each response
+    /// carries its evaluation time. Past the evaluation timeout of the project the remaining
+    /// requests are not evaluated and it gives up with `EvaluationTimeout`. The requests are
+    /// evaluated against `policies`, the current policies of the project when `None`.
+    pub async fn is_authorized_batch(
+        &self,
+        project_id: &Uuid,
+        requests: Vec<Request>,
+        principal_entity: Option<Entity>,
+        timings: bool,
+        policies: Option<Arc<CompiledPolicies>>,
+    ) -> Result<Vec<Response>, CedrusError> {
+        // The snapshot is taken out of the maps before evaluating, so no shard stays locked
+        // while the requests are evaluated
+        let (cedar_requests, cedar_entities, cedar_policies) = {
+            let cedar_schema = self
+                .project_cedar_schemas
+                .get(project_id)
+                .ok_or(CedrusError::NotFound)?;
+            let cedar_entities = self
+                .project_cedar_entities
                 .get(project_id)
-                .ok_or(CedrusError::NotFound)?
+                .map(|entities| entities.value().clone())
+                .ok_or(CedrusError::NotFound)?;
+            let cedar_policies = match policies {
+                Some(policies) => policies,
+                None => self.project_policies(project_id)?,
+            };
+
+            let principal = principal_entity.as_ref().map(|e| e.uid().clone());
+            let cedar_entities = match Self::with_principal(
+                &cedar_entities,
+                cedar_schema.as_ref(),
+                principal_entity,
+            )? {
+                Cow::Borrowed(_) => cedar_entities.clone(),
+                Cow::Owned(entities) => Arc::new(entities),
+            };
+
+            for request in &requests {
+                self.context_telemetry.record(
+                    project_id,
+                    &request.action,
+                    request.context.as_ref(),
+                );
+            }
+            let cedar_requests = self.cedar_requests(
+                project_id,
+                requests,
+                principal.as_ref(),
+                cedar_schema.as_ref(),
+            )?;
+            (cedar_requests, cedar_entities, cedar_policies)
         };
 
-        for request in requests {
-            let cedar_request = {
-                let cedar_principal = request.principal.into();
-                let cedar_action = request.action.into();
-                let cedar_resource = request.resource.into();
+        let mut permits = self.evaluation_slots.acquire(&[*project_id]).await?;
+        let timeout = self
+            .project_evaluation_timeouts
+            .get(project_id)
+            .map(|timeout| *timeout);
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+
+        // At most one chunk per available core, and per slot held when slots are bounded
+        let mut threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if self.evaluation_slots.is_bounded() {
+            permits.extend(
+                self.evaluation_slots
+                    .try_acquire_more(project_id, threads.saturating_sub(1)),
+            );
+            threads = permits.len();
+        }
+        let mut permits = permits.into_iter();
+        let chunk_size = cedar_requests
+            .len()
+            .div_ceil(threads)
+            .max(AUTHORIZATION_CHUNK_SIZE);
+        let mut cedar_requests = cedar_requests.into_iter().peekable();
+        let mut tasks = Vec::new();
+        while cedar_requests.peek().is_some() {
+            let chunk: Vec<_> = cedar_requests.by_ref().take(chunk_size).collect();
+            let cedar_entities = cedar_entities.clone();
+            let cedar_policies = cedar_policies.clone();
+            let permit = permits.next();
+            tasks.push(tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                let authorizer = cedar_policy::Authorizer::new();
+                chunk
+                    .iter()
+                    .map(|cedar_request| {
+                        let start = std::time::Instant::now();
+                        if deadline.is_some_and(|deadline| start > deadline) {
+                            return None;
+                        }
+                        let answer = authorizer.is_authorized(
+                            cedar_request,
+                            &cedar_policies,
+                            &cedar_entities,
+                        );
+                        let mut response = Response::from(answer);
+                        if timings {
+                            response.evaluation_micros = Some(start.elapsed().as_micros() as u64);
+                        }
+                        Some(response)
+                    })
+                    .collect::<Option<Vec<_>>>()
+            }));
+        }
+        drop(permits);
+
+        let mut answers = Vec::new();
+        let mut timed_out = false;
+        for task in tasks {
+            match task
+                .await
+                .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+            {
+                Some(chunk) => answers.extend(chunk),
+                None => timed_out = true,
+            }
+        }
+        match (timed_out, timeout) {
+            (true, Some(timeout)) => {
+                self.evaluation_timed_out(&[*project_id], timeout);
+                Err(CedrusError::EvaluationTimeout)
+            }
+            _ => Ok(answers),
+        }
+    }
 
-                let cedar_context = match request.context {
+    // Cedar requests of a batch, each made by `principal` when set, their context coerced
+    // and checked against the schema as `is_authorized` does
+    fn cedar_requests(
+        &self,
+        project_id: &Uuid,
+        requests: Vec<Request>,
+        principal: Option<&EntityUid>,
+        cedar_schema: Option<&cedar_policy::Schema>,
+    ) -> Result<Vec<cedar_policy::Request>, CedrusError> {
+        requests
+            .into_iter()
+            .map(|request| {
+                let request_principal = principal.cloned().unwrap_or(request.principal);
+                let conforms = self.request_conforms(
+                    project_id,
+                    &request_principal,
+                    &request.action,
+                    &request.resource,
+                )?;
+                let request_schema = cedar_schema.filter(|_| conforms);
+
+                let cedar_principal = request_principal.try_into()?;
+                let cedar_action = request.action.clone().try_into()?;
+                let cedar_resource = request.resource.try_into()?;
+
+                let context = self.coerce_context(project_id, &request.action, request.context)?;
+                let context = self.with_time_context(
+                    project_id,
+                    &request.action,
+                    context,
+                    chrono::Utc::now(),
+                );
+                let cedar_context = match context {
                     Some(value) => {
-                        let context_schema =
-                            cedar_schema.as_ref().map(|schema| (schema, &cedar_action));
-                        value.to_cedar_context(context_schema)?
+                        let context_schema = request_schema.map(|schema| (schema, &cedar_action));
+                        value.to_cedar_context(context_schema).map_err(|e| {
+                            self.context_error(project_id, &request.action, &value, e)
+                        })?
                     }
                     _ => cedar_policy::Context::empty(),
                 };
 
-                cedar_policy::Request::new(
+                Ok(cedar_policy::Request::new(
                     cedar_principal,
                     cedar_action,
                     cedar_resource,
                     cedar_context,
-                    cedar_schema.as_ref(),
-                )?
-            };
-
-            let authorizer = cedar_policy::Authorizer::new();
-            let answer = authorizer.is_authorized(&cedar_request, &cedar_policies, &cedar_entities);
-            answers.push(answer.into());
-        }
-
-        Ok(answers)
+                    request_schema,
+                )?)
+            })
+            .collect()
     }
 
+    /// Lists projects, sorted by at most one of [`PROJECT_SORT_FIELDS`].
     pub async fn projects_find(&self, query: Query) -> Result<PageList<Project>, CedrusError> {
+        if query.sort.len() > 1
+            || query
+                .sort
+                .iter()
+                .any(|s| !PROJECT_SORT_FIELDS.contains(&s.field.as_str()))
+        {
+            return Err(CedrusError::BadRequest);
+        }
         Ok(self.db.projects_load(&query).await?)
     }
 
@@ -591,19 +2717,48 @@ impl Cedrus {
         mut project: Project,
         owner: EntityUid,
     ) -> Result<Project, CedrusError> {
+        if project
+            .time_context
+            .as_ref()
+            .is_some_and(|tc| !tc.is_valid())
+            || !Project::labels_valid(&project.labels)
+            || project.evaluation_timeout_millis == Some(0)
+            || project
+                .notifications
+                .as_ref()
+                .is_some_and(|notifications| !self.notifications.is_project_valid(notifications))
+            || project
+                .region
+                .as_ref()
+                .is_some_and(|region| !self.regions.contains(region))
+            || project
+                .gitops
+                .as_ref()
+                .is_some_and(|src| !src.is_valid(&self.gitops))
+            || !self.is_anonymous_principal_valid(project.anonymous_principal.as_ref())
+        {
+            return Err(CedrusError::BadRequest);
+        }
         project.owner = owner.clone();
 
         let now = chrono::Utc::now();
         project.created_at = now;
         project.updated_at = now;
+        project.history_since = project.history.then_some(now);
+        if dry_run::record(|report| report.projects_changed.push(project.id)) {
+            return Ok(project);
+        }
 
         self.db.project_save(&project).await?;
+        if project.history {
+            self.project_history_baseline(&project.id, now).await?;
+        }
         self.cache.project_set(&project).await?;
 
         self.on_project_set(&project)?;
 
         let nil = Uuid::nil();
-        let entity = project.entity();
+        let entity = self.project_entity(&project);
 
         self.db
             .project_entities_save(&nil, &Vec::from([entity.clone()]))
@@ -639,6 +2794,101 @@ impl Cedrus {
         Ok(project)
     }
 
+    /// Replaces the key of the admin API key, after the secret holding it was rotated.
+    pub async fn admin_api_key_rotate(&self, admin_api_key: String) -> Result<(), CedrusError> {
+        let nil = Uuid::nil();
+        let page = self.db.project_apikeys_load(&nil, &Query::new()).await?;
+        let Some(original) = page.items.into_iter().find(|ak| ak.id == nil) else {
+            return Err(CedrusError::NotFound);
+        };
+        if original.key == admin_api_key {
+            return Ok(());
+        }
+
+        let mut apikey = original.clone();
+        apikey.key = admin_api_key;
+        apikey.updated_at = chrono::Utc::now();
+
+        self.db
+            .project_apikeys_save(&nil, &vec![apikey.clone()])
+            .await?;
+        self.cache
+            .project_set_apikeys(&nil, &vec![apikey.clone()])
+            .await?;
+
+        self.on_project_apikeys_del(std::slice::from_ref(&original.key))?;
+        self.on_project_apikeys_set(&[apikey.clone()])?;
+
+        self.publish(Event::project_remove_apikeys(
+            self.id,
+            nil,
+            HashSet::from([original.key]),
+        ))
+        .await;
+        self.publish(Event::project_add_apikeys(
+            self.id,
+            nil,
+            HashSet::from([apikey.id]),
+        ))
+        .await;
+
+        Ok(())
+    }
+
+    /// Server wide read-only mode, from the configuration or the admin project flag.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only || self.read_only_projects.contains(&Uuid::nil())
+    }
+
+    pub fn is_project_read_only(&self, project_id: &Uuid) -> bool {
+        self.read_only_projects.contains(project_id)
+    }
+
+    /// Projects synced from a Git repository, their policies and schema are read-only.
+    pub fn is_project_gitops(&self, project_id: &Uuid) -> bool {
+        self.gitops_projects.contains_key(project_id)
+    }
+
+    pub fn is_project_write_behind(&self, project_id: &Uuid) -> bool {
+        self.write_behind_projects.contains(project_id)
+    }
+
+    // Writes stay deferred while older ones are queued, even once the mode is turned off
+    fn is_write_deferred(&self, project_id: &Uuid) -> bool {
+        self.is_project_write_behind(project_id) || self.write_behind.is_pending(project_id)
+    }
+
+    /// Writes the deferred entity and policy changes to the Database, returning how many
+    /// were written. On failure the remaining writes are kept for the next flush.
+    pub async fn write_behind_flush(&self) -> Result<usize, CedrusError> {
+        Ok(self.write_behind.flush(self.db.as_ref()).await?)
+    }
+
+    pub async fn project_read_only_set(
+        &self,
+        project_id: Uuid,
+        read_only: bool,
+    ) -> Result<Project, CedrusError> {
+        let Some(mut project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        if project.read_only != read_only {
+            project.read_only = read_only;
+            project.updated_at = chrono::Utc::now();
+
+            self.db.project_save(&project).await?;
+            self.cache.project_set(&project).await?;
+
+            self.on_project_update(&project);
+
+            self.publish(Event::project_update(self.id, project_id))
+                .await;
+        }
+
+        Ok(project)
+    }
+
     pub async fn project_update(
         &self,
         project_id: Uuid,
@@ -648,7 +2898,8 @@ impl Cedrus {
             return Err(CedrusError::NotFound);
         };
 
-        if original.updated_at != project.updated_at {
+        // Moving a project would leave its data behind in the previous region
+        if original.updated_at != project.updated_at || original.region != project.region {
             return Err(CedrusError::BadRequest);
         }
 
@@ -663,26 +2914,129 @@ impl Cedrus {
             pristine = false;
         }
 
-        let now = chrono::Utc::now();
-        if original.created_at.timestamp_millis() == 0 {
-            original.created_at = now;
+        if original.read_only != project.read_only {
+            original.read_only = project.read_only;
             pristine = false;
         }
-        if original.updated_at.timestamp_millis() == 0 {
-            original.updated_at = now;
+
+        if original.write_behind != project.write_behind {
+            original.write_behind = project.write_behind;
             pristine = false;
         }
 
-        if !pristine {
-            original.updated_at = now;
+        if original.request_validation != project.request_validation {
+            original.request_validation = project.request_validation;
+            pristine = false;
+        }
 
-            self.db.project_save(&original).await?;
-            self.cache.project_set(&original).await?;
+        if original.linked_entity_removal != project.linked_entity_removal {
+            original.linked_entity_removal = project.linked_entity_removal;
+            pristine = false;
+        }
+
+        if original.evaluation_timeout_millis != project.evaluation_timeout_millis {
+            if project.evaluation_timeout_millis == Some(0) {
+                return Err(CedrusError::BadRequest);
+            }
+            original.evaluation_timeout_millis = project.evaluation_timeout_millis;
+            pristine = false;
+        }
+
+        if original.labels != project.labels {
+            if !Project::labels_valid(&project.labels) {
+                return Err(CedrusError::BadRequest);
+            }
+            original.labels = project.labels;
+            pristine = false;
+        }
+
+        if original.anonymous_principal != project.anonymous_principal {
+            if !self.is_anonymous_principal_valid(project.anonymous_principal.as_ref()) {
+                return Err(CedrusError::BadRequest);
+            }
+            original.anonymous_principal = project.anonymous_principal;
+            pristine = false;
+        }
+
+        let mut notifications = project.notifications;
+        if let Some(notifications) = &mut notifications {
+            notifications.unredact(original.notifications.as_ref());
+        }
+        if original.notifications != notifications {
+            if notifications
+                .as_ref()
+                .is_some_and(|notifications| !self.notifications.is_project_valid(notifications))
+            {
+                return Err(CedrusError::BadRequest);
+            }
+            original.notifications = notifications;
+            pristine = false;
+        }
+
+        if original.gitops != project.gitops {
+            if project
+                .gitops
+                .as_ref()
+                .is_some_and(|src| !src.is_valid(&self.gitops))
+            {
+                return Err(CedrusError::BadRequest);
+            }
+            original.gitops = project.gitops;
+            pristine = false;
+        }
+
+        if original.time_context != project.time_context {
+            if project
+                .time_context
+                .as_ref()
+                .is_some_and(|tc| !tc.is_valid())
+            {
+                return Err(CedrusError::BadRequest);
+            }
+            original.time_context = project.time_context;
+            pristine = false;
+        }
+
+        let history_changed = original.history != project.history;
+        if history_changed {
+            original.history = project.history;
+            pristine = false;
+        }
+
+        let now = chrono::Utc::now();
+        if original.created_at.timestamp_millis() == 0 {
+            original.created_at = now;
+            pristine = false;
+        }
+        if original.updated_at.timestamp_millis() == 0 {
+            original.updated_at = now;
+            pristine = false;
+        }
+
+        if !pristine {
+            original.updated_at = now;
+            if dry_run::record(|report| report.projects_changed.push(project_id)) {
+                return Ok(original);
+            }
+
+            // Turning history on records the current entities and policy set as its
+            // baseline, turning it off drops it
+            if history_changed {
+                original.history_since = None;
+                self.db.project_revisions_remove(&project_id).await?;
+                if original.history {
+                    self.project_history_baseline(&project_id, now).await?;
+                    original.history_since = Some(now);
+                }
+            }
+
+            self.db.project_save(&original).await?;
+            self.cache.project_set(&original).await?;
 
-            self.on_project_set(&original)?;
+            self.on_project_update(&original);
 
             let nil = Uuid::nil();
-            let entity = original.entity();
+            let entity = self.project_entity(&original);
             self.db
                 .project_entities_save(&nil, &Vec::from([entity.clone()]))
                 .await?;
@@ -699,10 +3053,29 @@ impl Cedrus {
         Ok(original)
     }
 
+    /// Entity of a project in the admin project, without the attributes the admin schema does
+    /// not declare, so an admin project created with an earlier schema keeps validating.
+    fn project_entity(&self, project: &Project) -> Entity {
+        let mut entity = project.entity();
+        if let Some(schema) = self.project_schemas.get(&Uuid::nil())
+            && let Some(declared) = schema.entity_attributes(entity.uid().type_name())
+        {
+            entity.attrs_mut().retain(|name, _| declared.contains(name));
+        }
+        entity
+    }
+
     pub async fn project_remove(&self, project_id: Uuid) -> Result<Project, CedrusError> {
         let Some(project) = self.db.project_load(&project_id).await? else {
             return Err(CedrusError::NotFound);
         };
+        // Environments are removed before the project whose schema they share
+        if !self.environment_ids(&project_id).is_empty() {
+            return Err(CedrusError::Conflict);
+        }
+        if dry_run::record(|report| report.projects_removed.push(project_id)) {
+            return Ok(project);
+        }
 
         let query = Query::new();
         let api_keys = self.db.project_apikeys_load(&project_id, &query).await?;
@@ -713,7 +3086,9 @@ impl Cedrus {
             .map(|x| x.key.clone())
             .collect::<Vec<String>>();
 
+        self.write_behind.discard(&project_id).await;
         self.db.project_remove(&project_id).await?;
+        self.db.project_revisions_remove(&project_id).await?;
         self.cache.project_del(&project_id).await?;
 
         self.on_project_del(&project_id, &api_key_ids)?;
@@ -739,6 +3114,89 @@ impl Cedrus {
         Ok(project)
     }
 
+    pub async fn project_stats(&self, project_id: Uuid) -> Result<ProjectStats, CedrusError> {
+        let Some(project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let query = Query::new();
+        let entities = self.db.project_entities_load(&project_id, &query).await?;
+        let policies = self.db.project_policies_load(&project_id, &query).await?;
+        let templates = self.db.project_templates_load(&project_id, &query).await?;
+        let template_links = self
+            .db
+            .project_template_links_load(&project_id, &query)
+            .await?;
+
+        let mut entities_by_type: HashMap<String, usize> = HashMap::new();
+        for entity in &entities.items {
+            *entities_by_type
+                .entry(entity.uid().type_name().to_string())
+                .or_default() += 1;
+        }
+
+        let mut schema_namespaces = match self.db.project_schema_load(&project_id).await? {
+            Some(schema) => schema.0.into_keys().collect::<Vec<String>>(),
+            None => Vec::new(),
+        };
+        schema_namespaces.sort();
+
+        let hydration = ProjectHydration {
+            schema: self
+                .project_cedar_schemas
+                .get(&project_id)
+                .is_some_and(|s| s.is_some()),
+            identity_source: self
+                .project_authorizers
+                .get(&project_id)
+                .is_some_and(|a| a.is_some()),
+            entities: self
+                .project_cedar_entities
+                .get(&project_id)
+                .map(|e| e.iter().count())
+                .unwrap_or_default(),
+            policies: self
+                .project_cedar_policies
+                .get(&project_id)
+                .map(|p| p.policies().count())
+                .unwrap_or_default(),
+            templates: self
+                .project_cedar_policies
+                .get(&project_id)
+                .map(|p| p.templates().count())
+                .unwrap_or_default(),
+        };
+
+        let context_usage = match self.project_cedar_policies.get(&project_id) {
+            Some(policies) => {
+                let schema = self.project_schemas.get(&project_id);
+                let cedar_schema = self.project_cedar_schemas.get(&project_id);
+                self.context_telemetry.report(
+                    &project_id,
+                    schema.as_deref(),
+                    cedar_schema.as_ref().and_then(|s| s.as_ref()),
+                    &policies,
+                )
+            }
+            None => Vec::new(),
+        };
+
+        Ok(ProjectStats {
+            project_id,
+            entities: entities.items.len(),
+            entities_by_type,
+            policies: policies.items.len(),
+            templates: templates.items.len(),
+            template_links: template_links.items.len(),
+            schema_namespaces,
+            hydration,
+            context_usage,
+            deny_rates: self.deny_rates.report(&project_id),
+            created_at: project.created_at,
+            updated_at: project.updated_at,
+        })
+    }
+
     pub async fn project_apikeys_find(
         &self,
         project_id: Uuid,
@@ -765,6 +3223,9 @@ impl Cedrus {
         apikey.key = BASE64_STANDARD.encode(bytes);
         apikey.project_id = project_id;
         apikey.created_at = chrono::Utc::now();
+        if dry_run::record(|report| report.api_keys_changed.push(apikey.id)) {
+            return Ok(apikey);
+        }
 
         self.db
             .project_apikeys_save(&project_id, &vec![apikey.clone()])
@@ -804,7 +3265,13 @@ impl Cedrus {
 
         let mut original = original.clone();
         original.name = apikey.name;
+        original.expires_at = apikey.expires_at;
+        original.scopes = apikey.scopes;
+        original.grants = apikey.grants;
         original.updated_at = chrono::Utc::now();
+        if dry_run::record(|report| report.api_keys_changed.push(original.id)) {
+            return Ok(original);
+        }
 
         self.db
             .project_apikeys_save(&project_id, &vec![original.clone()])
@@ -845,6 +3312,9 @@ impl Cedrus {
         let Some(apikey) = page.items.iter().find(|ak| ak.id == id) else {
             return Err(CedrusError::NotFound);
         };
+        if dry_run::record(|report| report.api_keys_removed.push(id)) {
+            return Ok(());
+        }
 
         self.db
             .project_apikeys_remove(&project_id, &vec![id])
@@ -885,7 +3355,9 @@ impl Cedrus {
             return Err(CedrusError::NotFound);
         };
 
-        let _authorizer = authorizer_factory(&identity_source.configuration).await;
+        if dry_run::record(|report| report.identity_source_changed = true) {
+            return Ok(());
+        }
 
         self.db
             .project_identity_source_save(&project_id, &identity_source)
@@ -911,6 +3383,10 @@ impl Cedrus {
             return Err(CedrusError::NotFound);
         };
 
+        if dry_run::record(|report| report.identity_source_removed = true) {
+            return Ok(());
+        }
+
         self.db.project_identity_source_remove(&project_id).await?;
         self.cache.project_del_identity_source(&project_id).await?;
 
@@ -933,348 +3409,3590 @@ impl Cedrus {
         Ok(self.db.project_schema_load(&project_id).await?)
     }
 
-    pub async fn project_schema_update(
+    /// Lints `schema`, else the stored schema of the project, reporting as well whether it
+    /// builds once the shared common types are resolved. Fails with `BadRequest` when there is
+    /// no schema to lint.
+    pub async fn project_schema_lint(
         &self,
         project_id: Uuid,
-        schema: Schema,
-    ) -> Result<(), CedrusError> {
-        let Some(_) = self.db.project_load(&project_id).await? else {
-            return Err(CedrusError::NotFound);
+        schema: Option<Schema>,
+    ) -> Result<SchemaLintReport, CedrusError> {
+        let schema = match schema {
+            Some(schema) => schema,
+            None => self
+                .project_schema_find(project_id)
+                .await?
+                .ok_or(CedrusError::BadRequest)?,
         };
 
-        let cedar_schema: cedar_policy::Schema = schema.clone().try_into()?;
-
-        let entities = self
-            .db
-            .project_entities_load(&project_id, &Query::new())
-            .await?
-            .items;
-        if !entities.is_empty() {
-            let cedar_schema = Some(cedar_schema);
-            for entry in &entities {
-                entry.to_cedar_entity(cedar_schema.as_ref())?;
-            }
+        let mut report = SchemaLintReport::new(&schema);
+        let cedar_schema: Result<cedar_policy::Schema, _> =
+            self.with_common_types(schema).try_into();
+        if let Err(e) = cedar_schema {
+            report.invalid(e.to_string());
         }
 
-        self.db.project_schema_save(&project_id, &schema).await?;
-        self.cache.project_set_schema(&project_id, &schema).await?;
-
-        self.on_project_schema_set(&project_id, &schema)?;
-
-        self.publish(Event::project_put_schema(self.id, project_id))
-            .await;
-
-        Ok(())
+        Ok(report)
     }
 
-    pub async fn project_schema_remove(&self, project_id: Uuid) -> Result<(), CedrusError> {
-        let Some(_) = self.db.project_load(&project_id).await? else {
-            return Err(CedrusError::NotFound);
-        };
-
-        self.db.project_schema_remove(&project_id).await?;
-        self.cache.project_del_schema(&project_id).await?;
-
-        self.on_project_schema_del(&project_id)?;
-
-        self.publish(Event::project_remove_schema(self.id, project_id))
-            .await;
+    /// Typed helpers for the entity types, actions and contexts of the stored schema of the
+    /// project, the shared common types resolved. Fails with `BadRequest` when there is no
+    /// schema.
+    pub async fn project_schema_sdk(
+        &self,
+        project_id: Uuid,
+        lang: SdkLang,
+    ) -> Result<String, CedrusError> {
+        let schema = self
+            .project_schema_find(project_id)
+            .await?
+            .ok_or(CedrusError::BadRequest)?;
 
-        Ok(())
+        Ok(sdk::generate(&self.with_common_types(schema), lang))
     }
 
-    pub async fn project_entities_find(
+    /// Sets the schema of a project and of its environments, whose schema is only set
+    /// through their parent project.
+    pub async fn project_schema_update(
         &self,
         project_id: Uuid,
-        query: Query,
-    ) -> Result<PageList<Entity>, CedrusError> {
+        schema: Schema,
+    ) -> Result<(), CedrusError> {
         let Some(_) = self.db.project_load(&project_id).await? else {
             return Err(CedrusError::NotFound);
         };
+        if self.project_environments.contains_key(&project_id) {
+            return Err(CedrusError::BadRequest);
+        }
 
-        Ok(self.db.project_entities_load(&project_id, &query).await?)
+        let mut project_ids = vec![project_id];
+        project_ids.extend(self.environment_ids(&project_id));
+        self.project_schema_set(&project_ids, schema).await
     }
 
-    pub async fn project_entities_add(
+    // The entities of every project are checked against the schema before any of them
+    // changes
+    async fn project_schema_set(
         &self,
-        project_id: Uuid,
-        entities: Vec<Entity>,
+        project_ids: &[Uuid],
+        schema: Schema,
     ) -> Result<(), CedrusError> {
-        let Some(_) = self.db.project_load(&project_id).await? else {
-            return Err(CedrusError::NotFound);
-        };
+        let _epochs: Vec<_> = project_ids
+            .iter()
+            .map(|project_id| self.project_epochs.begin(project_id))
+            .collect();
 
-        let schema = self.db.project_schema_load(&project_id).await?;
+        let cedar_schema: cedar_policy::Schema =
+            self.with_common_types(schema.clone()).try_into()?;
+        let cedar_schema = Some(cedar_schema);
 
-        let cedar_schema = schema.map(|s| s.try_into()).transpose()?;
-        for entry in &entities {
-            entry.to_cedar_entity(cedar_schema.as_ref())?;
+        for project_id in project_ids {
+            let entities = self
+                .db
+                .project_entities_load(project_id, &Query::new())
+                .await?
+                .items;
+            for entry in &entities {
+                entry.to_cedar_entity(cedar_schema.as_ref())?;
+            }
+        }
+        if dry_run::record(|report| report.plan.schema_changed = true) {
+            return Ok(());
         }
 
-        self.db
-            .project_entities_save(&project_id, &entities)
-            .await?;
-        self.cache
-            .project_set_entities(&project_id, &entities)
-            .await?;
+        for project_id in project_ids {
+            self.db.project_schema_save(project_id, &schema).await?;
+            self.cache.project_set_schema(project_id, &schema).await?;
 
-        self.on_project_entities(&project_id).await?;
+            self.on_project_schema_set(project_id, &schema)?;
 
-        let entity_uids = entities
-            .iter()
-            .map(|e| e.uid().clone())
-            .collect::<HashSet<_>>();
-        self.publish(Event::project_add_entities(
-            self.id,
-            project_id,
-            entity_uids,
-        ))
-        .await;
+            self.publish(Event::project_put_schema(self.id, *project_id))
+                .await;
+        }
 
         Ok(())
     }
 
-    pub async fn project_entities_remove(
+    pub async fn common_types_find(&self) -> Result<HashMap<String, TypeJson>, CedrusError> {
+        Ok(self.db.common_types_load().await?)
+    }
+
+    /// Replaces the shared library of common types, once every project schema referring to
+    /// it still builds.
+    pub async fn common_types_update(
         &self,
-        project_id: Uuid,
-        entity_uids: Vec<EntityUid>,
-    ) -> Result<(), CedrusError> {
-        let Some(_) = self.db.project_load(&project_id).await? else {
-            return Err(CedrusError::NotFound);
-        };
+        common_types: HashMap<String, TypeJson>,
+    ) -> Result<HashMap<String, TypeJson>, CedrusError> {
+        let _lock = self.common_types_lock.lock().await;
+        self.common_types_replace(common_types).await
+    }
 
-        self.db
-            .project_entities_remove(&project_id, &entity_uids)
-            .await?;
-        self.cache
-            .project_del_entities(&project_id, &entity_uids)
-            .await?;
+    pub async fn common_type_update(
+        &self,
+        name: String,
+        type_json: TypeJson,
+    ) -> Result<TypeJson, CedrusError> {
+        let _lock = self.common_types_lock.lock().await;
+        let mut common_types = self.db.common_types_load().await?;
+        common_types.insert(name, type_json.clone());
+        self.common_types_replace(common_types).await?;
+
+        Ok(type_json)
+    }
 
-        self.on_project_entities(&project_id).await?;
+    /// Removes a common type from the library, failing while a project schema still refers
+    /// to it.
+    pub async fn common_type_remove(&self, name: &str) -> Result<TypeJson, CedrusError> {
+        let _lock = self.common_types_lock.lock().await;
+        let mut common_types = self.db.common_types_load().await?;
+        let type_json = common_types.remove(name).ok_or(CedrusError::NotFound)?;
+        self.common_types_replace(common_types).await?;
 
-        let entity_uids = entity_uids.into_iter().collect::<HashSet<_>>();
-        self.publish(Event::project_remove_entities(
-            self.id,
-            project_id,
-            entity_uids,
-        ))
-        .await;
+        Ok(type_json)
+    }
 
-        Ok(())
+    async fn common_types_replace(
+        &self,
+        common_types: HashMap<String, TypeJson>,
+    ) -> Result<HashMap<String, TypeJson>, CedrusError> {
+        if !common_types.keys().all(|name| is_common_type_name(name)) {
+            return Err(CedrusError::BadRequest);
+        }
+        let changes = self.common_types_changes(&common_types).await?;
+
+        self.db.common_types_save(&common_types).await?;
+        self.on_common_types_set(common_types.clone(), changes)
+            .await?;
+
+        self.publish(Event::common_types_update(self.id)).await;
+
+        Ok(common_types)
     }
 
-    pub async fn project_policies_find(
+    // Stored schemas of the projects whose Cedar schema changes with a library, failing when
+    // one of them no longer builds
+    async fn common_types_changes(
         &self,
-        project_id: Uuid,
-        query: Query,
-    ) -> Result<PageHash<PolicyId, Policy>, CedrusError> {
-        let page = self.db.project_policies_load(&project_id, &query).await?;
-        Ok(page)
+        common_types: &HashMap<String, TypeJson>,
+    ) -> Result<Vec<(Uuid, Schema)>, CedrusError> {
+        let project_ids: Vec<Uuid> = self.project_schemas.iter().map(|e| *e.key()).collect();
+
+        let mut changes = Vec::new();
+        for project_id in project_ids {
+            let Some(schema) = self.cache.project_get_schema(&project_id).await? else {
+                continue;
+            };
+            let resolved = schema.clone().with_common_types(common_types);
+            let unchanged = self
+                .project_schemas
+                .get(&project_id)
+                .is_some_and(|current| *current == resolved);
+            if unchanged {
+                continue;
+            }
+            let _: cedar_policy::Schema = resolved.try_into()?;
+            changes.push((project_id, schema));
+        }
+
+        Ok(changes)
     }
 
-    pub async fn project_policies_add(
+    async fn on_common_types_set(
         &self,
-        project_id: Uuid,
-        mut policies: HashMap<PolicyId, Policy>,
+        common_types: HashMap<String, TypeJson>,
+        changes: Vec<(Uuid, Schema)>,
     ) -> Result<(), CedrusError> {
+        *self.common_types.write().unwrap() = common_types;
+        for (project_id, schema) in changes {
+            self.on_project_schema_set(&project_id, &schema)?;
+            self.on_project_entities(&project_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the schema of a project and of its environments.
+    pub async fn project_schema_remove(&self, project_id: Uuid) -> Result<(), CedrusError> {
         let Some(_) = self.db.project_load(&project_id).await? else {
             return Err(CedrusError::NotFound);
         };
+        if self.project_environments.contains_key(&project_id) {
+            return Err(CedrusError::BadRequest);
+        }
 
-        let _ = policies.iter_mut().map(|(id, policy)| {
-            policy
-                .annotations
-                .insert("id".to_string(), Some(id.to_string()))
-        });
+        if dry_run::record(|report| report.plan.schema_changed = true) {
+            return Ok(());
+        }
 
-        self.db
-            .project_policies_save(&project_id, &policies)
-            .await?;
-        self.cache
-            .project_set_policies(&project_id, &policies)
-            .await?;
+        let mut project_ids = vec![project_id];
+        project_ids.extend(self.environment_ids(&project_id));
+        for project_id in project_ids {
+            let _epoch = self.project_epochs.begin(&project_id);
 
-        self.on_project_policy_set(&project_id).await?;
+            self.db.project_schema_remove(&project_id).await?;
+            self.cache.project_del_schema(&project_id).await?;
 
-        let policy_ids = policies.into_keys().collect();
-        self.publish(Event::project_add_policies(self.id, project_id, policy_ids))
-            .await;
+            self.on_project_schema_del(&project_id)?;
+
+            self.publish(Event::project_remove_schema(self.id, project_id))
+                .await;
+        }
 
         Ok(())
     }
 
-    pub async fn project_policies_remove(
+    pub async fn project_entities_find(
         &self,
         project_id: Uuid,
-        policy_ids: Vec<PolicyId>,
-    ) -> Result<(), CedrusError> {
+        query: Query,
+    ) -> Result<PageList<Entity>, CedrusError> {
         let Some(_) = self.db.project_load(&project_id).await? else {
             return Err(CedrusError::NotFound);
         };
 
-        self.db
-            .project_policies_remove(&project_id, &policy_ids)
-            .await?;
-        self.cache
-            .project_del_policies(&project_id, &policy_ids)
-            .await?;
+        if !Self::is_sorted_by_tags(&query) {
+            return Ok(self.db.project_entities_load(&project_id, &query).await?);
+        }
 
-        self.on_project_policy_set(&project_id).await?;
+        // The page of a sort on tags starts at the offset held by the start key
+        let offset = match &query.start_key {
+            Some(start_key) => start_key.parse().map_err(|_| {
+                CedrusError::ValidationError(vec![format!(
+                    "start key {start_key} is not the offset of a sort on tags"
+                )])
+            })?,
+            None => 0,
+        };
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        let entities = self.project_entities_sorted(&project_id, &query).await?;
+        let end = offset.saturating_add(limit);
+        let last_key = (end < entities.len()).then(|| end.to_string());
+        let page = entities.into_iter().skip(offset).take(limit).collect();
 
-        let policy_ids = policy_ids.into_iter().collect();
-        self.publish(Event::project_remove_policies(
-            self.id, project_id, policy_ids,
-        ))
-        .await;
+        Ok(PageList::new(page, last_key))
+    }
 
-        Ok(())
+    fn is_sorted_by_tags(query: &Query) -> bool {
+        query
+            .sort
+            .iter()
+            .any(|sort| sort.field.starts_with(ENTITY_TAG_PREFIX))
     }
 
-    pub async fn project_templates_find(
+    // Tags are not indexed, so every matching entity is loaded and sorted before any page
+    // is taken from them
+    async fn project_entities_sorted(
         &self,
-        project_id: Uuid,
-        query: Query,
-    ) -> Result<PageHash<PolicyId, Template>, CedrusError> {
-        Ok(self.db.project_templates_load(&project_id, &query).await?)
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<Vec<Entity>, CedrusError> {
+        let mut page_query = Query {
+            selector: query.selector.clone(),
+            limit: Some(ENTITIES_STREAM_PAGE_SIZE),
+            ..Default::default()
+        };
+        let mut entities = Vec::new();
+        loop {
+            let page = self
+                .db
+                .project_entities_load(project_id, &page_query)
+                .await?;
+            entities.extend(page.items);
+            match page.last_key {
+                Some(last_key) => page_query.start_key = Some(last_key),
+                None => break,
+            }
+        }
+        sort_entities(&mut entities, &query.sort);
+
+        Ok(entities)
     }
 
-    pub async fn project_templates_add(
+    /// Loads the entities of a query page after page, sending each one as soon as its page
+    /// is loaded, up to the `limit` of the query when set. Stops early when the receiver is
+    /// dropped, and after sending the first error.
+    pub async fn project_entities_stream(
         &self,
         project_id: Uuid,
-        mut templates: HashMap<PolicyId, Template>,
+        mut query: Query,
+        sender: tokio::sync::mpsc::Sender<Result<Entity, CedrusError>>,
+    ) {
+        if Self::is_sorted_by_tags(&query) {
+            let entities = match self.project_entities_sorted(&project_id, &query).await {
+                Ok(entities) => entities,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+            for entity in entities.into_iter().take(query.limit.unwrap_or(usize::MAX)) {
+                if sender.send(Ok(entity)).await.is_err() {
+                    return;
+                }
+            }
+            return;
+        }
+
+        let mut remaining = query.limit;
+        loop {
+            query.limit = Some(remaining.map_or(ENTITIES_STREAM_PAGE_SIZE, |remaining| {
+                remaining.min(ENTITIES_STREAM_PAGE_SIZE)
+            }));
+            let page = match self.db.project_entities_load(&project_id, &query).await {
+                Ok(page) => page,
+                Err(e) => {
+                    let _ = sender.send(Err(e.into())).await;
+                    return;
+                }
+            };
+
+            remaining = remaining.map(|remaining| remaining.saturating_sub(page.items.len()));
+            for entity in page.items {
+                if sender.send(Ok(entity)).await.is_err() {
+                    return;
+                }
+            }
+
+            match page.last_key {
+                Some(last_key) if remaining != Some(0) => query.start_key = Some(last_key),
+                _ => return,
+            }
+        }
+    }
+
+    /// Entities of a project as they were at `as_of`, replayed from its history.
+    pub async fn project_entities_as_of(
+        &self,
+        project_id: Uuid,
+        as_of: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Entity>, CedrusError> {
+        let revisions = self
+            .project_history_load(project_id, RevisionKind::Entity, as_of)
+            .await?;
+
+        Revision::replay(revisions, as_of)
+            .into_values()
+            .map(|entity| Ok(serde_json::from_value(entity)?))
+            .collect()
+    }
+
+    // Fails with `BadRequest` when the project keeps no history covering `as_of`
+    async fn project_history_load(
+        &self,
+        project_id: Uuid,
+        kind: RevisionKind,
+        as_of: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Revision>, CedrusError> {
+        let Some(project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        if project.history_since.is_none_or(|since| since > as_of) {
+            return Err(CedrusError::BadRequest);
+        }
+        if self.write_behind.is_pending(&project_id) {
+            self.write_behind_flush().await?;
+        }
+
+        Ok(self
+            .db
+            .project_revisions_load(&project_id, kind, &as_of)
+            .await?)
+    }
+
+    // Revisions of the guardrails among changed policies of the admin project, a policy that
+    // is no guardrail removing any guardrail of the same id
+    fn guardrail_revisions(
+        policies: &HashMap<PolicyId, Policy>,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Revision>, CedrusError> {
+        policies
+            .iter()
+            .map(|(policy_id, policy)| {
+                let value = Self::is_guardrail(policy)
+                    .then(|| serde_json::to_value(policy))
+                    .transpose()?;
+                Ok(Revision::new(
+                    RevisionKind::Guardrail,
+                    policy_id.to_string(),
+                    at,
+                    value,
+                ))
+            })
+            .collect()
+    }
+
+    // Records changed guardrails in the history of every other project keeping one
+    async fn project_history_record_guardrails(
+        &self,
+        revisions: Vec<Revision>,
+    ) -> Result<(), CedrusError> {
+        let project_ids: Vec<Uuid> = self
+            .history_projects
+            .iter()
+            .map(|project_id| *project_id)
+            .filter(|project_id| !project_id.is_nil())
+            .collect();
+        for project_id in project_ids {
+            self.project_history_record(&project_id, revisions.clone())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // Records changes alongside their Database write, deferred with it in write-behind mode
+    async fn project_history_record(
+        &self,
+        project_id: &Uuid,
+        revisions: Vec<Revision>,
+    ) -> Result<(), CedrusError> {
+        if revisions.is_empty() || !self.history_projects.contains(project_id) {
+            return Ok(());
+        }
+        if self.is_write_deferred(project_id) {
+            self.write_behind
+                .push(WriteOp::SaveRevisions(*project_id, revisions))
+                .await;
+        } else {
+            self.db
+                .project_revisions_save(project_id, &revisions)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // Records the current entities, policy set and guardrails of a project as of `at`
+    async fn project_history_baseline(
+        &self,
+        project_id: &Uuid,
+        at: chrono::DateTime<chrono::Utc>,
     ) -> Result<(), CedrusError> {
+        if self.write_behind.is_pending(project_id) {
+            self.write_behind_flush().await?;
+        }
+
+        let query = Query::new();
+        let entities = self.db.project_entities_load(project_id, &query).await?;
+        let policies = self.db.project_policies_load(project_id, &query).await?;
+        let templates = self.db.project_templates_load(project_id, &query).await?;
+        let template_links = self
+            .db
+            .project_template_links_load(project_id, &query)
+            .await?;
+
+        let mut revisions = Revision::entities(&entities.items, at)?;
+        revisions.extend(Revision::policies(&policies.items, at)?);
+        revisions.extend(Revision::templates(&templates.items, at)?);
+        revisions.extend(Revision::template_links(&template_links.items, at)?);
+        if !project_id.is_nil() {
+            let guardrails = self.guardrails.read().unwrap().clone();
+            revisions.extend(Self::guardrail_revisions(&guardrails, at)?);
+        }
+        for chunk in revisions.chunks(JOB_CHUNK_SIZE) {
+            self.db.project_revisions_save(project_id, chunk).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn project_entities_add(
+        &self,
+        project_id: Uuid,
+        mut entities: Vec<Entity>,
+    ) -> Result<Vec<Entity>, CedrusError> {
         let Some(_) = self.db.project_load(&project_id).await? else {
             return Err(CedrusError::NotFound);
         };
+        let _epoch = self.project_epochs.begin(&project_id);
 
-        let _ = templates.iter_mut().map(|(policy_id, template)| {
-            template
-                .annotations
-                .insert("id".to_string(), Some(policy_id.to_string()))
-        });
+        let schema = self
+            .db
+            .project_schema_load(&project_id)
+            .await?
+            .map(|schema| self.with_common_types(schema));
 
-        self.db
-            .project_templates_save(&project_id, &templates)
-            .await?;
+        let errors: Vec<String> = entities.iter().flat_map(Entity::decimal_errors).collect();
+        if !errors.is_empty() {
+            return Err(CedrusError::ValidationError(errors));
+        }
+        if let Some(schema) = &schema {
+            entities.iter_mut().for_each(|e| e.coerce(schema));
+        }
+        let cedar_schema = schema.map(|s| s.try_into()).transpose()?;
+        for entry in &entities {
+            entry.to_cedar_entity(cedar_schema.as_ref())?;
+        }
+        if dry_run::is_active() {
+            let uids: Vec<EntityUid> = entities.iter().map(|e| e.uid().clone()).collect();
+            let current = self.cache_entities(&project_id, &uids).await?;
+            let changes = EntitiesSyncReport::diff(&current, &entities)?;
+            dry_run::record(|report| {
+                report.entities.added.extend(changes.added);
+                report.entities.updated.extend(changes.updated);
+            });
+            return Ok(entities);
+        }
+
+        if self.is_write_deferred(&project_id) {
+            self.write_behind
+                .push(WriteOp::SaveEntities(project_id, entities.clone()))
+                .await;
+        } else {
+            self.db
+                .project_entities_save(&project_id, &entities)
+                .await?;
+        }
+        self.project_history_record(
+            &project_id,
+            Revision::entities(&entities, chrono::Utc::now())?,
+        )
+        .await?;
         self.cache
-            .project_set_templates(&project_id, &templates)
+            .project_set_entities(&project_id, &entities)
             .await?;
 
-        self.on_project_policy_set(&project_id).await?;
+        self.on_project_entities(&project_id).await?;
 
-        let policy_ids = templates.into_keys().collect();
-        self.publish(Event::project_add_templates(
-            self.id, project_id, policy_ids,
+        let entity_uids = entities
+            .iter()
+            .map(|e| e.uid().clone())
+            .collect::<HashSet<_>>();
+        self.publish(Event::project_add_entities(
+            self.id,
+            project_id,
+            entity_uids,
         ))
         .await;
 
-        Ok(())
+        Ok(entities)
     }
 
-    pub async fn project_templates_remove(
+    /// Entities of the resources at the end of `paths` and of their containers, deduplicated.
+    /// A container already stored keeps its attributes, tags and other parents. Fails with
+    /// `BadRequest` on a path without segment.
+    pub async fn project_entities_paths(
+        &self,
+        project_id: &Uuid,
+        paths: &[ResourcePath],
+    ) -> Result<Vec<Entity>, CedrusError> {
+        let mut resources: HashSet<EntityUid> = HashSet::new();
+        let mut entities: Vec<Entity> = Vec::new();
+        let mut positions: HashMap<EntityUid, usize> = HashMap::new();
+        for path in paths {
+            resources.insert(path.uid().ok_or(CedrusError::BadRequest)?);
+            for entity in path.entities() {
+                match positions.get(entity.uid()) {
+                    Some(&at) => entities[at].merge(entity),
+                    None => {
+                        positions.insert(entity.uid().clone(), entities.len());
+                        entities.push(entity);
+                    }
+                }
+            }
+        }
+
+        let containers: Vec<EntityUid> = positions
+            .keys()
+            .filter(|uid| !resources.contains(*uid))
+            .cloned()
+            .collect();
+        let stored = match containers.is_empty() {
+            true => Vec::new(),
+            false => self.cache_entities(project_id, &containers).await?,
+        };
+        for mut stored in stored {
+            if let Some(&at) = positions.get(stored.uid()) {
+                stored.merge(std::mem::take(&mut entities[at]));
+                entities[at] = stored;
+            }
+        }
+
+        Ok(entities)
+    }
+
+    /// Adds the resources at the end of `paths` along with the chain of their containers.
+    pub async fn project_entities_paths_add(
         &self,
         project_id: Uuid,
-        template_ids: Vec<PolicyId>,
-    ) -> Result<(), CedrusError> {
+        paths: Vec<ResourcePath>,
+    ) -> Result<Vec<Entity>, CedrusError> {
         let Some(_) = self.db.project_load(&project_id).await? else {
             return Err(CedrusError::NotFound);
         };
 
-        self.db
-            .project_templates_remove(&project_id, &template_ids)
-            .await?;
-        self.cache
-            .project_del_templates(&project_id, &template_ids)
+        let entities = self.project_entities_paths(&project_id, &paths).await?;
+        self.project_entities_add(project_id, entities).await
+    }
+
+    /// Static policies and template links of the project whose scopes name `entity`.
+    pub async fn project_entity_references(
+        &self,
+        project_id: Uuid,
+        entity: EntityUid,
+    ) -> Result<EntityReferences, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        Ok(match self.project_references.get(&project_id) {
+            Some(index) => index.references(&entity),
+            None => EntityReferences {
+                entity,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Stored objects of `relations`, each relation applied to its object with `apply`. Fails
+    /// with `NotFound` when an object is not stored.
+    async fn project_relations_apply(
+        &self,
+        project_id: &Uuid,
+        relations: &[Relation],
+        apply: impl Fn(&Relation, &mut Entity),
+    ) -> Result<Vec<Entity>, CedrusError> {
+        let objects: Vec<EntityUid> = relations.iter().map(|r| r.object.clone()).collect();
+        let mut entities: HashMap<EntityUid, Entity> = match objects.is_empty() {
+            true => HashMap::new(),
+            false => self
+                .cache_entities(project_id, &objects)
+                .await?
+                .into_iter()
+                .map(|e| (e.uid().clone(), e))
+                .collect(),
+        };
+
+        for relation in relations {
+            let object = entities
+                .get_mut(&relation.object)
+                .ok_or(CedrusError::NotFound)?;
+            apply(relation, object);
+        }
+
+        Ok(entities.into_values().collect())
+    }
+
+    /// Relates each object to its subject, storing the relation as an attribute of the object.
+    pub async fn project_relations_add(
+        &self,
+        project_id: Uuid,
+        relations: Vec<Relation>,
+    ) -> Result<Vec<Entity>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let schema = self
+            .db
+            .project_schema_load(&project_id)
+            .await?
+            .map(|schema| self.with_common_types(schema));
+        let entities = self
+            .project_relations_apply(&project_id, &relations, |relation, object| {
+                relation.relate(object, schema.as_ref())
+            })
             .await?;
+        self.project_entities_add(project_id, entities).await
+    }
 
-        self.on_project_policy_set(&project_id).await?;
+    pub async fn project_relations_remove(
+        &self,
+        project_id: Uuid,
+        relations: Vec<Relation>,
+    ) -> Result<Vec<Entity>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
 
-        let policy_ids = template_ids.into_iter().collect();
-        self.publish(Event::project_remove_templates(
-            self.id, project_id, policy_ids,
-        ))
-        .await;
+        let entities = self
+            .project_relations_apply(&project_id, &relations, |relation, object| {
+                relation.unrelate(object);
+            })
+            .await?;
+        self.project_entities_add(project_id, entities).await
+    }
 
-        Ok(())
+    /// Relations of `object`, or to `subject`, e.g. what a user owns. Fails with `BadRequest`
+    /// without either.
+    pub async fn project_relations_find(
+        &self,
+        project_id: Uuid,
+        object: Option<EntityUid>,
+        subject: Option<EntityUid>,
+        relation: Option<String>,
+    ) -> Result<Vec<Relation>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        if object.is_none() && subject.is_none() {
+            return Err(CedrusError::BadRequest);
+        }
+
+        Ok(self
+            .project_relations
+            .get(&project_id)
+            .map(|index| index.find(object.as_ref(), subject.as_ref(), relation.as_deref()))
+            .unwrap_or_default())
     }
 
-    pub async fn project_template_links_find(
+    /// Applies the desired entity set of `sync`, adding, updating and removing entities in
+    /// batches, and publishes a single event for the whole synchronization.
+    pub async fn project_entities_sync(
         &self,
         project_id: Uuid,
-        query: Query,
-    ) -> Result<PageList<TemplateLink>, CedrusError> {
+        mut sync: EntitiesSync,
+    ) -> Result<EntitiesSyncReport, CedrusError> {
         let Some(_) = self.db.project_load(&project_id).await? else {
             return Err(CedrusError::NotFound);
         };
+        let _epoch = self.project_epochs.begin(&project_id);
+
+        let report = self
+            .project_entities_sync_plan(&project_id, &mut sync)
+            .await?;
+        if report.is_empty() {
+            return Ok(report);
+        }
+        let links = self
+            .project_entities_links(&project_id, &report.removed, false)
+            .await?;
+        if !links.is_empty() {
+            self.project_template_links_remove(project_id, links)
+                .await?;
+        }
+        if dry_run::record(|dry_run| dry_run.entities = report.clone()) {
+            return Ok(report);
+        }
+
+        let changed = report.changed();
+        let upserts = sync
+            .entities
+            .into_iter()
+            .filter(|e| changed.contains(e.uid()))
+            .collect::<Vec<_>>();
+        let deferred = self.is_write_deferred(&project_id);
+        for chunk in upserts.chunks(JOB_CHUNK_SIZE) {
+            let chunk = chunk.to_vec();
+            if deferred {
+                self.write_behind
+                    .push(WriteOp::SaveEntities(project_id, chunk.clone()))
+                    .await;
+            } else {
+                self.db.project_entities_save(&project_id, &chunk).await?;
+            }
+            self.project_history_record(
+                &project_id,
+                Revision::entities(&chunk, chrono::Utc::now())?,
+            )
+            .await?;
+            self.cache.project_set_entities(&project_id, &chunk).await?;
+        }
+        for chunk in report.removed.chunks(JOB_CHUNK_SIZE) {
+            let chunk = chunk.to_vec();
+            if deferred {
+                self.write_behind
+                    .push(WriteOp::RemoveEntities(project_id, chunk.clone()))
+                    .await;
+            } else {
+                self.db.project_entities_remove(&project_id, &chunk).await?;
+            }
+            self.project_history_record(
+                &project_id,
+                Revision::entities_removed(&chunk, chrono::Utc::now()),
+            )
+            .await?;
+            self.cache.project_del_entities(&project_id, &chunk).await?;
+        }
+
+        self.on_project_entities(&project_id).await?;
+
+        self.publish(Event::project_sync_entities(
+            self.id,
+            project_id,
+            changed,
+            report.removed.iter().cloned().collect(),
+        ))
+        .await;
+
+        Ok(report)
+    }
+
+    // Validates the desired entities of a sync and computes the changes it makes
+    async fn project_entities_sync_plan(
+        &self,
+        project_id: &Uuid,
+        sync: &mut EntitiesSync,
+    ) -> Result<EntitiesSyncReport, CedrusError> {
+        let uids = sync
+            .entities
+            .iter()
+            .map(|e| e.uid())
+            .collect::<HashSet<_>>();
+        if uids.len() != sync.entities.len() || uids.iter().any(|uid| !sync.in_scope(uid)) {
+            return Err(CedrusError::BadRequest);
+        }
+
+        let schema = self
+            .db
+            .project_schema_load(project_id)
+            .await?
+            .map(|schema| self.with_common_types(schema));
+        let errors: Vec<String> = sync
+            .entities
+            .iter()
+            .flat_map(Entity::decimal_errors)
+            .collect();
+        if !errors.is_empty() {
+            return Err(CedrusError::ValidationError(errors));
+        }
+        if let Some(schema) = &schema {
+            sync.entities.iter_mut().for_each(|e| e.coerce(schema));
+        }
+        let cedar_schema = schema.map(|s| s.try_into()).transpose()?;
+        for entry in &sync.entities {
+            entry.to_cedar_entity(cedar_schema.as_ref())?;
+        }
+
+        let current = self
+            .db
+            .project_entities_load(project_id, &Query::new())
+            .await?
+            .items
+            .into_iter()
+            .filter(|e| sync.in_scope(e.uid()))
+            .collect::<Vec<_>>();
+
+        Ok(EntitiesSyncReport::diff(&current, &sync.entities)?)
+    }
+
+    /// Removes the entities, and the template links whose slot values name them when the
+    /// project cascades, failing with `EntityLinked` otherwise.
+    pub async fn project_entities_remove(
+        &self,
+        project_id: Uuid,
+        entity_uids: Vec<EntityUid>,
+    ) -> Result<(), CedrusError> {
+        self.remove_entities(project_id, entity_uids, false).await
+    }
+
+    async fn remove_entities(
+        &self,
+        project_id: Uuid,
+        entity_uids: Vec<EntityUid>,
+        cascade_links: bool,
+    ) -> Result<(), CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let links = self
+            .project_entities_links(&project_id, &entity_uids, cascade_links)
+            .await?;
+        if !links.is_empty() {
+            self.project_template_links_remove(project_id, links)
+                .await?;
+        }
+        if dry_run::record(|report| report.entities.removed.extend(entity_uids.iter().cloned())) {
+            return Ok(());
+        }
+        let _epoch = self.project_epochs.begin(&project_id);
+
+        if self.is_write_deferred(&project_id) {
+            self.write_behind
+                .push(WriteOp::RemoveEntities(project_id, entity_uids.clone()))
+                .await;
+        } else {
+            self.db
+                .project_entities_remove(&project_id, &entity_uids)
+                .await?;
+        }
+        self.project_history_record(
+            &project_id,
+            Revision::entities_removed(&entity_uids, chrono::Utc::now()),
+        )
+        .await?;
+        self.cache
+            .project_del_entities(&project_id, &entity_uids)
+            .await?;
+
+        self.on_project_entities(&project_id).await?;
+
+        let entity_uids = entity_uids.into_iter().collect::<HashSet<_>>();
+        self.publish(Event::project_remove_entities(
+            self.id,
+            project_id,
+            entity_uids,
+        ))
+        .await;
+
+        Ok(())
+    }
+
+    /// Template links whose slot values name one of `entity_uids`, to remove along with them.
+    /// Fails with `EntityLinked`, reporting the links per entity, unless the project or the
+    /// caller cascades.
+    async fn project_entities_links(
+        &self,
+        project_id: &Uuid,
+        entity_uids: &[EntityUid],
+        cascade: bool,
+    ) -> Result<Vec<PolicyId>, CedrusError> {
+        if entity_uids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let entities: HashSet<EntityUid> = entity_uids.iter().cloned().collect();
+        let linked = references::linked_entities(
+            &self.cache.project_get_template_links(project_id).await?,
+            &entities,
+        );
+        if linked.is_empty() {
+            return Ok(Vec::new());
+        }
+        if !cascade && !self.cascade_link_projects.contains(project_id) {
+            return Err(CedrusError::EntityLinked(linked));
+        }
+
+        let links: BTreeSet<PolicyId> = linked
+            .into_iter()
+            .flat_map(|references| references.template_links)
+            .collect();
+        Ok(links.into_iter().collect())
+    }
+
+    /// Removes the entities past their `expiresAt` from every project having some, returning
+    /// how many entities and template links naming them were removed per project. Read-only
+    /// projects keep them until writable again. A project failing is logged and swept again
+    /// next time without holding back the others.
+    ///
+    /// On a sharded cluster each node sweeps the projects it owns, the only ones whose
+    /// entities, and so expiries, it keeps current. Otherwise a single node sweeps at a time,
+    /// the others skipping their turn.
+    pub async fn entities_expire(&self) -> Result<Vec<(Uuid, usize, usize)>, CedrusError> {
+        if self.is_read_only() {
+            return Ok(Vec::new());
+        }
+        let _lock = if self.shards.is_sharded() {
+            None
+        } else {
+            match self
+                .cache
+                .lock_entity_expiry(ENTITY_EXPIRY_LOCK_TTL)
+                .await?
+            {
+                Some(lock) => Some(lock),
+                None => return Ok(Vec::new()),
+            }
+        };
+
+        let now = chrono::Utc::now();
+        let project_ids: Vec<Uuid> = self
+            .project_entity_expiries
+            .iter()
+            .filter(|entry| {
+                *entry.value() <= now
+                    && self.shards.owns(entry.key())
+                    && !self.is_project_read_only(entry.key())
+            })
+            .map(|entry| *entry.key())
+            .collect();
+
+        let mut removed = Vec::new();
+        for project_id in project_ids {
+            match self.project_entities_expire(project_id, now).await {
+                Ok((0, _)) => {}
+                Ok((entities, links)) => removed.push((project_id, entities, links)),
+                Err(e) => tracing::error!(
+                    "Expiry of the entities of project {} failed: {:?}",
+                    project_id,
+                    e
+                ),
+            }
+        }
+
+        Ok(removed)
+    }
+
+    // Removes the expired entities of a project, the template links naming them first, so
+    // the policy set never keeps links to missing entities
+    async fn project_entities_expire(
+        &self,
+        project_id: Uuid,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(usize, usize), CedrusError> {
+        let expired: Vec<EntityUid> = self
+            .cache_entities(&project_id, &[])
+            .await?
+            .into_iter()
+            .filter(|e| e.is_expired(now))
+            .map(|e| e.uid().clone())
+            .collect();
+
+        if expired.is_empty() {
+            // Another node removed them already
+            self.on_project_entities(&project_id).await?;
+            return Ok((0, 0));
+        }
+
+        let links = self
+            .project_entities_links(&project_id, &expired, true)
+            .await?;
+        let linked = links.len();
+        if !links.is_empty() {
+            self.project_template_links_remove(project_id, links)
+                .await?;
+        }
+        let count = expired.len();
+        self.remove_entities(project_id, expired, true).await?;
+        Ok((count, linked))
+    }
+
+    /// Removes the given entities and reports the outcome per entity.
+    pub async fn project_entities_batch_remove(
+        &self,
+        project_id: Uuid,
+        entity_uids: Vec<EntityUid>,
+    ) -> Result<Vec<BatchDeleteResult<EntityUid>>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        if entity_uids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let existing: HashSet<EntityUid> = self
+            .cache_entities(&project_id, &entity_uids)
+            .await?
+            .into_iter()
+            .map(|e| e.uid().clone())
+            .collect();
+
+        let deleted: Vec<EntityUid> = existing.iter().cloned().collect();
+        if !deleted.is_empty() {
+            self.project_entities_remove(project_id, deleted).await?;
+        }
+
+        Ok(entity_uids
+            .into_iter()
+            .map(|uid| match existing.contains(&uid) {
+                true => BatchDeleteResult::new(uid, BatchDeleteStatus::Deleted),
+                false => BatchDeleteResult::new(uid, BatchDeleteStatus::NotFound),
+            })
+            .collect())
+    }
+
+    pub async fn project_policies_find(
+        &self,
+        project_id: Uuid,
+        query: Query,
+    ) -> Result<PageHash<PolicyId, Policy>, CedrusError> {
+        let page = self.db.project_policies_load(&project_id, &query).await?;
+        Ok(page)
+    }
+
+    /// Policies of a project as they were at `as_of`, replayed from its history.
+    pub async fn project_policies_as_of(
+        &self,
+        project_id: Uuid,
+        as_of: chrono::DateTime<chrono::Utc>,
+    ) -> Result<HashMap<PolicyId, Policy>, CedrusError> {
+        let revisions = self
+            .project_history_load(project_id, RevisionKind::Policy, as_of)
+            .await?;
+
+        Revision::replay(revisions, as_of)
+            .into_iter()
+            .map(|(id, policy)| Ok((id.into(), serde_json::from_value(policy)?)))
+            .collect()
+    }
+
+    /// Policy set of a project as evaluated at `as_of`, its policies, templates, template
+    /// links and the guardrails of the admin project replayed from its history.
+    pub async fn project_policy_set_as_of(
+        &self,
+        project_id: Uuid,
+        as_of: chrono::DateTime<chrono::Utc>,
+    ) -> Result<PolicySet, CedrusError> {
+        let static_policies = self.project_policies_as_of(project_id, as_of).await?;
+        let templates = self
+            .project_history_replay(project_id, RevisionKind::Template, as_of)
+            .await?
+            .into_iter()
+            .map(|(id, template)| Ok((id.into(), serde_json::from_value(template)?)))
+            .collect::<Result<_, CedrusError>>()?;
+        let template_links = self
+            .project_history_replay(project_id, RevisionKind::TemplateLink, as_of)
+            .await?
+            .into_values()
+            .map(|link| Ok(serde_json::from_value(link)?))
+            .collect::<Result<_, CedrusError>>()?;
+        let mut guardrails: HashMap<PolicyId, Policy> = self
+            .project_history_replay(project_id, RevisionKind::Guardrail, as_of)
+            .await?
+            .into_iter()
+            .map(|(id, policy)| Ok((id.into(), serde_json::from_value(policy)?)))
+            .collect::<Result<_, CedrusError>>()?;
+        guardrails.retain(|_id, policy| !self.is_policy_excluded(&policy.annotations));
+
+        let policy_set = PolicySet {
+            static_policies,
+            templates,
+            template_links,
+        };
+        let (policy_set, _) = self.effective_policy_set(&project_id, policy_set, &guardrails);
+        Ok(policy_set)
+    }
+
+    // Content of each id of a kind still present at `as_of`, replayed from the history
+    async fn project_history_replay(
+        &self,
+        project_id: Uuid,
+        kind: RevisionKind,
+        as_of: chrono::DateTime<chrono::Utc>,
+    ) -> Result<BTreeMap<String, Value>, CedrusError> {
+        let revisions = self.project_history_load(project_id, kind, as_of).await?;
+        Ok(Revision::replay(revisions, as_of))
+    }
+
+    /// Changes of the static policies of a project between two times of its history.
+    pub async fn project_revisions_diff(
+        &self,
+        project_id: Uuid,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+        format: DiffFormat,
+    ) -> Result<RevisionDiff, CedrusError> {
+        let before = self.project_policies_as_of(project_id, from).await?;
+        let after = self.project_policies_as_of(project_id, to).await?;
+
+        RevisionDiff::new(from, to, &before, &after, format)
+    }
+
+    pub async fn project_policies_add(
+        &self,
+        project_id: Uuid,
+        policies: HashMap<PolicyId, Policy>,
+    ) -> Result<HashMap<PolicyId, Policy>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let _lock = self.policy_set_lock(&project_id).await?;
+        let added = PolicySet {
+            static_policies: policies.clone(),
+            ..Default::default()
+        };
+        self.project_policy_set_check(&project_id, &added).await?;
+        self.project_policies_add_locked(project_id, policies).await
+    }
+
+    // Adds policies to a project whose policy set lock the caller holds
+    async fn project_policies_add_locked(
+        &self,
+        project_id: Uuid,
+        mut policies: HashMap<PolicyId, Policy>,
+    ) -> Result<HashMap<PolicyId, Policy>, CedrusError> {
+        let _epoch = self.project_epochs.begin(&project_id);
+
+        for (id, policy) in policies.iter_mut() {
+            policy
+                .annotations
+                .insert("id".to_string(), Some(id.to_string()));
+        }
+        if dry_run::record(|report| {
+            report
+                .plan
+                .policies_changed
+                .extend(policies.keys().cloned())
+        }) {
+            return Ok(policies);
+        }
+
+        if self.is_write_deferred(&project_id) {
+            self.write_behind
+                .push(WriteOp::SavePolicies(project_id, policies.clone()))
+                .await;
+        } else {
+            self.db
+                .project_policies_save(&project_id, &policies)
+                .await?;
+        }
+        let now = chrono::Utc::now();
+        self.project_history_record(&project_id, Revision::policies(&policies, now)?)
+            .await?;
+        if project_id.is_nil() {
+            self.project_history_record_guardrails(Self::guardrail_revisions(&policies, now)?)
+                .await?;
+        }
+        self.cache
+            .project_set_policies(&project_id, &policies)
+            .await?;
+
+        self.on_project_policy_set(&project_id).await?;
+
+        let policy_ids = policies.keys().cloned().collect();
+        self.publish(Event::project_add_policies(self.id, project_id, policy_ids))
+            .await;
+
+        Ok(policies)
+    }
+
+    pub async fn project_policies_remove(
+        &self,
+        project_id: Uuid,
+        policy_ids: Vec<PolicyId>,
+    ) -> Result<(), CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let _lock = self.policy_set_lock(&project_id).await?;
+        self.project_policies_remove_locked(project_id, policy_ids)
+            .await
+    }
+
+    // Removes policies from a project whose policy set lock the caller holds
+    async fn project_policies_remove_locked(
+        &self,
+        project_id: Uuid,
+        policy_ids: Vec<PolicyId>,
+    ) -> Result<(), CedrusError> {
+        let _epoch = self.project_epochs.begin(&project_id);
+        if dry_run::record(|report| {
+            report
+                .plan
+                .policies_removed
+                .extend(policy_ids.iter().cloned())
+        }) {
+            return Ok(());
+        }
+
+        if self.is_write_deferred(&project_id) {
+            self.write_behind
+                .push(WriteOp::RemovePolicies(project_id, policy_ids.clone()))
+                .await;
+        } else {
+            self.db
+                .project_policies_remove(&project_id, &policy_ids)
+                .await?;
+        }
+        let now = chrono::Utc::now();
+        self.project_history_record(&project_id, Revision::policies_removed(&policy_ids, now))
+            .await?;
+        if project_id.is_nil() {
+            let revisions = Revision::removed(RevisionKind::Guardrail, &policy_ids, now);
+            self.project_history_record_guardrails(revisions).await?;
+        }
+        self.cache
+            .project_del_policies(&project_id, &policy_ids)
+            .await?;
+
+        self.on_project_policy_set(&project_id).await?;
+
+        let policy_ids = policy_ids.into_iter().collect();
+        self.publish(Event::project_remove_policies(
+            self.id, project_id, policy_ids,
+        ))
+        .await;
+
+        Ok(())
+    }
+
+    /// Removes the given policies and reports the outcome per policy.
+    pub async fn project_policies_batch_remove(
+        &self,
+        project_id: Uuid,
+        policy_ids: Vec<PolicyId>,
+    ) -> Result<Vec<BatchDeleteResult<PolicyId>>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let policies = self.cache.project_get_policies(&project_id).await?;
+
+        let deleted: Vec<PolicyId> = policy_ids
+            .iter()
+            .filter(|id| policies.contains_key(*id))
+            .cloned()
+            .collect();
+        if !deleted.is_empty() {
+            self.project_policies_remove(project_id, deleted).await?;
+        }
+
+        Ok(policy_ids
+            .into_iter()
+            .map(|id| match policies.contains_key(&id) {
+                true => BatchDeleteResult::new(id, BatchDeleteStatus::Deleted),
+                false => BatchDeleteResult::new(id, BatchDeleteStatus::NotFound),
+            })
+            .collect())
+    }
+
+    pub async fn project_templates_find(
+        &self,
+        project_id: Uuid,
+        query: Query,
+    ) -> Result<PageHash<PolicyId, Template>, CedrusError> {
+        Ok(self.db.project_templates_load(&project_id, &query).await?)
+    }
+
+    pub async fn project_templates_add(
+        &self,
+        project_id: Uuid,
+        templates: HashMap<PolicyId, Template>,
+    ) -> Result<HashMap<PolicyId, Template>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let _lock = self.policy_set_lock(&project_id).await?;
+        let added = PolicySet {
+            templates: templates.clone(),
+            ..Default::default()
+        };
+        self.project_policy_set_check(&project_id, &added).await?;
+        self.project_templates_add_locked(project_id, templates)
+            .await
+    }
+
+    // Adds templates to a project whose policy set lock the caller holds
+    async fn project_templates_add_locked(
+        &self,
+        project_id: Uuid,
+        mut templates: HashMap<PolicyId, Template>,
+    ) -> Result<HashMap<PolicyId, Template>, CedrusError> {
+        let _epoch = self.project_epochs.begin(&project_id);
+
+        for (policy_id, template) in templates.iter_mut() {
+            template
+                .annotations
+                .insert("id".to_string(), Some(policy_id.to_string()));
+        }
+        if dry_run::record(|report| {
+            report
+                .plan
+                .templates_changed
+                .extend(templates.keys().cloned())
+        }) {
+            return Ok(templates);
+        }
+
+        self.db
+            .project_templates_save(&project_id, &templates)
+            .await?;
+        self.project_history_record(
+            &project_id,
+            Revision::templates(&templates, chrono::Utc::now())?,
+        )
+        .await?;
+        self.cache
+            .project_set_templates(&project_id, &templates)
+            .await?;
+
+        self.on_project_policy_set(&project_id).await?;
+
+        let policy_ids = templates.keys().cloned().collect();
+        self.publish(Event::project_add_templates(
+            self.id, project_id, policy_ids,
+        ))
+        .await;
+
+        Ok(templates)
+    }
+
+    pub async fn project_templates_remove(
+        &self,
+        project_id: Uuid,
+        template_ids: Vec<PolicyId>,
+        force: bool,
+    ) -> Result<(), CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let _lock = self.policy_set_lock(&project_id).await?;
+        self.project_templates_remove_locked(project_id, template_ids, force)
+            .await
+    }
+
+    // Removes templates from a project whose policy set lock the caller holds
+    async fn project_templates_remove_locked(
+        &self,
+        project_id: Uuid,
+        template_ids: Vec<PolicyId>,
+        force: bool,
+    ) -> Result<(), CedrusError> {
+        let _epoch = self.project_epochs.begin(&project_id);
+
+        // Links left behind by a removed template break the next PolicySet
+        // rebuild, so either refuse the removal or take the links with it.
+        let link_ids: Vec<PolicyId> = self
+            .cache
+            .project_get_template_links(&project_id)
+            .await?
+            .into_iter()
+            .filter(|tl| template_ids.contains(&tl.template_id))
+            .map(|tl| tl.new_id)
+            .collect();
+        if !link_ids.is_empty() && !force {
+            return Err(CedrusError::Conflict);
+        }
+        if dry_run::record(|report| {
+            report
+                .plan
+                .template_links_removed
+                .extend(link_ids.iter().cloned());
+            report
+                .plan
+                .templates_removed
+                .extend(template_ids.iter().cloned());
+        }) {
+            return Ok(());
+        }
+        if !link_ids.is_empty() {
+            self.db
+                .project_template_links_remove(&project_id, &link_ids)
+                .await?;
+            self.project_history_record(
+                &project_id,
+                Revision::removed(RevisionKind::TemplateLink, &link_ids, chrono::Utc::now()),
+            )
+            .await?;
+            self.cache
+                .project_del_template_links(&project_id, &link_ids)
+                .await?;
+        }
+
+        self.db
+            .project_templates_remove(&project_id, &template_ids)
+            .await?;
+        self.project_history_record(
+            &project_id,
+            Revision::removed(RevisionKind::Template, &template_ids, chrono::Utc::now()),
+        )
+        .await?;
+        self.cache
+            .project_del_templates(&project_id, &template_ids)
+            .await?;
+
+        self.on_project_policy_set(&project_id).await?;
+
+        if !link_ids.is_empty() {
+            let policy_ids = link_ids.into_iter().collect();
+            self.publish(Event::project_remove_template_links(
+                self.id, project_id, policy_ids,
+            ))
+            .await;
+        }
+
+        let policy_ids = template_ids.into_iter().collect();
+        self.publish(Event::project_remove_templates(
+            self.id, project_id, policy_ids,
+        ))
+        .await;
+
+        Ok(())
+    }
+
+    /// Removes the given templates and reports the outcome per template. Templates still
+    /// referenced by template links are reported as conflicts unless `force` is set, in which
+    /// case their links are removed along with them.
+    pub async fn project_templates_batch_remove(
+        &self,
+        project_id: Uuid,
+        template_ids: Vec<PolicyId>,
+        force: bool,
+    ) -> Result<Vec<BatchDeleteResult<PolicyId>>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let templates = self.cache.project_get_templates(&project_id).await?;
+        let linked: HashSet<PolicyId> = self
+            .cache
+            .project_get_template_links(&project_id)
+            .await?
+            .into_iter()
+            .map(|tl| tl.template_id)
+            .collect();
+
+        let results: Vec<BatchDeleteResult<PolicyId>> = template_ids
+            .into_iter()
+            .map(|id| {
+                let status = if !templates.contains_key(&id) {
+                    BatchDeleteStatus::NotFound
+                } else if linked.contains(&id) && !force {
+                    BatchDeleteStatus::Conflict
+                } else {
+                    BatchDeleteStatus::Deleted
+                };
+                BatchDeleteResult::new(id, status)
+            })
+            .collect();
+
+        let deleted: Vec<PolicyId> = results
+            .iter()
+            .filter(|r| r.status == BatchDeleteStatus::Deleted)
+            .map(|r| r.id.clone())
+            .collect();
+        if !deleted.is_empty() {
+            self.project_templates_remove(project_id, deleted, force)
+                .await?;
+        }
+
+        Ok(results)
+    }
+
+    pub async fn project_template_links_find(
+        &self,
+        project_id: Uuid,
+        query: Query,
+    ) -> Result<PageList<TemplateLink>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        Ok(self
+            .db
+            .project_template_links_load(&project_id, &query)
+            .await?)
+    }
+
+    pub async fn project_template_links_add(
+        &self,
+        project_id: Uuid,
+        template_links: Vec<TemplateLink>,
+    ) -> Result<Vec<TemplateLink>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let _lock = self.policy_set_lock(&project_id).await?;
+        let added = PolicySet {
+            template_links: template_links.clone(),
+            ..Default::default()
+        };
+        self.project_policy_set_check(&project_id, &added).await?;
+        self.project_template_links_add_locked(project_id, template_links)
+            .await
+    }
+
+    // Adds template links to a project whose policy set lock the caller holds
+    async fn project_template_links_add_locked(
+        &self,
+        project_id: Uuid,
+        template_links: Vec<TemplateLink>,
+    ) -> Result<Vec<TemplateLink>, CedrusError> {
+        let _epoch = self.project_epochs.begin(&project_id);
+        if dry_run::record(|report| {
+            report
+                .plan
+                .template_links_changed
+                .extend(template_links.iter().map(|tl| tl.new_id.clone()))
+        }) {
+            return Ok(template_links);
+        }
+
+        self.db
+            .project_template_links_save(&project_id, &template_links)
+            .await?;
+        self.project_history_record(
+            &project_id,
+            Revision::template_links(&template_links, chrono::Utc::now())?,
+        )
+        .await?;
+        self.cache
+            .project_set_template_links(&project_id, &template_links)
+            .await?;
+
+        self.on_project_policy_set(&project_id).await?;
+
+        let policy_ids = template_links.iter().map(|tl| tl.new_id.clone()).collect();
+        self.publish(Event::project_add_template_links(
+            self.id, project_id, policy_ids,
+        ))
+        .await;
+
+        Ok(template_links)
+    }
+
+    /// Links a template to every principal of the batch, those listed and the stored entities
+    /// matching its selector. Fails with `BadRequest` when the template needs a resource the
+    /// batch does not name.
+    pub async fn project_template_links_batch_add(
+        &self,
+        project_id: Uuid,
+        batch: TemplateLinkBatch,
+    ) -> Result<Vec<TemplateLink>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let templates = self.cache.project_get_templates(&project_id).await?;
+        let template = templates
+            .get(&batch.template_id)
+            .ok_or(CedrusError::NotFound)?;
+
+        let mut principals = batch.principals.clone();
+        if let Some(selector) = &batch.selector {
+            let mut query = Query::new();
+            query.selector = Some(selector.clone());
+            query.limit = Some(ENTITIES_STREAM_PAGE_SIZE);
+            loop {
+                let page = self.db.project_entities_load(&project_id, &query).await?;
+                let done = page.items.is_empty();
+                principals.extend(page.items.into_iter().map(|e| e.uid().clone()));
+                match page.last_key {
+                    Some(last_key) if !done => query.start_key = Some(last_key),
+                    _ => break,
+                }
+            }
+        }
+
+        let template_links = batch
+            .template_links(template, &principals)
+            .ok_or(CedrusError::BadRequest)?;
+        if template_links.is_empty() {
+            return Ok(template_links);
+        }
+
+        self.project_template_links_add(project_id, template_links)
+            .await
+    }
+
+    pub async fn project_template_links_remove(
+        &self,
+        project_id: Uuid,
+        policy_ids: Vec<PolicyId>,
+    ) -> Result<(), CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let _lock = self.policy_set_lock(&project_id).await?;
+        self.project_template_links_remove_locked(project_id, policy_ids)
+            .await
+    }
+
+    // Removes template links from a project whose policy set lock the caller holds
+    async fn project_template_links_remove_locked(
+        &self,
+        project_id: Uuid,
+        policy_ids: Vec<PolicyId>,
+    ) -> Result<(), CedrusError> {
+        let _epoch = self.project_epochs.begin(&project_id);
+        if dry_run::record(|report| {
+            report
+                .plan
+                .template_links_removed
+                .extend(policy_ids.iter().cloned())
+        }) {
+            return Ok(());
+        }
+
+        self.db
+            .project_template_links_remove(&project_id, &policy_ids)
+            .await?;
+        self.project_history_record(
+            &project_id,
+            Revision::removed(RevisionKind::TemplateLink, &policy_ids, chrono::Utc::now()),
+        )
+        .await?;
+        self.cache
+            .project_del_template_links(&project_id, &policy_ids)
+            .await?;
+
+        self.on_project_policy_set(&project_id).await?;
+
+        let policy_ids = policy_ids.into_iter().collect();
+        self.publish(Event::project_remove_template_links(
+            self.id, project_id, policy_ids,
+        ))
+        .await;
+
+        Ok(())
+    }
+
+    /// Signs the policy set of a project as a bundle.
+    pub async fn project_bundle_export(
+        &self,
+        project_id: Uuid,
+    ) -> Result<PolicyBundle, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let query = Query::new();
+        let policy_set = PolicySet {
+            static_policies: self
+                .db
+                .project_policies_load(&project_id, &query)
+                .await?
+                .items,
+            templates: self
+                .db
+                .project_templates_load(&project_id, &query)
+                .await?
+                .items,
+            template_links: self
+                .db
+                .project_template_links_load(&project_id, &query)
+                .await?
+                .items,
+        };
+
+        self.bundle_keys.sign(project_id, policy_set)
+    }
+
+    /// Checks that a bundle was exported from the project and signed by a trusted key.
+    pub fn project_bundle_verify(
+        &self,
+        project_id: &Uuid,
+        bundle: &PolicyBundle,
+    ) -> Result<(), CedrusError> {
+        if bundle.metadata.project_id != *project_id {
+            return Err(CedrusError::BundleError(
+                "the bundle was exported from another project".to_string(),
+            ));
+        }
+        self.bundle_keys.verify(bundle)
+    }
+
+    /// Adds the policy set of a bundle to a project once its signature is verified and the
+    /// project validated with it, returning the policies added. Nothing is written when the
+    /// bundle is invalid, and the policy set lock is held from the validation to the last
+    /// write.
+    pub async fn project_bundle_import(
+        &self,
+        project_id: Uuid,
+        bundle: PolicyBundle,
+    ) -> Result<PolicySet, CedrusError> {
+        self.project_bundle_verify(&project_id, &bundle)?;
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let _lock = self.policy_set_lock(&project_id).await?;
+        self.project_policy_set_check(&project_id, &bundle.policy_set)
+            .await?;
+        self.project_policy_set_add_locked(project_id, bundle.policy_set)
+            .await
+    }
+
+    /// Adds the templates, then the policies and the template links of a policy set under a
+    /// single policy set lock, returning them as stored.
+    pub async fn project_policy_set_add(
+        &self,
+        project_id: Uuid,
+        policy_set: PolicySet,
+    ) -> Result<PolicySet, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let _lock = self.policy_set_lock(&project_id).await?;
+        self.project_policy_set_check(&project_id, &policy_set)
+            .await?;
+        self.project_policy_set_add_locked(project_id, policy_set)
+            .await
+    }
+
+    // Builds the policy set of a project with `added` as its next compilation would, so an
+    // addition breaking it fails before anything is written
+    async fn project_policy_set_check(
+        &self,
+        project_id: &Uuid,
+        added: &PolicySet,
+    ) -> Result<(), CedrusError> {
+        let mut policy_set = self.cache.project_get_policy_set(project_id).await?;
+        policy_set
+            .static_policies
+            .extend(added.static_policies.clone());
+        policy_set.templates.extend(added.templates.clone());
+        policy_set.template_links.retain(|tl| {
+            !added
+                .template_links
+                .iter()
+                .any(|new| new.new_id == tl.new_id)
+        });
+        policy_set
+            .template_links
+            .extend(added.template_links.iter().cloned());
+
+        let (policy_set, _) = self.live_policy_set(project_id, policy_set);
+        let _: cedar_policy::PolicySet = policy_set.try_into()?;
+
+        Ok(())
+    }
+
+    // Adds a policy set to a project whose policy set lock the caller holds
+    async fn project_policy_set_add_locked(
+        &self,
+        project_id: Uuid,
+        policy_set: PolicySet,
+    ) -> Result<PolicySet, CedrusError> {
+        let PolicySet {
+            static_policies,
+            templates,
+            template_links,
+        } = policy_set;
+        let mut policy_set = PolicySet::default();
+        if !templates.is_empty() {
+            policy_set.templates = self
+                .project_templates_add_locked(project_id, templates)
+                .await?;
+        }
+        if !static_policies.is_empty() {
+            policy_set.static_policies = self
+                .project_policies_add_locked(project_id, static_policies)
+                .await?;
+        }
+        if !template_links.is_empty() {
+            policy_set.template_links = self
+                .project_template_links_add_locked(project_id, template_links)
+                .await?;
+        }
+
+        Ok(policy_set)
+    }
+
+    /// Compares a project with its Git repository and, when `apply` is set, reconciles the
+    /// schema, policies and templates of the project to match it.
+    pub async fn project_gitops_sync(
+        &self,
+        project_id: Uuid,
+        apply: bool,
+    ) -> Result<GitOpsReport, CedrusError> {
+        let Some(project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let Some(source) = project.gitops else {
+            return Err(CedrusError::BadRequest);
+        };
+
+        // Syncs of a project share its checkout
+        let lock = self
+            .gitops_locks
+            .entry(project_id)
+            .or_default()
+            .value()
+            .clone();
+        let _lock = lock.lock().await;
+        let GitOpsState {
+            commit,
+            state: mut desired,
+        } = gitops::load(&project_id, &source, &self.gitops).await?;
+        desired.annotate();
+
+        // Template links are not described by the repository, only those of removed
+        // templates go
+        let _policy_set_lock = self.policy_set_lock(&project_id).await?;
+        let current = self.project_state_load(&project_id).await?;
+        desired.template_links = current
+            .template_links
+            .iter()
+            .filter(|tl| desired.templates.contains_key(&tl.template_id))
+            .cloned()
+            .collect();
+        desired.validate(&self.common_types())?;
+
+        let plan = StatePlan::new(&current, &desired)?;
+        if apply {
+            self.project_state_plan_apply(project_id, desired, &plan)
+                .await?;
+        }
+
+        Ok(GitOpsReport {
+            project_id,
+            commit,
+            plan,
+            applied: apply,
+            synced_at: chrono::Utc::now(),
+        })
+    }
+
+    /// Makes the schema, policies, templates and template links of a project match the
+    /// state, changing only what differs, and returns the changes made. Without `apply`, the
+    /// state is validated and the changes planned only.
+    pub async fn project_state_apply(
+        &self,
+        project_id: Uuid,
+        mut desired: ProjectState,
+        apply: bool,
+    ) -> Result<StatePlan, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        desired.annotate();
+        desired.validate(&self.common_types())?;
+
+        let _lock = self.policy_set_lock(&project_id).await?;
+        let current = self.project_state_load(&project_id).await?;
+        let plan = StatePlan::new(&current, &desired)?;
+        if apply {
+            self.project_state_plan_apply(project_id, desired, &plan)
+                .await?;
+        }
+
+        Ok(plan)
+    }
+
+    // Projects that are environments of the project
+    fn environment_ids(&self, project_id: &Uuid) -> Vec<Uuid> {
+        self.project_environments
+            .iter()
+            .filter(|environment| environment.parent_id == *project_id)
+            .map(|environment| *environment.key())
+            .collect()
+    }
+
+    pub fn project_environment_id(&self, project_id: &Uuid, name: &str) -> Option<Uuid> {
+        self.project_environments
+            .iter()
+            .find(|environment| environment.parent_id == *project_id && environment.name == name)
+            .map(|environment| *environment.key())
+    }
+
+    /// Environments of a project, sorted by name.
+    pub async fn project_environments_find(
+        &self,
+        project_id: Uuid,
+    ) -> Result<Vec<Project>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let mut environments = Vec::new();
+        for environment_id in self.environment_ids(&project_id) {
+            if let Some(environment) = self.db.project_load(&environment_id).await? {
+                environments.push(environment);
+            }
+        }
+        environments.sort_by(|a, b| {
+            let name = |p: &Project| p.environment.as_ref().map(|e| e.name.clone());
+            name(a).cmp(&name(b))
+        });
+
+        Ok(environments)
+    }
+
+    /// Creates an environment of a project, a project of its own taking the region, labels
+    /// and evaluation settings of the project, and sharing its schema.
+    pub async fn project_environment_create(
+        &self,
+        project_id: Uuid,
+        name: String,
+        owner: EntityUid,
+    ) -> Result<Project, CedrusError> {
+        let Some(parent) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        // Environments don't nest
+        if project_id.is_nil()
+            || parent.environment.is_some()
+            || !ProjectEnvironment::name_valid(&name)
+        {
+            return Err(CedrusError::BadRequest);
+        }
+        if self.project_environment_id(&project_id, &name).is_some() {
+            return Err(CedrusError::Conflict);
+        }
+
+        let mut project = Project::new(
+            Uuid::now_v7(),
+            format!("{}/{}", parent.name, name),
+            owner.clone(),
+        );
+        project.region = parent.region;
+        project.labels = parent.labels;
+        project.time_context = parent.time_context;
+        project.request_validation = parent.request_validation;
+        project.linked_entity_removal = parent.linked_entity_removal;
+        project.evaluation_timeout_millis = parent.evaluation_timeout_millis;
+        project.environment = Some(ProjectEnvironment {
+            parent_id: project_id,
+            name,
+        });
+        let project = self.project_create(project, owner).await?;
+
+        if let Some(schema) = self.db.project_schema_load(&project_id).await? {
+            self.project_schema_set(&[project.id], schema).await?;
+        }
+
+        Ok(project)
+    }
+
+    /// Makes the policies, templates and template links of an environment of a project match
+    /// those of another of its environments, once they validate against their schema.
+    pub async fn project_promote(
+        &self,
+        project_id: Uuid,
+        from: &str,
+        promotion: Promotion,
+    ) -> Result<PromotionReport, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let (Some(from_id), Some(to_id)) = (
+            self.project_environment_id(&project_id, from),
+            self.project_environment_id(&project_id, &promotion.to),
+        ) else {
+            return Err(CedrusError::NotFound);
+        };
+        // The policies of a GitOps project come from its repository only
+        if from_id == to_id || self.is_project_gitops(&to_id) {
+            return Err(CedrusError::BadRequest);
+        }
+
+        // The version of the policies promoted, as the source evaluates them, so a change
+        // after it was tested is caught even before this node compiled it
+        if self.write_behind.is_pending(&from_id) {
+            self.write_behind_flush().await?;
+        }
+        let source = self.project_state_load(&from_id).await?;
+        let (source_policy_set, _) = self.live_policy_set(&from_id, source.policy_set());
+        let version = policy_set_version(&source_policy_set)?;
+        if promotion
+            .version
+            .as_ref()
+            .is_some_and(|tested| *tested != version)
+        {
+            return Err(CedrusError::Conflict);
+        }
+
+        let _lock = self.policy_set_lock(&to_id).await?;
+        let current = self.project_state_load(&to_id).await?;
+        let mut desired = ProjectState {
+            schema: current.schema.clone(),
+            ..source
+        };
+        desired.annotate();
+        desired.validate(&self.common_types())?;
+        if let Some(schema) = &desired.schema {
+            environment::validate(self.with_common_types(schema.clone()), desired.policy_set())?;
+        }
+
+        let plan = StatePlan::new(&current, &desired)?;
+        self.project_state_plan_apply(to_id, desired, &plan).await?;
+
+        Ok(PromotionReport {
+            from_project_id: from_id,
+            to_project_id: to_id,
+            version,
+            plan,
+            promoted_at: chrono::Utc::now(),
+        })
+    }
+
+    /// Attaches a candidate policy set to a project, replacing its previous one, or detaches
+    /// it. The candidate must build and validate against the schema of the project.
+    pub async fn project_candidate_set(
+        &self,
+        project_id: Uuid,
+        candidate: Option<ProjectCandidate>,
+    ) -> Result<Project, CedrusError> {
+        let Some(mut project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let now = chrono::Utc::now();
+        let candidate = match candidate {
+            Some(mut candidate) => {
+                if !candidate.is_valid() {
+                    return Err(CedrusError::BadRequest);
+                }
+                match self.project_schemas.get(&project_id) {
+                    Some(schema) => environment::validate(
+                        self.with_common_types(schema.clone()),
+                        candidate.policy_set.clone(),
+                    )?,
+                    None => {
+                        let _: cedar_policy::PolicySet = candidate.policy_set.clone().try_into()?;
+                    }
+                }
+                candidate.attached_at = Some(now);
+                Some(candidate)
+            }
+            None if project.candidate.is_none() => return Ok(project),
+            None => None,
+        };
+
+        project.candidate = candidate;
+        project.updated_at = now;
+
+        self.db.project_save(&project).await?;
+        self.cache.project_set(&project).await?;
+
+        self.on_project_update(&project);
+
+        self.publish(Event::project_update(self.id, project_id))
+            .await;
+
+        Ok(project)
+    }
+
+    pub fn project_candidate_report(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<CandidateReport, CedrusError> {
+        self.project_candidates
+            .get(project_id)
+            .map(|shadow| shadow.report())
+            .ok_or(CedrusError::NotFound)
+    }
+
+    /// Slots to evaluate `requests` requests of a project against its candidate, taken only
+    /// when free right away: a slot of the project and one of the candidate evaluations of
+    /// the node. Otherwise the requests are counted as skipped by the candidate and `None`
+    /// is returned, shadow evaluations never queueing behind the live ones.
+    pub fn project_candidate_slots(
+        &self,
+        project_id: &Uuid,
+        requests: usize,
+    ) -> Option<Vec<OwnedSemaphorePermit>> {
+        let slots = self
+            .candidate_evaluations
+            .clone()
+            .try_acquire_owned()
+            .ok()
+            .and_then(|permit| {
+                let mut permits = self.evaluation_slots.try_acquire(&[*project_id])?;
+                permits.push(permit);
+                Some(permits)
+            });
+        if slots.is_none()
+            && let Some(shadow) = self.project_candidates.get(project_id)
+        {
+            shadow.skip(requests);
+        }
+        slots
+    }
+
+    /// Evaluates requests of a project against its candidate policy set, on the same entities
+    /// as the live one, and records the decisions differing from the live `responses`.
+    pub fn project_candidate_evaluate(
+        &self,
+        project_id: &Uuid,
+        requests: Vec<Request>,
+        principal_entity: Option<Entity>,
+        responses: &[Response],
+    ) -> Result<(), CedrusError> {
+        let Some(shadow) = self.project_candidates.get(project_id) else {
+            return Ok(());
+        };
+        let cedar_schema = self
+            .project_cedar_schemas
+            .get(project_id)
+            .ok_or(CedrusError::NotFound)?;
+        let cedar_entities = self
+            .project_cedar_entities
+            .get(project_id)
+            .ok_or(CedrusError::NotFound)?;
+        let principal = principal_entity.as_ref().map(|e| e.uid().clone());
+        let cedar_entities =
+            Self::with_principal(&cedar_entities, cedar_schema.as_ref(), principal_entity)?;
+
+        let cedar_requests = self.cedar_requests(
+            project_id,
+            requests.clone(),
+            principal.as_ref(),
+            cedar_schema.as_ref(),
+        )?;
+
+        let authorizer = cedar_policy::Authorizer::new();
+        for ((request, cedar_request), live) in requests.iter().zip(&cedar_requests).zip(responses)
+        {
+            let answer = authorizer.is_authorized(cedar_request, &shadow.policies, &cedar_entities);
+            let candidate = Response::from(answer);
+            if shadow.record(request, live, &candidate) {
+                tracing::info!(
+                    target: "cedrus::candidate",
+                    "Project {}: candidate {} decides {:?} where the live policies decide {:?}: {}",
+                    project_id,
+                    shadow.version,
+                    candidate.decision,
+                    live.decision,
+                    serde_json::to_string(request).unwrap_or_default()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes the candidate policy set of a project its live one, changing only what differs,
+    /// and detaches it.
+    pub async fn project_candidate_promote(
+        &self,
+        project_id: Uuid,
+    ) -> Result<StatePlan, CedrusError> {
+        let Some(project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let Some(candidate) = project.candidate else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let _lock = self.policy_set_lock(&project_id).await?;
+        let current = self.project_state_load(&project_id).await?;
+        let mut desired = ProjectState {
+            schema: current.schema.clone(),
+            policies: candidate.policy_set.static_policies,
+            templates: candidate.policy_set.templates,
+            template_links: candidate.policy_set.template_links,
+        };
+        desired.annotate();
+        desired.validate(&self.common_types())?;
+        if let Some(schema) = &desired.schema {
+            environment::validate(
+                self.with_common_types(schema.clone()),
+                PolicySet {
+                    static_policies: desired.policies.clone(),
+                    templates: desired.templates.clone(),
+                    template_links: desired.template_links.clone(),
+                },
+            )?;
+        }
+
+        let plan = StatePlan::new(&current, &desired)?;
+        self.project_state_plan_apply(project_id, desired, &plan)
+            .await?;
+        self.project_candidate_set(project_id, None).await?;
+
+        Ok(plan)
+    }
+
+    // Loads the schema and policies of a project from the Database
+    async fn project_state_load(&self, project_id: &Uuid) -> Result<ProjectState, CedrusError> {
+        let query = Query::new();
+        Ok(ProjectState {
+            schema: self.db.project_schema_load(project_id).await?,
+            policies: self
+                .db
+                .project_policies_load(project_id, &query)
+                .await?
+                .items,
+            templates: self
+                .db
+                .project_templates_load(project_id, &query)
+                .await?
+                .items,
+            template_links: self
+                .db
+                .project_template_links_load(project_id, &query)
+                .await?
+                .items,
+        })
+    }
+
+    // Applies a plan to a project whose policy set lock the caller holds from the load of
+    // its current state, so concurrent plans apply one after the other. Every step leaves a
+    // valid PolicySet: templates are added before the links to them, and links are removed
+    // before their templates
+    async fn project_state_plan_apply(
+        &self,
+        project_id: Uuid,
+        desired: ProjectState,
+        plan: &StatePlan,
+    ) -> Result<(), CedrusError> {
+        // Each step would be checked against a state the steps before it never wrote, so a
+        // dry run takes the plan, validated as a whole, instead
+        if dry_run::record(|report| report.plan = plan.clone()) {
+            return Ok(());
+        }
+
+        let ProjectState {
+            schema,
+            mut policies,
+            mut templates,
+            template_links,
+        } = desired;
+
+        if plan.schema_changed {
+            match schema {
+                Some(schema) => self.project_schema_update(project_id, schema).await?,
+                None => self.project_schema_remove(project_id).await?,
+            }
+        }
+        if !plan.templates_changed.is_empty() {
+            templates.retain(|id, _| plan.templates_changed.contains(id));
+            self.project_templates_add_locked(project_id, templates)
+                .await?;
+        }
+        if !plan.policies_changed.is_empty() {
+            policies.retain(|id, _| plan.policies_changed.contains(id));
+            self.project_policies_add_locked(project_id, policies)
+                .await?;
+        }
+        if !plan.template_links_removed.is_empty() {
+            self.project_template_links_remove_locked(
+                project_id,
+                plan.template_links_removed.clone(),
+            )
+            .await?;
+        }
+        if !plan.template_links_changed.is_empty() {
+            let template_links = template_links
+                .into_iter()
+                .filter(|tl| plan.template_links_changed.contains(&tl.new_id))
+                .collect();
+            self.project_template_links_add_locked(project_id, template_links)
+                .await?;
+        }
+        if !plan.policies_removed.is_empty() {
+            self.project_policies_remove_locked(project_id, plan.policies_removed.clone())
+                .await?;
+        }
+        if !plan.templates_removed.is_empty() {
+            self.project_templates_remove_locked(project_id, plan.templates_removed.clone(), true)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn project_roles_find(
+        &self,
+        project_id: Uuid,
+        query: Query,
+    ) -> Result<PageList<Role>, CedrusError> {
+        if !project_id.is_nil() {
+            return Err(CedrusError::BadRequest);
+        }
+
+        Ok(self.db.project_roles_load(&project_id, &query).await?)
+    }
+
+    pub async fn project_roles_add(
+        &self,
+        project_id: Uuid,
+        mut roles: Vec<Role>,
+    ) -> Result<Vec<Role>, CedrusError> {
+        if !project_id.is_nil() {
+            return Err(CedrusError::BadRequest);
+        }
+
+        let templates = self
+            .db
+            .project_templates_load(&project_id, &Query::new())
+            .await?;
+        let missing = roles
+            .iter()
+            .flat_map(|role| role.templates.iter())
+            .any(|template_id| !templates.items.contains_key(template_id));
+        if missing || roles.iter().any(|role| role.id.is_empty()) {
+            return Err(CedrusError::BadRequest);
+        }
+
+        let existing = self
+            .db
+            .project_roles_load(&project_id, &Query::new())
+            .await?;
+        let now = chrono::Utc::now();
+        for role in roles.iter_mut() {
+            role.created_at = existing
+                .items
+                .iter()
+                .find(|r| r.id == role.id)
+                .map(|r| r.created_at)
+                .unwrap_or(now);
+            role.updated_at = now;
+        }
+
+        self.db.project_roles_save(&project_id, &roles).await?;
+        self.roles_reload().await?;
+        self.publish(Event::roles_update(self.id)).await;
+
+        Ok(roles)
+    }
+
+    pub async fn project_roles_remove(
+        &self,
+        project_id: Uuid,
+        ids: Vec<String>,
+    ) -> Result<(), CedrusError> {
+        if !project_id.is_nil() {
+            return Err(CedrusError::BadRequest);
+        }
+
+        self.db.project_roles_remove(&project_id, &ids).await?;
+        self.roles_reload().await?;
+        self.publish(Event::roles_update(self.id)).await;
+
+        Ok(())
+    }
+
+    async fn roles_reload(&self) -> Result<(), CedrusError> {
+        let roles = self
+            .db
+            .project_roles_load(&Uuid::nil(), &Query::new())
+            .await?;
+        *self.roles.write().unwrap() = roles
+            .items
+            .into_iter()
+            .map(|role| (role.id.clone(), role))
+            .collect();
+
+        Ok(())
+    }
+
+    async fn project_role_load(&self, role_id: &str) -> Result<Role, CedrusError> {
+        let roles = self
+            .db
+            .project_roles_load(&Uuid::nil(), &Query::new())
+            .await?;
+        roles
+            .items
+            .into_iter()
+            .find(|role| role.id == role_id)
+            .ok_or(CedrusError::NotFound)
+    }
+
+    /// Instantiates the role `role_id` for `principal` over `project_id`. The resulting
+    /// template links live in the nil project, where control-plane requests are evaluated, and
+    /// their ids are kept with the assignment in the project. Only roles whose templates all
+    /// have a `?resource` slot, bound to the project, can be assigned, and never over the nil
+    /// project, so the links grant no rights beyond the project.
+    pub async fn project_role_assign(
+        &self,
+        project_id: Uuid,
+        role_id: String,
+        principal: EntityUid,
+    ) -> Result<Vec<TemplateLink>, CedrusError> {
+        if project_id.is_nil() {
+            return Err(CedrusError::BadRequest);
+        }
+        let Some(project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let role = self.project_role_load(&role_id).await?;
+
+        let templates = self
+            .db
+            .project_templates_load(&Uuid::nil(), &Query::new())
+            .await?;
+        let templates = templates
+            .items
+            .into_iter()
+            .filter(|(template_id, _)| role.templates.contains(template_id))
+            .collect::<HashMap<PolicyId, Template>>();
+
+        let template_links = project
+            .template_links(&role.id, &templates, &principal)
+            .ok_or(CedrusError::BadRequest)?;
+        let link_ids = template_links
+            .iter()
+            .map(|tl| tl.new_id.clone())
+            .collect::<Vec<PolicyId>>();
+
+        // The links of a previous assignment of the role stay recorded until those the role
+        // no longer has are removed
+        let id = RoleAssignment::assignment_id(&role.id, &principal);
+        let mut assignment = self
+            .db
+            .project_role_assignment_load(&project_id, &id)
+            .await?
+            .unwrap_or_else(|| RoleAssignment::new(role.id.clone(), principal.clone()));
+        let stale = assignment
+            .template_links
+            .iter()
+            .filter(|link_id| !link_ids.contains(link_id))
+            .cloned()
+            .collect::<Vec<PolicyId>>();
+        assignment.template_links.extend(link_ids.iter().cloned());
+        assignment.template_links.sort();
+        assignment.template_links.dedup();
+        assignment.updated_at = chrono::Utc::now();
+        self.db
+            .project_role_assignment_save(&project_id, &assignment)
+            .await?;
+
+        let template_links = self
+            .project_template_links_add(Uuid::nil(), template_links)
+            .await?;
+        if !stale.is_empty() {
+            self.project_template_links_remove(Uuid::nil(), stale)
+                .await?;
+            assignment.template_links = link_ids;
+            self.db
+                .project_role_assignment_save(&project_id, &assignment)
+                .await?;
+        }
+
+        Ok(template_links)
+    }
+
+    /// Revokes the role `role_id` of `principal` over `project_id`, removing the template
+    /// links made when it was assigned, even once the role changed or was removed.
+    pub async fn project_role_revoke(
+        &self,
+        project_id: Uuid,
+        role_id: String,
+        principal: EntityUid,
+    ) -> Result<(), CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        let id = RoleAssignment::assignment_id(&role_id, &principal);
+        let Some(assignment) = self
+            .db
+            .project_role_assignment_load(&project_id, &id)
+            .await?
+        else {
+            return Err(CedrusError::NotFound);
+        };
+
+        if !assignment.template_links.is_empty() {
+            self.project_template_links_remove(Uuid::nil(), assignment.template_links)
+                .await?;
+        }
+        self.db
+            .project_role_assignment_remove(&project_id, &id)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn project_delegations_find(
+        &self,
+        project_id: Uuid,
+        mut query: Query,
+    ) -> Result<PageHash<PolicyId, Policy>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        query.annotations.insert(
+            ANNOTATION_DELEGATION_PROJECT.to_string(),
+            project_id.to_string(),
+        );
+        self.project_policies_find(Uuid::nil(), query).await
+    }
+
+    /// Grants, or restricts with a forbid effect, the `actions` of `capability` to `principal`
+    /// over `project_id` by adding a policy to the nil project.
+    pub async fn project_delegation_grant(
+        &self,
+        project_id: Uuid,
+        capability: &str,
+        effect: PolicyEffect,
+        principal: EntityUid,
+        actions: Vec<EntityUid>,
+    ) -> Result<(PolicyId, Policy), CedrusError> {
+        if project_id.is_nil() || actions.is_empty() {
+            return Err(CedrusError::BadRequest);
+        }
+        let Some(project) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let policy_id = Project::delegation_policy_id(capability, &effect, &project_id, &principal);
+        let policy = project.delegation_policy(capability, effect, principal, actions);
+        self.project_policies_add(
+            Uuid::nil(),
+            HashMap::from([(policy_id.clone(), policy.clone())]),
+        )
+        .await?;
+
+        Ok((policy_id, policy))
+    }
+
+    pub async fn project_delegation_revoke(
+        &self,
+        project_id: Uuid,
+        capability: &str,
+        effect: PolicyEffect,
+        principal: EntityUid,
+    ) -> Result<(), CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let policy_id = Project::delegation_policy_id(capability, &effect, &project_id, &principal);
+        self.project_policies_remove(Uuid::nil(), vec![policy_id])
+            .await
+    }
+
+    /// Digest of the settings, schema, entities, policies, API keys and identity source of a
+    /// project as this node holds them, plus the common types and roles for the admin
+    /// project. `None` when the project is unknown.
+    pub async fn project_state_digest(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<String>, CedrusError> {
+        let Some(project) = self.cache.project_get(project_id).await? else {
+            return Ok(None);
+        };
+        let mut api_keys: Vec<ApiKey> = self
+            .api_keys
+            .iter()
+            .filter(|api_key| api_key.project_id == *project_id)
+            .map(|api_key| api_key.clone())
+            .collect();
+        api_keys.sort_by_key(|api_key| api_key.id);
+        // A source whose authorizer failed to build is pending
+        let identity_source = self
+            .project_authorizers
+            .get(project_id)
+            .and_then(|authorizer| match authorizer.as_ref() {
+                Some(authorizer) => Some(authorizer.identity_source.clone()),
+                None => self
+                    .pending_identity_sources
+                    .get(project_id)
+                    .map(|identity_source| identity_source.clone()),
+            });
+
+        let mut state = serde_json::json!({
+            "project": project,
+            "schema": self.project_schemas.get(project_id).map(|s| s.clone()),
+            "entities": self.project_entities_fingerprints.get(project_id).map(|f| f.clone()),
+            "policies": self.project_cedar_policies.get(project_id).map(|p| p.version.clone()),
+            "apiKeys": api_keys,
+            "identitySource": identity_source,
+        });
+        if project_id.is_nil() {
+            state["commonTypes"] = serde_json::to_value(self.common_types())?;
+            state["roles"] = serde_json::to_value(&*self.roles.read().unwrap())?;
+        }
+
+        Ok(Some(json_digest(state)))
+    }
+
+    /// Appends a management change to the audit trail of a project. The change being made
+    /// already, a record the Database fails to save is queued to the write-behind writer,
+    /// which retries it and dead-letters it if it is rejected.
+    pub async fn project_audit_record(&self, record: AuditRecord) {
+        if let Err(e) = self
+            .db
+            .project_audit_save(&record.project_id, &record)
+            .await
+        {
+            tracing::warn!(
+                "audit: unable to record {} {}, retrying: {}",
+                record.method,
+                record.path,
+                e
+            );
+            self.write_behind
+                .push(WriteOp::SaveAudit(record.project_id, record))
+                .await;
+        }
+    }
+
+    /// Audit trail of a project, still readable once the project is removed.
+    pub async fn project_audit_find(
+        &self,
+        project_id: Uuid,
+        query: Query,
+    ) -> Result<PageList<AuditRecord>, CedrusError> {
+        if self.write_behind.is_pending(&project_id) {
+            self.write_behind_flush().await?;
+        }
+        Ok(self.db.project_audit_load(&project_id, &query).await?)
+    }
+
+    pub async fn project_jobs_find(
+        &self,
+        project_id: Uuid,
+        query: Query,
+    ) -> Result<PageList<Job>, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        Ok(self.db.project_jobs_load(&project_id, &query).await?)
+    }
+
+    pub async fn project_job_find(
+        &self,
+        project_id: Uuid,
+        job_id: Uuid,
+    ) -> Result<Job, CedrusError> {
+        self.db
+            .project_job_load(&project_id, &job_id)
+            .await?
+            .ok_or(CedrusError::NotFound)
+    }
+
+    /// Records a pending job for `project_id`, leased to this node, to be executed by
+    /// `project_job_run`. Its task is kept until it finished, for another node to take the
+    /// job over once the lease expired.
+    pub async fn project_job_create(
+        &self,
+        project_id: Uuid,
+        task: &JobTask,
+    ) -> Result<Job, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+
+        let mut job = Job::new(project_id, task.kind());
+        job.claim(self.id, job_lease());
+        self.db
+            .project_job_task_save(&project_id, &job.id, task)
+            .await?;
+        self.db.project_job_save(&project_id, &job).await?;
+
+        Ok(job)
+    }
+
+    /// Executes `task`, saving the job progress after every chunk and its final status, and
+    /// renewing the lease of the job while it runs. The job stops once another node took it
+    /// over.
+    pub async fn project_job_run(&self, job: Job, task: JobTask) -> Result<Job, CedrusError> {
+        let job_id = job.id;
+        let job = tokio::sync::Mutex::new(job);
+        // The work borrows the job, so it's dropped before the job is taken back
+        let result = {
+            let work = async {
+                match task {
+                    JobTask::Import(data) => {
+                        self.project_job_import(&job, data).await.map(|_| None)
+                    }
+                    JobTask::Export => self.project_job_export(&job).await.map(Some),
+                    JobTask::Cleanup(cleanup) => {
+                        self.project_job_cleanup(&job, cleanup).await.map(|_| None)
+                    }
+                }
+            };
+            tokio::pin!(work);
+
+            let mut heartbeat = tokio::time::interval_at(
+                tokio::time::Instant::now() + JOB_HEARTBEAT,
+                JOB_HEARTBEAT,
+            );
+            loop {
+                tokio::select! {
+                    result = &mut work => break result,
+                    _ = heartbeat.tick() => {
+                        if !self.project_job_heartbeat(&job).await {
+                            tracing::warn!(
+                                "cedrus: project_job_run: Job {} taken over by another node",
+                                job_id
+                            );
+                            return Err(CedrusError::Conflict);
+                        }
+                    }
+                }
+            }
+        };
+        let mut job = job.into_inner();
+
+        match result {
+            Ok(result) => job.succeed(result),
+            Err(err) => {
+                tracing::warn!("cedrus: project_job_run: Job {} failed: {}", job.id, err);
+                job.fail(err.to_string())
+            }
+        }
+        // A job whose outcome can't be saved, such as a result the Database rejects, is
+        // failed rather than left running
+        if let Err(err) = self.db.project_job_save(&job.project_id, &job).await {
+            tracing::warn!("cedrus: project_job_run: Job {} not saved: {}", job.id, err);
+            job.result = None;
+            job.fail(err.to_string());
+            self.db.project_job_save(&job.project_id, &job).await?;
+        }
+        self.project_job_task_remove(&job).await;
+
+        Ok(job)
+    }
+
+    // Renews the lease of a running job, unless the job was taken over by another node or
+    // removed with its project. While the job saves its progress, which keeps the lease of
+    // the last renewal, the renewal waits for the next heartbeat.
+    async fn project_job_heartbeat(&self, job: &tokio::sync::Mutex<Job>) -> bool {
+        let Ok(mut job) = job.try_lock() else {
+            return true;
+        };
+        match self.db.project_job_load(&job.project_id, &job.id).await {
+            Ok(Some(stored)) if stored.node_id == Some(self.id) => {}
+            Ok(_) => return false,
+            Err(e) => {
+                tracing::warn!(
+                    "cedrus: project_job_heartbeat: Job {} not loaded: {}",
+                    job.id,
+                    e
+                );
+            }
+        }
+
+        job.renew(job_lease());
+        if let Err(e) = self.db.project_job_save(&job.project_id, &job).await {
+            tracing::warn!(
+                "cedrus: project_job_heartbeat: Job {} lease not renewed: {}",
+                job.id,
+                e
+            );
+        }
+        true
+    }
+
+    async fn project_job_task_remove(&self, job: &Job) {
+        if let Err(e) = self
+            .db
+            .project_job_task_remove(&job.project_id, &job.id)
+            .await
+        {
+            tracing::warn!(
+                "cedrus: project_job_task_remove: Job {} task not removed: {}",
+                job.id,
+                e
+            );
+        }
+    }
+
+    /// Takes over the jobs whose node stopped renewing their lease, returning them with
+    /// their task to be run again from the start by `project_job_run`. A job started
+    /// `JOB_MAX_ATTEMPTS` times already, or whose task was not kept, fails instead. A single
+    /// node recovers at a time, the others skipping their turn.
+    pub async fn project_jobs_recover(&self) -> Result<Vec<(Job, JobTask)>, CedrusError> {
+        if self.is_read_only() {
+            return Ok(Vec::new());
+        }
+        let Some(_lock) = self.cache.lock_job_recovery(JOB_RECOVERY_LOCK_TTL).await? else {
+            return Ok(Vec::new());
+        };
+
+        let now = chrono::Utc::now();
+        let mut recovered = Vec::new();
+        for mut job in self.db.jobs_unfinished_load().await? {
+            if !job.is_lease_expired(now) {
+                continue;
+            }
+            let task = self
+                .db
+                .project_job_task_load(&job.project_id, &job.id)
+                .await?;
+            match task {
+                Some(task) if job.attempts < JOB_MAX_ATTEMPTS => {
+                    tracing::warn!(
+                        "cedrus: project_jobs_recover: Job {} of project {} taken over",
+                        job.id,
+                        job.project_id
+                    );
+                    job.claim(self.id, job_lease());
+                    self.db.project_job_save(&job.project_id, &job).await?;
+                    recovered.push((job, task));
+                }
+                task => {
+                    let error = match task {
+                        Some(_) => format!("the job stopped {} times", job.attempts),
+                        None => "the job stopped and its task was not kept".to_string(),
+                    };
+                    tracing::warn!(
+                        "cedrus: project_jobs_recover: Job {} of project {} failed: {}",
+                        job.id,
+                        job.project_id,
+                        error
+                    );
+                    job.fail(error);
+                    self.db.project_job_save(&job.project_id, &job).await?;
+                    self.project_job_task_remove(&job).await;
+                }
+            }
+        }
+
+        Ok(recovered)
+    }
+
+    async fn project_job_progress(
+        &self,
+        job: &tokio::sync::Mutex<Job>,
+        processed: usize,
+    ) -> Result<(), CedrusError> {
+        let mut job = job.lock().await;
+        job.progress(processed);
+        Ok(self.db.project_job_save(&job.project_id, &job).await?)
+    }
+
+    async fn project_job_start(
+        &self,
+        job: &tokio::sync::Mutex<Job>,
+        total: usize,
+    ) -> Result<(), CedrusError> {
+        let mut job = job.lock().await;
+        job.start(total);
+        Ok(self.db.project_job_save(&job.project_id, &job).await?)
+    }
+
+    async fn project_job_import(
+        &self,
+        job: &tokio::sync::Mutex<Job>,
+        data: ProjectData,
+    ) -> Result<(), CedrusError> {
+        let project_id = job.lock().await.project_id;
+        self.project_job_start(job, data.len()).await?;
+
+        if let Some(schema) = data.schema {
+            self.project_schema_update(project_id, schema).await?;
+            self.project_job_progress(job, 1).await?;
+        }
+        if !data.templates.is_empty() {
+            let count = data.templates.len();
+            self.project_templates_add(project_id, data.templates)
+                .await?;
+            self.project_job_progress(job, count).await?;
+        }
+        if !data.policies.is_empty() {
+            let count = data.policies.len();
+            self.project_policies_add(project_id, data.policies).await?;
+            self.project_job_progress(job, count).await?;
+        }
+        for chunk in data.entities.chunks(JOB_CHUNK_SIZE) {
+            self.project_entities_add(project_id, chunk.to_vec())
+                .await?;
+            self.project_job_progress(job, chunk.len()).await?;
+        }
+        for chunk in data.template_links.chunks(JOB_CHUNK_SIZE) {
+            self.project_template_links_add(project_id, chunk.to_vec())
+                .await?;
+            self.project_job_progress(job, chunk.len()).await?;
+        }
+
+        Ok(())
+    }
+
+    // Loads the data of a project from the Database
+    async fn project_data_load(&self, project_id: &Uuid) -> Result<ProjectData, CedrusError> {
+        let query = Query::new();
+        Ok(ProjectData {
+            schema: self.db.project_schema_load(project_id).await?,
+            entities: self
+                .db
+                .project_entities_load(project_id, &query)
+                .await?
+                .items,
+            policies: self
+                .db
+                .project_policies_load(project_id, &query)
+                .await?
+                .items,
+            templates: self
+                .db
+                .project_templates_load(project_id, &query)
+                .await?
+                .items,
+            template_links: self
+                .db
+                .project_template_links_load(project_id, &query)
+                .await?
+                .items,
+        })
+    }
+
+    /// Exports a snapshot of the project: its data is loaded again while a write overlapped
+    /// the load, and the export fails with a conflict when no attempt saw a single point in
+    /// time. The writes of this node are seen by its epoch, those of the other nodes by the
+    /// version of the project in the Cache, even before their events are received.
+    async fn project_job_export(
+        &self,
+        job: &tokio::sync::Mutex<Job>,
+    ) -> Result<ProjectData, CedrusError> {
+        let project_id = job.lock().await.project_id;
+        for attempt in 1..=EXPORT_SNAPSHOT_ATTEMPTS {
+            if let Some(epoch) = self.project_epochs.stable(&project_id) {
+                let version = self.cache.project_get_version(&project_id).await?;
+                // Deferred writes started before the epoch are in the Database once flushed
+                if self.write_behind.is_pending(&project_id) {
+                    self.write_behind_flush().await?;
+                }
+                let data = self.project_data_load(&project_id).await?;
+                if self.project_epochs.stable(&project_id) == Some(epoch)
+                    && self.cache.project_get_version(&project_id).await? == version
+                {
+                    job.lock().await.start(data.len());
+                    return Ok(data);
+                }
+            }
+            tokio::time::sleep(EXPORT_SNAPSHOT_BACKOFF * attempt).await;
+        }
+
+        Err(CedrusError::Conflict)
+    }
+
+    /// Takes the policy set lock of a project, shared by the nodes through the Cache, so
+    /// concurrent policy, template and link edits apply one after the other rather than
+    /// overwriting each other.
+    async fn policy_set_lock(&self, project_id: &Uuid) -> Result<CacheLock, CedrusError> {
+        let deadline = tokio::time::Instant::now() + POLICY_SET_LOCK_WAIT;
+        loop {
+            if let Some(lock) = self
+                .cache
+                .project_lock_policy_set(project_id, POLICY_SET_LOCK_TTL)
+                .await?
+            {
+                return Ok(lock);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(CedrusError::Conflict);
+            }
+            tokio::time::sleep(POLICY_SET_LOCK_RETRY).await;
+        }
+    }
+
+    async fn project_job_cleanup(
+        &self,
+        job: &tokio::sync::Mutex<Job>,
+        cleanup: ProjectCleanup,
+    ) -> Result<(), CedrusError> {
+        let project_id = job.lock().await.project_id;
+        let query = Query::new();
+
+        let entity_uids = match cleanup.entities {
+            true => self
+                .db
+                .project_entities_load(&project_id, &query)
+                .await?
+                .items
+                .into_iter()
+                .map(|e| e.uid().clone())
+                .collect(),
+            false => Vec::new(),
+        };
+        let policy_ids = match cleanup.policies {
+            true => self
+                .db
+                .project_policies_load(&project_id, &query)
+                .await?
+                .items
+                .into_keys()
+                .collect(),
+            false => Vec::new(),
+        };
+        let template_ids = match cleanup.templates {
+            true => self
+                .db
+                .project_templates_load(&project_id, &query)
+                .await?
+                .items
+                .into_keys()
+                .collect(),
+            false => Vec::new(),
+        };
+        let template_link_ids = match cleanup.template_links {
+            true => self
+                .db
+                .project_template_links_load(&project_id, &query)
+                .await?
+                .items
+                .into_iter()
+                .map(|tl| tl.new_id)
+                .collect(),
+            false => Vec::new(),
+        };
+
+        self.project_job_start(
+            job,
+            entity_uids.len() + policy_ids.len() + template_ids.len() + template_link_ids.len(),
+        )
+        .await?;
+
+        for chunk in template_link_ids.chunks(JOB_CHUNK_SIZE) {
+            self.project_template_links_remove(project_id, chunk.to_vec())
+                .await?;
+            self.project_job_progress(job, chunk.len()).await?;
+        }
+        for chunk in policy_ids.chunks(JOB_CHUNK_SIZE) {
+            self.project_policies_remove(project_id, chunk.to_vec())
+                .await?;
+            self.project_job_progress(job, chunk.len()).await?;
+        }
+        for chunk in template_ids.chunks(JOB_CHUNK_SIZE) {
+            self.project_templates_remove(project_id, chunk.to_vec(), true)
+                .await?;
+            self.project_job_progress(job, chunk.len()).await?;
+        }
+        for chunk in entity_uids.chunks(JOB_CHUNK_SIZE) {
+            self.project_entities_remove(project_id, chunk.to_vec())
+                .await?;
+            self.project_job_progress(job, chunk.len()).await?;
+        }
+
+        Ok(())
+    }
+
+    fn keyed_values<'a, T: serde::Serialize + 'a>(
+        items: impl Iterator<Item = (String, &'a T)>,
+    ) -> Result<HashMap<String, Value>, CedrusError> {
+        items
+            .map(|(key, item)| Ok((key, serde_json::to_value(item)?)))
+            .collect()
+    }
+
+    fn stale_keys(db: &HashMap<String, Value>, cache: &HashMap<String, Value>) -> HashSet<String> {
+        db.iter()
+            .filter(|(key, value)| cache.get(*key).is_some_and(|v| v != *value))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn is_policy_excluded(&self, annotations: &HashMap<String, Option<String>>) -> bool {
+        let exclude = self.exclude_policy_annotation.clone().unwrap_or_default();
+        annotations.contains_key(&exclude)
+    }
+
+    /// Cross-references the enforced policies of a project against its schema. Fails with
+    /// `BadRequest` when the project has no schema to compare against.
+    pub fn project_policy_coverage(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<PolicyCoverageReport, CedrusError> {
+        let cedar_schema = self
+            .project_cedar_schemas
+            .get(project_id)
+            .ok_or(CedrusError::NotFound)?;
+        let Some(cedar_schema) = cedar_schema.as_ref() else {
+            return Err(CedrusError::BadRequest);
+        };
+        let cedar_policies = self
+            .project_cedar_policies
+            .get(project_id)
+            .ok_or(CedrusError::NotFound)?;
+
+        Ok(PolicyCoverageReport::new(cedar_schema, &cedar_policies)?)
+    }
+
+    /// Compares the entities and policy set of a project across the Database (source of truth),
+    /// the Cache and the in-memory Cedar structures. When `repair` is set, drifted Cache entries
+    /// are rewritten from the Database, the in-memory state is rebuilt and the other nodes are
+    /// told about the rewritten entries.
+    pub async fn project_consistency_check(
+        &self,
+        project_id: Uuid,
+        repair: bool,
+    ) -> Result<ConsistencyReport, CedrusError> {
+        let Some(_) = self.db.project_load(&project_id).await? else {
+            return Err(CedrusError::NotFound);
+        };
+        // A stale project is behind the Cache by design, not inconsistent
+        self.project_refresh(&project_id).await?;
+
+        // The Cache is read before the Database, so a write landing in between shows as
+        // missing from or stale in the Cache, which the repair rewrites from the Database,
+        // rather than as extra entries it would remove
+        let version = self.cache.project_get_version(&project_id).await?;
+        let cache_entities = self.cache_entities(&project_id, &[]).await?;
+        let cache_schema = self.cache.project_get_schema(&project_id).await?;
+        let cache_policy_set = self.cache.project_get_policy_set(&project_id).await?;
+
+        let query = Query::new();
+        let db_entities = self.db.project_entities_load(&project_id, &query).await?;
+        let db_policies = self.db.project_policies_load(&project_id, &query).await?;
+        let db_templates = self.db.project_templates_load(&project_id, &query).await?;
+        let db_template_links = self
+            .db
+            .project_template_links_load(&project_id, &query)
+            .await?;
+
+        // Entities
+        let db_entity_values =
+            Self::keyed_values(db_entities.items.iter().map(|e| (e.uid().to_string(), e)))?;
+        let cache_entity_values =
+            Self::keyed_values(cache_entities.iter().map(|e| (e.uid().to_string(), e)))?;
+
+        let mut expected_memory_entities: HashSet<String> =
+            cache_entity_values.keys().cloned().collect();
+        if let Some(schema) = &cache_schema {
+            expected_memory_entities.extend(
+                Self::schema_enum_entities(schema)
+                    .iter()
+                    .map(|e| e.uid().to_string()),
+            );
+        }
+        let memory_entities: HashSet<String> = self
+            .project_cedar_entities
+            .get(&project_id)
+            .map(|entities| {
+                entities
+                    .iter()
+                    .map(|e| EntityUid::from(e.uid()).to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let entities = ConsistencyDrift::new(
+            &db_entity_values.keys().cloned().collect(),
+            &cache_entity_values.keys().cloned().collect(),
+            Self::stale_keys(&db_entity_values, &cache_entity_values),
+            &expected_memory_entities,
+            &memory_entities,
+        );
+
+        // Policies, templates and template links
+        let db_policy_values = Self::keyed_values(
+            db_policies
+                .items
+                .iter()
+                .map(|(id, policy)| (id.to_string(), policy)),
+        )?;
+        let cache_policy_values = Self::keyed_values(
+            cache_policy_set
+                .static_policies
+                .iter()
+                .map(|(id, policy)| (id.to_string(), policy)),
+        )?;
+        let db_template_values = Self::keyed_values(
+            db_templates
+                .items
+                .iter()
+                .map(|(id, template)| (id.to_string(), template)),
+        )?;
+        let cache_template_values = Self::keyed_values(
+            cache_policy_set
+                .templates
+                .iter()
+                .map(|(id, template)| (id.to_string(), template)),
+        )?;
+        let db_template_link_values = Self::keyed_values(
+            db_template_links
+                .items
+                .iter()
+                .map(|link| (link.new_id.to_string(), link)),
+        )?;
+        let cache_template_link_values = Self::keyed_values(
+            cache_policy_set
+                .template_links
+                .iter()
+                .map(|link| (link.new_id.to_string(), link)),
+        )?;
+
+        // As compiled: without the excluded policies, with the guardrails of the admin project
+        let (live_policy_set, _) = self.live_policy_set(&project_id, cache_policy_set.clone());
+        let expected_memory_policies: HashSet<String> = live_policy_set
+            .static_policies
+            .keys()
+            .map(|id| id.to_string())
+            .collect();
+        let expected_memory_templates: HashSet<String> = live_policy_set
+            .templates
+            .keys()
+            .map(|id| id.to_string())
+            .collect();
+        let expected_memory_template_links: HashSet<String> = live_policy_set
+            .template_links
+            .iter()
+            .map(|link| link.new_id.to_string())
+            .collect();
+
+        let (memory_policies, memory_templates, memory_template_links) = self
+            .project_cedar_policies
+            .get(&project_id)
+            .map(|policy_set| {
+                let policies: HashSet<String> = policy_set
+                    .policies()
+                    .filter(|p| p.template_id().is_none())
+                    .map(|p| p.id().to_string())
+                    .collect();
+                let templates: HashSet<String> =
+                    policy_set.templates().map(|t| t.id().to_string()).collect();
+                let template_links: HashSet<String> = policy_set
+                    .policies()
+                    .filter(|p| p.template_id().is_some())
+                    .map(|p| p.id().to_string())
+                    .collect();
+                (policies, templates, template_links)
+            })
+            .unwrap_or_default();
+
+        let policies = ConsistencyDrift::new(
+            &db_policy_values.keys().cloned().collect(),
+            &cache_policy_values.keys().cloned().collect(),
+            Self::stale_keys(&db_policy_values, &cache_policy_values),
+            &expected_memory_policies,
+            &memory_policies,
+        );
+        let templates = ConsistencyDrift::new(
+            &db_template_values.keys().cloned().collect(),
+            &cache_template_values.keys().cloned().collect(),
+            Self::stale_keys(&db_template_values, &cache_template_values),
+            &expected_memory_templates,
+            &memory_templates,
+        );
+        let template_links = ConsistencyDrift::new(
+            &db_template_link_values.keys().cloned().collect(),
+            &cache_template_link_values.keys().cloned().collect(),
+            Self::stale_keys(&db_template_link_values, &cache_template_link_values),
+            &expected_memory_template_links,
+            &memory_template_links,
+        );
+
+        let mut report = ConsistencyReport {
+            project_id,
+            entities,
+            policies,
+            templates,
+            template_links,
+            repaired: false,
+            error: None,
+            checked_at: chrono::Utc::now(),
+        };
+
+        if report.is_consistent() {
+            return Ok(report);
+        }
+
+        tracing::warn!(
+            "cedrus: consistency: project {} has {} discrepancies",
+            project_id,
+            report.discrepancies()
+        );
+
+        // The Database lags behind the Cache while writes are deferred, on any node
+        if !repair || self.is_write_deferred(&project_id) {
+            return Ok(report);
+        }
+        // A single node repairs a project at a time, holding off its policy edits, and not
+        // once the project changed since it was read: the next check compares it again
+        let Some(_lock) = self
+            .cache
+            .project_lock_policy_set(&project_id, POLICY_SET_LOCK_TTL)
+            .await?
+        else {
+            return Ok(report);
+        };
+        if self.cache.project_get_version(&project_id).await? != version {
+            return Ok(report);
+        }
+
+        // The other nodes rebuild what was repaired from the Cache once it is rewritten
+        let mut events = Vec::new();
+        if report.entities.cache_drift() > 0 {
+            let outdated: HashSet<&String> = report
+                .entities
+                .missing_in_cache
+                .iter()
+                .chain(report.entities.stale_in_cache.iter())
+                .collect();
+            let entities: Vec<Entity> = db_entities
+                .items
+                .into_iter()
+                .filter(|e| outdated.contains(&e.uid().to_string()))
+                .collect();
+            self.cache
+                .project_set_entities(&project_id, &entities)
+                .await?;
+
+            let extra: Vec<EntityUid> = cache_entities
+                .iter()
+                .filter(|e| {
+                    report
+                        .entities
+                        .extra_in_cache
+                        .contains(&e.uid().to_string())
+                })
+                .map(|e| e.uid().clone())
+                .collect();
+            self.cache.project_del_entities(&project_id, &extra).await?;
+
+            events.push(Event::project_sync_entities(
+                self.id,
+                project_id,
+                entities.iter().map(|e| e.uid().clone()).collect(),
+                extra.into_iter().collect(),
+            ));
+        }
+
+        if report.policies.cache_drift() > 0 {
+            let outdated: HashSet<&String> = report
+                .policies
+                .missing_in_cache
+                .iter()
+                .chain(report.policies.stale_in_cache.iter())
+                .collect();
+            let policies: HashMap<PolicyId, Policy> = db_policies
+                .items
+                .into_iter()
+                .filter(|(id, _)| outdated.contains(&id.to_string()))
+                .collect();
+            self.cache
+                .project_set_policies(&project_id, &policies)
+                .await?;
+
+            let extra: Vec<PolicyId> = cache_policy_set
+                .static_policies
+                .keys()
+                .filter(|id| report.policies.extra_in_cache.contains(&id.to_string()))
+                .cloned()
+                .collect();
+            self.cache.project_del_policies(&project_id, &extra).await?;
+
+            if !policies.is_empty() {
+                events.push(Event::project_add_policies(
+                    self.id,
+                    project_id,
+                    policies.into_keys().collect(),
+                ));
+            }
+            if !extra.is_empty() {
+                events.push(Event::project_remove_policies(
+                    self.id,
+                    project_id,
+                    extra.into_iter().collect(),
+                ));
+            }
+        }
+
+        if report.templates.cache_drift() > 0 {
+            let outdated: HashSet<&String> = report
+                .templates
+                .missing_in_cache
+                .iter()
+                .chain(report.templates.stale_in_cache.iter())
+                .collect();
+            let templates: HashMap<PolicyId, Template> = db_templates
+                .items
+                .into_iter()
+                .filter(|(id, _)| outdated.contains(&id.to_string()))
+                .collect();
+            self.cache
+                .project_set_templates(&project_id, &templates)
+                .await?;
 
-        Ok(self
-            .db
-            .project_template_links_load(&project_id, &query)
-            .await?)
-    }
+            let extra: Vec<PolicyId> = cache_policy_set
+                .templates
+                .keys()
+                .filter(|id| report.templates.extra_in_cache.contains(&id.to_string()))
+                .cloned()
+                .collect();
+            self.cache
+                .project_del_templates(&project_id, &extra)
+                .await?;
 
-    pub async fn project_template_links_add(
-        &self,
-        project_id: Uuid,
-        template_links: Vec<TemplateLink>,
-    ) -> Result<(), CedrusError> {
-        let Some(_) = self.db.project_load(&project_id).await? else {
-            return Err(CedrusError::NotFound);
-        };
+            if !templates.is_empty() {
+                events.push(Event::project_add_templates(
+                    self.id,
+                    project_id,
+                    templates.into_keys().collect(),
+                ));
+            }
+            if !extra.is_empty() {
+                events.push(Event::project_remove_templates(
+                    self.id,
+                    project_id,
+                    extra.into_iter().collect(),
+                ));
+            }
+        }
 
-        self.db
-            .project_template_links_save(&project_id, &template_links)
-            .await?;
-        self.cache
-            .project_set_template_links(&project_id, &template_links)
-            .await?;
+        if report.template_links.cache_drift() > 0 {
+            let outdated: HashSet<&String> = report
+                .template_links
+                .missing_in_cache
+                .iter()
+                .chain(report.template_links.stale_in_cache.iter())
+                .collect();
+            let template_links: Vec<TemplateLink> = db_template_links
+                .items
+                .into_iter()
+                .filter(|link| outdated.contains(&link.new_id.to_string()))
+                .collect();
+            self.cache
+                .project_set_template_links(&project_id, &template_links)
+                .await?;
+
+            let extra: Vec<PolicyId> = cache_policy_set
+                .template_links
+                .iter()
+                .filter(|link| {
+                    report
+                        .template_links
+                        .extra_in_cache
+                        .contains(&link.new_id.to_string())
+                })
+                .map(|link| link.new_id.clone())
+                .collect();
+            self.cache
+                .project_del_template_links(&project_id, &extra)
+                .await?;
+
+            if !template_links.is_empty() {
+                events.push(Event::project_add_template_links(
+                    self.id,
+                    project_id,
+                    template_links
+                        .iter()
+                        .map(|link| link.new_id.clone())
+                        .collect(),
+                ));
+            }
+            if !extra.is_empty() {
+                events.push(Event::project_remove_template_links(
+                    self.id,
+                    project_id,
+                    extra.into_iter().collect(),
+                ));
+            }
+        }
 
+        self.on_project_entities(&project_id).await?;
         self.on_project_policy_set(&project_id).await?;
+        self.on_project_references(&project_id, None).await?;
+        for event in events {
+            self.publish(event).await;
+        }
 
-        let policy_ids = template_links.into_iter().map(|tl| tl.new_id).collect();
-        self.publish(Event::project_add_template_links(
-            self.id, project_id, policy_ids,
-        ))
-        .await;
+        report.repaired = true;
 
-        Ok(())
+        Ok(report)
     }
 
-    pub async fn project_template_links_remove(
+    /// Checks the projects this node owns, repairing them when `repair` is set. A project
+    /// failing is reported with its error without holding back the others.
+    pub async fn consistency_check(
         &self,
-        project_id: Uuid,
-        policy_ids: Vec<PolicyId>,
-    ) -> Result<(), CedrusError> {
-        let Some(_) = self.db.project_load(&project_id).await? else {
-            return Err(CedrusError::NotFound);
-        };
-
-        self.db
-            .project_template_links_remove(&project_id, &policy_ids)
-            .await?;
-        self.cache
-            .project_del_template_links(&project_id, &policy_ids)
-            .await?;
+        repair: bool,
+    ) -> Result<Vec<ConsistencyReport>, CedrusError> {
+        let projects = self.db.projects_load(&Query::new()).await?;
 
-        self.on_project_policy_set(&project_id).await?;
-
-        let policy_ids = policy_ids.into_iter().collect();
-        self.publish(Event::project_remove_template_links(
-            self.id, project_id, policy_ids,
-        ))
-        .await;
+        let mut reports = Vec::new();
+        for project in projects.items {
+            if !self.shards.owns(&project.id) {
+                continue;
+            }
+            let report = match self.project_consistency_check(project.id, repair).await {
+                Ok(report) => report,
+                Err(e) => ConsistencyReport {
+                    project_id: project.id,
+                    error: Some(e.to_string()),
+                    checked_at: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            };
+            reports.push(report);
+        }
 
-        Ok(())
+        Ok(reports)
     }
 
     pub async fn update(&self, event: &Event, intern: bool) {
         if !intern && event.sender == self.id {
             return;
         }
+        if !intern && !matches!(event.msg(), EventType::NodeHeartbeat(_)) {
+            self.cluster.received(&event.sender);
+        }
+        if !intern && let Some(project_id) = event.msg().project_id() {
+            self.project_epochs.advance(project_id);
+        }
+        if let Some(project_id) = event.msg().project_id() {
+            self.event_staleness
+                .applied(project_id, event.sent_at().filter(|_| !intern));
+        }
 
         match event.msg() {
+            EventType::NodeHeartbeat(heartbeat) => {
+                self.cluster.observe(heartbeat);
+            }
             EventType::ReloadAll => {
-                let _ = self.load_cache().await;
+                if self.load_cache().await.is_ok() {
+                    self.cluster.resynced();
+                }
+            }
+            EventType::CommonTypesUpdate => {
+                let Ok(common_types) = self.db.common_types_load().await else {
+                    return;
+                };
+                let Ok(changes) = self.common_types_changes(&common_types).await else {
+                    return;
+                };
+                let _ = self.on_common_types_set(common_types, changes).await;
+            }
+            EventType::RolesUpdate => {
+                let _ = self.roles_reload().await;
             }
             EventType::ProjectCreate(id) => {
                 let Ok(project_cache) = self.cache.project_get(id).await else {
@@ -1297,7 +7015,7 @@ impl Cedrus {
                 };
 
                 if let Some(project) = project_cache {
-                    let _ = self.on_project_set(&project);
+                    self.on_project_update(&project);
                     let _ = self.on_project_entities(&Uuid::nil()).await;
                 }
             }
@@ -1305,6 +7023,18 @@ impl Cedrus {
                 let _ = self.on_project_del(id, &Vec::from_iter(api_keys.clone()));
                 let _ = self.on_project_entities(&Uuid::nil()).await;
             }
+            EventType::ProjectResync(id, api_keys) => {
+                let _ = self.on_project_apikeys_del(&Vec::from_iter(api_keys.clone()));
+                let _ = self.on_project_identity_source_del(id);
+
+                let Ok(Some(project)) = self.cache.project_get(id).await else {
+                    return;
+                };
+                if let Err(e) = self.project_cache_load(&project, true).await {
+                    tracing::warn!("cedrus: update: project resync: {e}");
+                }
+                let _ = self.on_project_entities(&Uuid::nil()).await;
+            }
             EventType::ProjectAddApikeys(project_id, api_key_ids) => {
                 let Ok(cache_api_keys) = self.cache.project_get_apikeys(project_id).await else {
                     return;
@@ -1339,37 +7069,1195 @@ impl Cedrus {
                 let Ok(schema_cache) = self.cache.project_get_schema(id).await else {
                     return;
                 };
-                if let Some(schema) = schema_cache {
-                    let _ = self.on_project_schema_set(id, &schema);
+                if let Some(schema) = schema_cache
+                    && let Err(e) = self.on_project_schema_set(id, &schema)
+                {
+                    self.notify(
+                        AlertKind::EventFailure,
+                        id,
+                        format!("Schema not applied: {e}"),
+                    );
                 }
             }
             EventType::ProjectRemoveSchema(id) => {
                 let _ = self.on_project_schema_del(id);
             }
-            EventType::ProjectAddEntities(id, _entity_uids) => {
-                let _ = self.on_project_entities(id).await;
-            }
-            EventType::ProjectRemoveEntities(id, _entity_uids) => {
-                let _ = self.on_project_entities(id).await;
-            }
-            EventType::ProjectAddPolicies(id, _policy_ids) => {
-                let _ = self.on_project_policy_set(id).await;
-            }
-            EventType::ProjectRemovePolicies(id, _policy_ids) => {
-                let _ = self.on_project_policy_set(id).await;
-            }
-            EventType::ProjectAddTemplates(id, _template_ids) => {
-                let _ = self.on_project_policy_set(id).await;
-            }
-            EventType::ProjectRemoveTemplates(id, _template_ids) => {
-                let _ = self.on_project_policy_set(id).await;
+            EventType::ProjectAddEntities(id, _)
+            | EventType::ProjectRemoveEntities(id, _)
+            | EventType::ProjectSyncEntities(id, _, _) => {
+                if self.is_rebuild_deferred(id) {
+                    return;
+                }
+                if let Err(e) = self.on_project_entities(id).await {
+                    self.notify(
+                        AlertKind::EventFailure,
+                        id,
+                        format!("Entities not rebuilt: {e}"),
+                    );
+                }
             }
-            EventType::ProjectAddTemplateLinks(id, _template_link_ids) => {
-                let _ = self.on_project_policy_set(id).await;
+            EventType::ProjectAddPolicies(id, _)
+            | EventType::ProjectRemovePolicies(id, _)
+            | EventType::ProjectAddTemplates(id, _)
+            | EventType::ProjectRemoveTemplates(id, _)
+            | EventType::ProjectAddTemplateLinks(id, _)
+            | EventType::ProjectRemoveTemplateLinks(id, _) => {
+                if self.is_rebuild_deferred(id) {
+                    return;
+                }
+                if let Err(e) = self.on_project_policy_set(id).await {
+                    self.notify(
+                        AlertKind::EventFailure,
+                        id,
+                        format!("Policy set not rebuilt: {e}"),
+                    );
+                }
+                let _ = self.on_project_references(id, Some(event.msg())).await;
             }
-            EventType::ProjectRemoveTemplateLinks(id, _template_link_ids) => {
-                let _ = self.on_project_policy_set(id).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cache::dashmap::DashMapCache,
+        core::{
+            DashMapCacheConfig, EvaluationLimitConfig, ShardConfig, is::OpenIdConnectConfiguration,
+            job::JobStatus,
+        },
+        db::memory::MemoryDb,
+        pubsub::{Op, PubSubError, dummy::DummyPubSub},
+    };
+    use cedrus_cedar::{EntityValue, SlotId};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    async fn cedrus() -> Cedrus {
+        cedrus_with(DashMapCache::default()).await
+    }
+
+    async fn cedrus_with(cache: DashMapCache) -> Cedrus {
+        Cedrus::new(
+            Box::new(MemoryDb::default()),
+            Box::new(cache),
+            Box::new(DummyPubSub::new()),
+            None,
+            false,
+            false,
+        )
+        .await
+    }
+
+    async fn project(cedrus: &Cedrus) -> Uuid {
+        let project = Project {
+            id: Uuid::now_v7(),
+            name: "test".to_string(),
+            ..Default::default()
+        };
+        let owner = EntityUid::from("App::User::owner");
+        cedrus.project_create(project, owner).await.unwrap().id
+    }
+
+    #[tokio::test]
+    async fn test_project_sync() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+
+        // Nothing changed since the project was created
+        assert!(!cedrus.project_sync(&project_id).await.unwrap());
+
+        // Unchanged versions skip the comparison, even with stale memory
+        cedrus
+            .project_entities_fingerprints
+            .insert(project_id, String::new());
+        assert!(!cedrus.project_sync(&project_id).await.unwrap());
+
+        // A write this node missed the event of advances the version and is caught up with
+        let uid = EntityUid::from("App::User::alice");
+        let entity = Entity::new_no_attrs(uid.clone(), Default::default());
+        cedrus
+            .cache
+            .project_set_entities(&project_id, &[entity])
+            .await
+            .unwrap();
+        assert!(cedrus.project_sync(&project_id).await.unwrap());
+        let cedar_uid: cedar_policy::EntityUid = uid.try_into().unwrap();
+        assert!(
+            cedrus
+                .project_cedar_entities
+                .get(&project_id)
+                .unwrap()
+                .get(&cedar_uid)
+                .is_some()
+        );
+        assert!(!cedrus.project_sync(&project_id).await.unwrap());
+    }
+
+    fn users(count: usize) -> Vec<Entity> {
+        (0..count)
+            .map(|i| {
+                let uid = EntityUid::from(format!("App::User::user{i}").as_str());
+                Entity::new_no_attrs(uid, Default::default())
+            })
+            .collect()
+    }
+
+    fn is_resident(cedrus: &Cedrus, project_id: &Uuid) -> bool {
+        cedrus.project_cedar_entities.contains_key(project_id)
+            && cedrus.project_cedar_policies.contains_key(project_id)
+    }
+
+    #[tokio::test]
+    async fn test_cache_eviction_residency() {
+        let cache = DashMapCache::with_config(&DashMapCacheConfig {
+            max_size: Some(1000),
+            eviction_target_size: None,
+        });
+        let cedrus = cedrus_with(cache).await;
+        let first = project(&cedrus).await;
+        let second = project(&cedrus).await;
+
+        cedrus.project_entities_add(first, users(10)).await.unwrap();
+        assert!(is_resident(&cedrus, &first));
+
+        // Filling the Cache with the second project evicts the first one from memory too
+        cedrus
+            .project_entities_add(second, users(10))
+            .await
+            .unwrap();
+        assert!(is_resident(&cedrus, &second));
+        assert!(!is_resident(&cedrus, &first));
+        assert!(cedrus.shards.is_stale(&first));
+        assert!(cedrus.project_cedar_entities.contains_key(&Uuid::nil()));
+
+        // Its next request rebuilds it from the Database
+        cedrus.project_refresh(&first).await.unwrap();
+        assert!(is_resident(&cedrus, &first));
+        let uid: cedar_policy::EntityUid = EntityUid::from("App::User::user0").try_into().unwrap();
+        assert!(
+            cedrus
+                .project_cedar_entities
+                .get(&first)
+                .unwrap()
+                .get(&uid)
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_is_authorized_batch() {
+        let mut cedrus = cedrus().await;
+        cedrus.evaluation_slots = EvaluationSlots::new(&EvaluationLimitConfig {
+            max_concurrency: Some(1),
+            max_queue: None,
+            max_abandoned: None,
+        });
+        let project_id = project(&cedrus).await;
+        cedrus
+            .project_entities_add(project_id, users(200))
+            .await
+            .unwrap();
+        let policy = cedar_policy::Policy::parse(
+            Some(cedar_policy::PolicyId::new("user0")),
+            r#"permit(principal == App::User::"user0", action, resource);"#,
+        )
+        .unwrap();
+        let policies = HashMap::from([(
+            PolicyId::from("user0".to_string()),
+            policy.try_into().unwrap(),
+        )]);
+        cedrus
+            .project_policies_add(project_id, policies)
+            .await
+            .unwrap();
+
+        // More requests than a chunk, answered in order
+        let requests: Vec<_> = users(200)
+            .iter()
+            .map(|user| Request {
+                principal: user.uid().clone(),
+                action: EntityUid::from(r#"App::Action::"view""#),
+                resource: EntityUid::from("App::Document::doc"),
+                context: None,
+            })
+            .collect();
+        let batch = || cedrus.is_authorized_batch(&project_id, requests.clone(), None, false, None);
+
+        // It waits for the evaluation slot of the project held elsewhere
+        let permits = cedrus
+            .evaluation_slots
+            .acquire(&[project_id])
+            .await
+            .unwrap();
+        let waiting = tokio::time::timeout(Duration::from_millis(50), batch()).await;
+        assert!(waiting.is_err());
+        drop(permits);
+
+        let responses = batch().await.unwrap();
+        assert_eq!(responses.len(), 200);
+        assert_eq!(responses[0].decision, Decision::Allow);
+        assert!(
+            responses[1..]
+                .iter()
+                .all(|response| response.decision == Decision::Deny)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entities_expire() {
+        let cedrus = cedrus().await;
+        let expired = chrono::Utc::now() - chrono::Duration::minutes(1);
+        let uid = EntityUid::from("App::User::session");
+        let entity =
+            Entity::new_no_attrs(uid.clone(), Default::default()).with_expires_at(Some(expired));
+
+        let linked = project(&cedrus).await;
+        let failing = project(&cedrus).await;
+        for project_id in [linked, failing] {
+            cedrus
+                .project_entities_add(project_id, vec![entity.clone(), users(1).remove(0)])
+                .await
+                .unwrap();
+        }
+        let template = cedar_policy::Template::parse(
+            Some(cedar_policy::PolicyId::new("session")),
+            "permit(principal == ?principal, action, resource);",
+        )
+        .unwrap();
+        let template_id = PolicyId::from("session".to_string());
+        cedrus
+            .project_templates_add(
+                linked,
+                HashMap::from([(template_id.clone(), template.try_into().unwrap())]),
+            )
+            .await
+            .unwrap();
+        let link = TemplateLink::new(
+            template_id,
+            PolicyId::from("session:link".to_string()),
+            HashMap::from([(SlotId::Principal, EntityValue::EntityUid(uid.clone()))]),
+        );
+        cedrus
+            .project_template_links_add(linked, vec![link])
+            .await
+            .unwrap();
+        cedrus.db.project_remove(&failing).await.unwrap();
+
+        // Another node sweeping keeps this one from sweeping
+        let lock = cedrus
+            .cache
+            .lock_entity_expiry(Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(cedrus.entities_expire().await.unwrap().is_empty());
+        drop(lock);
+
+        // The failing project doesn't hold back the other one, its link removed first
+        let removed = cedrus.entities_expire().await.unwrap();
+        assert_eq!(removed, vec![(linked, 1, 1)]);
+        assert!(
+            cedrus
+                .cache
+                .project_get_template_links(&linked)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let remaining = cedrus.cache_entities(&linked, &[]).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_ne!(remaining[0].uid(), &uid);
+
+        // A node only sweeps the projects it owns, their expiries being current on it alone
+        let mut cedrus = cedrus;
+        cedrus
+            .project_entities_add(linked, vec![entity.clone()])
+            .await
+            .unwrap();
+        cedrus.shards = ShardOwnership::new(&ShardConfig {
+            node_id: "a".to_string(),
+            nodes: vec!["b".to_string()],
+            virtual_nodes: None,
+        });
+        assert!(cedrus.entities_expire().await.unwrap().is_empty());
+        assert_eq!(cedrus.cache_entities(&linked, &[]).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_to_cedar_entities() {
+        // Several chunks, each entity converted once
+        let entities = users(2 * ENTITY_CONVERSION_CHUNK_SIZE + 1);
+        let cedar_entities = Cedrus::to_cedar_entities(entities, None).await;
+        let uids: HashSet<String> = cedar_entities.iter().map(|e| e.uid().to_string()).collect();
+        assert_eq!(cedar_entities.len(), 2 * ENTITY_CONVERSION_CHUNK_SIZE + 1);
+        assert_eq!(uids.len(), cedar_entities.len());
+    }
+
+    #[tokio::test]
+    async fn test_entities_fingerprint_after_build() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        cedrus
+            .project_entities_add(project_id, users(1))
+            .await
+            .unwrap();
+        let fingerprint = cedrus
+            .project_entities_fingerprints
+            .get(&project_id)
+            .map(|f| f.clone());
+
+        // A schema Cedar rejects fails the build, which keeps the fingerprint of the entities
+        // still in memory and is not skipped as unchanged next time
+        let schema: Schema = serde_json::from_value(serde_json::json!({
+            "App": {
+                "entityTypes": {"User": {"memberOfTypes": ["Missing"]}},
+                "actions": {}
             }
+        }))
+        .unwrap();
+        cedrus
+            .cache
+            .project_set_schema(&project_id, &schema)
+            .await
+            .unwrap();
+        assert!(cedrus.on_project_entities(&project_id).await.is_err());
+        assert_eq!(
+            cedrus
+                .project_entities_fingerprints
+                .get(&project_id)
+                .map(|f| f.clone()),
+            fingerprint
+        );
+        assert!(cedrus.on_project_entities(&project_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_project_policies_snapshot() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let snapshot = cedrus.project_policies(&project_id).unwrap();
+
+        let policy = cedar_policy::Policy::parse(
+            Some(cedar_policy::PolicyId::new("all")),
+            "permit(principal, action, resource);",
+        )
+        .unwrap();
+        cedrus
+            .project_policies_add(
+                project_id,
+                HashMap::from([(
+                    PolicyId::from("all".to_string()),
+                    policy.try_into().unwrap(),
+                )]),
+            )
+            .await
+            .unwrap();
+        assert_ne!(
+            cedrus.project_policy_version(&project_id).unwrap(),
+            snapshot.version
+        );
+
+        // The policies of a checked version still decide once replaced
+        let is_authorized = |policies| {
+            cedrus
+                .is_authorized(
+                    &project_id,
+                    EntityUid::from("App::User::alice"),
+                    EntityUid::from(r#"App::Action::"view""#),
+                    EntityUid::from("App::Document::doc"),
+                    None,
+                    None,
+                    policies,
+                )
+                .unwrap()
+                .decision
+        };
+        assert_eq!(is_authorized(Some(snapshot)), Decision::Deny);
+        assert_eq!(is_authorized(None), Decision::Allow);
+    }
+
+    #[tokio::test]
+    async fn test_project_bundle_import() {
+        let mut cedrus = cedrus().await;
+        let key = openssl::pkey::PKey::generate_ed25519().unwrap();
+        cedrus.bundle_keys = BundleKeys::new(&crate::core::BundleConfig {
+            private_key: Some(String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap()),
+            trusted_keys: Vec::new(),
+        })
+        .unwrap();
+        let project_id = project(&cedrus).await;
+        let other_id = project(&cedrus).await;
+
+        let policy = cedar_policy::Policy::parse(
+            Some(cedar_policy::PolicyId::new("all")),
+            "permit(principal, action, resource);",
+        )
+        .unwrap();
+        let mut policy_set = PolicySet {
+            static_policies: HashMap::from([(
+                PolicyId::from("all".to_string()),
+                policy.try_into().unwrap(),
+            )]),
+            ..Default::default()
+        };
+
+        // A bundle is only imported in the project it was exported from
+        let bundle = cedrus
+            .bundle_keys
+            .sign(other_id, policy_set.clone())
+            .unwrap();
+        assert!(matches!(
+            cedrus.project_bundle_import(project_id, bundle).await,
+            Err(CedrusError::BundleError(_))
+        ));
+
+        // Nothing of an invalid policy set is written
+        policy_set.template_links.push(TemplateLink::new(
+            PolicyId::from("missing".to_string()),
+            PolicyId::from("link".to_string()),
+            HashMap::new(),
+        ));
+        let bundle = cedrus
+            .bundle_keys
+            .sign(project_id, policy_set.clone())
+            .unwrap();
+        assert!(
+            cedrus
+                .project_bundle_import(project_id, bundle)
+                .await
+                .is_err()
+        );
+        let state = cedrus.project_state_load(&project_id).await.unwrap();
+        assert!(state.policies.is_empty());
+
+        policy_set.template_links.clear();
+        let bundle = cedrus.bundle_keys.sign(project_id, policy_set).unwrap();
+        let added = cedrus
+            .project_bundle_import(project_id, bundle)
+            .await
+            .unwrap();
+        assert_eq!(added.static_policies.len(), 1);
+        let state = cedrus.project_state_load(&project_id).await.unwrap();
+        assert_eq!(state.policies.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_project_state_apply_concurrent() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let state = |ids: &[&str]| ProjectState {
+            policies: ids
+                .iter()
+                .map(|id| {
+                    let policy = cedar_policy::Policy::parse(
+                        Some(cedar_policy::PolicyId::new(*id)),
+                        "permit(principal, action, resource);",
+                    )
+                    .unwrap();
+                    (PolicyId::from(id.to_string()), policy.try_into().unwrap())
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        // The second apply plans against the state the first one left, so the project ends
+        // up matching one of them rather than a mix of both
+        let (a, b) = tokio::join!(
+            cedrus.project_state_apply(project_id, state(&["a1", "a2"]), true),
+            cedrus.project_state_apply(project_id, state(&["b1", "b2"]), true),
+        );
+        a.unwrap();
+        b.unwrap();
+        let mut ids: Vec<String> = cedrus
+            .project_state_load(&project_id)
+            .await
+            .unwrap()
+            .policies
+            .keys()
+            .map(|id| id.to_string())
+            .collect();
+        ids.sort();
+        assert!(ids == ["a1", "a2"] || ids == ["b1", "b2"]);
+    }
+
+    // Counts the events published to the other nodes
+    struct CountingPubSub(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl PubSub for CountingPubSub {
+        async fn subscribe(&self, _ops: &[Op<'_>]) -> Result<(), PubSubError> {
+            Ok(())
+        }
+
+        async fn publish(&self, _msg: Event) -> Result<(), PubSubError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_project_state_dry_run() {
+        let published = Arc::new(AtomicUsize::new(0));
+        let cedrus = Cedrus::new(
+            Box::new(MemoryDb::default()),
+            Box::new(DashMapCache::default()),
+            Box::new(CountingPubSub(published.clone())),
+            None,
+            false,
+            false,
+        )
+        .await;
+        let project_id = project(&cedrus).await;
+        let published_before = published.load(Ordering::Relaxed);
+
+        // The link is checked against the template of the same state, never written
+        let policy = cedar_policy::Policy::parse(
+            Some(cedar_policy::PolicyId::new("all")),
+            "permit(principal, action, resource);",
+        )
+        .unwrap();
+        let template = cedar_policy::Template::parse(
+            Some(cedar_policy::PolicyId::new("viewer")),
+            "permit(principal == ?principal, action, resource);",
+        )
+        .unwrap();
+        let template_id = PolicyId::from("viewer".to_string());
+        let desired = ProjectState {
+            policies: HashMap::from([(
+                PolicyId::from("all".to_string()),
+                policy.try_into().unwrap(),
+            )]),
+            templates: HashMap::from([(template_id.clone(), template.try_into().unwrap())]),
+            template_links: vec![TemplateLink::new(
+                template_id,
+                PolicyId::from("viewer:alice".to_string()),
+                HashMap::from([(
+                    SlotId::Principal,
+                    EntityValue::EntityUid(EntityUid::from("App::User::alice")),
+                )]),
+            )],
+            ..Default::default()
+        };
+
+        let untouched = || async {
+            let policies = cedrus
+                .db
+                .project_policies_load(&project_id, &Query::new())
+                .await
+                .unwrap();
+            let templates = cedrus
+                .db
+                .project_templates_load(&project_id, &Query::new())
+                .await
+                .unwrap();
+            policies.items.is_empty()
+                && templates.items.is_empty()
+                && cedrus
+                    .cache
+                    .project_get_policies(&project_id)
+                    .await
+                    .unwrap()
+                    .is_empty()
+                && cedrus
+                    .cache
+                    .project_get_template_links(&project_id)
+                    .await
+                    .unwrap()
+                    .is_empty()
+                && published.load(Ordering::Relaxed) == published_before
+        };
+
+        let plan = cedrus
+            .project_state_apply(project_id, desired.clone(), false)
+            .await
+            .unwrap();
+        assert_eq!(plan.template_links_changed.len(), 1);
+        assert!(untouched().await);
+
+        let report = dry_run::run(cedrus.project_state_apply(project_id, desired, true))
+            .await
+            .unwrap();
+        assert_eq!(report.plan, plan);
+        assert!(untouched().await);
+    }
+
+    #[tokio::test]
+    async fn test_project_promote_version() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let owner = EntityUid::from("App::User::owner");
+        let staging = cedrus
+            .project_environment_create(project_id, "staging".to_string(), owner.clone())
+            .await
+            .unwrap()
+            .id;
+        cedrus
+            .project_environment_create(project_id, "production".to_string(), owner)
+            .await
+            .unwrap();
+
+        let policy = |id: &str| {
+            let policy = cedar_policy::Policy::parse(
+                Some(cedar_policy::PolicyId::new(id)),
+                "permit(principal, action, resource);",
+            )
+            .unwrap();
+            HashMap::from([(PolicyId::from(id.to_string()), policy.try_into().unwrap())])
+        };
+        cedrus
+            .project_policies_add(staging, policy("all"))
+            .await
+            .unwrap();
+        let tested = cedrus.project_policy_version(&staging).unwrap();
+        let promotion = |version| Promotion {
+            to: "production".to_string(),
+            version,
+        };
+
+        let report = cedrus
+            .project_promote(project_id, "staging", promotion(Some(tested.clone())))
+            .await
+            .unwrap();
+        assert_eq!(report.version, tested);
+
+        // A change made since the version was tested is caught, though not compiled yet
+        cedrus
+            .db
+            .project_policies_save(&staging, &policy("other"))
+            .await
+            .unwrap();
+        assert_eq!(cedrus.project_policy_version(&staging).unwrap(), tested);
+        assert!(matches!(
+            cedrus
+                .project_promote(project_id, "staging", promotion(Some(tested)))
+                .await,
+            Err(CedrusError::Conflict)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_project_candidate_slots() {
+        let mut cedrus = cedrus().await;
+        cedrus.evaluation_slots = EvaluationSlots::new(&EvaluationLimitConfig {
+            max_concurrency: Some(1),
+            max_queue: None,
+            max_abandoned: None,
+        });
+        let project_id = project(&cedrus).await;
+        cedrus.project_candidates.insert(
+            project_id,
+            CandidateShadow::new(
+                ProjectCandidate::default(),
+                cedar_policy::PolicySet::new(),
+                String::new(),
+            ),
+        );
+
+        // The live evaluations holding the slot of the project, shadow ones are dropped
+        let live = cedrus
+            .evaluation_slots
+            .acquire(&[project_id])
+            .await
+            .unwrap();
+        assert!(cedrus.project_candidate_slots(&project_id, 3).is_none());
+        drop(live);
+
+        let shadow = cedrus.project_candidate_slots(&project_id, 3).unwrap();
+        assert!(cedrus.project_candidate_slots(&project_id, 2).is_none());
+        drop(shadow);
+        assert!(cedrus.project_candidate_slots(&project_id, 1).is_some());
+
+        let report = cedrus.project_candidates.get(&project_id).unwrap().report();
+        assert_eq!((report.evaluations, report.skipped), (0, 5));
+    }
+
+    #[tokio::test]
+    async fn test_authorizer_degraded() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let identity_source = IdentitySource {
+            principal_entity_type: "User".to_string(),
+            configuration: Configuration::OpenIdConnectConfiguration(OpenIdConnectConfiguration {
+                issuer: "http://127.0.0.1:1".to_string(),
+                ..Default::default()
+            }),
+            entity_mapper: None,
+        };
+
+        // The identity provider being unreachable leaves the project without JWT
+        // authentication rather than failing, to be rebuilt later
+        cedrus
+            .on_project_identity_source_set(&project_id, &identity_source)
+            .await
+            .unwrap();
+        assert!(
+            cedrus
+                .project_authorizers
+                .get(&project_id)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(cedrus.authorizers_rebuild().await, 1);
+
+        // Removing the identity source stops the rebuilds
+        cedrus.on_project_identity_source_del(&project_id).unwrap();
+        assert_eq!(cedrus.authorizers_rebuild().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_principal() {
+        let cedrus = cedrus().await;
+        let admin_schema =
+            serde_json::from_str(include_str!("../../config/cedrus.cedarschema.json")).unwrap();
+        cedrus.project_schemas.insert(Uuid::nil(), admin_schema);
+        let project_id = project(&cedrus).await;
+        assert!(!cedrus.anonymous_principals.contains_key(&project_id));
+
+        // Never a principal of the admin project, whose rights extend beyond the project
+        let mut project = cedrus.db.project_load(&project_id).await.unwrap().unwrap();
+        project.anonymous_principal = Some(EntityUid::from("User::owner"));
+        let updated = cedrus.project_update(project_id, project).await;
+        assert!(matches!(updated, Err(CedrusError::BadRequest)));
+
+        // Credential-less requests act as the principal set on the project, until unset
+        let mut project = cedrus.db.project_load(&project_id).await.unwrap().unwrap();
+        project.anonymous_principal = Some(EntityUid::from("App::User::anonymous"));
+        let mut project = cedrus.project_update(project_id, project).await.unwrap();
+        assert_eq!(
+            cedrus
+                .anonymous_principals
+                .get(&project_id)
+                .map(|principal| principal.to_string()),
+            Some(EntityUid::from("App::User::anonymous").to_string())
+        );
+
+        project.anonymous_principal = None;
+        cedrus.project_update(project_id, project).await.unwrap();
+        assert!(!cedrus.anonymous_principals.contains_key(&project_id));
+    }
+
+    #[tokio::test]
+    async fn test_project_notifications() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let webhook = "https://hooks.slack.com/services/T0/B0/secret";
+
+        // Only Slack webhooks without hosts allowed by the configuration
+        let mut project = cedrus.db.project_load(&project_id).await.unwrap().unwrap();
+        project.notifications = Some(ProjectNotifications {
+            slack_webhook_url: Some("https://169.254.169.254/latest/meta-data".to_string()),
+            ..Default::default()
+        });
+        let updated = cedrus.project_update(project_id, project.clone()).await;
+        assert!(matches!(updated, Err(CedrusError::BadRequest)));
+
+        project.notifications = Some(ProjectNotifications {
+            slack_webhook_url: Some(webhook.to_string()),
+            ..Default::default()
+        });
+        let updated = cedrus.project_update(project_id, project).await.unwrap();
+
+        // The redacted webhook of a response sent back keeps it
+        let mut updated = updated.redacted();
+        updated.name = "renamed".to_string();
+        cedrus.project_update(project_id, updated).await.unwrap();
+        let stored = cedrus.db.project_load(&project_id).await.unwrap().unwrap();
+        assert_eq!(stored.name, "renamed");
+        assert_eq!(
+            stored.notifications.unwrap().slack_webhook_url.as_deref(),
+            Some(webhook)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consistency_guardrails() {
+        let cedrus = cedrus().await;
+        let guardrail = cedar_policy::Policy::parse(
+            Some(cedar_policy::PolicyId::new("no-delete")),
+            r#"@guardrail("") forbid(principal, action == App::Action::"delete", resource);"#,
+        )
+        .unwrap();
+        *cedrus.guardrails.write().unwrap() = HashMap::from([(
+            PolicyId::from("no-delete".to_string()),
+            guardrail.try_into().unwrap(),
+        )]);
+        let project_id = project(&cedrus).await;
+        cedrus.on_project_policy_set(&project_id).await.unwrap();
+
+        // The guardrails merged into the compiled policy set are expected there
+        let report = cedrus
+            .project_consistency_check(project_id, false)
+            .await
+            .unwrap();
+        assert!(report.policies.extra_in_memory.is_empty());
+        assert!(report.is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_consistency_repair() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        cedrus
+            .project_entities_add(project_id, users(2))
+            .await
+            .unwrap();
+        let uid = EntityUid::from("App::User::user0");
+        cedrus
+            .cache
+            .project_del_entities(&project_id, std::slice::from_ref(&uid))
+            .await
+            .unwrap();
+
+        // Another node holding the lock repairs it
+        let lock = cedrus
+            .cache
+            .project_lock_policy_set(&project_id, Duration::from_secs(1))
+            .await
+            .unwrap();
+        let report = cedrus
+            .project_consistency_check(project_id, true)
+            .await
+            .unwrap();
+        assert_eq!(report.entities.missing_in_cache, vec![uid.to_string()]);
+        assert!(!report.repaired);
+        drop(lock);
+
+        let reports = cedrus.consistency_check(true).await.unwrap();
+        let report = reports
+            .iter()
+            .find(|report| report.project_id == project_id)
+            .unwrap();
+        assert!(report.repaired);
+        assert_eq!(report.error, None);
+        assert_eq!(
+            cedrus.cache_entities(&project_id, &[]).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_timeout() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let mut project = cedrus.db.project_load(&project_id).await.unwrap().unwrap();
+        project.evaluation_timeout_millis = Some(50);
+        cedrus.project_update(project_id, project).await.unwrap();
+
+        let answer = cedrus.evaluate(&[project_id], || Ok(1)).await.unwrap();
+        assert_eq!(answer, 1);
+        assert!(!cedrus.evaluation_timeouts.contains_key(&project_id));
+
+        // An evaluation running past the timeout is given up on and counted
+        let stalled = cedrus
+            .evaluate(&[project_id], || {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(1)
+            })
+            .await;
+        assert!(matches!(stalled, Err(CedrusError::EvaluationTimeout)));
+        assert_eq!(*cedrus.evaluation_timeouts.get(&project_id).unwrap(), 1);
+        assert_eq!(cedrus.evaluation_slots.abandoned(&project_id), 1);
+
+        // No longer counted once the stalled evaluation completed
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(cedrus.evaluation_slots.abandoned(&project_id), 0);
+    }
+
+    #[tokio::test]
+    async fn test_project_role_assign() {
+        let cedrus = cedrus().await;
+        let admin = Project {
+            id: Uuid::nil(),
+            name: "admin".to_string(),
+            ..Default::default()
+        };
+        cedrus.db.project_save(&admin).await.unwrap();
+        let project_id = project(&cedrus).await;
+
+        let scoped = cedar_policy::Template::parse(
+            Some(cedar_policy::PolicyId::new("scoped")),
+            "permit(principal == ?principal, action, resource in ?resource);",
+        )
+        .unwrap();
+        let unscoped = cedar_policy::Template::parse(
+            Some(cedar_policy::PolicyId::new("unscoped")),
+            "permit(principal == ?principal, action, resource);",
+        )
+        .unwrap();
+        let scoped_id = PolicyId::from("scoped".to_string());
+        let unscoped_id = PolicyId::from("unscoped".to_string());
+        cedrus
+            .project_templates_add(
+                Uuid::nil(),
+                HashMap::from([
+                    (scoped_id.clone(), scoped.try_into().unwrap()),
+                    (unscoped_id.clone(), unscoped.try_into().unwrap()),
+                ]),
+            )
+            .await
+            .unwrap();
+        let role = |id: &str, templates: Vec<PolicyId>| Role {
+            id: id.to_string(),
+            templates,
+            ..Default::default()
+        };
+        cedrus
+            .project_roles_add(
+                Uuid::nil(),
+                vec![
+                    role("editor", vec![scoped_id.clone()]),
+                    role("admin", vec![scoped_id, unscoped_id]),
+                ],
+            )
+            .await
+            .unwrap();
+        let alice = EntityUid::from("App::User::alice");
+
+        // Neither over the nil project nor with a template unbound to the project
+        let assigned = cedrus
+            .project_role_assign(Uuid::nil(), "editor".to_string(), alice.clone())
+            .await;
+        assert!(matches!(assigned, Err(CedrusError::BadRequest)));
+        let assigned = cedrus
+            .project_role_assign(project_id, "admin".to_string(), alice.clone())
+            .await;
+        assert!(matches!(assigned, Err(CedrusError::BadRequest)));
+
+        let links = cedrus
+            .project_role_assign(project_id, "editor".to_string(), alice.clone())
+            .await
+            .unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(
+            links[0].values.get(&SlotId::Resource),
+            Some(&EntityValue::EntityUid(Project::entity_uid(project_id)))
+        );
+        let admin_links = || async {
+            cedrus
+                .cache
+                .project_get_template_links(&Uuid::nil())
+                .await
+                .unwrap()
+        };
+        assert_eq!(admin_links().await.len(), 1);
+
+        // The links made are revoked even once the role is removed
+        cedrus
+            .project_roles_remove(Uuid::nil(), vec!["editor".to_string()])
+            .await
+            .unwrap();
+        cedrus
+            .project_role_revoke(project_id, "editor".to_string(), alice.clone())
+            .await
+            .unwrap();
+        assert!(admin_links().await.is_empty());
+        let revoked = cedrus
+            .project_role_revoke(project_id, "editor".to_string(), alice)
+            .await;
+        assert!(matches!(revoked, Err(CedrusError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_project_entities_sorted_by_tags() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let tagged = |id: &str, tier: Option<i64>| {
+            let tags = tier
+                .map(|tier| {
+                    HashMap::from([(
+                        "tier".to_string(),
+                        cedrus_cedar::entity::EntityAttr::Number(tier),
+                    )])
+                })
+                .unwrap_or_default();
+            Entity::new_with_tags(
+                EntityUid::new("Doc".to_string(), id.to_string()),
+                HashMap::new(),
+                HashSet::new(),
+                tags,
+            )
+        };
+        cedrus
+            .project_entities_add(
+                project_id,
+                vec![
+                    tagged("a", None),
+                    tagged("b", Some(2)),
+                    tagged("c", Some(3)),
+                    tagged("d", Some(1)),
+                ],
+            )
+            .await
+            .unwrap();
+
+        // The second page carries on the order of the first one
+        let mut query = Query {
+            sort: crate::Sort::parse_list("-tags.tier"),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let ids = |page: &PageList<Entity>| {
+            page.items
+                .iter()
+                .map(|e| e.uid().id().to_string())
+                .collect::<Vec<_>>()
+        };
+        let first = cedrus
+            .project_entities_find(project_id, query.clone())
+            .await
+            .unwrap();
+        assert_eq!(ids(&first), vec!["c", "b"]);
+        assert_eq!(first.last_key.as_deref(), Some("2"));
+
+        query.start_key = first.last_key;
+        let second = cedrus
+            .project_entities_find(project_id, query.clone())
+            .await
+            .unwrap();
+        assert_eq!(ids(&second), vec!["d", "a"]);
+        assert!(second.last_key.is_none());
+
+        query.start_key = Some("g1AAAA".to_string());
+        let invalid = cedrus.project_entities_find(project_id, query).await;
+        assert!(matches!(invalid, Err(CedrusError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_project_entities_decimal_rejected() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let host = |load: f64| {
+            Entity::new(
+                EntityUid::new("Host".to_string(), "web".to_string()),
+                HashMap::from([(
+                    "load".to_string(),
+                    cedrus_cedar::entity::EntityAttr::Decimal(load),
+                )]),
+                HashSet::new(),
+            )
+        };
+
+        // A value a Cedar decimal cannot hold is refused rather than rounded
+        for load in [0.12345, 1e15] {
+            let added = cedrus
+                .project_entities_add(project_id, vec![host(load)])
+                .await;
+            assert!(matches!(added, Err(CedrusError::ValidationError(_))));
         }
+        let found = cedrus
+            .project_entities_find(project_id, Query::new())
+            .await
+            .unwrap();
+        assert!(found.items.is_empty());
+
+        cedrus
+            .project_entities_add(project_id, vec![host(0.1234)])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_project_state_digest() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let digest = || async { cedrus.project_state_digest(&project_id).await.unwrap() };
+        let initial = digest().await;
+        assert!(initial.is_some());
+
+        // API keys and the identity source are part of the state an audit record covers
+        let api_key = ApiKey::new(
+            Uuid::now_v7(),
+            String::new(),
+            "test".to_string(),
+            project_id,
+            EntityUid::from("App::User::owner"),
+        );
+        cedrus
+            .project_apikeys_add(project_id, api_key)
+            .await
+            .unwrap();
+        let with_api_key = digest().await;
+        assert_ne!(with_api_key, initial);
+
+        let identity_source = IdentitySource {
+            principal_entity_type: "User".to_string(),
+            ..Default::default()
+        };
+        cedrus
+            .project_identity_source_update(project_id, identity_source)
+            .await
+            .unwrap();
+        let with_identity_source = digest().await;
+        assert_ne!(with_identity_source, with_api_key);
+
+        // Roles are part of the admin project state
+        let admin_digest = || async { cedrus.project_state_digest(&Uuid::nil()).await.unwrap() };
+        let admin = admin_digest().await;
+        let role = Role {
+            id: "viewer".to_string(),
+            ..Default::default()
+        };
+        cedrus
+            .project_roles_add(Uuid::nil(), vec![role])
+            .await
+            .unwrap();
+        assert_ne!(admin_digest().await, admin);
+        assert_eq!(digest().await, with_identity_source);
+    }
+
+    #[tokio::test]
+    async fn test_project_jobs_recover() {
+        let cedrus = cedrus().await;
+        let project_id = project(&cedrus).await;
+        let job = cedrus
+            .project_job_create(project_id, &JobTask::Export)
+            .await
+            .unwrap();
+        assert_eq!(job.node_id, Some(cedrus.id));
+        assert!(cedrus.project_jobs_recover().await.unwrap().is_empty());
+
+        // The node running a job stops once another one took it over
+        let running = tokio::sync::Mutex::new(job.clone());
+        assert!(cedrus.project_job_heartbeat(&running).await);
+        let mut stopped = job;
+        stopped.node_id = Some(Uuid::now_v7());
+        stopped.lease_expires_at = Some(chrono::Utc::now() - chrono::Duration::seconds(1));
+        cedrus
+            .db
+            .project_job_save(&project_id, &stopped)
+            .await
+            .unwrap();
+        assert!(!cedrus.project_job_heartbeat(&running).await);
+
+        // A job whose lease expired is taken over and run again
+        let recovered = cedrus.project_jobs_recover().await.unwrap();
+        assert_eq!(recovered.len(), 1);
+        let (job, task) = recovered.into_iter().next().unwrap();
+        assert_eq!(task, JobTask::Export);
+        assert_eq!(job.node_id, Some(cedrus.id));
+        assert_eq!(job.attempts, 2);
+        let job = cedrus.project_job_run(job, task).await.unwrap();
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert!(
+            cedrus
+                .db
+                .project_job_task_load(&project_id, &job.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Past its attempts, a job fails
+        let mut job = cedrus
+            .project_job_create(project_id, &JobTask::Export)
+            .await
+            .unwrap();
+        job.attempts = JOB_MAX_ATTEMPTS;
+        job.lease_expires_at = None;
+        cedrus.db.project_job_save(&project_id, &job).await.unwrap();
+        assert!(cedrus.project_jobs_recover().await.unwrap().is_empty());
+        let job = cedrus.project_job_find(project_id, job.id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
     }
 }
@@ -0,0 +1,246 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::ClusterConfig;
+
+/// Expiries after which a node without heartbeat is forgotten rather than reported.
+const FORGET_AFTER_EXPIRIES: i32 = 10;
+
+/// Status a node publishes on every heartbeat.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeHeartbeat {
+    pub node_id: Uuid,
+    pub version: String,
+    pub started_at: DateTime<Utc>,
+    pub sent_at: DateTime<Utc>,
+    /// Projects loaded in memory
+    pub projects: usize,
+    /// Events the node published, heartbeats excluded
+    pub published: u64,
+    /// Events the other nodes published that the node never received
+    pub missed: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum NodeState {
+    InSync,
+    /// Missed events of other nodes, its state differing until it reloads
+    Lagging,
+    /// No heartbeat received within the expiry
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStatus {
+    #[serde(flatten)]
+    pub heartbeat: NodeHeartbeat,
+    /// When the node answering last heard of it
+    pub last_seen: DateTime<Utc>,
+    pub state: NodeState,
+}
+
+/// Nodes of the cluster as seen by the node answering, itself first.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterStatus {
+    pub node_id: Uuid,
+    pub nodes: Vec<NodeStatus>,
+}
+
+#[derive(Debug, Default)]
+struct Peer {
+    heartbeat: Option<NodeHeartbeat>,
+    last_seen: Option<DateTime<Utc>>,
+    /// Events received from the node since its first heartbeat
+    received: u64,
+    missed: u64,
+    /// Until when this node is known to have received the events of the node
+    synced_at: Option<DateTime<Utc>>,
+}
+
+/// Other nodes known from their heartbeats, and the events of each this node received. The
+/// pubsub delivering the events of a node in order, the events it published before a
+/// heartbeat and this node has not received by then were missed.
+#[derive(Debug)]
+pub struct ClusterMembership {
+    expiry: chrono::Duration,
+    started_at: DateTime<Utc>,
+    published: AtomicU64,
+    peers: DashMap<Uuid, Peer>,
+}
+
+impl Default for ClusterMembership {
+    fn default() -> Self {
+        Self::new(&ClusterConfig::default())
+    }
+}
+
+impl ClusterMembership {
+    pub fn new(conf: &ClusterConfig) -> Self {
+        Self {
+            expiry: chrono::Duration::seconds(conf.expiry as i64),
+            started_at: Utc::now(),
+            published: AtomicU64::new(0),
+            peers: DashMap::new(),
+        }
+    }
+
+    /// Counts an event this node published.
+    pub fn published(&self) {
+        self.published.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts an event received from another node.
+    pub fn received(&self, node_id: &Uuid) {
+        self.peers.entry(*node_id).or_default().received += 1;
+    }
+
+    /// Records the heartbeat of another node. The events it published before its first
+    /// heartbeat, or before it restarted, are taken as received, this node having loaded its
+    /// state from the Cache since.
+    pub fn observe(&self, heartbeat: &NodeHeartbeat) {
+        let mut peer = self.peers.entry(heartbeat.node_id).or_default();
+        let restarted = peer.heartbeat.as_ref().is_none_or(|previous| {
+            previous.started_at != heartbeat.started_at || previous.published > heartbeat.published
+        });
+        if restarted {
+            peer.received = heartbeat.published;
+            peer.missed = 0;
+        } else {
+            peer.missed = heartbeat.published.saturating_sub(peer.received);
+        }
+        if peer.missed == 0 {
+            peer.synced_at = Some(heartbeat.sent_at);
+        }
+        peer.heartbeat = Some(heartbeat.clone());
+        peer.last_seen = Some(Utc::now());
+    }
+
+    /// Takes the events of the other nodes as received, this node having reloaded its state.
+    pub fn resynced(&self) {
+        let now = Utc::now();
+        for mut peer in self.peers.iter_mut() {
+            if let Some(heartbeat) = &peer.heartbeat {
+                peer.received = heartbeat.published;
+            }
+            peer.missed = 0;
+            peer.synced_at = Some(now);
+        }
+    }
+
+    /// Until when this node is known to have received the events of every reachable node, now
+    /// when it knows of none.
+    pub fn synced_at(&self) -> DateTime<Utc> {
+        let now = Utc::now();
+        self.peers
+            .iter()
+            .filter(|peer| peer.last_seen.is_some_and(|seen| now - seen <= self.expiry))
+            .filter_map(|peer| peer.synced_at)
+            .min()
+            .unwrap_or(now)
+    }
+
+    /// Heartbeat of this node, forgetting the nodes silent for long.
+    pub fn heartbeat(&self, node_id: Uuid, projects: usize) -> NodeHeartbeat {
+        let forgotten = Utc::now() - self.expiry * FORGET_AFTER_EXPIRIES;
+        self.peers
+            .retain(|_, peer| peer.last_seen.is_none_or(|seen| seen > forgotten));
+
+        NodeHeartbeat {
+            node_id,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: self.started_at,
+            sent_at: Utc::now(),
+            projects,
+            published: self.published.load(Ordering::Relaxed),
+            missed: self.peers.iter().map(|peer| peer.missed).sum(),
+        }
+    }
+
+    /// This node, from its current heartbeat, and the other nodes from their last one.
+    pub fn status(&self, heartbeat: NodeHeartbeat) -> ClusterStatus {
+        let now = Utc::now();
+        let state = |heartbeat: &NodeHeartbeat, last_seen: DateTime<Utc>| {
+            if now - last_seen > self.expiry {
+                NodeState::Unreachable
+            } else if heartbeat.missed > 0 {
+                NodeState::Lagging
+            } else {
+                NodeState::InSync
+            }
+        };
+
+        let node_id = heartbeat.node_id;
+        let mut nodes = vec![NodeStatus {
+            state: state(&heartbeat, now),
+            heartbeat,
+            last_seen: now,
+        }];
+        let mut peers: Vec<NodeStatus> = self
+            .peers
+            .iter()
+            .filter_map(|peer| {
+                let (heartbeat, last_seen) = (peer.heartbeat.clone()?, peer.last_seen?);
+                Some(NodeStatus {
+                    state: state(&heartbeat, last_seen),
+                    heartbeat,
+                    last_seen,
+                })
+            })
+            .collect();
+        peers.sort_by_key(|peer| peer.heartbeat.started_at);
+        nodes.extend(peers);
+
+        ClusterStatus { node_id, nodes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_membership() {
+        let cluster = ClusterMembership::default();
+        let local_id = Uuid::now_v7();
+        let peer = ClusterMembership::default();
+        let peer_id = Uuid::now_v7();
+
+        // Events published before the first heartbeat are not missed
+        peer.published();
+        peer.published();
+        cluster.received(&peer_id);
+        cluster.observe(&peer.heartbeat(peer_id, 3));
+        assert_eq!(cluster.heartbeat(local_id, 1).missed, 0);
+
+        peer.published();
+        cluster.received(&peer_id);
+        peer.published();
+        cluster.observe(&peer.heartbeat(peer_id, 3));
+        assert_eq!(cluster.heartbeat(local_id, 1).missed, 1);
+
+        let status = cluster.status(cluster.heartbeat(local_id, 1));
+        assert_eq!(status.node_id, local_id);
+        assert_eq!(
+            status
+                .nodes
+                .iter()
+                .map(|node| (node.heartbeat.node_id, node.state))
+                .collect::<Vec<_>>(),
+            vec![(local_id, NodeState::Lagging), (peer_id, NodeState::InSync)]
+        );
+
+        let synced_at = cluster.synced_at();
+        cluster.resynced();
+        assert_eq!(cluster.heartbeat(local_id, 1).missed, 0);
+        assert!(cluster.synced_at() > synced_at);
+    }
+}
@@ -0,0 +1,98 @@
+use cedrus_cedar::{Decision, Response};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// How the decisions of several projects, e.g. platform guardrails and tenant policies,
+/// combine into one.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DecisionStrategy {
+    /// Denied when a project explicitly denies, allowed when another one allows
+    #[default]
+    DenyOverrides,
+    /// Allowed when any project allows
+    PermitOverrides,
+}
+
+impl DecisionStrategy {
+    /// A Deny without reason is a project none of whose policies apply, which only abstains.
+    pub fn combine<'a>(&self, responses: impl IntoIterator<Item = &'a Response>) -> Decision {
+        let mut allowed = false;
+        for response in responses {
+            match (self, &response.decision) {
+                (DecisionStrategy::DenyOverrides, Decision::Deny)
+                    if !response.reason.is_empty() =>
+                {
+                    return Decision::Deny;
+                }
+                (DecisionStrategy::PermitOverrides, Decision::Allow) => return Decision::Allow,
+                (_, Decision::Allow) => allowed = true,
+                (_, Decision::Deny) => {}
+            }
+        }
+
+        match allowed {
+            true => Decision::Allow,
+            false => Decision::Deny,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectDecision {
+    pub project_id: Uuid,
+    pub response: Response,
+}
+
+/// Decision combined from the ones of several projects, listed in the order requested.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CombinedResponse {
+    pub decision: Decision,
+    pub strategy: DecisionStrategy,
+    pub projects: Vec<ProjectDecision>,
+}
+
+impl CombinedResponse {
+    pub fn new(strategy: DecisionStrategy, projects: Vec<ProjectDecision>) -> Self {
+        Self {
+            decision: strategy.combine(projects.iter().map(|p| &p.response)),
+            strategy,
+            projects,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(decision: Decision, reason: &[&str]) -> Response {
+        Response {
+            decision,
+            reason: reason.iter().map(|r| r.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_combine() {
+        let allow = response(Decision::Allow, &["tenant-permit"]);
+        let forbid = response(Decision::Deny, &["guardrail-forbid"]);
+        let abstain = response(Decision::Deny, &[]);
+
+        let deny_overrides = DecisionStrategy::DenyOverrides;
+        assert_eq!(deny_overrides.combine([&abstain, &allow]), Decision::Allow);
+        assert_eq!(deny_overrides.combine([&allow, &forbid]), Decision::Deny);
+        assert_eq!(deny_overrides.combine([&abstain, &abstain]), Decision::Deny);
+
+        let permit_overrides = DecisionStrategy::PermitOverrides;
+        assert_eq!(permit_overrides.combine([&forbid, &allow]), Decision::Allow);
+        assert_eq!(
+            permit_overrides.combine([&forbid, &abstain]),
+            Decision::Deny
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::{BTreeSet, HashSet};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConsistencyDrift {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_in_cache: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_in_cache: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stale_in_cache: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_in_memory: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_in_memory: Vec<String>,
+}
+
+impl ConsistencyDrift {
+    /// Compares the Database ids against the Cache ids, and the expected ids against the ids
+    /// loaded in memory. `stale` holds the ids present in both Database and Cache whose
+    /// content differs.
+    pub fn new(
+        db: &HashSet<String>,
+        cache: &HashSet<String>,
+        stale: HashSet<String>,
+        expected_memory: &HashSet<String>,
+        memory: &HashSet<String>,
+    ) -> Self {
+        fn sorted<'a>(it: impl Iterator<Item = &'a String>) -> Vec<String> {
+            it.cloned()
+                .collect::<BTreeSet<String>>()
+                .into_iter()
+                .collect()
+        }
+
+        Self {
+            missing_in_cache: sorted(db.difference(cache)),
+            extra_in_cache: sorted(cache.difference(db)),
+            stale_in_cache: sorted(stale.iter()),
+            missing_in_memory: sorted(expected_memory.difference(memory)),
+            extra_in_memory: sorted(memory.difference(expected_memory)),
+        }
+    }
+
+    pub fn cache_drift(&self) -> usize {
+        self.missing_in_cache.len() + self.extra_in_cache.len() + self.stale_in_cache.len()
+    }
+
+    pub fn memory_drift(&self) -> usize {
+        self.missing_in_memory.len() + self.extra_in_memory.len()
+    }
+
+    pub fn discrepancies(&self) -> usize {
+        self.cache_drift() + self.memory_drift()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConsistencyReport {
+    pub project_id: Uuid,
+    pub entities: ConsistencyDrift,
+    pub policies: ConsistencyDrift,
+    pub templates: ConsistencyDrift,
+    pub template_links: ConsistencyDrift,
+    pub repaired: bool,
+    /// Why the project could not be checked, its drifts then left empty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ConsistencyReport {
+    pub fn discrepancies(&self) -> usize {
+        self.entities.discrepancies()
+            + self.policies.discrepancies()
+            + self.templates.discrepancies()
+            + self.template_links.discrepancies()
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.error.is_none() && self.discrepancies() == 0
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use cedar_policy::{
+    ActionConstraint, Effect, EntityTypeName, PrincipalConstraint, ResourceConstraint,
+    entities_errors::EntitiesError,
+};
+use cedrus_cedar::EntityUid;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ActionCoverage {
+    pub action: EntityUid,
+    pub permit: usize,
+    pub forbid: usize,
+}
+
+/// How the enforced policies of a project (static and template linked) cover the actions and
+/// entity types declared in its schema.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PolicyCoverageReport {
+    pub permit: usize,
+    pub forbid: usize,
+    pub actions: Vec<ActionCoverage>,
+    /// Actions no policy applies to, so they are always denied
+    pub unreferenced_actions: Vec<EntityUid>,
+    /// Actions only forbid policies apply to, so they are always denied
+    pub forbid_only_actions: Vec<EntityUid>,
+    /// Principal and resource types no policy applies to
+    pub uncovered_entity_types: Vec<String>,
+}
+
+impl PolicyCoverageReport {
+    pub fn new(
+        schema: &cedar_policy::Schema,
+        policy_set: &cedar_policy::PolicySet,
+    ) -> Result<Self, EntitiesError> {
+        let action_entities = schema.action_entities()?;
+
+        // An entity of type `ty` is `in` an entity of type `parent` when it is of that type or
+        // may have it as an ancestor.
+        let may_be_in = |ty: &EntityTypeName, parent: &EntityTypeName| {
+            ty == parent
+                || schema
+                    .ancestors(ty)
+                    .is_some_and(|mut ancestors| ancestors.any(|a| a == parent))
+        };
+        let principal_matches =
+            |constraint: &PrincipalConstraint, ty: &EntityTypeName| match constraint {
+                PrincipalConstraint::Any => true,
+                PrincipalConstraint::Eq(uid) => uid.type_name() == ty,
+                PrincipalConstraint::In(uid) => may_be_in(ty, uid.type_name()),
+                PrincipalConstraint::Is(is) => is == ty,
+                PrincipalConstraint::IsIn(is, uid) => is == ty && may_be_in(ty, uid.type_name()),
+            };
+        let resource_matches =
+            |constraint: &ResourceConstraint, ty: &EntityTypeName| match constraint {
+                ResourceConstraint::Any => true,
+                ResourceConstraint::Eq(uid) => uid.type_name() == ty,
+                ResourceConstraint::In(uid) => may_be_in(ty, uid.type_name()),
+                ResourceConstraint::Is(is) => is == ty,
+                ResourceConstraint::IsIn(is, uid) => is == ty && may_be_in(ty, uid.type_name()),
+            };
+
+        let mut report = Self::default();
+        let mut actions: BTreeMap<String, ActionCoverage> = schema
+            .actions()
+            .map(|action| {
+                let coverage = ActionCoverage {
+                    action: action.clone().into(),
+                    ..Default::default()
+                };
+                (action.to_string(), coverage)
+            })
+            .collect();
+        let mut covered_types: HashSet<&EntityTypeName> = HashSet::new();
+
+        for policy in policy_set.policies() {
+            let effect = policy.effect();
+            match effect {
+                Effect::Permit => report.permit += 1,
+                Effect::Forbid => report.forbid += 1,
+            }
+
+            let principal = policy.principal_constraint();
+            let resource = policy.resource_constraint();
+            let action_constraint = policy.action_constraint();
+
+            for action in schema.actions() {
+                let applies = match &action_constraint {
+                    ActionConstraint::Any => true,
+                    ActionConstraint::Eq(uid) => uid == action,
+                    ActionConstraint::In(uids) => {
+                        uids.contains(action)
+                            || action_entities
+                                .ancestors(action)
+                                .is_some_and(|mut ancestors| ancestors.any(|a| uids.contains(a)))
+                    }
+                };
+                if !applies {
+                    continue;
+                }
+
+                let principals: Vec<&EntityTypeName> = schema
+                    .principals_for_action(action)
+                    .map(|types| {
+                        types
+                            .filter(|ty| principal_matches(&principal, ty))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let resources: Vec<&EntityTypeName> = schema
+                    .resources_for_action(action)
+                    .map(|types| types.filter(|ty| resource_matches(&resource, ty)).collect())
+                    .unwrap_or_default();
+                if principals.is_empty() || resources.is_empty() {
+                    continue;
+                }
+
+                covered_types.extend(principals);
+                covered_types.extend(resources);
+                if let Some(coverage) = actions.get_mut(&action.to_string()) {
+                    match effect {
+                        Effect::Permit => coverage.permit += 1,
+                        Effect::Forbid => coverage.forbid += 1,
+                    }
+                }
+            }
+        }
+
+        report.actions = actions.into_values().collect();
+        for coverage in &report.actions {
+            match (coverage.permit, coverage.forbid) {
+                (0, 0) => report.unreferenced_actions.push(coverage.action.clone()),
+                (0, _) => report.forbid_only_actions.push(coverage.action.clone()),
+                _ => {}
+            }
+        }
+        report.uncovered_entity_types = schema
+            .principals()
+            .chain(schema.resources())
+            .filter(|ty| !covered_types.contains(ty))
+            .map(|ty| ty.to_string())
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn action(id: &str) -> EntityUid {
+        cedar_policy::EntityUid::from_str(&format!("Action::\"{id}\""))
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn test_policy_coverage() {
+        let (schema, _) = cedar_policy::Schema::from_cedarschema_str(
+            r#"
+            entity User;
+            entity Photo;
+            entity Album;
+            action view, delete appliesTo { principal: User, resource: Photo };
+            action share appliesTo { principal: User, resource: Album };
+            "#,
+        )
+        .unwrap();
+        let policy_set = cedar_policy::PolicySet::from_str(
+            r#"
+            permit(principal, action == Action::"view", resource);
+            forbid(principal, action == Action::"delete", resource);
+            "#,
+        )
+        .unwrap();
+
+        let report = PolicyCoverageReport::new(&schema, &policy_set).unwrap();
+        assert_eq!((report.permit, report.forbid), (1, 1));
+        assert_eq!(report.actions.len(), 3);
+        assert_eq!(report.unreferenced_actions, vec![action("share")]);
+        assert_eq!(report.forbid_only_actions, vec![action("delete")]);
+        assert_eq!(report.uncovered_entity_types, vec!["Album".to_string()]);
+    }
+}
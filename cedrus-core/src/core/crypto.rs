@@ -0,0 +1,343 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use openssl::{
+    hash::MessageDigest,
+    memcmp,
+    pkey::PKey,
+    rand::rand_bytes,
+    sign::Signer,
+    symm::{Cipher, decrypt_aead, encrypt_aead},
+};
+use uuid::Uuid;
+
+use crate::CedrusError;
+
+use super::{EncryptionKeyConfig, SigningConfig};
+
+pub const HMAC_ALGORITHM: &str = "hmac-sha256";
+
+/// Prefix of the values encrypted at rest, followed by `<key id>:<base64 payload>`.
+pub const ENCRYPTED_PREFIX: &str = "cedrus-enc:v1:";
+
+/// Length in bytes of the AES-256 keys.
+pub const ENCRYPTION_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Shortest secret accepted, in bytes, the output size of SHA-256.
+const MIN_SECRET_LEN: usize = 32;
+
+fn signing_error(message: impl std::fmt::Display) -> CedrusError {
+    CedrusError::SigningError(message.to_string())
+}
+
+pub fn hmac_sha256(key: &[u8], payload: &[u8]) -> Result<Vec<u8>, CedrusError> {
+    let key = PKey::hmac(key).map_err(signing_error)?;
+    Signer::new(MessageDigest::sha256(), &key)
+        .and_then(|mut signer| signer.sign_oneshot_to_vec(payload))
+        .map_err(signing_error)
+}
+
+/// Compares two MACs in constant time.
+fn mac_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && memcmp::eq(a, b)
+}
+
+/// HMAC-SHA256 keys signing payloads on behalf of a project. Each project signs with its own
+/// secret, derived from the configured ones, so what a project signed does not verify as
+/// signed by another. The first key signs and every key verifies: a key is rotated by
+/// configuring the new one first, and dropped once what it signed expired.
+#[derive(Debug, Default, Clone)]
+pub struct HmacKeys {
+    keys: Vec<(String, Vec<u8>)>,
+}
+
+impl HmacKeys {
+    pub fn new(conf: &SigningConfig) -> Result<Self, CedrusError> {
+        let mut keys = Vec::new();
+        for key in &conf.keys {
+            if key.id.is_empty() || key.id.contains(['.', ',', '=']) {
+                return Err(signing_error(format!(
+                    "invalid signing key id: {:?}",
+                    key.id
+                )));
+            }
+            if key.secret.len() < MIN_SECRET_LEN {
+                return Err(signing_error(format!(
+                    "the signing key {} is shorter than {MIN_SECRET_LEN} bytes",
+                    key.id
+                )));
+            }
+            keys.push((key.id.clone(), key.secret.as_bytes().to_vec()));
+        }
+
+        Ok(Self { keys })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Secret of a project under the key `key_id`.
+    fn project_secret(&self, project_id: &Uuid, key_id: &str) -> Result<Vec<u8>, CedrusError> {
+        let (_, secret) = self
+            .keys
+            .iter()
+            .find(|(id, _)| id == key_id)
+            .ok_or_else(|| signing_error(format!("unknown signing key: {key_id}")))?;
+        hmac_sha256(
+            secret,
+            format!("cedrus-project:v1\n{project_id}").as_bytes(),
+        )
+    }
+
+    /// Id of the signing key and MAC of a payload.
+    pub fn sign(
+        &self,
+        project_id: &Uuid,
+        payload: &[u8],
+    ) -> Result<(String, Vec<u8>), CedrusError> {
+        let (key_id, _) = self
+            .keys
+            .first()
+            .ok_or_else(|| signing_error("no signing key is configured"))?;
+        let mac = hmac_sha256(&self.project_secret(project_id, key_id)?, payload)?;
+        Ok((key_id.clone(), mac))
+    }
+
+    pub fn verify(
+        &self,
+        project_id: &Uuid,
+        key_id: &str,
+        payload: &[u8],
+        mac: &[u8],
+    ) -> Result<(), CedrusError> {
+        let expected = hmac_sha256(&self.project_secret(project_id, key_id)?, payload)?;
+        match mac_eq(&expected, mac) {
+            true => Ok(()),
+            false => Err(signing_error("the signature is invalid")),
+        }
+    }
+}
+
+fn encryption_error(message: impl std::fmt::Display) -> CedrusError {
+    CedrusError::EncryptionError(message.to_string())
+}
+
+/// Source of the AES-256 keys encrypting values at rest.
+#[async_trait::async_trait]
+pub trait KeyProvider: Send + Sync {
+    /// Id and key encrypting new values.
+    async fn encryption_key(&self) -> Result<(String, Vec<u8>), CedrusError>;
+    /// Key of an id `encryption_key` returned, on this node or another, before a rotation or
+    /// since.
+    async fn decryption_key(&self, key_id: &str) -> Result<Vec<u8>, CedrusError>;
+}
+
+/// Keys held in the configuration, the first one encrypts and all of them decrypt.
+#[derive(Debug, Default, Clone)]
+pub struct LocalKeys {
+    keys: Vec<(String, Vec<u8>)>,
+}
+
+impl LocalKeys {
+    pub fn new(keys: &[EncryptionKeyConfig]) -> Result<Self, CedrusError> {
+        if keys.is_empty() {
+            return Err(encryption_error("no encryption key is configured"));
+        }
+        let mut local_keys = Vec::new();
+        for key in keys {
+            if key.id.is_empty() || key.id.contains(':') {
+                return Err(encryption_error(format!(
+                    "invalid encryption key id: {:?}",
+                    key.id
+                )));
+            }
+            let secret = BASE64_STANDARD.decode(&key.key).map_err(encryption_error)?;
+            if secret.len() != ENCRYPTION_KEY_LEN {
+                return Err(encryption_error(format!(
+                    "the encryption key {} is not {ENCRYPTION_KEY_LEN} bytes",
+                    key.id
+                )));
+            }
+            local_keys.push((key.id.clone(), secret));
+        }
+
+        Ok(Self { keys: local_keys })
+    }
+}
+
+#[async_trait::async_trait]
+impl KeyProvider for LocalKeys {
+    async fn encryption_key(&self) -> Result<(String, Vec<u8>), CedrusError> {
+        self.keys
+            .first()
+            .cloned()
+            .ok_or_else(|| encryption_error("no encryption key is configured"))
+    }
+
+    async fn decryption_key(&self, key_id: &str) -> Result<Vec<u8>, CedrusError> {
+        self.keys
+            .iter()
+            .find(|(id, _)| id == key_id)
+            .map(|(_, key)| key.clone())
+            .ok_or_else(|| encryption_error(format!("unknown encryption key: {key_id}")))
+    }
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Encrypts `plaintext` with AES-256-GCM. The associated data is authenticated but not
+/// stored, so the value only opens in the place it was sealed for.
+pub fn seal(key_id: &str, key: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<String, CedrusError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand_bytes(&mut nonce).map_err(encryption_error)?;
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(&nonce),
+        aad,
+        plaintext,
+        &mut tag,
+    )
+    .map_err(encryption_error)?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    payload.extend_from_slice(&tag);
+    Ok(format!(
+        "{ENCRYPTED_PREFIX}{key_id}:{}",
+        BASE64_STANDARD.encode(payload)
+    ))
+}
+
+/// Id of the key a sealed value needs.
+pub fn sealed_key_id(sealed: &str) -> Result<&str, CedrusError> {
+    sealed
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .map(|(key_id, _)| key_id)
+        .ok_or_else(|| encryption_error("invalid encrypted value"))
+}
+
+pub fn open(sealed: &str, key: &[u8], aad: &[u8]) -> Result<Vec<u8>, CedrusError> {
+    let invalid = || encryption_error("invalid encrypted value");
+    let (_, payload) = sealed
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .ok_or_else(invalid)?;
+    let payload = BASE64_STANDARD.decode(payload).map_err(|_| invalid())?;
+    if payload.len() < NONCE_LEN + TAG_LEN {
+        return Err(invalid());
+    }
+    let (nonce, rest) = payload.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    decrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        aad,
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| encryption_error("the encrypted value does not authenticate"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{SigningKeyConfig, cedrus::to_hex};
+
+    fn keys(ids: &[&str]) -> HmacKeys {
+        HmacKeys::new(&SigningConfig {
+            keys: ids
+                .iter()
+                .map(|id| SigningKeyConfig {
+                    id: id.to_string(),
+                    secret: format!("{id}-0123456789abcdef0123456789abcdef"),
+                })
+                .collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?").unwrap();
+        assert_eq!(
+            to_hex(&mac),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let project_id = Uuid::now_v7();
+        let old = keys(&["k1"]);
+        let (key_id, mac) = old.sign(&project_id, b"{}").unwrap();
+        assert_eq!(key_id, "k1");
+        assert!(old.verify(&project_id, &key_id, b"{}", &mac).is_ok());
+
+        // Tampered payload, other project
+        assert!(old.verify(&project_id, &key_id, b"[]", &mac).is_err());
+        assert!(old.verify(&Uuid::now_v7(), &key_id, b"{}", &mac).is_err());
+
+        // Rotated: the new key signs, the previous one still verifies
+        let rotated = keys(&["k2", "k1"]);
+        assert!(rotated.verify(&project_id, "k1", b"{}", &mac).is_ok());
+        let (key_id, mac) = rotated.sign(&project_id, b"{}").unwrap();
+        assert_eq!(key_id, "k2");
+        assert!(old.verify(&project_id, &key_id, b"{}", &mac).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_seal_open() {
+        let key = BASE64_STANDARD.encode([7u8; ENCRYPTION_KEY_LEN]);
+        let keys = LocalKeys::new(&[EncryptionKeyConfig {
+            id: "k1".to_string(),
+            key,
+        }])
+        .unwrap();
+        let (key_id, key) = keys.encryption_key().await.unwrap();
+
+        let sealed = seal(&key_id, &key, b"123-45-6789", b"User::alice\nssn").unwrap();
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains("123-45-6789"));
+        assert_eq!(sealed_key_id(&sealed).unwrap(), "k1");
+
+        let key = keys.decryption_key("k1").await.unwrap();
+        assert_eq!(
+            open(&sealed, &key, b"User::alice\nssn").unwrap(),
+            b"123-45-6789"
+        );
+        // Moved to another attribute or entity
+        assert!(open(&sealed, &key, b"User::bob\nssn").is_err());
+        assert!(open(&sealed, &[8u8; ENCRYPTION_KEY_LEN], b"User::alice\nssn").is_err());
+        assert!(keys.decryption_key("k2").await.is_err());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let conf = |id: &str, secret: &str| SigningConfig {
+            keys: vec![SigningKeyConfig {
+                id: id.to_string(),
+                secret: secret.to_string(),
+            }],
+        };
+        assert!(HmacKeys::new(&conf("k1", "short")).is_err());
+        assert!(HmacKeys::new(&conf("k.1", &"s".repeat(MIN_SECRET_LEN))).is_err());
+        assert!(HmacKeys::default().sign(&Uuid::nil(), b"").is_err());
+
+        let key = |id: &str, key: &[u8]| EncryptionKeyConfig {
+            id: id.to_string(),
+            key: BASE64_STANDARD.encode(key),
+        };
+        assert!(LocalKeys::new(&[]).is_err());
+        assert!(LocalKeys::new(&[key("k1", b"short")]).is_err());
+        assert!(LocalKeys::new(&[key("k:1", &[0u8; ENCRYPTION_KEY_LEN])]).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::{BTreeSet, HashMap};
+
+use cedrus_cedar::{Policy, PolicyId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::CedrusError;
+
+/// Line width the Cedar text of policies is formatted to.
+const CEDAR_LINE_WIDTH: usize = 80;
+const CEDAR_INDENT_WIDTH: isize = 2;
+
+/// Text the policies of a diff are rendered in.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffFormat {
+    /// Pretty-printed JSON policies
+    #[default]
+    Json,
+    /// Cedar policies, formatted as the Cedar CLI does
+    Cedar,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffLineKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+/// Line of a diff, with its number in the text before and after the change, from 1, as
+/// a side-by-side view aligns them.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<usize>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PolicyChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyDiff {
+    pub id: PolicyId,
+    pub change: PolicyChange,
+    /// Text of the policy before the change, unset when added
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// Text of the policy after the change, unset when removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Changes of the static policies of a project between two times of its history, sorted
+/// by policy id.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionDiff {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub format: DiffFormat,
+    pub policies: Vec<PolicyDiff>,
+}
+
+impl RevisionDiff {
+    pub fn new(
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        before: &HashMap<PolicyId, Policy>,
+        after: &HashMap<PolicyId, Policy>,
+        format: DiffFormat,
+    ) -> Result<Self, CedrusError> {
+        let ids: BTreeSet<&PolicyId> = before.keys().chain(after.keys()).collect();
+
+        let mut policies = Vec::new();
+        for id in ids {
+            let (old, new) = (before.get(id), after.get(id));
+            let change = match (old, new) {
+                (None, Some(_)) => PolicyChange::Added,
+                (Some(_), None) => PolicyChange::Removed,
+                (Some(old), Some(new))
+                    if serde_json::to_value(old)? != serde_json::to_value(new)? =>
+                {
+                    PolicyChange::Modified
+                }
+                _ => continue,
+            };
+            let before = old.map(|p| render(id, p, format)).transpose()?;
+            let after = new.map(|p| render(id, p, format)).transpose()?;
+            let lines = line_diff(
+                before.as_deref().unwrap_or_default(),
+                after.as_deref().unwrap_or_default(),
+            );
+            policies.push(PolicyDiff {
+                id: id.clone(),
+                change,
+                before,
+                after,
+                lines,
+            });
+        }
+
+        Ok(Self {
+            from,
+            to,
+            format,
+            policies,
+        })
+    }
+}
+
+/// Text of a policy in a format, a Cedar one carrying its `@id` annotation.
+pub fn render(id: &PolicyId, policy: &Policy, format: DiffFormat) -> Result<String, CedrusError> {
+    match format {
+        DiffFormat::Json => Ok(serde_json::to_string_pretty(policy)?),
+        DiffFormat::Cedar => {
+            let mut policy = policy.clone();
+            policy
+                .annotations
+                .insert("id".to_string(), Some(id.to_string()));
+            let cedar_policy =
+                cedar_policy::Policy::from_json(None, serde_json::to_value(policy)?)?;
+            let text = cedar_policy.to_string();
+
+            let config = cedar_policy_formatter::Config {
+                line_width: CEDAR_LINE_WIDTH,
+                indent_width: CEDAR_INDENT_WIDTH,
+            };
+            // The policy printed by Cedar always parses back, left as is otherwise
+            Ok(cedar_policy_formatter::policies_str_to_pretty(&text, &config).unwrap_or(text))
+        }
+    }
+}
+
+/// Line diff of two texts, from their longest common subsequence of lines, removals
+/// listed before the additions replacing them.
+pub fn line_diff(before: &str, after: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine {
+                kind: DiffLineKind::Unchanged,
+                before: Some(i + 1),
+                after: Some(j + 1),
+                text: old[i].to_string(),
+            });
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(DiffLine {
+                kind: DiffLineKind::Removed,
+                before: Some(i + 1),
+                after: None,
+                text: old[i].to_string(),
+            });
+            i += 1;
+        } else {
+            lines.push(DiffLine {
+                kind: DiffLineKind::Added,
+                before: None,
+                after: Some(j + 1),
+                text: new[j].to_string(),
+            });
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(src: &str) -> Policy {
+        Policy::try_from(cedar_policy::Policy::parse(None, src).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_line_diff() {
+        let lines = line_diff("a\nb\nc", "a\nx\nc\nd");
+        let kinds: Vec<(DiffLineKind, &str)> =
+            lines.iter().map(|l| (l.kind, l.text.as_str())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (DiffLineKind::Unchanged, "a"),
+                (DiffLineKind::Removed, "b"),
+                (DiffLineKind::Added, "x"),
+                (DiffLineKind::Unchanged, "c"),
+                (DiffLineKind::Added, "d"),
+            ]
+        );
+        assert_eq!((lines[3].before, lines[3].after), (Some(3), Some(3)));
+        assert_eq!((lines[4].before, lines[4].after), (None, Some(4)));
+    }
+
+    #[test]
+    fn test_revision_diff() {
+        let from = Utc::now();
+        let kept = policy("permit(principal, action, resource);");
+        let before = HashMap::from([
+            (PolicyId::from("kept".to_string()), kept.clone()),
+            (
+                PolicyId::from("changed".to_string()),
+                policy("permit(principal, action, resource) when { resource.public };"),
+            ),
+            (PolicyId::from("removed".to_string()), kept.clone()),
+        ]);
+        let after = HashMap::from([
+            (PolicyId::from("kept".to_string()), kept.clone()),
+            (
+                PolicyId::from("changed".to_string()),
+                policy("permit(principal, action, resource) when { resource.shared };"),
+            ),
+            (PolicyId::from("added".to_string()), kept),
+        ]);
+
+        let diff = RevisionDiff::new(from, from, &before, &after, DiffFormat::Cedar).unwrap();
+        let changes: Vec<(String, PolicyChange)> = diff
+            .policies
+            .iter()
+            .map(|p| (p.id.to_string(), p.change))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("added".to_string(), PolicyChange::Added),
+                ("changed".to_string(), PolicyChange::Modified),
+                ("removed".to_string(), PolicyChange::Removed),
+            ]
+        );
+
+        let changed = &diff.policies[1];
+        assert!(
+            changed
+                .before
+                .as_ref()
+                .unwrap()
+                .starts_with("@id(\"changed\")")
+        );
+        assert!(
+            changed
+                .lines
+                .iter()
+                .any(|l| l.kind == DiffLineKind::Added && l.text.contains("resource.shared"))
+        );
+        assert!(
+            changed
+                .lines
+                .iter()
+                .any(|l| l.kind == DiffLineKind::Removed && l.text.contains("resource.public"))
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::CedrusError;
+
+use super::{state::StatePlan, sync::EntitiesSyncReport};
+
+/// Changes a mutation would make to a project, validated but neither persisted nor
+/// published.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunReport {
+    #[serde(flatten)]
+    pub plan: StatePlan,
+    pub entities: EntitiesSyncReport,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub projects_changed: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub projects_removed: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_keys_changed: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_keys_removed: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub identity_source_changed: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub identity_source_removed: bool,
+}
+
+impl DryRunReport {
+    pub fn has_changes(&self) -> bool {
+        self.plan.has_changes()
+            || !self.entities.is_empty()
+            || !self.projects_changed.is_empty()
+            || !self.projects_removed.is_empty()
+            || !self.api_keys_changed.is_empty()
+            || !self.api_keys_removed.is_empty()
+            || self.identity_source_changed
+            || self.identity_source_removed
+    }
+}
+
+tokio::task_local! {
+    static DRY_RUN: Mutex<DryRunReport>;
+}
+
+/// Runs a mutation with its persistence disabled: it validates the change as it would when
+/// applied, then records what it would write in the report instead of writing it.
+pub async fn run<T>(
+    mutation: impl Future<Output = Result<T, CedrusError>>,
+) -> Result<DryRunReport, CedrusError> {
+    DRY_RUN
+        .scope(Mutex::new(DryRunReport::default()), async {
+            mutation.await?;
+            Ok(DRY_RUN.with(|report| std::mem::take(&mut *report.lock().unwrap())))
+        })
+        .await
+}
+
+/// Whether the current mutation runs dry.
+pub fn is_active() -> bool {
+    DRY_RUN.try_with(|_| ()).is_ok()
+}
+
+/// Records a change of the current mutation when it runs dry, returning whether it does,
+/// in which case the change must not be written.
+pub fn record(change: impl FnOnce(&mut DryRunReport)) -> bool {
+    DRY_RUN
+        .try_with(|report| change(&mut report.lock().unwrap()))
+        .is_ok()
+}
+
+/// Whether a side effect carried on by another task, such as a publish or a deferred write,
+/// may start. Those run outside the dry run, so reaching one from a mutation running dry is
+/// a bug: it fails debug builds, and the side effect is skipped otherwise.
+pub fn allows_side_effect(side_effect: &str) -> bool {
+    let active = is_active();
+    debug_assert!(!active, "{side_effect} reached by a dry run");
+    if active {
+        tracing::error!("cedrus: dry_run: {} skipped", side_effect);
+    }
+    !active
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run() {
+        assert!(!is_active());
+        assert!(!record(|report| report.plan.schema_changed = true));
+
+        let report = run(async {
+            assert!(is_active());
+            assert!(record(|report| report.plan.schema_changed = true));
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert!(report.plan.schema_changed);
+        assert!(report.has_changes());
+
+        assert!(matches!(
+            run(async { Err::<(), _>(CedrusError::Conflict) }).await,
+            Err(CedrusError::Conflict)
+        ));
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    #[should_panic(expected = "publish reached by a dry run")]
+    async fn test_side_effect_asserted() {
+        assert!(allows_side_effect("publish"));
+        let _ = run(async {
+            allows_side_effect("publish");
+            Ok(())
+        })
+        .await;
+    }
+}
@@ -0,0 +1,145 @@
+use cedrus_cedar::{PolicySet, Schema};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::CedrusError;
+
+use super::state::StatePlan;
+
+/// Longest name of an environment.
+pub const MAX_ENVIRONMENT_NAME_LEN: usize = 64;
+
+/// Environment a project is of, e.g. `dev`, `stage` or `prod`, with its own entities and
+/// policies and the schema of its parent project.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectEnvironment {
+    pub parent_id: Uuid,
+    pub name: String,
+}
+
+impl ProjectEnvironment {
+    /// Whether the name is non-empty, short, and made of ASCII letters, digits, `-` and `_`.
+    pub fn name_valid(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= MAX_ENVIRONMENT_NAME_LEN
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewEnvironment {
+    pub name: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Promotion {
+    /// Environment the policy set is copied to
+    pub to: String,
+    /// Policy version of the source environment that was tested, the promotion is
+    /// rejected with 409 when its policies changed since
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// Changes made to the policies, templates and template links of the target environment
+/// for them to match the source one.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PromotionReport {
+    pub from_project_id: Uuid,
+    pub to_project_id: Uuid,
+    /// Policy version promoted
+    pub version: String,
+    #[serde(flatten)]
+    pub plan: StatePlan,
+    pub promoted_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Validates a policy set against a schema in strict mode, as Cedar would before
+/// evaluating it, one message per error.
+pub fn validate(schema: Schema, policy_set: PolicySet) -> Result<(), CedrusError> {
+    let cedar_schema: cedar_policy::Schema = schema.try_into()?;
+    let cedar_policy_set: cedar_policy::PolicySet = policy_set.try_into()?;
+
+    let validator = cedar_policy::Validator::new(cedar_schema);
+    let result = validator.validate(&cedar_policy_set, cedar_policy::ValidationMode::Strict);
+    if result.validation_passed() {
+        return Ok(());
+    }
+
+    Err(CedrusError::ValidationError(
+        result
+            .validation_errors()
+            .map(|error| format!("{}: {}", error.policy_id(), error))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cedrus_cedar::{Policy, PolicyId};
+
+    use super::*;
+
+    fn policy_set(src: &str) -> PolicySet {
+        let policy = Policy::try_from(cedar_policy::Policy::parse(None, src).unwrap()).unwrap();
+        PolicySet {
+            static_policies: HashMap::from([(PolicyId::from("policy0".to_string()), policy)]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_environment_validate() {
+        let schema: Schema = serde_json::from_value(serde_json::json!({
+            "": {
+                "entityTypes": {
+                    "User": {},
+                    "Document": {
+                        "shape": {
+                            "type": "Record",
+                            "attributes": {"public": {"type": "Boolean"}}
+                        }
+                    }
+                },
+                "actions": {
+                    "view": {
+                        "appliesTo": {"principalTypes": ["User"], "resourceTypes": ["Document"]}
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        validate(
+            schema.clone(),
+            policy_set(
+                "permit(principal, action == Action::\"view\", resource) when { resource.public };",
+            ),
+        )
+        .unwrap();
+
+        let Err(CedrusError::ValidationError(errors)) = validate(
+            schema,
+            policy_set(
+                "permit(principal, action == Action::\"view\", resource) when { resource.owner == principal };",
+            ),
+        ) else {
+            panic!("expected a validation error");
+        };
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("policy0"));
+
+        assert!(ProjectEnvironment::name_valid("prod-eu_1"));
+        assert!(!ProjectEnvironment::name_valid(""));
+        assert!(!ProjectEnvironment::name_valid("prod/eu"));
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use dashmap::DashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Default)]
+struct Epoch {
+    started: AtomicU64,
+    finished: AtomicU64,
+}
+
+/// Ends a write of a project when dropped.
+#[derive(Debug)]
+pub struct EpochGuard {
+    epoch: Arc<Epoch>,
+}
+
+impl Drop for EpochGuard {
+    fn drop(&mut self) {
+        self.epoch.finished.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Write epochs of the projects. Every write advances the epoch of its project, so a
+/// reader that sees the same stable epoch before and after reading saw a single point in
+/// time.
+#[derive(Debug, Default)]
+pub struct ProjectEpochs {
+    epochs: DashMap<Uuid, Arc<Epoch>>,
+}
+
+impl ProjectEpochs {
+    fn epoch(&self, project_id: &Uuid) -> Arc<Epoch> {
+        self.epochs.entry(*project_id).or_default().clone()
+    }
+
+    /// Starts a write of the project, in flight until the guard is dropped.
+    pub fn begin(&self, project_id: &Uuid) -> EpochGuard {
+        let epoch = self.epoch(project_id);
+        epoch.started.fetch_add(1, Ordering::SeqCst);
+        EpochGuard { epoch }
+    }
+
+    /// Records a write of the project already done, by another node.
+    pub fn advance(&self, project_id: &Uuid) {
+        drop(self.begin(project_id));
+    }
+
+    /// Epoch of the project, `None` while one of its writes is in flight. A project never
+    /// written to is at epoch 0, without being tracked.
+    pub fn stable(&self, project_id: &Uuid) -> Option<u64> {
+        let Some(epoch) = self.epochs.get(project_id) else {
+            return Some(0);
+        };
+        let finished = epoch.finished.load(Ordering::SeqCst);
+        let started = epoch.started.load(Ordering::SeqCst);
+        (started == finished).then_some(started)
+    }
+
+    pub fn remove(&self, project_id: &Uuid) {
+        self.epochs.remove(project_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_epochs() {
+        let epochs = ProjectEpochs::default();
+        let project_id = Uuid::now_v7();
+
+        // Reading a project never written to tracks nothing
+        assert_eq!(epochs.stable(&project_id), Some(0));
+        assert!(epochs.epochs.is_empty());
+
+        let write = epochs.begin(&project_id);
+        assert_eq!(epochs.stable(&project_id), None);
+        drop(write);
+        assert_eq!(epochs.stable(&project_id), Some(1));
+
+        epochs.advance(&project_id);
+        assert_eq!(epochs.stable(&project_id), Some(2));
+
+        // Overlapping writes keep the project unstable until the last one ends
+        let first = epochs.begin(&project_id);
+        let second = epochs.begin(&project_id);
+        drop(first);
+        assert_eq!(epochs.stable(&project_id), None);
+        drop(second);
+        assert_eq!(epochs.stable(&project_id), Some(4));
+
+        epochs.remove(&project_id);
+        assert_eq!(epochs.stable(&project_id), Some(0));
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::{HashMap, HashSet};
+
+use cedrus_cedar::{
+    Context, Entity, EntityUid, EntityUidEscape, Request, Schema,
+    entity::EntityAttr,
+    schema::{Action, EntityType, TypeJson},
+};
+use rand::{RngExt, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Entities generated of each type when not told otherwise.
+pub const DEFAULT_ENTITIES_PER_TYPE: usize = 5;
+pub const MAX_ENTITIES_PER_TYPE: usize = 100;
+/// Requests generated when not told otherwise.
+pub const DEFAULT_GENERATED_REQUESTS: usize = 10;
+pub const MAX_GENERATED_REQUESTS: usize = 1000;
+
+// Nesting of records, sets and common types values stop at, for recursive common types
+const MAX_DEPTH: usize = 8;
+// Largest sets, and most tags of an entity
+const MAX_ELEMENTS: usize = 3;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneratorOptions {
+    /// Entities of each entity type, 5 by default and 100 at most. Enumerated types only
+    /// take their declared values.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entities_per_type: Option<usize>,
+    /// Requests made by the generated entities, 10 by default and 1000 at most
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests: Option<usize>,
+    /// Seed of the generator, the same seed and schema generating the same data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GeneratedData {
+    pub entities: Vec<Entity>,
+    pub requests: Vec<Request>,
+}
+
+/// Random entities and requests conforming to a schema, for tests and benchmarks: attributes
+/// of the declared types, required ones always and optional ones at random, parents of the
+/// `memberOfTypes` of their type, tags, and requests of the actions with their `appliesTo`
+/// types and context.
+pub struct DataGenerator<'a> {
+    schema: &'a Schema,
+    rng: StdRng,
+    uids: HashMap<String, Vec<EntityUid>>,
+}
+
+fn qualify(namespace: &str, name: &str) -> String {
+    match namespace.is_empty() || name.contains("::") {
+        true => name.to_string(),
+        false => format!("{namespace}::{name}"),
+    }
+}
+
+impl<'a> DataGenerator<'a> {
+    pub fn new(schema: &'a Schema, seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => rand::make_rng(),
+        };
+        Self {
+            schema,
+            rng,
+            uids: HashMap::new(),
+        }
+    }
+
+    /// Draws the entities of the requests, and those the generated entities refer to, among
+    /// these, e.g. the entities of a project.
+    pub fn with_entities(mut self, uids: impl IntoIterator<Item = EntityUid>) -> Self {
+        for uid in uids {
+            self.uids
+                .entry(uid.type_name().to_string())
+                .or_default()
+                .push(uid);
+        }
+        self
+    }
+
+    // Entity types of the schema by qualified name, in a stable order for seeded generators
+    fn entity_types(&self) -> Vec<(&'a str, String, &'a EntityType)> {
+        let mut types: Vec<_> = self
+            .schema
+            .0
+            .iter()
+            .flat_map(|(namespace, ns)| {
+                ns.entity_types.iter().map(move |(name, entity_type)| {
+                    (namespace.as_str(), qualify(namespace, name), entity_type)
+                })
+            })
+            .collect();
+        types.sort_by(|a, b| a.1.cmp(&b.1));
+        types
+    }
+
+    fn actions(&self) -> Vec<(&'a str, EntityUid, &'a Action)> {
+        let mut actions: Vec<_> = self
+            .schema
+            .0
+            .iter()
+            .flat_map(|(namespace, ns)| {
+                let action_type = qualify(namespace, "Action");
+                ns.actions.iter().map(move |(id, action)| {
+                    let uid = EntityUid::new(action_type.clone(), id.clone());
+                    (namespace.as_str(), uid, action)
+                })
+            })
+            .filter(|(_, _, action)| {
+                !action.principal_types().is_empty() && !action.resource_types().is_empty()
+            })
+            .collect();
+        actions.sort_by(|a, b| a.1.cmp(&b.1));
+        actions
+    }
+
+    fn pick<T: Clone>(&mut self, items: &[T]) -> Option<T> {
+        match items.is_empty() {
+            true => None,
+            false => Some(items[self.rng.random_range(0..items.len())].clone()),
+        }
+    }
+
+    // One of the entities of a type, or a made up one of a type without any
+    fn uid(&mut self, type_name: &str) -> EntityUid {
+        let uids = self.uids.get(type_name).cloned().unwrap_or_default();
+        self.pick(&uids)
+            .unwrap_or_else(|| EntityUid::new(type_name.to_string(), "generated".to_string()))
+    }
+
+    /// `per_type` entities of every entity type with their attributes, parents and tags, named
+    /// after their type, e.g. `App::User::"user-0"`.
+    pub fn entities(&mut self, per_type: usize) -> Vec<Entity> {
+        let types = self.entity_types();
+        for (_, name, entity_type) in &types {
+            let uids = match &entity_type.r#enum {
+                Some(values) => values
+                    .iter()
+                    .map(|value| EntityUid::new(name.clone(), value.clone()))
+                    .collect(),
+                None => {
+                    let prefix = name.rsplit("::").next().unwrap_or(name).to_lowercase();
+                    (0..per_type)
+                        .map(|index| EntityUid::new(name.clone(), format!("{prefix}-{index}")))
+                        .collect()
+                }
+            };
+            self.uids.insert(name.clone(), uids);
+        }
+
+        let mut entities = Vec::new();
+        for (namespace, name, entity_type) in types {
+            // Enumerated entities are implied by the schema
+            if entity_type.r#enum.is_some() {
+                continue;
+            }
+            let uids = self.uids.get(&name).cloned().unwrap_or_default();
+            for (index, uid) in uids.iter().enumerate() {
+                let mut parents = HashSet::new();
+                for member_type in entity_type.member_of_types.iter().flatten() {
+                    let member_type = qualify(namespace, member_type);
+                    if !self.rng.random_bool(0.5) {
+                        continue;
+                    }
+                    // Parents of the same type come first, keeping the hierarchy acyclic
+                    let candidates = match member_type == name {
+                        true => uids[..index].to_vec(),
+                        false => self.uids.get(&member_type).cloned().unwrap_or_default(),
+                    };
+                    if let Some(parent) = self.pick(&candidates) {
+                        parents.insert(parent);
+                    }
+                }
+
+                let attrs = match &entity_type.shape {
+                    Some(shape) => match self.schema.resolve(namespace, shape) {
+                        TypeJson::Record { attributes, .. } => {
+                            self.record(namespace, &attributes, 0)
+                        }
+                        _ => HashMap::new(),
+                    },
+                    None => HashMap::new(),
+                };
+
+                let mut tags = HashMap::new();
+                if let Some(tag_type) = &entity_type.tags {
+                    for tag in 0..self.rng.random_range(0..=MAX_ELEMENTS) {
+                        if let Some(value) = self.value(namespace, tag_type, 0) {
+                            tags.insert(format!("tag-{tag}"), value);
+                        }
+                    }
+                }
+
+                entities.push(Entity::new_with_tags(uid.clone(), attrs, parents, tags));
+            }
+        }
+        entities
+    }
+
+    /// `count` requests of actions at random, by entities of the types they apply to, with a
+    /// context of the declared type. None when no action declares what it applies to.
+    pub fn requests(&mut self, count: usize) -> Vec<Request> {
+        let actions = self.actions();
+        let mut requests = Vec::new();
+        for _ in 0..count {
+            let Some((namespace, action, declared)) = self.pick(&actions) else {
+                break;
+            };
+            let principal_type = self.pick(declared.principal_types()).unwrap_or_default();
+            let resource_type = self.pick(declared.resource_types()).unwrap_or_default();
+            let principal = self.uid(&qualify(namespace, &principal_type));
+            let resource = self.uid(&qualify(namespace, &resource_type));
+            let context = declared
+                .context()
+                .map(|context| match self.schema.resolve(namespace, context) {
+                    TypeJson::Record { attributes, .. } => self.record(namespace, &attributes, 0),
+                    _ => HashMap::new(),
+                })
+                .map(Context::from);
+
+            requests.push(Request {
+                principal,
+                action,
+                resource,
+                context,
+            });
+        }
+        requests
+    }
+
+    fn record(
+        &mut self,
+        namespace: &str,
+        attributes: &HashMap<String, TypeJson>,
+        depth: usize,
+    ) -> HashMap<String, EntityAttr> {
+        let mut names: Vec<&String> = attributes.keys().collect();
+        names.sort();
+
+        let mut record = HashMap::new();
+        for name in names {
+            let type_json = &attributes[name];
+            if !type_json.is_required() && !self.rng.random_bool(0.5) {
+                continue;
+            }
+            if let Some(value) = self.value(namespace, type_json, depth) {
+                record.insert(name.clone(), value);
+            }
+        }
+        record
+    }
+
+    fn value(&mut self, namespace: &str, type_json: &TypeJson, depth: usize) -> Option<EntityAttr> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let value = match type_json {
+            TypeJson::EntityOrCommon { .. } => {
+                let resolved = self.schema.resolve(namespace, type_json);
+                return self.value(namespace, &resolved, depth + 1);
+            }
+            TypeJson::Long { .. } => EntityAttr::Number(self.rng.random_range(0..100)),
+            TypeJson::String { .. } => {
+                EntityAttr::String(format!("value-{}", self.rng.random_range(0..1000)))
+            }
+            TypeJson::Boolean { .. } => EntityAttr::Boolean(self.rng.random_bool(0.5)),
+            TypeJson::Set { element, .. } => {
+                let len = self.rng.random_range(0..=MAX_ELEMENTS);
+                EntityAttr::Set(
+                    (0..len)
+                        .filter_map(|_| self.value(namespace, element, depth + 1))
+                        .collect(),
+                )
+            }
+            TypeJson::Entity { name, .. } => {
+                let uid = self.uid(&qualify(namespace, name));
+                EntityAttr::EntityUidEscape(EntityUidEscape::from(uid))
+            }
+            TypeJson::Record { attributes, .. } => {
+                EntityAttr::Record(self.record(namespace, attributes, depth + 1))
+            }
+            TypeJson::Extension { name, .. } => match name.as_str() {
+                "ipaddr" => {
+                    let host = self.rng.random_range(1..255);
+                    EntityAttr::extension("ip", format!("10.0.0.{host}"))
+                }
+                "decimal" => {
+                    let cents = self.rng.random_range(0..100_000);
+                    EntityAttr::extension("decimal", format!("{}.{:02}", cents / 100, cents % 100))
+                }
+                "datetime" => {
+                    let day = self.rng.random_range(1..=28);
+                    EntityAttr::extension("datetime", format!("2024-01-{day:02}"))
+                }
+                "duration" => {
+                    let hours = self.rng.random_range(1..48);
+                    EntityAttr::extension("duration", format!("{hours}h"))
+                }
+                _ => return None,
+            },
+        };
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Schema {
+        serde_json::from_value(serde_json::json!({
+            "App": {
+                "commonTypes": {
+                    "Address": {
+                        "type": "Record",
+                        "attributes": {
+                            "city": { "type": "String" },
+                            "zip": { "type": "Long", "required": false }
+                        }
+                    }
+                },
+                "entityTypes": {
+                    "User": {
+                        "memberOfTypes": ["Group"],
+                        "shape": {
+                            "type": "Record",
+                            "attributes": {
+                                "age": { "type": "Long" },
+                                "address": { "type": "EntityOrCommon", "name": "Address" },
+                                "manager": { "type": "Entity", "name": "User", "required": false },
+                                "roles": { "type": "Set", "element": { "type": "String" } },
+                                "ip": { "type": "Extension", "name": "ipaddr" }
+                            }
+                        },
+                        "tags": { "type": "String" }
+                    },
+                    "Group": { "memberOfTypes": ["Group"] },
+                    "Document": {
+                        "shape": {
+                            "type": "Record",
+                            "attributes": {
+                                "owner": { "type": "Entity", "name": "User" },
+                                "level": { "type": "Entity", "name": "Level" }
+                            }
+                        }
+                    },
+                    "Level": { "enum": ["public", "secret"] }
+                },
+                "actions": {
+                    "view": {
+                        "appliesTo": {
+                            "principalTypes": ["User"],
+                            "resourceTypes": ["Document"],
+                            "context": {
+                                "type": "Record",
+                                "attributes": {
+                                    "mfa": { "type": "Boolean" },
+                                    "since": { "type": "Extension", "name": "datetime" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generated_data_conforms() {
+        let schema = schema();
+        let cedar_schema: cedar_policy::Schema = schema.clone().try_into().unwrap();
+
+        let mut generator = DataGenerator::new(&schema, Some(7));
+        let entities = generator.entities(4);
+        let requests = generator.requests(20);
+        assert_eq!(entities.len(), 12);
+        assert_eq!(requests.len(), 20);
+
+        let cedar_entities = entities
+            .iter()
+            .map(|entity| entity.to_cedar_entity(Some(&cedar_schema)).unwrap())
+            .collect::<Vec<_>>();
+        cedar_policy::Entities::from_entities(cedar_entities, Some(&cedar_schema)).unwrap();
+
+        for request in requests {
+            let action: cedar_policy::EntityUid = request.action.clone().try_into().unwrap();
+            let context = request
+                .context
+                .unwrap()
+                .to_cedar_context(Some((&cedar_schema, &action)))
+                .unwrap();
+            cedar_policy::Request::new(
+                request.principal.try_into().unwrap(),
+                action,
+                request.resource.try_into().unwrap(),
+                context,
+                Some(&cedar_schema),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_generator_seed() {
+        let schema = schema();
+        let generate = |seed| {
+            let mut generator = DataGenerator::new(&schema, Some(seed));
+            let entities = generator.entities(3);
+            serde_json::to_value((entities, generator.requests(5))).unwrap()
+        };
+        assert_eq!(generate(1), generate(1));
+
+        // Requests are made by the given entities
+        let alice = EntityUid::new("App::User".to_string(), "alice".to_string());
+        let requests = DataGenerator::new(&schema, None)
+            .with_entities([alice.clone()])
+            .requests(3);
+        assert!(requests.iter().all(|request| request.principal == alice));
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use cedrus_cedar::{Policy, PolicyId, Template};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::CedrusError;
+
+use super::{
+    GitOpsConfig,
+    state::{ProjectState, StatePlan},
+};
+
+pub const GITOPS_SCHEMA_CEDAR: &str = "schema.cedarschema";
+pub const GITOPS_SCHEMA_JSON: &str = "schema.json";
+pub const GITOPS_POLICY_EXTENSION: &str = "cedar";
+
+/// Git repository holding the schema, policies and templates of a project, which is
+/// reconciled to match it.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GitOpsSource {
+    pub url: String,
+    pub branch: String,
+    /// Directory of the repository holding the project files, its root when empty
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
+    /// Interval in seconds between pulls of the repository, synced on request only when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll_interval: Option<u64>,
+}
+
+impl GitOpsSource {
+    /// Whether the source is an `https` or `ssh` repository on one of the allowed hosts, its
+    /// path relative and within the checkout.
+    pub fn is_valid(&self, conf: &GitOpsConfig) -> bool {
+        !self.branch.is_empty()
+            && !self.branch.starts_with('-')
+            && Path::new(&self.path)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+            && self.host().is_some_and(|host| {
+                conf.allowed_hosts
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(host))
+            })
+    }
+
+    // Host of an `https://` or `ssh://` URL, or of the `user@host:path` form of ssh
+    fn host(&self) -> Option<&str> {
+        let authority = match self.url.split_once("://") {
+            Some((scheme, rest)) => {
+                if !scheme.eq_ignore_ascii_case("https") && !scheme.eq_ignore_ascii_case("ssh") {
+                    return None;
+                }
+                rest.split('/').next()?
+            }
+            None => {
+                let (authority, path) = self.url.split_once(':')?;
+                if !authority.contains('@') || path.is_empty() {
+                    return None;
+                }
+                authority
+            }
+        };
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        let host = host.split(':').next()?;
+        let valid = !host.is_empty()
+            && !host.starts_with('-')
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+        valid.then_some(host)
+    }
+}
+
+/// Differences between a project and its Git repository, applied when `applied` is set.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GitOpsReport {
+    pub project_id: Uuid,
+    pub commit: String,
+    #[serde(flatten)]
+    pub plan: StatePlan,
+    pub applied: bool,
+    pub synced_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl GitOpsReport {
+    pub fn has_drift(&self) -> bool {
+        self.plan.has_changes()
+    }
+}
+
+/// Schema, policies and templates read from a checkout of a repository.
+#[derive(Debug, Default)]
+pub struct GitOpsState {
+    pub commit: String,
+    pub state: ProjectState,
+}
+
+fn gitops_error(message: impl std::fmt::Display) -> CedrusError {
+    CedrusError::GitOpsError(message.to_string())
+}
+
+// Transports of the allowed URLs only, without following redirects to other hosts
+const GIT_OPTIONS: [&str; 8] = [
+    "-c",
+    "protocol.allow=never",
+    "-c",
+    "protocol.https.allow=always",
+    "-c",
+    "protocol.ssh.allow=always",
+    "-c",
+    "http.followRedirects=false",
+];
+
+async fn git(args: &[&str], timeout: Duration) -> Result<String, CedrusError> {
+    let output = Command::new("git")
+        .args(GIT_OPTIONS)
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(timeout, output)
+        .await
+        .map_err(|_| gitops_error("git timed out"))?
+        .map_err(gitops_error)?;
+    if !output.status.success() {
+        return Err(gitops_error(String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Checkout of the repository of a project, kept between syncs.
+fn checkout_dir(project_id: &Uuid) -> PathBuf {
+    std::env::temp_dir()
+        .join("cedrus-gitops")
+        .join(project_id.to_string())
+}
+
+/// Pulls the branch of the source into the checkout of the project, returning the commit.
+async fn pull(
+    project_id: &Uuid,
+    source: &GitOpsSource,
+    timeout: Duration,
+) -> Result<PathBuf, CedrusError> {
+    let dir = checkout_dir(project_id);
+    let dir_str = dir.to_string_lossy().to_string();
+
+    if dir.join(".git").is_dir() {
+        git(
+            &[
+                "-C",
+                &dir_str,
+                "remote",
+                "set-url",
+                "origin",
+                "--",
+                &source.url,
+            ],
+            timeout,
+        )
+        .await?;
+        git(
+            &[
+                "-C",
+                &dir_str,
+                "fetch",
+                "--depth",
+                "1",
+                "origin",
+                "--",
+                &source.branch,
+            ],
+            timeout,
+        )
+        .await?;
+        git(
+            &["-C", &dir_str, "checkout", "--force", "FETCH_HEAD"],
+            timeout,
+        )
+        .await?;
+    } else {
+        if let Some(parent) = dir.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(gitops_error)?;
+        }
+        git(
+            &[
+                "clone",
+                "--depth",
+                "1",
+                "--branch",
+                &source.branch,
+                "--",
+                &source.url,
+                &dir_str,
+            ],
+            timeout,
+        )
+        .await?;
+    }
+
+    Ok(dir)
+}
+
+// Symbolic links of the repository could point anywhere on the host, they are refused
+fn no_symlink(path: &Path) -> Result<(), CedrusError> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => Err(gitops_error(format!(
+            "{}: symbolic links are not followed",
+            path.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn policy_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), CedrusError> {
+    for entry in std::fs::read_dir(dir).map_err(gitops_error)? {
+        let path = entry.map_err(gitops_error)?.path();
+        no_symlink(&path)?;
+        if path.is_dir() {
+            if path.file_name().is_some_and(|name| name != ".git") {
+                policy_files(&path, files)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == GITOPS_POLICY_EXTENSION)
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// Id of a policy: its `@id` annotation, or its position in its file
+fn policy_id(
+    annotation: Option<&str>,
+    root: &Path,
+    file: &Path,
+    position: &cedar_policy::PolicyId,
+) -> PolicyId {
+    if let Some(id) = annotation {
+        return PolicyId::from(id.to_string());
+    }
+    let name = file
+        .strip_prefix(root)
+        .unwrap_or(file)
+        .with_extension("")
+        .to_string_lossy()
+        .replace(std::path::MAIN_SEPARATOR, "/");
+    PolicyId::from(format!("{name}/{position}"))
+}
+
+fn read_state(root: &Path, commit: String) -> Result<GitOpsState, CedrusError> {
+    let mut state = ProjectState::default();
+
+    let cedar_schema = root.join(GITOPS_SCHEMA_CEDAR);
+    let json_schema = root.join(GITOPS_SCHEMA_JSON);
+    no_symlink(&cedar_schema)?;
+    no_symlink(&json_schema)?;
+    if cedar_schema.is_file() {
+        let src = std::fs::read_to_string(&cedar_schema).map_err(gitops_error)?;
+        let (fragment, _) =
+            cedar_policy::SchemaFragment::from_cedarschema_str(&src).map_err(gitops_error)?;
+        let json = fragment.to_json_value().map_err(gitops_error)?;
+        state.schema = Some(serde_json::from_value(json)?);
+    } else if json_schema.is_file() {
+        let src = std::fs::read_to_string(&json_schema).map_err(gitops_error)?;
+        state.schema = Some(serde_json::from_str(&src)?);
+    }
+
+    let mut files = Vec::new();
+    policy_files(root, &mut files)?;
+    files.sort();
+    for file in files {
+        let src = std::fs::read_to_string(&file).map_err(gitops_error)?;
+        let policy_set = cedar_policy::PolicySet::from_str(&src)
+            .map_err(|e| gitops_error(format!("{}: {}", file.display(), e)))?;
+
+        for policy in policy_set.policies() {
+            let id = policy_id(policy.annotation("id"), root, &file, policy.id());
+            let policy = Policy::try_from(policy.clone())?;
+            if state.policies.insert(id.clone(), policy).is_some() {
+                return Err(gitops_error(format!("duplicate policy id {id}")));
+            }
+        }
+        for template in policy_set.templates() {
+            let id = policy_id(template.annotation("id"), root, &file, template.id());
+            let template = Template::try_from(template.clone())?;
+            if state.templates.insert(id.clone(), template).is_some() {
+                return Err(gitops_error(format!("duplicate template id {id}")));
+            }
+        }
+    }
+
+    Ok(GitOpsState { commit, state })
+}
+
+/// Pulls the repository of a project and reads the state it describes.
+pub async fn load(
+    project_id: &Uuid,
+    source: &GitOpsSource,
+    conf: &GitOpsConfig,
+) -> Result<GitOpsState, CedrusError> {
+    if !source.is_valid(conf) {
+        return Err(CedrusError::BadRequest);
+    }
+
+    let timeout = Duration::from_secs(conf.timeout);
+    let dir = pull(project_id, source, timeout).await?;
+    let commit = git(
+        &["-C", &dir.to_string_lossy(), "rev-parse", "HEAD"],
+        timeout,
+    )
+    .await?;
+
+    // The directories of the path may be links out of the checkout too
+    let root = dir.join(&source.path);
+    let canonical_dir = dir.canonicalize().map_err(gitops_error)?;
+    let canonical_root = root.canonicalize().map_err(gitops_error)?;
+    if !canonical_root.starts_with(&canonical_dir) {
+        return Err(gitops_error(format!(
+            "{}: outside the checkout",
+            source.path
+        )));
+    }
+
+    tokio::task::spawn_blocking(move || read_state(&root, commit))
+        .await
+        .map_err(gitops_error)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_state() {
+        let root = std::env::temp_dir().join(format!("cedrus-gitops-test-{}", Uuid::now_v7()));
+        std::fs::create_dir_all(root.join("admin")).unwrap();
+        std::fs::write(
+            root.join(GITOPS_SCHEMA_CEDAR),
+            "entity User;\nentity Doc;\naction view appliesTo { principal: User, resource: Doc };",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("admin").join("docs.cedar"),
+            "@id(\"view\")\npermit(principal, action, resource);\n\
+             forbid(principal == User::\"bob\", action, resource);\n\
+             @id(\"owner\")\npermit(principal == ?principal, action, resource);",
+        )
+        .unwrap();
+
+        let state = read_state(&root, "commit".to_string()).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let state = state.state;
+        assert!(state.schema.is_some());
+        assert!(
+            state
+                .policies
+                .contains_key(&PolicyId::from("view".to_string()))
+        );
+        assert!(
+            state
+                .policies
+                .contains_key(&PolicyId::from("admin/docs/policy1".to_string()))
+        );
+        assert!(
+            state
+                .templates
+                .contains_key(&PolicyId::from("owner".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_read_state_symlink() {
+        let root = std::env::temp_dir().join(format!("cedrus-gitops-test-{}", Uuid::now_v7()));
+        std::fs::create_dir_all(&root).unwrap();
+        let outside = root.with_extension("secret");
+        std::fs::write(&outside, "permit(principal, action, resource);").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("secret.cedar")).unwrap();
+
+        let state = read_state(&root, "commit".to_string());
+        std::fs::remove_dir_all(&root).unwrap();
+        std::fs::remove_file(&outside).unwrap();
+        assert!(matches!(state, Err(CedrusError::GitOpsError(_))));
+    }
+
+    #[test]
+    fn test_source_is_valid() {
+        let conf = GitOpsConfig {
+            allowed_hosts: vec!["git.example.com".to_string()],
+            ..Default::default()
+        };
+        let source = GitOpsSource {
+            url: "https://git.example.com/policies.git".to_string(),
+            branch: "main".to_string(),
+            path: "projects/docs".to_string(),
+            ..Default::default()
+        };
+        assert!(source.is_valid(&conf));
+        assert!(!source.is_valid(&GitOpsConfig::default()));
+        for url in [
+            "ssh://git@git.example.com:22/policies.git",
+            "git@git.example.com:policies.git",
+        ] {
+            let source = GitOpsSource {
+                url: url.to_string(),
+                ..source.clone()
+            };
+            assert!(source.is_valid(&conf), "{url}");
+        }
+
+        // Other schemes and hosts, e.g. local repositories or internal services
+        for url in [
+            "file:///etc",
+            "/srv/repositories/policies.git",
+            "http://git.example.com/policies.git",
+            "ext::sh -c touch% /tmp/pwned",
+            "https://169.254.169.254/latest",
+            "https://git.example.com.evil.com/policies.git",
+            "https://user@evil.com/git.example.com/policies.git",
+        ] {
+            let source = GitOpsSource {
+                url: url.to_string(),
+                ..source.clone()
+            };
+            assert!(!source.is_valid(&conf), "{url}");
+        }
+
+        assert!(
+            !GitOpsSource {
+                branch: "--upload-pack=sh".to_string(),
+                ..source.clone()
+            }
+            .is_valid(&conf)
+        );
+        for path in ["../other", "/etc", "docs/../../other"] {
+            let source = GitOpsSource {
+                path: path.to_string(),
+                ..source.clone()
+            };
+            assert!(!source.is_valid(&conf), "{path}");
+        }
+    }
+}
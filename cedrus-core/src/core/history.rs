@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use cedrus_cedar::{Entity, EntityUid, Policy, PolicyId, Template, TemplateLink};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RevisionKind {
+    Entity,
+    Policy,
+    Template,
+    TemplateLink,
+    /// Guardrail of the admin project, recorded in the history of every other project
+    Guardrail,
+}
+
+impl RevisionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevisionKind::Entity => "entity",
+            RevisionKind::Policy => "policy",
+            RevisionKind::Template => "template",
+            RevisionKind::TemplateLink => "templateLink",
+            RevisionKind::Guardrail => "guardrail",
+        }
+    }
+}
+
+/// Content of an entity, a policy, a template, a template link or a guardrail of a project
+/// from `at` on, `None` once removed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    pub kind: RevisionKind,
+    /// Entity uid, or id of the policy, template, template link or guardrail
+    pub id: String,
+    pub at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+impl Revision {
+    pub fn new(kind: RevisionKind, id: String, at: DateTime<Utc>, value: Option<Value>) -> Self {
+        Self {
+            kind,
+            id,
+            at,
+            value,
+        }
+    }
+
+    pub fn entities(
+        entities: &[Entity],
+        at: DateTime<Utc>,
+    ) -> Result<Vec<Self>, serde_json::Error> {
+        entities
+            .iter()
+            .map(|entity| {
+                Ok(Self {
+                    kind: RevisionKind::Entity,
+                    id: entity.uid().to_string(),
+                    at,
+                    value: Some(serde_json::to_value(entity)?),
+                })
+            })
+            .collect()
+    }
+
+    pub fn entities_removed(entity_uids: &[EntityUid], at: DateTime<Utc>) -> Vec<Self> {
+        entity_uids
+            .iter()
+            .map(|uid| Self {
+                kind: RevisionKind::Entity,
+                id: uid.to_string(),
+                at,
+                value: None,
+            })
+            .collect()
+    }
+
+    pub fn policies<'a>(
+        policies: impl IntoIterator<Item = (&'a PolicyId, &'a Policy)>,
+        at: DateTime<Utc>,
+    ) -> Result<Vec<Self>, serde_json::Error> {
+        policies
+            .into_iter()
+            .map(|(policy_id, policy)| {
+                Ok(Self {
+                    kind: RevisionKind::Policy,
+                    id: policy_id.to_string(),
+                    at,
+                    value: Some(serde_json::to_value(policy)?),
+                })
+            })
+            .collect()
+    }
+
+    pub fn policies_removed(policy_ids: &[PolicyId], at: DateTime<Utc>) -> Vec<Self> {
+        policy_ids
+            .iter()
+            .map(|policy_id| Self {
+                kind: RevisionKind::Policy,
+                id: policy_id.to_string(),
+                at,
+                value: None,
+            })
+            .collect()
+    }
+
+    pub fn templates<'a>(
+        templates: impl IntoIterator<Item = (&'a PolicyId, &'a Template)>,
+        at: DateTime<Utc>,
+    ) -> Result<Vec<Self>, serde_json::Error> {
+        templates
+            .into_iter()
+            .map(|(template_id, template)| {
+                let value = serde_json::to_value(template)?;
+                Ok(Self::new(
+                    RevisionKind::Template,
+                    template_id.to_string(),
+                    at,
+                    Some(value),
+                ))
+            })
+            .collect()
+    }
+
+    pub fn template_links(
+        template_links: &[TemplateLink],
+        at: DateTime<Utc>,
+    ) -> Result<Vec<Self>, serde_json::Error> {
+        template_links
+            .iter()
+            .map(|link| {
+                let value = serde_json::to_value(link)?;
+                Ok(Self::new(
+                    RevisionKind::TemplateLink,
+                    link.new_id.to_string(),
+                    at,
+                    Some(value),
+                ))
+            })
+            .collect()
+    }
+
+    /// Revisions of the given kind removing ids.
+    pub fn removed(kind: RevisionKind, ids: &[PolicyId], at: DateTime<Utc>) -> Vec<Self> {
+        ids.iter()
+            .map(|id| Self::new(kind, id.to_string(), at, None))
+            .collect()
+    }
+
+    /// Replays revisions up to `as_of`, returning the content of each id still present
+    /// then, keyed by id.
+    pub fn replay(mut revisions: Vec<Self>, as_of: DateTime<Utc>) -> BTreeMap<String, Value> {
+        revisions.retain(|revision| revision.at <= as_of);
+        revisions.sort_by_key(|revision| revision.at);
+
+        let mut state = BTreeMap::new();
+        for revision in revisions {
+            match revision.value {
+                Some(value) => state.insert(revision.id, value),
+                None => state.remove(&revision.id),
+            };
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use serde_json::json;
+
+    use super::*;
+
+    fn revision(id: &str, at: DateTime<Utc>, value: Option<Value>) -> Revision {
+        Revision {
+            kind: RevisionKind::Policy,
+            id: id.to_string(),
+            at,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let t0 = Utc::now();
+        let t1 = t0 + TimeDelta::seconds(1);
+        let t2 = t0 + TimeDelta::seconds(2);
+        let revisions = vec![
+            revision("p2", t1, None),
+            revision("p1", t0, Some(json!(1))),
+            revision("p2", t0, Some(json!(2))),
+            revision("p1", t2, Some(json!(3))),
+        ];
+
+        let state = Revision::replay(revisions.clone(), t0);
+        assert_eq!(
+            state,
+            BTreeMap::from([("p1".into(), json!(1)), ("p2".into(), json!(2))])
+        );
+
+        let state = Revision::replay(revisions.clone(), t1);
+        assert_eq!(state, BTreeMap::from([("p1".into(), json!(1))]));
+
+        let state = Revision::replay(revisions, t2);
+        assert_eq!(state, BTreeMap::from([("p1".into(), json!(3))]));
+    }
+
+    #[test]
+    fn test_replay_template_links() {
+        let t0 = Utc::now();
+        let t1 = t0 + TimeDelta::seconds(1);
+        let template_id = PolicyId::from("role".to_string());
+        let link = TemplateLink::new(
+            template_id.clone(),
+            PolicyId::from("role:alice".to_string()),
+            Default::default(),
+        );
+
+        let mut revisions =
+            Revision::templates([(&template_id, &Template::default())], t0).unwrap();
+        revisions.extend(Revision::template_links(std::slice::from_ref(&link), t0).unwrap());
+        revisions.extend(Revision::removed(
+            RevisionKind::TemplateLink,
+            std::slice::from_ref(&link.new_id),
+            t1,
+        ));
+        let links = |revisions: &[Revision], at| {
+            let links: Vec<Revision> = revisions
+                .iter()
+                .filter(|revision| revision.kind == RevisionKind::TemplateLink)
+                .cloned()
+                .collect();
+            Revision::replay(links, at)
+        };
+
+        assert_eq!(
+            links(&revisions, t0),
+            BTreeMap::from([("role:alice".into(), serde_json::to_value(&link).unwrap())])
+        );
+        assert!(links(&revisions, t1).is_empty());
+        assert_eq!(revisions[0].kind, RevisionKind::Template);
+        assert_eq!(revisions[0].id, "role");
+    }
+}
@@ -0,0 +1,370 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+use dashmap::DashMap;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+use crate::CedrusError;
+
+use super::EvaluationLimitConfig;
+
+#[derive(Debug)]
+struct ProjectSlots {
+    permits: Arc<Semaphore>,
+    waiting: AtomicUsize,
+}
+
+// Counts a waiting evaluation until it gets its permit or its request is dropped
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    // Counts the evaluation as waiting, unless `max` evaluations already wait
+    fn enter(waiting: &'a AtomicUsize, max: Option<usize>) -> Option<Self> {
+        waiting
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| match max {
+                Some(max) if count >= max => None,
+                _ => Some(count + 1),
+            })
+            .ok()?;
+        Some(Self(waiting))
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Abandoned evaluations of a project past which its next ones are refused.
+const DEFAULT_MAX_ABANDONED: usize = 4;
+
+const RUNNING: u8 = 0;
+const COMPLETED: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// Evaluation running on a blocking thread, counted by each of its projects as abandoned from
+/// the time its caller gives up on it until it completes.
+#[derive(Debug)]
+pub struct TrackedEvaluation {
+    abandoned: Vec<Arc<AtomicUsize>>,
+    state: AtomicU8,
+}
+
+impl TrackedEvaluation {
+    /// Gives up on the evaluation, unless it already completed.
+    pub fn abandon(&self) {
+        // Counted first, so completing right after never sees a count it wasn't added to
+        for abandoned in &self.abandoned {
+            abandoned.fetch_add(1, Ordering::AcqRel);
+        }
+        if self
+            .state
+            .compare_exchange(RUNNING, ABANDONED, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            self.uncount();
+        }
+    }
+
+    fn complete(&self) {
+        if self
+            .state
+            .compare_exchange(RUNNING, COMPLETED, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            self.uncount();
+        }
+    }
+
+    fn uncount(&self) {
+        for abandoned in &self.abandoned {
+            abandoned.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Completes the evaluation once dropped by its thread, even when it panicked.
+    pub fn running(self: &Arc<Self>) -> RunningEvaluation {
+        RunningEvaluation(self.clone())
+    }
+}
+
+pub struct RunningEvaluation(Arc<TrackedEvaluation>);
+
+impl Drop for RunningEvaluation {
+    fn drop(&mut self) {
+        self.0.complete();
+    }
+}
+
+/// Evaluation slots of each project, so one tenant sending large batches only waits on its
+/// own slots while the others keep being evaluated. Without a limit, as by default, every
+/// evaluation runs at once. Cedar can't interrupt an evaluation, so those given up on after
+/// their timeout are counted until they complete, and past `max_abandoned` of them the
+/// project is refused new evaluations rather than taking every blocking thread.
+#[derive(Debug)]
+pub struct EvaluationSlots {
+    max_concurrency: Option<usize>,
+    max_queue: Option<usize>,
+    max_abandoned: usize,
+    projects: DashMap<Uuid, Arc<ProjectSlots>>,
+    abandoned: DashMap<Uuid, Arc<AtomicUsize>>,
+}
+
+impl Default for EvaluationSlots {
+    fn default() -> Self {
+        Self::new(&EvaluationLimitConfig::default())
+    }
+}
+
+impl EvaluationSlots {
+    pub fn new(conf: &EvaluationLimitConfig) -> Self {
+        Self {
+            max_concurrency: conf.max_concurrency.map(|max| max.max(1)),
+            max_queue: conf.max_queue,
+            max_abandoned: conf.max_abandoned.unwrap_or(DEFAULT_MAX_ABANDONED),
+            projects: DashMap::new(),
+            abandoned: DashMap::new(),
+        }
+    }
+
+    /// Evaluations of the project given up on and still running.
+    pub fn abandoned(&self, project_id: &Uuid) -> usize {
+        self.abandoned
+            .get(project_id)
+            .map_or(0, |abandoned| abandoned.load(Ordering::Acquire))
+    }
+
+    /// Tracks an evaluation of the projects its caller may give up on.
+    pub fn track(&self, project_ids: &[Uuid]) -> Arc<TrackedEvaluation> {
+        let abandoned = project_ids
+            .iter()
+            .map(|project_id| self.abandoned.entry(*project_id).or_default().clone())
+            .collect();
+        Arc::new(TrackedEvaluation {
+            abandoned,
+            state: AtomicU8::new(RUNNING),
+        })
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.max_concurrency.is_some()
+    }
+
+    fn slots(&self, project_id: &Uuid, max_concurrency: usize) -> Arc<ProjectSlots> {
+        self.projects
+            .entry(*project_id)
+            .or_insert_with(|| {
+                Arc::new(ProjectSlots {
+                    permits: Arc::new(Semaphore::new(max_concurrency)),
+                    waiting: AtomicUsize::new(0),
+                })
+            })
+            .clone()
+    }
+
+    /// Waits for a slot of each project, in a fixed order so evaluations of several projects
+    /// can't hold each other's slots. Past `max_queue` waiting evaluations, or
+    /// `max_abandoned` abandoned ones, of a project it gives up with `Overloaded`.
+    pub async fn acquire(
+        &self,
+        project_ids: &[Uuid],
+    ) -> Result<Vec<OwnedSemaphorePermit>, CedrusError> {
+        if project_ids
+            .iter()
+            .any(|project_id| self.abandoned(project_id) >= self.max_abandoned)
+        {
+            return Err(CedrusError::Overloaded);
+        }
+        let Some(max_concurrency) = self.max_concurrency else {
+            return Ok(Vec::new());
+        };
+        let mut project_ids = project_ids.to_vec();
+        project_ids.sort();
+        project_ids.dedup();
+
+        let mut permits = Vec::with_capacity(project_ids.len());
+        for project_id in &project_ids {
+            let slots = self.slots(project_id, max_concurrency);
+            if let Ok(permit) = slots.permits.clone().try_acquire_owned() {
+                permits.push(permit);
+                continue;
+            }
+            let Some(_waiting) = Waiting::enter(&slots.waiting, self.max_queue) else {
+                return Err(CedrusError::Overloaded);
+            };
+            let permit = slots
+                .permits
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|_| CedrusError::Overloaded)?;
+            permits.push(permit);
+        }
+        Ok(permits)
+    }
+
+    /// Takes a slot of each project when one is free right away, `None` otherwise, for
+    /// evaluations better dropped than waited for.
+    pub fn try_acquire(&self, project_ids: &[Uuid]) -> Option<Vec<OwnedSemaphorePermit>> {
+        let Some(max_concurrency) = self.max_concurrency else {
+            return Some(Vec::new());
+        };
+        let mut project_ids = project_ids.to_vec();
+        project_ids.sort();
+        project_ids.dedup();
+
+        project_ids
+            .iter()
+            .map(|project_id| {
+                self.slots(project_id, max_concurrency)
+                    .permits
+                    .clone()
+                    .try_acquire_owned()
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Takes up to `count` more slots of the project among those free right away, so a batch
+    /// runs on as many threads as it holds slots.
+    pub fn try_acquire_more(&self, project_id: &Uuid, count: usize) -> Vec<OwnedSemaphorePermit> {
+        let Some(max_concurrency) = self.max_concurrency else {
+            return Vec::new();
+        };
+        let slots = self.slots(project_id, max_concurrency);
+        std::iter::from_fn(|| slots.permits.clone().try_acquire_owned().ok())
+            .take(count)
+            .collect()
+    }
+
+    /// Evaluations of each project waiting for a slot.
+    pub fn queue_depths(&self) -> Vec<(Uuid, usize)> {
+        self.projects
+            .iter()
+            .map(|slots| (*slots.key(), slots.waiting.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    pub fn remove(&self, project_id: &Uuid) {
+        self.projects.remove(project_id);
+        self.abandoned.remove(project_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_evaluation_slots() {
+        let slots = EvaluationSlots::new(&EvaluationLimitConfig {
+            max_concurrency: Some(1),
+            max_queue: Some(0),
+            max_abandoned: None,
+        });
+        let busy = Uuid::now_v7();
+        let other = Uuid::now_v7();
+
+        let permits = slots.acquire(&[busy]).await.unwrap();
+        assert_eq!(permits.len(), 1);
+
+        // The busy project has no slot left nor room to wait, the other one is unaffected
+        assert!(matches!(
+            slots.acquire(&[busy]).await,
+            Err(CedrusError::Overloaded)
+        ));
+        assert_eq!(slots.acquire(&[other, other]).await.unwrap().len(), 1);
+
+        drop(permits);
+        assert_eq!(slots.acquire(&[busy, other]).await.unwrap().len(), 2);
+        assert!(
+            slots
+                .queue_depths()
+                .iter()
+                .all(|(_, waiting)| *waiting == 0)
+        );
+
+        let unbounded = EvaluationSlots::default();
+        assert!(unbounded.acquire(&[busy]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_evaluations() {
+        let slots = EvaluationSlots::new(&EvaluationLimitConfig {
+            max_abandoned: Some(1),
+            ..Default::default()
+        });
+        let project_id = Uuid::now_v7();
+
+        // Completed before its caller gives up, it is not counted
+        let completed = slots.track(&[project_id]);
+        drop(completed.running());
+        completed.abandon();
+        assert_eq!(slots.abandoned(&project_id), 0);
+
+        let stalled = slots.track(&[project_id]);
+        let running = stalled.running();
+        stalled.abandon();
+        assert_eq!(slots.abandoned(&project_id), 1);
+        assert!(matches!(
+            slots.acquire(&[project_id]).await,
+            Err(CedrusError::Overloaded)
+        ));
+        assert!(slots.acquire(&[Uuid::now_v7()]).await.is_ok());
+
+        drop(running);
+        assert_eq!(slots.abandoned(&project_id), 0);
+        assert!(slots.acquire(&[project_id]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_try_acquire_more() {
+        let slots = EvaluationSlots::new(&EvaluationLimitConfig {
+            max_concurrency: Some(3),
+            max_queue: None,
+            max_abandoned: None,
+        });
+        let project_id = Uuid::now_v7();
+
+        let permits = slots.acquire(&[project_id]).await.unwrap();
+        let more = slots.try_acquire_more(&project_id, 8);
+        assert_eq!(permits.len() + more.len(), 3);
+        assert!(slots.try_acquire_more(&project_id, 1).is_empty());
+
+        drop(more);
+        assert_eq!(slots.try_acquire_more(&project_id, 1).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_queue() {
+        let slots = Arc::new(EvaluationSlots::new(&EvaluationLimitConfig {
+            max_concurrency: Some(1),
+            max_queue: Some(1),
+            max_abandoned: None,
+        }));
+        let project_id = Uuid::now_v7();
+        let permits = slots.acquire(&[project_id]).await.unwrap();
+
+        // A single evaluation waits, whichever of the concurrent ones counts itself first
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let slots = slots.clone();
+                tokio::spawn(async move { slots.acquire(&[project_id]).await.map(|_| ()) })
+            })
+            .collect();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        drop(permits);
+
+        let mut overloaded = 0;
+        for waiter in waiters {
+            if let Err(CedrusError::Overloaded) = waiter.await.unwrap() {
+                overloaded += 1;
+            }
+        }
+        assert_eq!(overloaded, 3);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use cedrus_cedar::{Entity, Policy, PolicyId, Schema, Template, TemplateLink};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Number of items written per step of an import or cleanup job.
+pub const JOB_CHUNK_SIZE: usize = 100;
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    #[default]
+    Import,
+    Export,
+    Cleanup,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    #[default]
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Schema, entities and policies of a project, imported or exported by a job.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Schema>,
+    pub entities: Vec<Entity>,
+    pub policies: HashMap<PolicyId, Policy>,
+    pub templates: HashMap<PolicyId, Template>,
+    pub template_links: Vec<TemplateLink>,
+}
+
+impl ProjectData {
+    pub fn len(&self) -> usize {
+        usize::from(self.schema.is_some())
+            + self.entities.len()
+            + self.policies.len()
+            + self.templates.len()
+            + self.template_links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Collections of a project removed by a cleanup job.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectCleanup {
+    pub entities: bool,
+    pub policies: bool,
+    pub templates: bool,
+    pub template_links: bool,
+}
+
+/// Work of a job, kept until the job finished so another node can take it over.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobTask {
+    Import(ProjectData),
+    Export,
+    Cleanup(ProjectCleanup),
+}
+
+impl JobTask {
+    pub fn kind(&self) -> JobKind {
+        match self {
+            JobTask::Import(_) => JobKind::Import,
+            JobTask::Export => JobKind::Export,
+            JobTask::Cleanup(_) => JobKind::Cleanup,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Job {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub total: usize,
+    pub processed: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ProjectData>,
+    /// Node running the job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<Uuid>,
+    /// Renewed by the node running the job, another node takes the job over once past it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Times the job was started
+    pub attempts: u32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Job {
+    pub fn new(project_id: Uuid, kind: JobKind) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::now_v7(),
+            project_id,
+            kind,
+            status: JobStatus::Pending,
+            created_at: now,
+            updated_at: now,
+            ..Default::default()
+        }
+    }
+
+    /// Assigns the job to `node_id` for `lease`, as one more attempt.
+    pub fn claim(&mut self, node_id: Uuid, lease: chrono::Duration) {
+        self.node_id = Some(node_id);
+        self.attempts += 1;
+        self.renew(lease);
+    }
+
+    pub fn renew(&mut self, lease: chrono::Duration) {
+        let now = chrono::Utc::now();
+        self.lease_expires_at = Some(now + lease);
+        self.updated_at = now;
+    }
+
+    /// Whether the node running the job stopped renewing its lease before it finished.
+    pub fn is_lease_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        !self.is_finished()
+            && self
+                .lease_expires_at
+                .is_none_or(|expires_at| expires_at <= now)
+    }
+
+    pub fn start(&mut self, total: usize) {
+        self.status = JobStatus::Running;
+        self.total = total;
+        // Taken over, the job starts again from the beginning
+        self.processed = 0;
+        self.updated_at = chrono::Utc::now();
+    }
+
+    pub fn progress(&mut self, processed: usize) {
+        self.processed = (self.processed + processed).min(self.total);
+        self.updated_at = chrono::Utc::now();
+    }
+
+    pub fn succeed(&mut self, result: Option<ProjectData>) {
+        self.status = JobStatus::Succeeded;
+        self.processed = self.total;
+        self.result = result;
+        self.lease_expires_at = None;
+        self.updated_at = chrono::Utc::now();
+    }
+
+    pub fn fail(&mut self, error: String) {
+        self.status = JobStatus::Failed;
+        self.error = Some(error);
+        self.lease_expires_at = None;
+        self.updated_at = chrono::Utc::now();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use cedrus_cedar::Schema;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum SchemaLintSeverity {
+    /// The schema does not build
+    Error,
+    /// The schema builds but part of it can never be used
+    Warning,
+    /// The schema is usable but may be missing something
+    Info,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum SchemaLintRule {
+    /// Cedar rejects the schema
+    InvalidSchema,
+    /// An `appliesTo` or `memberOfTypes` names an entity type no namespace declares
+    UndeclaredEntityType,
+    /// An action without principal or resource types, no request can match it
+    EmptyAppliesTo,
+    /// An entity type no action applies to, neither directly nor as an ancestor
+    UnusedEntityType,
+    /// Entity types that are members of each other through `memberOfTypes`
+    MemberOfTypesCycle,
+    /// A common type nothing refers to
+    UnusedCommonType,
+    /// An action declaring no context, so requests for it cannot carry any
+    UntypedContext,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaLintFinding {
+    pub severity: SchemaLintSeverity,
+    pub rule: SchemaLintRule,
+    /// Qualified name of the entity type, action or common type, empty for the whole schema
+    pub subject: String,
+    pub message: String,
+}
+
+/// Common pitfalls found in a schema, most severe first.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SchemaLintReport {
+    pub errors: usize,
+    pub warnings: usize,
+    pub findings: Vec<SchemaLintFinding>,
+}
+
+/// Qualifies `name` as written in `namespace`, names holding `::` being qualified already.
+fn qualify(namespace: &str, name: &str) -> String {
+    if namespace.is_empty() || name.contains("::") {
+        name.to_owned()
+    } else {
+        format!("{namespace}::{name}")
+    }
+}
+
+impl SchemaLintReport {
+    /// Lints `schema` as written, without resolving the shared common types, so a reference
+    /// to one of them does not make a namespace type look unused.
+    pub fn new(schema: &Schema) -> Self {
+        let mut findings = Vec::new();
+
+        let mut entity_types: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (namespace, ns) in &schema.0 {
+            for (name, entity_type) in &ns.entity_types {
+                let parents = entity_type
+                    .member_of_types
+                    .iter()
+                    .flatten()
+                    .map(|parent| qualify(namespace, parent))
+                    .collect();
+                entity_types.insert(qualify(namespace, name), parents);
+            }
+        }
+
+        let mut applied: BTreeSet<String> = BTreeSet::new();
+        for (namespace, ns) in &schema.0 {
+            for (id, action) in &ns.actions {
+                let subject = format!("{}::\"{id}\"", qualify(namespace, "Action"));
+                let principals = action.principal_types();
+                let resources = action.resource_types();
+                if principals.is_empty() || resources.is_empty() {
+                    findings.push(SchemaLintFinding {
+                        severity: SchemaLintSeverity::Warning,
+                        rule: SchemaLintRule::EmptyAppliesTo,
+                        subject: subject.clone(),
+                        message: "no principal or resource type, no request can match it"
+                            .to_owned(),
+                    });
+                } else if action.context().is_none() {
+                    findings.push(SchemaLintFinding {
+                        severity: SchemaLintSeverity::Info,
+                        rule: SchemaLintRule::UntypedContext,
+                        subject: subject.clone(),
+                        message: "no context declared, requests cannot carry any".to_owned(),
+                    });
+                }
+
+                for type_name in principals.iter().chain(resources) {
+                    let type_name = qualify(namespace, type_name);
+                    if !entity_types.contains_key(&type_name) {
+                        findings.push(SchemaLintFinding {
+                            severity: SchemaLintSeverity::Error,
+                            rule: SchemaLintRule::UndeclaredEntityType,
+                            subject: subject.clone(),
+                            message: format!("applies to undeclared entity type {type_name}"),
+                        });
+                    }
+                    applied.insert(type_name);
+                }
+            }
+        }
+
+        for (type_name, parents) in &entity_types {
+            for parent in parents.iter().filter(|p| !entity_types.contains_key(*p)) {
+                findings.push(SchemaLintFinding {
+                    severity: SchemaLintSeverity::Error,
+                    rule: SchemaLintRule::UndeclaredEntityType,
+                    subject: type_name.clone(),
+                    message: format!("member of undeclared entity type {parent}"),
+                });
+            }
+        }
+
+        // Entity types reachable from the ones actions apply to through `memberOfTypes`
+        let mut used: HashSet<&str> = HashSet::new();
+        let mut pending: Vec<&str> = applied.iter().map(String::as_str).collect();
+        while let Some(type_name) = pending.pop() {
+            if used.insert(type_name) {
+                pending.extend(
+                    entity_types
+                        .get(type_name)
+                        .into_iter()
+                        .flatten()
+                        .map(|p| p.as_str()),
+                );
+            }
+        }
+        for type_name in entity_types.keys().filter(|t| !used.contains(t.as_str())) {
+            findings.push(SchemaLintFinding {
+                severity: SchemaLintSeverity::Warning,
+                rule: SchemaLintRule::UnusedEntityType,
+                subject: type_name.clone(),
+                message: "no action applies to it, nor to a member of it".to_owned(),
+            });
+        }
+
+        // A type being a member of itself is the usual nested group, only longer cycles are
+        // reported
+        for (type_name, parents) in &entity_types {
+            let mut seen: HashSet<&str> = HashSet::new();
+            let mut pending: Vec<&str> = parents
+                .iter()
+                .filter(|p| *p != type_name)
+                .map(String::as_str)
+                .collect();
+            while let Some(parent) = pending.pop() {
+                if parent == type_name {
+                    findings.push(SchemaLintFinding {
+                        severity: SchemaLintSeverity::Warning,
+                        rule: SchemaLintRule::MemberOfTypesCycle,
+                        subject: type_name.clone(),
+                        message: "member of itself through other entity types".to_owned(),
+                    });
+                    break;
+                }
+                if seen.insert(parent) {
+                    pending.extend(
+                        entity_types
+                            .get(parent)
+                            .into_iter()
+                            .flatten()
+                            .map(|p| p.as_str()),
+                    );
+                }
+            }
+        }
+
+        let mut references: HashSet<String> = HashSet::new();
+        for (namespace, ns) in &schema.0 {
+            let mut names = Vec::new();
+            for entity_type in ns.entity_types.values() {
+                for type_json in entity_type.shape.iter().chain(entity_type.tags.iter()) {
+                    type_json.references(&mut names);
+                }
+            }
+            for action in ns.actions.values() {
+                if let Some(context) = action.context() {
+                    context.references(&mut names);
+                }
+            }
+            for type_json in ns.common_types.iter().flat_map(|types| types.values()) {
+                type_json.references(&mut names);
+            }
+            references.extend(names.iter().map(|name| qualify(namespace, name)));
+        }
+        for (namespace, ns) in &schema.0 {
+            for name in ns.common_types.iter().flat_map(|types| types.keys()) {
+                let name = qualify(namespace, name);
+                if !references.contains(&name) {
+                    findings.push(SchemaLintFinding {
+                        severity: SchemaLintSeverity::Info,
+                        rule: SchemaLintRule::UnusedCommonType,
+                        subject: name,
+                        message: "no type refers to it".to_owned(),
+                    });
+                }
+            }
+        }
+
+        let mut report = Self::default();
+        findings
+            .into_iter()
+            .for_each(|finding| report.push(finding));
+        report
+    }
+
+    /// Reports the error Cedar rejects the schema with.
+    pub fn invalid(&mut self, message: String) {
+        self.push(SchemaLintFinding {
+            severity: SchemaLintSeverity::Error,
+            rule: SchemaLintRule::InvalidSchema,
+            subject: String::new(),
+            message,
+        });
+    }
+
+    fn push(&mut self, finding: SchemaLintFinding) {
+        match finding.severity {
+            SchemaLintSeverity::Error => self.errors += 1,
+            SchemaLintSeverity::Warning => self.warnings += 1,
+            SchemaLintSeverity::Info => {}
+        }
+        let at = self.findings.partition_point(|f| {
+            (f.severity, f.rule, &f.subject) <= (finding.severity, finding.rule, &finding.subject)
+        });
+        self.findings.insert(at, finding);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Schema {
+        serde_json::from_value(serde_json::json!({
+            "App": {
+                "entityTypes": {
+                    "User": {"memberOfTypes": ["Group"]},
+                    "Group": {"memberOfTypes": ["Group"]},
+                    "Document": {
+                        "shape": {
+                            "type": "Record",
+                            "attributes": {"owner": {"type": "EntityOrCommon", "name": "Owner"}}
+                        }
+                    },
+                    "Folder": {"memberOfTypes": ["Drive"]},
+                    "Drive": {"memberOfTypes": ["Folder"]},
+                    "Audit": {}
+                },
+                "actions": {
+                    "view": {
+                        "appliesTo": {
+                            "principalTypes": ["User"],
+                            "resourceTypes": ["Document", "Tenant"],
+                            "context": {"type": "Record", "attributes": {}}
+                        }
+                    },
+                    "edit": {
+                        "appliesTo": {"principalTypes": ["User"], "resourceTypes": ["Document"]}
+                    },
+                    "archive": {
+                        "appliesTo": {"principalTypes": [], "resourceTypes": ["Document"]}
+                    }
+                },
+                "commonTypes": {
+                    "Owner": {"type": "Entity", "name": "User"},
+                    "Address": {"type": "String"}
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_schema_lint() {
+        let report = SchemaLintReport::new(&schema());
+        let findings: Vec<(SchemaLintRule, &str)> = report
+            .findings
+            .iter()
+            .map(|f| (f.rule, f.subject.as_str()))
+            .collect();
+
+        assert_eq!(
+            findings,
+            vec![
+                (
+                    SchemaLintRule::UndeclaredEntityType,
+                    "App::Action::\"view\""
+                ),
+                (SchemaLintRule::EmptyAppliesTo, "App::Action::\"archive\""),
+                (SchemaLintRule::UnusedEntityType, "App::Audit"),
+                (SchemaLintRule::UnusedEntityType, "App::Drive"),
+                (SchemaLintRule::UnusedEntityType, "App::Folder"),
+                (SchemaLintRule::MemberOfTypesCycle, "App::Drive"),
+                (SchemaLintRule::MemberOfTypesCycle, "App::Folder"),
+                (SchemaLintRule::UnusedCommonType, "App::Address"),
+                (SchemaLintRule::UntypedContext, "App::Action::\"edit\""),
+            ]
+        );
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.warnings, 6);
+    }
+}
@@ -1,10 +1,47 @@
+use std::collections::HashMap;
+
+use cedrus_cedar::{Entity, EntityUid, entity::EntityAttr};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use utoipa::ToSchema;
 
 use crate::core::is::OpenIdConnectTokenSelection;
 
+pub mod anomaly;
+pub mod audit;
+pub mod batch;
+pub mod benchmark;
+pub mod bundle;
+pub mod candidate;
 pub mod cedrus;
+pub mod cluster;
+pub mod combine;
+pub mod consistency;
+pub mod coverage;
+pub mod crypto;
+pub mod diff;
+pub mod dry_run;
+pub mod environment;
+pub mod epoch;
+pub mod generator;
+pub mod gitops;
+pub mod history;
+pub mod isolation;
+pub mod job;
+pub mod lint;
+pub mod modified;
+pub mod notification;
 pub mod project;
+pub mod references;
+pub mod relation;
+pub mod sdk;
+pub mod shard;
+pub mod staleness;
+pub mod state;
+pub mod sync;
+pub mod telemetry;
+pub mod token;
+pub mod write_behind;
 
 pub mod is {
     use super::*;
@@ -117,6 +154,46 @@ pub mod is {
         pub entity_id_prefix: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         pub group_configuration: Option<OpenIdConnectGroupConfiguration>,
+        /// Scopes the token must grant, read from the `scope` or `scp` claim.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub required_scopes: Vec<String>,
+        /// Claims the token must carry with the given value. A claim holding an array matches
+        /// when it contains the value.
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        #[schema(value_type = HashMap<String, Object>)]
+        pub required_claims: HashMap<String, Value>,
+    }
+
+    impl OpenIdConnectConfiguration {
+        fn scopes(claims: &Value) -> Vec<&str> {
+            match claims.get("scope").or_else(|| claims.get("scp")) {
+                Some(Value::String(scopes)) => scopes.split_whitespace().collect(),
+                Some(Value::Array(scopes)) => scopes.iter().filter_map(|s| s.as_str()).collect(),
+                _ => Vec::new(),
+            }
+        }
+
+        /// Checks the required scopes and claims against the claims of a validated token.
+        pub fn assert_claims(&self, claims: &Value) -> bool {
+            let scopes = Self::scopes(claims);
+            let has_scopes = self
+                .required_scopes
+                .iter()
+                .all(|scope| scopes.contains(&scope.as_str()));
+
+            let has_claims =
+                self.required_claims
+                    .iter()
+                    .all(|(name, expected)| match claims.get(name) {
+                        Some(Value::Array(values)) => {
+                            values.contains(expected) || Some(values) == expected.as_array()
+                        }
+                        Some(value) => value == expected,
+                        None => false,
+                    });
+
+            has_scopes && has_claims
+        }
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -134,11 +211,71 @@ pub mod is {
     }
 }
 
+/// Materializes the principal entity from the claims of a verified token. Claims are
+/// addressed by a dot separated path (`address.country`), or a JSON pointer when the
+/// path starts with `/`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EntityMapper {
+    /// Claim path of every principal attribute, by attribute name.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, String>,
+    /// Entity type of the parents read from a claim path, each string of the claim being
+    /// the id of a parent.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub parents: HashMap<String, String>,
+}
+
+impl EntityMapper {
+    pub fn claim<'a>(claims: &'a Value, path: &str) -> Option<&'a Value> {
+        if path.starts_with('/') {
+            return claims.pointer(path);
+        }
+        path.split('.')
+            .try_fold(claims, |value, segment| value.get(segment))
+    }
+
+    /// Builds the principal `uid` from `claims`. Claims missing or not representable as
+    /// Cedar values are left out.
+    pub fn map(&self, uid: EntityUid, claims: &Value) -> Entity {
+        let attrs = self
+            .attributes
+            .iter()
+            .filter_map(|(name, path)| {
+                let value = Self::claim(claims, path)?;
+                let attr = serde_json::from_value::<EntityAttr>(value.clone()).ok()?;
+                Some((name.clone(), attr))
+            })
+            .collect();
+
+        let parents = self
+            .parents
+            .iter()
+            .flat_map(|(path, entity_type)| {
+                let ids = match Self::claim(claims, path) {
+                    Some(Value::String(id)) => vec![id.as_str()],
+                    Some(Value::Array(ids)) => ids.iter().filter_map(Value::as_str).collect(),
+                    _ => Vec::new(),
+                };
+                ids.into_iter()
+                    .map(|id| EntityUid::new(entity_type.clone(), id.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        Entity::new(uid, attrs, parents)
+    }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct IdentitySource {
     pub principal_entity_type: String,
     pub configuration: is::Configuration,
+    /// Builds the principal of is-authorized requests carrying a token from its claims,
+    /// over the stored entity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_mapper: Option<EntityMapper>,
 }
 
 impl IdentitySource {
@@ -191,6 +328,15 @@ impl IdentitySource {
         }
     }
 
+    /// Whether the claims of a validated token satisfy the scopes and claims the identity
+    /// source requires, so tokens minted for other APIs of the same issuer are rejected.
+    pub fn assert_claims(&self, claims: &Value) -> bool {
+        match &self.configuration {
+            is::Configuration::CognitoUserPoolConfiguration(_) => true,
+            is::Configuration::OpenIdConnectConfiguration(conf) => conf.assert_claims(claims),
+        }
+    }
+
     pub fn group_entity_type(&self) -> Option<String> {
         match &self.configuration {
             is::Configuration::CognitoUserPoolConfiguration(conf) => conf
@@ -210,6 +356,10 @@ impl IdentitySource {
 pub struct DynamoDBConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub endpoint_url: Option<String>,
+    /// Endpoint the cache warm-up reads from, e.g. a DAX cluster or a replica table, in
+    /// place of `endpoint_url`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_endpoint_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub region: Option<String>,
     pub table_name: String,
@@ -221,6 +371,9 @@ pub struct DynamoDBConfig {
 #[serde(rename_all = "camelCase")]
 pub struct CouchDbConfig {
     pub uri: String,
+    /// Replica the cache warm-up reads from, with the same credentials and database name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_uri: Option<String>,
     pub username: String,
     pub password: String,
     pub db_name: String,
@@ -245,13 +398,22 @@ impl Default for DbConfig {
 pub struct ValKeyCacheConfig {
     pub urls: Vec<String>,
     pub cluster: bool,
+    /// Prefix of every key, isolating environments that share a Valkey cluster
+    pub namespace: Option<String>,
     pub root_key: Option<String>,
     pub client_cert: Option<String>,
     pub client_key: Option<String>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
-pub struct DashMapCacheConfig {}
+#[serde(rename_all = "camelCase")]
+pub struct DashMapCacheConfig {
+    /// Approximate bytes of cached entities above which the entities of the least recently
+    /// used projects are evicted, unbounded by default
+    pub max_size: Option<usize>,
+    /// Approximate bytes an eviction frees the Cache down to, 90% of `max_size` by default
+    pub eviction_target_size: Option<usize>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -272,6 +434,8 @@ pub struct ValKeyPubSubConfig {
     pub urls: Vec<String>,
     pub channel_name: String,
     pub cluster: bool,
+    /// Prefix of the channel, isolating environments that share a Valkey cluster
+    pub namespace: Option<String>,
     pub root_key: Option<String>,
     pub client_cert: Option<String>,
     pub client_key: Option<String>,
@@ -298,11 +462,580 @@ impl Default for PubSubConfig {
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
-    pub public_key: Option<String>,
-    pub private_key: Option<String>,
-    pub chains_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exclude_policy_annotation: Option<String>,
+    /// Interval in seconds between background consistency checks, disabled when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency_check_interval: Option<u64>,
+    /// Interval in seconds between sweeps of expired entities (default: 60), disabled with 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_expiry_interval: Option<u64>,
+    /// Persist validated Cedar entities in the cache so nodes skip re-validating them on startup.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiled_entities: Option<bool>,
+    /// Interval in seconds between snapshots of the changed compiled entities (default: 30).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiled_entities_interval: Option<u64>,
+    /// Start the server in read-only mode, rejecting every mutation route.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    /// Serve the admin project from the bundled configuration files when its stored data
+    /// fails the startup integrity check, instead of refusing to start.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safe_mode: Option<bool>,
+    #[serde(default)]
+    pub request_log: RequestLogConfig,
+    /// Credentials accepted on the management listener.
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// Serve the authorization routes on a separate listener, keeping only the management
+    /// routes on `host` and `port`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_plane: Option<ListenerConfig>,
+    /// Interval in seconds between resolutions of the `secretRef:` values of the
+    /// configuration, picking up rotated secrets. Disabled when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_refresh_interval: Option<u64>,
+    /// Serve HTTPS on the management listener.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Keys of the signed policy bundles.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundles: Option<BundleConfig>,
+    /// HMAC keys signing webhook and event payloads, stream tokens and, without an ed25519
+    /// private key, the exported bundles.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing: Option<SigningConfig>,
+    /// Key provider of the entity attributes encrypted at rest, named by the `@encrypted`
+    /// annotation of their entity type.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionConfig>,
+    /// Nodes of the cluster sharing the rebuilds of the projects by consistent hashing, every
+    /// node rebuilds every project when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shards: Option<ShardConfig>,
+    /// Files the admin project is created with, in place of the bundled ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bootstrap: Option<BootstrapConfig>,
+    /// Largest request body accepted in bytes, once decompressed. Defaults to 64 MiB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_size: Option<usize>,
+    /// Smallest response body compressed in bytes. Defaults to 1 KiB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_min_size: Option<u16>,
+    /// Timeouts and concurrency limits of the project routes, by cost.
+    #[serde(default)]
+    pub limits: RouteLimitsConfig,
+    /// Evaluations each project runs at once, unbounded when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_evaluations: Option<EvaluationLimitConfig>,
+    /// Share of authorization decisions written to an analytics sink, disabled when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingConfig>,
+    /// Serve the development routes, e.g. random test data of a project. Never in production.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev_routes: Option<bool>,
+    /// Channels alerted on policy changes, failed events and Deny rate spikes of every
+    /// project, disabled when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<NotificationsConfig>,
+    /// Rolling Deny rates of each project per action, checked against their baseline after
+    /// each policy change, disabled when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deny_rate_anomalies: Option<DenyRateAnomalyConfig>,
+    /// Principals of the JWTs sent to `is-authorized`, kept once verified.
+    #[serde(default)]
+    pub token_cache: TokenCacheConfig,
+    /// Heartbeats the nodes exchange over the pubsub, reported by `GET /v1/admin/cluster`.
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    /// Repositories GitOps projects may be reconciled from, none by default.
+    #[serde(default)]
+    pub gitops: GitOpsConfig,
+}
+
+/// Sampling of evaluated authorization requests for offline analysis.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SamplingConfig {
+    /// Share of Allow decisions sampled, from 0 to 1.
+    pub allow_rate: f64,
+    /// Share of Deny decisions sampled, from 0 to 1.
+    pub deny_rate: f64,
+    pub sink: SamplingSinkConfig,
+    /// Samples written at once.
+    pub batch_size: usize,
+    /// Seconds between writes of the samples collected so far.
+    pub flush_interval: u64,
+    /// Samples kept in memory while the sink is slow, later ones are dropped.
+    pub buffer_size: usize,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        SamplingConfig {
+            allow_rate: 0.01,
+            deny_rate: 1.0,
+            sink: SamplingSinkConfig::default(),
+            batch_size: 1000,
+            flush_interval: 60,
+            buffer_size: 10000,
+        }
+    }
+}
+
+/// Destination of the sampled decisions, written as JSON lines.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum SamplingSinkConfig {
+    /// `tracing` events on the `cedrus::sampling` target
+    #[default]
+    #[serde(rename = "log")]
+    Log,
+    /// `INSERT ... FORMAT JSONEachRow` through the ClickHouse HTTP interface
+    #[serde(rename = "clickhouse", rename_all = "camelCase")]
+    ClickHouse {
+        url: String,
+        table: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        user: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+    },
+    /// One object per batch under `prefix`, partitioned by day, with the AWS credentials
+    /// of the environment
+    #[serde(rename = "s3", rename_all = "camelCase")]
+    S3 {
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    },
+}
+
+/// Alerts of the projects and the channels they are sent to, those of each project being
+/// set on the project.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotificationsConfig {
+    /// Slack channel the alerts of every project are posted to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slack: Option<SlackConfig>,
+    /// Server the alerts are emailed through, to `to` for every project and to the
+    /// `emails` of each project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp: Option<SmtpConfig>,
+    /// Alerts sent to the channels of the configuration, every kind when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<notification::AlertKind>,
+    /// Hosts the webhooks of projects may post to besides `hooks.slack.com`, over `https`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub webhook_hosts: Vec<String>,
+    pub deny_rate: DenyRateConfig,
+    /// Alerts kept in memory while the channels are slow, later ones are dropped.
+    pub buffer_size: usize,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        NotificationsConfig {
+            slack: None,
+            smtp: None,
+            alerts: Vec::new(),
+            webhook_hosts: Vec::new(),
+            deny_rate: DenyRateConfig::default(),
+            buffer_size: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SlackConfig {
+    /// Incoming webhook URL of the channel.
+    pub webhook_url: String,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SmtpTls {
+    /// Plain connection, upgraded with `STARTTLS`, usually on port 587.
+    #[default]
+    StartTls,
+    /// TLS from the start, usually on port 465.
+    Implicit,
+    /// No TLS, for a relay on a trusted network only.
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    /// Authenticates with `AUTH PLAIN` when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    pub from: String,
+    /// Addresses the alerts of every project are emailed to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub to: Vec<String>,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        SmtpConfig {
+            host: String::new(),
+            port: 587,
+            tls: SmtpTls::default(),
+            username: None,
+            password: None,
+            from: String::new(),
+            to: Vec::new(),
+        }
+    }
+}
+
+/// Share of Deny decisions of a project raising a `denyRate` alert.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DenyRateConfig {
+    /// Share of Deny decisions, from 0 to 1, of projects not setting their own.
+    pub threshold: f64,
+    /// Decisions of the window needed before the rate counts.
+    pub min_requests: u64,
+    /// Seconds the decisions are counted over.
+    pub window: u64,
+    /// Seconds before a project is alerted on again.
+    pub cooldown: u64,
+}
+
+impl Default for DenyRateConfig {
+    fn default() -> Self {
+        DenyRateConfig {
+            threshold: 0.5,
+            min_requests: 100,
+            window: 60,
+            cooldown: 600,
+        }
+    }
+}
+
+/// Verified JWTs kept per project, skipping their signature check while valid.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TokenCacheConfig {
+    /// Seconds a verified token is kept at most, never past its `exp`. Disabled with 0.
+    pub ttl: u64,
+    /// Tokens kept across all projects, the next ones verified every time.
+    pub max_entries: usize,
+}
+
+impl Default for TokenCacheConfig {
+    fn default() -> Self {
+        TokenCacheConfig {
+            ttl: 300,
+            max_entries: 100_000,
+        }
+    }
+}
+
+/// Heartbeats of the nodes, telling which are alive and which missed events.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClusterConfig {
+    /// Seconds between the heartbeats of a node. Disabled with 0.
+    pub heartbeat_interval: u64,
+    /// Seconds without a heartbeat after which a node is reported unreachable.
+    pub expiry: u64,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        ClusterConfig {
+            heartbeat_interval: 10,
+            expiry: 30,
+        }
+    }
+}
+
+/// Git repositories the projects may be reconciled from.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GitOpsConfig {
+    /// Hosts the `https` and `ssh` repository URLs may point to, none when empty.
+    pub allowed_hosts: Vec<String>,
+    /// Seconds a `git` command may run before it is killed.
+    pub timeout: u64,
+}
+
+impl Default for GitOpsConfig {
+    fn default() -> Self {
+        GitOpsConfig {
+            allowed_hosts: Vec::new(),
+            timeout: 60,
+        }
+    }
+}
+
+/// Deny rate rise after a policy change treated as an anomaly, a sign of a bad rollout.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DenyRateAnomalyConfig {
+    /// Seconds of decisions the rolling rate of an action is taken over.
+    pub window: u64,
+    /// Decisions needed for a baseline, and after the change before comparing to it.
+    pub min_requests: u64,
+    /// Rise of the Deny rate, from 0 to 1, over its baseline raising an anomaly.
+    pub spike: f64,
+    /// Seconds after a policy change its decisions are checked for.
+    pub watch: u64,
+}
+
+impl Default for DenyRateAnomalyConfig {
+    fn default() -> Self {
+        DenyRateAnomalyConfig {
+            window: 300,
+            min_requests: 50,
+            spike: 0.2,
+            watch: 900,
+        }
+    }
+}
+
+/// Timeout and concurrency limit of a class of routes.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RouteLimit {
+    /// Seconds before a request is answered 408, unbounded when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Requests of the class served at once, others wait their turn. Unbounded when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
+    /// Answer 503 instead of waiting while `maxConcurrency` requests are served.
+    pub load_shed: bool,
+}
+
+/// Limits of the project routes, split so heavy imports and exports can't starve
+/// authorization traffic.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RouteLimitsConfig {
+    /// `is-authorized`
+    pub authorization: RouteLimit,
+    /// `is-authorized-batch`
+    pub batch: RouteLimit,
+    /// Imports, exports, syncs and batch deletions
+    pub bulk: RouteLimit,
+    /// Every other route
+    pub default: RouteLimit,
+}
+
+/// Evaluation slots of each project, keeping a tenant sending large batches from starving the
+/// others.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EvaluationLimitConfig {
+    /// Evaluations of a project run at once, off the async runtime, others wait their turn.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
+    /// Evaluations of a project waiting at most, others are answered 503. Unbounded when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_queue: Option<usize>,
+    /// Evaluations of a project given up on after its evaluation timeout and still running,
+    /// past which the project is answered 503 until some complete. 4 when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_abandoned: Option<usize>,
+}
+
+/// Signed policy bundle settings, keys are ed25519 PEM contents.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleConfig {
+    /// Private key signing the exported bundles, its public key is also trusted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    /// Public keys whose bundles are accepted on import.
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+}
+
+/// HMAC-SHA256 signing keys, the first one signs and all of them verify.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningConfig {
+    pub keys: Vec<SigningKeyConfig>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningKeyConfig {
+    /// Named in the signatures, so a rotated key keeps verifying what it signed.
+    pub id: String,
+    /// At least 32 bytes, may be a `secretRef:`.
+    pub secret: String,
+}
+
+/// Nodes of a cluster, each owning the projects it rebuilds as soon as they change.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ShardConfig {
+    /// Name of this node among `nodes`, e.g. `secretRef:env:HOSTNAME`.
+    pub node_id: String,
+    pub nodes: Vec<String>,
+    /// Points of each node on the hash ring, 64 by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virtual_nodes: Option<usize>,
+}
+
+/// Provider of the keys encrypting entity attributes at rest.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum EncryptionConfig {
+    /// Keys of the configuration, the first one encrypts and all of them decrypt
+    #[serde(rename = "local")]
+    Local { keys: Vec<EncryptionKeyConfig> },
+    /// Data keys generated and decrypted by AWS KMS with the credentials of the environment
+    #[serde(rename = "kms", rename_all = "camelCase")]
+    Kms {
+        key_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    },
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionKeyConfig {
+    /// Stored with each value, so a rotated key keeps decrypting what it encrypted.
+    pub id: String,
+    /// Base64 of 32 random bytes, may be a `secretRef:`.
+    pub key: String,
+}
+
+/// Paths of the JSON files the admin project is created with on first start, customizing the
+/// authorization model of the management API. Each one left unset is bundled with the server.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BootstrapConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entities: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy_set: Option<String>,
+}
+
+/// HTTPS listener settings, certificates and keys are PEM files.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// Server certificate chain, leaf first.
+    pub cert: String,
+    pub key: String,
+    /// CAs signing client certificates, enables mutual TLS when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ca: Option<String>,
+    /// Also accept clients without a certificate when `clientCa` is set.
+    #[serde(default)]
+    pub client_auth_optional: bool,
+    /// Interval in seconds between checks of the files, reloaded when they change.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reload_interval: Option<u64>,
+}
+
+/// Kinds of credentials a listener accepts.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthConfig {
+    /// Accept `x-api-key` headers.
+    pub api_key: bool,
+    /// Accept JWT bearer tokens.
+    pub bearer: bool,
+    /// Take the principal of requests without credentials from the identity an AWS load
+    /// balancer or API gateway in front of the listener asserts. Only for listeners reachable
+    /// through it alone, as the headers are trusted as is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<GatewayAuthConfig>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            api_key: true,
+            bearer: true,
+            gateway: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GatewaySource {
+    /// Application Load Balancer authenticating users through OIDC or Cognito, and passing
+    /// their claims in the `x-amzn-oidc-data` header.
+    #[default]
+    Alb,
+    /// API Gateway whose authorizer context is mapped into a request header.
+    ApiGateway,
+}
+
+/// Identity asserted by the gateway, mapped to a principal `entityType::"<id>"`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GatewayAuthConfig {
+    pub source: GatewaySource,
+    /// Entity type of the principals, e.g. `App::User`.
+    pub entity_type: String,
+    /// Claim of `x-amzn-oidc-data` the id is read from, `sub` by default. ALB only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim: Option<String>,
+    /// ARN of the load balancer `x-amzn-oidc-data` must name as its signer. ALB only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+    /// Header the authorizer context maps the id into, `x-apigateway-principal` by default.
+    /// API Gateway only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+}
+
+impl Default for GatewayAuthConfig {
+    fn default() -> Self {
+        Self {
+            source: GatewaySource::default(),
+            entity_type: "User".to_string(),
+            claim: None,
+            signer: None,
+            header: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenerConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+}
+
+/// Structured request logging, emitted on the `cedrus::request` tracing target.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RequestLogConfig {
+    /// Also log request and response JSON bodies, after redaction.
+    pub bodies: bool,
+    /// Names of entity attributes, entity tags and context attributes whose values are
+    /// replaced in logged bodies. `*` redacts all of them.
+    pub redact: Vec<String>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
@@ -310,9 +1043,64 @@ pub struct ServerConfig {
 pub struct CedrusConfig {
     pub server: ServerConfig,
     pub db: DbConfig,
+    /// Databases of the data residency regions projects can be created in, by name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub regions: HashMap<String, DbConfig>,
     #[serde(default)]
     pub cache: CacheConfig,
+    /// Caches of the data residency regions, by name. Required for each of the `regions`
+    /// when `cache` is shared by several nodes, so the data of a regional project is never
+    /// held outside of its region.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub cache_regions: HashMap<String, CacheConfig>,
     #[serde(default)]
     pub pubsub: PubSubConfig,
     pub identity_source: Option<IdentitySource>,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_identity_source_assert_claims() {
+        let identity_source = IdentitySource {
+            principal_entity_type: "User".to_string(),
+            configuration: is::Configuration::OpenIdConnectConfiguration(
+                is::OpenIdConnectConfiguration {
+                    issuer: "https://issuer.example.com".to_string(),
+                    required_scopes: vec!["cedrus.authorize".to_string()],
+                    required_claims: HashMap::from([("aud".to_string(), json!("cedrus"))]),
+                    ..Default::default()
+                },
+            ),
+            entity_mapper: None,
+        };
+
+        // Scopes are read from `scope` or `scp`, a claim array matching when it holds the value
+        assert!(identity_source.assert_claims(&json!({
+            "scope": "openid cedrus.authorize",
+            "aud": ["other", "cedrus"],
+        })));
+        assert!(identity_source.assert_claims(&json!({
+            "scp": ["cedrus.authorize"],
+            "aud": "cedrus",
+        })));
+
+        // A token minted for another API of the same issuer is rejected
+        assert!(!identity_source.assert_claims(&json!({
+            "scope": "openid billing.read",
+            "aud": "cedrus",
+        })));
+        assert!(!identity_source.assert_claims(&json!({
+            "scope": "cedrus.authorize",
+            "aud": "billing",
+        })));
+        assert!(!identity_source.assert_claims(&json!({"scope": "cedrus.authorize"})));
+
+        // Cognito user pools require nothing more
+        assert!(IdentitySource::default().assert_claims(&json!({})));
+    }
+}
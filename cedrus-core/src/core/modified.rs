@@ -0,0 +1,37 @@
+use dashmap::DashMap;
+use uuid::Uuid;
+
+/// Data of a project served by the read routes with caching headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProjectResource {
+    Schema,
+    Entities,
+    /// Static policies, templates and template links
+    Policies,
+}
+
+/// When the data of each project last changed, as this node loaded it. Loading a project on
+/// startup counts as a change, so a time is never earlier than the change it reflects.
+#[derive(Debug, Default)]
+pub struct ModifiedTimes {
+    times: DashMap<(Uuid, ProjectResource), chrono::DateTime<chrono::Utc>>,
+}
+
+impl ModifiedTimes {
+    pub fn touch(&self, project_id: &Uuid, resource: ProjectResource) {
+        self.times
+            .insert((*project_id, resource), chrono::Utc::now());
+    }
+
+    pub fn get(
+        &self,
+        project_id: &Uuid,
+        resource: ProjectResource,
+    ) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.times.get(&(*project_id, resource)).map(|time| *time)
+    }
+
+    pub fn remove(&self, project_id: &Uuid) {
+        self.times.retain(|(id, _), _| id != project_id);
+    }
+}
@@ -0,0 +1,384 @@
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{DenyRateConfig, NotificationsConfig};
+
+const NOTIFICATIONS_TARGET: &str = "cedrus::notifications";
+/// Host of the Slack incoming webhooks, which projects may always post to.
+pub const SLACK_WEBHOOK_HOST: &str = "hooks.slack.com";
+/// Addresses a project may email its alerts to.
+pub const MAX_PROJECT_EMAILS: usize = 10;
+const REDACTED_WEBHOOK_PATH: &str = "[REDACTED]";
+
+// Host of an `https` URL with a path, without credentials or port
+fn webhook_host(url: &str) -> Option<&str> {
+    let (host, path) = url.strip_prefix("https://")?.split_once('/')?;
+    let valid = !host.is_empty()
+        && !path.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    valid.then_some(host)
+}
+
+/// Whether a webhook URL posts to Slack or to a host of `allowed_hosts`.
+pub fn is_webhook_allowed(url: &str, allowed_hosts: &[String]) -> bool {
+    webhook_host(url).is_some_and(|host| {
+        host.eq_ignore_ascii_case(SLACK_WEBHOOK_HOST)
+            || allowed_hosts
+                .iter()
+                .any(|allowed| host.eq_ignore_ascii_case(allowed))
+    })
+}
+
+/// Whether an email address is safe to put in an SMTP command and a mail header: a single
+/// `@` between a non-empty local part and domain, without whitespace, control characters
+/// or the `<`, `>` and `,` delimiters.
+pub fn is_email_valid(address: &str) -> bool {
+    let Some((local, domain)) = address.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.is_empty()
+        && !domain.contains('@')
+        && !address
+            .chars()
+            .any(|c| c.is_control() || c.is_whitespace() || matches!(c, '<' | '>' | ','))
+}
+
+// The path of a webhook URL is its secret, only its host is shown
+fn redacted_webhook(url: &str) -> String {
+    match webhook_host(url) {
+        Some(host) => format!("https://{host}/{REDACTED_WEBHOOK_PATH}"),
+        None => REDACTED_WEBHOOK_PATH.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum AlertKind {
+    /// Policies, templates or template links of a project added or removed
+    PolicyChange,
+    /// A node failed to apply an event, and may serve stale data for the project
+    EventFailure,
+    /// The share of Deny decisions of a project went past its threshold
+    DenyRate,
+    /// The Deny rate of an action rose past its baseline after a policy change
+    DenyRateSpike,
+}
+
+impl AlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertKind::PolicyChange => "Policy change",
+            AlertKind::EventFailure => "Event failure",
+            AlertKind::DenyRate => "Deny rate",
+            AlertKind::DenyRateSpike => "Deny rate spike",
+        }
+    }
+}
+
+/// Where the alerts of a project are sent, besides the channels of the configuration.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectNotifications {
+    /// Slack incoming webhook the alerts of the project are posted to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slack_webhook_url: Option<String>,
+    /// Addresses the alerts of the project are emailed to, through the SMTP server of the
+    /// configuration
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub emails: Vec<String>,
+    /// Alerts sent to these channels, every kind when empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<AlertKind>,
+    /// Share of Deny decisions, from 0 to 1, raising a `denyRate` alert, the one of the
+    /// configuration when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deny_rate_threshold: Option<f64>,
+}
+
+// The threshold is read from JSON, which has no NaN
+impl Eq for ProjectNotifications {}
+
+impl ProjectNotifications {
+    /// Whether the channels are well formed, the webhook posting to Slack or to a host of
+    /// `allowed_hosts`.
+    pub fn is_valid(&self, allowed_hosts: &[String]) -> bool {
+        self.deny_rate_threshold
+            .is_none_or(|threshold| threshold > 0.0 && threshold <= 1.0)
+            && self.emails.len() <= MAX_PROJECT_EMAILS
+            && self.emails.iter().all(|email| is_email_valid(email))
+            && self
+                .slack_webhook_url
+                .as_ref()
+                .is_none_or(|url| is_webhook_allowed(url, allowed_hosts))
+    }
+
+    /// Hides the path of the webhook URL, which authorizes posting to the channel.
+    pub fn redact(&mut self) {
+        if let Some(url) = &mut self.slack_webhook_url {
+            *url = redacted_webhook(url);
+        }
+    }
+
+    /// Keeps the webhook URL of `original` when this one is its redacted form, as read back
+    /// from a response.
+    pub fn unredact(&mut self, original: Option<&ProjectNotifications>) {
+        let Some(original) = original.and_then(|o| o.slack_webhook_url.as_ref()) else {
+            return;
+        };
+        if self.slack_webhook_url.as_deref() == Some(redacted_webhook(original).as_str()) {
+            self.slack_webhook_url = Some(original.clone());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub project_id: Uuid,
+    /// Node the alert was raised on
+    pub node_id: Uuid,
+    pub message: String,
+    pub at: chrono::DateTime<chrono::Utc>,
+    /// Channels of the project, taken when the alert is raised
+    #[serde(skip)]
+    pub project: Option<ProjectNotifications>,
+}
+
+impl Alert {
+    /// Whether the channels filtering on `alerts` take the alert.
+    pub fn is_sent_to(&self, alerts: &[AlertKind]) -> bool {
+        alerts.is_empty() || alerts.contains(&self.kind)
+    }
+}
+
+// Decisions of a project since the start of its window
+#[derive(Debug)]
+struct DenyWindow {
+    started: Instant,
+    allowed: u64,
+    denied: u64,
+    alerted: Option<Instant>,
+}
+
+/// Alerts handed to the background delivery of the server, and the Deny decisions they
+/// are raised on. Without a delivery, as by default, alerts are dropped.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    sender: Option<mpsc::Sender<Alert>>,
+    deny_rate: DenyRateConfig,
+    webhook_hosts: Vec<String>,
+    windows: DashMap<Uuid, DenyWindow>,
+}
+
+impl Notifications {
+    pub fn new(conf: &NotificationsConfig, sender: mpsc::Sender<Alert>) -> Self {
+        Self {
+            sender: Some(sender),
+            deny_rate: conf.deny_rate.clone(),
+            webhook_hosts: conf.webhook_hosts.clone(),
+            windows: DashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Whether the channels of a project are valid, with the webhook hosts of the
+    /// configuration.
+    pub fn is_project_valid(&self, project: &ProjectNotifications) -> bool {
+        project.is_valid(&self.webhook_hosts)
+    }
+
+    /// Queues an alert for delivery, dropped while the delivery is behind by more than the
+    /// buffer size.
+    pub fn notify(&self, alert: Alert) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        if sender.try_send(alert).is_err() {
+            tracing::debug!(target: NOTIFICATIONS_TARGET, "Notification buffer full, alert dropped");
+        }
+    }
+
+    /// Counts decisions of a project, returning the Deny rate of its window once it reaches
+    /// `threshold` over at least `minRequests` decisions. A project is alerted on at most
+    /// once per `cooldown`.
+    pub fn deny_rate_record(
+        &self,
+        project_id: &Uuid,
+        allowed: u64,
+        denied: u64,
+        threshold: Option<f64>,
+    ) -> Option<f64> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let now = Instant::now();
+        let mut window = self
+            .windows
+            .entry(*project_id)
+            .or_insert_with(|| DenyWindow {
+                started: now,
+                allowed: 0,
+                denied: 0,
+                alerted: None,
+            });
+        if now.duration_since(window.started) >= Duration::from_secs(self.deny_rate.window) {
+            window.started = now;
+            window.allowed = 0;
+            window.denied = 0;
+        }
+        window.allowed += allowed;
+        window.denied += denied;
+
+        let total = window.allowed + window.denied;
+        let rate = window.denied as f64 / total.max(1) as f64;
+        let cooling = window.alerted.is_some_and(|alerted| {
+            now.duration_since(alerted) < Duration::from_secs(self.deny_rate.cooldown)
+        });
+        if total < self.deny_rate.min_requests
+            || rate < threshold.unwrap_or(self.deny_rate.threshold)
+            || cooling
+        {
+            return None;
+        }
+        window.alerted = Some(now);
+
+        Some(rate)
+    }
+
+    pub fn remove(&self, project_id: &Uuid) {
+        self.windows.remove(project_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deny_rate_record() {
+        let conf = NotificationsConfig {
+            deny_rate: DenyRateConfig {
+                threshold: 0.5,
+                min_requests: 10,
+                window: 60,
+                cooldown: 600,
+            },
+            ..Default::default()
+        };
+        let (sender, _receiver) = mpsc::channel(1);
+        let notifications = Notifications::new(&conf, sender);
+        let project_id = Uuid::now_v7();
+
+        // Too few decisions, then below the threshold
+        assert_eq!(
+            notifications.deny_rate_record(&project_id, 0, 5, None),
+            None
+        );
+        assert_eq!(
+            notifications.deny_rate_record(&project_id, 5, 0, Some(0.6)),
+            None
+        );
+
+        assert_eq!(
+            notifications.deny_rate_record(&project_id, 0, 10, None),
+            Some(0.75)
+        );
+        // Cooling down
+        assert_eq!(
+            notifications.deny_rate_record(&project_id, 0, 10, None),
+            None
+        );
+
+        assert_eq!(
+            Notifications::default().deny_rate_record(&project_id, 0, 100, None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_project_notifications_valid() {
+        let allowed = vec!["alerts.example.com".to_string()];
+        let webhook = |url: &str| ProjectNotifications {
+            slack_webhook_url: Some(url.to_string()),
+            ..Default::default()
+        };
+
+        assert!(webhook("https://hooks.slack.com/services/T0/B0/x").is_valid(&[]));
+        assert!(webhook("https://alerts.example.com/hook").is_valid(&allowed));
+        assert!(!webhook("https://alerts.example.com/hook").is_valid(&[]));
+        for url in [
+            "http://hooks.slack.com/services/T0/B0/x",
+            "https://hooks.slack.com",
+            "https://hooks.slack.com.evil.com/x",
+            "https://user@hooks.slack.com/x",
+            "https://169.254.169.254:443/latest",
+        ] {
+            assert!(!webhook(url).is_valid(&allowed), "{url}");
+        }
+
+        let emails = ProjectNotifications {
+            emails: (0..=MAX_PROJECT_EMAILS)
+                .map(|i| format!("user{i}@example.com"))
+                .collect(),
+            ..Default::default()
+        };
+        assert!(!emails.is_valid(&[]));
+
+        let email = |address: &str| ProjectNotifications {
+            emails: vec![address.to_string()],
+            ..Default::default()
+        };
+        assert!(email("alerts@example.com").is_valid(&[]));
+        for address in [
+            "a@b>\r\nRCPT TO:<victim@x",
+            "alerts@example.com\r\nBcc: victim@x",
+            "alerts@exa\tmple.com",
+            "alerts @example.com",
+            "<alerts@example.com>",
+            "alerts@example.com,victim@x",
+            "alerts@@example.com",
+            "@example.com",
+            "alerts@",
+            "alerts",
+        ] {
+            assert!(!email(address).is_valid(&[]), "{address:?}");
+        }
+    }
+
+    #[test]
+    fn test_project_notifications_redact() {
+        let original = ProjectNotifications {
+            slack_webhook_url: Some("https://hooks.slack.com/services/T0/B0/secret".to_string()),
+            ..Default::default()
+        };
+        let mut redacted = original.clone();
+        redacted.redact();
+        assert_eq!(
+            redacted.slack_webhook_url.as_deref(),
+            Some("https://hooks.slack.com/[REDACTED]")
+        );
+
+        // Sent back as read, the webhook is kept
+        redacted.unredact(Some(&original));
+        assert_eq!(redacted, original);
+
+        let mut changed = ProjectNotifications {
+            slack_webhook_url: Some("https://hooks.slack.com/services/T1/B1/other".to_string()),
+            ..Default::default()
+        };
+        changed.unredact(Some(&original));
+        assert_ne!(changed, original);
+    }
+}
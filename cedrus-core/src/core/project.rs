@@ -1,16 +1,85 @@
 use std::collections::{HashMap, HashSet};
 
-use cedrus_cedar::{Entity, EntityUid, entity::EntityAttr};
+use cedrus_cedar::{
+    ActionOp, Entity, EntityUid, EntityValue, ExtensionFn, Policy, PolicyEffect, PolicyId,
+    PrincipalOp, ResourceOp, SlotId, Template, TemplateLink, entity::EntityAttr,
+};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::{Sort, SortOrder};
+
+use super::{
+    anomaly::ActionDenyRate, candidate::ProjectCandidate, environment::ProjectEnvironment,
+    gitops::GitOpsSource, notification::ProjectNotifications, telemetry::ActionContextUsage,
+};
+
 pub const PROJECT_ENTITY_TYPE: &str = "Project";
+/// Fields projects can be sorted by when listed.
+pub const PROJECT_SORT_FIELDS: [&str; 3] = ["name", "createdAt", "updatedAt"];
 pub const PARENT_UID: &str = "Application::Cedrus";
 
 const ATTR_ENABLED: &str = "enabled";
 const ATTR_OWNER: &str = "owner";
+const ATTR_NAME: &str = "name";
+const ATTR_CREATED_AT: &str = "createdAt";
 const TAG_NAME: &str = "name";
+const ROLE_LINK_PREFIX: &str = "role";
+const DELEGATION_PREFIX: &str = "delegation";
+
+pub const ANNOTATION_DELEGATION: &str = "delegation";
+pub const ANNOTATION_DELEGATION_PROJECT: &str = "delegationProject";
+/// Forbid policies of the admin project annotated with it apply to every other project.
+pub const ANNOTATION_GUARDRAIL: &str = "guardrail";
+/// Prefix of the ids of the guardrails in the policy set of a project.
+pub const GUARDRAIL_ID_PREFIX: &str = "guardrail:";
+
+/// Kinds of requests an API key may be used for.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ApiKeyScope {
+    /// `GET` requests
+    Read,
+    /// Requests changing the state of a project
+    Write,
+    /// `is-authorized` requests
+    Authorize,
+}
+
+/// Route group of a project an API key is granted, e.g. `entities:write` for `Device` only.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ApiKeyGrant {
+    /// First path segment after the project, e.g. `entities`, `policies` or `is-authorized`
+    pub routes: String,
+    /// Requests granted on the routes, all of them when empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<ApiKeyScope>,
+    /// Entity types the entity routes may read or change, all of them when empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entity_types: Vec<String>,
+}
+
+/// Entity types a request may read or change, all of them when `None`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EntityTypeScope(pub Option<HashSet<String>>);
+
+impl EntityTypeScope {
+    pub fn is_restricted(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn allows(&self, type_name: &str) -> bool {
+        self.0
+            .as_ref()
+            .is_none_or(|entity_types| entity_types.contains(type_name))
+    }
+
+    pub fn allows_all<'a>(&self, uids: impl IntoIterator<Item = &'a EntityUid>) -> bool {
+        uids.into_iter().all(|uid| self.allows(uid.type_name()))
+    }
+}
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
 #[serde(rename_all = "camelCase", default)]
@@ -19,7 +88,17 @@ pub struct ApiKey {
     pub key: String,
     pub name: String,
     pub project_id: Uuid,
+    /// Principal of the requests made with the key, its creator by default.
     pub owner: EntityUid,
+    /// The key is rejected from then on when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Requests the key may be used for, all of them when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<ApiKeyScope>,
+    /// Route groups the key is limited to on top of its scopes, all of them when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub grants: Vec<ApiKeyGrant>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -34,10 +113,164 @@ impl ApiKey {
             name,
             project_id,
             owner,
+            expires_at: None,
+            scopes: Vec::new(),
+            grants: Vec::new(),
             created_at: now,
             updated_at: now,
         }
     }
+
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn allows(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.is_empty() || self.scopes.contains(&scope)
+    }
+
+    /// Entity types a request to the `routes` group with `scope` may touch, `None` when the
+    /// key is not granted the request at all.
+    pub fn granted(&self, routes: &str, scope: ApiKeyScope) -> Option<EntityTypeScope> {
+        if !self.allows(scope) {
+            return None;
+        }
+        if self.grants.is_empty() {
+            return Some(EntityTypeScope::default());
+        }
+
+        let mut entity_types = HashSet::new();
+        let mut granted = false;
+        for grant in self.grants.iter().filter(|grant| {
+            grant.routes == routes && (grant.scopes.is_empty() || grant.scopes.contains(&scope))
+        }) {
+            if grant.entity_types.is_empty() {
+                return Some(EntityTypeScope::default());
+            }
+            entity_types.extend(grant.entity_types.iter().cloned());
+            granted = true;
+        }
+
+        granted.then_some(EntityTypeScope(Some(entity_types)))
+    }
+}
+
+/// How authorization requests not conforming to the `appliesTo` of the schema are handled.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RequestValidation {
+    /// Rejected with 400, naming the undeclared action or the unexpected entity type
+    #[default]
+    Strict,
+    /// Evaluated without the schema, usually a Deny
+    Permissive,
+}
+
+/// What removing entities named by the slot values of template links does to the links.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkedEntityRemoval {
+    /// The removal is rejected with 409, reporting the links of each entity
+    #[default]
+    Fail,
+    /// The links are removed along with the entities
+    Cascade,
+}
+
+pub const CONTEXT_NOW: &str = "now";
+pub const CONTEXT_WEEKDAY: &str = "weekday";
+pub const CONTEXT_BUSINESS_HOURS: &str = "businessHours";
+
+/// Time attributes the server adds to the context of authorization requests, so time based
+/// policies do not depend on every caller supplying a timestamp. Attributes sent by the caller
+/// are never overwritten.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TimeContext {
+    /// IANA timezone of the project, e.g. `Europe/Paris`, following its daylight saving time.
+    /// Takes precedence over `utcOffset`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Fixed UTC offset of the project, e.g. `+02:00`, when it has no `timezone`
+    pub utc_offset: String,
+    /// First hour of business hours in project local time, inclusive
+    pub business_hours_start: u32,
+    /// Last hour of business hours in project local time, exclusive
+    pub business_hours_end: u32,
+    /// Business days as short weekday names, e.g. `Mon`
+    pub business_days: Vec<String>,
+}
+
+impl Default for TimeContext {
+    fn default() -> Self {
+        Self {
+            timezone: None,
+            utc_offset: "+00:00".to_string(),
+            business_hours_start: 9,
+            business_hours_end: 17,
+            business_days: ["Mon", "Tue", "Wed", "Thu", "Fri"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+impl TimeContext {
+    pub fn offset(&self) -> Option<chrono::FixedOffset> {
+        self.utc_offset.parse().ok()
+    }
+
+    pub fn tz(&self) -> Option<chrono_tz::Tz> {
+        self.timezone.as_ref()?.parse().ok()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.offset().is_some()
+            && (self.timezone.is_none() || self.tz().is_some())
+            && self.business_hours_start <= self.business_hours_end
+            && self.business_hours_end <= 24
+            && self
+                .business_days
+                .iter()
+                .all(|day| day.parse::<chrono::Weekday>().is_ok())
+    }
+
+    /// The `now` (Cedar `datetime`), `weekday` and `businessHours` attributes at `now`.
+    pub fn attributes(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<(&'static str, EntityAttr)> {
+        use chrono::{Datelike, Timelike};
+
+        // The offset of a timezone is the one in effect at `now`
+        let local = match self.tz() {
+            Some(tz) => now.with_timezone(&tz).fixed_offset(),
+            None => now.with_timezone(
+                &self
+                    .offset()
+                    .unwrap_or(chrono::FixedOffset::east_opt(0).unwrap()),
+            ),
+        };
+        let weekday = local.weekday();
+        let business_day = self
+            .business_days
+            .iter()
+            .any(|day| day.parse::<chrono::Weekday>().ok() == Some(weekday));
+        let business_hours = business_day
+            && (self.business_hours_start..self.business_hours_end).contains(&local.hour());
+
+        let datetime = local.format("%Y-%m-%dT%H:%M:%S%.3f%z").to_string();
+        vec![
+            (
+                CONTEXT_NOW,
+                EntityAttr::FunctionEscape(
+                    ExtensionFn::new("datetime".to_string(), datetime).into(),
+                ),
+            ),
+            (CONTEXT_WEEKDAY, EntityAttr::String(weekday.to_string())),
+            (CONTEXT_BUSINESS_HOURS, EntityAttr::Boolean(business_hours)),
+        ]
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
@@ -49,8 +282,76 @@ pub struct Project {
 
     pub enabled: bool,
 
+    /// Mutations are rejected while set, authorization keeps being evaluated. On the
+    /// admin project it puts the whole server in read-only mode.
+    pub read_only: bool,
+
+    /// Entity and policy writes are acknowledged once in the cache and memory, and
+    /// written to the Database in the background. Faster, but writes not yet flushed
+    /// are lost if the node stops abruptly.
+    pub write_behind: bool,
+
+    /// Entity and policy changes are recorded, so they can be read as they were at any
+    /// time since history was turned on.
+    pub history: bool,
+
+    /// When history was last turned on, set by the server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_since: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Principal of the read and is-authorized requests to the project presenting no
+    /// credentials, authorized by the admin project policies like any other. Such
+    /// requests are rejected when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anonymous_principal: Option<EntityUid>,
+
+    /// Git repository the schema, policies and templates of the project are synced
+    /// from. They are read-only through the API while set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gitops: Option<GitOpsSource>,
+
     pub owner: EntityUid,
 
+    /// Data residency region, one of the `regions` of the configuration, whose Database holds
+    /// the schema, entities, policies and keys of the project. The default Database when
+    /// unset. Only set on creation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    /// Free-form key/value labels, e.g. `team: payments`, projects are listed by with
+    /// `label.<key>=<value>` and tags of the `Project` entity of the admin project.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_context: Option<TimeContext>,
+
+    /// Handling of authorization requests whose action the schema does not declare, or
+    /// does not apply to their principal and resource types.
+    pub request_validation: RequestValidation,
+
+    /// Handling of the template links whose slot values name a removed entity.
+    pub linked_entity_removal: LinkedEntityRemoval,
+
+    /// Longest the authorization routes may evaluate a request of the project for, in
+    /// milliseconds, before answering 503. Unbounded when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evaluation_timeout_millis: Option<u64>,
+
+    /// Environment of another project the project is, sharing its schema, set by the
+    /// server when the environment is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<ProjectEnvironment>,
+
+    /// Channels alerted on the policy changes, failed events and Deny rate spikes of the
+    /// project, besides those of the configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<ProjectNotifications>,
+
+    /// Policy set evaluated in the shadow of the live one, set through its own routes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate: Option<ProjectCandidate>,
+
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -63,28 +364,437 @@ impl Project {
             id,
             name,
             enabled: true,
+            read_only: false,
+            write_behind: false,
+            history: false,
+            history_since: None,
+            anonymous_principal: None,
+            gitops: None,
             owner,
+            region: None,
+            labels: HashMap::new(),
+            time_context: None,
+            request_validation: RequestValidation::Strict,
+            linked_entity_removal: LinkedEntityRemoval::Fail,
+            evaluation_timeout_millis: None,
+            environment: None,
+            notifications: None,
+            candidate: None,
             created_at: now,
             updated_at: now,
         }
     }
 
+    /// The project as answered by the API, the secrets of its notifications redacted.
+    pub fn redacted(mut self) -> Self {
+        if let Some(notifications) = &mut self.notifications {
+            notifications.redact();
+        }
+        self
+    }
+
     pub fn entity_uid(id: Uuid) -> EntityUid {
         EntityUid::new(PROJECT_ENTITY_TYPE.to_string(), id.to_string())
     }
 
+    /// Whether every label key is non-empty and free of dots, which select into `labels`.
+    pub fn labels_valid(labels: &HashMap<String, String>) -> bool {
+        labels
+            .keys()
+            .all(|key| !key.is_empty() && !key.contains('.'))
+    }
+
+    /// Sorts projects in place by the given fields, unknown fields are ignored.
+    pub fn sort(projects: &mut [Project], sort: &[Sort]) {
+        projects.sort_by(|a, b| {
+            sort.iter()
+                .map(|s| {
+                    let ordering = match s.field.as_str() {
+                        "name" => a.name.cmp(&b.name),
+                        "createdAt" => a.created_at.cmp(&b.created_at),
+                        "updatedAt" => a.updated_at.cmp(&b.updated_at),
+                        _ => std::cmp::Ordering::Equal,
+                    };
+                    match s.order {
+                        SortOrder::Asc => ordering,
+                        SortOrder::Desc => ordering.reverse(),
+                    }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+
+    /// Entity of the project in the admin project, its `name`, `owner` and `createdAt`
+    /// (Cedar `datetime`) attributes available to the admin policies, and its labels as tags
+    /// besides the `name` one.
     pub fn entity(&self) -> Entity {
         let uid = EntityUid::new(PROJECT_ENTITY_TYPE.to_string(), self.id.to_string());
+        let created_at = self.created_at.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
         let attrs = HashMap::from([
             (ATTR_ENABLED.to_string(), EntityAttr::Boolean(true)),
             (
                 ATTR_OWNER.to_string(),
                 EntityAttr::EntityUid(self.owner.clone()),
             ),
+            (ATTR_NAME.to_string(), EntityAttr::String(self.name.clone())),
+            (
+                ATTR_CREATED_AT.to_string(),
+                EntityAttr::FunctionEscape(
+                    ExtensionFn::new("datetime".to_string(), created_at).into(),
+                ),
+            ),
         ]);
         let parents = HashSet::from([EntityUid::from(PARENT_UID)]);
-        let tags = HashMap::from([(TAG_NAME.to_string(), EntityAttr::String(self.name.clone()))]);
+        let mut tags: HashMap<String, EntityAttr> = self
+            .labels
+            .iter()
+            .map(|(key, value)| (key.clone(), EntityAttr::String(value.clone())))
+            .collect();
+        tags.insert(TAG_NAME.to_string(), EntityAttr::String(self.name.clone()));
 
         Entity::new_with_tags(uid, attrs, parents, tags)
     }
+
+    /// Builds the template links that grant `principal` the templates of role `role_id` over
+    /// this project. `?principal` is bound to the principal and `?resource` to the project
+    /// entity. `None` when a template has no `?resource` slot, its links granting rights beyond
+    /// the project. Link ids are deterministic so the same assignment is not linked twice.
+    pub fn template_links(
+        &self,
+        role_id: &str,
+        templates: &HashMap<PolicyId, Template>,
+        principal: &EntityUid,
+    ) -> Option<Vec<TemplateLink>> {
+        let resource = Self::entity_uid(self.id);
+        let mut links = Vec::new();
+        for (template_id, template) in templates {
+            let cedar = template.to_cedar(template_id.clone()).ok()?;
+            let values = cedar
+                .slots()
+                .map(|slot| match SlotId::from(slot.clone()) {
+                    SlotId::Principal => {
+                        (SlotId::Principal, EntityValue::EntityUid(principal.clone()))
+                    }
+                    SlotId::Resource => {
+                        (SlotId::Resource, EntityValue::EntityUid(resource.clone()))
+                    }
+                })
+                .collect::<HashMap<SlotId, EntityValue>>();
+            if !values.contains_key(&SlotId::Resource) {
+                return None;
+            }
+            let new_id = Self::role_link_id(role_id, template_id, &self.id, principal);
+            links.push(TemplateLink::new(template_id.clone(), new_id, values));
+        }
+        links.sort_by(|a, b| a.new_id.cmp(&b.new_id));
+        Some(links)
+    }
+
+    pub fn role_link_id(
+        role_id: &str,
+        template_id: &PolicyId,
+        project_id: &Uuid,
+        principal: &EntityUid,
+    ) -> PolicyId {
+        PolicyId::from(format!(
+            "{ROLE_LINK_PREFIX}:{role_id}:{template_id}:{project_id}:{principal}"
+        ))
+    }
+
+    pub fn delegation_policy_id(
+        capability: &str,
+        effect: &PolicyEffect,
+        project_id: &Uuid,
+        principal: &EntityUid,
+    ) -> PolicyId {
+        let effect = match effect {
+            PolicyEffect::Permit => "permit",
+            PolicyEffect::Forbid => "forbid",
+        };
+        PolicyId::from(format!(
+            "{DELEGATION_PREFIX}:{capability}:{effect}:{project_id}:{principal}"
+        ))
+    }
+
+    /// Builds the control-plane policy that permits, or forbids, `principal` to perform
+    /// `actions` over this project. It is annotated with the capability name and the project id
+    /// so the delegations of a project can be listed from the nil project.
+    pub fn delegation_policy(
+        &self,
+        capability: &str,
+        effect: PolicyEffect,
+        principal: EntityUid,
+        actions: Vec<EntityUid>,
+    ) -> Policy {
+        Policy {
+            effect,
+            principal: PrincipalOp::new_eq(principal),
+            action: ActionOp::new_in(actions),
+            resource: ResourceOp::new_eq(Self::entity_uid(self.id)),
+            conditions: Vec::new(),
+            annotations: HashMap::from([
+                (
+                    ANNOTATION_DELEGATION.to_string(),
+                    Some(capability.to_string()),
+                ),
+                (
+                    ANNOTATION_DELEGATION_PROJECT.to_string(),
+                    Some(self.id.to_string()),
+                ),
+            ]),
+        }
+    }
+}
+
+/// A reusable role: a named bundle of templates defined in the nil project that can be
+/// instantiated for a principal in any child project.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Role {
+    pub id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub templates: Vec<PolicyId>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A role instantiated for a principal over a project, stored in that project with the ids of
+/// the template links made in the nil project, so they are all revoked even once the role
+/// changes or is removed.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RoleAssignment {
+    pub role_id: String,
+    pub principal: EntityUid,
+    pub template_links: Vec<PolicyId>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl RoleAssignment {
+    pub fn new(role_id: String, principal: EntityUid) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            role_id,
+            principal,
+            template_links: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn id(&self) -> String {
+        Self::assignment_id(&self.role_id, &self.principal)
+    }
+
+    pub fn assignment_id(role_id: &str, principal: &EntityUid) -> String {
+        format!("{role_id}:{principal}")
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectHydration {
+    pub schema: bool,
+    pub identity_source: bool,
+    pub entities: usize,
+    pub policies: usize,
+    pub templates: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectStats {
+    pub project_id: Uuid,
+
+    pub entities: usize,
+    pub entities_by_type: HashMap<String, usize>,
+    pub policies: usize,
+    pub templates: usize,
+    pub template_links: usize,
+    pub schema_namespaces: Vec<String>,
+
+    pub hydration: ProjectHydration,
+    /// Context attributes of the authorization requests served by this node, per action
+    pub context_usage: Vec<ActionContextUsage>,
+    /// Rolling Deny rates of the authorization requests served by this node, per action
+    pub deny_rates: Vec<ActionDenyRate>,
+
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use cedrus_cedar::Schema;
+
+    use super::*;
+
+    #[test]
+    fn test_entity_matches_admin_schema() {
+        let schema: Schema =
+            serde_json::from_str(include_str!("../../config/cedrus.cedarschema.json")).unwrap();
+        let cedar_schema: cedar_policy::Schema = schema.clone().try_into().unwrap();
+        let mut project = Project::new(
+            Uuid::now_v7(),
+            "Billing".to_string(),
+            EntityUid::from("User::alice"),
+        );
+        project.labels = HashMap::from([
+            ("team".to_string(), "payments".to_string()),
+            ("name".to_string(), "ignored".to_string()),
+        ]);
+        assert!(Project::labels_valid(&project.labels));
+
+        let entity = project.entity();
+        let mut declared = schema.entity_attributes(PROJECT_ENTITY_TYPE).unwrap();
+        declared.sort();
+        let mut attrs: Vec<String> = entity.attrs().keys().cloned().collect();
+        attrs.sort();
+        assert_eq!(attrs, declared);
+
+        let cedar_entity = entity.to_cedar_entity(Some(&cedar_schema)).unwrap();
+        let name = cedar_entity.attr(ATTR_NAME).unwrap().unwrap();
+        assert_eq!(
+            name,
+            cedar_policy::EvalResult::String("Billing".to_string())
+        );
+        assert!(cedar_entity.attr(ATTR_CREATED_AT).is_some());
+        assert_eq!(
+            entity.tags().get("team"),
+            Some(&EntityAttr::String("payments".to_string()))
+        );
+        assert_eq!(
+            entity.tags().get(TAG_NAME),
+            Some(&EntityAttr::String("Billing".to_string()))
+        );
+
+        project
+            .labels
+            .insert("team.lead".to_string(), "bob".to_string());
+        assert!(!Project::labels_valid(&project.labels));
+    }
+
+    #[test]
+    fn test_time_context_timezone() {
+        let attribute = |time_context: &TimeContext, at: &str, name: &str| {
+            let at = chrono::DateTime::parse_from_rfc3339(at).unwrap().to_utc();
+            time_context
+                .attributes(at)
+                .into_iter()
+                .find(|(n, _)| *n == name)
+                .unwrap()
+                .1
+        };
+        let datetime = |value: &str| {
+            EntityAttr::FunctionEscape(
+                ExtensionFn::new("datetime".to_string(), value.to_string()).into(),
+            )
+        };
+
+        let paris = TimeContext {
+            timezone: Some("Europe/Paris".to_string()),
+            ..Default::default()
+        };
+        assert!(paris.is_valid());
+        // Winter time, then summer time, at the same UTC hour
+        assert_eq!(
+            attribute(&paris, "2026-01-15T07:30:00Z", CONTEXT_NOW),
+            datetime("2026-01-15T08:30:00.000+0100")
+        );
+        assert_eq!(
+            attribute(&paris, "2026-01-15T07:30:00Z", CONTEXT_BUSINESS_HOURS),
+            EntityAttr::Boolean(false)
+        );
+        assert_eq!(
+            attribute(&paris, "2026-07-15T07:30:00Z", CONTEXT_NOW),
+            datetime("2026-07-15T09:30:00.000+0200")
+        );
+        assert_eq!(
+            attribute(&paris, "2026-07-15T07:30:00Z", CONTEXT_BUSINESS_HOURS),
+            EntityAttr::Boolean(true)
+        );
+
+        // A fixed offset ignores daylight saving time
+        let fixed = TimeContext {
+            utc_offset: "+01:00".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            attribute(&fixed, "2026-07-15T07:30:00Z", CONTEXT_NOW),
+            datetime("2026-07-15T08:30:00.000+0100")
+        );
+
+        let unknown = TimeContext {
+            timezone: Some("Europe/Atlantis".to_string()),
+            ..Default::default()
+        };
+        assert!(!unknown.is_valid());
+    }
+
+    #[test]
+    fn test_api_key_scopes() {
+        let now = chrono::Utc::now();
+        let mut api_key = ApiKey::new(
+            Uuid::now_v7(),
+            "key".to_string(),
+            "service".to_string(),
+            Uuid::now_v7(),
+            EntityUid::from("App::Service::billing"),
+        );
+        assert!(!api_key.is_expired(now));
+        assert!(api_key.allows(ApiKeyScope::Write));
+
+        // A scoped key is limited to its scopes, and rejected once expired
+        api_key.scopes = vec![ApiKeyScope::Read, ApiKeyScope::Authorize];
+        api_key.expires_at = Some(now);
+        assert!(api_key.allows(ApiKeyScope::Read));
+        assert!(api_key.allows(ApiKeyScope::Authorize));
+        assert!(!api_key.allows(ApiKeyScope::Write));
+        assert!(api_key.is_expired(now));
+        assert!(!api_key.is_expired(now - chrono::Duration::seconds(1)));
+    }
+
+    #[test]
+    fn test_api_key_grants() {
+        let mut api_key = ApiKey::new(
+            Uuid::now_v7(),
+            "key".to_string(),
+            "provisioning".to_string(),
+            Uuid::now_v7(),
+            EntityUid::from("App::Service::provisioning"),
+        );
+        api_key.grants = vec![
+            ApiKeyGrant {
+                routes: "entities".to_string(),
+                scopes: vec![ApiKeyScope::Write],
+                entity_types: vec!["App::Device".to_string()],
+            },
+            ApiKeyGrant {
+                routes: "is-authorized".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        // Writing entities is limited to the granted types
+        let entity_types = api_key.granted("entities", ApiKeyScope::Write).unwrap();
+        assert!(entity_types.is_restricted());
+        let device = EntityUid::from("App::Device::d1");
+        let user = EntityUid::from("App::User::alice");
+        assert!(entity_types.allows_all([&device]));
+        assert!(!entity_types.allows_all([&device, &user]));
+
+        // Routes granted without entity types are not restricted, others not granted at all
+        let entity_types = api_key
+            .granted("is-authorized", ApiKeyScope::Authorize)
+            .unwrap();
+        assert!(!entity_types.is_restricted());
+        assert!(entity_types.allows_all([&user]));
+        assert!(api_key.granted("entities", ApiKeyScope::Read).is_none());
+        assert!(api_key.granted("policies", ApiKeyScope::Write).is_none());
+    }
 }
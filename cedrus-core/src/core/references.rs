@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use cedrus_cedar::{EntityUid, Policy, PolicyId, PolicySet, Template, TemplateLink};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Static policies and template links whose scopes name an entity, which break or stop
+/// applying once it is removed.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityReferences {
+    pub entity: EntityUid,
+    pub policies: Vec<PolicyId>,
+    pub template_links: Vec<PolicyId>,
+}
+
+#[derive(Debug, Default)]
+struct Referrers {
+    policies: BTreeSet<PolicyId>,
+    template_links: BTreeSet<PolicyId>,
+}
+
+/// Entities named by the scopes of the static policies and template links of a project,
+/// updated policy by policy.
+#[derive(Debug, Default)]
+pub struct ReferenceIndex {
+    policies: HashMap<PolicyId, Vec<EntityUid>>,
+    /// Template and entities of each link, its slot values and the ones the template names
+    template_links: HashMap<PolicyId, (PolicyId, Vec<EntityUid>)>,
+    entities: HashMap<EntityUid, Referrers>,
+}
+
+fn dedup(entities: Vec<&EntityUid>) -> Vec<EntityUid> {
+    let entities: BTreeSet<&EntityUid> = entities.into_iter().collect();
+    entities.into_iter().cloned().collect()
+}
+
+impl ReferenceIndex {
+    pub fn new(policy_set: &PolicySet) -> Self {
+        let mut index = Self::default();
+        for (policy_id, policy) in &policy_set.static_policies {
+            index.set_policy(policy_id, policy);
+        }
+        for link in &policy_set.template_links {
+            index.set_template_link(link, policy_set.templates.get(&link.template_id));
+        }
+        index
+    }
+
+    pub fn set_policy(&mut self, policy_id: &PolicyId, policy: &Policy) {
+        self.remove_policy(policy_id);
+
+        let entities = dedup(policy.scope_entities());
+        for uid in &entities {
+            self.entities
+                .entry(uid.clone())
+                .or_default()
+                .policies
+                .insert(policy_id.clone());
+        }
+        self.policies.insert(policy_id.clone(), entities);
+    }
+
+    pub fn remove_policy(&mut self, policy_id: &PolicyId) {
+        for uid in self.policies.remove(policy_id).into_iter().flatten() {
+            if let Some(referrers) = self.entities.get_mut(&uid) {
+                referrers.policies.remove(policy_id);
+                if referrers.policies.is_empty() && referrers.template_links.is_empty() {
+                    self.entities.remove(&uid);
+                }
+            }
+        }
+    }
+
+    pub fn set_template_link(&mut self, link: &TemplateLink, template: Option<&Template>) {
+        self.remove_template_link(&link.new_id);
+
+        let mut entities = link.entities();
+        entities.extend(template.map(Template::scope_entities).unwrap_or_default());
+        let entities = dedup(entities);
+        for uid in &entities {
+            self.entities
+                .entry(uid.clone())
+                .or_default()
+                .template_links
+                .insert(link.new_id.clone());
+        }
+        self.template_links
+            .insert(link.new_id.clone(), (link.template_id.clone(), entities));
+    }
+
+    pub fn remove_template_link(&mut self, policy_id: &PolicyId) {
+        let Some((_, entities)) = self.template_links.remove(policy_id) else {
+            return;
+        };
+        for uid in entities {
+            if let Some(referrers) = self.entities.get_mut(&uid) {
+                referrers.template_links.remove(policy_id);
+                if referrers.policies.is_empty() && referrers.template_links.is_empty() {
+                    self.entities.remove(&uid);
+                }
+            }
+        }
+    }
+
+    /// Ids of the links of `template_id`.
+    pub fn template_links_of(&self, template_id: &PolicyId) -> Vec<PolicyId> {
+        self.template_links
+            .iter()
+            .filter(|(_, (id, _))| id == template_id)
+            .map(|(policy_id, _)| policy_id.clone())
+            .collect()
+    }
+
+    pub fn references(&self, entity: &EntityUid) -> EntityReferences {
+        let referrers = self.entities.get(entity);
+        EntityReferences {
+            entity: entity.clone(),
+            policies: referrers
+                .map(|r| r.policies.iter().cloned().collect())
+                .unwrap_or_default(),
+            template_links: referrers
+                .map(|r| r.template_links.iter().cloned().collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Template links whose slot values name one of `entities`, per entity, sorted. Links naming
+/// it only through the scope of their template are left out, the template still does.
+pub fn linked_entities(
+    template_links: &[TemplateLink],
+    entities: &HashSet<EntityUid>,
+) -> Vec<EntityReferences> {
+    let mut linked: BTreeMap<&EntityUid, BTreeSet<PolicyId>> = BTreeMap::new();
+    for link in template_links {
+        for uid in link.entities() {
+            if entities.contains(uid) {
+                linked.entry(uid).or_default().insert(link.new_id.clone());
+            }
+        }
+    }
+    linked
+        .into_iter()
+        .map(|(entity, template_links)| EntityReferences {
+            entity: entity.clone(),
+            policies: Vec::new(),
+            template_links: template_links.into_iter().collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn policy(text: &str) -> Policy {
+        let policy = cedar_policy::Policy::from_str(text).unwrap();
+        serde_json::from_value(policy.to_json().unwrap()).unwrap()
+    }
+
+    fn template(text: &str) -> Template {
+        let template = cedar_policy::Template::from_str(text).unwrap();
+        serde_json::from_value(template.to_json().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_references() {
+        let alice = EntityUid::from("User::alice");
+        let folder = EntityUid::from("Folder::docs");
+        let link: TemplateLink = serde_json::from_value(serde_json::json!({
+            "templateId": "t0",
+            "newId": "l0",
+            "values": {"?principal": {"type": "User", "id": "alice"}}
+        }))
+        .unwrap();
+        let policy_set = PolicySet {
+            static_policies: HashMap::from([
+                (
+                    PolicyId::from("p0".to_string()),
+                    policy(
+                        r#"permit(principal == User::"alice", action, resource in Folder::"docs");"#,
+                    ),
+                ),
+                (
+                    PolicyId::from("p1".to_string()),
+                    policy(r#"permit(principal in User::"alice", action, resource);"#),
+                ),
+            ]),
+            templates: HashMap::from([(
+                PolicyId::from("t0".to_string()),
+                template(r#"permit(principal == ?principal, action, resource in Folder::"docs");"#),
+            )]),
+            template_links: vec![link],
+        };
+
+        let mut index = ReferenceIndex::new(&policy_set);
+        let references = index.references(&alice);
+        assert_eq!(
+            references.policies,
+            vec!["p0".to_string().into(), "p1".to_string().into()]
+        );
+        assert_eq!(references.template_links, vec!["l0".to_string().into()]);
+        assert_eq!(index.references(&folder).template_links.len(), 1);
+
+        index.remove_policy(&"p0".to_string().into());
+        index.remove_template_link(&"l0".to_string().into());
+        assert_eq!(
+            index.references(&alice).policies,
+            vec!["p1".to_string().into()]
+        );
+        assert!(index.references(&folder).policies.is_empty());
+        assert!(index.references(&folder).template_links.is_empty());
+
+        let linked = linked_entities(
+            &policy_set.template_links,
+            &HashSet::from([alice.clone(), folder]),
+        );
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].entity, alice);
+        assert_eq!(linked[0].template_links, vec!["l0".to_string().into()]);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use cedrus_cedar::{Entity, EntityUid, Schema, entity::EntityAttr, schema::TypeJson};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Named relation from an entity to another, stored as an attribute of the object referring
+/// to the subject, e.g. `Document::"d1"` `owner` `User::"alice"` for `resource.owner ==
+/// principal`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct Relation {
+    pub object: EntityUid,
+    pub relation: String,
+    pub subject: EntityUid,
+}
+
+fn attr_uids(attr: &EntityAttr) -> Vec<&EntityUid> {
+    match attr {
+        EntityAttr::EntityUid(uid) => vec![uid],
+        EntityAttr::EntityUidEscape(escape) => vec![escape.uid()],
+        EntityAttr::Set(values) => values.iter().flat_map(attr_uids).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the schema declares `relation` on the entity type as a set, `None` when it does
+/// not declare it.
+fn is_set_relation(schema: &Schema, type_name: &str, relation: &str) -> Option<bool> {
+    let (namespace, entity_type) = schema.entity_type(type_name)?;
+    let TypeJson::Record { attributes, .. } =
+        schema.resolve(namespace, entity_type.shape.as_ref()?)
+    else {
+        return None;
+    };
+    let attribute = schema.resolve(namespace, attributes.get(relation)?);
+    Some(matches!(attribute, TypeJson::Set { .. }))
+}
+
+impl Relation {
+    /// Relations of `entity`, one per entity its attributes refer to, directly or in a set.
+    pub fn of(entity: &Entity) -> Vec<Relation> {
+        entity
+            .attrs()
+            .iter()
+            .flat_map(|(name, attr)| {
+                attr_uids(attr).into_iter().map(|subject| Relation {
+                    object: entity.uid().clone(),
+                    relation: name.clone(),
+                    subject: subject.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Stores the relation in `object`: added to the set when the schema declares the
+    /// attribute as a set, or when undeclared the attribute already holds one, else replacing
+    /// the attribute.
+    pub fn relate(&self, object: &mut Entity, schema: Option<&Schema>) {
+        let attrs = object.attrs_mut();
+        let is_set = schema
+            .and_then(|schema| is_set_relation(schema, self.object.type_name(), &self.relation))
+            .unwrap_or_else(|| matches!(attrs.get(&self.relation), Some(EntityAttr::Set(_))));
+        let subject = EntityAttr::EntityUidEscape(self.subject.clone().into());
+
+        match (is_set, attrs.get_mut(&self.relation)) {
+            (true, Some(EntityAttr::Set(values))) => {
+                if !values.iter().any(|v| attr_uids(v).contains(&&self.subject)) {
+                    values.push(subject);
+                }
+            }
+            (true, _) => {
+                attrs.insert(self.relation.clone(), EntityAttr::Set(vec![subject]));
+            }
+            (false, _) => {
+                attrs.insert(self.relation.clone(), subject);
+            }
+        }
+    }
+
+    /// Removes the relation from `object`, returning whether it held it. A set is left empty
+    /// rather than removed, keeping a required attribute.
+    pub fn unrelate(&self, object: &mut Entity) -> bool {
+        let attrs = object.attrs_mut();
+        match attrs.get_mut(&self.relation) {
+            Some(EntityAttr::Set(values)) => {
+                let len = values.len();
+                values.retain(|v| !attr_uids(v).contains(&&self.subject));
+                values.len() != len
+            }
+            Some(attr) if attr_uids(attr).contains(&&self.subject) => {
+                attrs.remove(&self.relation);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Relations of the entities of a project, looked up by object and by subject.
+#[derive(Debug, Default)]
+pub struct RelationIndex {
+    objects: HashMap<EntityUid, Vec<Relation>>,
+    subjects: HashMap<EntityUid, Vec<Relation>>,
+}
+
+impl RelationIndex {
+    pub fn new(entities: &[Entity]) -> Self {
+        let mut index = Self::default();
+        for relation in entities.iter().flat_map(Relation::of) {
+            index
+                .objects
+                .entry(relation.object.clone())
+                .or_default()
+                .push(relation.clone());
+            index
+                .subjects
+                .entry(relation.subject.clone())
+                .or_default()
+                .push(relation);
+        }
+        index
+    }
+
+    /// Relations of `object`, or to `subject`, or both, of the named relation when given,
+    /// sorted.
+    pub fn find(
+        &self,
+        object: Option<&EntityUid>,
+        subject: Option<&EntityUid>,
+        relation: Option<&str>,
+    ) -> Vec<Relation> {
+        let relations = match (object, subject) {
+            (Some(object), _) => self.objects.get(object),
+            (None, Some(subject)) => self.subjects.get(subject),
+            (None, None) => None,
+        };
+
+        let mut relations: Vec<Relation> = relations
+            .into_iter()
+            .flatten()
+            .filter(|r| subject.is_none_or(|subject| r.subject == *subject))
+            .filter(|r| relation.is_none_or(|relation| r.relation == relation))
+            .cloned()
+            .collect();
+        relations.sort();
+        relations.dedup();
+        relations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn uid(value: &str) -> EntityUid {
+        EntityUid::from(value)
+    }
+
+    #[test]
+    fn test_relations() {
+        let schema: Schema = serde_json::from_value(json!({
+            "App": {
+                "entityTypes": {
+                    "User": {},
+                    "Document": {"shape": {"type": "Record", "attributes": {
+                        "owner": {"type": "Entity", "name": "User"},
+                        "editors": {"type": "Set", "element": {"type": "Entity", "name": "User"}}
+                    }}}
+                },
+                "actions": {}
+            }
+        }))
+        .unwrap();
+        let mut document = Entity::new_no_attrs(uid("App::Document::d1"), Default::default());
+
+        let relation = |name: &str, subject: &str| Relation {
+            object: uid("App::Document::d1"),
+            relation: name.to_string(),
+            subject: uid(subject),
+        };
+        relation("owner", "App::User::alice").relate(&mut document, Some(&schema));
+        relation("owner", "App::User::bob").relate(&mut document, Some(&schema));
+        relation("editors", "App::User::alice").relate(&mut document, Some(&schema));
+        relation("editors", "App::User::carol").relate(&mut document, Some(&schema));
+        relation("editors", "App::User::carol").relate(&mut document, Some(&schema));
+
+        let index = RelationIndex::new(std::slice::from_ref(&document));
+        assert_eq!(
+            index.find(None, Some(&uid("App::User::alice")), None),
+            vec![relation("editors", "App::User::alice")]
+        );
+        assert_eq!(
+            index.find(Some(&uid("App::Document::d1")), None, Some("owner")),
+            vec![relation("owner", "App::User::bob")]
+        );
+        assert_eq!(
+            index
+                .find(Some(&uid("App::Document::d1")), None, None)
+                .len(),
+            3
+        );
+
+        assert!(relation("editors", "App::User::carol").unrelate(&mut document));
+        assert!(!relation("owner", "App::User::alice").unrelate(&mut document));
+        assert!(relation("owner", "App::User::bob").unrelate(&mut document));
+        assert_eq!(
+            Relation::of(&document),
+            vec![relation("editors", "App::User::alice")]
+        );
+    }
+}
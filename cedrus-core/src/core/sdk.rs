@@ -0,0 +1,507 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use cedrus_cedar::{Schema, schema::TypeJson};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use utoipa::ToSchema;
+
+const HEADER: &str = "Generated by Cedrus from the schema of the project, do not edit.";
+
+const RUST_KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "box", "break", "const", "continue", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type", "unsafe", "use",
+    "where", "while", "yield",
+];
+
+/// Languages the authorization model of a project can be generated in.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SdkLang {
+    /// TypeScript constants and context interfaces
+    #[default]
+    Ts,
+    /// Rust constants and context structs, serializable with serde
+    Rust,
+    /// OpenAPI components schemas
+    Openapi,
+}
+
+impl SdkLang {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            SdkLang::Ts | SdkLang::Rust => "text/plain; charset=utf-8",
+            SdkLang::Openapi => "application/json",
+        }
+    }
+}
+
+/// Entity types, actions and context shapes of a namespace, sorted by name.
+struct Model<'a> {
+    namespace: &'a str,
+    entity_types: Vec<String>,
+    actions: Vec<(&'a str, Option<BTreeMap<String, TypeJson>>)>,
+}
+
+fn qualify(namespace: &str, name: &str) -> String {
+    match namespace.is_empty() {
+        true => name.to_owned(),
+        false => format!("{namespace}::{name}"),
+    }
+}
+
+fn models(schema: &Schema) -> Vec<Model<'_>> {
+    let mut namespaces: Vec<_> = schema.0.iter().collect();
+    namespaces.sort_by_key(|(namespace, _)| namespace.as_str());
+
+    namespaces
+        .into_iter()
+        .map(|(namespace, ns)| {
+            let mut entity_types: Vec<String> = ns
+                .entity_types
+                .keys()
+                .map(|name| qualify(namespace, name))
+                .collect();
+            entity_types.sort();
+
+            let mut actions: Vec<_> = ns
+                .actions
+                .iter()
+                .map(|(id, action)| {
+                    let context = action.context().and_then(|context| {
+                        match schema.resolve(namespace, context) {
+                            TypeJson::Record { attributes, .. } => {
+                                Some(attributes.into_iter().collect::<BTreeMap<_, _>>())
+                            }
+                            _ => None,
+                        }
+                    });
+                    (id.as_str(), context)
+                })
+                .collect();
+            actions.sort_by_key(|(id, _)| *id);
+
+            Model {
+                namespace,
+                entity_types,
+                actions,
+            }
+        })
+        .collect()
+}
+
+// Words of an identifier, split on anything but letters and digits and on camel case humps
+fn words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() {
+            previous = None;
+            continue;
+        }
+        let hump = c.is_ascii_uppercase()
+            && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit());
+        match words.last_mut() {
+            Some(word) if previous.is_some() && !hump => word.push(c),
+            _ => words.push(c.to_string()),
+        }
+        previous = Some(c);
+    }
+    words
+}
+
+fn pascal_case(name: &str) -> String {
+    let name: String = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        })
+        .collect();
+    match name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        true => format!("_{name}"),
+        false => name,
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let name = words(name)
+        .iter()
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        format!("_{name}")
+    } else if RUST_KEYWORDS.contains(&name.as_str()) {
+        format!("r#{name}")
+    } else {
+        name
+    }
+}
+
+fn screaming_snake_case(name: &str) -> String {
+    snake_case(name)
+        .trim_start_matches("r#")
+        .to_ascii_uppercase()
+}
+
+fn ts_type(schema: &Schema, namespace: &str, type_json: &TypeJson) -> String {
+    match schema.resolve(namespace, type_json) {
+        TypeJson::Long { .. } => "number".to_owned(),
+        TypeJson::String { .. } | TypeJson::Extension { .. } => "string".to_owned(),
+        TypeJson::Boolean { .. } => "boolean".to_owned(),
+        TypeJson::Set { element, .. } => format!("Array<{}>", ts_type(schema, namespace, &element)),
+        TypeJson::Entity { .. } | TypeJson::EntityOrCommon { .. } => "EntityRef".to_owned(),
+        TypeJson::Record { attributes, .. } => {
+            let attributes: BTreeMap<_, _> = attributes.iter().collect();
+            let fields: Vec<String> = attributes
+                .into_iter()
+                .map(|(name, t)| {
+                    let optional = if t.is_required() { "" } else { "?" };
+                    format!("{name:?}{optional}: {}", ts_type(schema, namespace, t))
+                })
+                .collect();
+            format!("{{ {} }}", fields.join("; "))
+        }
+    }
+}
+
+fn typescript(schema: &Schema) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "// {HEADER}\n");
+    let _ = writeln!(
+        out,
+        "export interface EntityUid {{\n  type: string;\n  id: string;\n}}\n"
+    );
+    let _ = writeln!(
+        out,
+        "export interface EntityRef {{\n  __entity: EntityUid;\n}}"
+    );
+
+    for model in models(schema) {
+        let (indent, close) = match model.namespace.is_empty() {
+            true => ("", None),
+            false => {
+                let _ = writeln!(
+                    out,
+                    "\nexport namespace {} {{",
+                    model.namespace.replace("::", ".")
+                );
+                ("  ", Some("}"))
+            }
+        };
+        let action_type = qualify(model.namespace, "Action");
+
+        let _ = writeln!(out, "\n{indent}export const EntityTypes = {{");
+        for entity_type in &model.entity_types {
+            let name = entity_type.rsplit("::").next().unwrap_or_default();
+            let _ = writeln!(out, "{indent}  {name:?}: {entity_type:?},");
+        }
+        let _ = writeln!(out, "{indent}}} as const;");
+
+        let _ = writeln!(out, "\n{indent}export const ActionType = {action_type:?};");
+        let _ = writeln!(out, "\n{indent}export const Actions = {{");
+        for (id, _) in &model.actions {
+            let _ = writeln!(
+                out,
+                "{indent}  {id:?}: {{ type: {action_type:?}, id: {id:?} }},"
+            );
+        }
+        let _ = writeln!(out, "{indent}}} as const;");
+
+        for (id, context) in &model.actions {
+            let Some(attributes) = context else {
+                continue;
+            };
+            let _ = writeln!(
+                out,
+                "\n{indent}export interface {}Context {{",
+                pascal_case(id)
+            );
+            for (name, t) in attributes {
+                let optional = if t.is_required() { "" } else { "?" };
+                let t = ts_type(schema, model.namespace, t);
+                let _ = writeln!(out, "{indent}  {name:?}{optional}: {t};");
+            }
+            let _ = writeln!(out, "{indent}}}");
+        }
+
+        if let Some(close) = close {
+            let _ = writeln!(out, "{close}");
+        }
+    }
+    out
+}
+
+fn rust_type(schema: &Schema, namespace: &str, type_json: &TypeJson) -> String {
+    match schema.resolve(namespace, type_json) {
+        TypeJson::Long { .. } => "i64".to_owned(),
+        TypeJson::String { .. } | TypeJson::Extension { .. } => "String".to_owned(),
+        TypeJson::Boolean { .. } => "bool".to_owned(),
+        TypeJson::Set { element, .. } => {
+            format!("Vec<{}>", rust_type(schema, namespace, &element))
+        }
+        TypeJson::Entity { .. } | TypeJson::EntityOrCommon { .. } | TypeJson::Record { .. } => {
+            "serde_json::Value".to_owned()
+        }
+    }
+}
+
+fn rust(schema: &Schema) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "//! {HEADER}");
+
+    for model in models(schema) {
+        let modules: Vec<String> = model
+            .namespace
+            .split("::")
+            .filter(|ns| !ns.is_empty())
+            .map(snake_case)
+            .collect();
+        let indent = "    ".repeat(modules.len());
+        for (depth, module) in modules.iter().enumerate() {
+            let _ = writeln!(out, "\n{}pub mod {module} {{", "    ".repeat(depth));
+        }
+        let action_type = qualify(model.namespace, "Action");
+
+        let _ = writeln!(out, "\n{indent}pub mod entity_types {{");
+        for entity_type in &model.entity_types {
+            let name = entity_type.rsplit("::").next().unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "{indent}    pub const {}: &str = {entity_type:?};",
+                screaming_snake_case(name)
+            );
+        }
+        let _ = writeln!(out, "{indent}}}");
+
+        let _ = writeln!(
+            out,
+            "\n{indent}pub const ACTION_TYPE: &str = {action_type:?};"
+        );
+        let _ = writeln!(out, "\n{indent}pub mod actions {{");
+        for (id, _) in &model.actions {
+            let _ = writeln!(
+                out,
+                "{indent}    pub const {}: &str = {id:?};",
+                screaming_snake_case(id)
+            );
+        }
+        let _ = writeln!(out, "{indent}}}");
+
+        for (id, context) in &model.actions {
+            let Some(attributes) = context else {
+                continue;
+            };
+            let _ = writeln!(
+                out,
+                "\n{indent}#[derive(Debug, Clone, Default, serde::Serialize)]"
+            );
+            let _ = writeln!(out, "{indent}pub struct {}Context {{", pascal_case(id));
+            for (name, t) in attributes {
+                let field = snake_case(name);
+                if field.trim_start_matches("r#") != *name {
+                    let _ = writeln!(out, "{indent}    #[serde(rename = {name:?})]");
+                }
+                let rust_type = rust_type(schema, model.namespace, t);
+                match t.is_required() {
+                    true => {
+                        let _ = writeln!(out, "{indent}    pub {field}: {rust_type},");
+                    }
+                    false => {
+                        let _ = writeln!(
+                            out,
+                            "{indent}    #[serde(skip_serializing_if = \"Option::is_none\")]"
+                        );
+                        let _ = writeln!(out, "{indent}    pub {field}: Option<{rust_type}>,");
+                    }
+                }
+            }
+            let _ = writeln!(out, "{indent}}}");
+        }
+
+        for depth in (0..modules.len()).rev() {
+            let _ = writeln!(out, "{}}}", "    ".repeat(depth));
+        }
+    }
+    out
+}
+
+fn openapi_type(schema: &Schema, namespace: &str, type_json: &TypeJson) -> Value {
+    match schema.resolve(namespace, type_json) {
+        TypeJson::Long { .. } => json!({"type": "integer", "format": "int64"}),
+        TypeJson::String { .. } => json!({"type": "string"}),
+        TypeJson::Extension { name, .. } => json!({"type": "string", "format": name}),
+        TypeJson::Boolean { .. } => json!({"type": "boolean"}),
+        TypeJson::Set { element, .. } => {
+            json!({"type": "array", "items": openapi_type(schema, namespace, &element)})
+        }
+        TypeJson::Entity { .. } | TypeJson::EntityOrCommon { .. } => {
+            json!({"$ref": "#/components/schemas/EntityRef"})
+        }
+        TypeJson::Record { attributes, .. } => {
+            let attributes: BTreeMap<_, _> = attributes
+                .iter()
+                .map(|(name, t)| (name.as_str(), t))
+                .collect();
+            openapi_record(schema, namespace, attributes)
+        }
+    }
+}
+
+fn openapi_record<'a>(
+    schema: &Schema,
+    namespace: &str,
+    attributes: impl IntoIterator<Item = (&'a str, &'a TypeJson)>,
+) -> Value {
+    let mut properties = serde_json::Map::new();
+    let mut required = Vec::new();
+    for (name, t) in attributes {
+        if t.is_required() {
+            required.push(name);
+        }
+        properties.insert(name.to_owned(), openapi_type(schema, namespace, t));
+    }
+    json!({"type": "object", "properties": properties, "required": required})
+}
+
+fn openapi(schema: &Schema) -> String {
+    let mut schemas = serde_json::Map::new();
+    schemas.insert(
+        "EntityRef".to_owned(),
+        json!({
+            "type": "object",
+            "properties": {
+                "__entity": {
+                    "type": "object",
+                    "properties": {"type": {"type": "string"}, "id": {"type": "string"}},
+                    "required": ["type", "id"]
+                }
+            },
+            "required": ["__entity"]
+        }),
+    );
+
+    for model in models(schema) {
+        let prefix = match model.namespace.is_empty() {
+            true => String::new(),
+            false => format!("{}.", model.namespace.replace("::", ".")),
+        };
+        schemas.insert(
+            format!("{prefix}EntityType"),
+            json!({"type": "string", "enum": model.entity_types}),
+        );
+        let ids: Vec<&str> = model.actions.iter().map(|(id, _)| *id).collect();
+        schemas.insert(
+            format!("{prefix}Action"),
+            json!({"type": "string", "enum": ids}),
+        );
+        for (id, context) in &model.actions {
+            let Some(attributes) = context else {
+                continue;
+            };
+            schemas.insert(
+                format!("{prefix}{}Context", pascal_case(id)),
+                openapi_record(
+                    schema,
+                    model.namespace,
+                    attributes.iter().map(|(name, t)| (name.as_str(), t)),
+                ),
+            );
+        }
+    }
+
+    let document = json!({
+        "info": {"description": HEADER},
+        "components": {"schemas": schemas}
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_default()
+}
+
+/// Typed constants for the entity types and actions of `schema`, and the shapes of the
+/// contexts its actions declare. The shared common types must be resolved in the schema.
+pub fn generate(schema: &Schema, lang: SdkLang) -> String {
+    match lang {
+        SdkLang::Ts => typescript(schema),
+        SdkLang::Rust => rust(schema),
+        SdkLang::Openapi => openapi(schema),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Schema {
+        serde_json::from_value(json!({
+            "Acme::App": {
+                "entityTypes": {"User": {}, "Document": {}},
+                "actions": {
+                    "readDocument": {
+                        "appliesTo": {
+                            "principalTypes": ["User"],
+                            "resourceTypes": ["Document"],
+                            "context": {"type": "EntityOrCommon", "name": "Ctx"}
+                        }
+                    }
+                },
+                "commonTypes": {
+                    "Ctx": {
+                        "type": "Record",
+                        "attributes": {
+                            "sourceIp": {"type": "Extension", "name": "ipaddr"},
+                            "level": {"type": "Long", "required": false},
+                            "type": {"type": "String"}
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generate_typescript() {
+        let ts = generate(&schema(), SdkLang::Ts);
+
+        assert!(ts.contains("export namespace Acme.App {"));
+        assert!(ts.contains("    \"Document\": \"Acme::App::Document\","));
+        assert!(ts.contains(
+            "    \"readDocument\": { type: \"Acme::App::Action\", id: \"readDocument\" },"
+        ));
+        assert!(ts.contains("  export interface ReadDocumentContext {"));
+        assert!(ts.contains("    \"level\"?: number;"));
+        assert!(ts.contains("    \"sourceIp\": string;"));
+    }
+
+    #[test]
+    fn test_generate_rust() {
+        let rust = generate(&schema(), SdkLang::Rust);
+
+        assert!(rust.contains("pub mod acme {\n\n    pub mod app {"));
+        assert!(rust.contains("            pub const READ_DOCUMENT: &str = \"readDocument\";"));
+        assert!(rust.contains("        pub struct ReadDocumentContext {"));
+        assert!(rust.contains(
+            "            #[serde(rename = \"sourceIp\")]\n            pub source_ip: String,"
+        ));
+        assert!(rust.contains("            pub level: Option<i64>,"));
+        assert!(rust.contains("            pub r#type: String,"));
+    }
+
+    #[test]
+    fn test_generate_openapi() {
+        let openapi: Value = serde_json::from_str(&generate(&schema(), SdkLang::Openapi)).unwrap();
+        let schemas = &openapi["components"]["schemas"];
+
+        assert_eq!(
+            schemas["Acme.App.Action"],
+            json!({"type": "string", "enum": ["readDocument"]})
+        );
+        assert_eq!(
+            schemas["Acme.App.ReadDocumentContext"]["required"],
+            json!(["sourceIp", "type"])
+        );
+    }
+}
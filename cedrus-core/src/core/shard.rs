@@ -0,0 +1,217 @@
+use std::sync::{
+    Arc, RwLock,
+    atomic::{AtomicU64, Ordering},
+};
+
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::ShardConfig;
+
+/// Points each node takes on the ring by default, spreading the projects evenly.
+pub const DEFAULT_VIRTUAL_NODES: usize = 64;
+
+fn ring_hash(key: &[u8]) -> u64 {
+    let digest = Sha256::digest(key);
+    u64::from_be_bytes(digest[..8].try_into().unwrap_or_default())
+}
+
+/// Consistent hash ring of the nodes of a cluster. Each node takes `virtual_nodes` points, a
+/// project belongs to the node of the first point at or after its hash, so adding or removing
+/// a node only moves the projects of its own points.
+#[derive(Debug, Default, Clone)]
+pub struct HashRing {
+    points: Vec<(u64, usize)>,
+    nodes: Vec<String>,
+}
+
+impl HashRing {
+    pub fn new(nodes: &[String], virtual_nodes: usize) -> Self {
+        let mut nodes = nodes.to_vec();
+        nodes.sort();
+        nodes.dedup();
+
+        let mut points = Vec::new();
+        for (index, node) in nodes.iter().enumerate() {
+            for point in 0..virtual_nodes.max(1) {
+                points.push((ring_hash(format!("{node}#{point}").as_bytes()), index));
+            }
+        }
+        points.sort();
+
+        Self { points, nodes }
+    }
+
+    /// Node owning a project, `None` on an empty ring.
+    pub fn owner(&self, project_id: &Uuid) -> Option<&str> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let hash = ring_hash(project_id.as_bytes());
+        let position = self.points.partition_point(|(point, _)| *point < hash);
+        let (_, index) = self.points[position % self.points.len()];
+        Some(&self.nodes[index])
+    }
+}
+
+/// Projects this node owns, rebuilding their compiled entities and policies as soon as an
+/// event changes them. The other projects are only marked stale, and rebuilt from the Cache on
+/// their next request, so a change is not compiled again by every node of the cluster. Without
+/// a ring, as on a single node, every project is owned. The admin project always is, it
+/// authorizes every request.
+#[derive(Debug, Default)]
+pub struct ShardOwnership {
+    node_id: String,
+    ring: RwLock<Option<HashRing>>,
+    /// Generation of the last time each stale project was marked
+    stale: DashMap<Uuid, u64>,
+    generation: AtomicU64,
+    rebuild_locks: DashMap<Uuid, Arc<tokio::sync::Mutex<()>>>,
+}
+
+impl ShardOwnership {
+    pub fn new(conf: &ShardConfig) -> Self {
+        let ring = HashRing::new(
+            &conf.nodes,
+            conf.virtual_nodes.unwrap_or(DEFAULT_VIRTUAL_NODES),
+        );
+        Self {
+            node_id: conf.node_id.clone(),
+            ring: RwLock::new(Some(ring)),
+            ..Default::default()
+        }
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Replaces the nodes of the ring, e.g. as nodes join or leave.
+    pub fn set_nodes(&self, nodes: &[String], virtual_nodes: usize) {
+        if let Ok(mut ring) = self.ring.write() {
+            *ring = Some(HashRing::new(nodes, virtual_nodes));
+        }
+    }
+
+    /// Whether the projects are spread over the nodes of a ring, rather than all owned.
+    pub fn is_sharded(&self) -> bool {
+        self.ring.read().map(|ring| ring.is_some()).unwrap_or(false)
+    }
+
+    pub fn owns(&self, project_id: &Uuid) -> bool {
+        if project_id.is_nil() {
+            return true;
+        }
+        let Ok(ring) = self.ring.read() else {
+            return true;
+        };
+        match ring.as_ref().and_then(|ring| ring.owner(project_id)) {
+            Some(owner) => owner == self.node_id,
+            None => true,
+        }
+    }
+
+    pub fn mark_stale(&self, project_id: &Uuid) {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        self.stale.insert(*project_id, generation);
+    }
+
+    /// Generation the project was last marked stale at, `None` when it is not.
+    pub fn stale_generation(&self, project_id: &Uuid) -> Option<u64> {
+        self.stale.get(project_id).map(|generation| *generation)
+    }
+
+    /// Clears a project rebuilt from the state of the given generation, unless it was marked
+    /// stale again meanwhile.
+    pub fn clear_stale(&self, project_id: &Uuid, generation: u64) {
+        self.stale
+            .remove_if(project_id, |_, current| *current == generation);
+    }
+
+    pub fn is_stale(&self, project_id: &Uuid) -> bool {
+        self.stale.contains_key(project_id)
+    }
+
+    /// Lock serializing the rebuilds of a project, requests waiting on it for the rebuild in
+    /// progress rather than deciding on the stale state.
+    pub fn rebuild_lock(&self, project_id: &Uuid) -> Arc<tokio::sync::Mutex<()>> {
+        self.rebuild_locks
+            .entry(*project_id)
+            .or_default()
+            .value()
+            .clone()
+    }
+
+    pub fn remove(&self, project_id: &Uuid) {
+        self.stale.remove(project_id);
+        self.rebuild_locks.remove(project_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_hash_ring() {
+        let ring = HashRing::new(&nodes(&["a", "b", "c"]), DEFAULT_VIRTUAL_NODES);
+        let projects: Vec<Uuid> = (0..300).map(|_| Uuid::new_v4()).collect();
+
+        // Every node gets a share, and the same order of nodes is not needed
+        let reordered = HashRing::new(&nodes(&["c", "a", "b"]), DEFAULT_VIRTUAL_NODES);
+        for name in ["a", "b", "c"] {
+            assert!(projects.iter().any(|id| ring.owner(id) == Some(name)));
+        }
+        assert!(
+            projects
+                .iter()
+                .all(|id| ring.owner(id) == reordered.owner(id))
+        );
+
+        // Removing a node only moves its projects
+        let smaller = HashRing::new(&nodes(&["a", "b"]), DEFAULT_VIRTUAL_NODES);
+        for id in &projects {
+            if ring.owner(id) != Some("c") {
+                assert_eq!(ring.owner(id), smaller.owner(id));
+            }
+        }
+
+        assert_eq!(HashRing::default().owner(&Uuid::nil()), None);
+    }
+
+    #[test]
+    fn test_shard_ownership() {
+        let shards = ShardOwnership::default();
+        assert!(shards.owns(&Uuid::now_v7()));
+
+        let shards = ShardOwnership::new(&ShardConfig {
+            node_id: "a".to_string(),
+            nodes: nodes(&["a", "b"]),
+            virtual_nodes: None,
+        });
+        let projects: Vec<Uuid> = (0..100).map(|_| Uuid::new_v4()).collect();
+        assert!(projects.iter().any(|id| shards.owns(id)));
+        assert!(projects.iter().any(|id| !shards.owns(id)));
+        assert!(shards.owns(&Uuid::nil()));
+
+        assert!(shards.is_sharded());
+        assert!(!ShardOwnership::default().is_sharded());
+
+        // A rebuild only clears the generation it started from
+        let project_id = projects[0];
+        assert_eq!(shards.stale_generation(&project_id), None);
+        shards.mark_stale(&project_id);
+        let generation = shards.stale_generation(&project_id).unwrap();
+        shards.mark_stale(&project_id);
+        shards.clear_stale(&project_id, generation);
+        assert!(shards.is_stale(&project_id));
+        let generation = shards.stale_generation(&project_id).unwrap();
+        shards.clear_stale(&project_id, generation);
+        assert!(!shards.is_stale(&project_id));
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use uuid::Uuid;
+
+/// Last event of a project applied by this node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppliedEvent {
+    pub applied_at: DateTime<Utc>,
+    /// Delay between the last event of another node being sent and applied, `None` until one
+    /// was received
+    pub lag: Option<Duration>,
+}
+
+/// Events applied to each project on this node. A node misses no change of a project between
+/// its last applied event and the time it last knew it received the events of every node, so
+/// the later of the two bounds how stale the data it serves may be.
+#[derive(Debug, Default)]
+pub struct EventStaleness {
+    applied: DashMap<Uuid, AppliedEvent>,
+}
+
+impl EventStaleness {
+    /// Records an event of the project applied, or the project loaded, `sent_at` being set
+    /// on the events of other nodes.
+    pub fn applied(&self, project_id: &Uuid, sent_at: Option<DateTime<Utc>>) {
+        let applied_at = Utc::now();
+        let lag = sent_at.map(|sent_at| (applied_at - sent_at).max(Duration::zero()));
+        let mut entry = self.applied.entry(*project_id).or_insert(AppliedEvent {
+            applied_at,
+            lag: None,
+        });
+        entry.applied_at = applied_at;
+        entry.lag = lag.or(entry.lag);
+    }
+
+    pub fn get(&self, project_id: &Uuid) -> Option<AppliedEvent> {
+        self.applied.get(project_id).map(|applied| *applied)
+    }
+
+    /// How long the data of the project may have changed elsewhere without this node knowing,
+    /// given when it last knew it was in sync.
+    pub fn staleness(&self, project_id: &Uuid, synced_at: DateTime<Utc>) -> Option<Duration> {
+        let applied = self.get(project_id)?;
+        Some((Utc::now() - applied.applied_at.max(synced_at)).max(Duration::zero()))
+    }
+
+    pub fn projects(&self) -> Vec<(Uuid, AppliedEvent)> {
+        self.applied
+            .iter()
+            .map(|applied| (*applied.key(), *applied.value()))
+            .collect()
+    }
+
+    pub fn remove(&self, project_id: &Uuid) {
+        self.applied.remove(project_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_staleness() {
+        let staleness = EventStaleness::default();
+        let project_id = Uuid::now_v7();
+        assert_eq!(staleness.staleness(&project_id, Utc::now()), None);
+
+        staleness.applied(&project_id, Some(Utc::now() - Duration::seconds(2)));
+        staleness.applied(&project_id, None);
+        let applied = staleness.get(&project_id).unwrap();
+        assert!(applied.lag.unwrap() >= Duration::seconds(2));
+
+        // Known in sync since, the project is as stale as its last applied event
+        let synced_at = applied.applied_at - Duration::seconds(60);
+        assert!(staleness.staleness(&project_id, synced_at).unwrap() < Duration::seconds(60));
+        staleness.applied.get_mut(&project_id).unwrap().applied_at = synced_at;
+        assert!(staleness.staleness(&project_id, synced_at).unwrap() >= Duration::seconds(60));
+        assert_eq!(
+            staleness.staleness(&project_id, Utc::now() + Duration::seconds(1)),
+            Some(Duration::zero())
+        );
+
+        staleness.remove(&project_id);
+        assert_eq!(staleness.get(&project_id), None);
+    }
+}
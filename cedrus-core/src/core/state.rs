@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use cedrus_cedar::{Policy, PolicyId, PolicySet, Schema, Template, TemplateLink, schema::TypeJson};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::CedrusError;
+
+/// Complete description of the schema, policies, templates and template links of a
+/// project, which the project is made to match.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Schema>,
+    pub policies: HashMap<PolicyId, Policy>,
+    pub templates: HashMap<PolicyId, Template>,
+    pub template_links: Vec<TemplateLink>,
+}
+
+impl ProjectState {
+    /// Sets the `id` annotation of the policies and templates, as stored once added.
+    pub fn annotate(&mut self) {
+        for (id, policy) in self.policies.iter_mut() {
+            policy
+                .annotations
+                .insert("id".to_string(), Some(id.to_string()));
+        }
+        for (id, template) in self.templates.iter_mut() {
+            template
+                .annotations
+                .insert("id".to_string(), Some(id.to_string()));
+        }
+    }
+
+    /// Policies, templates and template links of the state.
+    pub fn policy_set(&self) -> PolicySet {
+        PolicySet {
+            static_policies: self.policies.clone(),
+            templates: self.templates.clone(),
+            template_links: self.template_links.clone(),
+        }
+    }
+
+    /// Checks the schema, with the library `common_types` it refers to, and that the
+    /// policies, templates and links form a PolicySet.
+    pub fn validate(&self, common_types: &HashMap<String, TypeJson>) -> Result<(), CedrusError> {
+        if let Some(schema) = &self.schema {
+            let _: cedar_policy::Schema =
+                schema.clone().with_common_types(common_types).try_into()?;
+        }
+        let _: cedar_policy::PolicySet = self.policy_set().try_into()?;
+
+        Ok(())
+    }
+}
+
+/// Changes making a project match a state.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatePlan {
+    pub schema_changed: bool,
+    pub policies_changed: Vec<PolicyId>,
+    pub policies_removed: Vec<PolicyId>,
+    pub templates_changed: Vec<PolicyId>,
+    pub templates_removed: Vec<PolicyId>,
+    pub template_links_changed: Vec<PolicyId>,
+    pub template_links_removed: Vec<PolicyId>,
+}
+
+// Ids of the desired items missing or different in the current ones, and of the current
+// items missing in the desired ones
+fn diff<T: Serialize>(
+    current: &HashMap<PolicyId, T>,
+    desired: &HashMap<PolicyId, T>,
+) -> Result<(Vec<PolicyId>, Vec<PolicyId>), CedrusError> {
+    let mut changed = Vec::new();
+    for (id, item) in desired {
+        if current.get(id).map(serde_json::to_value).transpose()?
+            != Some(serde_json::to_value(item)?)
+        {
+            changed.push(id.clone());
+        }
+    }
+    let mut removed: Vec<PolicyId> = current
+        .keys()
+        .filter(|id| !desired.contains_key(*id))
+        .cloned()
+        .collect();
+    changed.sort();
+    removed.sort();
+
+    Ok((changed, removed))
+}
+
+fn links_by_id(links: &[TemplateLink]) -> HashMap<PolicyId, &TemplateLink> {
+    links.iter().map(|tl| (tl.new_id.clone(), tl)).collect()
+}
+
+impl StatePlan {
+    pub fn new(current: &ProjectState, desired: &ProjectState) -> Result<Self, CedrusError> {
+        let (policies_changed, policies_removed) = diff(&current.policies, &desired.policies)?;
+        let (templates_changed, templates_removed) = diff(&current.templates, &desired.templates)?;
+        let (template_links_changed, template_links_removed) = diff(
+            &links_by_id(&current.template_links),
+            &links_by_id(&desired.template_links),
+        )?;
+
+        Ok(Self {
+            schema_changed: serde_json::to_value(&current.schema)?
+                != serde_json::to_value(&desired.schema)?,
+            policies_changed,
+            policies_removed,
+            templates_changed,
+            templates_removed,
+            template_links_changed,
+            template_links_removed,
+        })
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.schema_changed
+            || !self.policies_changed.is_empty()
+            || !self.policies_removed.is_empty()
+            || !self.templates_changed.is_empty()
+            || !self.templates_removed.is_empty()
+            || !self.template_links_changed.is_empty()
+            || !self.template_links_removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(effect: &str) -> Policy {
+        serde_json::from_value(serde_json::json!({
+            "effect": effect,
+            "principal": {"op": "All"},
+            "action": {"op": "All"},
+            "resource": {"op": "All"},
+            "conditions": []
+        }))
+        .unwrap()
+    }
+
+    fn id(id: &str) -> PolicyId {
+        PolicyId::from(id.to_string())
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut current = ProjectState {
+            policies: [
+                (id("same"), policy("permit")),
+                (id("changed"), policy("permit")),
+                (id("removed"), policy("permit")),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let mut desired = ProjectState {
+            policies: [
+                (id("same"), policy("permit")),
+                (id("changed"), policy("forbid")),
+                (id("added"), policy("permit")),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        current.annotate();
+        desired.annotate();
+
+        let plan = StatePlan::new(&current, &desired).unwrap();
+        assert!(plan.has_changes());
+        assert!(!plan.schema_changed);
+        assert_eq!(plan.policies_changed, vec![id("added"), id("changed")]);
+        assert_eq!(plan.policies_removed, vec![id("removed")]);
+
+        let plan = StatePlan::new(&desired, &desired).unwrap();
+        assert!(!plan.has_changes());
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, HashSet};
+
+use cedrus_cedar::{Entity, EntityUid};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+/// Desired state of the entities of a project. When `entity_types` is not empty only the
+/// entities of those types are synchronized, the rest of the project is left untouched.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EntitiesSync {
+    pub entities: Vec<Entity>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entity_types: Vec<String>,
+}
+
+impl EntitiesSync {
+    pub fn in_scope(&self, uid: &EntityUid) -> bool {
+        self.entity_types.is_empty() || self.entity_types.iter().any(|t| t == uid.type_name())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EntitiesSyncReport {
+    pub added: Vec<EntityUid>,
+    pub updated: Vec<EntityUid>,
+    pub removed: Vec<EntityUid>,
+}
+
+impl EntitiesSyncReport {
+    /// Computes the changes turning `current` into `desired`. Entities are compared by their
+    /// full content, not only by uid.
+    pub fn diff(current: &[Entity], desired: &[Entity]) -> Result<Self, serde_json::Error> {
+        fn keyed(
+            entities: &[Entity],
+        ) -> Result<BTreeMap<String, (&Entity, Value)>, serde_json::Error> {
+            entities
+                .iter()
+                .map(|e| Ok((e.uid().to_string(), (e, serde_json::to_value(e)?))))
+                .collect()
+        }
+
+        let current = keyed(current)?;
+        let desired = keyed(desired)?;
+
+        let mut report = Self::default();
+        for (key, (entity, value)) in &desired {
+            match current.get(key) {
+                None => report.added.push(entity.uid().clone()),
+                Some((_, current)) if current != value => report.updated.push(entity.uid().clone()),
+                Some(_) => {}
+            }
+        }
+        for (key, (entity, _)) in &current {
+            if !desired.contains_key(key) {
+                report.removed.push(entity.uid().clone());
+            }
+        }
+
+        Ok(report)
+    }
+
+    pub fn changed(&self) -> HashSet<EntityUid> {
+        self.added
+            .iter()
+            .chain(self.updated.iter())
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
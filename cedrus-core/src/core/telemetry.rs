@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use cedrus_cedar::{Context, EntityUid, Schema};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Default)]
+struct ActionCounters {
+    requests: u64,
+    sizes: BTreeMap<usize, u64>,
+    attributes: HashMap<String, HashMap<String, u64>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextAttributeUsage {
+    pub name: String,
+    /// Requests carrying the attribute
+    pub requests: u64,
+    /// Requests carrying the attribute by type of its value, e.g. `Long` or `Entity<User>`
+    pub types: BTreeMap<String, u64>,
+    /// Whether the schema declares it in the context of the action
+    pub declared: bool,
+    /// Whether a policy applying to the action reads it
+    pub referenced: bool,
+}
+
+/// Context attributes of the requests for an action, alongside the ones the schema declares
+/// and the policies read, so the ones no policy reads can be pruned.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionContextUsage {
+    pub action: EntityUid,
+    pub requests: u64,
+    /// Requests by number of context attributes they carry
+    pub sizes: BTreeMap<usize, u64>,
+    pub attributes: Vec<ContextAttributeUsage>,
+}
+
+/// Context attributes seen in the authorization requests of each project, per action, since
+/// the node started.
+#[derive(Debug, Default)]
+pub struct ContextTelemetry {
+    projects: DashMap<Uuid, HashMap<EntityUid, ActionCounters>>,
+}
+
+impl ContextTelemetry {
+    pub fn record(&self, project_id: &Uuid, action: &EntityUid, context: Option<&Context>) {
+        let mut actions = self.projects.entry(*project_id).or_default();
+        if !actions.contains_key(action) {
+            actions.insert(action.clone(), ActionCounters::default());
+        }
+        let Some(counters) = actions.get_mut(action) else {
+            return;
+        };
+
+        counters.requests += 1;
+        *counters
+            .sizes
+            .entry(context.map(Context::len).unwrap_or_default())
+            .or_default() += 1;
+        for (name, attr) in context.iter().flat_map(|context| context.iter()) {
+            if !counters.attributes.contains_key(name) {
+                counters.attributes.insert(name.clone(), HashMap::new());
+            }
+            if let Some(types) = counters.attributes.get_mut(name) {
+                *types.entry(attr.type_name()).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn remove(&self, project_id: &Uuid) {
+        self.projects.remove(project_id);
+    }
+
+    /// Usage of the context of every action requested, sorted by action. Policies in an
+    /// action group apply to its members when the schema declares the group.
+    pub fn report(
+        &self,
+        project_id: &Uuid,
+        schema: Option<&Schema>,
+        cedar_schema: Option<&cedar_policy::Schema>,
+        policies: &cedar_policy::PolicySet,
+    ) -> Vec<ActionContextUsage> {
+        let Some(actions) = self.projects.get(project_id) else {
+            return Vec::new();
+        };
+        let action_entities = cedar_schema.and_then(|schema| schema.action_entities().ok());
+
+        let policies: Vec<(cedar_policy::ActionConstraint, BTreeSet<String>)> = policies
+            .policies()
+            .map(|policy| {
+                let mut names = BTreeSet::new();
+                if let Ok(json) = policy.to_json() {
+                    context_references(&json, &mut names);
+                }
+                (policy.action_constraint(), names)
+            })
+            .collect();
+
+        let mut report: Vec<ActionContextUsage> = actions
+            .iter()
+            // Actions that are no valid entity uid were rejected, never evaluated
+            .filter_map(|(action, counters)| {
+                let cedar_action: cedar_policy::EntityUid = action.clone().try_into().ok()?;
+                let mut groups = vec![&cedar_action];
+                if let Some(ancestors) = action_entities
+                    .as_ref()
+                    .and_then(|entities| entities.ancestors(&cedar_action))
+                {
+                    groups.extend(ancestors);
+                }
+
+                let referenced: BTreeSet<&String> = policies
+                    .iter()
+                    .filter(|(constraint, _)| match constraint {
+                        cedar_policy::ActionConstraint::Any => true,
+                        cedar_policy::ActionConstraint::Eq(uid) => *uid == cedar_action,
+                        cedar_policy::ActionConstraint::In(uids) => {
+                            uids.iter().any(|uid| groups.contains(&uid))
+                        }
+                    })
+                    .flat_map(|(_, names)| names)
+                    .collect();
+                let declared: BTreeSet<String> = schema
+                    .and_then(|schema| schema.action_context_attributes(action))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect();
+
+                let names: BTreeSet<&String> = counters
+                    .attributes
+                    .keys()
+                    .chain(declared.iter())
+                    .chain(referenced.iter().copied())
+                    .collect();
+                let attributes = names
+                    .into_iter()
+                    .map(|name| {
+                        let types: BTreeMap<String, u64> = counters
+                            .attributes
+                            .get(name)
+                            .map(|types| types.clone().into_iter().collect())
+                            .unwrap_or_default();
+                        ContextAttributeUsage {
+                            name: name.clone(),
+                            requests: types.values().sum(),
+                            types,
+                            declared: declared.contains(name),
+                            referenced: referenced.contains(name),
+                        }
+                    })
+                    .collect();
+
+                Some(ActionContextUsage {
+                    action: action.clone(),
+                    requests: counters.requests,
+                    sizes: counters.sizes.clone(),
+                    attributes,
+                })
+            })
+            .collect();
+        report.sort_by(|a, b| a.action.cmp(&b.action));
+        report
+    }
+}
+
+// Names of the `context` attributes a policy in the JSON format reads or tests
+fn context_references(value: &Value, names: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if key == "." || key == "has" {
+                    let is_context = value
+                        .get("left")
+                        .and_then(|left| left.get("Var"))
+                        .is_some_and(|var| var == "context");
+                    if let Some(attr) = value.get("attr").and_then(Value::as_str)
+                        && is_context
+                    {
+                        names.insert(attr.to_owned());
+                    }
+                }
+                context_references(value, names);
+            }
+        }
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| context_references(value, names)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_context_usage() {
+        let schema: Schema = serde_json::from_value(json!({
+            "App": {
+                "entityTypes": {"User": {}, "Document": {}},
+                "actions": {
+                    "read": {"appliesTo": {
+                        "principalTypes": ["User"],
+                        "resourceTypes": ["Document"],
+                        "context": {"type": "Record", "attributes": {
+                            "mfa": {"type": "Boolean"},
+                            "region": {"type": "String", "required": false}
+                        }}
+                    }}
+                }
+            }
+        }))
+        .unwrap();
+        let cedar_schema: cedar_policy::Schema = schema.clone().try_into().unwrap();
+        let policies = cedar_policy::PolicySet::from_str(
+            r#"permit(principal, action in [App::Action::"read"], resource) when { context.mfa };
+            permit(principal, action == App::Action::"write", resource) when { context has ip };"#,
+        )
+        .unwrap();
+
+        let action = EntityUid::from("App::Action::read");
+        let context: Context =
+            serde_json::from_value(json!({"mfa": true, "device": "phone"})).unwrap();
+        let project_id = Uuid::nil();
+        let telemetry = ContextTelemetry::default();
+        telemetry.record(&project_id, &action, Some(&context));
+        telemetry.record(&project_id, &action, None);
+
+        let report = telemetry.report(&project_id, Some(&schema), Some(&cedar_schema), &policies);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].requests, 2);
+        assert_eq!(report[0].sizes, BTreeMap::from([(0, 1), (2, 1)]));
+
+        let attributes: Vec<(&str, u64, bool, bool)> = report[0]
+            .attributes
+            .iter()
+            .map(|a| (a.name.as_str(), a.requests, a.declared, a.referenced))
+            .collect();
+        assert_eq!(
+            attributes,
+            vec![
+                ("device", 1, false, false),
+                ("mfa", 1, true, true),
+                ("region", 0, true, false),
+            ]
+        );
+        assert_eq!(
+            report[0].attributes[1].types,
+            BTreeMap::from([("Boolean".to_string(), 1)])
+        );
+    }
+}
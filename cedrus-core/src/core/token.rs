@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+use cedrus_cedar::Entity;
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::TokenCacheConfig;
+
+#[derive(Debug)]
+struct VerifiedToken {
+    entity: Entity,
+    expires: Instant,
+}
+
+/// Principals of the JWTs verified for each project, keyed by a digest of the token, so a
+/// caller sending the same token is not verified again until it expires or `ttl` elapses.
+/// The entries of a project are dropped as soon as its identity source changes.
+#[derive(Debug)]
+pub struct TokenCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: DashMap<(Uuid, [u8; 32]), VerifiedToken>,
+    /// Invalidations of each project, an entry only being kept when none happened while its
+    /// token was verified
+    generations: DashMap<Uuid, u64>,
+}
+
+impl Default for TokenCache {
+    fn default() -> Self {
+        Self::new(&TokenCacheConfig::default())
+    }
+}
+
+impl TokenCache {
+    pub fn new(conf: &TokenCacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(conf.ttl),
+            max_entries: conf.max_entries,
+            entries: DashMap::new(),
+            generations: DashMap::new(),
+        }
+    }
+
+    fn key(project_id: &Uuid, token: &str) -> (Uuid, [u8; 32]) {
+        (*project_id, Sha256::digest(token.as_bytes()).into())
+    }
+
+    /// Principal of a token verified earlier for the project, while still valid.
+    pub fn get(&self, project_id: &Uuid, token: &str) -> Option<Entity> {
+        let key = Self::key(project_id, token);
+        {
+            let verified = self.entries.get(&key)?;
+            if verified.expires > Instant::now() {
+                return Some(verified.entity.clone());
+            }
+        }
+        self.entries.remove(&key);
+        None
+    }
+
+    /// Invalidations of the project so far, taken before verifying a token.
+    pub fn generation(&self, project_id: &Uuid) -> u64 {
+        self.generations
+            .get(project_id)
+            .map(|generation| *generation)
+            .unwrap_or_default()
+    }
+
+    /// Keeps the principal of a token verified since `generation`, until its `exp` (seconds
+    /// since the epoch) or `ttl`, whichever comes first. Tokens without `exp` are not kept.
+    pub fn insert(
+        &self,
+        project_id: &Uuid,
+        token: &str,
+        entity: Entity,
+        exp: Option<u64>,
+        generation: u64,
+    ) {
+        let Some(exp) = exp else {
+            return;
+        };
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        let lifetime = Duration::from_secs(exp.saturating_sub(now)).min(self.ttl);
+        if lifetime.is_zero() {
+            return;
+        }
+        if self.entries.len() >= self.max_entries {
+            let now = Instant::now();
+            self.entries.retain(|_, verified| verified.expires > now);
+            if self.entries.len() >= self.max_entries {
+                return;
+            }
+        }
+
+        // Holding the generation of the project while inserting, an invalidation either
+        // happened before and is seen, or happens after and drops the entry
+        let current = self.generations.entry(*project_id).or_default();
+        if *current != generation {
+            return;
+        }
+        self.entries.insert(
+            Self::key(project_id, token),
+            VerifiedToken {
+                entity,
+                expires: Instant::now() + lifetime,
+            },
+        );
+    }
+
+    /// Drops the tokens verified for a project, its identity source having changed.
+    pub fn invalidate(&self, project_id: &Uuid) {
+        *self.generations.entry(*project_id).or_default() += 1;
+        self.entries.retain(|(id, _), _| id != project_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use cedrus_cedar::EntityUid;
+
+    use super::*;
+
+    #[test]
+    fn test_token_cache() {
+        let cache = TokenCache::new(&TokenCacheConfig {
+            ttl: 60,
+            max_entries: 2,
+        });
+        let project_id = Uuid::now_v7();
+        let other_id = Uuid::now_v7();
+        let entity = Entity::new_no_attrs(EntityUid::from("User::alice"), HashSet::new());
+        let exp = chrono::Utc::now().timestamp() as u64 + 3600;
+
+        cache.insert(&project_id, "token", entity.clone(), Some(exp), 0);
+        assert_eq!(cache.get(&project_id, "token"), Some(entity.clone()));
+        assert_eq!(cache.get(&other_id, "token"), None);
+
+        // Without exp, expired, or verified before an invalidation, tokens are not kept
+        cache.insert(&other_id, "no-exp", entity.clone(), None, 0);
+        cache.insert(&other_id, "expired", entity.clone(), Some(1), 0);
+        let generation = cache.generation(&other_id);
+        cache.invalidate(&other_id);
+        cache.insert(&other_id, "stale", entity.clone(), Some(exp), generation);
+        assert_eq!(cache.len(), 1);
+
+        cache.insert(&other_id, "token", entity.clone(), Some(exp), 1);
+        cache.insert(&other_id, "full", entity.clone(), Some(exp), 1);
+        assert_eq!(cache.len(), 2);
+
+        cache.invalidate(&project_id);
+        assert_eq!(cache.get(&project_id, "token"), None);
+        assert_eq!(cache.get(&other_id, "token"), Some(entity));
+    }
+}
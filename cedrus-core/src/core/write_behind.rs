@@ -0,0 +1,376 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use cedrus_cedar::{Entity, EntityUid, Policy, PolicyId};
+use tokio::sync::{Notify, Semaphore};
+use uuid::Uuid;
+
+use crate::{
+    core::{audit::AuditRecord, dry_run, history::Revision},
+    db::{Database, DatabaseError},
+};
+
+/// A Database write deferred for a project in write-behind mode, or retried after it failed.
+#[derive(Debug, Clone)]
+pub enum WriteOp {
+    SaveEntities(Uuid, Vec<Entity>),
+    RemoveEntities(Uuid, Vec<EntityUid>),
+    SavePolicies(Uuid, HashMap<PolicyId, Policy>),
+    RemovePolicies(Uuid, Vec<PolicyId>),
+    SaveRevisions(Uuid, Vec<Revision>),
+    SaveAudit(Uuid, AuditRecord),
+}
+
+impl WriteOp {
+    pub fn project_id(&self) -> &Uuid {
+        match self {
+            WriteOp::SaveEntities(project_id, _)
+            | WriteOp::RemoveEntities(project_id, _)
+            | WriteOp::SavePolicies(project_id, _)
+            | WriteOp::RemovePolicies(project_id, _)
+            | WriteOp::SaveRevisions(project_id, _)
+            | WriteOp::SaveAudit(project_id, _) => project_id,
+        }
+    }
+
+    /// Name of the write, for logs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WriteOp::SaveEntities(..) => "saveEntities",
+            WriteOp::RemoveEntities(..) => "removeEntities",
+            WriteOp::SavePolicies(..) => "savePolicies",
+            WriteOp::RemovePolicies(..) => "removePolicies",
+            WriteOp::SaveRevisions(..) => "saveRevisions",
+            WriteOp::SaveAudit(..) => "saveAudit",
+        }
+    }
+
+    async fn apply(&self, db: &(dyn Database + Send + Sync)) -> Result<(), DatabaseError> {
+        match self {
+            WriteOp::SaveEntities(project_id, entities) => {
+                db.project_entities_save(project_id, entities).await
+            }
+            WriteOp::RemoveEntities(project_id, entity_uids) => {
+                db.project_entities_remove(project_id, entity_uids).await
+            }
+            WriteOp::SavePolicies(project_id, policies) => {
+                db.project_policies_save(project_id, policies).await
+            }
+            WriteOp::RemovePolicies(project_id, policy_ids) => {
+                db.project_policies_remove(project_id, policy_ids).await
+            }
+            WriteOp::SaveRevisions(project_id, revisions) => {
+                db.project_revisions_save(project_id, revisions).await
+            }
+            WriteOp::SaveAudit(project_id, record) => {
+                db.project_audit_save(project_id, record).await
+            }
+        }
+    }
+}
+
+/// Writes the queue holds by default before `push` waits for a flush to make room.
+pub const WRITE_BEHIND_CAPACITY: usize = 10_000;
+/// Attempts of a write failing with a transient error before it is dead-lettered.
+pub const WRITE_BEHIND_MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Debug)]
+struct PendingOp {
+    op: WriteOp,
+    attempts: u32,
+}
+
+/// A deferred write given up on: the Database rejected it, or kept failing past the retry cap.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub op: WriteOp,
+    pub attempts: u32,
+    pub error: String,
+    pub at: chrono::DateTime<chrono::Utc>,
+}
+
+/// FIFO of the deferred writes of this node, flushed to the Database in order by a
+/// single writer so the writes of a project are never reordered. The queue is bounded:
+/// once full, `push` waits for a flush to make room, slowing writers down to the pace of
+/// the Database instead of growing without limit.
+#[derive(Debug)]
+pub struct WriteBehindQueue {
+    ops: Mutex<VecDeque<PendingOp>>,
+    // Free places in the queue
+    slots: Semaphore,
+    capacity: usize,
+    max_attempts: u32,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+    notify: Notify,
+    // Held while writing, so discarded writes are never in flight
+    flushing: tokio::sync::Mutex<()>,
+}
+
+impl Default for WriteBehindQueue {
+    fn default() -> Self {
+        Self::new(WRITE_BEHIND_CAPACITY, WRITE_BEHIND_MAX_ATTEMPTS)
+    }
+}
+
+impl WriteBehindQueue {
+    pub fn new(capacity: usize, max_attempts: u32) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            ops: Mutex::new(VecDeque::new()),
+            slots: Semaphore::new(capacity),
+            capacity,
+            max_attempts: max_attempts.max(1),
+            dead_letters: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            flushing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Queues a write, waiting while the queue is full.
+    pub async fn push(&self, op: WriteOp) {
+        if !dry_run::allows_side_effect("write-behind") {
+            return;
+        }
+        if let Ok(slot) = self.slots.acquire().await {
+            slot.forget();
+        }
+        self.ops
+            .lock()
+            .unwrap()
+            .push_back(PendingOp { op, attempts: 0 });
+        self.notify.notify_one();
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.lock().unwrap().is_empty()
+    }
+
+    pub fn is_pending(&self, project_id: &Uuid) -> bool {
+        self.ops
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|pending| pending.op.project_id() == project_id)
+    }
+
+    /// Writes still queued, in order.
+    pub fn pending(&self) -> Vec<WriteOp> {
+        self.ops
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|pending| pending.op.clone())
+            .collect()
+    }
+
+    /// Writes given up on, oldest first. Only the latest `capacity` are kept.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Waits until a write is pushed.
+    pub async fn notified(&self) {
+        self.notify.notified().await
+    }
+
+    /// Drops the pending writes of a project, waiting for the one in flight. Its audit
+    /// records are kept, the audit trail outliving the project.
+    pub async fn discard(&self, project_id: &Uuid) {
+        let _flushing = self.flushing.lock().await;
+        let mut ops = self.ops.lock().unwrap();
+        let len = ops.len();
+        ops.retain(|pending| {
+            pending.op.project_id() != project_id || matches!(pending.op, WriteOp::SaveAudit(..))
+        });
+        self.slots.add_permits(len - ops.len());
+    }
+
+    /// Applies the pending writes in order. A write failing with a transient error stays
+    /// at the head of the queue to be retried by the next flush, and its error is returned;
+    /// one the Database rejects, or still failing after `max_attempts`, is dead-lettered and
+    /// the flush goes on with the next.
+    pub async fn flush(&self, db: &(dyn Database + Send + Sync)) -> Result<usize, DatabaseError> {
+        let _flushing = self.flushing.lock().await;
+
+        let mut flushed = 0;
+        loop {
+            let Some(mut pending) = self.ops.lock().unwrap().pop_front() else {
+                return Ok(flushed);
+            };
+            match pending.op.apply(db).await {
+                Ok(()) => flushed += 1,
+                Err(e) => {
+                    pending.attempts += 1;
+                    if e.is_transient() && pending.attempts < self.max_attempts {
+                        self.ops.lock().unwrap().push_front(pending);
+                        return Err(e);
+                    }
+                    self.dead_letter(pending, e);
+                }
+            }
+            self.slots.add_permits(1);
+        }
+    }
+
+    fn dead_letter(&self, pending: PendingOp, e: DatabaseError) {
+        tracing::error!(
+            "write-behind: dead-lettered {} of project {} after {} attempts: {}",
+            pending.op.kind(),
+            pending.op.project_id(),
+            pending.attempts,
+            e
+        );
+
+        let mut dead_letters = self.dead_letters.lock().unwrap();
+        if dead_letters.len() == self.capacity {
+            dead_letters.pop_front();
+        }
+        dead_letters.push_back(DeadLetter {
+            op: pending.op,
+            attempts: pending.attempts,
+            error: e.to_string(),
+            at: chrono::Utc::now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{Query, db::memory::MemoryDb};
+
+    use super::*;
+
+    fn save(project_id: Uuid, name: &str) -> WriteOp {
+        let uid = EntityUid::from(format!("App::User::{name}").as_str());
+        WriteOp::SaveEntities(
+            project_id,
+            vec![Entity::new_no_attrs(uid, Default::default())],
+        )
+    }
+
+    async fn stored(db: &MemoryDb, project_id: &Uuid) -> usize {
+        db.project_entities_load(project_id, &Query::new())
+            .await
+            .unwrap()
+            .items
+            .len()
+    }
+
+    #[tokio::test]
+    async fn test_flush_retries_transient_errors() {
+        let db = MemoryDb::default();
+        let queue = WriteBehindQueue::new(10, 3);
+        let project_id = Uuid::now_v7();
+        queue.push(save(project_id, "alice")).await;
+
+        db.failures
+            .lock()
+            .unwrap()
+            .push_back(DatabaseError::ConnectionError("down".to_string()));
+        assert!(queue.flush(&db).await.is_err());
+        assert!(queue.is_pending(&project_id));
+
+        assert_eq!(queue.flush(&db).await.unwrap(), 1);
+        assert!(queue.is_empty());
+        assert!(queue.dead_letters().is_empty());
+        assert_eq!(stored(&db, &project_id).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_dead_letters() {
+        let db = MemoryDb::default();
+        let queue = WriteBehindQueue::new(10, 2);
+        let project_id = Uuid::now_v7();
+
+        // A rejected write is dead-lettered at once, and the next one still written
+        queue.push(save(project_id, "alice")).await;
+        queue.push(save(project_id, "bob")).await;
+        db.failures
+            .lock()
+            .unwrap()
+            .push_back(DatabaseError::InvalidAttribute("uid".to_string()));
+        assert_eq!(queue.flush(&db).await.unwrap(), 1);
+        assert_eq!(queue.dead_letters().len(), 1);
+        assert_eq!(stored(&db, &project_id).await, 1);
+
+        // A transient failure is dead-lettered once past the retry cap
+        queue.push(save(project_id, "carol")).await;
+        for _ in 0..2 {
+            db.failures
+                .lock()
+                .unwrap()
+                .push_back(DatabaseError::ConnectionError("down".to_string()));
+        }
+        assert!(queue.flush(&db).await.is_err());
+        assert_eq!(queue.flush(&db).await.unwrap(), 0);
+        assert!(queue.is_empty());
+        let dead_letters = queue.dead_letters();
+        assert_eq!(dead_letters.len(), 2);
+        assert_eq!(dead_letters[1].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_push_backpressure() {
+        let db = MemoryDb::default();
+        let queue = WriteBehindQueue::new(1, 1);
+        let project_id = Uuid::now_v7();
+        queue.push(save(project_id, "alice")).await;
+
+        // A full queue holds the writer back until a flush makes room
+        let push = queue.push(save(project_id, "bob"));
+        tokio::pin!(push);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), &mut push)
+                .await
+                .is_err()
+        );
+        assert_eq!(queue.flush(&db).await.unwrap(), 1);
+        push.await;
+        assert_eq!(queue.len(), 1);
+
+        queue.discard(&project_id).await;
+        assert!(queue.is_empty());
+        tokio::time::timeout(
+            Duration::from_millis(10),
+            queue.push(save(project_id, "carol")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_audit_retry() {
+        let db = MemoryDb::default();
+        let queue = WriteBehindQueue::new(10, 3);
+        let project_id = Uuid::now_v7();
+        let record = AuditRecord::new(project_id, EntityUid::from("App::User::alice"));
+        queue.push(save(project_id, "alice")).await;
+        queue
+            .push(WriteOp::SaveAudit(project_id, record.clone()))
+            .await;
+
+        // The audit trail outlives the project, its records are kept when it is removed
+        queue.discard(&project_id).await;
+        assert_eq!(queue.len(), 1);
+
+        db.failures
+            .lock()
+            .unwrap()
+            .push_back(DatabaseError::ConnectionError("down".to_string()));
+        assert!(queue.flush(&db).await.is_err());
+        assert_eq!(queue.flush(&db).await.unwrap(), 1);
+        let records = db
+            .project_audit_load(&project_id, &Query::new())
+            .await
+            .unwrap();
+        assert_eq!(records.items, vec![record]);
+    }
+}
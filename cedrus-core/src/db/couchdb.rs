@@ -1,41 +1,74 @@
 use std::collections::HashMap;
 
 use couch_rs::types::{
-    find::{FindQuery, IndexSpec, SortSpec},
+    find::{FindQuery, IndexSpec, SortDirection, SortSpec},
     index::IndexFields,
 };
 use serde_json::{Value, json};
 use uuid::Uuid;
 
-use cedrus_cedar::{Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink};
+use cedrus_cedar::{
+    Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink, schema::TypeJson,
+};
 
 use crate::{
-    PageHash, PageList, Query,
+    PageHash, PageList, Query, SortOrder,
     core::{
         self, IdentitySource,
-        project::{ApiKey, Project},
+        audit::AuditRecord,
+        history::{Revision, RevisionKind},
+        job::{Job, JobTask},
+        project::{ApiKey, PROJECT_SORT_FIELDS, Project, Role, RoleAssignment},
     },
 };
 
-use super::{Database, DatabaseError};
+use super::{
+    ANNOTATION_INDEX_KEY, Database, DatabaseError, annotation_index_terms, migration::Migration,
+    query_annotation_terms, sort_entities,
+};
 
 const ENTITY_TYPE_DDOC: &str = "cedrus-entity-type-ddoc";
 const ENTITY_TYPE_INDEX: &str = "cedrus-entity-type-index";
+const ANNOTATION_INDEX: &str = "cedrus-annotation-index";
+const PROJECT_SORT_INDEX_PREFIX: &str = "cedrus-project-sort-index";
 
 const ID_KEY: &str = "_id";
+const PROJECT_ID_FIELD: &str = "id";
+const REV_KEY: &str = "_rev";
+const DELETED_KEY: &str = "_deleted";
 const ENTITY_TYPE_KEY: &str = "entityType";
 const PROJECT_ID_KEY: &str = "projectId";
 const POLICY_ID_KEY: &str = "policyId";
 const SCHEMA_KEY: &str = "schema";
+const VERSION_KEY: &str = "version";
+const COMMON_TYPES_KEY: &str = "commonTypes";
+const REVISION_KIND_KEY: &str = "kind";
+const REVISION_TIME_KEY: &str = "timestamp";
+
+const REVISION_PAGE_SIZE: u64 = 1000;
+
+const SCHEMA_VERSION_TYPE: &str = "SV";
+const COMMON_TYPES_TYPE: &str = "CT";
+
+const MIGRATIONS: &[Migration] = &[
+    Migration::new(1, "baseline document layout"),
+    Migration::new(2, "backfill the policy annotation index with escaped terms"),
+];
 
 const PROJECT_TYPE: &str = "P";
 const PROJECT_APIKEY_TYPE: &str = "PAK";
 const PROJECT_IDENTITY_SOURCE_TYPE: &str = "PIS";
+const PROJECT_ROLE_TYPE: &str = "PR";
+const PROJECT_ROLE_ASSIGNMENT_TYPE: &str = "PRA";
+const PROJECT_JOB_TYPE: &str = "PJ";
+const PROJECT_JOB_TASK_TYPE: &str = "PJT";
 const PROJECT_SCHEMA_TYPE: &str = "PS";
 const PROJECT_ENTITY_TYPE: &str = "PE";
 const PROJECT_POLICY_TYPE: &str = "PP";
 const PROJECT_TEMPLATE_TYPE: &str = "PT";
 const PROJECT_TEMPLATE_LINK_TYPE: &str = "PTL";
+const PROJECT_REVISION_TYPE: &str = "PRV";
+const PROJECT_AUDIT_TYPE: &str = "PAU";
 
 pub struct CouchDb {
     client: couch_rs::Client,
@@ -54,28 +87,166 @@ impl CouchDb {
 
     pub async fn init(&self) -> Result<(), Box<dyn std::error::Error>> {
         let db = self.client.db(&self.db_name).await?;
-        match db
-            .insert_index(
-                ENTITY_TYPE_INDEX,
-                IndexFields {
-                    fields: vec![
-                        SortSpec::Simple(ENTITY_TYPE_KEY.to_string()),
-                        SortSpec::Simple(PROJECT_ID_KEY.to_string()),
-                    ],
-                },
-                None,
-                Some(ENTITY_TYPE_DDOC.to_string()),
+        Self::insert_index(&db, ENTITY_TYPE_INDEX, &[ENTITY_TYPE_KEY, PROJECT_ID_KEY]).await;
+        Self::insert_index(
+            &db,
+            ANNOTATION_INDEX,
+            &[ENTITY_TYPE_KEY, PROJECT_ID_KEY, ANNOTATION_INDEX_KEY],
+        )
+        .await;
+        for field in PROJECT_SORT_FIELDS {
+            Self::insert_index(
+                &db,
+                &Self::project_sort_index(field),
+                &[ENTITY_TYPE_KEY, PROJECT_ID_KEY, field],
             )
+            .await;
+        }
+        Ok(())
+    }
+
+    async fn insert_index(db: &couch_rs::database::Database, name: &str, fields: &[&str]) {
+        let fields = IndexFields {
+            fields: fields
+                .iter()
+                .map(|field| SortSpec::Simple(field.to_string()))
+                .collect(),
+        };
+        match db
+            .insert_index(name, fields, None, Some(ENTITY_TYPE_DDOC.to_string()))
             .await
         {
             Ok(doc_created) => match doc_created.result {
-                Some(r) => println!("Index {} {}", ENTITY_TYPE_INDEX, r),
-                None => println!("Index {} validated", ENTITY_TYPE_INDEX),
+                Some(r) => {
+                    tracing::info!(target: "cedrus::db", "couchdb: index {} {}", name, r)
+                }
+                None => {
+                    tracing::info!(target: "cedrus::db", "couchdb: index {} validated", name)
+                }
             },
             Err(e) => {
-                println!("Unable to validate index {}: {}", ENTITY_TYPE_INDEX, e);
+                tracing::warn!(target: "cedrus::db", "couchdb: unable to validate index {}: {}", name, e);
             }
         };
+    }
+
+    fn project_sort_index(field: &str) -> String {
+        format!("{}-{}", PROJECT_SORT_INDEX_PREFIX, field)
+    }
+
+    /// Saves documents in one `_bulk_docs` request. CouchDB applies bulk writes per
+    /// document, so when any document is rejected the written ones are rolled back
+    /// to their previous revision (or deleted when they were new).
+    async fn bulk_save(
+        db: &couch_rs::database::Database,
+        mut values: Vec<Value>,
+    ) -> Result<(), DatabaseError> {
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let ids = values
+            .iter()
+            .filter_map(|value| value.get(ID_KEY).and_then(Value::as_str))
+            .map(str::to_string)
+            .collect();
+        let previous: HashMap<String, Value> = db
+            .get_bulk_raw(ids)
+            .await?
+            .rows
+            .into_iter()
+            .filter_map(|doc| Some((doc.get(ID_KEY)?.as_str()?.to_string(), doc)))
+            .collect();
+
+        for value in values.iter_mut() {
+            if let Some(rev) = value
+                .get(ID_KEY)
+                .and_then(Value::as_str)
+                .and_then(|id| previous.get(id))
+                .and_then(|doc| doc.get(REV_KEY))
+                .cloned()
+            {
+                value[REV_KEY] = rev;
+            }
+        }
+
+        let results = db.bulk_docs(&mut values).await?;
+        if results.iter().all(Result::is_ok) {
+            return Ok(());
+        }
+
+        let mut rollback = Vec::new();
+        let mut error = None;
+        for result in results {
+            match result {
+                Ok(created) => {
+                    let mut doc = match previous.get(&created.id) {
+                        Some(doc) => doc.clone(),
+                        None => json!({ ID_KEY: created.id, DELETED_KEY: true }),
+                    };
+                    doc[REV_KEY] = Value::String(created.rev);
+                    rollback.push(doc);
+                }
+                Err(e) => {
+                    if error.is_none() {
+                        error = Some(DatabaseError::from(e));
+                    }
+                }
+            }
+        }
+
+        if !rollback.is_empty() {
+            match db.bulk_docs(&mut rollback).await {
+                Ok(results) => {
+                    for e in results.into_iter().filter_map(Result::err) {
+                        tracing::error!(target: "cedrus::db", "couchdb: bulk save rollback failed: {}", e);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(target: "cedrus::db", "couchdb: bulk save rollback failed: {}", e);
+                }
+            }
+        }
+
+        Err(error.unwrap_or(DatabaseError::Unknown))
+    }
+
+    /// Rewrites the annotation index of every policy, indexing the policies saved before it
+    /// existed and the terms saved before their escaping.
+    async fn policy_annotations_reindex(&self) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let selector = json!({ ENTITY_TYPE_KEY: PROJECT_POLICY_TYPE });
+
+        let mut bookmark: Option<String> = None;
+        loop {
+            let mut find = FindQuery::new(selector.clone())
+                .limit(REVISION_PAGE_SIZE)
+                .use_index(IndexSpec::IndexName((
+                    ENTITY_TYPE_DDOC.to_string(),
+                    ENTITY_TYPE_INDEX.to_string(),
+                )));
+            if let Some(bookmark) = &bookmark {
+                find = find.bookmark(bookmark);
+            }
+            let docs = db.find_raw(&find).await?;
+            if docs.rows.is_empty() {
+                break;
+            }
+            // Rows keep their revision, so they are updated in place
+            let mut values = docs.rows;
+            for value in values.iter_mut() {
+                let policy = Self::project_policy_from_value(value.clone())?;
+                value[ANNOTATION_INDEX_KEY] = json!(annotation_index_terms(&policy.annotations));
+            }
+            for result in db.bulk_docs(&mut values).await? {
+                result?;
+            }
+            bookmark = docs.bookmark;
+            if bookmark.is_none() {
+                break;
+            }
+        }
+
         Ok(())
     }
 
@@ -129,6 +300,116 @@ impl CouchDb {
         Ok(serde_json::from_value(value)?)
     }
 
+    fn project_role_id(project_id: &Uuid, id: &str) -> String {
+        format!("{}#{}#{}", PROJECT_ROLE_TYPE, project_id, id)
+    }
+
+    fn project_role_to_value(project_id: &Uuid, role: &Role) -> Result<Value, DatabaseError> {
+        let id = Self::project_role_id(project_id, &role.id);
+        let mut value = serde_json::to_value(role)?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert(ID_KEY.to_string(), Value::String(id));
+            obj.insert(
+                ENTITY_TYPE_KEY.to_string(),
+                Value::String(PROJECT_ROLE_TYPE.to_string()),
+            );
+            obj.insert(
+                PROJECT_ID_KEY.to_string(),
+                Value::String(project_id.to_string()),
+            );
+        }
+        Ok(value)
+    }
+
+    fn project_role_from_value(value: Value) -> Result<Role, DatabaseError> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn project_role_assignment_id(project_id: &Uuid, id: &str) -> String {
+        format!("{}#{}#{}", PROJECT_ROLE_ASSIGNMENT_TYPE, project_id, id)
+    }
+
+    fn project_role_assignment_to_value(
+        project_id: &Uuid,
+        assignment: &RoleAssignment,
+    ) -> Result<Value, DatabaseError> {
+        let id = Self::project_role_assignment_id(project_id, &assignment.id());
+        let mut value = serde_json::to_value(assignment)?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert(ID_KEY.to_string(), Value::String(id));
+            obj.insert(
+                ENTITY_TYPE_KEY.to_string(),
+                Value::String(PROJECT_ROLE_ASSIGNMENT_TYPE.to_string()),
+            );
+            obj.insert(
+                PROJECT_ID_KEY.to_string(),
+                Value::String(project_id.to_string()),
+            );
+        }
+        Ok(value)
+    }
+
+    fn project_role_assignment_from_value(value: Value) -> Result<RoleAssignment, DatabaseError> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn project_job_id(project_id: &Uuid, id: &Uuid) -> String {
+        format!("{}#{}#{}", PROJECT_JOB_TYPE, project_id, id)
+    }
+
+    fn project_job_to_value(project_id: &Uuid, job: &Job) -> Result<Value, DatabaseError> {
+        let id = Self::project_job_id(project_id, &job.id);
+        let mut value = serde_json::to_value(job)?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert(ID_KEY.to_string(), Value::String(id));
+            obj.insert(
+                ENTITY_TYPE_KEY.to_string(),
+                Value::String(PROJECT_JOB_TYPE.to_string()),
+            );
+            obj.insert(
+                PROJECT_ID_KEY.to_string(),
+                Value::String(project_id.to_string()),
+            );
+        }
+        Ok(value)
+    }
+
+    fn project_job_from_value(value: Value) -> Result<Job, DatabaseError> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn project_job_task_id(project_id: &Uuid, id: &Uuid) -> String {
+        format!("{}#{}#{}", PROJECT_JOB_TASK_TYPE, project_id, id)
+    }
+
+    fn project_audit_id(project_id: &Uuid, id: &Uuid) -> String {
+        format!("{}#{}#{}", PROJECT_AUDIT_TYPE, project_id, id)
+    }
+
+    fn project_audit_to_value(
+        project_id: &Uuid,
+        record: &AuditRecord,
+    ) -> Result<Value, DatabaseError> {
+        let id = Self::project_audit_id(project_id, &record.id);
+        let mut value = serde_json::to_value(record)?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert(ID_KEY.to_string(), Value::String(id));
+            obj.insert(
+                ENTITY_TYPE_KEY.to_string(),
+                Value::String(PROJECT_AUDIT_TYPE.to_string()),
+            );
+            obj.insert(
+                PROJECT_ID_KEY.to_string(),
+                Value::String(project_id.to_string()),
+            );
+        }
+        Ok(value)
+    }
+
+    fn project_audit_from_value(value: Value) -> Result<AuditRecord, DatabaseError> {
+        Ok(serde_json::from_value(value)?)
+    }
+
     fn project_identity_source_id(project_id: &Uuid) -> String {
         format!("{}#{}", PROJECT_IDENTITY_SOURCE_TYPE, project_id)
     }
@@ -230,6 +511,10 @@ impl CouchDb {
                 POLICY_ID_KEY.to_string(),
                 Value::String(policy_id.to_string()),
             );
+            obj.insert(
+                ANNOTATION_INDEX_KEY.to_string(),
+                json!(annotation_index_terms(&policy.annotations)),
+            );
         }
         Ok(value)
     }
@@ -299,12 +584,52 @@ impl CouchDb {
         Ok(serde_json::from_value(value)?)
     }
 
+    fn project_revision_id(project_id: &Uuid, revision: &Revision) -> String {
+        format!(
+            "{}#{}#{}#{}#{}",
+            PROJECT_REVISION_TYPE,
+            project_id,
+            revision.kind.as_str(),
+            revision.at.timestamp_micros(),
+            revision.id
+        )
+    }
+
+    fn project_revision_to_value(
+        project_id: &Uuid,
+        revision: &Revision,
+    ) -> Result<Value, DatabaseError> {
+        let id = Self::project_revision_id(project_id, revision);
+        let mut value = serde_json::to_value(revision)?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert(ID_KEY.to_string(), Value::String(id));
+            obj.insert(
+                ENTITY_TYPE_KEY.to_string(),
+                Value::String(PROJECT_REVISION_TYPE.to_string()),
+            );
+            obj.insert(
+                PROJECT_ID_KEY.to_string(),
+                Value::String(project_id.to_string()),
+            );
+            // Compared numerically, the RFC 3339 time has a variable number of digits
+            obj.insert(
+                REVISION_TIME_KEY.to_string(),
+                json!(revision.at.timestamp_micros()),
+            );
+        }
+        Ok(value)
+    }
+
+    fn project_revision_from_value(value: Value) -> Result<Revision, DatabaseError> {
+        Ok(serde_json::from_value(value)?)
+    }
+
     fn query_to_find_query(
         query: &Query,
         entity_type: &str,
         project_id: &Uuid,
     ) -> Result<FindQuery, DatabaseError> {
-        let selector = match query.selector.as_ref() {
+        let mut selector = match query.selector.as_ref() {
             Some(selector) => {
                 let mut value = serde_json::to_value(selector)?;
                 if let Some(obj) = value.as_object_mut() {
@@ -324,9 +649,21 @@ impl CouchDb {
                 PROJECT_ID_KEY: project_id.to_string()
             }),
         };
+        let terms = query_annotation_terms(query);
+        if terms.is_empty() {
+            let find = FindQuery::new(selector).use_index(IndexSpec::IndexName((
+                ENTITY_TYPE_DDOC.to_string(),
+                ENTITY_TYPE_INDEX.to_string(),
+            )));
+            return Ok(find);
+        }
+
+        if let Some(obj) = selector.as_object_mut() {
+            obj.insert(ANNOTATION_INDEX_KEY.to_string(), json!({ "$all": terms }));
+        }
         let find = FindQuery::new(selector).use_index(IndexSpec::IndexName((
             ENTITY_TYPE_DDOC.to_string(),
-            ENTITY_TYPE_INDEX.to_string(),
+            ANNOTATION_INDEX.to_string(),
         )));
 
         Ok(find)
@@ -335,10 +672,105 @@ impl CouchDb {
 
 #[async_trait::async_trait]
 impl Database for CouchDb {
+    fn migrations(&self) -> &'static [Migration] {
+        MIGRATIONS
+    }
+
+    async fn schema_version_load(&self) -> Result<u32, DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        if let Ok(doc) = db.get::<Value>(SCHEMA_VERSION_TYPE).await {
+            let version = doc
+                .get(VERSION_KEY)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| DatabaseError::InvalidAttribute(VERSION_KEY.to_string()))?;
+            return Ok(version as u32);
+        }
+        Ok(0)
+    }
+
+    async fn schema_version_save(&self, version: u32) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let mut value = json!({
+            ID_KEY: SCHEMA_VERSION_TYPE,
+            ENTITY_TYPE_KEY: SCHEMA_VERSION_TYPE,
+            PROJECT_ID_KEY: Uuid::nil().to_string(),
+            VERSION_KEY: version,
+        });
+        db.upsert(&mut value).await?;
+
+        Ok(())
+    }
+
+    async fn migration_apply(&self, migration: &Migration) -> Result<(), DatabaseError> {
+        match migration.version {
+            1 => Ok(()),
+            2 => self.policy_annotations_reindex().await,
+            version => Err(DatabaseError::MigrationError(format!(
+                "unknown couchdb migration {}",
+                version
+            ))),
+        }
+    }
+
+    async fn common_types_load(&self) -> Result<HashMap<String, TypeJson>, DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        if let Ok(mut doc) = db.get::<Value>(COMMON_TYPES_TYPE).await {
+            let common_types = doc
+                .get_mut(COMMON_TYPES_KEY)
+                .map(Value::take)
+                .ok_or_else(|| DatabaseError::MissingAttribute(COMMON_TYPES_KEY.to_string()))?;
+            return Ok(serde_json::from_value(common_types)?);
+        }
+        Ok(HashMap::new())
+    }
+
+    async fn common_types_save(
+        &self,
+        common_types: &HashMap<String, TypeJson>,
+    ) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let mut value = json!({
+            ID_KEY: COMMON_TYPES_TYPE,
+            ENTITY_TYPE_KEY: COMMON_TYPES_TYPE,
+            PROJECT_ID_KEY: Uuid::nil().to_string(),
+            COMMON_TYPES_KEY: common_types,
+        });
+        db.upsert(&mut value).await?;
+
+        Ok(())
+    }
+
     async fn projects_load(&self, query: &Query) -> Result<PageList<Project>, DatabaseError> {
         let db = self.client.db(&self.db_name).await?;
 
-        let find = Self::query_to_find_query(query, PROJECT_TYPE, &Uuid::nil())?;
+        let mut find = Self::query_to_find_query(query, PROJECT_TYPE, &Uuid::nil())?;
+        if let Some(sort) = query.sort.first() {
+            let direction = match sort.order {
+                SortOrder::Asc => SortDirection::Asc,
+                SortOrder::Desc => SortDirection::Desc,
+            };
+            // Mango sorts on a prefix of the index, every key in the same direction
+            let sort_spec = [ENTITY_TYPE_KEY, PROJECT_ID_KEY, sort.field.as_str()]
+                .iter()
+                .map(|key| SortSpec::Complex(HashMap::from([(key.to_string(), direction.clone())])))
+                .collect();
+            find = find.sort(sort_spec).use_index(IndexSpec::IndexName((
+                ENTITY_TYPE_DDOC.to_string(),
+                Self::project_sort_index(&sort.field),
+            )));
+        }
+        if !query.fields.is_empty() {
+            // The id is always returned, for authorization
+            let mut fields = vec![PROJECT_ID_FIELD.to_string()];
+            fields.extend(
+                query
+                    .fields
+                    .iter()
+                    .filter(|f| *f != PROJECT_ID_FIELD)
+                    .cloned(),
+            );
+            find = find.fields(fields);
+        }
         let docs = db.find_raw(&find).await?;
 
         let mut datas = Vec::new();
@@ -424,6 +856,239 @@ impl Database for CouchDb {
         Ok(())
     }
 
+    async fn project_roles_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Role>, DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let find = Self::query_to_find_query(query, PROJECT_ROLE_TYPE, project_id)?;
+        let docs = db.find_raw(&find).await?;
+
+        let mut datas = Vec::new();
+        for doc in docs.rows {
+            datas.push(Self::project_role_from_value(doc)?);
+        }
+
+        Ok(PageList::new(datas, docs.bookmark))
+    }
+
+    async fn project_roles_save(
+        &self,
+        project_id: &Uuid,
+        roles: &Vec<Role>,
+    ) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        for role in roles {
+            let mut value = Self::project_role_to_value(project_id, role)?;
+            db.upsert(&mut value).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn project_roles_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<String>,
+    ) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        for id in ids {
+            let id = Self::project_role_id(project_id, id);
+            if let Ok(doc) = db.get::<Value>(&id).await {
+                let _ = db.remove(&doc).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn project_role_assignment_load(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<Option<RoleAssignment>, DatabaseError> {
+        let id = Self::project_role_assignment_id(project_id, id);
+        let db = self.client.db(&self.db_name).await?;
+        if let Ok(doc) = db.get::<Value>(&id).await {
+            return Ok(Some(Self::project_role_assignment_from_value(doc)?));
+        }
+        Ok(None)
+    }
+
+    async fn project_role_assignment_save(
+        &self,
+        project_id: &Uuid,
+        assignment: &RoleAssignment,
+    ) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let mut value = Self::project_role_assignment_to_value(project_id, assignment)?;
+        db.upsert(&mut value).await?;
+
+        Ok(())
+    }
+
+    async fn project_role_assignment_remove(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<(), DatabaseError> {
+        let id = Self::project_role_assignment_id(project_id, id);
+        let db = self.client.db(&self.db_name).await?;
+        if let Ok(doc) = db.get::<Value>(&id).await {
+            let _ = db.remove(&doc).await;
+        }
+
+        Ok(())
+    }
+
+    async fn project_jobs_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Job>, DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let find = Self::query_to_find_query(query, PROJECT_JOB_TYPE, project_id)?;
+        let docs = db.find_raw(&find).await?;
+
+        let mut datas = Vec::new();
+        for doc in docs.rows {
+            datas.push(Self::project_job_from_value(doc)?);
+        }
+
+        Ok(PageList::new(datas, docs.bookmark))
+    }
+
+    async fn project_job_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<Job>, DatabaseError> {
+        let id = Self::project_job_id(project_id, id);
+        let db = self.client.db(&self.db_name).await?;
+        if let Ok(doc) = db.get::<Value>(&id).await {
+            return Ok(Some(Self::project_job_from_value(doc)?));
+        }
+        Ok(None)
+    }
+
+    async fn project_job_save(&self, project_id: &Uuid, job: &Job) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let mut value = Self::project_job_to_value(project_id, job)?;
+        db.upsert(&mut value).await?;
+
+        Ok(())
+    }
+
+    async fn jobs_unfinished_load(&self) -> Result<Vec<Job>, DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let selector = json!({
+            ENTITY_TYPE_KEY: PROJECT_JOB_TYPE,
+            "status": { "$in": ["pending", "running"] },
+        });
+
+        let mut jobs = Vec::new();
+        let mut bookmark: Option<String> = None;
+        loop {
+            let mut find = FindQuery::new(selector.clone())
+                .limit(REVISION_PAGE_SIZE)
+                .use_index(IndexSpec::IndexName((
+                    ENTITY_TYPE_DDOC.to_string(),
+                    ENTITY_TYPE_INDEX.to_string(),
+                )));
+            if let Some(bookmark) = &bookmark {
+                find = find.bookmark(bookmark);
+            }
+            let docs = db.find_raw(&find).await?;
+            if docs.rows.is_empty() {
+                break;
+            }
+            for doc in docs.rows {
+                jobs.push(Self::project_job_from_value(doc)?);
+            }
+            bookmark = docs.bookmark;
+            if bookmark.is_none() {
+                break;
+            }
+        }
+
+        Ok(jobs)
+    }
+
+    async fn project_job_task_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<JobTask>, DatabaseError> {
+        let id = Self::project_job_task_id(project_id, id);
+        let db = self.client.db(&self.db_name).await?;
+        if let Ok(mut doc) = db.get::<Value>(&id).await {
+            return Ok(Some(serde_json::from_value(doc["task"].take())?));
+        }
+        Ok(None)
+    }
+
+    async fn project_job_task_save(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+        task: &JobTask,
+    ) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let mut value = json!({
+            ID_KEY: Self::project_job_task_id(project_id, id),
+            ENTITY_TYPE_KEY: PROJECT_JOB_TASK_TYPE,
+            PROJECT_ID_KEY: project_id.to_string(),
+            "task": task,
+        });
+        db.upsert(&mut value).await?;
+
+        Ok(())
+    }
+
+    async fn project_job_task_remove(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<(), DatabaseError> {
+        let id = Self::project_job_task_id(project_id, id);
+        let db = self.client.db(&self.db_name).await?;
+        if let Ok(doc) = db.get::<Value>(&id).await {
+            let _ = db.remove(&doc).await;
+        }
+
+        Ok(())
+    }
+
+    async fn project_audit_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<AuditRecord>, DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let find = Self::query_to_find_query(query, PROJECT_AUDIT_TYPE, project_id)?;
+        let docs = db.find_raw(&find).await?;
+
+        let mut datas = Vec::new();
+        for doc in docs.rows {
+            datas.push(Self::project_audit_from_value(doc)?);
+        }
+
+        Ok(PageList::new(datas, docs.bookmark))
+    }
+
+    async fn project_audit_save(
+        &self,
+        project_id: &Uuid,
+        record: &AuditRecord,
+    ) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let mut value = Self::project_audit_to_value(project_id, record)?;
+        db.create(&mut value).await?;
+
+        Ok(())
+    }
+
     async fn project_identity_source_load(
         &self,
         project_id: &Uuid,
@@ -505,6 +1170,8 @@ impl Database for CouchDb {
         for doc in docs.rows {
             datas.push(Self::project_entity_from_value(doc)?);
         }
+        // Sorted within the page only, a sort on tags is applied to the whole set by Cedrus
+        sort_entities(&mut datas, &query.sort);
 
         Ok(PageList::new(datas, docs.bookmark))
     }
@@ -569,12 +1236,12 @@ impl Database for CouchDb {
         policies: &HashMap<PolicyId, Policy>,
     ) -> Result<(), DatabaseError> {
         let db = self.client.db(&self.db_name).await?;
-        for (policy_id, policy) in policies {
-            let mut value = Self::project_policy_to_value(project_id, policy_id, policy)?;
-            db.upsert(&mut value).await?;
-        }
+        let values = policies
+            .iter()
+            .map(|(policy_id, policy)| Self::project_policy_to_value(project_id, policy_id, policy))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(())
+        Self::bulk_save(&db, values).await
     }
 
     async fn project_policies_remove(
@@ -623,12 +1290,14 @@ impl Database for CouchDb {
         templates: &HashMap<PolicyId, Template>,
     ) -> Result<(), DatabaseError> {
         let db = self.client.db(&self.db_name).await?;
-        for (template_id, template) in templates {
-            let mut value = Self::project_template_to_value(project_id, template_id, template)?;
-            db.upsert(&mut value).await?;
-        }
-
-        Ok(())
+        let values = templates
+            .iter()
+            .map(|(template_id, template)| {
+                Self::project_template_to_value(project_id, template_id, template)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::bulk_save(&db, values).await
     }
 
     async fn project_templates_remove(
@@ -670,12 +1339,12 @@ impl Database for CouchDb {
         template_links: &Vec<TemplateLink>,
     ) -> Result<(), DatabaseError> {
         let db = self.client.db(&self.db_name).await?;
-        for template_link in template_links {
-            let mut value = Self::project_template_link_to_value(project_id, template_link)?;
-            db.upsert(&mut value).await?;
-        }
+        let values = template_links
+            .iter()
+            .map(|template_link| Self::project_template_link_to_value(project_id, template_link))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(())
+        Self::bulk_save(&db, values).await
     }
 
     async fn project_template_links_remove(
@@ -693,4 +1362,86 @@ impl Database for CouchDb {
 
         Ok(())
     }
+
+    async fn project_revisions_load(
+        &self,
+        project_id: &Uuid,
+        kind: RevisionKind,
+        until: &chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Revision>, DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let selector = json!({
+            ENTITY_TYPE_KEY: PROJECT_REVISION_TYPE,
+            PROJECT_ID_KEY: project_id.to_string(),
+            REVISION_KIND_KEY: kind.as_str(),
+            REVISION_TIME_KEY: { "$lte": until.timestamp_micros() },
+        });
+
+        let mut revisions = Vec::new();
+        let mut bookmark: Option<String> = None;
+        loop {
+            let mut find = FindQuery::new(selector.clone())
+                .limit(REVISION_PAGE_SIZE)
+                .use_index(IndexSpec::IndexName((
+                    ENTITY_TYPE_DDOC.to_string(),
+                    ENTITY_TYPE_INDEX.to_string(),
+                )));
+            if let Some(bookmark) = &bookmark {
+                find = find.bookmark(bookmark);
+            }
+            let docs = db.find_raw(&find).await?;
+            if docs.rows.is_empty() {
+                break;
+            }
+            for doc in docs.rows {
+                revisions.push(Self::project_revision_from_value(doc)?);
+            }
+            bookmark = docs.bookmark;
+            if bookmark.is_none() {
+                break;
+            }
+        }
+
+        Ok(revisions)
+    }
+
+    async fn project_revisions_save(
+        &self,
+        project_id: &Uuid,
+        revisions: &[Revision],
+    ) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let values = revisions
+            .iter()
+            .map(|revision| Self::project_revision_to_value(project_id, revision))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::bulk_save(&db, values).await
+    }
+
+    async fn project_revisions_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        let db = self.client.db(&self.db_name).await?;
+        let selector = json!({
+            ENTITY_TYPE_KEY: PROJECT_REVISION_TYPE,
+            PROJECT_ID_KEY: project_id.to_string(),
+        });
+        loop {
+            let find = FindQuery::new(selector.clone())
+                .limit(REVISION_PAGE_SIZE)
+                .fields(vec![ID_KEY.to_string(), REV_KEY.to_string()]);
+            let docs = db.find_raw(&find).await?;
+            if docs.rows.is_empty() {
+                return Ok(());
+            }
+            let mut deleted = docs
+                .rows
+                .into_iter()
+                .map(|mut doc| {
+                    doc[DELETED_KEY] = Value::Bool(true);
+                    doc
+                })
+                .collect::<Vec<_>>();
+            db.bulk_docs(&mut deleted).await?;
+        }
+    }
 }
@@ -1,19 +1,30 @@
 use std::collections::HashMap;
 
-use aws_sdk_dynamodb::types::{AttributeValue, DeleteRequest, PutRequest, WriteRequest};
+use aws_sdk_dynamodb::{
+    primitives::Blob,
+    types::{AttributeValue, DeleteRequest, PutRequest, WriteRequest},
+};
 use uuid::Uuid;
 
-use cedrus_cedar::{Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink};
+use cedrus_cedar::{
+    Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink, schema::TypeJson,
+};
 
 use crate::{
     PageHash, PageList, Query, Selector,
     core::{
         self, IdentitySource,
-        project::{ApiKey, Project},
+        audit::AuditRecord,
+        history::{Revision, RevisionKind},
+        job::{Job, JobTask, ProjectData},
+        project::{ApiKey, Project, Role, RoleAssignment},
     },
 };
 
-use super::{Database, DatabaseError};
+use super::{
+    ANNOTATION_INDEX_KEY, Database, DatabaseError, annotation_index_terms, migration::Migration,
+    query_annotation_terms, sort_entities,
+};
 
 const PK: &str = "PK";
 const SK: &str = "SK";
@@ -24,11 +35,33 @@ const GSI1_PK: &str = "GSI1PK";
 const PROJECT_TYPE: &str = "P";
 const PROJECT_APIKEY_TYPE: &str = "PAK";
 const PROJECT_IDENTITY_SOURCE_TYPE: &str = "PIS";
+const PROJECT_ROLE_TYPE: &str = "PR";
+const PROJECT_ROLE_ASSIGNMENT_TYPE: &str = "PRA";
+const PROJECT_JOB_TYPE: &str = "PJ";
+const PROJECT_JOB_RESULT_TYPE: &str = "PJR";
+const PROJECT_JOB_TASK_TYPE: &str = "PJT";
 const PROJECT_SCHEMA_TYPE: &str = "PS";
 const PROJECT_ENTITY_TYPE: &str = "PE";
 const PROJECT_POLICY_TYPE: &str = "PP";
 const PROJECT_TEMPLATE_TYPE: &str = "PT";
 const PROJECT_TEMPLATE_LINK_TYPE: &str = "PTL";
+const PROJECT_REVISION_TYPE: &str = "PRV";
+const PROJECT_AUDIT_TYPE: &str = "PAU";
+const SCHEMA_VERSION_TYPE: &str = "SV";
+const COMMON_TYPES_TYPE: &str = "CT";
+
+type Item = HashMap<String, AttributeValue>;
+
+// Bytes of a job result stored per item, below the 400 KB item size limit of DynamoDB
+const JOB_RESULT_CHUNK_SIZE: usize = 350 * 1024;
+
+// Policies read and rewritten per page by the annotation reindex migration
+const REINDEX_PAGE_SIZE: i32 = 100;
+
+const MIGRATIONS: &[Migration] = &[
+    Migration::new(1, "baseline single-table layout"),
+    Migration::new(2, "backfill the policy annotation index with escaped terms"),
+];
 
 /*
 Types of items in the table:
@@ -43,6 +76,36 @@ PK: "P#[PROJECT_UUID]"
 SK: "P#[PROJECT_UUID]#PAK#[API_KEY_UUID]"
 GSI1PK: "PAK"
 
+Role:
+PK: "P#[PROJECT_UUID]"
+SK: "P#[PROJECT_UUID]#PR#[ROLE_ID]"
+GSI1PK: "PR"
+
+Role Assignment:
+PK: "P#[PROJECT_UUID]"
+SK: "P#[PROJECT_UUID]#PRA#[ROLE_ID]:[PRINCIPAL_UID]"
+GSI1PK: "PRA"
+
+Job:
+PK: "P#[PROJECT_UUID]"
+SK: "P#[PROJECT_UUID]#PJ#[JOB_UUID]"
+GSI1PK: "PJ"
+
+Job Result Chunk (the result of a job, split under the item size limit):
+PK: "P#[PROJECT_UUID]"
+SK: "P#[PROJECT_UUID]#PJR#[JOB_UUID]#[CHUNK_INDEX]"
+GSI1PK: "PJR"
+
+Job Task Chunk (the task of a job until it finished, split under the item size limit):
+PK: "P#[PROJECT_UUID]"
+SK: "P#[PROJECT_UUID]#PJT#[JOB_UUID]#[CHUNK_INDEX]"
+GSI1PK: "PJT"
+
+Audit Record (outside the project partition, kept once the project is removed):
+PK: "PAU#[PROJECT_UUID]"
+SK: "PAU#[RECORD_UUID]"
+GSI1PK: "PAU"
+
 Identity Source:
 PK: "P#[PROJECT_UUID]"
 SK: "P#[PROJECT_UUID]#PIS"
@@ -72,12 +135,25 @@ Template Link:
 PK: "P#[PROJECT_UUID]"
 SK: "P#[PROJECT_UUID]#PTL#[POLICY_ID]"
 GSI1PK: "PTL"
+
+Schema Version:
+PK: "SV"
+SK: "SV"
+GSI1PK: "SV"
 */
 
 const DEFAULT_ATT: &str = "__DEFAULT__";
 const SCHEMA_ATT: &str = "schema";
+const PROJECT_ID_ATT: &str = "id";
 const CREATED_AT_ATT: &str = "createdAt";
 const UPDATED_AT_ATT: &str = "updatedAt";
+const JOB_RESULT_ATT: &str = "result";
+const JOB_RESULT_CHUNKS_ATT: &str = "resultChunks";
+const JOB_TASK_ATT: &str = "task";
+const JOB_STATUS_ATT: &str = "status";
+const VERSION_ATT: &str = "version";
+const COMMON_TYPES_ATT: &str = "commonTypes";
+const REVISION_ATT: &str = "revision";
 
 #[derive(Debug)]
 pub struct QueryFilter {
@@ -88,6 +164,7 @@ pub struct QueryFilter {
     pub values: HashMap<String, AttributeValue>,
     pub limit: Option<i32>,
     pub start_key: Option<HashMap<String, AttributeValue>>,
+    pub projection: Option<String>,
 }
 
 impl Default for QueryFilter {
@@ -106,6 +183,7 @@ impl QueryFilter {
             values: HashMap::new(),
             limit: None,
             start_key: None,
+            projection: None,
         }
     }
 
@@ -120,6 +198,14 @@ impl QueryFilter {
             }
         }
 
+        for term in query_annotation_terms(query) {
+            let expression = filter.add_contains(ANNOTATION_INDEX_KEY, AttributeValue::S(term));
+            filter.filter = match filter.filter.take() {
+                Some(current) => Some(format!("({current}) AND {expression}")),
+                None => Some(expression),
+            };
+        }
+
         if let Some(limit) = query.limit
             && limit > 0
         {
@@ -147,6 +233,21 @@ impl QueryFilter {
         self.filter.clone()
     }
 
+    pub fn projection(&self) -> Option<String> {
+        self.projection.clone()
+    }
+
+    /// Only returns the given attributes of the items.
+    pub fn add_projection(&mut self, attributes: &[&str]) {
+        let mut projection = Vec::new();
+        for attribute in attributes {
+            let att_name = format!("#p{}", projection.len());
+            self.names.insert(att_name.clone(), attribute.to_string());
+            projection.push(att_name);
+        }
+        self.projection = Some(projection.join(", "));
+    }
+
     pub fn names(&self) -> Option<HashMap<String, String>> {
         if self.names.is_empty() {
             None
@@ -195,6 +296,39 @@ impl QueryFilter {
         format!("begins_with({att_name}, {att_val})")
     }
 
+    pub fn add_contains(&mut self, name: &str, value: AttributeValue) -> String {
+        let x = self.names.len();
+        let att_name = format!("#n{x}");
+        self.names.insert(att_name.clone(), name.to_string());
+
+        let x = self.values.len();
+        let att_val = format!(":v{x}");
+        self.values.insert(att_val.clone(), value);
+
+        format!("contains({att_name}, {att_val})")
+    }
+
+    // Filters of every selector, in parentheses, and none without selectors
+    fn join_filters(
+        path: String,
+        selectors: Vec<Selector>,
+        separator: &str,
+        expression: &mut String,
+        filter: &mut QueryFilter,
+    ) {
+        if selectors.is_empty() {
+            return;
+        }
+        expression.push('(');
+        for (i, selector) in selectors.into_iter().enumerate() {
+            if i > 0 {
+                expression.push_str(separator);
+            }
+            Self::selector_to_filter(path.clone(), selector, expression, filter);
+        }
+        expression.push(')');
+    }
+
     fn selector_to_filter(
         path: String,
         expr: Selector,
@@ -202,20 +336,8 @@ impl QueryFilter {
         filter: &mut QueryFilter,
     ) {
         match expr {
-            Selector::And(val) => {
-                let left = val[0].clone();
-                Self::selector_to_filter(path.clone(), left, expression, filter);
-                expression.push_str(" AND ");
-                let right = val[1].clone();
-                Self::selector_to_filter(path, right, expression, filter);
-            }
-            Selector::Or(val) => {
-                let left = val[0].clone();
-                Self::selector_to_filter(path.clone(), left, expression, filter);
-                expression.push_str(" OR ");
-                let right = val[1].clone();
-                Self::selector_to_filter(path, right, expression, filter);
-            }
+            Selector::And(val) => Self::join_filters(path, val, " AND ", expression, filter),
+            Selector::Or(val) => Self::join_filters(path, val, " OR ", expression, filter),
             Selector::Eq(val) => {
                 expression.push_str(&path);
                 expression.push_str(" = ");
@@ -258,10 +380,20 @@ impl QueryFilter {
             Selector::In(_items) => {}
             Selector::Nin(_items) => {}
             Selector::Record(map) => {
-                for (key, val) in map {
-                    let x = filter.names.len();
-                    let att_name = format!("#n{x}");
-                    filter.names.insert(att_name.clone(), key);
+                for (i, (key, val)) in map.into_iter().enumerate() {
+                    if i > 0 {
+                        expression.push_str(" AND ");
+                    }
+                    // A dotted key, e.g. `tags.env`, is a path into nested maps
+                    let att_name = key
+                        .split('.')
+                        .map(|segment| {
+                            let att_name = format!("#n{}", filter.names.len());
+                            filter.names.insert(att_name.clone(), segment.to_string());
+                            att_name
+                        })
+                        .collect::<Vec<_>>()
+                        .join(".");
 
                     let path = if path.is_empty() {
                         att_name
@@ -608,6 +740,306 @@ impl DynamoDb {
         Ok(serde_dynamo::from_item(item.clone())?)
     }
 
+    fn project_role_to_item(
+        &self,
+        project_id: &Uuid,
+        role: &Role,
+    ) -> Result<HashMap<String, AttributeValue>, DatabaseError> {
+        let mut item: HashMap<String, AttributeValue> = serde_dynamo::to_item(role)?;
+
+        item.insert(
+            CREATED_AT_ATT.to_string(),
+            AttributeValue::N(role.created_at.timestamp_millis().to_string()),
+        );
+        item.insert(
+            UPDATED_AT_ATT.to_string(),
+            AttributeValue::N(role.updated_at.timestamp_millis().to_string()),
+        );
+
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = format!("{}#{}#{}", pk, PROJECT_ROLE_TYPE, role.id);
+
+        self.add_indexes_to_item(&mut item, &pk, &sk, PROJECT_ROLE_TYPE);
+
+        Ok(item)
+    }
+
+    fn project_role_from_item(
+        &self,
+        item: &mut HashMap<String, AttributeValue>,
+    ) -> Result<Role, DatabaseError> {
+        let Some(created_at_att) = item.get(CREATED_AT_ATT) else {
+            return Err(DatabaseError::MissingAttribute(CREATED_AT_ATT.to_string()));
+        };
+        let Some(updated_at_att) = item.get(UPDATED_AT_ATT) else {
+            return Err(DatabaseError::MissingAttribute(UPDATED_AT_ATT.to_string()));
+        };
+        let Ok(created_at_val) = created_at_att.as_n() else {
+            return Err(DatabaseError::InvalidAttribute(CREATED_AT_ATT.to_string()));
+        };
+        let Ok(updated_at_val) = updated_at_att.as_n() else {
+            return Err(DatabaseError::InvalidAttribute(UPDATED_AT_ATT.to_string()));
+        };
+        let Ok(created_at_int) = created_at_val.parse::<i64>() else {
+            return Err(DatabaseError::InvalidAttribute(CREATED_AT_ATT.to_string()));
+        };
+        let Ok(updated_at_int) = updated_at_val.parse::<i64>() else {
+            return Err(DatabaseError::InvalidAttribute(UPDATED_AT_ATT.to_string()));
+        };
+        let Some(created_at) = chrono::DateTime::from_timestamp_millis(created_at_int) else {
+            return Err(DatabaseError::InvalidAttribute(CREATED_AT_ATT.to_string()));
+        };
+        let Some(updated_at) = chrono::DateTime::from_timestamp_millis(updated_at_int) else {
+            return Err(DatabaseError::InvalidAttribute(UPDATED_AT_ATT.to_string()));
+        };
+
+        item.insert(
+            CREATED_AT_ATT.to_string(),
+            AttributeValue::S(created_at.to_rfc3339()),
+        );
+        item.insert(
+            UPDATED_AT_ATT.to_string(),
+            AttributeValue::S(updated_at.to_rfc3339()),
+        );
+
+        Ok(serde_dynamo::from_item(item.clone())?)
+    }
+
+    // Prefix of the SKs of the chunk items of type `item_type` of a job
+    fn project_job_chunk_sk(project_id: &Uuid, job_id: &Uuid, item_type: &str) -> String {
+        format!("{}#{}#{}#{}#", PROJECT_TYPE, project_id, item_type, job_id)
+    }
+
+    // Items holding `bytes` under `att`, in as many chunks as the item size limit requires
+    fn project_job_chunk_items(
+        &self,
+        project_id: &Uuid,
+        job_id: &Uuid,
+        item_type: &str,
+        att: &str,
+        bytes: &[u8],
+    ) -> Vec<Item> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = Self::project_job_chunk_sk(project_id, job_id, item_type);
+        bytes
+            .chunks(JOB_RESULT_CHUNK_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut item =
+                    HashMap::from([(att.to_string(), AttributeValue::B(Blob::new(chunk)))]);
+                let sk = format!("{}{:06}", sk, index);
+                self.add_indexes_to_item(&mut item, &pk, &sk, item_type);
+                item
+            })
+            .collect()
+    }
+
+    // Chunk items of type `item_type` of a job, in order
+    async fn project_job_chunks_query(
+        &self,
+        project_id: &Uuid,
+        job_id: &Uuid,
+        item_type: &str,
+    ) -> Result<Vec<Item>, DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = Self::project_job_chunk_sk(project_id, job_id, item_type);
+
+        let mut filter = QueryFilter::new("#PK = :PK AND begins_with(#SK, :SK)");
+        filter.add_name("#PK", PK);
+        filter.add_name("#SK", SK);
+        filter.add_value(":PK", AttributeValue::S(pk));
+        filter.add_value(":SK", AttributeValue::S(sk));
+
+        Ok(self.query(&filter).await?.items)
+    }
+
+    fn project_job_chunks_join(items: &[Item], att: &str) -> Result<Vec<u8>, DatabaseError> {
+        let mut bytes = Vec::new();
+        for item in items {
+            let Some(AttributeValue::B(chunk)) = item.get(att) else {
+                return Err(DatabaseError::InvalidAttribute(att.to_string()));
+            };
+            bytes.extend_from_slice(chunk.as_ref());
+        }
+        Ok(bytes)
+    }
+
+    // The result of a job is stored apart from it, in as many chunk items as the item size
+    // limit requires, the job item keeping their count
+    fn project_job_to_items(
+        &self,
+        project_id: &Uuid,
+        job: &Job,
+    ) -> Result<(Item, Vec<Item>), DatabaseError> {
+        let mut item: HashMap<String, AttributeValue> = serde_dynamo::to_item(job)?;
+        item.remove(JOB_RESULT_ATT);
+
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+
+        // The export result may hold a schema with an empty namespace name, which is not a
+        // valid attribute name, so it is stored as a JSON document.
+        let mut chunks = Vec::new();
+        if let Some(result) = &job.result {
+            chunks = self.project_job_chunk_items(
+                project_id,
+                &job.id,
+                PROJECT_JOB_RESULT_TYPE,
+                JOB_RESULT_ATT,
+                &serde_json::to_vec(result)?,
+            );
+            item.insert(
+                JOB_RESULT_CHUNKS_ATT.to_string(),
+                AttributeValue::N(chunks.len().to_string()),
+            );
+        }
+
+        item.insert(
+            CREATED_AT_ATT.to_string(),
+            AttributeValue::N(job.created_at.timestamp_millis().to_string()),
+        );
+        item.insert(
+            UPDATED_AT_ATT.to_string(),
+            AttributeValue::N(job.updated_at.timestamp_millis().to_string()),
+        );
+
+        let sk = format!("{}#{}#{}", pk, PROJECT_JOB_TYPE, job.id);
+
+        self.add_indexes_to_item(&mut item, &pk, &sk, PROJECT_JOB_TYPE);
+
+        Ok((item, chunks))
+    }
+
+    // Returns the job of an item with the number of chunk items holding its result
+    fn project_job_from_item(
+        &self,
+        item: &mut HashMap<String, AttributeValue>,
+    ) -> Result<(Job, usize), DatabaseError> {
+        let Some(created_at_att) = item.get(CREATED_AT_ATT) else {
+            return Err(DatabaseError::MissingAttribute(CREATED_AT_ATT.to_string()));
+        };
+        let Some(updated_at_att) = item.get(UPDATED_AT_ATT) else {
+            return Err(DatabaseError::MissingAttribute(UPDATED_AT_ATT.to_string()));
+        };
+        let Ok(created_at_val) = created_at_att.as_n() else {
+            return Err(DatabaseError::InvalidAttribute(CREATED_AT_ATT.to_string()));
+        };
+        let Ok(updated_at_val) = updated_at_att.as_n() else {
+            return Err(DatabaseError::InvalidAttribute(UPDATED_AT_ATT.to_string()));
+        };
+        let Ok(created_at_int) = created_at_val.parse::<i64>() else {
+            return Err(DatabaseError::InvalidAttribute(CREATED_AT_ATT.to_string()));
+        };
+        let Ok(updated_at_int) = updated_at_val.parse::<i64>() else {
+            return Err(DatabaseError::InvalidAttribute(UPDATED_AT_ATT.to_string()));
+        };
+        let Some(created_at) = chrono::DateTime::from_timestamp_millis(created_at_int) else {
+            return Err(DatabaseError::InvalidAttribute(CREATED_AT_ATT.to_string()));
+        };
+        let Some(updated_at) = chrono::DateTime::from_timestamp_millis(updated_at_int) else {
+            return Err(DatabaseError::InvalidAttribute(UPDATED_AT_ATT.to_string()));
+        };
+
+        item.insert(
+            CREATED_AT_ATT.to_string(),
+            AttributeValue::S(created_at.to_rfc3339()),
+        );
+        item.insert(
+            UPDATED_AT_ATT.to_string(),
+            AttributeValue::S(updated_at.to_rfc3339()),
+        );
+        let result = item.remove(JOB_RESULT_ATT);
+        let chunks = match item.remove(JOB_RESULT_CHUNKS_ATT) {
+            Some(chunks) => chunks
+                .as_n()
+                .ok()
+                .and_then(|chunks| chunks.parse::<usize>().ok())
+                .ok_or_else(|| {
+                    DatabaseError::InvalidAttribute(JOB_RESULT_CHUNKS_ATT.to_string())
+                })?,
+            None => 0,
+        };
+
+        let mut job: Job = serde_dynamo::from_item(item.clone())?;
+        // Results saved before they were chunked are held by the job item
+        if let Some(result) = result {
+            let Ok(result) = result.as_s() else {
+                return Err(DatabaseError::InvalidAttribute(JOB_RESULT_ATT.to_string()));
+            };
+            job.result = Some(serde_json::from_str(result)?);
+        }
+
+        Ok((job, chunks))
+    }
+
+    fn project_job_result_from_items(
+        items: &[HashMap<String, AttributeValue>],
+        chunks: usize,
+    ) -> Result<ProjectData, DatabaseError> {
+        if items.len() != chunks {
+            return Err(DatabaseError::MissingAttribute(JOB_RESULT_ATT.to_string()));
+        }
+        let result = Self::project_job_chunks_join(items, JOB_RESULT_ATT)?;
+        Ok(serde_json::from_slice(&result)?)
+    }
+
+    async fn project_job_result_load(
+        &self,
+        project_id: &Uuid,
+        job: &mut Job,
+        chunks: usize,
+    ) -> Result<(), DatabaseError> {
+        if chunks == 0 {
+            return Ok(());
+        }
+        let items = self
+            .project_job_chunks_query(project_id, &job.id, PROJECT_JOB_RESULT_TYPE)
+            .await?;
+        job.result = Some(Self::project_job_result_from_items(&items, chunks)?);
+
+        Ok(())
+    }
+
+    fn project_audit_to_item(
+        &self,
+        project_id: &Uuid,
+        record: &AuditRecord,
+    ) -> Result<HashMap<String, AttributeValue>, DatabaseError> {
+        let mut item: HashMap<String, AttributeValue> = serde_dynamo::to_item(record)?;
+
+        let pk = format!("{}#{}", PROJECT_AUDIT_TYPE, project_id);
+        let sk = format!("{}#{}", PROJECT_AUDIT_TYPE, record.id);
+
+        self.add_indexes_to_item(&mut item, &pk, &sk, PROJECT_AUDIT_TYPE);
+
+        Ok(item)
+    }
+
+    fn project_role_assignment_to_item(
+        &self,
+        project_id: &Uuid,
+        assignment: &RoleAssignment,
+    ) -> Result<HashMap<String, AttributeValue>, DatabaseError> {
+        let mut item: HashMap<String, AttributeValue> = serde_dynamo::to_item(assignment)?;
+
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = format!(
+            "{}#{}#{}",
+            pk,
+            PROJECT_ROLE_ASSIGNMENT_TYPE,
+            assignment.id()
+        );
+        self.add_indexes_to_item(&mut item, &pk, &sk, PROJECT_ROLE_ASSIGNMENT_TYPE);
+
+        Ok(item)
+    }
+
+    fn project_role_assignment_from_item(
+        &self,
+        item: &HashMap<String, AttributeValue>,
+    ) -> Result<RoleAssignment, DatabaseError> {
+        Ok(serde_dynamo::from_item(item.clone())?)
+    }
+
     fn project_identity_source_to_item(
         &self,
         project_id: &Uuid,
@@ -663,6 +1095,11 @@ impl DynamoDb {
             AttributeValue::S(policy_id.to_string()),
         );
 
+        let terms = annotation_index_terms(&policy.annotations);
+        if !terms.is_empty() {
+            item.insert(ANNOTATION_INDEX_KEY.to_string(), AttributeValue::Ss(terms));
+        }
+
         let pk = format!("{}#{}", PROJECT_TYPE, project_id);
         let sk = format!("{}#{}#{}", pk, PROJECT_POLICY_TYPE, policy_id);
         self.add_indexes_to_item(&mut item, &pk, &sk, PROJECT_POLICY_TYPE);
@@ -749,6 +1186,52 @@ impl DynamoDb {
         Ok(serde_dynamo::from_item(item.clone())?)
     }
 
+    fn project_revisions_sk(project_id: &Uuid, kind: Option<RevisionKind>) -> String {
+        let sk = format!("{}#{}#{}#", PROJECT_TYPE, project_id, PROJECT_REVISION_TYPE);
+        match kind {
+            Some(kind) => format!("{}{}#", sk, kind.as_str()),
+            None => sk,
+        }
+    }
+
+    fn project_revision_to_item(
+        &self,
+        project_id: &Uuid,
+        revision: &Revision,
+    ) -> Result<HashMap<String, AttributeValue>, DatabaseError> {
+        // Kept as one JSON string, policies nest deeper than DynamoDB maps allow
+        let mut item = HashMap::new();
+        item.insert(
+            REVISION_ATT.to_string(),
+            AttributeValue::S(serde_json::to_string(revision)?),
+        );
+
+        // Zero padded, so the sort key orders revisions by time
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = format!(
+            "{}{:020}#{}",
+            Self::project_revisions_sk(project_id, Some(revision.kind)),
+            revision.at.timestamp_micros(),
+            revision.id
+        );
+        self.add_indexes_to_item(&mut item, &pk, &sk, PROJECT_REVISION_TYPE);
+
+        Ok(item)
+    }
+
+    fn project_revision_from_item(
+        &self,
+        item: &HashMap<String, AttributeValue>,
+    ) -> Result<Revision, DatabaseError> {
+        let Some(revision) = item.get(REVISION_ATT) else {
+            return Err(DatabaseError::MissingAttribute(REVISION_ATT.to_string()));
+        };
+        let Ok(revision) = revision.as_s() else {
+            return Err(DatabaseError::InvalidAttribute(REVISION_ATT.to_string()));
+        };
+        Ok(serde_json::from_str(revision)?)
+    }
+
     async fn batch_write_item(
         &self,
         request_items: Vec<WriteRequest>,
@@ -848,6 +1331,44 @@ impl DynamoDb {
         self.batch_write_item(requests).await
     }
 
+    /// Rewrites the annotation index of every policy, indexing the policies saved before it
+    /// existed and the terms saved before their escaping. The policies are paged through and
+    /// each page is written back before the next one is read.
+    async fn policy_annotations_reindex(&self) -> Result<(), DatabaseError> {
+        let mut filter = QueryFilter::new("#GSI1_PK = :GSI1_PK");
+        filter.add_name("#GSI1_PK", GSI1_PK);
+        filter.add_value(
+            ":GSI1_PK",
+            AttributeValue::S(PROJECT_POLICY_TYPE.to_string()),
+        );
+        filter.index = Some(GSI1.to_string());
+        filter.limit = Some(REINDEX_PAGE_SIZE);
+
+        // Each page is written as it is read, holding a single page in memory
+        loop {
+            let page = self.query(&filter).await?;
+            let mut items = page.items;
+            for item in items.iter_mut() {
+                let (_, policy) = self.project_policy_from_item(item)?;
+                let terms = annotation_index_terms(&policy.annotations);
+                if terms.is_empty() {
+                    item.remove(ANNOTATION_INDEX_KEY);
+                } else {
+                    item.insert(ANNOTATION_INDEX_KEY.to_string(), AttributeValue::Ss(terms));
+                }
+            }
+            if !items.is_empty() {
+                self.put_items(items).await?;
+            }
+
+            let Some(last_key) = page.last_key else {
+                return Ok(());
+            };
+            let key: serde_json::Value = serde_json::from_str(&last_key)?;
+            filter.start_key = Some(serde_dynamo::to_item(key)?);
+        }
+    }
+
     pub async fn query(&self, filter: &QueryFilter) -> Result<DynamoDBPage, DatabaseError> {
         let limit = filter.limit;
 
@@ -861,6 +1382,7 @@ impl DynamoDb {
             .set_expression_attribute_names(filter.names())
             .set_expression_attribute_values(filter.values())
             .set_exclusive_start_key(filter.start_key.clone())
+            .set_projection_expression(filter.projection())
             .set_limit(limit)
             .send()
             .await
@@ -891,6 +1413,7 @@ impl DynamoDb {
                 .set_filter_expression(filter.filter())
                 .set_expression_attribute_names(filter.names())
                 .set_expression_attribute_values(filter.values())
+                .set_projection_expression(filter.projection())
                 .set_limit(new_limit)
                 .set_exclusive_start_key(Some(last_key))
                 .send()
@@ -924,19 +1447,112 @@ impl DynamoDb {
 
 #[async_trait::async_trait]
 impl Database for DynamoDb {
-    async fn projects_load(&self, query: &Query) -> Result<PageList<Project>, DatabaseError> {
-        let mut filter = QueryFilter::new_with_query(query, "#GSI1_PK = :GSI1_PK")?;
-        filter.add_name("#GSI1_PK", GSI1_PK);
+    fn migrations(&self) -> &'static [Migration] {
+        MIGRATIONS
+    }
+
+    async fn schema_version_load(&self) -> Result<u32, DatabaseError> {
+        let Some(item) = self
+            .get_item(SCHEMA_VERSION_TYPE, SCHEMA_VERSION_TYPE)
+            .await?
+        else {
+            return Ok(0);
+        };
+        item.get(VERSION_ATT)
+            .and_then(|v| v.as_n().ok())
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or_else(|| DatabaseError::InvalidAttribute(VERSION_ATT.to_string()))
+    }
+
+    async fn schema_version_save(&self, version: u32) -> Result<(), DatabaseError> {
+        let mut item = HashMap::new();
+        item.insert(
+            VERSION_ATT.to_string(),
+            AttributeValue::N(version.to_string()),
+        );
+        self.add_indexes_to_item(
+            &mut item,
+            SCHEMA_VERSION_TYPE,
+            SCHEMA_VERSION_TYPE,
+            SCHEMA_VERSION_TYPE,
+        );
+        self.put_item(item).await
+    }
+
+    async fn migration_apply(&self, migration: &Migration) -> Result<(), DatabaseError> {
+        match migration.version {
+            1 => Ok(()),
+            2 => self.policy_annotations_reindex().await,
+            version => Err(DatabaseError::MigrationError(format!(
+                "unknown dynamodb migration {}",
+                version
+            ))),
+        }
+    }
+
+    async fn common_types_load(&self) -> Result<HashMap<String, TypeJson>, DatabaseError> {
+        let Some(item) = self.get_item(COMMON_TYPES_TYPE, COMMON_TYPES_TYPE).await? else {
+            return Ok(HashMap::new());
+        };
+        let Some(common_types) = item.get(COMMON_TYPES_ATT) else {
+            return Err(DatabaseError::MissingAttribute(
+                COMMON_TYPES_ATT.to_string(),
+            ));
+        };
+        let Ok(common_types) = common_types.as_s() else {
+            return Err(DatabaseError::InvalidAttribute(
+                COMMON_TYPES_ATT.to_string(),
+            ));
+        };
+        Ok(serde_json::from_str(common_types)?)
+    }
+
+    async fn common_types_save(
+        &self,
+        common_types: &HashMap<String, TypeJson>,
+    ) -> Result<(), DatabaseError> {
+        // Kept as one JSON string, types nest deeper than DynamoDB maps allow
+        let mut item = HashMap::new();
+        item.insert(
+            COMMON_TYPES_ATT.to_string(),
+            AttributeValue::S(serde_json::to_string(common_types)?),
+        );
+        self.add_indexes_to_item(
+            &mut item,
+            COMMON_TYPES_TYPE,
+            COMMON_TYPES_TYPE,
+            COMMON_TYPES_TYPE,
+        );
+        self.put_item(item).await
+    }
+
+    async fn projects_load(&self, query: &Query) -> Result<PageList<Project>, DatabaseError> {
+        check_projects_sort(query)?;
+        let mut filter = QueryFilter::new_with_query(query, "#GSI1_PK = :GSI1_PK")?;
+        filter.add_name("#GSI1_PK", GSI1_PK);
         filter.add_value(":GSI1_PK", AttributeValue::S(PROJECT_TYPE.to_string()));
 
         filter.index = Some(GSI1.to_string());
 
+        if !query.fields.is_empty() {
+            // The id is always returned, for authorization, and the dates to rebuild projects
+            let mut attributes = vec![PROJECT_ID_ATT, CREATED_AT_ATT, UPDATED_AT_ATT];
+            for field in &query.fields {
+                if !attributes.contains(&field.as_str()) {
+                    attributes.push(field);
+                }
+            }
+            filter.add_projection(&attributes);
+        }
+
         let page = self.query(&filter).await?;
 
         let mut datas = Vec::new();
         for mut item in page.items {
             datas.push(Self::project_from_item(&self, &mut item)?);
         }
+        // GSI1 has no sort key, a sorted listing holds every project
+        Project::sort(&mut datas, &query.sort);
 
         Ok(PageList::new(datas, page.last_key))
     }
@@ -1054,6 +1670,248 @@ impl Database for DynamoDb {
         Ok(())
     }
 
+    async fn project_roles_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Role>, DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = format!("{}#{}#", pk, PROJECT_ROLE_TYPE);
+
+        let mut filter = QueryFilter::new_with_query(query, "#PK = :PK AND begins_with(#SK, :SK)")?;
+        filter.add_name("#PK", PK);
+        filter.add_name("#SK", SK);
+        filter.add_value(":PK", AttributeValue::S(pk));
+        filter.add_value(":SK", AttributeValue::S(sk));
+
+        let page = self.query(&filter).await?;
+
+        let mut datas = Vec::new();
+        for mut item in page.items {
+            datas.push(Self::project_role_from_item(&self, &mut item)?);
+        }
+
+        Ok(PageList::new(datas, page.last_key))
+    }
+
+    async fn project_roles_save(
+        &self,
+        project_id: &Uuid,
+        roles: &Vec<Role>,
+    ) -> Result<(), DatabaseError> {
+        let mut items = Vec::new();
+        for role in roles {
+            let item = self.project_role_to_item(project_id, role)?;
+            items.push(item)
+        }
+
+        self.put_items(items).await
+    }
+
+    async fn project_roles_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<String>,
+    ) -> Result<(), DatabaseError> {
+        let mut keys = Vec::new();
+        for id in ids {
+            let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+            let sk = format!("{}#{}#{}", pk, PROJECT_ROLE_TYPE, id);
+
+            keys.push((pk, sk));
+        }
+
+        self.delete_items(keys).await?;
+
+        Ok(())
+    }
+
+    async fn project_role_assignment_load(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<Option<RoleAssignment>, DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = format!("{}#{}#{}", pk, PROJECT_ROLE_ASSIGNMENT_TYPE, id);
+
+        let item = self.get_item(&pk, &sk).await?;
+        let assignment = item
+            .map(|i| self.project_role_assignment_from_item(&i))
+            .transpose()?;
+        Ok(assignment)
+    }
+
+    async fn project_role_assignment_save(
+        &self,
+        project_id: &Uuid,
+        assignment: &RoleAssignment,
+    ) -> Result<(), DatabaseError> {
+        let item = self.project_role_assignment_to_item(project_id, assignment)?;
+        self.put_item(item).await
+    }
+
+    async fn project_role_assignment_remove(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<(), DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = format!("{}#{}#{}", pk, PROJECT_ROLE_ASSIGNMENT_TYPE, id);
+
+        self.delete_item(&pk, &sk).await
+    }
+
+    async fn project_jobs_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Job>, DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = format!("{}#{}#", pk, PROJECT_JOB_TYPE);
+
+        let mut filter = QueryFilter::new_with_query(query, "#PK = :PK AND begins_with(#SK, :SK)")?;
+        filter.add_name("#PK", PK);
+        filter.add_name("#SK", SK);
+        filter.add_value(":PK", AttributeValue::S(pk));
+        filter.add_value(":SK", AttributeValue::S(sk));
+
+        let page = self.query(&filter).await?;
+
+        let mut datas = Vec::new();
+        for mut item in page.items {
+            let (mut job, chunks) = self.project_job_from_item(&mut item)?;
+            self.project_job_result_load(project_id, &mut job, chunks)
+                .await?;
+            datas.push(job);
+        }
+
+        Ok(PageList::new(datas, page.last_key))
+    }
+
+    async fn project_job_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<Job>, DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = format!("{}#{}#{}", pk, PROJECT_JOB_TYPE, id);
+
+        let Some(mut item) = self.get_item(&pk, &sk).await? else {
+            return Ok(None);
+        };
+        let (mut job, chunks) = self.project_job_from_item(&mut item)?;
+        self.project_job_result_load(project_id, &mut job, chunks)
+            .await?;
+        Ok(Some(job))
+    }
+
+    async fn project_job_save(&self, project_id: &Uuid, job: &Job) -> Result<(), DatabaseError> {
+        let (item, chunks) = self.project_job_to_items(project_id, job)?;
+        // The chunks are written first, the job item referencing them only once complete
+        self.put_items(chunks).await?;
+        self.put_item(item).await
+    }
+
+    async fn jobs_unfinished_load(&self) -> Result<Vec<Job>, DatabaseError> {
+        let mut filter = QueryFilter::new("#GSI1_PK = :GSI1_PK");
+        filter.add_name("#GSI1_PK", GSI1_PK);
+        filter.add_value(":GSI1_PK", AttributeValue::S(PROJECT_JOB_TYPE.to_string()));
+        filter.index = Some(GSI1.to_string());
+        filter.filter = Some("#STATUS IN (:PENDING, :RUNNING)".to_string());
+        filter.add_name("#STATUS", JOB_STATUS_ATT);
+        filter.add_value(":PENDING", AttributeValue::S("pending".to_string()));
+        filter.add_value(":RUNNING", AttributeValue::S("running".to_string()));
+
+        let mut jobs = Vec::new();
+        for mut item in self.query(&filter).await?.items {
+            // Unfinished, the job has no result yet
+            let (job, _) = self.project_job_from_item(&mut item)?;
+            jobs.push(job);
+        }
+
+        Ok(jobs)
+    }
+
+    async fn project_job_task_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<JobTask>, DatabaseError> {
+        let items = self
+            .project_job_chunks_query(project_id, id, PROJECT_JOB_TASK_TYPE)
+            .await?;
+        if items.is_empty() {
+            return Ok(None);
+        }
+        let task = Self::project_job_chunks_join(&items, JOB_TASK_ATT)?;
+        Ok(Some(serde_json::from_slice(&task)?))
+    }
+
+    // Stored as a JSON document, the imported schema may have an empty namespace name
+    async fn project_job_task_save(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+        task: &JobTask,
+    ) -> Result<(), DatabaseError> {
+        let items = self.project_job_chunk_items(
+            project_id,
+            id,
+            PROJECT_JOB_TASK_TYPE,
+            JOB_TASK_ATT,
+            &serde_json::to_vec(task)?,
+        );
+        self.put_items(items).await
+    }
+
+    async fn project_job_task_remove(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<(), DatabaseError> {
+        let keys = self
+            .project_job_chunks_query(project_id, id, PROJECT_JOB_TASK_TYPE)
+            .await?
+            .iter()
+            .filter_map(|item| Some((item.get(PK)?.as_s().ok()?, item.get(SK)?.as_s().ok()?)))
+            .map(|(pk, sk)| (pk.clone(), sk.clone()))
+            .collect();
+        self.delete_items(keys).await
+    }
+
+    async fn project_audit_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<AuditRecord>, DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_AUDIT_TYPE, project_id);
+        let sk = format!("{}#", PROJECT_AUDIT_TYPE);
+
+        let mut filter = QueryFilter::new_with_query(query, "#PK = :PK AND begins_with(#SK, :SK)")?;
+        filter.add_name("#PK", PK);
+        filter.add_name("#SK", SK);
+        filter.add_value(":PK", AttributeValue::S(pk));
+        filter.add_value(":SK", AttributeValue::S(sk));
+
+        let page = self.query(&filter).await?;
+
+        let mut datas = Vec::new();
+        for item in page.items {
+            datas.push(serde_dynamo::from_item(item)?);
+        }
+
+        Ok(PageList::new(datas, page.last_key))
+    }
+
+    async fn project_audit_save(
+        &self,
+        project_id: &Uuid,
+        record: &AuditRecord,
+    ) -> Result<(), DatabaseError> {
+        let item = self.project_audit_to_item(project_id, record)?;
+        self.put_item(item).await
+    }
+
     async fn project_identity_source_load(
         &self,
         project_id: &Uuid,
@@ -1135,6 +1993,8 @@ impl Database for DynamoDb {
         for item in page.items {
             datas.push(self.project_entity_from_item(&item)?);
         }
+        // Sorted within the page only, a sort on tags is applied to the whole set by Cedrus
+        sort_entities(&mut datas, &query.sort);
 
         Ok(PageList::new(datas, page.last_key))
     }
@@ -1338,13 +2198,89 @@ impl Database for DynamoDb {
 
         self.delete_items(keys).await
     }
+
+    async fn project_revisions_load(
+        &self,
+        project_id: &Uuid,
+        kind: RevisionKind,
+        until: &chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Revision>, DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let from = Self::project_revisions_sk(project_id, Some(kind));
+        // `$` sorts right after the `#` ending the time of the revisions at `until`
+        let to = format!("{}{:020}$", from, until.timestamp_micros());
+
+        let mut filter = QueryFilter::new("#PK = :PK AND #SK BETWEEN :FROM AND :TO");
+        filter.add_name("#PK", PK);
+        filter.add_name("#SK", SK);
+        filter.add_value(":PK", AttributeValue::S(pk));
+        filter.add_value(":FROM", AttributeValue::S(from));
+        filter.add_value(":TO", AttributeValue::S(to));
+
+        let page = self.query(&filter).await?;
+
+        page.items
+            .iter()
+            .map(|item| self.project_revision_from_item(item))
+            .collect()
+    }
+
+    async fn project_revisions_save(
+        &self,
+        project_id: &Uuid,
+        revisions: &[Revision],
+    ) -> Result<(), DatabaseError> {
+        let items = revisions
+            .iter()
+            .map(|revision| self.project_revision_to_item(project_id, revision))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.put_items(items).await
+    }
+
+    async fn project_revisions_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        let pk = format!("{}#{}", PROJECT_TYPE, project_id);
+        let sk = Self::project_revisions_sk(project_id, None);
+
+        let mut filter = QueryFilter::new("#PK = :PK AND begins_with(#SK, :SK)");
+        filter.add_name("#PK", PK);
+        filter.add_name("#SK", SK);
+        filter.add_value(":PK", AttributeValue::S(pk.clone()));
+        filter.add_value(":SK", AttributeValue::S(sk));
+        filter.add_projection(&[SK]);
+
+        let page = self.query(&filter).await?;
+        let keys = page
+            .items
+            .iter()
+            .filter_map(|item| item.get(SK).and_then(|sk| sk.as_s().ok()))
+            .map(|sk| (pk.clone(), sk.clone()))
+            .collect();
+
+        self.delete_items(keys).await
+    }
+}
+
+/// GSI1 has no sort key, so projects are sorted once every page is read: a sorted listing
+/// can't be limited nor continued.
+fn check_projects_sort(query: &Query) -> Result<(), DatabaseError> {
+    if !query.sort.is_empty()
+        && (query.limit.is_some_and(|limit| limit > 0) || query.start_key.is_some())
+    {
+        return Err(DatabaseError::InvalidQuery(
+            "projects can't be sorted with a limit or a last key".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::Query;
-    use cedrus_cedar::{Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink};
+    use cedrus_cedar::{
+        Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink, entity::EntityAttr,
+    };
     use std::collections::{HashMap, HashSet};
     use uuid::Uuid;
 
@@ -1358,6 +2294,7 @@ mod tests {
         let table_name = format!("test_cedrus_table_{}", Uuid::now_v7().simple());
         let conf = crate::core::DynamoDBConfig {
             endpoint_url: Some("http://localhost:8000".to_string()),
+            read_endpoint_url: None,
             region: Some("us-east-1".to_string()),
             table_name,
             initialize: true,
@@ -1379,6 +2316,85 @@ mod tests {
             .await;
     }
 
+    #[test]
+    fn test_query_filter_selectors() {
+        let query = |selector| Query {
+            selector: Some(serde_json::from_value(selector).unwrap()),
+            ..Default::default()
+        };
+
+        let or = query(serde_json::json!({ "$or": [{ "a": "x" }, { "b": 1 }, { "c": true }] }));
+        let filter = QueryFilter::new_with_query(&or, "#PK = :PK").unwrap();
+        assert_eq!(
+            filter.filter.as_deref(),
+            Some("(#n0 = :v0 OR #n1 = :v1 OR #n2 = :v2)")
+        );
+
+        // Joins without selectors filter nothing
+        let and = query(serde_json::json!({ "$and": [] }));
+        let filter = QueryFilter::new_with_query(&and, "#PK = :PK").unwrap();
+        assert_eq!(filter.filter, None);
+    }
+
+    #[test]
+    fn test_query_filter_annotations() {
+        // The key of a filter can not reach into the value of an annotation
+        let query = Query {
+            annotations: HashMap::from([("owner=team".to_string(), "x".to_string())]),
+            ..Default::default()
+        };
+        let filter = QueryFilter::new_with_query(&query, "#PK = :PK").unwrap();
+        assert_eq!(filter.filter(), Some("contains(#n0, :v0)".to_string()));
+        assert_eq!(filter.names["#n0"], ANNOTATION_INDEX_KEY);
+        assert_eq!(
+            filter.values[":v0"],
+            AttributeValue::S("owner\\=team=x".to_string())
+        );
+
+        let annotations = HashMap::from([("owner".to_string(), Some("team=x".to_string()))]);
+        assert_eq!(
+            annotation_index_terms(&annotations),
+            vec!["owner=team\\=x".to_string()]
+        );
+        let annotations = HashMap::from([("path".to_string(), Some("a\\".to_string()))]);
+        assert_eq!(
+            annotation_index_terms(&annotations),
+            vec!["path=a\\\\".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_check_projects_sort() {
+        let sorted = Query {
+            sort: vec![crate::Sort::new("name".to_string(), crate::SortOrder::Asc)],
+            ..Default::default()
+        };
+        assert!(check_projects_sort(&sorted).is_ok());
+        assert!(
+            check_projects_sort(&Query {
+                limit: Some(10),
+                ..Default::default()
+            })
+            .is_ok()
+        );
+
+        for query in [
+            Query {
+                limit: Some(10),
+                ..sorted.clone()
+            },
+            Query {
+                start_key: Some("{}".to_string()),
+                ..sorted.clone()
+            },
+        ] {
+            assert!(matches!(
+                check_projects_sort(&query),
+                Err(DatabaseError::InvalidQuery(_))
+            ));
+        }
+    }
+
     #[tokio::test]
     async fn test_project_crud() {
         let db = setup_test_db().await;
@@ -1487,6 +2503,7 @@ mod tests {
                     group_configuration: None,
                 },
             ),
+            entity_mapper: None,
         };
 
         // Test save
@@ -1629,6 +2646,40 @@ mod tests {
         teardown_test_db(&db).await;
     }
 
+    #[tokio::test]
+    async fn test_policy_annotations_reindex() {
+        let db = setup_test_db().await;
+
+        let project_id = Uuid::now_v7();
+        let policy_id = PolicyId::from("owned".to_string());
+        let policy = Policy {
+            annotations: HashMap::from([("owner".to_string(), Some("team=x".to_string()))]),
+            ..Default::default()
+        };
+        let query = Query {
+            annotations: HashMap::from([("owner".to_string(), "team=x".to_string())]),
+            ..Default::default()
+        };
+
+        // A policy indexed before the terms were escaped
+        let mut item = db
+            .project_policy_to_item(&project_id, &policy_id, &policy)
+            .unwrap();
+        item.insert(
+            ANNOTATION_INDEX_KEY.to_string(),
+            AttributeValue::Ss(vec!["owner=team=x".to_string()]),
+        );
+        db.put_items(vec![item]).await.unwrap();
+        let page = db.project_policies_load(&project_id, &query).await.unwrap();
+        assert!(page.items.is_empty());
+
+        db.migration_apply(&MIGRATIONS[1]).await.unwrap();
+        let page = db.project_policies_load(&project_id, &query).await.unwrap();
+        assert!(page.items.contains_key(&policy_id));
+
+        teardown_test_db(&db).await;
+    }
+
     #[tokio::test]
     async fn test_template_crud() {
         let db = setup_test_db().await;
@@ -1706,6 +2757,7 @@ mod tests {
         teardown_test_db(&db).await;
     }
 
+    #[allow(clippy::field_reassign_with_default)]
     #[tokio::test]
     async fn test_query_limit_and_pagination() {
         let db = setup_test_db().await;
@@ -1750,4 +2802,82 @@ mod tests {
 
         teardown_test_db(&db).await;
     }
+
+    #[tokio::test]
+    async fn test_job_result_chunks() {
+        let conf = crate::core::DynamoDBConfig {
+            endpoint_url: Some("http://localhost:8000".to_string()),
+            region: Some("us-east-1".to_string()),
+            table_name: "test_cedrus_table".to_string(),
+            ..Default::default()
+        };
+        let db = DynamoDb::new(&conf).await.unwrap();
+
+        let project_id = Uuid::now_v7();
+        let mut job = Job::new(project_id, crate::core::job::JobKind::Export);
+        let entities = (0..20_000)
+            .map(|i| {
+                Entity::new_no_attrs(
+                    EntityUid::new("User".to_string(), format!("user-{i:032}")),
+                    HashSet::new(),
+                )
+            })
+            .collect();
+        job.succeed(Some(ProjectData {
+            entities,
+            ..Default::default()
+        }));
+
+        let (mut item, chunks) = db.project_job_to_items(&project_id, &job).unwrap();
+        assert!(chunks.len() > 1);
+        assert!(!item.contains_key(JOB_RESULT_ATT));
+        for chunk in &chunks {
+            let Some(AttributeValue::B(bytes)) = chunk.get(JOB_RESULT_ATT) else {
+                panic!("chunk without result");
+            };
+            assert!(bytes.as_ref().len() <= JOB_RESULT_CHUNK_SIZE);
+        }
+
+        let (mut loaded, count) = db.project_job_from_item(&mut item).unwrap();
+        assert_eq!(count, chunks.len());
+        assert!(DynamoDb::project_job_result_from_items(&chunks[1..], count).is_err());
+        loaded.result = Some(DynamoDb::project_job_result_from_items(&chunks, count).unwrap());
+        assert_eq!((loaded.id, loaded.status), (job.id, job.status));
+        assert!(loaded.result == job.result);
+    }
+
+    #[test]
+    fn test_tag_selector_and_sort() {
+        let selector: crate::Selector =
+            serde_json::from_value(serde_json::json!({"tags.env": {"$eq": "prod"}})).unwrap();
+        let query = Query {
+            selector: Some(selector),
+            ..Default::default()
+        };
+        let filter = QueryFilter::new_with_query(&query, "#PK = :PK").unwrap();
+        assert_eq!(filter.filter(), Some("#n0.#n1 = :v0".to_string()));
+        assert_eq!(filter.names["#n0"], "tags");
+        assert_eq!(filter.names["#n1"], "env");
+        assert_eq!(filter.values[":v0"], AttributeValue::S("prod".to_string()));
+
+        let tagged = |id: &str, tier: Option<i64>| {
+            let tags = tier
+                .map(|tier| HashMap::from([("tier".to_string(), EntityAttr::Number(tier))]))
+                .unwrap_or_default();
+            Entity::new_with_tags(
+                EntityUid::new("Doc".to_string(), id.to_string()),
+                HashMap::new(),
+                HashSet::new(),
+                tags,
+            )
+        };
+        let mut entities = vec![
+            tagged("a", None),
+            tagged("b", Some(2)),
+            tagged("c", Some(1)),
+        ];
+        sort_entities(&mut entities, &crate::Sort::parse_list("tags.tier"));
+        let ids: Vec<&str> = entities.iter().map(|e| e.uid().id()).collect();
+        assert_eq!(ids, vec!["c", "b", "a"]);
+    }
 }
@@ -0,0 +1,684 @@
+use std::{collections::HashMap, sync::Arc};
+
+use cedrus_cedar::{
+    Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink, entity::EntityAttr,
+    schema::TypeJson,
+};
+use uuid::Uuid;
+
+use crate::{
+    PageHash, PageList, Query,
+    core::{
+        IdentitySource,
+        audit::AuditRecord,
+        crypto::{KeyProvider, is_encrypted, open, seal, sealed_key_id},
+        history::{Revision, RevisionKind},
+        job::{Job, JobTask},
+        project::{ApiKey, Project, Role, RoleAssignment},
+    },
+};
+
+use super::{Database, DatabaseError, migration::Migration};
+
+/// Entity type annotation listing the attributes encrypted at rest, comma separated, e.g.
+/// `@encrypted("ssn, email")`.
+pub const ANNOTATION_ENCRYPTED: &str = "encrypted";
+
+fn encryption_error(e: impl std::fmt::Display) -> DatabaseError {
+    DatabaseError::EncryptionError(e.to_string())
+}
+
+// Binds a value to the attribute of the entity it was sealed for
+fn attribute_aad(project_id: &Uuid, uid: &EntityUid, attr: &str) -> Vec<u8> {
+    format!("{project_id}\n{uid}\n{attr}").into_bytes()
+}
+
+/// Attributes of an entity type its schema marks as encrypted.
+pub fn encrypted_attributes(schema: &Schema, type_name: &str) -> Vec<String> {
+    schema
+        .entity_type(type_name)
+        .and_then(|(_, entity_type)| entity_type.annotations.get(ANNOTATION_ENCRYPTED))
+        .map(|attrs| {
+            attrs
+                .split(',')
+                .map(str::trim)
+                .filter(|attr| !attr.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Attributes marked encrypted of the types of the entities, by type
+fn attributes_by_type<'a>(
+    schema: &Schema,
+    entities: impl Iterator<Item = &'a Entity>,
+) -> HashMap<String, Vec<String>> {
+    let mut attrs_by_type: HashMap<String, Vec<String>> = HashMap::new();
+    for entity in entities {
+        let type_name = entity.uid().type_name();
+        if !attrs_by_type.contains_key(type_name) {
+            attrs_by_type.insert(
+                type_name.to_string(),
+                encrypted_attributes(schema, type_name),
+            );
+        }
+    }
+    attrs_by_type
+}
+
+/// Database storing the entity attributes marked `@encrypted` by the project schema as
+/// AES-256-GCM ciphertext, and handing them back in plaintext. Attributes are encrypted when
+/// entities are saved, with the schema stored then, and only the attributes the schema marks
+/// are decrypted when they are loaded, so values saved before an attribute was marked stay
+/// readable until they are saved again, and those of an attribute no longer marked stay
+/// sealed. Values written in the sealed format are rejected, whatever the attribute.
+/// Encrypted attributes can't be matched by the selectors of entity queries.
+pub struct EncryptedDb {
+    db: Box<dyn Database + Send + Sync>,
+    keys: Arc<dyn KeyProvider>,
+}
+
+impl EncryptedDb {
+    pub fn new(db: Box<dyn Database + Send + Sync>, keys: Arc<dyn KeyProvider>) -> Self {
+        Self { db, keys }
+    }
+
+    // Sealed values only ever come from this database, a client writing one could make the
+    // loads of the project fail once the attribute is marked
+    fn reject_sealed(entities: &[Entity]) -> Result<(), DatabaseError> {
+        for entity in entities {
+            for (attr, value) in entity.attrs() {
+                if matches!(value, EntityAttr::String(s) if is_encrypted(s)) {
+                    return Err(DatabaseError::SealedValue(format!(
+                        "{}.{}",
+                        entity.uid(),
+                        attr
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn encrypt_entities(
+        &self,
+        project_id: &Uuid,
+        entities: &[Entity],
+    ) -> Result<Option<Vec<Entity>>, DatabaseError> {
+        Self::reject_sealed(entities)?;
+        if entities.is_empty() {
+            return Ok(None);
+        }
+        let Some(schema) = self.db.project_schema_load(project_id).await? else {
+            return Ok(None);
+        };
+        let attrs_by_type = attributes_by_type(&schema, entities.iter());
+        if attrs_by_type.values().all(Vec::is_empty) {
+            return Ok(None);
+        }
+
+        let (key_id, key) = self.keys.encryption_key().await.map_err(encryption_error)?;
+        let mut encrypted = entities.to_vec();
+        for entity in &mut encrypted {
+            let uid = entity.uid().clone();
+            for attr in &attrs_by_type[uid.type_name()] {
+                let Some(value) = entity.attrs_mut().get_mut(attr) else {
+                    continue;
+                };
+                let plaintext = serde_json::to_vec(value)?;
+                let aad = attribute_aad(project_id, &uid, attr);
+                *value = EntityAttr::String(
+                    seal(&key_id, &key, &plaintext, &aad).map_err(encryption_error)?,
+                );
+            }
+        }
+
+        Ok(Some(encrypted))
+    }
+
+    async fn decrypt_entities(
+        &self,
+        project_id: &Uuid,
+        entities: &mut [Entity],
+    ) -> Result<(), DatabaseError> {
+        if entities.is_empty() {
+            return Ok(());
+        }
+        let Some(schema) = self.db.project_schema_load(project_id).await? else {
+            return Ok(());
+        };
+        let attrs_by_type = attributes_by_type(&schema, entities.iter());
+        if attrs_by_type.values().all(Vec::is_empty) {
+            return Ok(());
+        }
+
+        let mut keys: HashMap<String, Vec<u8>> = HashMap::new();
+        for entity in entities {
+            let uid = entity.uid().clone();
+            for attr in &attrs_by_type[uid.type_name()] {
+                let Some(value) = entity.attrs_mut().get_mut(attr) else {
+                    continue;
+                };
+                let EntityAttr::String(sealed) = value else {
+                    continue;
+                };
+                if !is_encrypted(sealed) {
+                    continue;
+                }
+
+                let key_id = sealed_key_id(sealed).map_err(encryption_error)?;
+                if !keys.contains_key(key_id) {
+                    let key = self
+                        .keys
+                        .decryption_key(key_id)
+                        .await
+                        .map_err(encryption_error)?;
+                    keys.insert(key_id.to_string(), key);
+                }
+                let aad = attribute_aad(project_id, &uid, attr);
+                let plaintext = open(sealed, &keys[key_id], &aad).map_err(encryption_error)?;
+                *value = serde_json::from_slice(&plaintext)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Positions and entities of the entity revisions holding a value
+    fn revision_entities(
+        revisions: &[Revision],
+    ) -> Result<(Vec<usize>, Vec<Entity>), DatabaseError> {
+        let mut positions = Vec::new();
+        let mut entities = Vec::new();
+        for (position, revision) in revisions.iter().enumerate() {
+            if let (RevisionKind::Entity, Some(value)) = (revision.kind, &revision.value) {
+                positions.push(position);
+                entities.push(serde_json::from_value(value.clone())?);
+            }
+        }
+        Ok((positions, entities))
+    }
+
+    fn revision_values(
+        revisions: &mut [Revision],
+        positions: &[usize],
+        entities: &[Entity],
+    ) -> Result<(), DatabaseError> {
+        for (position, entity) in positions.iter().zip(entities) {
+            revisions[*position].value = Some(serde_json::to_value(entity)?);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Database for EncryptedDb {
+    fn migrations(&self) -> &'static [Migration] {
+        self.db.migrations()
+    }
+
+    async fn schema_version_load(&self) -> Result<u32, DatabaseError> {
+        self.db.schema_version_load().await
+    }
+
+    async fn schema_version_save(&self, version: u32) -> Result<(), DatabaseError> {
+        self.db.schema_version_save(version).await
+    }
+
+    async fn migration_apply(&self, migration: &Migration) -> Result<(), DatabaseError> {
+        self.db.migration_apply(migration).await
+    }
+
+    async fn common_types_load(&self) -> Result<HashMap<String, TypeJson>, DatabaseError> {
+        self.db.common_types_load().await
+    }
+
+    async fn common_types_save(
+        &self,
+        common_types: &HashMap<String, TypeJson>,
+    ) -> Result<(), DatabaseError> {
+        self.db.common_types_save(common_types).await
+    }
+
+    async fn projects_load(&self, query: &Query) -> Result<PageList<Project>, DatabaseError> {
+        self.db.projects_load(query).await
+    }
+
+    async fn project_load(&self, id: &Uuid) -> Result<Option<Project>, DatabaseError> {
+        self.db.project_load(id).await
+    }
+
+    async fn project_save(&self, project: &Project) -> Result<(), DatabaseError> {
+        self.db.project_save(project).await
+    }
+
+    async fn project_remove(&self, id: &Uuid) -> Result<(), DatabaseError> {
+        self.db.project_remove(id).await
+    }
+
+    async fn project_identity_source_load(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<IdentitySource>, DatabaseError> {
+        self.db.project_identity_source_load(project_id).await
+    }
+
+    async fn project_identity_source_save(
+        &self,
+        project_id: &Uuid,
+        identity_source: &IdentitySource,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .project_identity_source_save(project_id, identity_source)
+            .await
+    }
+
+    async fn project_identity_source_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.db.project_identity_source_remove(project_id).await
+    }
+
+    async fn project_apikeys_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<ApiKey>, DatabaseError> {
+        self.db.project_apikeys_load(project_id, query).await
+    }
+
+    async fn project_apikeys_save(
+        &self,
+        project_id: &Uuid,
+        apikeys: &Vec<ApiKey>,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_apikeys_save(project_id, apikeys).await
+    }
+
+    async fn project_apikeys_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<Uuid>,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_apikeys_remove(project_id, ids).await
+    }
+
+    async fn project_roles_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Role>, DatabaseError> {
+        self.db.project_roles_load(project_id, query).await
+    }
+
+    async fn project_roles_save(
+        &self,
+        project_id: &Uuid,
+        roles: &Vec<Role>,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_roles_save(project_id, roles).await
+    }
+
+    async fn project_roles_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<String>,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_roles_remove(project_id, ids).await
+    }
+
+    async fn project_role_assignment_load(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<Option<RoleAssignment>, DatabaseError> {
+        self.db.project_role_assignment_load(project_id, id).await
+    }
+
+    async fn project_role_assignment_save(
+        &self,
+        project_id: &Uuid,
+        assignment: &RoleAssignment,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .project_role_assignment_save(project_id, assignment)
+            .await
+    }
+
+    async fn project_role_assignment_remove(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_role_assignment_remove(project_id, id).await
+    }
+
+    async fn project_jobs_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Job>, DatabaseError> {
+        self.db.project_jobs_load(project_id, query).await
+    }
+
+    async fn project_job_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<Job>, DatabaseError> {
+        self.db.project_job_load(project_id, id).await
+    }
+
+    async fn project_job_save(&self, project_id: &Uuid, job: &Job) -> Result<(), DatabaseError> {
+        self.db.project_job_save(project_id, job).await
+    }
+
+    async fn jobs_unfinished_load(&self) -> Result<Vec<Job>, DatabaseError> {
+        self.db.jobs_unfinished_load().await
+    }
+
+    async fn project_job_task_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<JobTask>, DatabaseError> {
+        self.db.project_job_task_load(project_id, id).await
+    }
+
+    async fn project_job_task_save(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+        task: &JobTask,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_job_task_save(project_id, id, task).await
+    }
+
+    async fn project_job_task_remove(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_job_task_remove(project_id, id).await
+    }
+
+    async fn project_audit_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<AuditRecord>, DatabaseError> {
+        self.db.project_audit_load(project_id, query).await
+    }
+
+    async fn project_audit_save(
+        &self,
+        project_id: &Uuid,
+        record: &AuditRecord,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_audit_save(project_id, record).await
+    }
+
+    async fn project_schema_load(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<Schema>, DatabaseError> {
+        self.db.project_schema_load(project_id).await
+    }
+
+    async fn project_schema_save(
+        &self,
+        project_id: &Uuid,
+        schema: &Schema,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_schema_save(project_id, schema).await
+    }
+
+    async fn project_schema_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.db.project_schema_remove(project_id).await
+    }
+
+    async fn project_entities_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Entity>, DatabaseError> {
+        let mut page = self.db.project_entities_load(project_id, query).await?;
+        self.decrypt_entities(project_id, &mut page.items).await?;
+        Ok(page)
+    }
+
+    async fn project_entities_save(
+        &self,
+        project_id: &Uuid,
+        entities: &Vec<Entity>,
+    ) -> Result<(), DatabaseError> {
+        match self.encrypt_entities(project_id, entities).await? {
+            Some(encrypted) => self.db.project_entities_save(project_id, &encrypted).await,
+            None => self.db.project_entities_save(project_id, entities).await,
+        }
+    }
+
+    async fn project_entities_remove(
+        &self,
+        project_id: &Uuid,
+        entity_uids: &Vec<EntityUid>,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .project_entities_remove(project_id, entity_uids)
+            .await
+    }
+
+    async fn project_policies_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageHash<PolicyId, Policy>, DatabaseError> {
+        self.db.project_policies_load(project_id, query).await
+    }
+
+    async fn project_policies_save(
+        &self,
+        project_id: &Uuid,
+        policies: &HashMap<PolicyId, Policy>,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_policies_save(project_id, policies).await
+    }
+
+    async fn project_policies_remove(
+        &self,
+        project_id: &Uuid,
+        policy_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .project_policies_remove(project_id, policy_ids)
+            .await
+    }
+
+    async fn project_templates_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageHash<PolicyId, Template>, DatabaseError> {
+        self.db.project_templates_load(project_id, query).await
+    }
+
+    async fn project_templates_save(
+        &self,
+        project_id: &Uuid,
+        templates: &HashMap<PolicyId, Template>,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_templates_save(project_id, templates).await
+    }
+
+    async fn project_templates_remove(
+        &self,
+        project_id: &Uuid,
+        template_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .project_templates_remove(project_id, template_ids)
+            .await
+    }
+
+    async fn project_template_links_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<TemplateLink>, DatabaseError> {
+        self.db.project_template_links_load(project_id, query).await
+    }
+
+    async fn project_template_links_save(
+        &self,
+        project_id: &Uuid,
+        template_links: &Vec<TemplateLink>,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .project_template_links_save(project_id, template_links)
+            .await
+    }
+
+    async fn project_template_links_remove(
+        &self,
+        project_id: &Uuid,
+        link_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .project_template_links_remove(project_id, link_ids)
+            .await
+    }
+
+    async fn project_revisions_load(
+        &self,
+        project_id: &Uuid,
+        kind: RevisionKind,
+        until: &chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Revision>, DatabaseError> {
+        let mut revisions = self
+            .db
+            .project_revisions_load(project_id, kind, until)
+            .await?;
+        let (positions, mut entities) = Self::revision_entities(&revisions)?;
+        if !entities.is_empty() {
+            self.decrypt_entities(project_id, &mut entities).await?;
+            Self::revision_values(&mut revisions, &positions, &entities)?;
+        }
+        Ok(revisions)
+    }
+
+    /// Entity revisions keep their attributes encrypted as the entities do.
+    async fn project_revisions_save(
+        &self,
+        project_id: &Uuid,
+        revisions: &[Revision],
+    ) -> Result<(), DatabaseError> {
+        let (positions, entities) = Self::revision_entities(revisions)?;
+        match self.encrypt_entities(project_id, &entities).await? {
+            Some(encrypted) => {
+                let mut revisions = revisions.to_vec();
+                Self::revision_values(&mut revisions, &positions, &encrypted)?;
+                self.db.project_revisions_save(project_id, &revisions).await
+            }
+            None => self.db.project_revisions_save(project_id, revisions).await,
+        }
+    }
+
+    async fn project_revisions_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.db.project_revisions_remove(project_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_attributes() {
+        let schema: Schema = serde_json::from_value(serde_json::json!({
+            "App": {
+                "entityTypes": {
+                    "User": {
+                        "shape": {"type": "Record", "attributes": {}},
+                        "annotations": {"encrypted": "ssn, email,"}
+                    },
+                    "Group": {}
+                },
+                "actions": {}
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            encrypted_attributes(&schema, "App::User"),
+            vec!["ssn".to_string(), "email".to_string()]
+        );
+        assert!(encrypted_attributes(&schema, "App::Group").is_empty());
+        assert!(encrypted_attributes(&schema, "App::Unknown").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sealed_values() {
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        use crate::{
+            core::{EncryptionKeyConfig, crypto::LocalKeys},
+            db::memory::MemoryDb,
+        };
+
+        let keys = LocalKeys::new(&[EncryptionKeyConfig {
+            id: "k1".to_string(),
+            key: BASE64_STANDARD.encode([7u8; 32]),
+        }])
+        .unwrap();
+        let db = EncryptedDb::new(Box::new(MemoryDb::default()), Arc::new(keys));
+        let project_id = Uuid::now_v7();
+        let schema: Schema = serde_json::from_value(serde_json::json!({
+            "App": {
+                "entityTypes": {
+                    "User": {
+                        "shape": {"type": "Record", "attributes": {}},
+                        "annotations": {"encrypted": "ssn"}
+                    }
+                },
+                "actions": {}
+            }
+        }))
+        .unwrap();
+        db.project_schema_save(&project_id, &schema).await.unwrap();
+
+        let user = |id: &str, attr: &str, value: &str| {
+            Entity::new(
+                EntityUid::from(format!("App::User::{id}").as_str()),
+                HashMap::from([(attr.to_string(), EntityAttr::String(value.to_string()))]),
+                Default::default(),
+            )
+        };
+        db.project_entities_save(&project_id, &vec![user("alice", "ssn", "123-45-6789")])
+            .await
+            .unwrap();
+
+        // A client value in the sealed format is refused, whatever the attribute, so it
+        // can't break the loads of the project
+        for (attr, value) in [
+            ("ssn", "cedrus-enc:v1:k1:AAAA"),
+            ("note", "cedrus-enc:v1:x"),
+        ] {
+            let saved = db
+                .project_entities_save(&project_id, &vec![user("bob", attr, value)])
+                .await;
+            assert!(
+                matches!(saved, Err(DatabaseError::SealedValue(_))),
+                "{attr}"
+            );
+        }
+
+        let entities = db
+            .project_entities_load(&project_id, &Query::new())
+            .await
+            .unwrap()
+            .items;
+        assert_eq!(entities.len(), 1);
+        assert_eq!(
+            entities[0].attrs().get("ssn"),
+            Some(&EntityAttr::String("123-45-6789".to_string()))
+        );
+    }
+}
@@ -0,0 +1,559 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+};
+
+use cedrus_cedar::{
+    Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink, schema::TypeJson,
+};
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::{
+    PageHash, PageList, Query,
+    core::{
+        IdentitySource,
+        audit::AuditRecord,
+        history::{Revision, RevisionKind},
+        job::{Job, JobTask},
+        project::{ApiKey, Project, Role, RoleAssignment},
+    },
+};
+
+use super::{Database, DatabaseError, migration::Migration};
+
+/// Database held in memory, for the tests of the layers above the backends. Queries are
+/// ignored: every load returns all the items in a single page.
+#[derive(Default)]
+pub struct MemoryDb {
+    /// Errors returned, one each, by the next entity and policy writes
+    pub failures: Mutex<VecDeque<DatabaseError>>,
+    migrations: &'static [Migration],
+    schema_version: AtomicU32,
+    /// Versions of the migrations applied, in order, still readable once the database is boxed
+    pub applied: Arc<Mutex<Vec<u32>>>,
+    common_types: Mutex<HashMap<String, TypeJson>>,
+    projects: DashMap<Uuid, Project>,
+    identity_sources: DashMap<Uuid, IdentitySource>,
+    apikeys: DashMap<(Uuid, Uuid), ApiKey>,
+    roles: DashMap<(Uuid, String), Role>,
+    role_assignments: DashMap<(Uuid, String), RoleAssignment>,
+    jobs: DashMap<(Uuid, Uuid), Job>,
+    job_tasks: DashMap<(Uuid, Uuid), JobTask>,
+    audit: DashMap<Uuid, Vec<AuditRecord>>,
+    schemas: DashMap<Uuid, Schema>,
+    entities: DashMap<(Uuid, EntityUid), Entity>,
+    policies: DashMap<(Uuid, PolicyId), Policy>,
+    templates: DashMap<(Uuid, PolicyId), Template>,
+    template_links: DashMap<(Uuid, PolicyId), TemplateLink>,
+    revisions: DashMap<Uuid, Vec<Revision>>,
+}
+
+fn project_items<K, V: Clone>(map: &DashMap<(Uuid, K), V>, project_id: &Uuid) -> Vec<V>
+where
+    K: Eq + std::hash::Hash,
+{
+    map.iter()
+        .filter(|r| r.key().0 == *project_id)
+        .map(|r| r.value().clone())
+        .collect()
+}
+
+fn project_hash<V: Clone>(
+    map: &DashMap<(Uuid, PolicyId), V>,
+    project_id: &Uuid,
+) -> HashMap<PolicyId, V> {
+    map.iter()
+        .filter(|r| r.key().0 == *project_id)
+        .map(|r| (r.key().1.clone(), r.value().clone()))
+        .collect()
+}
+
+impl MemoryDb {
+    /// Database whose backend has the given migrations, none by default.
+    pub fn with_migrations(migrations: &'static [Migration]) -> Self {
+        Self {
+            migrations,
+            ..Default::default()
+        }
+    }
+
+    fn fail(&self) -> Result<(), DatabaseError> {
+        match self.failures.lock().unwrap().pop_front() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Database for MemoryDb {
+    fn migrations(&self) -> &'static [Migration] {
+        self.migrations
+    }
+
+    async fn schema_version_load(&self) -> Result<u32, DatabaseError> {
+        Ok(self.schema_version.load(Ordering::SeqCst))
+    }
+
+    async fn schema_version_save(&self, version: u32) -> Result<(), DatabaseError> {
+        self.schema_version.store(version, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn migration_apply(&self, migration: &Migration) -> Result<(), DatabaseError> {
+        self.applied.lock().unwrap().push(migration.version);
+        Ok(())
+    }
+
+    async fn common_types_load(&self) -> Result<HashMap<String, TypeJson>, DatabaseError> {
+        Ok(self.common_types.lock().unwrap().clone())
+    }
+
+    async fn common_types_save(
+        &self,
+        common_types: &HashMap<String, TypeJson>,
+    ) -> Result<(), DatabaseError> {
+        *self.common_types.lock().unwrap() = common_types.clone();
+        Ok(())
+    }
+
+    async fn projects_load(&self, _query: &Query) -> Result<PageList<Project>, DatabaseError> {
+        let projects = self.projects.iter().map(|r| r.value().clone()).collect();
+        Ok(PageList::new(projects, None))
+    }
+
+    async fn project_load(&self, id: &Uuid) -> Result<Option<Project>, DatabaseError> {
+        Ok(self.projects.get(id).map(|r| r.value().clone()))
+    }
+
+    async fn project_save(&self, project: &Project) -> Result<(), DatabaseError> {
+        self.projects.insert(project.id, project.clone());
+        Ok(())
+    }
+
+    async fn project_remove(&self, id: &Uuid) -> Result<(), DatabaseError> {
+        self.projects.remove(id);
+        self.identity_sources.remove(id);
+        self.apikeys.retain(|(pid, _), _| pid != id);
+        self.roles.retain(|(pid, _), _| pid != id);
+        self.role_assignments.retain(|(pid, _), _| pid != id);
+        self.jobs.retain(|(pid, _), _| pid != id);
+        self.schemas.remove(id);
+        self.entities.retain(|(pid, _), _| pid != id);
+        self.policies.retain(|(pid, _), _| pid != id);
+        self.templates.retain(|(pid, _), _| pid != id);
+        self.template_links.retain(|(pid, _), _| pid != id);
+        Ok(())
+    }
+
+    async fn project_identity_source_load(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<IdentitySource>, DatabaseError> {
+        Ok(self
+            .identity_sources
+            .get(project_id)
+            .map(|r| r.value().clone()))
+    }
+
+    async fn project_identity_source_save(
+        &self,
+        project_id: &Uuid,
+        identity_source: &IdentitySource,
+    ) -> Result<(), DatabaseError> {
+        self.identity_sources
+            .insert(*project_id, identity_source.clone());
+        Ok(())
+    }
+
+    async fn project_identity_source_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.identity_sources.remove(project_id);
+        Ok(())
+    }
+
+    async fn project_apikeys_load(
+        &self,
+        project_id: &Uuid,
+        _query: &Query,
+    ) -> Result<PageList<ApiKey>, DatabaseError> {
+        Ok(PageList::new(
+            project_items(&self.apikeys, project_id),
+            None,
+        ))
+    }
+
+    async fn project_apikeys_save(
+        &self,
+        project_id: &Uuid,
+        apikeys: &Vec<ApiKey>,
+    ) -> Result<(), DatabaseError> {
+        for apikey in apikeys {
+            self.apikeys
+                .insert((*project_id, apikey.id), apikey.clone());
+        }
+        Ok(())
+    }
+
+    async fn project_apikeys_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<Uuid>,
+    ) -> Result<(), DatabaseError> {
+        for id in ids {
+            self.apikeys.remove(&(*project_id, *id));
+        }
+        Ok(())
+    }
+
+    async fn project_roles_load(
+        &self,
+        project_id: &Uuid,
+        _query: &Query,
+    ) -> Result<PageList<Role>, DatabaseError> {
+        Ok(PageList::new(project_items(&self.roles, project_id), None))
+    }
+
+    async fn project_roles_save(
+        &self,
+        project_id: &Uuid,
+        roles: &Vec<Role>,
+    ) -> Result<(), DatabaseError> {
+        for role in roles {
+            self.roles
+                .insert((*project_id, role.id.clone()), role.clone());
+        }
+        Ok(())
+    }
+
+    async fn project_roles_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<String>,
+    ) -> Result<(), DatabaseError> {
+        for id in ids {
+            self.roles.remove(&(*project_id, id.clone()));
+        }
+        Ok(())
+    }
+
+    async fn project_role_assignment_load(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<Option<RoleAssignment>, DatabaseError> {
+        Ok(self
+            .role_assignments
+            .get(&(*project_id, id.to_string()))
+            .map(|r| r.value().clone()))
+    }
+
+    async fn project_role_assignment_save(
+        &self,
+        project_id: &Uuid,
+        assignment: &RoleAssignment,
+    ) -> Result<(), DatabaseError> {
+        self.role_assignments
+            .insert((*project_id, assignment.id()), assignment.clone());
+        Ok(())
+    }
+
+    async fn project_role_assignment_remove(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<(), DatabaseError> {
+        self.role_assignments.remove(&(*project_id, id.to_string()));
+        Ok(())
+    }
+
+    async fn project_jobs_load(
+        &self,
+        project_id: &Uuid,
+        _query: &Query,
+    ) -> Result<PageList<Job>, DatabaseError> {
+        Ok(PageList::new(project_items(&self.jobs, project_id), None))
+    }
+
+    async fn project_job_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<Job>, DatabaseError> {
+        Ok(self
+            .jobs
+            .get(&(*project_id, *id))
+            .map(|r| r.value().clone()))
+    }
+
+    async fn project_job_save(&self, project_id: &Uuid, job: &Job) -> Result<(), DatabaseError> {
+        self.jobs.insert((*project_id, job.id), job.clone());
+        Ok(())
+    }
+
+    async fn jobs_unfinished_load(&self) -> Result<Vec<Job>, DatabaseError> {
+        Ok(self
+            .jobs
+            .iter()
+            .filter(|r| !r.value().is_finished())
+            .map(|r| r.value().clone())
+            .collect())
+    }
+
+    async fn project_job_task_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<JobTask>, DatabaseError> {
+        Ok(self
+            .job_tasks
+            .get(&(*project_id, *id))
+            .map(|r| r.value().clone()))
+    }
+
+    async fn project_job_task_save(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+        task: &JobTask,
+    ) -> Result<(), DatabaseError> {
+        self.job_tasks.insert((*project_id, *id), task.clone());
+        Ok(())
+    }
+
+    async fn project_job_task_remove(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<(), DatabaseError> {
+        self.job_tasks.remove(&(*project_id, *id));
+        Ok(())
+    }
+
+    async fn project_audit_load(
+        &self,
+        project_id: &Uuid,
+        _query: &Query,
+    ) -> Result<PageList<AuditRecord>, DatabaseError> {
+        let records = self
+            .audit
+            .get(project_id)
+            .map(|r| r.value().clone())
+            .unwrap_or_default();
+        Ok(PageList::new(records, None))
+    }
+
+    async fn project_audit_save(
+        &self,
+        project_id: &Uuid,
+        record: &AuditRecord,
+    ) -> Result<(), DatabaseError> {
+        self.fail()?;
+        self.audit
+            .entry(*project_id)
+            .or_default()
+            .push(record.clone());
+        Ok(())
+    }
+
+    async fn project_schema_load(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<Schema>, DatabaseError> {
+        Ok(self.schemas.get(project_id).map(|r| r.value().clone()))
+    }
+
+    async fn project_schema_save(
+        &self,
+        project_id: &Uuid,
+        schema: &Schema,
+    ) -> Result<(), DatabaseError> {
+        self.schemas.insert(*project_id, schema.clone());
+        Ok(())
+    }
+
+    async fn project_schema_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.schemas.remove(project_id);
+        Ok(())
+    }
+
+    async fn project_entities_load(
+        &self,
+        project_id: &Uuid,
+        _query: &Query,
+    ) -> Result<PageList<Entity>, DatabaseError> {
+        Ok(PageList::new(
+            project_items(&self.entities, project_id),
+            None,
+        ))
+    }
+
+    async fn project_entities_save(
+        &self,
+        project_id: &Uuid,
+        entities: &Vec<Entity>,
+    ) -> Result<(), DatabaseError> {
+        self.fail()?;
+        for entity in entities {
+            self.entities
+                .insert((*project_id, entity.uid().clone()), entity.clone());
+        }
+        Ok(())
+    }
+
+    async fn project_entities_remove(
+        &self,
+        project_id: &Uuid,
+        entity_uids: &Vec<EntityUid>,
+    ) -> Result<(), DatabaseError> {
+        self.fail()?;
+        for entity_uid in entity_uids {
+            self.entities.remove(&(*project_id, entity_uid.clone()));
+        }
+        Ok(())
+    }
+
+    async fn project_policies_load(
+        &self,
+        project_id: &Uuid,
+        _query: &Query,
+    ) -> Result<PageHash<PolicyId, Policy>, DatabaseError> {
+        Ok(PageHash::new(
+            project_hash(&self.policies, project_id),
+            None,
+        ))
+    }
+
+    async fn project_policies_save(
+        &self,
+        project_id: &Uuid,
+        policies: &HashMap<PolicyId, Policy>,
+    ) -> Result<(), DatabaseError> {
+        self.fail()?;
+        for (policy_id, policy) in policies {
+            self.policies
+                .insert((*project_id, policy_id.clone()), policy.clone());
+        }
+        Ok(())
+    }
+
+    async fn project_policies_remove(
+        &self,
+        project_id: &Uuid,
+        policy_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        self.fail()?;
+        for policy_id in policy_ids {
+            self.policies.remove(&(*project_id, policy_id.clone()));
+        }
+        Ok(())
+    }
+
+    async fn project_templates_load(
+        &self,
+        project_id: &Uuid,
+        _query: &Query,
+    ) -> Result<PageHash<PolicyId, Template>, DatabaseError> {
+        Ok(PageHash::new(
+            project_hash(&self.templates, project_id),
+            None,
+        ))
+    }
+
+    async fn project_templates_save(
+        &self,
+        project_id: &Uuid,
+        templates: &HashMap<PolicyId, Template>,
+    ) -> Result<(), DatabaseError> {
+        for (template_id, template) in templates {
+            self.templates
+                .insert((*project_id, template_id.clone()), template.clone());
+        }
+        Ok(())
+    }
+
+    async fn project_templates_remove(
+        &self,
+        project_id: &Uuid,
+        template_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        for template_id in template_ids {
+            self.templates.remove(&(*project_id, template_id.clone()));
+        }
+        Ok(())
+    }
+
+    async fn project_template_links_load(
+        &self,
+        project_id: &Uuid,
+        _query: &Query,
+    ) -> Result<PageList<TemplateLink>, DatabaseError> {
+        Ok(PageList::new(
+            project_items(&self.template_links, project_id),
+            None,
+        ))
+    }
+
+    async fn project_template_links_save(
+        &self,
+        project_id: &Uuid,
+        template_links: &Vec<TemplateLink>,
+    ) -> Result<(), DatabaseError> {
+        for template_link in template_links {
+            self.template_links.insert(
+                (*project_id, template_link.new_id.clone()),
+                template_link.clone(),
+            );
+        }
+        Ok(())
+    }
+
+    async fn project_template_links_remove(
+        &self,
+        project_id: &Uuid,
+        link_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        for link_id in link_ids {
+            self.template_links.remove(&(*project_id, link_id.clone()));
+        }
+        Ok(())
+    }
+
+    async fn project_revisions_load(
+        &self,
+        project_id: &Uuid,
+        kind: RevisionKind,
+        until: &chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Revision>, DatabaseError> {
+        Ok(self
+            .revisions
+            .get(project_id)
+            .map(|r| {
+                r.iter()
+                    .filter(|revision| revision.kind == kind && revision.at <= *until)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn project_revisions_save(
+        &self,
+        project_id: &Uuid,
+        revisions: &[Revision],
+    ) -> Result<(), DatabaseError> {
+        self.revisions
+            .entry(*project_id)
+            .or_default()
+            .extend_from_slice(revisions);
+        Ok(())
+    }
+
+    async fn project_revisions_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.revisions.remove(project_id);
+        Ok(())
+    }
+}
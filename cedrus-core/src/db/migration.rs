@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Database, DatabaseError};
+
+/// A versioned step changing the stored layout of a database backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+}
+
+impl Migration {
+    pub const fn new(version: u32, description: &'static str) -> Self {
+        Self {
+            version,
+            description,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    pub applied: Vec<u32>,
+    pub dry_run: bool,
+}
+
+/// Latest schema version known to the given migrations, `0` when there are none.
+pub fn latest_version(migrations: &[Migration]) -> u32 {
+    migrations.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Returns the migrations to run to go from `current` up to `target`, in order.
+pub fn pending(
+    migrations: &[Migration],
+    current: u32,
+    target: u32,
+) -> Result<Vec<Migration>, DatabaseError> {
+    let latest = latest_version(migrations);
+    if current > latest {
+        return Err(DatabaseError::MigrationError(format!(
+            "database schema version {} is newer than the supported version {}",
+            current, latest
+        )));
+    }
+    if target < current {
+        return Err(DatabaseError::MigrationError(format!(
+            "cannot downgrade database schema version {} to {}",
+            current, target
+        )));
+    }
+    if target > latest {
+        return Err(DatabaseError::MigrationError(format!(
+            "unknown database schema version {}",
+            target
+        )));
+    }
+
+    let mut steps = migrations
+        .iter()
+        .filter(|m| m.version > current && m.version <= target)
+        .copied()
+        .collect::<Vec<_>>();
+    steps.sort_by_key(|m| m.version);
+    if steps.windows(2).any(|w| w[0].version == w[1].version) {
+        return Err(DatabaseError::MigrationError(
+            "duplicated migration versions".to_string(),
+        ));
+    }
+
+    Ok(steps)
+}
+
+/// Applies the pending migrations of the backend up to `target` (the latest version
+/// by default), recording the schema version after every step.
+pub async fn migrate(
+    db: &(dyn Database + Send + Sync),
+    target: Option<u32>,
+    dry_run: bool,
+) -> Result<MigrationReport, DatabaseError> {
+    let migrations = db.migrations();
+    let from = db.schema_version_load().await?;
+    let to = target.unwrap_or_else(|| latest_version(migrations));
+
+    let mut applied = Vec::new();
+    for migration in pending(migrations, from, to)? {
+        if !dry_run {
+            tracing::info!(target: "cedrus::db", "migration {}: {}", migration.version, migration.description);
+            db.migration_apply(&migration).await?;
+            db.schema_version_save(migration.version).await?;
+        }
+        applied.push(migration.version);
+    }
+
+    Ok(MigrationReport {
+        from,
+        to,
+        applied,
+        dry_run,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration::new(2, "second"),
+        Migration::new(1, "first"),
+        Migration::new(3, "third"),
+    ];
+
+    #[test]
+    fn test_pending() {
+        assert_eq!(latest_version(MIGRATIONS), 3);
+        assert_eq!(latest_version(&[]), 0);
+
+        let steps = pending(MIGRATIONS, 0, 3).unwrap();
+        assert_eq!(
+            steps.iter().map(|m| m.version).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        let steps = pending(MIGRATIONS, 1, 2).unwrap();
+        assert_eq!(steps.iter().map(|m| m.version).collect::<Vec<_>>(), vec![2]);
+
+        assert!(pending(MIGRATIONS, 3, 3).unwrap().is_empty());
+        assert!(pending(MIGRATIONS, 2, 1).is_err());
+        assert!(pending(MIGRATIONS, 4, 4).is_err());
+        assert!(pending(MIGRATIONS, 0, 5).is_err());
+    }
+}
@@ -1,23 +1,37 @@
-use std::{collections::HashMap, error::Error};
+use std::{cmp::Ordering, collections::HashMap, error::Error};
 
-use cedrus_cedar::{Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink};
+use cedrus_cedar::{
+    Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink, entity::EntityAttr,
+    schema::TypeJson,
+};
 use couch_rs::error::CouchError;
 use uuid::Uuid;
 
 use crate::{
-    PageHash, PageList, Query,
+    PageHash, PageList, Query, Sort, SortOrder,
     core::{
-        DbConfig, IdentitySource,
-        project::{ApiKey, Project},
+        CouchDbConfig, DbConfig, DynamoDBConfig, IdentitySource,
+        audit::AuditRecord,
+        history::{Revision, RevisionKind},
+        job::{Job, JobTask},
+        project::{ApiKey, Project, Role, RoleAssignment},
     },
 };
 
 pub mod couchdb;
 pub mod dynamodb;
+pub mod encrypted;
+#[cfg(any(test, feature = "testing"))]
+pub mod memory;
+pub mod migration;
+pub mod regional;
+
+use migration::Migration;
 
 #[derive(Debug)]
 pub enum DatabaseError {
     NotFound,
+    Conflict,
     Unknown,
     ConnectionError(String),
     MissingAttribute(String),
@@ -27,12 +41,19 @@ pub enum DatabaseError {
     SerdeDynamoError(serde_dynamo::Error),
     AwsSdkError(String),
     SerializationError(String),
+    MigrationError(String),
+    EncryptionError(String),
+    /// Attribute written with a value in the format of the encrypted ones
+    SealedValue(String),
+    /// Query the backend can't answer, such as a sort it can't page through
+    InvalidQuery(String),
 }
 
 impl std::fmt::Display for DatabaseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DatabaseError::NotFound => write!(f, "not found"),
+            DatabaseError::Conflict => write!(f, "conflict"),
             DatabaseError::Unknown => write!(f, "unknown"),
             DatabaseError::ConnectionError(e) => write!(f, "connection error: {}", e),
             DatabaseError::MissingAttribute(a) => write!(f, "missing attribute: {}", a),
@@ -42,12 +63,32 @@ impl std::fmt::Display for DatabaseError {
             DatabaseError::SerdeDynamoError(e) => write!(f, "dynamodb error: {}", e),
             DatabaseError::AwsSdkError(e) => write!(f, "aws sdk error: {}", e),
             DatabaseError::SerializationError(e) => write!(f, "serialization error: {}", e),
+            DatabaseError::MigrationError(e) => write!(f, "migration error: {}", e),
+            DatabaseError::EncryptionError(e) => write!(f, "encryption error: {}", e),
+            DatabaseError::SealedValue(a) => write!(f, "sealed value of attribute: {}", a),
+            DatabaseError::InvalidQuery(e) => write!(f, "invalid query: {}", e),
         }
     }
 }
 
 impl Error for DatabaseError {}
 
+impl DatabaseError {
+    /// Whether retrying may succeed, the backend being unreachable or overloaded rather than
+    /// rejecting the operation itself.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DatabaseError::Unknown
+            | DatabaseError::ConnectionError(_)
+            | DatabaseError::AwsSdkError(_) => true,
+            DatabaseError::CouchError(e) => e.status().is_none_or(|status| {
+                status.is_server_error() || status == couch_rs::http::StatusCode::TOO_MANY_REQUESTS
+            }),
+            _ => false,
+        }
+    }
+}
+
 impl From<serde_dynamo::Error> for DatabaseError {
     fn from(e: serde_dynamo::Error) -> Self {
         DatabaseError::SerdeDynamoError(e)
@@ -62,12 +103,103 @@ impl From<serde_json::Error> for DatabaseError {
 
 impl From<CouchError> for DatabaseError {
     fn from(e: CouchError) -> Self {
-        DatabaseError::CouchError(e)
+        match e.status() {
+            Some(couch_rs::http::StatusCode::CONFLICT) => DatabaseError::Conflict,
+            _ => DatabaseError::CouchError(e),
+        }
+    }
+}
+
+/// Attribute holding the indexed policy annotations, stored as `key=value` terms.
+pub const ANNOTATION_INDEX_KEY: &str = "annotationIndex";
+
+// `\` and `=` are escaped in the key and the value, so the first unescaped `=` of a term
+// always separates them
+fn annotation_index_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('=', "\\=")
+}
+
+pub fn annotation_index_term(key: &str, value: &str) -> String {
+    format!(
+        "{}={}",
+        annotation_index_escape(key),
+        annotation_index_escape(value)
+    )
+}
+
+pub fn annotation_index_terms(annotations: &HashMap<String, Option<String>>) -> Vec<String> {
+    let mut terms = annotations
+        .iter()
+        .map(|(key, value)| annotation_index_term(key, value.as_deref().unwrap_or_default()))
+        .collect::<Vec<String>>();
+    terms.sort();
+    terms
+}
+
+pub fn query_annotation_terms(query: &Query) -> Vec<String> {
+    let mut terms = query
+        .annotations
+        .iter()
+        .map(|(key, value)| annotation_index_term(key, value))
+        .collect::<Vec<String>>();
+    terms.sort();
+    terms
+}
+
+/// Prefix of the sort fields and selector keys naming an entity tag, e.g. `tags.env`.
+pub const ENTITY_TAG_PREFIX: &str = "tags.";
+
+// Orders tag values of the same type by value, and of different types by type name
+fn entity_tag_cmp(a: Option<&EntityAttr>, b: Option<&EntityAttr>) -> Ordering {
+    match (a, b) {
+        (Some(EntityAttr::Number(a)), Some(EntityAttr::Number(b))) => a.cmp(b),
+        (Some(EntityAttr::Decimal(a)), Some(EntityAttr::Decimal(b))) => a.total_cmp(b),
+        (Some(EntityAttr::String(a)), Some(EntityAttr::String(b))) => a.cmp(b),
+        (Some(EntityAttr::Boolean(a)), Some(EntityAttr::Boolean(b))) => a.cmp(b),
+        (Some(a), Some(b)) => a.type_name().cmp(&b.type_name()),
+        // Untagged entities go last
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
     }
 }
 
+/// Sorts a page of entities in place by `uid` or by `tags.<key>`, other fields are ignored.
+pub fn sort_entities(entities: &mut [Entity], sort: &[Sort]) {
+    entities.sort_by(|a, b| {
+        sort.iter()
+            .map(|s| {
+                let ordering = match s.field.strip_prefix(ENTITY_TAG_PREFIX) {
+                    Some(key) => entity_tag_cmp(a.tags().get(key), b.tags().get(key)),
+                    None if s.field == "uid" => a.uid().cmp(b.uid()),
+                    None => Ordering::Equal,
+                };
+                match s.order {
+                    SortOrder::Asc => ordering,
+                    SortOrder::Desc => ordering.reverse(),
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
 #[async_trait::async_trait]
 pub trait Database: Send + Sync {
+    /// Ordered migration steps of the backend layout.
+    fn migrations(&self) -> &'static [Migration];
+    /// Stored schema version, `0` when the database was never migrated.
+    async fn schema_version_load(&self) -> Result<u32, DatabaseError>;
+    async fn schema_version_save(&self, version: u32) -> Result<(), DatabaseError>;
+    async fn migration_apply(&self, migration: &Migration) -> Result<(), DatabaseError>;
+
+    /// Library of common types shared by the schemas of every project.
+    async fn common_types_load(&self) -> Result<HashMap<String, TypeJson>, DatabaseError>;
+    async fn common_types_save(
+        &self,
+        common_types: &HashMap<String, TypeJson>,
+    ) -> Result<(), DatabaseError>;
+
     async fn projects_load(&self, query: &Query) -> Result<PageList<Project>, DatabaseError>;
     async fn project_load(&self, id: &Uuid) -> Result<Option<Project>, DatabaseError>;
     async fn project_save(&self, project: &Project) -> Result<(), DatabaseError>;
@@ -100,6 +232,82 @@ pub trait Database: Send + Sync {
         ids: &Vec<Uuid>,
     ) -> Result<(), DatabaseError>;
 
+    async fn project_roles_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Role>, DatabaseError>;
+    async fn project_roles_save(
+        &self,
+        project_id: &Uuid,
+        roles: &Vec<Role>,
+    ) -> Result<(), DatabaseError>;
+    async fn project_roles_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<String>,
+    ) -> Result<(), DatabaseError>;
+
+    /// Role instantiated for a principal over a project, by its `RoleAssignment::id`.
+    async fn project_role_assignment_load(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<Option<RoleAssignment>, DatabaseError>;
+    async fn project_role_assignment_save(
+        &self,
+        project_id: &Uuid,
+        assignment: &RoleAssignment,
+    ) -> Result<(), DatabaseError>;
+    async fn project_role_assignment_remove(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<(), DatabaseError>;
+
+    async fn project_jobs_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Job>, DatabaseError>;
+    async fn project_job_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<Job>, DatabaseError>;
+    async fn project_job_save(&self, project_id: &Uuid, job: &Job) -> Result<(), DatabaseError>;
+    /// Jobs of every project not finished yet.
+    async fn jobs_unfinished_load(&self) -> Result<Vec<Job>, DatabaseError>;
+    /// Task of a job, stored apart from it until the job finished.
+    async fn project_job_task_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<JobTask>, DatabaseError>;
+    async fn project_job_task_save(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+        task: &JobTask,
+    ) -> Result<(), DatabaseError>;
+    async fn project_job_task_remove(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<(), DatabaseError>;
+
+    /// Append-only trail of the management changes of a project, kept once it is removed.
+    async fn project_audit_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<AuditRecord>, DatabaseError>;
+    async fn project_audit_save(
+        &self,
+        project_id: &Uuid,
+        record: &AuditRecord,
+    ) -> Result<(), DatabaseError>;
+
     async fn project_schema_load(&self, project_id: &Uuid)
     -> Result<Option<Schema>, DatabaseError>;
     async fn project_schema_save(
@@ -172,6 +380,21 @@ pub trait Database: Send + Sync {
         project_id: &Uuid,
         link_ids: &Vec<PolicyId>,
     ) -> Result<(), DatabaseError>;
+
+    /// Revisions of a kind recorded for a project up to `until`, in no particular order.
+    async fn project_revisions_load(
+        &self,
+        project_id: &Uuid,
+        kind: RevisionKind,
+        until: &chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Revision>, DatabaseError>;
+    async fn project_revisions_save(
+        &self,
+        project_id: &Uuid,
+        revisions: &[Revision],
+    ) -> Result<(), DatabaseError>;
+    /// Removes the whole history of a project.
+    async fn project_revisions_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError>;
 }
 
 pub async fn database_factory(
@@ -206,3 +429,35 @@ pub async fn database_factory(
 
     Ok(db)
 }
+
+/// Database reading from the read endpoint of the configuration, `None` without one. Replicas
+/// lag behind the primary, so they only serve the cache warm-up on startup, never the reads
+/// preceding a write.
+pub async fn read_replica_factory(
+    conf: &DbConfig,
+) -> Result<Option<Box<dyn Database + Send + Sync>>, DatabaseError> {
+    let db: Box<dyn Database + Send + Sync> = match conf {
+        DbConfig::DynamoDbConfig(conf) => {
+            let Some(endpoint_url) = &conf.read_endpoint_url else {
+                return Ok(None);
+            };
+            let conf = DynamoDBConfig {
+                endpoint_url: Some(endpoint_url.clone()),
+                ..conf.clone()
+            };
+            Box::new(dynamodb::DynamoDb::new(&conf).await?)
+        }
+        DbConfig::CouchDbConfig(conf) => {
+            let Some(read_uri) = &conf.read_uri else {
+                return Ok(None);
+            };
+            let conf = CouchDbConfig {
+                uri: read_uri.clone(),
+                ..conf.clone()
+            };
+            Box::new(couchdb::CouchDb::new(&conf)?)
+        }
+    };
+
+    Ok(Some(db))
+}
@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+
+use cedrus_cedar::{
+    Entity, EntityUid, Policy, PolicyId, Schema, Template, TemplateLink, schema::TypeJson,
+};
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::{
+    PageHash, PageList, Query,
+    core::{
+        IdentitySource,
+        audit::AuditRecord,
+        history::{Revision, RevisionKind},
+        job::{Job, JobTask},
+        project::{ApiKey, Project, Role, RoleAssignment},
+    },
+};
+
+use super::{Database, DatabaseError, migration::Migration};
+
+/// Database keeping the data of each project in the backend of its `region`, while the
+/// projects themselves, the common types and the audit trail stay in the default backend
+/// shared by every region. Projects without a region, the admin project included, are
+/// entirely in the default backend.
+pub struct RegionalDb {
+    db: Box<dyn Database + Send + Sync>,
+    regions: HashMap<String, Box<dyn Database + Send + Sync>>,
+    /// Region of each project loaded so far, set on creation and never changed
+    project_regions: DashMap<Uuid, Option<String>>,
+}
+
+impl RegionalDb {
+    pub fn new(
+        db: Box<dyn Database + Send + Sync>,
+        regions: HashMap<String, Box<dyn Database + Send + Sync>>,
+    ) -> Self {
+        Self {
+            db,
+            regions,
+            project_regions: DashMap::new(),
+        }
+    }
+
+    // Every backend, the default one first
+    fn backends(&self) -> impl Iterator<Item = &(dyn Database + Send + Sync)> {
+        std::iter::once(self.db.as_ref()).chain(self.regions.values().map(|db| db.as_ref()))
+    }
+
+    // Backend of the data of a project, the default one until the project exists
+    async fn project_db(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<&(dyn Database + Send + Sync), DatabaseError> {
+        if project_id.is_nil() {
+            return Ok(self.db.as_ref());
+        }
+        let region = match self.project_regions.get(project_id) {
+            Some(region) => region.clone(),
+            None => match self.db.project_load(project_id).await? {
+                Some(project) => {
+                    self.project_regions
+                        .insert(*project_id, project.region.clone());
+                    project.region
+                }
+                None => None,
+            },
+        };
+
+        match region {
+            Some(region) => self
+                .regions
+                .get(&region)
+                .map(|db| db.as_ref())
+                .ok_or_else(|| DatabaseError::InvalidAttribute(format!("region {}", region))),
+            None => Ok(self.db.as_ref()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Database for RegionalDb {
+    fn migrations(&self) -> &'static [Migration] {
+        self.db.migrations()
+    }
+
+    // The least migrated backend, so the migrations any of them lacks are pending
+    async fn schema_version_load(&self) -> Result<u32, DatabaseError> {
+        let mut version = self.db.schema_version_load().await?;
+        for db in self.regions.values() {
+            version = version.min(db.schema_version_load().await?);
+        }
+        Ok(version)
+    }
+
+    async fn schema_version_save(&self, version: u32) -> Result<(), DatabaseError> {
+        for db in self.backends() {
+            if db.schema_version_load().await? < version {
+                db.schema_version_save(version).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Applied by every backend having the migration and not applied it yet
+    async fn migration_apply(&self, migration: &Migration) -> Result<(), DatabaseError> {
+        for db in self.backends() {
+            if db.migrations().contains(migration)
+                && db.schema_version_load().await? < migration.version
+            {
+                db.migration_apply(migration).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn common_types_load(&self) -> Result<HashMap<String, TypeJson>, DatabaseError> {
+        self.db.common_types_load().await
+    }
+
+    async fn common_types_save(
+        &self,
+        common_types: &HashMap<String, TypeJson>,
+    ) -> Result<(), DatabaseError> {
+        self.db.common_types_save(common_types).await
+    }
+
+    async fn projects_load(&self, query: &Query) -> Result<PageList<Project>, DatabaseError> {
+        self.db.projects_load(query).await
+    }
+
+    async fn project_load(&self, id: &Uuid) -> Result<Option<Project>, DatabaseError> {
+        self.db.project_load(id).await
+    }
+
+    async fn project_save(&self, project: &Project) -> Result<(), DatabaseError> {
+        self.db.project_save(project).await?;
+        self.project_regions
+            .insert(project.id, project.region.clone());
+        Ok(())
+    }
+
+    async fn project_remove(&self, id: &Uuid) -> Result<(), DatabaseError> {
+        let db = self.project_db(id).await?;
+        if !std::ptr::addr_eq(db, self.db.as_ref()) {
+            db.project_remove(id).await?;
+        }
+        // The region stays known, for the removals that follow
+        self.db.project_remove(id).await
+    }
+
+    async fn project_identity_source_load(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<IdentitySource>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_identity_source_load(project_id)
+            .await
+    }
+
+    async fn project_identity_source_save(
+        &self,
+        project_id: &Uuid,
+        identity_source: &IdentitySource,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_identity_source_save(project_id, identity_source)
+            .await
+    }
+
+    async fn project_identity_source_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_identity_source_remove(project_id)
+            .await
+    }
+
+    async fn project_apikeys_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<ApiKey>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_apikeys_load(project_id, query)
+            .await
+    }
+
+    async fn project_apikeys_save(
+        &self,
+        project_id: &Uuid,
+        apikeys: &Vec<ApiKey>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_apikeys_save(project_id, apikeys)
+            .await
+    }
+
+    async fn project_apikeys_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<Uuid>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_apikeys_remove(project_id, ids)
+            .await
+    }
+
+    async fn project_roles_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Role>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_roles_load(project_id, query)
+            .await
+    }
+
+    async fn project_roles_save(
+        &self,
+        project_id: &Uuid,
+        roles: &Vec<Role>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_roles_save(project_id, roles)
+            .await
+    }
+
+    async fn project_roles_remove(
+        &self,
+        project_id: &Uuid,
+        ids: &Vec<String>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_roles_remove(project_id, ids)
+            .await
+    }
+
+    async fn project_role_assignment_load(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<Option<RoleAssignment>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_role_assignment_load(project_id, id)
+            .await
+    }
+
+    async fn project_role_assignment_save(
+        &self,
+        project_id: &Uuid,
+        assignment: &RoleAssignment,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_role_assignment_save(project_id, assignment)
+            .await
+    }
+
+    async fn project_role_assignment_remove(
+        &self,
+        project_id: &Uuid,
+        id: &str,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_role_assignment_remove(project_id, id)
+            .await
+    }
+
+    async fn project_jobs_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Job>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_jobs_load(project_id, query)
+            .await
+    }
+
+    async fn project_job_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<Job>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_job_load(project_id, id)
+            .await
+    }
+
+    async fn project_job_save(&self, project_id: &Uuid, job: &Job) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_job_save(project_id, job)
+            .await
+    }
+
+    async fn jobs_unfinished_load(&self) -> Result<Vec<Job>, DatabaseError> {
+        let mut jobs = Vec::new();
+        for db in self.backends() {
+            jobs.extend(db.jobs_unfinished_load().await?);
+        }
+        Ok(jobs)
+    }
+
+    async fn project_job_task_load(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<JobTask>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_job_task_load(project_id, id)
+            .await
+    }
+
+    async fn project_job_task_save(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+        task: &JobTask,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_job_task_save(project_id, id, task)
+            .await
+    }
+
+    async fn project_job_task_remove(
+        &self,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_job_task_remove(project_id, id)
+            .await
+    }
+
+    async fn project_audit_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<AuditRecord>, DatabaseError> {
+        self.db.project_audit_load(project_id, query).await
+    }
+
+    async fn project_audit_save(
+        &self,
+        project_id: &Uuid,
+        record: &AuditRecord,
+    ) -> Result<(), DatabaseError> {
+        self.db.project_audit_save(project_id, record).await
+    }
+
+    async fn project_schema_load(
+        &self,
+        project_id: &Uuid,
+    ) -> Result<Option<Schema>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_schema_load(project_id)
+            .await
+    }
+
+    async fn project_schema_save(
+        &self,
+        project_id: &Uuid,
+        schema: &Schema,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_schema_save(project_id, schema)
+            .await
+    }
+
+    async fn project_schema_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_schema_remove(project_id)
+            .await
+    }
+
+    async fn project_entities_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<Entity>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_entities_load(project_id, query)
+            .await
+    }
+
+    async fn project_entities_save(
+        &self,
+        project_id: &Uuid,
+        entities: &Vec<Entity>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_entities_save(project_id, entities)
+            .await
+    }
+
+    async fn project_entities_remove(
+        &self,
+        project_id: &Uuid,
+        entity_uids: &Vec<EntityUid>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_entities_remove(project_id, entity_uids)
+            .await
+    }
+
+    async fn project_policies_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageHash<PolicyId, Policy>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_policies_load(project_id, query)
+            .await
+    }
+
+    async fn project_policies_save(
+        &self,
+        project_id: &Uuid,
+        policies: &HashMap<PolicyId, Policy>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_policies_save(project_id, policies)
+            .await
+    }
+
+    async fn project_policies_remove(
+        &self,
+        project_id: &Uuid,
+        policy_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_policies_remove(project_id, policy_ids)
+            .await
+    }
+
+    async fn project_templates_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageHash<PolicyId, Template>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_templates_load(project_id, query)
+            .await
+    }
+
+    async fn project_templates_save(
+        &self,
+        project_id: &Uuid,
+        templates: &HashMap<PolicyId, Template>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_templates_save(project_id, templates)
+            .await
+    }
+
+    async fn project_templates_remove(
+        &self,
+        project_id: &Uuid,
+        template_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_templates_remove(project_id, template_ids)
+            .await
+    }
+
+    async fn project_template_links_load(
+        &self,
+        project_id: &Uuid,
+        query: &Query,
+    ) -> Result<PageList<TemplateLink>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_template_links_load(project_id, query)
+            .await
+    }
+
+    async fn project_template_links_save(
+        &self,
+        project_id: &Uuid,
+        template_links: &Vec<TemplateLink>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_template_links_save(project_id, template_links)
+            .await
+    }
+
+    async fn project_template_links_remove(
+        &self,
+        project_id: &Uuid,
+        link_ids: &Vec<PolicyId>,
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_template_links_remove(project_id, link_ids)
+            .await
+    }
+
+    async fn project_revisions_load(
+        &self,
+        project_id: &Uuid,
+        kind: RevisionKind,
+        until: &chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Revision>, DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_revisions_load(project_id, kind, until)
+            .await
+    }
+
+    async fn project_revisions_save(
+        &self,
+        project_id: &Uuid,
+        revisions: &[Revision],
+    ) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_revisions_save(project_id, revisions)
+            .await
+    }
+
+    async fn project_revisions_remove(&self, project_id: &Uuid) -> Result<(), DatabaseError> {
+        self.project_db(project_id)
+            .await?
+            .project_revisions_remove(project_id)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{memory::MemoryDb, migration};
+
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[Migration::new(1, "first"), Migration::new(2, "second")];
+
+    #[tokio::test]
+    async fn test_regional_migrate() {
+        let default = MemoryDb::with_migrations(MIGRATIONS);
+        let eu = MemoryDb::with_migrations(MIGRATIONS);
+        eu.schema_version_save(1).await.unwrap();
+        let us = MemoryDb::with_migrations(MIGRATIONS);
+        let applied = [
+            default.applied.clone(),
+            eu.applied.clone(),
+            us.applied.clone(),
+        ];
+
+        let db = RegionalDb::new(
+            Box::new(default),
+            HashMap::from([
+                (
+                    "eu".to_string(),
+                    Box::new(eu) as Box<dyn Database + Send + Sync>,
+                ),
+                (
+                    "us".to_string(),
+                    Box::new(us) as Box<dyn Database + Send + Sync>,
+                ),
+            ]),
+        );
+
+        // The least migrated backend sets the pending migrations, each backend applying
+        // only the ones it lacks
+        let report = migration::migrate(&db, None, false).await.unwrap();
+        assert_eq!((report.from, report.to), (0, 2));
+        assert_eq!(*applied[0].lock().unwrap(), vec![1, 2]);
+        assert_eq!(*applied[1].lock().unwrap(), vec![2]);
+        assert_eq!(*applied[2].lock().unwrap(), vec![1, 2]);
+        for backend in db.backends() {
+            assert_eq!(backend.schema_version_load().await.unwrap(), 2);
+        }
+
+        let report = migration::migrate(&db, None, false).await.unwrap();
+        assert!(report.applied.is_empty());
+    }
+}
@@ -9,10 +9,10 @@ use std::{
     hash::Hash,
 };
 
-use cedrus_cedar::{Entity, EntityUid, PolicyId};
+use cedrus_cedar::{ContextError, Entity, EntityUid, PolicyId, RequestError};
 use jwt_authorizer::{JwtAuthorizer, Validation};
 use serde::{Deserialize, Serialize};
-use serde_json::{Value, json};
+use serde_json::Value;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -20,7 +20,9 @@ use crate::{
     cache::CacheError,
     core::{
         IdentitySource,
+        cluster::NodeHeartbeat,
         is::{Configuration, OpenIdConnectTokenSelection},
+        references::EntityReferences,
     },
     db::DatabaseError,
     pubsub::PubSubError,
@@ -55,54 +57,33 @@ impl Authorizer {
         Ok(EntityUid::new(entity_type, id))
     }
 
-    pub fn get_entity(&self, token: Value) -> Result<Entity, CedrusError> {
-        let prefix = self.identity_source.prefix();
-        let entity_type = self.identity_source.principal_entity_type.clone();
-        let id_claim = self.identity_source.id_claim();
-        let sub = token.get(id_claim).ok_or(CedrusError::Unauthorized)?;
-        let id = format!(
-            "{}|{}",
-            prefix,
-            sub.as_str().ok_or(CedrusError::Unauthorized)?
-        );
-
-        let parents: Result<HashSet<EntityUid>, CedrusError> = {
-            if let Some(group_claim) = self.identity_source.group_claim() {
-                if let Some(group) = token.get(group_claim) {
-                    let group = group.as_array().ok_or(CedrusError::Unauthorized)?;
-                    let group_entity_type = self
-                        .identity_source
-                        .group_entity_type()
-                        .ok_or(CedrusError::Unauthorized)?;
-                    group
-                        .iter()
-                        .map(|v| {
-                            let group_id = v.as_str().ok_or(CedrusError::Unauthorized)?;
-                            Ok(EntityUid::new(
-                                group_entity_type.to_string(),
-                                group_id.to_string(),
-                            ))
-                        })
-                        .collect::<Result<HashSet<EntityUid>, CedrusError>>()
-                } else {
-                    Ok(HashSet::new())
-                }
-            } else {
-                Ok(HashSet::new())
+    /// Materializes the principal of a validated token, its parents read from the group
+    /// claim and its attributes and other parents by the entity mapper, if any.
+    pub fn get_entity(&self, token: &Value) -> Result<Entity, CedrusError> {
+        let uid = self.get_entity_uid(token)?;
+
+        let mut parents = HashSet::new();
+        if let Some(group_claim) = self.identity_source.group_claim()
+            && let Some(group) = token.get(group_claim)
+        {
+            let group = group.as_array().ok_or(CedrusError::Unauthorized)?;
+            let group_entity_type = self
+                .identity_source
+                .group_entity_type()
+                .ok_or(CedrusError::Unauthorized)?;
+            for v in group {
+                let group_id = v.as_str().ok_or(CedrusError::Unauthorized)?;
+                parents.insert(EntityUid::new(
+                    group_entity_type.to_string(),
+                    group_id.to_string(),
+                ));
             }
-        };
-
-        let value = json!({
-            "uid": {
-                "type": entity_type,
-                "id": id
-            },
-            "attrs": token,
-            "parents": parents?
-        });
+        }
 
-        let entity: Entity =
-            serde_json::from_value(value).map_err(|_| CedrusError::Unauthorized)?;
+        let mut entity = Entity::new_no_attrs(uid.clone(), parents);
+        if let Some(mapper) = &self.identity_source.entity_mapper {
+            entity.merge(mapper.map(uid, token));
+        }
 
         Ok(entity)
     }
@@ -180,8 +161,28 @@ pub enum SortOrder {
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Sort {
-    field: String,
-    order: SortOrder,
+    pub field: String,
+    pub order: SortOrder,
+}
+
+impl Sort {
+    pub fn new(field: String, order: SortOrder) -> Self {
+        Self { field, order }
+    }
+
+    /// Parses a comma-separated list of fields, each prefixed by `-` to sort it in
+    /// descending order (`name,-createdAt`).
+    pub fn parse_list(value: &str) -> Vec<Sort> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(|field| match field.strip_prefix('-') {
+                Some(field) => Sort::new(field.to_string(), SortOrder::Desc),
+                None => Sort::new(field.to_string(), SortOrder::Asc),
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -252,6 +253,9 @@ pub struct Query {
     pub skip: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub index: Option<String>,
+    /// Policy annotation filters (`key` -> `value`), matched against the annotation index.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
 }
 
 impl Query {
@@ -260,6 +264,32 @@ impl Query {
             ..Default::default()
         }
     }
+
+    /// Narrows the selector to the items also matching `selector`.
+    pub fn and_selector(&mut self, selector: Selector) {
+        self.selector = Some(match (self.selector.take(), selector) {
+            (None, selector) => selector,
+            (Some(Selector::Record(mut fields)), Selector::Record(more))
+                if more.keys().all(|key| !fields.contains_key(key)) =>
+            {
+                fields.extend(more);
+                Selector::Record(fields)
+            }
+            (Some(current), selector) => Selector::And(vec![current, selector]),
+        });
+    }
+
+    /// Keeps only the requested `fields` (and the `id` or `uid` identifying it) of a
+    /// serialized item, the whole item when no fields were requested.
+    pub fn select_fields(&self, value: Value) -> Value {
+        match value {
+            Value::Object(mut map) if !self.fields.is_empty() => {
+                map.retain(|key, _| key == "id" || key == "uid" || self.fields.contains(key));
+                Value::Object(map)
+            }
+            value => value,
+        }
+    }
 }
 
 // The kinds of errors we can hit in our application.
@@ -269,20 +299,35 @@ pub enum CedrusError {
     Unauthorized, // 401
     Forbidden,    // 403
     NotFound,     // 404
+    Conflict,     // 409
+
+    EvaluationTimeout, // 503
+    Overloaded,        // 503
 
+    BundleError(String),
+    SigningError(String),
+    EncryptionError(String),
+    BootstrapError(String),
+    GitOpsError(String),
     AuthorizerError(String),
     DatabaseError(DatabaseError),
     CacheError(CacheError),
     PubSubError(PubSubError),
 
     SerdeJsonError(serde_json::Error),
+    EntityUidError(cedar_policy::ParseErrors), // 400
     SchemaError(cedar_policy::SchemaError),
     EntitiesError(cedar_policy::entities_errors::EntitiesError),
     PolicyFromJsonError(cedar_policy::PolicyFromJsonError),
     PolicyToJsonError(cedar_policy::PolicyToJsonError),
     PolicySetError(cedar_policy::PolicySetError),
     ContextJsonError(cedar_policy::ContextJsonError),
+    ContextValidationError(Vec<ContextError>),
     RequestValidationError(cedar_policy::RequestValidationError),
+    RequestSchemaError(Vec<RequestError>),
+    EntityLinked(Vec<EntityReferences>),
+    IntegrityError(Vec<String>),
+    ValidationError(Vec<String>), // 400
 }
 
 impl Error for CedrusError {}
@@ -294,25 +339,61 @@ impl fmt::Display for CedrusError {
             CedrusError::Unauthorized => write!(f, "Unauthorized"),
             CedrusError::Forbidden => write!(f, "Forbidden"),
             CedrusError::NotFound => write!(f, "Not found"),
+            CedrusError::Conflict => write!(f, "Conflict"),
+            CedrusError::EvaluationTimeout => write!(f, "Evaluation timed out"),
+            CedrusError::Overloaded => write!(f, "Overloaded"),
+            CedrusError::BundleError(ref err) => write!(f, "Bundle error: {}", err),
+            CedrusError::SigningError(ref err) => write!(f, "Signing error: {}", err),
+            CedrusError::EncryptionError(ref err) => write!(f, "Encryption error: {}", err),
+            CedrusError::BootstrapError(ref err) => write!(f, "Bootstrap error: {}", err),
+            CedrusError::GitOpsError(ref err) => write!(f, "GitOps error: {}", err),
             CedrusError::AuthorizerError(ref err) => err.fmt(f),
             CedrusError::DatabaseError(ref err) => err.fmt(f),
             CedrusError::CacheError(ref err) => err.fmt(f),
             CedrusError::PubSubError(ref err) => err.fmt(f),
             CedrusError::SerdeJsonError(ref err) => err.fmt(f),
+            CedrusError::EntityUidError(ref err) => write!(f, "Invalid entity uid: {}", err),
             CedrusError::SchemaError(ref err) => err.fmt(f),
             CedrusError::EntitiesError(ref err) => err.fmt(f),
             CedrusError::PolicyFromJsonError(ref err) => err.fmt(f),
             CedrusError::PolicyToJsonError(ref err) => err.fmt(f),
             CedrusError::PolicySetError(ref err) => err.fmt(f),
             CedrusError::ContextJsonError(ref err) => err.fmt(f),
+            CedrusError::ContextValidationError(ref errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "Context does not match schema: {}", errors.join("; "))
+            }
             CedrusError::RequestValidationError(ref err) => err.fmt(f),
+            CedrusError::RequestSchemaError(ref errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "Request does not match schema: {}", errors.join("; "))
+            }
+            CedrusError::EntityLinked(ref references) => {
+                let entities: Vec<String> =
+                    references.iter().map(|r| r.entity.to_string()).collect();
+                write!(
+                    f,
+                    "Entities named by template links: {}",
+                    entities.join(", ")
+                )
+            }
+            CedrusError::IntegrityError(ref diagnostics) => {
+                write!(f, "Integrity check failed: {}", diagnostics.join("; "))
+            }
+            CedrusError::ValidationError(ref errors) => {
+                write!(f, "Policy validation failed: {}", errors.join("; "))
+            }
         }
     }
 }
 
 impl From<DatabaseError> for CedrusError {
     fn from(error: DatabaseError) -> Self {
-        Self::DatabaseError(error)
+        match error {
+            DatabaseError::Conflict => Self::Conflict,
+            DatabaseError::SealedValue(_) | DatabaseError::InvalidQuery(_) => Self::BadRequest,
+            error => Self::DatabaseError(error),
+        }
     }
 }
 
@@ -334,6 +415,12 @@ impl From<serde_json::Error> for CedrusError {
     }
 }
 
+impl From<cedar_policy::ParseErrors> for CedrusError {
+    fn from(error: cedar_policy::ParseErrors) -> Self {
+        Self::EntityUidError(error)
+    }
+}
+
 impl From<cedar_policy::SchemaError> for CedrusError {
     fn from(error: cedar_policy::SchemaError) -> Self {
         Self::SchemaError(error)
@@ -379,9 +466,12 @@ impl From<cedar_policy::RequestValidationError> for CedrusError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EventType {
     ReloadAll,
+    CommonTypesUpdate,
+    RolesUpdate,
     ProjectCreate(Uuid),
     ProjectUpdate(Uuid),
     ProjectRemove(Uuid, HashSet<String>),
+    ProjectResync(Uuid, HashSet<String>),
 
     ProjectAddApikeys(Uuid, HashSet<Uuid>),
     ProjectRemoveApikeys(Uuid, HashSet<String>),
@@ -392,51 +482,83 @@ pub enum EventType {
     ProjectRemoveSchema(Uuid),
     ProjectAddEntities(Uuid, HashSet<EntityUid>),
     ProjectRemoveEntities(Uuid, HashSet<EntityUid>),
+    ProjectSyncEntities(Uuid, HashSet<EntityUid>, HashSet<EntityUid>),
     ProjectAddPolicies(Uuid, HashSet<PolicyId>),
     ProjectRemovePolicies(Uuid, HashSet<PolicyId>),
     ProjectAddTemplates(Uuid, HashSet<PolicyId>),
     ProjectRemoveTemplates(Uuid, HashSet<PolicyId>),
     ProjectAddTemplateLinks(Uuid, HashSet<PolicyId>),
     ProjectRemoveTemplateLinks(Uuid, HashSet<PolicyId>),
+
+    NodeHeartbeat(NodeHeartbeat),
+}
+
+impl EventType {
+    pub fn project_id(&self) -> Option<&Uuid> {
+        match self {
+            EventType::ReloadAll
+            | EventType::CommonTypesUpdate
+            | EventType::RolesUpdate
+            | EventType::NodeHeartbeat(_) => None,
+            EventType::ProjectCreate(id)
+            | EventType::ProjectUpdate(id)
+            | EventType::ProjectRemove(id, _)
+            | EventType::ProjectResync(id, _)
+            | EventType::ProjectAddApikeys(id, _)
+            | EventType::ProjectRemoveApikeys(id, _)
+            | EventType::ProjectPutIdentitySource(id)
+            | EventType::ProjectRemoveIdentitySource(id)
+            | EventType::ProjectPutSchema(id)
+            | EventType::ProjectRemoveSchema(id)
+            | EventType::ProjectAddEntities(id, _)
+            | EventType::ProjectRemoveEntities(id, _)
+            | EventType::ProjectSyncEntities(id, _, _)
+            | EventType::ProjectAddPolicies(id, _)
+            | EventType::ProjectRemovePolicies(id, _)
+            | EventType::ProjectAddTemplates(id, _)
+            | EventType::ProjectRemoveTemplates(id, _)
+            | EventType::ProjectAddTemplateLinks(id, _)
+            | EventType::ProjectRemoveTemplateLinks(id, _) => Some(id),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
     sender: Uuid,
     msg: EventType,
+    /// When the event was created, unset on events of earlier versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sent_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl Event {
     pub fn new(sender: Uuid, msg: EventType) -> Self {
-        Self { sender, msg }
-    }
-
-    pub fn project_create(sender: Uuid, project_id: Uuid) -> Self {
         Self {
             sender,
-            msg: EventType::ProjectCreate(project_id),
+            msg,
+            sent_at: Some(chrono::Utc::now()),
         }
     }
 
+    pub fn project_create(sender: Uuid, project_id: Uuid) -> Self {
+        Self::new(sender, EventType::ProjectCreate(project_id))
+    }
+
     pub fn project_update(sender: Uuid, project_id: Uuid) -> Self {
-        Self {
-            sender,
-            msg: EventType::ProjectUpdate(project_id),
-        }
+        Self::new(sender, EventType::ProjectUpdate(project_id))
     }
 
     pub fn project_remove(sender: Uuid, project_id: Uuid, api_keys: HashSet<String>) -> Self {
-        Self {
-            sender,
-            msg: EventType::ProjectRemove(project_id, api_keys),
-        }
+        Self::new(sender, EventType::ProjectRemove(project_id, api_keys))
+    }
+
+    pub fn project_resync(sender: Uuid, project_id: Uuid, api_keys: HashSet<String>) -> Self {
+        Self::new(sender, EventType::ProjectResync(project_id, api_keys))
     }
 
     pub fn project_add_apikeys(sender: Uuid, project_id: Uuid, api_keys: HashSet<Uuid>) -> Self {
-        Self {
-            sender,
-            msg: EventType::ProjectAddApikeys(project_id, api_keys),
-        }
+        Self::new(sender, EventType::ProjectAddApikeys(project_id, api_keys))
     }
 
     pub fn project_remove_apikeys(
@@ -444,38 +566,38 @@ impl Event {
         project_id: Uuid,
         api_keys: HashSet<String>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectRemoveApikeys(project_id, api_keys),
-        }
+            EventType::ProjectRemoveApikeys(project_id, api_keys),
+        )
     }
 
     pub fn project_put_identity_source(sender: Uuid, project_id: Uuid) -> Self {
-        Self {
-            sender,
-            msg: EventType::ProjectPutIdentitySource(project_id),
-        }
+        Self::new(sender, EventType::ProjectPutIdentitySource(project_id))
     }
 
     pub fn project_remove_identity_source(sender: Uuid, project_id: Uuid) -> Self {
-        Self {
-            sender,
-            msg: EventType::ProjectRemoveIdentitySource(project_id),
-        }
+        Self::new(sender, EventType::ProjectRemoveIdentitySource(project_id))
+    }
+
+    pub fn node_heartbeat(sender: Uuid, heartbeat: NodeHeartbeat) -> Self {
+        Self::new(sender, EventType::NodeHeartbeat(heartbeat))
+    }
+
+    pub fn common_types_update(sender: Uuid) -> Self {
+        Self::new(sender, EventType::CommonTypesUpdate)
+    }
+
+    pub fn roles_update(sender: Uuid) -> Self {
+        Self::new(sender, EventType::RolesUpdate)
     }
 
     pub fn project_put_schema(sender: Uuid, project_id: Uuid) -> Self {
-        Self {
-            sender,
-            msg: EventType::ProjectPutSchema(project_id),
-        }
+        Self::new(sender, EventType::ProjectPutSchema(project_id))
     }
 
     pub fn project_remove_schema(sender: Uuid, project_id: Uuid) -> Self {
-        Self {
-            sender,
-            msg: EventType::ProjectRemoveSchema(project_id),
-        }
+        Self::new(sender, EventType::ProjectRemoveSchema(project_id))
     }
 
     pub fn project_add_entities(
@@ -483,10 +605,10 @@ impl Event {
         project_id: Uuid,
         entities_uids: HashSet<EntityUid>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectAddEntities(project_id, entities_uids),
-        }
+            EventType::ProjectAddEntities(project_id, entities_uids),
+        )
     }
 
     pub fn project_remove_entities(
@@ -494,10 +616,22 @@ impl Event {
         project_id: Uuid,
         entities_uids: HashSet<EntityUid>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectRemoveEntities(project_id, entities_uids),
-        }
+            EventType::ProjectRemoveEntities(project_id, entities_uids),
+        )
+    }
+
+    pub fn project_sync_entities(
+        sender: Uuid,
+        project_id: Uuid,
+        changed_uids: HashSet<EntityUid>,
+        removed_uids: HashSet<EntityUid>,
+    ) -> Self {
+        Self::new(
+            sender,
+            EventType::ProjectSyncEntities(project_id, changed_uids, removed_uids),
+        )
     }
 
     pub fn project_add_policies(
@@ -505,10 +639,10 @@ impl Event {
         project_id: Uuid,
         policy_ids: HashSet<PolicyId>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectAddPolicies(project_id, policy_ids),
-        }
+            EventType::ProjectAddPolicies(project_id, policy_ids),
+        )
     }
 
     pub fn project_remove_policies(
@@ -516,10 +650,10 @@ impl Event {
         project_id: Uuid,
         policy_ids: HashSet<PolicyId>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectRemovePolicies(project_id, policy_ids),
-        }
+            EventType::ProjectRemovePolicies(project_id, policy_ids),
+        )
     }
 
     pub fn project_add_templates(
@@ -527,10 +661,10 @@ impl Event {
         project_id: Uuid,
         policy_ids: HashSet<PolicyId>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectAddTemplates(project_id, policy_ids),
-        }
+            EventType::ProjectAddTemplates(project_id, policy_ids),
+        )
     }
 
     pub fn project_remove_templates(
@@ -538,10 +672,10 @@ impl Event {
         project_id: Uuid,
         policy_ids: HashSet<PolicyId>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectRemoveTemplates(project_id, policy_ids),
-        }
+            EventType::ProjectRemoveTemplates(project_id, policy_ids),
+        )
     }
 
     pub fn project_add_template_links(
@@ -549,10 +683,10 @@ impl Event {
         project_id: Uuid,
         policy_ids: HashSet<PolicyId>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectAddTemplateLinks(project_id, policy_ids),
-        }
+            EventType::ProjectAddTemplateLinks(project_id, policy_ids),
+        )
     }
 
     pub fn project_remove_template_links(
@@ -560,10 +694,10 @@ impl Event {
         project_id: Uuid,
         policy_ids: HashSet<PolicyId>,
     ) -> Self {
-        Self {
+        Self::new(
             sender,
-            msg: EventType::ProjectRemoveTemplateLinks(project_id, policy_ids),
-        }
+            EventType::ProjectRemoveTemplateLinks(project_id, policy_ids),
+        )
     }
 
     pub fn sender(&self) -> Uuid {
@@ -573,6 +707,10 @@ impl Event {
     pub fn msg(&self) -> &EventType {
         &self.msg
     }
+
+    pub fn sent_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.sent_at
+    }
 }
 
 #[async_trait::async_trait]
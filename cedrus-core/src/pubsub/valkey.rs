@@ -38,7 +38,10 @@ impl ValKeyPubSub {
             ConnectionType::Multiplexed(conn)
         };
 
-        let topic = conf.channel_name.clone();
+        let topic = match &conf.namespace {
+            Some(namespace) => format!("{}:{}", namespace, conf.channel_name),
+            None => conf.channel_name.clone(),
+        };
 
         Ok(Self {
             conn,
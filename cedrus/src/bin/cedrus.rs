@@ -1,33 +1,85 @@
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use axum::{Router, middleware, routing::get};
+use axum::{Router, extract::DefaultBodyLimit, middleware, routing::get};
 use cedrus::{
-    AppState, QueryParams,
-    routes::{auth, projects},
+    AppState, DiagnosticSeverity, QueryParams,
+    kms::KmsKeys,
+    notifications,
+    offline::{self, Diagnostics},
+    routes::{
+        admin, audit, auth, common_types,
+        limits::{self, RouteLimits},
+        log, projects, read_only, shards, staleness,
+    },
+    sampling::DecisionSampler,
+    secrets,
+    tls::TlsListener,
 };
 use cedrus_core::{
     CedrusError, Event, Selector,
-    cache::cache_factory,
-    core::{CedrusConfig, cedrus::Cedrus},
-    db::database_factory,
+    cache::{Cache, cache_factory, regional::RegionalCache, valkey::ValKeyCache},
+    core::{
+        AuthConfig, CacheConfig, CedrusConfig, DashMapCacheConfig, DbConfig, DummyPubSubConfig,
+        EncryptionConfig, PubSubConfig, ServerConfig, TlsConfig,
+        anomaly::DenyRates,
+        bundle::BundleKeys,
+        cedrus::Cedrus,
+        cluster::ClusterMembership,
+        crypto::{HmacKeys, KeyProvider, LocalKeys},
+        dry_run::DryRunReport,
+        isolation::EvaluationSlots,
+        shard::ShardOwnership,
+        token::TokenCache,
+        write_behind::WRITE_BEHIND_MAX_ATTEMPTS,
+    },
+    db::{
+        Database, database_factory, encrypted::EncryptedDb, migration, read_replica_factory,
+        regional::RegionalDb,
+    },
     pubsub::pubsub_factory,
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use opentelemetry_sdk::propagation::TraceContextPropagator;
 use tower_http::{
-    compression::CompressionLayer,
+    compression::{
+        CompressionLayer, Predicate,
+        predicate::{NotForContentType, SizeAbove},
+    },
     cors::{Any, CorsLayer},
+    decompression::RequestDecompressionLayer,
     trace::TraceLayer,
 };
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use utoipa::{
-    Modify, OpenApi,
-    openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi, PartialSchema, ToSchema,
+    openapi::{
+        Content, Ref, Response,
+        schema::OneOfBuilder,
+        security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
+    },
     schema,
 };
 use utoipa_swagger_ui::SwaggerUi;
+use uuid::Uuid;
 
 const CEDRUS_ADMIN_API_KEY_ENV: &str = "CEDRUS_ADMIN_API_KEY";
+/// Interval between the checks of the projects due a GitOps sync.
+const GITOPS_TICK: Duration = Duration::from_secs(10);
+/// Default interval in seconds between sweeps of expired entities.
+const ENTITY_EXPIRY_INTERVAL: u64 = 60;
+/// Default interval in seconds between snapshots of the changed compiled entities.
+const COMPILED_ENTITIES_INTERVAL: u64 = 30;
+/// Default largest decompressed request body, policy sets and entity imports reach tens of MB.
+const MAX_BODY_SIZE: usize = 64 * 1024 * 1024;
+/// Default smallest response body worth compressing.
+const COMPRESSION_MIN_SIZE: u16 = 1024;
 
 /// Initializes the OpenTelemetry tracer provider with OTLP gRPC export.
 /// The OTLP endpoint defaults to `http://localhost:4317` and can be overridden
@@ -156,6 +208,55 @@ impl Modify for SecurityAddon {
     }
 }
 
+/// Documents the report a route taking `dryRun` answers with instead of its response.
+struct DryRunAddon;
+
+impl Modify for DryRunAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        let mut schemas = vec![(DryRunReport::name().into_owned(), DryRunReport::schema())];
+        DryRunReport::schemas(&mut schemas);
+        components.schemas.extend(schemas);
+
+        let report = Ref::from_schema_name(DryRunReport::name());
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                let dry_run = operation
+                    .parameters
+                    .iter()
+                    .flatten()
+                    .any(|parameter| parameter.name == "dryRun");
+                if !dry_run {
+                    continue;
+                }
+                let response = operation
+                    .responses
+                    .responses
+                    .entry("200".to_string())
+                    .or_insert_with(|| Response::new("Changes a dry run would make").into());
+                let utoipa::openapi::RefOr::T(response) = response else {
+                    continue;
+                };
+                // The applied response and the report share the JSON content of the status
+                let content = response
+                    .content
+                    .entry("application/json".to_string())
+                    .or_insert_with(|| Content::new(None::<Ref>));
+                content.schema = Some(match content.schema.take() {
+                    Some(schema) => OneOfBuilder::new().item(schema).item(report.clone()).into(),
+                    None => report.clone().into(),
+                });
+            }
+        }
+    }
+}
+
 #[derive(OpenApi)]
 #[openapi(
     info(
@@ -167,13 +268,28 @@ impl Modify for SecurityAddon {
             identifier = "Apache-2.0",
         ),
     ),
-    modifiers(&SecurityAddon),
+    modifiers(&SecurityAddon, &DryRunAddon),
     paths(
         projects::projects_get,
         projects::projects_post,
         projects::projects_id_get,
         projects::projects_id_put,
+        projects::projects_id_read_only_put,
         projects::projects_id_delete,
+        projects::projects_id_stats_get,
+        projects::projects_id_consistency_get,
+        projects::projects_id_consistency_post,
+        projects::projects_id_state_put,
+        projects::projects_id_gitops_get,
+        projects::projects_id_gitops_sync_post,
+        projects::projects_id_environments_get,
+        projects::projects_id_environments_post,
+        projects::projects_id_environments_name_promote_post,
+        projects::projects_id_candidate_get,
+        projects::projects_id_candidate_put,
+        projects::projects_id_candidate_delete,
+        projects::projects_id_candidate_promote_post,
+        projects::projects_id_resync_post,
         projects::projects_id_identity_source_get,
         projects::projects_id_identity_source_put,
         projects::projects_id_identity_source_delete,
@@ -181,6 +297,20 @@ impl Modify for SecurityAddon {
         projects::projects_id_apikeys_post,
         projects::projects_id_apikeys_key_put,
         projects::projects_id_apikeys_key_delete,
+        projects::projects_id_roles_get,
+        projects::projects_id_roles_post,
+        projects::projects_id_roles_delete,
+        projects::projects_id_roles_role_id_principals_post,
+        projects::projects_id_roles_role_id_principals_delete,
+        projects::projects_id_delegations_get,
+        projects::projects_id_delegations_post,
+        projects::projects_id_delegations_delete,
+        projects::projects_id_jobs_get,
+        projects::projects_id_jobs_job_id_get,
+        projects::projects_id_jobs_import_post,
+        projects::projects_id_jobs_export_post,
+        projects::projects_id_jobs_cleanup_post,
+        projects::projects_id_audit_get,
         projects::projects_id_schema_get,
         projects::projects_id_schema_put,
         projects::projects_id_schema_delete,
@@ -188,30 +318,64 @@ impl Modify for SecurityAddon {
         projects::projects_id_schema_cedar_put,
         projects::projects_id_schema_validate_cedar_post,
         projects::projects_id_schema_validate_json_post,
+        projects::projects_id_schema_lint_post,
+        projects::projects_id_schema_sdk_get,
         projects::projects_id_entities_get,
         projects::projects_id_entities_post,
+        projects::projects_id_entities_paths_post,
+        projects::projects_id_entities_uid_get,
+        projects::projects_id_entities_uid_references_get,
+        projects::projects_id_relations_get,
+        projects::projects_id_relations_post,
+        projects::projects_id_relations_delete,
         projects::projects_id_entities_delete,
+        projects::projects_id_entities_batch_delete_post,
+        projects::projects_id_entities_sync_post,
         projects::projects_id_policies_get,
         projects::projects_id_policies_post,
         projects::projects_id_policies_delete,
+        projects::projects_id_policies_batch_delete_post,
+        projects::projects_id_revisions_diff_get,
+        projects::projects_id_policies_version_get,
+        projects::projects_id_policies_coverage_get,
         projects::projects_id_policies_validate_cedar_post,
         projects::projects_id_policies_validate_json_post,
+        projects::projects_id_policies_policy_id_get,
         projects::projects_id_policies_policy_id_cedar_get,
         projects::projects_id_policies_policy_id_cedar_put,
+        projects::projects_id_policies_policy_id_references_get,
         projects::projects_id_templates_get,
         projects::projects_id_templates_post,
         projects::projects_id_templates_delete,
+        projects::projects_id_templates_batch_delete_post,
+        projects::projects_id_templates_template_id_get,
+        projects::projects_id_templates_template_id_put,
+        projects::projects_id_templates_template_id_delete,
         projects::projects_id_templates_template_id_cedar_get,
         projects::projects_id_templates_template_id_cedar_put,
         projects::projects_id_template_links_get,
         projects::projects_id_template_links_post,
+        projects::projects_id_template_links_batch_create_post,
         projects::projects_id_template_links_delete,
+        projects::projects_id_template_links_policy_id_get,
         projects::projects_id_template_links_policy_id_cedar_get,
         projects::projects_id_template_links_policy_id_cedar_put,
         projects::projects_id_policy_set_get,
         projects::projects_id_policy_set_cedar_get,
+        projects::projects_id_policy_set_cedar_post,
+        projects::projects_id_policy_set_bundle_get,
+        projects::projects_id_policy_set_bundle_post,
         projects::projects_id_is_authorized_post,
         projects::projects_id_is_authorized_batch_post,
+        projects::projects_id_replay_post,
+        projects::projects_id_benchmark_post,
+        projects::projects_id_generate_post,
+        projects::projects_is_authorized_post,
+        common_types::common_types_get,
+        common_types::common_types_put,
+        common_types::common_types_name_put,
+        common_types::common_types_name_delete,
+        admin::admin_cluster_get,
     ),
     tags(
         (name = "Cedrus", description = "Cedar Policy Server")
@@ -221,10 +385,121 @@ struct ApiDoc;
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    #[arg(short = 'c', long)]
+    #[arg(short = 'c', long, global = true)]
     config: Option<String>,
-    #[arg(short = 'u', long)]
+    #[arg(short = 'u', long, global = true)]
     url_config: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Apply pending database migrations and exit
+    Migrate {
+        /// Schema version to migrate to, the latest one by default
+        #[arg(long)]
+        target: Option<u32>,
+        /// Only list the migrations that would be applied
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Rename the Valkey cache keys of a namespace into the configured one and exit
+    MigrateCache {
+        /// Namespace the keys are renamed from, the keys without namespace by default
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Serve the API as an AWS Lambda function, reloading the projects of every invocation
+    #[cfg(feature = "lambda")]
+    Lambda,
+    /// Check or format a local policy set, without a server or configuration
+    Policy {
+        #[command(subcommand)]
+        command: PolicyCommand,
+    },
+    /// Evaluate a request against local policies and entities, printing the response
+    Authorize {
+        /// Policy set, `.cedar` with `@id` annotations or JSON
+        #[arg(long)]
+        policies: PathBuf,
+        /// Entities, a JSON array
+        #[arg(long)]
+        entities: Option<PathBuf>,
+        /// Schema, `.cedarschema` or JSON
+        #[arg(long)]
+        schema: Option<PathBuf>,
+        /// Request, JSON with `principal`, `action`, `resource` and `context`
+        #[arg(long)]
+        request: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PolicyCommand {
+    /// Parse a policy set and validate it against a schema, printing the diagnostics
+    Check {
+        /// Policy set, `.cedar` with `@id` annotations or JSON
+        #[arg(long)]
+        policies: PathBuf,
+        /// Schema, `.cedarschema` or JSON
+        #[arg(long)]
+        schema: Option<PathBuf>,
+    },
+    /// Print a policy set in Cedar syntax, or as JSON
+    Format {
+        /// Policy set, `.cedar` with `@id` annotations or JSON
+        #[arg(long)]
+        policies: PathBuf,
+        /// Print JSON instead of Cedar syntax
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+// Runs the subcommands working on local files. Diagnostics are printed as JSON and exit
+// with status 1 when any of them is an error.
+fn offline_command(command: Command) -> Result<(), Diagnostics> {
+    let output = match command {
+        Command::Policy {
+            command: PolicyCommand::Check { policies, schema },
+        } => {
+            let policy_set = offline::load_policy_set(&policies)?;
+            let schema = schema.as_deref().map(offline::load_schema).transpose()?;
+            let diagnostics = offline::policy_check(policy_set, schema);
+            if diagnostics
+                .iter()
+                .any(|d| matches!(d.severity, DiagnosticSeverity::Error))
+            {
+                return Err(diagnostics);
+            }
+            serde_json::to_string_pretty(&diagnostics).unwrap_or_default()
+        }
+        Command::Policy {
+            command: PolicyCommand::Format { policies, json },
+        } => offline::policy_format(offline::load_policy_set(&policies)?, json)?,
+        Command::Authorize {
+            policies,
+            entities,
+            schema,
+            request,
+        } => {
+            let response = offline::authorize(
+                offline::load_policy_set(&policies)?,
+                entities
+                    .as_deref()
+                    .map(offline::load_entities)
+                    .transpose()?
+                    .unwrap_or_default(),
+                schema.as_deref().map(offline::load_schema).transpose()?,
+                offline::load_request(&request)?,
+            )?;
+            serde_json::to_string_pretty(&response).unwrap_or_default()
+        }
+        _ => return Ok(()),
+    };
+    println!("{}", output.trim_end());
+    Ok(())
 }
 
 type SubscribeFn<'a> =
@@ -239,17 +514,646 @@ fn subscribe_closure<'a>(state: &'a Cedrus) -> SubscribeFn<'a> {
     Box::new(closure)
 }
 
-async fn cedrus_init(config: &CedrusConfig) -> Result<Cedrus, CedrusError> {
+async fn entity_expiry_loop(cedrus: &Cedrus, interval: u64) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+
+    loop {
+        ticker.tick().await;
+
+        match cedrus.entities_expire().await {
+            Ok(removed) => {
+                for (project_id, count, links) in removed {
+                    tracing::info!(
+                        "Removed {count} expired entities of project {project_id} and {links} template links naming them"
+                    );
+                }
+            }
+            Err(e) => tracing::error!("Entity expiry failed: {:?}", e),
+        }
+    }
+}
+
+async fn consistency_check_loop(cedrus: &Cedrus, interval: u64) {
+    #[cfg(feature = "metrics")]
+    let discrepancies = opentelemetry::global::meter("cedrus")
+        .u64_counter("cedrus.consistency.discrepancies")
+        .with_description("Discrepancies found between Database, Cache and memory")
+        .build();
+
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval));
+    // The first tick completes immediately, the state was just loaded
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+
+        match cedrus.consistency_check(true).await {
+            Ok(reports) => {
+                for report in reports.iter().filter(|r| !r.is_consistent()) {
+                    if let Some(e) = &report.error {
+                        tracing::error!(
+                            "Consistency check of project {} failed: {}",
+                            report.project_id,
+                            e
+                        );
+                        continue;
+                    }
+                    tracing::warn!(
+                        "Consistency check repaired project {}: {} discrepancies",
+                        report.project_id,
+                        report.discrepancies()
+                    );
+
+                    #[cfg(feature = "metrics")]
+                    discrepancies.add(
+                        report.discrepancies() as u64,
+                        &[opentelemetry::KeyValue::new(
+                            "project_id",
+                            report.project_id.to_string(),
+                        )],
+                    );
+                }
+            }
+            Err(e) => tracing::error!("Consistency check failed: {:?}", e),
+        }
+    }
+}
+
+/// Persists the compiled entities of the projects changed since the previous tick.
+async fn compiled_entities_loop(cedrus: &Cedrus, interval: u64) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+
+    loop {
+        ticker.tick().await;
+
+        let persisted = cedrus.compiled_entities_snapshot().await;
+        if persisted > 0 {
+            tracing::debug!("Persisted the compiled entities of {persisted} projects");
+        }
+    }
+}
+
+/// Publishes the heartbeats of this node to the others.
+async fn cluster_heartbeat_loop(cedrus: &Cedrus, interval: u64) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+
+    loop {
+        ticker.tick().await;
+        cedrus.cluster_heartbeat().await;
+    }
+}
+
+/// Syncs the projects polling their Git repository once their interval elapsed, each on the
+/// node owning it only so the changes are applied once.
+async fn gitops_loop(cedrus: &Cedrus) {
+    let mut last_syncs: HashMap<Uuid, Instant> = HashMap::new();
+    let mut ticker = tokio::time::interval(GITOPS_TICK);
+
+    loop {
+        ticker.tick().await;
+
+        let due: Vec<Uuid> = cedrus
+            .gitops_projects
+            .iter()
+            .filter(|entry| cedrus.shards.owns(entry.key()))
+            .filter_map(|entry| {
+                let interval = Duration::from_secs(entry.value().poll_interval?);
+                let elapsed = last_syncs
+                    .get(entry.key())
+                    .is_none_or(|last| last.elapsed() >= interval);
+                elapsed.then_some(*entry.key())
+            })
+            .collect();
+        last_syncs.retain(|id, _| cedrus.gitops_projects.contains_key(id));
+
+        for project_id in due {
+            last_syncs.insert(project_id, Instant::now());
+            match cedrus.project_gitops_sync(project_id, true).await {
+                Ok(report) if report.has_drift() => tracing::info!(
+                    "GitOps sync of project {} to commit {}",
+                    project_id,
+                    report.commit
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::error!("GitOps sync of project {} failed: {:?}", project_id, e),
+            }
+        }
+    }
+}
+
+/// Exposes the memory accounting of an in-process Cache as metrics.
+#[cfg(feature = "metrics")]
+fn register_cache_metrics(state: Arc<AppState>) {
+    let meter = opentelemetry::global::meter("cedrus");
+
+    let shared = state.clone();
+    let _ = meter
+        .u64_observable_gauge("cedrus.cache.size")
+        .with_description("Approximate bytes of entities held by the in-memory Cache")
+        .with_callback(move |observer| {
+            if let Some(usage) = shared.cedrus.cache.usage() {
+                observer.observe(usage.size as u64, &[]);
+            }
+        })
+        .build();
+
+    let shared = state.clone();
+    let _ = meter
+        .u64_observable_gauge("cedrus.cache.evicted_projects")
+        .with_description("Projects whose entities are evicted from the in-memory Cache")
+        .with_callback(move |observer| {
+            if let Some(usage) = shared.cedrus.cache.usage() {
+                observer.observe(usage.evicted_projects as u64, &[]);
+            }
+        })
+        .build();
+
+    let _ = meter
+        .u64_observable_counter("cedrus.cache.evictions")
+        .with_description("Evictions of project entities from the in-memory Cache")
+        .with_callback(move |observer| {
+            if let Some(usage) = state.cedrus.cache.usage() {
+                observer.observe(usage.evictions, &[]);
+            }
+        })
+        .build();
+}
+
+/// Exposes the authorizations given up on past the evaluation timeout of their project, those
+/// waiting for an evaluation slot, and the Deny rates of each project.
+#[cfg(feature = "metrics")]
+fn register_evaluation_metrics(state: Arc<AppState>) {
+    let meter = opentelemetry::global::meter("cedrus");
+
+    let shared = state.clone();
+    let _ = meter
+        .u64_observable_gauge("cedrus.evaluation.queue_depth")
+        .with_description("Evaluations of the project waiting for an evaluation slot")
+        .with_callback(move |observer| {
+            for (project_id, waiting) in shared.cedrus.evaluation_slots.queue_depths() {
+                observer.observe(
+                    waiting as u64,
+                    &[opentelemetry::KeyValue::new(
+                        "project_id",
+                        project_id.to_string(),
+                    )],
+                );
+            }
+        })
+        .build();
+
+    let shared = state.clone();
+    let _ = meter
+        .f64_observable_gauge("cedrus.authorization.deny_rate")
+        .with_description("Rolling share of Deny decisions of the project per action")
+        .with_callback(move |observer| {
+            for (project_id, rates) in shared.cedrus.deny_rates.reports() {
+                for rate in rates {
+                    observer.observe(
+                        rate.rate,
+                        &[
+                            opentelemetry::KeyValue::new("project_id", project_id.to_string()),
+                            opentelemetry::KeyValue::new("action", rate.action.to_string()),
+                        ],
+                    );
+                }
+            }
+        })
+        .build();
+
+    let shared = state.clone();
+    let _ = meter
+        .u64_observable_counter("cedrus.authorization.deny_rate_spikes")
+        .with_description("Deny rate rises past their baseline after a policy change")
+        .with_callback(move |observer| {
+            for (project_id, spikes) in shared.cedrus.deny_rates.spike_counts() {
+                observer.observe(
+                    spikes,
+                    &[opentelemetry::KeyValue::new(
+                        "project_id",
+                        project_id.to_string(),
+                    )],
+                );
+            }
+        })
+        .build();
+
+    let _ = meter
+        .u64_observable_counter("cedrus.authorization.timeouts")
+        .with_description("Authorizations given up on past the evaluation timeout of the project")
+        .with_callback(move |observer| {
+            for timeouts in state.cedrus.evaluation_timeouts.iter() {
+                observer.observe(
+                    *timeouts.value(),
+                    &[opentelemetry::KeyValue::new(
+                        "project_id",
+                        timeouts.key().to_string(),
+                    )],
+                );
+            }
+        })
+        .build();
+}
+
+/// Exposes how the decisions of the candidate policy set of each project compare to the live
+/// ones.
+#[cfg(feature = "metrics")]
+fn register_candidate_metrics(state: Arc<AppState>) {
+    let meter = opentelemetry::global::meter("cedrus");
+
+    let shared = state.clone();
+    let _ = meter
+        .u64_observable_counter("cedrus.candidate.evaluations")
+        .with_description("Requests evaluated against the candidate policy set of the project")
+        .with_callback(move |observer| {
+            for shadow in shared.cedrus.project_candidates.iter() {
+                let (evaluations, _, _) = shadow.counts();
+                observer.observe(
+                    evaluations,
+                    &[
+                        opentelemetry::KeyValue::new("project_id", shadow.key().to_string()),
+                        opentelemetry::KeyValue::new("version", shadow.version.clone()),
+                    ],
+                );
+            }
+        })
+        .build();
+
+    let _ = meter
+        .u64_observable_counter("cedrus.candidate.divergences")
+        .with_description("Requests the candidate policy set decides otherwise than the live one")
+        .with_callback(move |observer| {
+            for shadow in state.cedrus.project_candidates.iter() {
+                let (_, allow_to_deny, deny_to_allow) = shadow.counts();
+                for (direction, divergences) in [
+                    ("allow_to_deny", allow_to_deny),
+                    ("deny_to_allow", deny_to_allow),
+                ] {
+                    observer.observe(
+                        divergences,
+                        &[
+                            opentelemetry::KeyValue::new("project_id", shadow.key().to_string()),
+                            opentelemetry::KeyValue::new("version", shadow.version.clone()),
+                            opentelemetry::KeyValue::new("direction", direction),
+                        ],
+                    );
+                }
+            }
+        })
+        .build();
+}
+
+/// Exposes how stale the data of each project may be on this node, and the delay its last
+/// event from another node was applied after.
+#[cfg(feature = "metrics")]
+fn register_staleness_metrics(state: Arc<AppState>) {
+    let meter = opentelemetry::global::meter("cedrus");
+
+    let shared = state.clone();
+    let _ = meter
+        .f64_observable_gauge("cedrus.project.staleness")
+        .with_description("Seconds the data of the project may have changed elsewhere unseen")
+        .with_callback(move |observer| {
+            for (project_id, _) in shared.cedrus.event_staleness.projects() {
+                if let Some(staleness) = shared.cedrus.project_staleness(&project_id) {
+                    observer.observe(
+                        staleness.num_milliseconds() as f64 / 1000.0,
+                        &[opentelemetry::KeyValue::new(
+                            "project_id",
+                            project_id.to_string(),
+                        )],
+                    );
+                }
+            }
+        })
+        .build();
+
+    let _ = meter
+        .f64_observable_gauge("cedrus.project.event_lag")
+        .with_description("Seconds between the last event of the project being sent and applied")
+        .with_callback(move |observer| {
+            for (project_id, applied) in state.cedrus.event_staleness.projects() {
+                if let Some(lag) = applied.lag {
+                    observer.observe(
+                        lag.num_milliseconds() as f64 / 1000.0,
+                        &[opentelemetry::KeyValue::new(
+                            "project_id",
+                            project_id.to_string(),
+                        )],
+                    );
+                }
+            }
+        })
+        .build();
+}
+
+/// Interval in seconds between attempts to rebuild JWT authorizers whose identity provider
+/// was unreachable.
+const AUTHORIZER_REBUILD_INTERVAL: u64 = 30;
+
+async fn authorizer_rebuild_loop(cedrus: &Cedrus) {
+    let mut ticker =
+        tokio::time::interval(std::time::Duration::from_secs(AUTHORIZER_REBUILD_INTERVAL));
+
+    loop {
+        ticker.tick().await;
+
+        if cedrus.pending_identity_sources.is_empty() {
+            continue;
+        }
+
+        match cedrus.authorizers_rebuild().await {
+            0 => tracing::info!("JWT authorizers rebuilt"),
+            pending => tracing::warn!("{} JWT authorizers still unavailable", pending),
+        }
+    }
+}
+
+/// Interval in seconds between the checks for jobs whose node stopped renewing their lease.
+const JOB_RECOVERY_INTERVAL: u64 = 30;
+
+async fn job_recovery_loop(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(JOB_RECOVERY_INTERVAL));
+
+    loop {
+        ticker.tick().await;
+
+        match state.cedrus.project_jobs_recover().await {
+            Ok(recovered) => {
+                for (job, task) in recovered {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        let job_id = job.id;
+                        if let Err(e) = state.cedrus.project_job_run(job, task).await {
+                            tracing::error!("Job {} not completed: {}", job_id, e);
+                        }
+                    });
+                }
+            }
+            Err(e) => tracing::error!("Job recovery failed: {:?}", e),
+        }
+    }
+}
+
+/// Bounds in seconds of the delay before retrying a failed write-behind flush, doubled
+/// after every failure.
+const WRITE_BEHIND_RETRY_MIN: u64 = 1;
+const WRITE_BEHIND_RETRY_MAX: u64 = 60;
+
+/// Flushes the deferred writes on shutdown, retrying while the Database makes progress. Every
+/// write left behind, still pending or dead-lettered, is logged and fails the shutdown, so the
+/// node exits with an error instead of losing writes silently.
+async fn write_behind_drain(cedrus: &Cedrus) -> Result<(), Box<dyn std::error::Error>> {
+    let mut retry = WRITE_BEHIND_RETRY_MIN;
+    let mut failures = 0;
+    while !cedrus.write_behind.is_empty() && failures < WRITE_BEHIND_MAX_ATTEMPTS {
+        match cedrus.write_behind_flush().await {
+            Ok(_) => failures = 0,
+            Err(e) => {
+                failures += 1;
+                tracing::warn!(
+                    "Write-behind flush failed, {} writes pending, retrying in {}s: {:?}",
+                    cedrus.write_behind.len(),
+                    retry,
+                    e
+                );
+                tokio::time::sleep(std::time::Duration::from_secs(retry)).await;
+                retry = (retry * 2).min(WRITE_BEHIND_RETRY_MAX);
+            }
+        }
+    }
+
+    let pending = cedrus.write_behind.pending();
+    for op in &pending {
+        tracing::error!(
+            "Write-behind write lost: {} of project {} still pending",
+            op.kind(),
+            op.project_id()
+        );
+    }
+    let dead_letters = cedrus.write_behind.dead_letters();
+    for dead_letter in &dead_letters {
+        tracing::error!(
+            "Write-behind write lost: {} of project {} dead-lettered at {}: {}",
+            dead_letter.op.kind(),
+            dead_letter.op.project_id(),
+            dead_letter.at,
+            dead_letter.error
+        );
+    }
+
+    let lost = pending.len() + dead_letters.len();
+    if lost > 0 {
+        return Err(format!("{} write-behind writes lost", lost).into());
+    }
+    Ok(())
+}
+
+async fn write_behind_loop(cedrus: &Cedrus) {
+    let mut retry = WRITE_BEHIND_RETRY_MIN;
+
+    loop {
+        if cedrus.write_behind.is_empty() {
+            cedrus.write_behind.notified().await;
+        }
+
+        match cedrus.write_behind_flush().await {
+            Ok(_) => retry = WRITE_BEHIND_RETRY_MIN,
+            Err(e) => {
+                tracing::warn!(
+                    "Write-behind flush failed, {} writes pending, retrying in {}s: {:?}",
+                    cedrus.write_behind.len(),
+                    retry,
+                    e
+                );
+                tokio::time::sleep(std::time::Duration::from_secs(retry)).await;
+                retry = (retry * 2).min(WRITE_BEHIND_RETRY_MAX);
+            }
+        }
+    }
+}
+
+async fn admin_api_key() -> String {
     let admin_api_key = match std::env::var(CEDRUS_ADMIN_API_KEY_ENV) {
         Ok(key) => key,
         Err(_) => panic!("Environment variable {} not set", CEDRUS_ADMIN_API_KEY_ENV),
     };
+    match secrets::resolve(&admin_api_key).await {
+        Ok(key) => key,
+        Err(e) => panic!("Failed to resolve {}: {}", CEDRUS_ADMIN_API_KEY_ENV, e),
+    }
+}
+
+// Resolves the secret references again, rotating the admin API key in place. Other
+// secrets are only read at startup, a change of them is reported.
+async fn secret_refresh_loop(
+    cedrus: &Cedrus,
+    raw_config: serde_json::Value,
+    mut resolved_config: serde_json::Value,
+    interval: u64,
+) {
+    let mut admin_api_key = admin_api_key().await;
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(interval)).await;
 
-    let db = match database_factory(&config.db).await {
+        if let Ok(key) = std::env::var(CEDRUS_ADMIN_API_KEY_ENV) {
+            match secrets::resolve(&key).await {
+                Ok(key) if key != admin_api_key => {
+                    match cedrus.admin_api_key_rotate(key.clone()).await {
+                        Ok(_) => {
+                            tracing::info!("Admin API key rotated");
+                            admin_api_key = key;
+                        }
+                        Err(e) => tracing::warn!("Failed to rotate admin API key: {:?}", e),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to resolve {}: {}", CEDRUS_ADMIN_API_KEY_ENV, e),
+            }
+        }
+
+        let mut config = raw_config.clone();
+        match secrets::resolve_value(&mut config).await {
+            Ok(_) if config != resolved_config => {
+                tracing::warn!("Configuration secrets changed, restart to apply them");
+                resolved_config = config;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to resolve config secrets: {}", e),
+        }
+    }
+}
+
+// Connection to a Database, or to its read replica, `None` when it has no read endpoint
+async fn database_connect(
+    conf: &DbConfig,
+    replica: bool,
+) -> Option<Box<dyn Database + Send + Sync>> {
+    let db = match replica {
+        true => read_replica_factory(conf).await,
+        false => database_factory(conf).await.map(Some),
+    };
+    match db {
         Ok(db) => db,
         Err(e) => panic!("Failed to create database connection: {}", e),
+    }
+}
+
+// Database of the configuration, with its regions and attribute encryption. With `replica`,
+// the same reading from the read endpoints, regions without one reading from their primary,
+// `None` when the default Database has no read endpoint.
+async fn database_init(
+    config: &CedrusConfig,
+    keys: Option<Arc<dyn KeyProvider>>,
+    replica: bool,
+) -> Option<Box<dyn Database + Send + Sync>> {
+    let db = database_connect(&config.db, replica).await?;
+    let db: Box<dyn Database + Send + Sync> = match config.regions.is_empty() {
+        true => db,
+        false => {
+            let mut regions = HashMap::new();
+            for (region, conf) in &config.regions {
+                let region_db = match database_connect(conf, replica).await {
+                    Some(region_db) => region_db,
+                    None => database_connect(conf, false).await?,
+                };
+                regions.insert(region.clone(), region_db);
+            }
+            Box::new(RegionalDb::new(db, regions))
+        }
+    };
+    Some(match keys {
+        Some(keys) => Box::new(EncryptedDb::new(db, keys)),
+        None => db,
+    })
+}
+
+// Cache of the configuration, with the Cache of each region. A Cache shared by several
+// nodes must be regional as soon as the Database is, an in-process one never leaving its node.
+async fn cache_init(config: &CedrusConfig) -> Result<Box<dyn Cache + Send + Sync>, String> {
+    let shared = |conf: &CacheConfig| !matches!(conf, CacheConfig::DashMapConfig(_));
+    if let Some(region) = config
+        .regions
+        .keys()
+        .find(|region| shared(&config.cache) && !config.cache_regions.contains_key(region.as_str()))
+    {
+        return Err(format!(
+            "region {} requires a cacheRegions entry with a shared cache",
+            region
+        ));
+    }
+    if let Some(region) = config
+        .cache_regions
+        .keys()
+        .find(|region| !config.regions.contains_key(region.as_str()))
+    {
+        return Err(format!("cache region {} is not one of the regions", region));
+    }
+
+    let cache = cache_factory(&config.cache)
+        .await
+        .map_err(|e| e.to_string())?;
+    if config.cache_regions.is_empty() {
+        return Ok(cache);
+    }
+    let mut regions = HashMap::new();
+    for (region, conf) in &config.cache_regions {
+        let region_cache = cache_factory(conf).await.map_err(|e| e.to_string())?;
+        regions.insert(region.clone(), region_cache);
+    }
+    Ok(Box::new(RegionalCache::new(cache, regions)))
+}
+
+// Lambda deployments keep their state in DynamoDB, the Cache and events living no longer than
+// an instance
+fn lambda_config(config: &mut CedrusConfig) -> Result<(), String> {
+    let dynamodb = |conf: &DbConfig| matches!(conf, DbConfig::DynamoDbConfig(_));
+    if !dynamodb(&config.db) || !config.regions.values().all(dynamodb) {
+        return Err("Lambda deployments require a DynamoDB database".to_string());
+    }
+    if !matches!(config.cache, CacheConfig::DashMapConfig(_)) {
+        tracing::warn!("Lambda deployments use an in-process cache, ignoring the configured one");
+        config.cache = CacheConfig::DashMapConfig(DashMapCacheConfig::default());
+    }
+    if !config.cache_regions.is_empty() {
+        tracing::warn!("Lambda deployments use an in-process cache, ignoring the cache regions");
+        config.cache_regions.clear();
+    }
+    if !matches!(config.pubsub, PubSubConfig::DummyConfig(_)) {
+        tracing::warn!("Lambda deployments reload their projects, ignoring the configured pubsub");
+        config.pubsub = PubSubConfig::DummyConfig(DummyPubSubConfig::default());
+    }
+
+    Ok(())
+}
+
+async fn cedrus_init(config: &CedrusConfig, lazy_load: bool) -> Result<Cedrus, CedrusError> {
+    let admin_api_key = admin_api_key().await;
+
+    let keys: Option<Arc<dyn KeyProvider>> = match &config.server.encryption {
+        Some(EncryptionConfig::Local { keys }) => match LocalKeys::new(keys) {
+            Ok(keys) => Some(Arc::new(keys)),
+            Err(e) => panic!("Failed to load encryption keys: {}", e),
+        },
+        Some(EncryptionConfig::Kms { key_id, region }) => {
+            Some(Arc::new(KmsKeys::new(key_id.clone(), region.clone())))
+        }
+        None => None,
+    };
+    let Some(db) = database_init(config, keys.clone(), false).await else {
+        unreachable!("the primary Database is always connected");
     };
-    let cache = match cache_factory(&config.cache).await {
+    let read_db = database_init(config, keys, true).await;
+    match db.schema_version_load().await {
+        Ok(version) if version < migration::latest_version(db.migrations()) => tracing::warn!(
+            "Database schema version {} is behind {}, run `cedrus migrate`",
+            version,
+            migration::latest_version(db.migrations())
+        ),
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to load database schema version: {}", e),
+    };
+    let cache = match cache_init(config).await {
         Ok(cache) => cache,
         Err(e) => panic!("Failed to create cache connection: {}", e),
     };
@@ -263,14 +1167,79 @@ async fn cedrus_init(config: &CedrusConfig) -> Result<Cedrus, CedrusError> {
         cache,
         pubsub,
         config.server.exclude_policy_annotation.clone(),
+        config.server.compiled_entities.unwrap_or_default(),
+        config.server.read_only.unwrap_or_default(),
     )
     .await;
 
+    if let Some(signing) = &config.server.signing {
+        match HmacKeys::new(signing) {
+            Ok(signing_keys) => cedrus.signing_keys = signing_keys,
+            Err(e) => panic!("Failed to load signing keys: {}", e),
+        };
+    }
+
+    if let Some(bundles) = &config.server.bundles {
+        match BundleKeys::new(bundles) {
+            Ok(bundle_keys) => cedrus.bundle_keys = bundle_keys,
+            Err(e) => panic!("Failed to load bundle keys: {}", e),
+        };
+    }
+    if !cedrus.signing_keys.is_empty() {
+        cedrus.bundle_keys =
+            std::mem::take(&mut cedrus.bundle_keys).with_hmac(cedrus.signing_keys.clone());
+    }
+
+    if let Some(bootstrap) = &config.server.bootstrap {
+        cedrus.bootstrap = bootstrap.clone();
+    }
+    if read_db.is_some() {
+        tracing::info!("Warming up the cache from the Database read replicas");
+    }
+    cedrus.read_db = read_db;
+    cedrus.regions = config.regions.keys().cloned().collect();
+    if let Some(shards) = &config.server.shards {
+        cedrus.shards = ShardOwnership::new(shards);
+        tracing::info!(
+            "Rebuilding the projects of node {} of {}",
+            shards.node_id,
+            shards.nodes.len()
+        );
+    }
+    if let Some(evaluations) = &config.server.project_evaluations {
+        cedrus.evaluation_slots = EvaluationSlots::new(evaluations);
+    }
+    if let Some(notifications) = &config.server.notifications {
+        cedrus.notifications = notifications::start(notifications.clone());
+    }
+    cedrus.token_cache = TokenCache::new(&config.server.token_cache);
+    cedrus.cluster = ClusterMembership::new(&config.server.cluster);
+    cedrus.gitops = config.server.gitops.clone();
+    cedrus.lazy_load = lazy_load;
+    if let Some(anomalies) = &config.server.deny_rate_anomalies {
+        cedrus.deny_rates = DenyRates::new(anomalies, true);
+    }
+
     match cedrus.init_admin_project(config, admin_api_key).await {
         Ok(_) => tracing::info!("Admin project initialized successfully"),
         Err(e) => panic!("Failed to initialize admin project: {:?}", e),
     };
 
+    match cedrus.admin_project_check().await {
+        Ok(_) => tracing::info!("Admin project checked successfully"),
+        Err(CedrusError::IntegrityError(diagnostics)) => {
+            for diagnostic in &diagnostics {
+                tracing::error!("Admin project integrity: {}", diagnostic);
+            }
+            if !config.server.safe_mode.unwrap_or_default() {
+                return Err(CedrusError::IntegrityError(diagnostics));
+            }
+            tracing::warn!("Starting in safe mode with the bootstrap admin project");
+            cedrus.admin_safe_mode = true;
+        }
+        Err(e) => panic!("Failed to check admin project: {:?}", e),
+    };
+
     match cedrus.init_cache().await {
         Ok(_) => tracing::info!("Cache initialized successfully"),
         Err(e) => panic!("Failed to initialize cache: {:?}", e),
@@ -280,10 +1249,92 @@ async fn cedrus_init(config: &CedrusConfig) -> Result<Cedrus, CedrusError> {
         Ok(_) => tracing::info!("Cache loaded successfully"),
         Err(e) => panic!("Failed to load cache: {:?}", e),
     };
+    // Persist what the warm-up compiled right away, later changes wait for the next snapshot
+    cedrus.compiled_entities_snapshot().await;
 
     Ok(cedrus)
 }
 
+async fn serve(addr: &str, tls: Option<&TlsConfig>, app: Router) -> std::io::Result<()> {
+    match tls {
+        Some(tls) => {
+            let listener = TlsListener::bind(addr, tls)
+                .await
+                .map_err(std::io::Error::other)?;
+            axum::serve(listener, app).await
+        }
+        None => axum::serve(tokio::net::TcpListener::bind(addr).await?, app).await,
+    }
+}
+
+fn listener_addr(host: &str, port: u16) -> String {
+    if std::env::var("CEDRUS_IPV6").is_ok() {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+// Wraps project routes with the staleness header, the audit trail, the shard refresh, the read-only guard,
+// authentication restricted to the credentials the listener accepts, the route limits, and request logging
+fn secured(
+    routes: Router<Arc<AppState>>,
+    state: &Arc<AppState>,
+    config: &CedrusConfig,
+    auth_config: &AuthConfig,
+    limits: &Arc<RouteLimits>,
+) -> Router<Arc<AppState>> {
+    routes
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            staleness::header,
+        ))
+        .layer(middleware::from_fn_with_state(state.clone(), audit::record))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            shards::refresh,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            read_only::guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authorize,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(auth_config.clone()),
+            auth::restrict,
+        ))
+        .layer(middleware::from_fn_with_state(
+            limits.clone(),
+            limits::limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(config.server.request_log.clone()),
+            log::log_requests,
+        ))
+}
+
+// Decompresses gzip and zstd request bodies, bounds their decompressed size, and compresses
+// the responses the client accepts encoded
+fn payload<S: Clone + Send + Sync + 'static>(
+    router: Router<S>,
+    config: &ServerConfig,
+) -> Router<S> {
+    let compress_when = SizeAbove::new(config.compression_min_size.unwrap_or(COMPRESSION_MIN_SIZE))
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::SSE);
+
+    router
+        .layer(DefaultBodyLimit::max(
+            config.max_body_size.unwrap_or(MAX_BODY_SIZE),
+        ))
+        .layer(RequestDecompressionLayer::new().no_br().no_deflate())
+        .layer(CompressionLayer::new().compress_when(compress_when))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables from .env file if present
@@ -351,7 +1402,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let args = Args::parse();
 
-    let config: CedrusConfig = if let Some(config_file_name) = args.config {
+    if let Some(command @ (Command::Policy { .. } | Command::Authorize { .. })) = args.command {
+        if let Err(diagnostics) = offline_command(command) {
+            eprintln!(
+                "{}",
+                serde_json::to_string_pretty(&diagnostics).unwrap_or_default()
+            );
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let raw_config: serde_json::Value = if let Some(config_file_name) = args.config {
         let config_file = std::fs::File::open(&config_file_name)
             .unwrap_or_else(|_| panic!("Failed to open config file: {}", config_file_name));
         serde_json::from_reader(config_file).expect("Failed to parse config file")
@@ -369,22 +1431,168 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         panic!("Either the config file or the config url argument must be provided");
     };
 
-    let cedrus = cedrus_init(&config).await?;
+    let mut resolved_config = raw_config.clone();
+    secrets::resolve_value(&mut resolved_config)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to resolve config secrets: {}", e));
+    let mut config: CedrusConfig =
+        serde_json::from_value(resolved_config.clone()).expect("Failed to parse config file");
+
+    #[cfg(feature = "lambda")]
+    let lambda = matches!(args.command, Some(Command::Lambda));
+    #[cfg(not(feature = "lambda"))]
+    let lambda = false;
+    if lambda {
+        lambda_config(&mut config)?;
+    }
+
+    if let Some(Command::Migrate { target, dry_run }) = args.command {
+        let mut db = database_factory(&config.db).await?;
+        // Every regional Database is migrated along with the default one
+        if !config.regions.is_empty() {
+            let mut regions = HashMap::new();
+            for (region, conf) in &config.regions {
+                regions.insert(region.clone(), database_factory(conf).await?);
+            }
+            db = Box::new(RegionalDb::new(db, regions));
+        }
+        let report = migration::migrate(db.as_ref(), target, dry_run).await?;
+        tracing::info!(
+            "Database migrated from version {} to {} (applied: {:?}, dry run: {})",
+            report.from,
+            report.to,
+            report.applied,
+            report.dry_run
+        );
+        return Ok(());
+    }
 
-    let state = AppState::new(cedrus);
+    if let Some(Command::MigrateCache { from }) = args.command {
+        let CacheConfig::ValKeyConfig(cache_config) = &config.cache else {
+            return Err("Cache migration requires a Valkey cache".into());
+        };
+        let cache = ValKeyCache::new(cache_config).await?;
+        let moved = cache.migrate_namespace(from.as_deref()).await?;
+        tracing::info!(
+            "Cache keys migrated from namespace {:?} to {:?} (keys: {})",
+            from,
+            cache_config.namespace,
+            moved
+        );
+        return Ok(());
+    }
+
+    let cedrus = cedrus_init(&config, lambda).await?;
+
+    let mut state = AppState::new(cedrus);
+    if let Some(sampling) = &config.server.sampling {
+        state = state.with_sampler(DecisionSampler::start(sampling.clone()));
+    }
     let shared_state = Arc::new(state);
 
-    let shared = shared_state.clone();
-    tokio::spawn(async move {
-        let ops = [subscribe_closure(&shared.cedrus)];
-        let _ = shared.cedrus.pubsub.subscribe(&ops).await;
-    });
+    #[cfg(feature = "metrics")]
+    register_cache_metrics(shared_state.clone());
+    #[cfg(feature = "metrics")]
+    register_evaluation_metrics(shared_state.clone());
+    #[cfg(feature = "metrics")]
+    register_candidate_metrics(shared_state.clone());
+    #[cfg(feature = "metrics")]
+    register_staleness_metrics(shared_state.clone());
+
+    // Lambda instances are frozen between invocations, and reload their projects instead
+    if !lambda {
+        let shared = shared_state.clone();
+        tokio::spawn(async move {
+            let ops = [subscribe_closure(&shared.cedrus)];
+            let _ = shared.cedrus.pubsub.subscribe(&ops).await;
+        });
+
+        let shared = shared_state.clone();
+        tokio::spawn(async move {
+            authorizer_rebuild_loop(&shared.cedrus).await;
+        });
+
+        let shared = shared_state.clone();
+        tokio::spawn(async move {
+            write_behind_loop(&shared.cedrus).await;
+        });
+
+        if let Some(interval) = config
+            .server
+            .secret_refresh_interval
+            .filter(|interval| *interval > 0)
+        {
+            let shared = shared_state.clone();
+            tokio::spawn(async move {
+                secret_refresh_loop(&shared.cedrus, raw_config, resolved_config, interval).await;
+            });
+        }
+
+        if let Some(interval) = config
+            .server
+            .consistency_check_interval
+            .filter(|interval| *interval > 0)
+        {
+            let shared = shared_state.clone();
+            tokio::spawn(async move {
+                consistency_check_loop(&shared.cedrus, interval).await;
+            });
+        }
+
+        let interval = config
+            .server
+            .entity_expiry_interval
+            .unwrap_or(ENTITY_EXPIRY_INTERVAL);
+        if interval > 0 {
+            let shared = shared_state.clone();
+            tokio::spawn(async move {
+                entity_expiry_loop(&shared.cedrus, interval).await;
+            });
+        }
+
+        tokio::spawn(job_recovery_loop(shared_state.clone()));
+
+        let shared = shared_state.clone();
+        tokio::spawn(async move {
+            gitops_loop(&shared.cedrus).await;
+        });
+
+        let interval = config
+            .server
+            .compiled_entities_interval
+            .unwrap_or(COMPILED_ENTITIES_INTERVAL);
+        if shared_state.cedrus.compiled_entities && interval > 0 {
+            let shared = shared_state.clone();
+            tokio::spawn(async move {
+                compiled_entities_loop(&shared.cedrus, interval).await;
+            });
+        }
+
+        let interval = config.server.cluster.heartbeat_interval;
+        if interval > 0 {
+            let shared = shared_state.clone();
+            tokio::spawn(async move {
+                cluster_heartbeat_loop(&shared.cedrus, interval).await;
+            });
+        }
+    }
 
     let cors = CorsLayer::new()
         .allow_headers(Any)
         .allow_methods(Any)
         .allow_origin(Any);
 
+    let limits = Arc::new(RouteLimits::new(&config.server.limits));
+
+    let mut project_routes = if config.server.data_plane.is_some() && !lambda {
+        projects::management_routes()
+    } else {
+        projects::routes()
+    };
+    if config.server.dev_routes == Some(true) {
+        project_routes = project_routes.merge(projects::dev_routes());
+    }
+
     let app = Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .route("/", get(|| async { "Hello, World!" }))
@@ -392,29 +1600,97 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .layer(CompressionLayer::new())
         .nest(
             "/v1/projects",
-            projects::routes().layer(middleware::from_fn_with_state(
-                shared_state.clone(),
-                auth::authorize,
-            )),
+            payload(
+                secured(
+                    project_routes,
+                    &shared_state,
+                    &config,
+                    &config.server.auth,
+                    &limits,
+                ),
+                &config.server,
+            ),
+        )
+        .nest(
+            "/v1/common-types",
+            payload(
+                secured(
+                    common_types::routes(),
+                    &shared_state,
+                    &config,
+                    &config.server.auth,
+                    &limits,
+                ),
+                &config.server,
+            ),
+        )
+        .nest(
+            "/v1/admin",
+            secured(
+                admin::routes(),
+                &shared_state,
+                &config,
+                &config.server.auth,
+                &limits,
+            ),
         )
         .layer(cors.clone())
-        .layer(CompressionLayer::new())
         .layer(TraceLayer::new_for_http())
-        .with_state(shared_state);
+        .with_state(shared_state.clone());
 
-    let addr = if std::env::var("CEDRUS_IPV6").is_ok() {
-        format!("[{}]:{}", config.server.host, config.server.port)
-    } else {
-        format!("{}:{}", config.server.host, config.server.port)
-    };
+    if let Some(data_plane) = config.server.data_plane.as_ref().filter(|_| !lambda) {
+        let data_app = Router::new()
+            .nest(
+                "/v1/projects",
+                payload(
+                    secured(
+                        projects::data_routes(),
+                        &shared_state,
+                        &config,
+                        &data_plane.auth,
+                        &limits,
+                    ),
+                    &config.server,
+                ),
+            )
+            .layer(cors.clone())
+            .layer(TraceLayer::new_for_http())
+            .with_state(shared_state.clone());
+
+        let addr = listener_addr(&data_plane.host, data_plane.port);
+        tracing::info!("Data plane starting on {}", addr);
+
+        let tls = data_plane.tls.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve(&addr, tls.as_ref(), data_app).await {
+                tracing::error!("Data plane stopped: {}", e);
+            }
+        });
+    }
+
+    #[cfg(feature = "lambda")]
+    if lambda {
+        let app = app.layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            cedrus::routes::reload::reload,
+        ));
+        tracing::info!("Lambda function starting");
+        lambda_http::run(app)
+            .await
+            .map_err(|e| -> Box<dyn std::error::Error> { e })?;
+        return Ok(());
+    }
+
+    let addr = listener_addr(&config.server.host, config.server.port);
 
     tracing::info!("Server starting on {}", addr);
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    serve(&addr, config.server.tls.as_ref(), app).await?;
 
     tracing::info!("Server stopped");
 
+    let drained = write_behind_drain(&shared_state.cedrus).await;
+
     // Gracefully shut down the tracer provider, flushing remaining spans
     #[cfg(feature = "trace")]
     tracer_provider.shutdown()?;
@@ -423,7 +1699,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(feature = "metrics")]
     metrics_provider.shutdown()?;
 
-    Ok(())
+    drained
 }
 
 #[cfg(test)]
@@ -0,0 +1,114 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use aws_sdk_kms::{Client, error::DisplayErrorContext, primitives::Blob, types::DataKeySpec};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use cedrus_core::{CedrusError, core::crypto::KeyProvider};
+
+fn kms_error(message: impl std::fmt::Display) -> CedrusError {
+    CedrusError::EncryptionError(format!("kms: {}", message))
+}
+
+/// Envelope encryption with AWS KMS: a data key generated on first use encrypts the values
+/// of this node, and its KMS ciphertext is the key id stored with them. Data keys are
+/// decrypted by KMS once per node and kept in memory.
+pub struct KmsKeys {
+    key_id: String,
+    region: Option<String>,
+    /// Built once from the environment, on the first call
+    client: tokio::sync::OnceCell<Client>,
+    data_key: tokio::sync::OnceCell<(String, Vec<u8>)>,
+    keys: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl KmsKeys {
+    pub fn new(key_id: String, region: Option<String>) -> Self {
+        Self {
+            key_id,
+            region,
+            client: tokio::sync::OnceCell::new(),
+            data_key: tokio::sync::OnceCell::new(),
+            keys: RwLock::new(HashMap::new()),
+        }
+    }
+
+    async fn client(&self) -> &Client {
+        self.client
+            .get_or_init(|| async {
+                let mut loader = aws_config::from_env();
+                if let Some(region) = &self.region {
+                    loader = loader.region(aws_config::Region::new(region.clone()));
+                }
+                Client::new(&loader.load().await)
+            })
+            .await
+    }
+
+    fn missing(name: &str) -> CedrusError {
+        kms_error(format!("missing {}", name))
+    }
+}
+
+#[async_trait::async_trait]
+impl KeyProvider for KmsKeys {
+    async fn encryption_key(&self) -> Result<(String, Vec<u8>), CedrusError> {
+        let data_key = self
+            .data_key
+            .get_or_try_init(|| async {
+                let response = self
+                    .client()
+                    .await
+                    .generate_data_key()
+                    .key_id(&self.key_id)
+                    .key_spec(DataKeySpec::Aes256)
+                    .send()
+                    .await
+                    .map_err(|e| kms_error(DisplayErrorContext(e)))?;
+                let key_id = BASE64_STANDARD.encode(
+                    response
+                        .ciphertext_blob()
+                        .ok_or_else(|| Self::missing("CiphertextBlob"))?,
+                );
+                let key = response
+                    .plaintext()
+                    .ok_or_else(|| Self::missing("Plaintext"))?
+                    .as_ref()
+                    .to_vec();
+                if let Ok(mut keys) = self.keys.write() {
+                    keys.insert(key_id.clone(), key.clone());
+                }
+                Ok::<_, CedrusError>((key_id, key))
+            })
+            .await?;
+        Ok(data_key.clone())
+    }
+
+    async fn decryption_key(&self, key_id: &str) -> Result<Vec<u8>, CedrusError> {
+        if let Some(key) = self
+            .keys
+            .read()
+            .ok()
+            .and_then(|keys| keys.get(key_id).cloned())
+        {
+            return Ok(key);
+        }
+
+        let ciphertext = BASE64_STANDARD.decode(key_id).map_err(kms_error)?;
+        let response = self
+            .client()
+            .await
+            .decrypt()
+            .ciphertext_blob(Blob::new(ciphertext))
+            .send()
+            .await
+            .map_err(|e| kms_error(DisplayErrorContext(e)))?;
+        let key = response
+            .plaintext()
+            .ok_or_else(|| Self::missing("Plaintext"))?
+            .as_ref()
+            .to_vec();
+        if let Ok(mut keys) = self.keys.write() {
+            keys.insert(key_id.to_string(), key.clone());
+        }
+        Ok(key)
+    }
+}
@@ -1,14 +1,21 @@
 #![doc = include_str!("../README.md")]
+#![allow(clippy::result_large_err)]
 
-use std::error::Error;
+use std::{collections::HashMap, error::Error};
 
 use axum::{
     extract::{FromRequest, rejection::JsonRejection},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
-use cedrus_cedar::EntityUid;
-use cedrus_core::{Query, Selector, core::cedrus::Cedrus};
+use cedrus_cedar::{EntityUid, PolicyEffect};
+use cedrus_core::{
+    Query, Selector, Sort, SortOrder,
+    core::{
+        cedrus::Cedrus, diff::DiffFormat, dry_run, dry_run::DryRunReport,
+        references::EntityReferences, sdk::SdkLang,
+    },
+};
 use jsonwebtoken::TokenData;
 use quick_cache::sync::Cache;
 use serde::{Deserialize, Serialize};
@@ -16,6 +23,8 @@ use serde_json::Value;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use crate::sampling::DecisionSampler;
+
 pub const DEFAULT_LIMIT: usize = 1000;
 pub const MAX_TOKENS: usize = 1_000_000;
 
@@ -42,6 +51,7 @@ pub struct AuthData {
 pub struct AppState {
     pub cedrus: Cedrus,
     pub tokens: Cache<String, AuthData>,
+    pub sampler: Option<DecisionSampler>,
 }
 
 impl AppState {
@@ -49,16 +59,30 @@ impl AppState {
         Self {
             cedrus,
             tokens: Cache::new(MAX_TOKENS),
+            sampler: None,
         }
     }
+
+    /// Samples the authorization decisions to an analytics sink.
+    pub fn with_sampler(mut self, sampler: DecisionSampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
 }
 
 pub enum CedrusActions {
     GetProjects,
     PostProject,
+    GetCommonTypes,
+    PutCommonTypes,
+    DeleteCommonTypes,
     GetProject,
     PutProject,
     DeleteProject,
+    GetProjectStats,
+    GetProjectConsistency,
+    PostProjectConsistency,
+    PostProjectResync,
     GetProjectIdentitySource,
     PutProjectIdentitySource,
     DeleteProjectIdentitySource,
@@ -66,6 +90,17 @@ pub enum CedrusActions {
     PostProjectApiKey,
     PutProjectApiKey,
     DeleteProjectApiKey,
+    GetProjectRoles,
+    PostProjectRoles,
+    DeleteProjectRoles,
+    PostProjectRolePrincipals,
+    DeleteProjectRolePrincipals,
+    GetProjectDelegations,
+    PostProjectDelegations,
+    DeleteProjectDelegations,
+    GetProjectJobs,
+    PostProjectJobs,
+    GetProjectAudit,
     GetProjectSchema,
     PutProjectSchema,
     DeleteProjectSchema,
@@ -82,6 +117,9 @@ pub enum CedrusActions {
     GetProjectTemplateLinks,
     DeleteProjectTemplateLinks,
     PostProjectIsAuthorized,
+    PostProjectReplay,
+    PostProjectBenchmark,
+    GetCluster,
 }
 
 impl CedrusActions {
@@ -93,6 +131,15 @@ impl CedrusActions {
             CedrusActions::PostProject => {
                 EntityUid::new("Action".to_string(), "postProject".to_string())
             }
+            CedrusActions::GetCommonTypes => {
+                EntityUid::new("Action".to_string(), "getCommonTypes".to_string())
+            }
+            CedrusActions::PutCommonTypes => {
+                EntityUid::new("Action".to_string(), "putCommonTypes".to_string())
+            }
+            CedrusActions::DeleteCommonTypes => {
+                EntityUid::new("Action".to_string(), "deleteCommonTypes".to_string())
+            }
             CedrusActions::GetProject => {
                 EntityUid::new("Action".to_string(), "getProject".to_string())
             }
@@ -102,6 +149,18 @@ impl CedrusActions {
             CedrusActions::DeleteProject => {
                 EntityUid::new("Action".to_string(), "deleteProject".to_string())
             }
+            CedrusActions::GetProjectStats => {
+                EntityUid::new("Action".to_string(), "getProjectStats".to_string())
+            }
+            CedrusActions::GetProjectConsistency => {
+                EntityUid::new("Action".to_string(), "getProjectConsistency".to_string())
+            }
+            CedrusActions::PostProjectConsistency => {
+                EntityUid::new("Action".to_string(), "postProjectConsistency".to_string())
+            }
+            CedrusActions::PostProjectResync => {
+                EntityUid::new("Action".to_string(), "postProjectResync".to_string())
+            }
             CedrusActions::GetProjectIdentitySource => {
                 EntityUid::new("Action".to_string(), "getProjectSchema".to_string())
             }
@@ -123,6 +182,41 @@ impl CedrusActions {
             CedrusActions::DeleteProjectApiKey => {
                 EntityUid::new("Action".to_string(), "deleteProjectApiKey".to_string())
             }
+            CedrusActions::GetProjectRoles => {
+                EntityUid::new("Action".to_string(), "getProjectRoles".to_string())
+            }
+            CedrusActions::PostProjectRoles => {
+                EntityUid::new("Action".to_string(), "postProjectRoles".to_string())
+            }
+            CedrusActions::DeleteProjectRoles => {
+                EntityUid::new("Action".to_string(), "deleteProjectRoles".to_string())
+            }
+            CedrusActions::PostProjectRolePrincipals => EntityUid::new(
+                "Action".to_string(),
+                "postProjectRolePrincipals".to_string(),
+            ),
+            CedrusActions::DeleteProjectRolePrincipals => EntityUid::new(
+                "Action".to_string(),
+                "deleteProjectRolePrincipals".to_string(),
+            ),
+            CedrusActions::GetProjectDelegations => {
+                EntityUid::new("Action".to_string(), "getProjectDelegations".to_string())
+            }
+            CedrusActions::PostProjectDelegations => {
+                EntityUid::new("Action".to_string(), "postProjectDelegations".to_string())
+            }
+            CedrusActions::DeleteProjectDelegations => {
+                EntityUid::new("Action".to_string(), "deleteProjectDelegations".to_string())
+            }
+            CedrusActions::GetProjectJobs => {
+                EntityUid::new("Action".to_string(), "getProjectJobs".to_string())
+            }
+            CedrusActions::PostProjectJobs => {
+                EntityUid::new("Action".to_string(), "postProjectJobs".to_string())
+            }
+            CedrusActions::GetProjectAudit => {
+                EntityUid::new("Action".to_string(), "getProjectAudit".to_string())
+            }
             CedrusActions::GetProjectSchema => {
                 EntityUid::new("Action".to_string(), "getProjectSchema".to_string())
             }
@@ -172,6 +266,15 @@ impl CedrusActions {
             CedrusActions::PostProjectIsAuthorized => {
                 EntityUid::new("Action".to_string(), "postProjectIsAuthorized".to_string())
             }
+            CedrusActions::PostProjectReplay => {
+                EntityUid::new("Action".to_string(), "postProjectReplay".to_string())
+            }
+            CedrusActions::PostProjectBenchmark => {
+                EntityUid::new("Action".to_string(), "postProjectBenchmark".to_string())
+            }
+            CedrusActions::GetCluster => {
+                EntityUid::new("Action".to_string(), "getCluster".to_string())
+            }
         }
     }
 }
@@ -206,6 +309,85 @@ impl CedrusEntities {
     }
 }
 
+/// Common bundles of management actions that can be delegated on a project.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum AdminCapability {
+    #[default]
+    Viewer,
+    PolicyEditor,
+    EntityEditor,
+    SchemaEditor,
+    KeyManager,
+}
+
+impl AdminCapability {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            AdminCapability::Viewer => "viewer",
+            AdminCapability::PolicyEditor => "policyEditor",
+            AdminCapability::EntityEditor => "entityEditor",
+            AdminCapability::SchemaEditor => "schemaEditor",
+            AdminCapability::KeyManager => "keyManager",
+        }
+    }
+
+    pub fn actions(&self) -> Vec<CedrusActions> {
+        match *self {
+            AdminCapability::Viewer => vec![
+                CedrusActions::GetProject,
+                CedrusActions::GetProjectStats,
+                CedrusActions::GetProjectIdentitySource,
+                CedrusActions::GetProjectSchema,
+                CedrusActions::GetProjectEntities,
+                CedrusActions::GetProjectPolicies,
+                CedrusActions::GetProjectTemplates,
+                CedrusActions::GetProjectTemplateLinks,
+            ],
+            AdminCapability::PolicyEditor => vec![
+                CedrusActions::GetProject,
+                CedrusActions::GetProjectPolicies,
+                CedrusActions::PostProjectPolicies,
+                CedrusActions::DeleteProjectPolicies,
+                CedrusActions::GetProjectTemplates,
+                CedrusActions::PostProjectTemplates,
+                CedrusActions::DeleteProjectTemplates,
+                CedrusActions::GetProjectTemplateLinks,
+                CedrusActions::PostProjectTemplateLinks,
+                CedrusActions::DeleteProjectTemplateLinks,
+            ],
+            AdminCapability::EntityEditor => vec![
+                CedrusActions::GetProject,
+                CedrusActions::GetProjectEntities,
+                CedrusActions::PostProjectEntities,
+                CedrusActions::DeleteProjectEntities,
+            ],
+            AdminCapability::SchemaEditor => vec![
+                CedrusActions::GetProject,
+                CedrusActions::GetProjectSchema,
+                CedrusActions::PutProjectSchema,
+                CedrusActions::DeleteProjectSchema,
+            ],
+            AdminCapability::KeyManager => vec![
+                CedrusActions::GetProject,
+                CedrusActions::GetProjectApiKey,
+                CedrusActions::PostProjectApiKey,
+                CedrusActions::PutProjectApiKey,
+                CedrusActions::DeleteProjectApiKey,
+            ],
+        }
+    }
+}
+
+/// Grants, or restricts when `effect` is forbid, a capability to a principal on a project.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Delegation {
+    pub principal: EntityUid,
+    pub capability: AdminCapability,
+    pub effect: PolicyEffect,
+}
+
 // The kinds of errors we can hit in our application.
 #[derive(Debug)]
 pub enum AppError {
@@ -213,7 +395,13 @@ pub enum AppError {
     Unauthorized,        // 401
     Forbidden,           // 403
     NotFound,            // 404
+    RequestTimeout,      // 408
+    PreconditionFailed,  // 412
+    Locked,              // 423
     InternalServerError, // 500
+    ServiceUnavailable,  // 503
+    Overloaded,          // 503
+    EvaluationTimeout,   // 503
 
     JsonRejection(JsonRejection), // 422
     CedrusError(cedrus_core::CedrusError),
@@ -227,9 +415,104 @@ pub enum AppError {
     PolicyToJsonError(cedar_policy::PolicyToJsonError),
     PolicySetError(cedar_policy::PolicySetError),
     ContextJsonError(cedar_policy::ContextJsonError),
+    ContextValidationError(Vec<cedrus_cedar::ContextError>),
+    RequestSchemaError(Vec<cedrus_cedar::RequestError>),
+    EntityLinked(Vec<EntityReferences>),
+    CedarDiagnostics(Vec<CedarDiagnostic>),
     SerdeJsonError(serde_json::Error),
 }
 
+/// Most Cedar errors wrap the interesting message as their source.
+fn error_detail(e: &dyn Error) -> String {
+    e.source()
+        .map_or_else(|| e.to_string(), |source| source.to_string())
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+/// A Cedar parser or validator diagnostic. `line` and `column` are 1-based and only present
+/// when the diagnostic points into a source text.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CedarDiagnostic {
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+}
+
+impl CedarDiagnostic {
+    pub fn error(message: String) -> Self {
+        Self {
+            severity: DiagnosticSeverity::Error,
+            message,
+            line: None,
+            column: None,
+            help: None,
+        }
+    }
+
+    /// Flattens a diagnostic and its related diagnostics, resolving their first label
+    /// against `src`.
+    pub fn collect(
+        diagnostic: &dyn miette::Diagnostic,
+        src: Option<&str>,
+        severity: DiagnosticSeverity,
+    ) -> Vec<Self> {
+        let related: Vec<&dyn miette::Diagnostic> = diagnostic
+            .related()
+            .map(|related| related.collect())
+            .unwrap_or_default();
+        if !related.is_empty() {
+            return related
+                .into_iter()
+                .flat_map(|d| Self::collect(d, src, severity))
+                .collect();
+        }
+
+        let offset = diagnostic
+            .labels()
+            .and_then(|mut labels| labels.next())
+            .map(|label| label.offset());
+        let (line, column) = match (src, offset) {
+            (Some(src), Some(offset)) => {
+                let (line, column) = line_column(src, offset);
+                (Some(line), Some(column))
+            }
+            _ => (None, None),
+        };
+
+        vec![Self {
+            severity,
+            message: diagnostic.to_string(),
+            line,
+            column,
+            help: diagnostic.help().map(|help| help.to_string()),
+        }]
+    }
+}
+
+fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let prefix = src.get(..offset).unwrap_or(src);
+    let line = prefix.matches('\n').count() + 1;
+    let column = prefix
+        .rsplit_once('\n')
+        .map_or(prefix, |(_, last)| last)
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
 // Tell axum how `AppError` should be converted into a response.
 //
 // This is also a convenient place to log errors.
@@ -241,6 +524,14 @@ impl IntoResponse for AppError {
             error: String,   // error code
             message: String, // human readable error message
             detail: String,  // additional details about the error
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            errors: Vec<cedrus_cedar::ContextError>, // offending attributes of a request context
+            #[serde(rename = "requestErrors", skip_serializing_if = "Vec::is_empty")]
+            request_errors: Vec<cedrus_cedar::RequestError>, // action or types outside the schema
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            diagnostics: Vec<CedarDiagnostic>, // parser and validator diagnostics
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            references: Vec<EntityReferences>, // template links naming removed entities
         }
 
         let (status, error_response) = match self {
@@ -272,6 +563,27 @@ impl IntoResponse for AppError {
                     ..Default::default()
                 },
             ),
+            AppError::RequestTimeout => (
+                StatusCode::REQUEST_TIMEOUT,
+                ErrorResponse {
+                    message: "Request Timeout".to_owned(),
+                    ..Default::default()
+                },
+            ),
+            AppError::PreconditionFailed => (
+                StatusCode::PRECONDITION_FAILED,
+                ErrorResponse {
+                    message: "Precondition Failed".to_owned(),
+                    ..Default::default()
+                },
+            ),
+            AppError::Locked => (
+                StatusCode::LOCKED,
+                ErrorResponse {
+                    message: "Project is read-only".to_owned(),
+                    ..Default::default()
+                },
+            ),
             AppError::InternalServerError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorResponse {
@@ -279,6 +591,28 @@ impl IntoResponse for AppError {
                     ..Default::default()
                 },
             ),
+            AppError::ServiceUnavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorResponse {
+                    message: "Server is read-only".to_owned(),
+                    ..Default::default()
+                },
+            ),
+            AppError::Overloaded => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorResponse {
+                    message: "Server is overloaded".to_owned(),
+                    ..Default::default()
+                },
+            ),
+            AppError::EvaluationTimeout => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorResponse {
+                    error: "EvaluationTimeout".to_owned(),
+                    message: "Evaluation timed out".to_owned(),
+                    ..Default::default()
+                },
+            ),
             AppError::JsonRejection(rejection) => {
                 // This error is caused by bad user input so don't log it
                 (
@@ -296,6 +630,13 @@ impl IntoResponse for AppError {
                     cedrus_core::CedrusError::Unauthorized => StatusCode::UNAUTHORIZED,
                     cedrus_core::CedrusError::Forbidden => StatusCode::FORBIDDEN,
                     cedrus_core::CedrusError::BadRequest => StatusCode::BAD_REQUEST,
+                    cedrus_core::CedrusError::Conflict => StatusCode::CONFLICT,
+                    cedrus_core::CedrusError::BundleError(_) => StatusCode::BAD_REQUEST,
+                    cedrus_core::CedrusError::SigningError(_) => StatusCode::BAD_REQUEST,
+                    cedrus_core::CedrusError::GitOpsError(_) => StatusCode::BAD_REQUEST,
+                    cedrus_core::CedrusError::RequestValidationError(_) => StatusCode::BAD_REQUEST,
+                    cedrus_core::CedrusError::EntityUidError(_) => StatusCode::BAD_REQUEST,
+                    cedrus_core::CedrusError::ValidationError(_) => StatusCode::BAD_REQUEST,
                     _ => StatusCode::INTERNAL_SERVER_ERROR,
                 };
 
@@ -303,6 +644,7 @@ impl IntoResponse for AppError {
                     error: "CedrusError".to_string(),
                     message: cedrus_error.to_string(),
                     detail: cedrus_error.to_string(),
+                    ..Default::default()
                 };
 
                 (status, error_response)
@@ -312,7 +654,7 @@ impl IntoResponse for AppError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorResponse {
                     message: "Entities Error".to_owned(),
-                    detail: format!("{}", e.source().unwrap()),
+                    detail: error_detail(&e),
                     ..Default::default()
                 },
             ),
@@ -320,7 +662,8 @@ impl IntoResponse for AppError {
                 StatusCode::BAD_REQUEST,
                 ErrorResponse {
                     message: "Schema Error".to_owned(),
-                    detail: format!("{}", e.source().unwrap()),
+                    detail: error_detail(&e),
+                    diagnostics: CedarDiagnostic::collect(&e, None, DiagnosticSeverity::Error),
                     ..Default::default()
                 },
             ),
@@ -328,7 +671,8 @@ impl IntoResponse for AppError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorResponse {
                     message: "CedarSchema Error".to_owned(),
-                    detail: format!("{}", e.source().unwrap()),
+                    detail: error_detail(&e),
+                    diagnostics: CedarDiagnostic::collect(&e, None, DiagnosticSeverity::Error),
                     ..Default::default()
                 },
             ),
@@ -336,7 +680,8 @@ impl IntoResponse for AppError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorResponse {
                     message: "ToCedarSchema Error".to_owned(),
-                    detail: format!("{}", e.source().unwrap()),
+                    detail: error_detail(&e),
+                    diagnostics: CedarDiagnostic::collect(&e, None, DiagnosticSeverity::Error),
                     ..Default::default()
                 },
             ),
@@ -355,7 +700,7 @@ impl IntoResponse for AppError {
                 StatusCode::BAD_REQUEST,
                 ErrorResponse {
                     message: "PolicyFromJson Error".to_owned(),
-                    detail: format!("{}", e.source().unwrap()),
+                    detail: error_detail(&e),
                     ..Default::default()
                 },
             ),
@@ -374,7 +719,7 @@ impl IntoResponse for AppError {
                 StatusCode::BAD_REQUEST,
                 ErrorResponse {
                     message: "PolicySet Error".to_owned(),
-                    detail: format!("{}", e.source().unwrap()),
+                    detail: error_detail(&e),
                     ..Default::default()
                 },
             ),
@@ -382,7 +727,59 @@ impl IntoResponse for AppError {
                 StatusCode::BAD_REQUEST,
                 ErrorResponse {
                     message: "ContextJson Error".to_owned(),
-                    detail: format!("{}", e.source().unwrap()),
+                    detail: e.to_string(),
+                    ..Default::default()
+                },
+            ),
+            AppError::ContextValidationError(errors) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    message: "Context Validation Error".to_owned(),
+                    detail: errors
+                        .iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<String>>()
+                        .join("; "),
+                    errors,
+                    ..Default::default()
+                },
+            ),
+            AppError::RequestSchemaError(errors) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    message: "Request Validation Error".to_owned(),
+                    detail: errors
+                        .iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<String>>()
+                        .join("; "),
+                    request_errors: errors,
+                    ..Default::default()
+                },
+            ),
+            AppError::EntityLinked(references) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    message: "Entities named by template links".to_owned(),
+                    detail: references
+                        .iter()
+                        .map(|r| format!("{}: {}", r.entity, r.template_links.len()))
+                        .collect::<Vec<String>>()
+                        .join("; "),
+                    references,
+                    ..Default::default()
+                },
+            ),
+            AppError::CedarDiagnostics(diagnostics) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    message: "Cedar Error".to_owned(),
+                    detail: diagnostics
+                        .iter()
+                        .map(|d| d.message.clone())
+                        .collect::<Vec<String>>()
+                        .join("; "),
+                    diagnostics,
                     ..Default::default()
                 },
             ),
@@ -390,7 +787,7 @@ impl IntoResponse for AppError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorResponse {
                     message: "SerdeJson Error".to_owned(),
-                    detail: format!("{}", e.source().unwrap()),
+                    detail: error_detail(&e),
                     ..Default::default()
                 },
             ),
@@ -408,7 +805,19 @@ impl From<JsonRejection> for AppError {
 
 impl From<cedrus_core::CedrusError> for AppError {
     fn from(error: cedrus_core::CedrusError) -> Self {
-        Self::CedrusError(error)
+        match error {
+            cedrus_core::CedrusError::ContextJsonError(e) => Self::ContextJsonError(e),
+            cedrus_core::CedrusError::ContextValidationError(errors) => {
+                Self::ContextValidationError(errors)
+            }
+            cedrus_core::CedrusError::RequestSchemaError(errors) => {
+                Self::RequestSchemaError(errors)
+            }
+            cedrus_core::CedrusError::EntityLinked(references) => Self::EntityLinked(references),
+            cedrus_core::CedrusError::EvaluationTimeout => Self::EvaluationTimeout,
+            cedrus_core::CedrusError::Overloaded => Self::Overloaded,
+            error => Self::CedrusError(error),
+        }
     }
 }
 
@@ -462,21 +871,47 @@ pub fn option_uuid_eq(a: Option<Uuid>, b: Option<Uuid>) -> bool {
     }
 }
 
+// A selector as JSON text in a query string, or as is in a JSON body
+fn deserialize_selector<'de, D>(deserializer: D) -> Result<Option<Selector>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SelectorParam {
+        Text(String),
+        Selector(Selector),
+    }
+
+    match Option::<SelectorParam>::deserialize(deserializer)? {
+        Some(SelectorParam::Text(text)) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        Some(SelectorParam::Selector(selector)) => Ok(Some(selector)),
+        None => Ok(None),
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
 #[serde(rename_all = "camelCase")]
 #[into_params(parameter_in = Query)]
 pub struct QueryParams {
-    #[param(style = DeepObject, explode, inline, nullable)]
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// JSON selector, keys such as `tags.env` reaching into nested fields
+    #[param(value_type = Option<String>, nullable, example = r#"{"tags.env":{"$eq":"prod"}}"#)]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_selector",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub selector: Option<Selector>,
-    /*
-    #[param(style = DeepObject, explode, inline, nullable)]
+    /// Comma-separated fields to sort by, prefixed by `-` for descending order
+    #[param(nullable, example = "-createdAt")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sort: Option<Vec<Sort>>,
-    #[param(nullable)]
+    pub sort: Option<String>,
+    /// Comma-separated fields to return, `id` or `uid` is always included
+    #[param(nullable, example = "name,owner")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub fields: Option<Vec<String>>,
-     */
+    pub fields: Option<String>,
     #[param(nullable)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_key: Option<String>,
@@ -497,10 +932,18 @@ impl From<Query> for QueryParams {
     fn from(query: Query) -> Self {
         Self {
             selector: query.selector,
-            /*
-            sort: query.sort.len().ne(&0).then(|| query.sort),
-            fields: query.fields.len().ne(&0).then(|| query.fields),
-            */
+            sort: (!query.sort.is_empty()).then(|| {
+                query
+                    .sort
+                    .iter()
+                    .map(|s| match s.order {
+                        SortOrder::Asc => s.field.clone(),
+                        SortOrder::Desc => format!("-{}", s.field),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+            fields: (!query.fields.is_empty()).then(|| query.fields.join(",")),
             start_key: query.start_key,
             limit: query.limit,
             /*
@@ -515,14 +958,195 @@ impl From<QueryParams> for Query {
     fn from(val: QueryParams) -> Self {
         Query {
             selector: val.selector,
-            sort: Vec::new(),   // self.sort.unwrap_or_default(),
-            fields: Vec::new(), // self.fields.unwrap_or_default(),
+            sort: val
+                .sort
+                .as_deref()
+                .map(Sort::parse_list)
+                .unwrap_or_default(),
+            fields: val
+                .fields
+                .map(|fields| {
+                    fields
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|field| !field.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
             start_key: val.start_key,
             limit: val.limit,
             skip: None,  // self.skip.unwrap_or(0),
             index: None, //self.index,
+            annotations: HashMap::new(),
         }
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadOnly {
+    pub read_only: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct ForceParams {
+    /// Also remove whatever still references the deleted items
+    #[param(nullable)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct DryRunParams {
+    /// Validate the change and return what it would change, without applying it
+    #[param(nullable)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<bool>,
+}
+
+impl DryRunParams {
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.unwrap_or(false)
+    }
+
+    /// Applies a mutation, or runs it with its persistence disabled when asked to, reporting
+    /// what it would change.
+    pub async fn mutate<T>(
+        &self,
+        mutation: impl Future<Output = Result<T, cedrus_core::CedrusError>>,
+    ) -> Result<Mutation<T>, cedrus_core::CedrusError> {
+        Ok(match self.is_dry_run() {
+            true => Mutation::DryRun(Box::new(dry_run::run(mutation).await?)),
+            false => Mutation::Applied(mutation.await?),
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct WarningsParams {
+    /// Wrap the response as `{ schema, warnings }` to include the parser warnings
+    #[param(nullable)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<bool>,
+}
+
+impl WarningsParams {
+    pub fn with_warnings(&self) -> bool {
+        self.warnings.unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct AsOfParams {
+    /// Read every item as it was at this time, from the history of the project, instead of
+    /// a page of the current ones
+    #[param(nullable, example = "2024-05-01T00:00:00Z")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct SdkParams {
+    /// Language of the generated helpers
+    #[serde(default)]
+    pub lang: SdkLang,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct DiffParams {
+    /// Text the policies are diffed in, `json` by default
+    #[serde(default)]
+    pub format: DiffFormat,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct RelationParams {
+    /// Entity whose relations to list, e.g. `App::Document::d1`
+    #[param(nullable)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object: Option<String>,
+    /// Entity the listed relations are to, e.g. `App::User::alice` for what it owns
+    #[param(nullable)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    /// Name of the relation, e.g. `owner`, all of them when unset
+    #[param(nullable)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relation: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct TemplateLinkBatchParams {
+    /// Template to link, for a CSV body
+    #[param(nullable)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+    /// Entity bound to `?resource` in every link, e.g. `App::Folder::docs`, for a CSV body
+    #[param(nullable)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+}
+
+/// Response of a mutation, or the changes it would make when run dry.
+pub enum Mutation<T> {
+    Applied(T),
+    DryRun(Box<DryRunReport>),
+}
+
+impl<T> Mutation<T> {
+    /// Maps the response of an applied mutation, leaving a dry run report as it is.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Mutation<U> {
+        match self {
+            Mutation::Applied(response) => Mutation::Applied(f(response)),
+            Mutation::DryRun(report) => Mutation::DryRun(report),
+        }
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for Mutation<T> {
+    fn into_response(self) -> Response {
+        match self {
+            Mutation::Applied(response) => response.into_response(),
+            Mutation::DryRun(report) => AppJson(*report).into_response(),
+        }
+    }
+}
+
+pub const ANNOTATION_PARAM_PREFIX: &str = "annotation.";
+
+/// Extracts `annotation.<key>=<value>` query parameters as annotation filters.
+pub fn annotation_params(params: &HashMap<String, String>) -> HashMap<String, String> {
+    params
+        .iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix(ANNOTATION_PARAM_PREFIX)
+                .filter(|key| !key.is_empty())
+                .map(|key| (key.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+pub mod kms;
+pub mod notifications;
+pub mod offline;
 pub mod routes;
+pub mod sampling;
+pub mod secrets;
+pub mod tls;
@@ -0,0 +1,279 @@
+use std::sync::Arc;
+
+use cedrus_core::core::{
+    NotificationsConfig, SmtpConfig, SmtpTls,
+    notification::{Alert, Notifications, is_email_valid, is_webhook_allowed},
+};
+use rustls::{ClientConfig, RootCertStore, crypto::aws_lc_rs, pki_types::ServerName};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    sync::mpsc,
+};
+use tokio_rustls::TlsConnector;
+
+const NOTIFICATIONS_TARGET: &str = "cedrus::notifications";
+
+/// Starts the delivery of the alerts to their channels and returns the notifications
+/// feeding it.
+pub fn start(config: NotificationsConfig) -> Notifications {
+    let (sender, receiver) = mpsc::channel(config.buffer_size.max(1));
+    let notifications = Notifications::new(&config, sender);
+    tokio::spawn(deliver(config, receiver));
+
+    notifications
+}
+
+// Alerts go out one at a time, a slow channel holding back the next ones
+async fn deliver(config: NotificationsConfig, mut receiver: mpsc::Receiver<Alert>) {
+    // A webhook redirecting elsewhere is not followed
+    let client = match reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!(target: NOTIFICATIONS_TARGET, "Notifications disabled: {}", e);
+            return;
+        }
+    };
+    while let Some(alert) = receiver.recv().await {
+        let project = alert.project.clone().unwrap_or_default();
+        let project_alerted = alert.is_sent_to(&project.alerts);
+        let alerted = alert.is_sent_to(&config.alerts);
+
+        let mut webhooks = Vec::new();
+        if let Some(slack) = config.slack.as_ref().filter(|_| alerted) {
+            webhooks.push(&slack.webhook_url);
+        }
+        // Checked again, the allowed hosts may have changed since the project was set
+        if let Some(url) = project
+            .slack_webhook_url
+            .as_ref()
+            .filter(|url| project_alerted && is_webhook_allowed(url, &config.webhook_hosts))
+        {
+            webhooks.push(url);
+        }
+        for url in webhooks {
+            if let Err(e) = post_slack(&client, url, &alert).await {
+                tracing::warn!(target: NOTIFICATIONS_TARGET, "Slack alert lost: {}", e);
+            }
+        }
+
+        if let Some(smtp) = &config.smtp {
+            let mut to: Vec<&String> = Vec::new();
+            if alerted {
+                to.extend(&smtp.to);
+            }
+            if project_alerted {
+                to.extend(&project.emails);
+            }
+            // Addresses stored before they were checked strictly are left out
+            to.retain(|address| {
+                let valid = is_email_valid(address);
+                if !valid {
+                    tracing::warn!(target: NOTIFICATIONS_TARGET, "Invalid email address skipped: {:?}", address);
+                }
+                valid
+            });
+            to.sort();
+            to.dedup();
+            if !to.is_empty()
+                && let Err(e) = send_email(smtp, &to, &alert).await
+            {
+                tracing::warn!(target: NOTIFICATIONS_TARGET, "Email alert lost: {}", e);
+            }
+        }
+    }
+}
+
+fn subject(alert: &Alert) -> String {
+    format!(
+        "[Cedrus] {} on project {}",
+        alert.kind.as_str(),
+        alert.project_id
+    )
+}
+
+async fn post_slack(client: &reqwest::Client, url: &str, alert: &Alert) -> Result<(), String> {
+    let text = format!("*{}*\n{}", subject(alert), alert.message);
+    client
+        .post(url)
+        .json(&serde_json::json!({ "text": text }))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// SMTP session over a plain or TLS stream, reading the multiline replies of the server
+struct Smtp<S> {
+    stream: BufStream<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Smtp<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream: BufStream::new(stream),
+        }
+    }
+
+    async fn reply(&mut self, expected: u16) -> Result<(), String> {
+        loop {
+            let mut line = String::new();
+            if self
+                .stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| e.to_string())?
+                == 0
+            {
+                return Err("connection closed".to_string());
+            }
+            let code = line.get(..3).and_then(|code| code.parse::<u16>().ok());
+            if code != Some(expected) {
+                return Err(format!("unexpected reply: {}", line.trim_end()));
+            }
+            // `250-` continues a multiline reply, `250 ` ends it
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn command(&mut self, command: &str, expected: u16) -> Result<(), String> {
+        self.stream
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await
+            .and(self.stream.flush().await)
+            .map_err(|e| e.to_string())?;
+        self.reply(expected).await
+    }
+
+    async fn send(
+        mut self,
+        config: &SmtpConfig,
+        to: &[&String],
+        message: &str,
+    ) -> Result<(), String> {
+        // Addresses go into the commands as is, a line break would start another command
+        if let Some(address) = std::iter::once(&&config.from)
+            .chain(to)
+            .find(|address| !is_email_valid(address))
+        {
+            return Err(format!("invalid address: {address:?}"));
+        }
+        if let Some(username) = &config.username {
+            use base64::{Engine, prelude::BASE64_STANDARD};
+
+            let password = config.password.as_deref().unwrap_or_default();
+            let credentials = BASE64_STANDARD.encode(format!("\0{username}\0{password}"));
+            self.command(&format!("AUTH PLAIN {credentials}"), 235)
+                .await?;
+        }
+        self.command(&format!("MAIL FROM:<{}>", config.from), 250)
+            .await?;
+        for address in to {
+            self.command(&format!("RCPT TO:<{address}>"), 250).await?;
+        }
+        self.command("DATA", 354).await?;
+        self.command(&format!("{message}\r\n."), 250).await?;
+        self.command("QUIT", 221).await
+    }
+}
+
+async fn tls_connect(
+    host: &str,
+    stream: TcpStream,
+) -> Result<impl AsyncRead + AsyncWrite + Unpin, String> {
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+    let config = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(host.to_string()).map_err(|e| e.to_string())?;
+
+    TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Message with dot-stuffed lines, as DATA ends on a line holding a single dot
+fn email(config: &SmtpConfig, to: &[&String], alert: &Alert) -> Result<String, String> {
+    let to = to
+        .iter()
+        .map(|address| address.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let body = format!(
+        "{}\r\n\r\nProject: {}\r\nNode: {}\r\nAt: {}\r\n",
+        alert.message,
+        alert.project_id,
+        alert.node_id,
+        alert.at.to_rfc3339()
+    );
+    let body = body
+        .lines()
+        .map(|line| match line.starts_with('.') {
+            true => format!(".{line}"),
+            false => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    let subject = subject(alert);
+
+    // A line break in a header value would add headers of its own
+    if [config.from.as_str(), to.as_str(), subject.as_str()]
+        .iter()
+        .any(|value| value.contains(['\r', '\n']))
+    {
+        return Err("line break in a header".to_string());
+    }
+
+    Ok(format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+        config.from,
+        to,
+        subject,
+        alert.at.to_rfc2822(),
+        body
+    ))
+}
+
+async fn send_email(config: &SmtpConfig, to: &[&String], alert: &Alert) -> Result<(), String> {
+    let message = email(config, to, alert)?;
+    let stream = TcpStream::connect((config.host.as_str(), config.port))
+        .await
+        .map_err(|e| e.to_string())?;
+    let ehlo = "EHLO cedrus";
+
+    match config.tls {
+        SmtpTls::Implicit => {
+            let mut smtp = Smtp::new(tls_connect(&config.host, stream).await?);
+            smtp.reply(220).await?;
+            smtp.command(ehlo, 250).await?;
+            smtp.send(config, to, &message).await
+        }
+        SmtpTls::StartTls => {
+            let mut smtp = Smtp::new(stream);
+            smtp.reply(220).await?;
+            smtp.command(ehlo, 250).await?;
+            smtp.command("STARTTLS", 220).await?;
+
+            let mut smtp = Smtp::new(tls_connect(&config.host, smtp.stream.into_inner()).await?);
+            smtp.command(ehlo, 250).await?;
+            smtp.send(config, to, &message).await
+        }
+        SmtpTls::None => {
+            let mut smtp = Smtp::new(stream);
+            smtp.reply(220).await?;
+            smtp.command(ehlo, 250).await?;
+            smtp.send(config, to, &message).await
+        }
+    }
+}
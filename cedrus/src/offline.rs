@@ -0,0 +1,206 @@
+use std::path::Path;
+
+use cedrus_cedar::{Entity, PolicySet, Request, Response, Schema};
+use cedrus_core::core::cedrus::{Cedrus, canonical_json};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    CedarDiagnostic, DiagnosticSeverity,
+    routes::projects::{CedarSchema, policy_set_from_cedar},
+};
+
+/// Diagnostics of a local file or of the policies it holds, in the form the server reports.
+pub type Diagnostics = Vec<CedarDiagnostic>;
+
+fn errors(e: &dyn miette::Diagnostic) -> Diagnostics {
+    CedarDiagnostic::collect(e, None, DiagnosticSeverity::Error)
+}
+
+fn error(message: String) -> Diagnostics {
+    vec![CedarDiagnostic::error(message)]
+}
+
+fn read(path: &Path) -> Result<String, Diagnostics> {
+    std::fs::read_to_string(path).map_err(|e| error(format!("{}: {}", path.display(), e)))
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, Diagnostics> {
+    serde_json::from_str(&read(path)?).map_err(|e| error(format!("{}: {}", path.display(), e)))
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|e| e == extension)
+}
+
+/// Policy set of a `.cedar` file, whose policies and templates are identified by their `@id`
+/// annotation as on `POST /policy-set/cedar`, or of the JSON `/policy-set` takes.
+pub fn load_policy_set(path: &Path) -> Result<PolicySet, Diagnostics> {
+    match has_extension(path, "cedar") {
+        true => policy_set_from_cedar(&read(path)?),
+        false => read_json(path),
+    }
+}
+
+/// Schema of a `.cedarschema` file, or of a JSON schema.
+pub fn load_schema(path: &Path) -> Result<Schema, Diagnostics> {
+    match has_extension(path, "cedarschema") {
+        true => CedarSchema::parse(&read(path)?).map(|cedar| cedar.schema),
+        false => read_json(path),
+    }
+}
+
+/// Entities of a JSON array, as posted to `/entities`.
+pub fn load_entities(path: &Path) -> Result<Vec<Entity>, Diagnostics> {
+    read_json(path)
+}
+
+/// Request of a JSON document, as posted to `/is-authorized`.
+pub fn load_request(path: &Path) -> Result<Request, Diagnostics> {
+    read_json(path)
+}
+
+/// Parser and validator diagnostics of a policy set, validated strictly against `schema`
+/// when given. Empty when the policies are valid.
+pub fn policy_check(policy_set: PolicySet, schema: Option<Schema>) -> Diagnostics {
+    let cedar_policy_set: cedar_policy::PolicySet = match policy_set.try_into() {
+        Ok(cedar_policy_set) => cedar_policy_set,
+        Err(e) => return errors(&e),
+    };
+    let Some(schema) = schema else {
+        return Vec::new();
+    };
+    let cedar_schema: cedar_policy::Schema = match schema.try_into() {
+        Ok(cedar_schema) => cedar_schema,
+        Err(e) => return errors(&e),
+    };
+
+    let result = cedar_policy::Validator::new(cedar_schema)
+        .validate(&cedar_policy_set, cedar_policy::ValidationMode::Strict);
+    let mut diagnostics: Diagnostics = result.validation_errors().flat_map(|e| errors(e)).collect();
+    diagnostics.extend(
+        result
+            .validation_warnings()
+            .flat_map(|w| CedarDiagnostic::collect(w, None, DiagnosticSeverity::Warning)),
+    );
+    diagnostics
+}
+
+/// Policy set in Cedar syntax, policies then templates sorted by id, or in JSON with sorted
+/// keys when `json`. Template links only have a JSON form.
+pub fn policy_format(policy_set: PolicySet, json: bool) -> Result<String, Diagnostics> {
+    if json {
+        let value = serde_json::to_value(&policy_set).map_err(|e| error(e.to_string()))?;
+        return serde_json::to_string_pretty(&canonical_json(value))
+            .map_err(|e| error(e.to_string()));
+    }
+    if !policy_set.template_links.is_empty() {
+        return Err(error(
+            "template links have no Cedar syntax, format them as JSON".to_string(),
+        ));
+    }
+
+    let mut policies: Vec<_> = policy_set.static_policies.iter().collect();
+    policies.sort_by(|a, b| a.0.cmp(b.0));
+    let mut templates: Vec<_> = policy_set.templates.iter().collect();
+    templates.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut cedar = Vec::new();
+    for (id, policy) in policies {
+        cedar.push(
+            policy
+                .to_cedar(id.clone())
+                .map_err(|e| errors(&e))?
+                .to_string(),
+        );
+    }
+    for (id, template) in templates {
+        cedar.push(
+            template
+                .to_cedar(id.clone())
+                .map_err(|e| errors(&e))?
+                .to_string(),
+        );
+    }
+    Ok(cedar.join("\n\n") + "\n")
+}
+
+/// Decision of a request over local policies and entities, as `/is-authorized` answers it.
+/// The schema, when given, validates the entities and the request and types their values.
+pub fn authorize(
+    policy_set: PolicySet,
+    entities: Vec<Entity>,
+    schema: Option<Schema>,
+    request: Request,
+) -> Result<Response, Diagnostics> {
+    let cedar_schema: Option<cedar_policy::Schema> = schema
+        .clone()
+        .map(TryInto::try_into)
+        .transpose()
+        .map_err(|e| errors(&e))?;
+
+    let enum_entities = schema
+        .as_ref()
+        .map(Cedrus::schema_enum_entities)
+        .unwrap_or_default();
+    let decimal_errors: Vec<String> = entities.iter().flat_map(Entity::decimal_errors).collect();
+    if !decimal_errors.is_empty() {
+        return Err(decimal_errors
+            .into_iter()
+            .map(CedarDiagnostic::error)
+            .collect());
+    }
+    let mut cedar_entities = Vec::new();
+    for mut entity in entities.into_iter().chain(enum_entities) {
+        if let Some(schema) = &schema {
+            entity.coerce(schema);
+        }
+        cedar_entities.push(
+            entity
+                .to_cedar_entity(cedar_schema.as_ref())
+                .map_err(|e| errors(&e))?,
+        );
+    }
+    let cedar_entities =
+        cedar_policy::Entities::from_entities(cedar_entities, cedar_schema.as_ref())
+            .map_err(|e| errors(&e))?;
+    let cedar_policy_set: cedar_policy::PolicySet =
+        policy_set.try_into().map_err(|e| errors(&e))?;
+
+    let Request {
+        principal,
+        action,
+        resource,
+        mut context,
+    } = request;
+    if let Some(context) = &context {
+        let decimal_errors = context.decimal_errors();
+        if !decimal_errors.is_empty() {
+            return Err(decimal_errors
+                .into_iter()
+                .map(CedarDiagnostic::error)
+                .collect());
+        }
+    }
+    if let (Some(context), Some(schema)) = (&mut context, &schema) {
+        context.coerce(schema, &action);
+    }
+    let cedar_action: cedar_policy::EntityUid = action.try_into().map_err(|e| errors(&e))?;
+    let cedar_context = match context {
+        Some(context) => context
+            .to_cedar_context(cedar_schema.as_ref().map(|schema| (schema, &cedar_action)))
+            .map_err(|e| errors(&e))?,
+        None => cedar_policy::Context::empty(),
+    };
+    let cedar_request = cedar_policy::Request::new(
+        principal.try_into().map_err(|e| errors(&e))?,
+        cedar_action,
+        resource.try_into().map_err(|e| errors(&e))?,
+        cedar_context,
+        cedar_schema.as_ref(),
+    )
+    .map_err(|e| errors(&e))?;
+
+    Ok(cedar_policy::Authorizer::new()
+        .is_authorized(&cedar_request, &cedar_policy_set, &cedar_entities)
+        .into())
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use axum::{Extension, Router, extract::State, routing::get};
+use cedrus_cedar::EntityUid;
+use cedrus_core::core::cluster::ClusterStatus;
+
+use crate::{AppError, AppJson, AppState, CedrusActions, CedrusEntities};
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/cluster",
+    responses(
+        (status = 200, description = "Nodes known from their heartbeats, the answering one first", body = ClusterStatus)
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "admin_cluster_get", skip(principal, state))]
+async fn admin_cluster_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<AppJson<ClusterStatus>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetCluster.value(),
+        CedrusEntities::ApplicationCedrus.value(),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(AppJson(state.cedrus.cluster_status()))
+}
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/cluster", get(admin_cluster_get))
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Request, State},
+    http::Response,
+    middleware::Next,
+};
+use cedrus_cedar::EntityUid;
+use cedrus_core::core::audit::AuditRecord;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    routes::{
+        log::project_id,
+        read_only::{is_dry_run, is_mutation, route},
+    },
+};
+
+/// Appends every successful management mutation to the audit trail of its project, with the
+/// principal, the route and a digest of the project before and after. Changes of no project
+/// in particular, creating one or the common types, go to the admin project. Dry runs change
+/// nothing and are not recorded.
+pub async fn record(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    if !is_mutation(&req) || is_dry_run(&req) {
+        return next.run(req).await;
+    }
+    let Some(principal) = req.extensions().get::<EntityUid>().cloned() else {
+        return next.run(req).await;
+    };
+
+    let project_id = project_id(&req).unwrap_or(Uuid::nil());
+    let mut record = AuditRecord::new(project_id, principal);
+    record.method = req.method().to_string();
+    record.route = route(&req).to_string();
+    record.path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| req.uri().path(), |uri| uri.path())
+        .to_string();
+
+    let before = state.cedrus.project_state_digest(&project_id).await;
+
+    let response = next.run(req).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let after = state.cedrus.project_state_digest(&project_id).await;
+    match (before, after) {
+        (Ok(before), Ok(after)) => {
+            record.before = before;
+            record.after = after;
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::warn!(
+                "audit: unable to digest {} {}: {}",
+                record.method,
+                record.path,
+                e
+            );
+        }
+    }
+    record.status = response.status().as_u16();
+
+    state.cedrus.project_audit_record(record).await;
+
+    response
+}
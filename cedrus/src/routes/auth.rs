@@ -2,15 +2,26 @@ use std::{error::Error, fmt, sync::Arc};
 
 use axum::{
     body::Body,
-    extract::{Request, State},
-    http::{self, Response, StatusCode},
+    extract::{MatchedPath, OriginalUri, Request, State},
+    http::{self, Method, Response, StatusCode},
     middleware::Next,
     response::IntoResponse,
 };
+use cedrus_cedar::EntityUid;
+use cedrus_core::core::{
+    AuthConfig,
+    project::{ApiKeyScope, EntityTypeScope},
+};
 use headers::{Authorization, HeaderMapExt, authorization::Bearer};
 use uuid::Uuid;
 
-use crate::{AppState, AuthData};
+use crate::{
+    AppState, AuthData,
+    routes::{
+        gateway::{GatewayPrincipal, gateway_principal},
+        log::project_id,
+    },
+};
 
 const X_API_KEY: &str = "x-api-key";
 
@@ -23,6 +34,7 @@ fn stract_token(h: &http::HeaderMap) -> Option<String> {
 #[derive(Debug)]
 pub enum AuthError {
     Unauthorized,
+    Forbidden,
 }
 
 impl Error for AuthError {}
@@ -31,6 +43,7 @@ impl fmt::Display for AuthError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             AuthError::Unauthorized => write!(f, "Unauthorized"),
+            AuthError::Forbidden => write!(f, "Forbidden"),
         }
     }
 }
@@ -39,8 +52,73 @@ impl IntoResponse for AuthError {
     fn into_response(self) -> Response<Body> {
         match self {
             AuthError::Unauthorized => (StatusCode::UNAUTHORIZED, Body::empty()).into_response(),
+            AuthError::Forbidden => (StatusCode::FORBIDDEN, Body::empty()).into_response(),
+        }
+    }
+}
+
+fn is_authorization_route(req: &Request) -> bool {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str())
+        .unwrap_or_else(|| req.uri().path());
+    route.ends_with("/is-authorized") || route.ends_with("/is-authorized-batch")
+}
+
+// Scope an API key needs to make the request
+fn request_scope(req: &Request) -> ApiKeyScope {
+    if is_authorization_route(req) {
+        ApiKeyScope::Authorize
+    } else if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        ApiKeyScope::Read
+    } else {
+        ApiKeyScope::Write
+    }
+}
+
+// Group of routes an API key grant names: the path segment following the project, e.g.
+// `entities` for `/v1/projects/{id}/entities:batchDelete`, or following `/v1` elsewhere
+fn route_group(req: &Request) -> String {
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| req.uri().path(), |uri| uri.path());
+    let mut segments = path.split('/').skip(2);
+    let mut group = segments.next().unwrap_or_default();
+    if group == "projects" {
+        if project_id(req).is_some() {
+            segments.next();
         }
+        group = segments.next().unwrap_or(group);
+    }
+    group.split(':').next().unwrap_or_default().to_owned()
+}
+
+// Route groups of a project whose reads are reachable without credentials
+const ANONYMOUS_READ_GROUPS: [&str; 3] = ["entities", "policies", "schema"];
+
+// Routes reachable without credentials, as the anonymous principal of the project: the
+// authorization requests and the reads of its entities, policies and schema
+fn is_anonymous_route(req: &Request) -> bool {
+    if is_authorization_route(req) {
+        return true;
     }
+    matches!(*req.method(), Method::GET | Method::HEAD)
+        && project_id(req).is_some()
+        && ANONYMOUS_READ_GROUPS.contains(&route_group(req).as_str())
+}
+
+fn anonymous_principal(state: &AppState, req: &Request) -> Option<EntityUid> {
+    if !is_anonymous_route(req) {
+        return None;
+    }
+    let project_id = project_id(req)?;
+    state
+        .cedrus
+        .anonymous_principals
+        .get(&project_id)
+        .map(|principal| principal.value().clone())
 }
 
 #[tracing::instrument(name = "authorize", skip(state, req, next))]
@@ -49,22 +127,26 @@ pub async fn authorize(
     mut req: Request,
     next: Next,
 ) -> Result<Response<Body>, AuthError> {
+    let principal: EntityUid;
+    let mut entity_types = EntityTypeScope::default();
     if let Some(header_api_key) = req.headers().get(X_API_KEY) {
         let api_key = header_api_key
             .to_str()
             .map_err(|_| AuthError::Unauthorized)?;
-        let principal = state
+        let api_key = state
             .cedrus
             .api_keys
             .get(api_key)
             .ok_or(AuthError::Unauthorized)?;
-
-        req.extensions_mut().insert(principal.value().clone());
-    } else {
-        let Some(token) = stract_token(req.headers()) else {
+        if api_key.is_expired(chrono::Utc::now()) {
             return Err(AuthError::Unauthorized);
-        };
+        }
+        entity_types = api_key
+            .granted(&route_group(&req), request_scope(&req))
+            .ok_or(AuthError::Forbidden)?;
 
+        principal = api_key.owner.clone();
+    } else if let Some(token) = stract_token(req.headers()) {
         match state.tokens.get_value_or_guard_async(&token).await {
             Ok(auth_data) => {
                 let now = chrono::Utc::now().timestamp() as u64;
@@ -73,7 +155,7 @@ pub async fn authorize(
                     return Err(AuthError::Unauthorized);
                 }
 
-                req.extensions_mut().insert(auth_data.entity_uid.clone());
+                principal = auth_data.entity_uid.clone();
             }
             Err(guard) => {
                 let authorizer = state.cedrus.project_authorizers.get(&Uuid::nil());
@@ -90,6 +172,10 @@ pub async fn authorize(
                     }
                 };
 
+                if !authorizer.identity_source.assert_claims(&token_data.claims) {
+                    return Err(AuthError::Unauthorized);
+                }
+
                 let expires_at = token_data
                     .claims
                     .get("exp")
@@ -101,7 +187,7 @@ pub async fn authorize(
                     .get_entity_uid(&token_data.claims)
                     .map_err(|_e| AuthError::Unauthorized)?;
 
-                req.extensions_mut().insert(entity_uid.clone());
+                principal = entity_uid.clone();
 
                 let auth_data = AuthData {
                     token: token_data,
@@ -112,7 +198,126 @@ pub async fn authorize(
                 let _ = guard.insert(auth_data);
             }
         }
+    } else if let Some(GatewayPrincipal(gateway)) = req.extensions().get::<GatewayPrincipal>() {
+        principal = gateway.clone();
+    } else {
+        principal = anonymous_principal(&state, &req).ok_or(AuthError::Unauthorized)?;
+    }
+
+    req.extensions_mut().insert(principal.clone());
+    req.extensions_mut().insert(entity_types);
+
+    // Exposed to the request log, which wraps this middleware
+    let mut response = next.run(req).await;
+    response.extensions_mut().insert(principal);
+
+    Ok(response)
+}
+
+/// Rejects the kinds of credentials the listener does not accept, ahead of `authorize`, and
+/// takes the principal of the other requests from the gateway of the listener, if any.
+pub async fn restrict(
+    State(config): State<Arc<AuthConfig>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response<Body>, AuthError> {
+    let allowed = if req.headers().contains_key(X_API_KEY) {
+        config.api_key
+    } else if req.headers().contains_key(http::header::AUTHORIZATION) {
+        config.bearer
+    } else {
+        if let Some(gateway) = &config.gateway {
+            match gateway_principal(gateway, req.headers()) {
+                Ok(Some(principal)) => {
+                    req.extensions_mut().insert(GatewayPrincipal(principal));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::debug!("Gateway identity rejected: {}", e);
+                    return Err(AuthError::Unauthorized);
+                }
+            }
+        }
+        // Anonymous requests, left to `authorize`
+        true
+    };
+    if !allowed {
+        return Err(AuthError::Unauthorized);
     }
 
     Ok(next.run(req).await)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, path: &str) -> Request {
+        Request::builder()
+            .method(method)
+            .uri(format!("/v1/projects/{}{path}", Uuid::nil()))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_is_anonymous_route() {
+        for (method, path) in [
+            (Method::GET, "/entities"),
+            (Method::HEAD, "/policies"),
+            (Method::GET, "/policies/p1/cedar"),
+            (Method::GET, "/schema/cedar"),
+            (Method::POST, "/is-authorized"),
+            (Method::POST, "/is-authorized-batch"),
+        ] {
+            assert!(is_anonymous_route(&request(method, path)), "{path}");
+        }
+        for (method, path) in [
+            (Method::POST, "/entities"),
+            (Method::PUT, "/schema"),
+            (Method::DELETE, "/policies/p1"),
+            (Method::POST, "/api-keys"),
+            (Method::GET, "/api-keys"),
+            (Method::GET, "/audit"),
+            (Method::GET, "/identity-source"),
+            (Method::GET, "/jobs"),
+            (Method::GET, ""),
+        ] {
+            assert!(!is_anonymous_route(&request(method, path)), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_request_scope() {
+        for (method, path, scope) in [
+            (Method::GET, "/entities", ApiKeyScope::Read),
+            (Method::HEAD, "/policies", ApiKeyScope::Read),
+            (Method::POST, "/is-authorized", ApiKeyScope::Authorize),
+            (Method::POST, "/is-authorized-batch", ApiKeyScope::Authorize),
+            (Method::POST, "/entities", ApiKeyScope::Write),
+            (Method::DELETE, "/policies/p1", ApiKeyScope::Write),
+        ] {
+            assert_eq!(request_scope(&request(method, path)), scope, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_route_group() {
+        for (path, group) in [
+            (format!("/v1/projects/{}/entities", Uuid::nil()), "entities"),
+            (
+                format!("/v1/projects/{}/entities:batchDelete", Uuid::nil()),
+                "entities",
+            ),
+            (
+                format!("/v1/projects/{}/policies/p1/cedar", Uuid::nil()),
+                "policies",
+            ),
+            ("/v1/projects".to_string(), "projects"),
+            ("/v1/schemas".to_string(), "schemas"),
+        ] {
+            let req = Request::get(path.as_str()).body(Body::empty()).unwrap();
+            assert_eq!(route_group(&req), group, "{path}");
+        }
+    }
+}
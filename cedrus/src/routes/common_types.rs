@@ -0,0 +1,150 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, State},
+    routing::{delete, get, put},
+};
+use cedrus_cedar::{EntityUid, schema::TypeJson};
+
+use crate::{AppError, AppJson, AppState, CedrusActions, CedrusEntities};
+
+#[utoipa::path(
+    get,
+    path = "/v1/common-types",
+    responses(
+        (status = 200, description = "Common types library", body = HashMap<String, TypeJson>)
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "common_types_get", skip(principal, state))]
+async fn common_types_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<AppJson<HashMap<String, TypeJson>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetCommonTypes.value(),
+        CedrusEntities::ApplicationCedrus.value(),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let common_types = state.cedrus.common_types_find().await?;
+
+    Ok(AppJson(common_types))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/common-types",
+    request_body = HashMap<String, TypeJson>,
+    responses(
+        (status = 200, description = "Common types library", body = HashMap<String, TypeJson>),
+        (status = 400, description = "Invalid name or a project schema no longer builds")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "common_types_put", skip(principal, state, common_types))]
+async fn common_types_put(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Json(common_types): Json<HashMap<String, TypeJson>>,
+) -> Result<AppJson<HashMap<String, TypeJson>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PutCommonTypes.value(),
+        CedrusEntities::ApplicationCedrus.value(),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let common_types = state.cedrus.common_types_update(common_types).await?;
+
+    Ok(AppJson(common_types))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/common-types/{name}",
+    request_body = TypeJson,
+    params(
+        ("name" = String, Path, description = "Common type name")
+    ),
+    responses(
+        (status = 200, description = "Common type", body = TypeJson),
+        (status = 400, description = "Invalid name or a project schema no longer builds")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "common_types_name_put", skip(principal, state, type_json))]
+async fn common_types_name_put(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(type_json): Json<TypeJson>,
+) -> Result<AppJson<TypeJson>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PutCommonTypes.value(),
+        CedrusEntities::ApplicationCedrus.value(),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let type_json = state.cedrus.common_type_update(name, type_json).await?;
+
+    Ok(AppJson(type_json))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/common-types/{name}",
+    params(
+        ("name" = String, Path, description = "Common type name")
+    ),
+    responses(
+        (status = 200, description = "Removed common type", body = TypeJson),
+        (status = 400, description = "A project schema still refers to the common type"),
+        (status = 404, description = "Common type not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "common_types_name_delete", skip(principal, state))]
+async fn common_types_name_delete(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<AppJson<TypeJson>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteCommonTypes.value(),
+        CedrusEntities::ApplicationCedrus.value(),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let type_json = state.cedrus.common_type_remove(&name).await?;
+
+    Ok(AppJson(type_json))
+}
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(common_types_get))
+        .route("/", put(common_types_put))
+        .route("/{name}", put(common_types_name_put))
+        .route("/{name}", delete(common_types_name_delete))
+}
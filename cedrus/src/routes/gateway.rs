@@ -0,0 +1,172 @@
+use axum::http::HeaderMap;
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use cedrus_cedar::EntityUid;
+use cedrus_core::core::{GatewayAuthConfig, GatewaySource};
+use serde_json::Value;
+
+const X_AMZN_OIDC_DATA: &str = "x-amzn-oidc-data";
+const X_AMZN_OIDC_IDENTITY: &str = "x-amzn-oidc-identity";
+const DEFAULT_CLAIM: &str = "sub";
+const DEFAULT_API_GATEWAY_HEADER: &str = "x-apigateway-principal";
+
+/// Principal asserted by the gateway, set on the request by `auth::restrict`.
+#[derive(Debug, Clone)]
+pub struct GatewayPrincipal(pub EntityUid);
+
+/// Principal of the identity the gateway in front of the listener asserted, `None` when the
+/// request carries none. The signature of the ALB claims is not verified again, the load
+/// balancer having verified the identity.
+pub fn gateway_principal(
+    config: &GatewayAuthConfig,
+    headers: &HeaderMap,
+) -> Result<Option<EntityUid>, String> {
+    let id = match config.source {
+        GatewaySource::Alb => alb_identity(config, headers)?,
+        GatewaySource::ApiGateway => {
+            let header = config
+                .header
+                .as_deref()
+                .unwrap_or(DEFAULT_API_GATEWAY_HEADER);
+            header_value(headers, header)?
+        }
+    };
+
+    Ok(id
+        .filter(|id| !id.is_empty())
+        .map(|id| EntityUid::new(config.entity_type.clone(), id)))
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Result<Option<String>, String> {
+    headers
+        .get(name)
+        .map(|value| {
+            value
+                .to_str()
+                .map(str::to_owned)
+                .map_err(|e| format!("{name}: {e}"))
+        })
+        .transpose()
+}
+
+fn alb_identity(config: &GatewayAuthConfig, headers: &HeaderMap) -> Result<Option<String>, String> {
+    let claim = config.claim.as_deref().unwrap_or(DEFAULT_CLAIM);
+    let Some(data) = header_value(headers, X_AMZN_OIDC_DATA)? else {
+        // Without the claims, only the subject is known
+        return match (claim, &config.signer) {
+            (DEFAULT_CLAIM, None) => header_value(headers, X_AMZN_OIDC_IDENTITY),
+            _ => Ok(None),
+        };
+    };
+
+    let mut parts = data.split('.');
+    let (Some(header), Some(payload)) = (parts.next(), parts.next()) else {
+        return Err(format!("{X_AMZN_OIDC_DATA}: not a JWT"));
+    };
+    if let Some(signer) = &config.signer {
+        let header = decode_part(header)?;
+        if header.get("signer").and_then(Value::as_str) != Some(signer) {
+            return Err(format!("{X_AMZN_OIDC_DATA}: unexpected signer"));
+        }
+    }
+
+    let claims = decode_part(payload)?;
+    Ok(claims.get(claim).and_then(|value| match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }))
+}
+
+// The ALB pads the base64url parts of its tokens
+fn decode_part(part: &str) -> Result<Value, String> {
+    let bytes = BASE64_URL_SAFE_NO_PAD
+        .decode(part.trim_end_matches('='))
+        .map_err(|e| format!("{X_AMZN_OIDC_DATA}: {e}"))?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("{X_AMZN_OIDC_DATA}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use serde_json::json;
+
+    use super::*;
+
+    const SIGNER: &str =
+        "arn:aws:elasticloadbalancing:eu-west-1:123456789012:loadbalancer/app/cedrus/1";
+
+    // Claims as the ALB passes them, padded and with a signature left unverified
+    fn oidc_data(signer: &str, claims: Value) -> HeaderValue {
+        let header = BASE64_URL_SAFE_NO_PAD.encode(json!({"signer": signer}).to_string());
+        let payload = BASE64_URL_SAFE_NO_PAD.encode(claims.to_string());
+        HeaderValue::from_str(&format!("{header}==.{payload}=.signature")).unwrap()
+    }
+
+    #[test]
+    fn test_gateway_principal_alb() {
+        let mut config = GatewayAuthConfig {
+            entity_type: "App::User".to_string(),
+            claim: Some("email".to_string()),
+            signer: Some(SIGNER.to_string()),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            X_AMZN_OIDC_DATA,
+            oidc_data(SIGNER, json!({"sub": "1234", "email": "alice@example.com"})),
+        );
+        assert_eq!(
+            gateway_principal(&config, &headers).unwrap(),
+            Some(EntityUid::new(
+                "App::User".to_string(),
+                "alice@example.com".to_string()
+            ))
+        );
+
+        // Claims signed by another load balancer are rejected
+        headers.insert(
+            X_AMZN_OIDC_DATA,
+            oidc_data("arn:other", json!({"email": "mallory@example.com"})),
+        );
+        assert!(gateway_principal(&config, &headers).is_err());
+
+        // Without the claims the subject is only taken from the identity header, and only
+        // when neither a claim nor a signer is required
+        headers.remove(X_AMZN_OIDC_DATA);
+        headers.insert(X_AMZN_OIDC_IDENTITY, HeaderValue::from_static("1234"));
+        assert_eq!(gateway_principal(&config, &headers).unwrap(), None);
+        config.claim = None;
+        config.signer = None;
+        assert_eq!(
+            gateway_principal(&config, &headers).unwrap(),
+            Some(EntityUid::new("App::User".to_string(), "1234".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_gateway_principal_api_gateway() {
+        let config = GatewayAuthConfig {
+            source: GatewaySource::ApiGateway,
+            entity_type: "App::Service".to_string(),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        assert_eq!(gateway_principal(&config, &headers).unwrap(), None);
+
+        headers.insert(
+            DEFAULT_API_GATEWAY_HEADER,
+            HeaderValue::from_static("billing"),
+        );
+        assert_eq!(
+            gateway_principal(&config, &headers).unwrap(),
+            Some(EntityUid::new(
+                "App::Service".to_string(),
+                "billing".to_string()
+            ))
+        );
+
+        // An empty identity is no identity
+        headers.insert(DEFAULT_API_GATEWAY_HEADER, HeaderValue::from_static(""));
+        assert_eq!(gateway_principal(&config, &headers).unwrap(), None);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::Response,
+    middleware::Next,
+};
+use cedrus_core::core::{RouteLimit, RouteLimitsConfig};
+use tokio::sync::Semaphore;
+
+use crate::{AppError, routes::read_only::route};
+
+// Routes loading, replacing or deleting a project's data at once
+const BULK_ROUTES: [&str; 12] = [
+    "/entities/sync",
+    "/entities:batchDelete",
+    "/policies:batchDelete",
+    "/templates:batchDelete",
+    "/template-links:batchCreate",
+    "/policy-set/bundle",
+    "/state",
+    "/gitops/sync",
+    "/consistency",
+    "/resync",
+    "/jobs/import",
+    "/jobs/export",
+];
+
+struct Limit {
+    timeout: Option<Duration>,
+    permits: Option<Arc<Semaphore>>,
+    load_shed: bool,
+}
+
+impl From<&RouteLimit> for Limit {
+    fn from(config: &RouteLimit) -> Self {
+        Limit {
+            timeout: config.timeout.map(Duration::from_secs),
+            permits: config
+                .max_concurrency
+                .map(|max| Arc::new(Semaphore::new(max))),
+            load_shed: config.load_shed,
+        }
+    }
+}
+
+/// Timeouts and concurrency permits of the route classes, shared by the listeners.
+pub struct RouteLimits {
+    authorization: Limit,
+    batch: Limit,
+    bulk: Limit,
+    default: Limit,
+}
+
+impl RouteLimits {
+    pub fn new(config: &RouteLimitsConfig) -> Self {
+        RouteLimits {
+            authorization: (&config.authorization).into(),
+            batch: (&config.batch).into(),
+            bulk: (&config.bulk).into(),
+            default: (&config.default).into(),
+        }
+    }
+
+    fn limit(&self, route: &str) -> &Limit {
+        if route.ends_with("/is-authorized") {
+            &self.authorization
+        } else if route.ends_with("/is-authorized-batch") {
+            &self.batch
+        } else if BULK_ROUTES.iter().any(|r| route.ends_with(r)) {
+            &self.bulk
+        } else {
+            &self.default
+        }
+    }
+}
+
+/// Serves a request within the limits of its route class: waits for, or with load
+/// shedding rejects with 503, a concurrency permit, and answers 408 once the timeout
+/// elapses. Streamed bodies are not bounded past their first byte.
+pub async fn limit(
+    State(limits): State<Arc<RouteLimits>>,
+    req: Request,
+    next: Next,
+) -> Result<Response<Body>, AppError> {
+    let limit = limits.limit(route(&req));
+
+    let serve = async {
+        let _permit = match &limit.permits {
+            Some(permits) if limit.load_shed => Some(
+                permits
+                    .clone()
+                    .try_acquire_owned()
+                    .map_err(|_| AppError::Overloaded)?,
+            ),
+            Some(permits) => Some(
+                permits
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .map_err(|_| AppError::Overloaded)?,
+            ),
+            None => None,
+        };
+        Ok(next.run(req).await)
+    };
+
+    match limit.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, serve)
+            .await
+            .map_err(|_| AppError::RequestTimeout)?,
+        None => serve.await,
+    }
+}
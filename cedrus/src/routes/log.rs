@@ -0,0 +1,202 @@
+use std::{sync::Arc, time::Instant};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{MatchedPath, OriginalUri, Request, State},
+    http::{HeaderMap, Response, header},
+    middleware::Next,
+};
+use cedrus_cedar::EntityUid;
+use cedrus_core::core::RequestLogConfig;
+use serde_json::Value;
+use tokio_stream::StreamExt;
+use uuid::Uuid;
+
+const REDACTED: &str = "[REDACTED]";
+const MAX_LOGGED_BODY: usize = 1024 * 1024;
+
+// Keys whose object values hold user supplied attributes
+const ATTRIBUTE_KEYS: [&str; 3] = ["attrs", "tags", "context"];
+// Keys holding credentials, always redacted
+const SECRET_KEYS: [&str; 1] = ["token"];
+
+fn redact(value: &mut Value, config: &RequestLogConfig) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) && value.is_string() {
+                    *value = Value::String(REDACTED.to_string());
+                    continue;
+                }
+                if ATTRIBUTE_KEYS.contains(&key.as_str())
+                    && let Value::Object(attrs) = value
+                {
+                    for (name, attr) in attrs.iter_mut() {
+                        if config.redact.iter().any(|r| r == "*" || r == name) {
+                            *attr = Value::String(REDACTED.to_string());
+                        }
+                    }
+                }
+                redact(value, config);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| redact(v, config)),
+        _ => {}
+    }
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+// Buffers a body to log it, returning the body to rebuild the request or response with. A
+// body over the logged size is forwarded whole, streaming what was not read, and not logged.
+async fn read_body(body: Body, config: &RequestLogConfig) -> (Body, Option<Value>) {
+    let mut stream = body.into_data_stream();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => bytes.extend_from_slice(&chunk),
+            Err(e) => {
+                let read = tokio_stream::iter([Ok(Bytes::from(bytes)), Err(e)]);
+                return (Body::from_stream(read), None);
+            }
+        }
+        if bytes.len() > MAX_LOGGED_BODY {
+            let read = tokio_stream::once(Ok(Bytes::from(bytes)));
+            return (Body::from_stream(read.chain(stream)), None);
+        }
+    }
+    let logged = serde_json::from_slice::<Value>(&bytes).ok().map(|mut v| {
+        redact(&mut v, config);
+        v
+    });
+    (Body::from(bytes), logged)
+}
+
+// Nested routers see the path without their prefix, the original one keeps `/v1/projects`
+pub(crate) fn project_id(req: &Request) -> Option<Uuid> {
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| req.uri().path(), |uri| uri.path());
+    path.split('/')
+        .nth(3)
+        .and_then(|id| Uuid::parse_str(id).ok())
+}
+
+/// Logs method, route, project, principal, status and latency of every request. The
+/// principal is read from the response extensions, where `auth::authorize` leaves it.
+pub async fn log_requests(
+    State(config): State<Arc<RequestLogConfig>>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    let start = Instant::now();
+    let method = req.method().clone();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_owned())
+        .unwrap_or_else(|| req.uri().path().to_owned());
+    let project_id = project_id(&req);
+
+    let (req, request_body) = if config.bodies && is_json(req.headers()) {
+        let (parts, body) = req.into_parts();
+        let (body, logged) = read_body(body, &config).await;
+        (Request::from_parts(parts, body), logged)
+    } else {
+        (req, None)
+    };
+
+    let response = next.run(req).await;
+
+    let (response, response_body) = if config.bodies && is_json(response.headers()) {
+        let (parts, body) = response.into_parts();
+        let (body, logged) = read_body(body, &config).await;
+        (Response::from_parts(parts, body), logged)
+    } else {
+        (response, None)
+    };
+
+    let principal = response
+        .extensions()
+        .get::<EntityUid>()
+        .map(|p| p.to_string())
+        .unwrap_or_default();
+    let project_id = project_id.map(|id| id.to_string()).unwrap_or_default();
+
+    tracing::info!(
+        target: "cedrus::request",
+        method = %method,
+        route = %route,
+        project_id = %project_id,
+        principal = %principal,
+        status = response.status().as_u16(),
+        latency_ms = start.elapsed().as_millis() as u64,
+        request_body = request_body.map(|v| v.to_string()),
+        response_body = response_body.map(|v| v.to_string()),
+    );
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, body::to_bytes, extract::DefaultBodyLimit, middleware, routing::post};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_log_requests_large_body() {
+        let config = Arc::new(RequestLogConfig {
+            bodies: true,
+            redact: vec!["*".to_string()],
+        });
+        let app =
+            Router::new()
+                .route(
+                    "/echo",
+                    post(|body: String| async move {
+                        ([(header::CONTENT_TYPE, "application/json")], body)
+                    }),
+                )
+                .layer(DefaultBodyLimit::disable())
+                .layer(middleware::from_fn_with_state(config, log_requests));
+
+        let large = serde_json::to_string(&vec!["cedrus"; MAX_LOGGED_BODY / 4]).unwrap();
+        for body in [r#"{"attrs":{"a":1}}"#.to_string(), large] {
+            let req = Request::post("/echo")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.clone()))
+                .unwrap();
+            let response = app.clone().oneshot(req).await.unwrap();
+            let echoed = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert!(echoed == body.as_bytes(), "{} bytes echoed", echoed.len());
+        }
+    }
+
+    #[test]
+    fn test_redact() {
+        let config = RequestLogConfig {
+            bodies: true,
+            redact: vec!["ssn".to_string()],
+        };
+        let mut value = serde_json::json!({
+            "token": "secret",
+            "entities": [{"attrs": {"ssn": "1", "name": "a"}}],
+        });
+        redact(&mut value, &config);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "token": REDACTED,
+                "entities": [{"attrs": {"ssn": REDACTED, "name": "a"}}],
+            })
+        );
+    }
+}
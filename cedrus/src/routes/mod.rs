@@ -1,3 +1,13 @@
+pub mod audit;
 pub mod auth;
+pub mod gateway;
+pub mod limits;
+pub mod log;
+pub mod read_only;
+pub mod reload;
+pub mod shards;
+pub mod staleness;
 
+pub mod admin;
+pub mod common_types;
 pub mod projects;
@@ -1,15 +1,24 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+};
 
 use axum::{
     Extension, Json, Router,
+    body::Body,
     extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response as HttpResponse},
     routing::{delete, get, post, put},
 };
 use cedrus_cedar::{
-    Context, Entity, EntityUid, Policy, PolicyId, PolicySet, Request, Response, Schema, Template,
-    TemplateLink,
+    Context, Entity, EntityUid, Policy, PolicyId, PolicySet, Request, ResourcePath, Response,
+    Schema, Template, TemplateLink, expr::AttributeReferences,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_stream::{StreamExt, wrappers::ReceiverStream};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -17,24 +26,89 @@ use cedrus_core::{
     PageHash, PageList, Selector,
     core::{
         IdentitySource,
-        project::{ApiKey, Project},
+        audit::AuditRecord,
+        batch::{BatchDeleteResult, TemplateLinkBatch},
+        benchmark::{Benchmark, BenchmarkReport},
+        bundle::PolicyBundle,
+        candidate::{CandidateReport, ProjectCandidate},
+        cedrus::json_digest,
+        combine::{CombinedResponse, DecisionStrategy},
+        consistency::ConsistencyReport,
+        coverage::PolicyCoverageReport,
+        diff::RevisionDiff,
+        dry_run::DryRunReport,
+        environment::{NewEnvironment, Promotion, PromotionReport},
+        generator::{GeneratedData, GeneratorOptions},
+        gitops::GitOpsReport,
+        job::{Job, JobTask, ProjectCleanup, ProjectData},
+        lint::SchemaLintReport,
+        modified::ProjectResource,
+        project::{ApiKey, EntityTypeScope, Project, ProjectStats, Role},
+        references::EntityReferences,
+        relation::Relation,
+        state::{ProjectState, StatePlan},
+        sync::{EntitiesSync, EntitiesSyncReport},
     },
 };
 
-use crate::{AppError, AppJson, AppState, CedrusActions, CedrusEntities, QueryParams};
+use crate::{
+    AppError, AppJson, AppState, AsOfParams, CedarDiagnostic, CedrusActions, CedrusEntities,
+    Delegation, DiagnosticSeverity, DiffParams, DryRunParams, ForceParams, Mutation, QueryParams,
+    ReadOnly, RelationParams, SdkParams, TemplateLinkBatchParams, WarningsParams,
+    annotation_params, sampling::sampled_request,
+};
 
 #[derive(Default, Clone, Serialize, Deserialize, ToSchema)]
 pub struct IsAuthorizedRequest {
+    /// Ignored when `token` is set
+    #[serde(default)]
     pub principal: EntityUid,
     pub action: EntityUid,
     pub resource: EntityUid,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context: Option<Context>,
+    /// Token verified by the identity source of the project, whose principal makes the
+    /// request, materialized by the entity mapper
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Evaluate once the node caught up with the latest version of the project in the Cache,
+    /// for read-your-writes right after a change
+    #[serde(default)]
+    pub sync: bool,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayRequest {
+    /// Time the policies and entities are replayed as of
+    pub as_of: chrono::DateTime<chrono::Utc>,
+    pub request: Request,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CombinedRequest {
+    /// Projects the request is evaluated against, e.g. platform guardrails then the tenant
+    pub projects: Vec<Uuid>,
+    #[serde(default)]
+    pub strategy: DecisionStrategy,
+    pub request: Request,
+    /// Evaluate once the node caught up with the projects, see `IsAuthorizedRequest`
+    #[serde(default)]
+    pub sync: bool,
 }
 
 #[derive(Default, Clone, Serialize, Deserialize, ToSchema)]
 pub struct IsAuthorizedRequests {
     pub requests: Vec<Request>,
+    /// Token whose principal makes every request, see `IsAuthorizedRequest`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Return the evaluation time of every request
+    #[serde(default)]
+    pub timings: bool,
+    /// Evaluate once the node caught up with the project, see `IsAuthorizedRequest`
+    #[serde(default)]
+    pub sync: bool,
 }
 
 #[derive(Default, Clone, Serialize, Deserialize, ToSchema)]
@@ -42,11 +116,271 @@ pub struct CedarSyntax {
     pub cedar: Option<String>,
 }
 
+/// A schema parsed from Cedar syntax, along with the warnings raised by the parser.
+#[derive(Default, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CedarSchema {
+    pub schema: Schema,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<CedarDiagnostic>,
+}
+
+impl CedarSchema {
+    /// Parses a schema in Cedar syntax, reporting syntax errors with their position in `src`.
+    pub fn parse(src: &str) -> Result<Self, Vec<CedarDiagnostic>> {
+        let errors = |e: &dyn miette::Diagnostic| {
+            CedarDiagnostic::collect(e, Some(src), DiagnosticSeverity::Error)
+        };
+
+        let (cedar_schema, warnings) =
+            cedar_policy::SchemaFragment::from_cedarschema_str(src).map_err(|e| errors(&e))?;
+        let warnings = warnings
+            .flat_map(|w| CedarDiagnostic::collect(&w, Some(src), DiagnosticSeverity::Warning))
+            .collect();
+        let json = cedar_schema.to_json_value().map_err(|e| errors(&e))?;
+        let schema: Schema = serde_json::from_value(json)
+            .map_err(|e| vec![CedarDiagnostic::error(e.to_string())])?;
+
+        Ok(Self { schema, warnings })
+    }
+}
+
+/// A schema parsed from Cedar syntax as the schema routes answer with it: the schema alone,
+/// or along with the parser warnings when asked for them with `warnings=true`.
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum ParsedSchema {
+    Schema(Schema),
+    WithWarnings(CedarSchema),
+}
+
+impl ParsedSchema {
+    fn new(cedar_schema: CedarSchema, params: &WarningsParams) -> Self {
+        match params.with_warnings() {
+            true => Self::WithWarnings(cedar_schema),
+            false => Self::Schema(cedar_schema.schema),
+        }
+    }
+}
+
+/// Converts a JSON schema to Cedar syntax, reporting why it can not be represented.
+fn schema_to_cedar(schema: &Schema) -> Result<String, Vec<CedarDiagnostic>> {
+    let errors =
+        |e: &dyn miette::Diagnostic| CedarDiagnostic::collect(e, None, DiagnosticSeverity::Error);
+
+    let value =
+        serde_json::to_value(schema).map_err(|e| vec![CedarDiagnostic::error(e.to_string())])?;
+    let cedar_schema =
+        cedar_policy::SchemaFragment::from_json_value(value).map_err(|e| errors(&e))?;
+    cedar_schema.to_cedarschema().map_err(|e| errors(&e))
+}
+
+/// Serializes an item, keeping only the `fields` of the query when it has some.
+fn select_fields<T: Serialize>(
+    query: &cedrus_core::Query,
+    item: &T,
+) -> Result<Value, serde_json::Error> {
+    Ok(query.select_fields(serde_json::to_value(item)?))
+}
+
+/// Splits a multi-policy Cedar text into its policies and templates, keyed by their `@id`
+/// annotation, which every one of them must have.
+pub(crate) fn policy_set_from_cedar(src: &str) -> Result<PolicySet, Vec<CedarDiagnostic>> {
+    let cedar_policy_set = cedar_policy::PolicySet::from_str(src)
+        .map_err(|e| CedarDiagnostic::collect(&e, Some(src), DiagnosticSeverity::Error))?;
+
+    let mut policy_set = PolicySet::default();
+    let mut errors = Vec::new();
+    let mut id = |annotation: Option<&str>, position: &cedar_policy::PolicyId| match annotation {
+        Some(id) => Some(PolicyId::from(id.to_string())),
+        None => {
+            errors.push(format!("{position} has no @id annotation"));
+            None
+        }
+    };
+    let mut ids = HashSet::new();
+    let mut duplicates = Vec::new();
+    for policy in cedar_policy_set.policies() {
+        let Some(policy_id) = id(policy.annotation("id"), policy.id()) else {
+            continue;
+        };
+        if !ids.insert(policy_id.clone()) {
+            duplicates.push(policy_id.clone());
+        }
+        let policy = Policy::try_from(policy.clone())
+            .map_err(|e| vec![CedarDiagnostic::error(e.to_string())])?;
+        policy_set.static_policies.insert(policy_id, policy);
+    }
+    for template in cedar_policy_set.templates() {
+        let Some(template_id) = id(template.annotation("id"), template.id()) else {
+            continue;
+        };
+        if !ids.insert(template_id.clone()) {
+            duplicates.push(template_id.clone());
+        }
+        let template = Template::try_from(template.clone())
+            .map_err(|e| vec![CedarDiagnostic::error(e.to_string())])?;
+        policy_set.templates.insert(template_id, template);
+    }
+
+    errors.sort();
+    errors.extend(duplicates.iter().map(|id| format!("duplicate @id {id}")));
+    match errors.is_empty() {
+        true => Ok(policy_set),
+        false => Err(errors.into_iter().map(CedarDiagnostic::error).collect()),
+    }
+}
+
+/// Header carrying the policy set version a decision was made against. When sent by the client
+/// on is-authorized requests, the request fails with 412 if the version is no longer current.
+pub const POLICY_VERSION_HEADER: &str = "x-policy-version";
+
+/// Prefix of the query parameters listing the projects with a label, e.g. `label.team=payments`.
+const LABEL_PARAM_PREFIX: &str = "label.";
+
+/// Reads may be stored by the client only, and revalidated before every use.
+const READ_CACHE_CONTROL: &str = "private, no-cache";
+/// Format of the `Last-Modified` header.
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Media type of listings streamed as one JSON item per line.
+const NDJSON: &str = "application/x-ndjson";
+/// Number of streamed items buffered ahead of a slow client.
+const NDJSON_CHANNEL_CAPACITY: usize = 64;
+
+/// Actions required to make a project match a state, whether sent or read from Git.
+const STATE_ACTIONS: [CedrusActions; 8] = [
+    CedrusActions::PutProjectSchema,
+    CedrusActions::DeleteProjectSchema,
+    CedrusActions::PostProjectTemplates,
+    CedrusActions::DeleteProjectTemplates,
+    CedrusActions::PostProjectPolicies,
+    CedrusActions::DeleteProjectPolicies,
+    CedrusActions::PostProjectTemplateLinks,
+    CedrusActions::DeleteProjectTemplateLinks,
+];
+
+#[derive(Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyVersion {
+    pub project_id: Uuid,
+    pub version: String,
+}
+
+fn is_policy_version_stale(version: &str, request_headers: &HeaderMap) -> bool {
+    request_headers
+        .get(POLICY_VERSION_HEADER)
+        .is_some_and(|expected| expected.as_bytes() != version.as_bytes())
+}
+
+/// Response of a created resource: `201`, its `Location` and its canonical representation
+type Created<T> = (StatusCode, HeaderMap, AppJson<T>);
+
+fn location_headers(location: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(location) {
+        headers.insert(header::LOCATION, value);
+    }
+    headers
+}
+
+/// `Location` of the one resource a request created under `collection`. None is set when it
+/// created several, the body listing them all.
+fn created_location_headers<I: ToString>(
+    collection: &str,
+    ids: impl IntoIterator<Item = I>,
+) -> HeaderMap {
+    let mut ids = ids.into_iter();
+    match (ids.next(), ids.next()) {
+        (Some(id), None) => {
+            location_headers(&format!("{collection}/{}", path_segment(&id.to_string())))
+        }
+        _ => HeaderMap::new(),
+    }
+}
+
+/// Percent-encodes `value` as a single path segment.
+fn path_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b':' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Current schema, entities or policies of a project, with an `ETag` digest of the body and
+/// the `Last-Modified` time of the data. Answers 304 while the `If-None-Match`, or without it
+/// the `If-Modified-Since`, of the request still matches.
+fn cached_response(
+    request_headers: &HeaderMap,
+    last_modified: Option<chrono::DateTime<chrono::Utc>>,
+    body: Value,
+) -> HttpResponse {
+    let etag = format!("\"{}\"", json_digest(body.clone()));
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(READ_CACHE_CONTROL),
+    );
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Some(last_modified) = last_modified
+        && let Ok(value) = HeaderValue::from_str(&last_modified.format(HTTP_DATE).to_string())
+    {
+        headers.insert(header::LAST_MODIFIED, value);
+    }
+
+    let not_modified = match request_headers.get(header::IF_NONE_MATCH) {
+        Some(value) => value.to_str().is_ok_and(|etags| {
+            etags
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*")
+        }),
+        None => request_headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| chrono::DateTime::parse_from_rfc2822(value).ok())
+            .zip(last_modified)
+            .is_some_and(|(since, modified)| modified.timestamp() <= since.timestamp()),
+    };
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    (headers, AppJson(body)).into_response()
+}
+
+fn accepts_ndjson(request_headers: &HeaderMap) -> bool {
+    request_headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| media_type.split(';').next().unwrap_or_default().trim() == NDJSON)
+}
+
+fn policy_version_headers(version: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(version) {
+        headers.insert(POLICY_VERSION_HEADER, value);
+    }
+    if let Ok(etag) = HeaderValue::from_str(&format!("\"{version}\"")) {
+        headers.insert(header::ETAG, etag);
+    }
+    headers
+}
+
 #[utoipa::path(
     get,
     path = "/v1/projects",
     params(
         QueryParams,
+        ("label.{key}" = Option<String>, Query, description = "Filter by project label value, e.g. label.team=payments"),
     ),
     responses(
         (status = 200, description = "Projects Page", body = PageList<Project>)
@@ -56,12 +390,13 @@ pub struct CedarSyntax {
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_get", skip(principal, state, query_params))]
+#[tracing::instrument(name = "projects_get", skip(principal, state, query_params, params))]
 async fn projects_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Query(query_params): Query<QueryParams>,
-) -> Result<AppJson<PageList<Project>>, AppError> {
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<AppJson<PageList<Value>>, AppError> {
     if !state.cedrus.is_allow(
         principal.clone(),
         CedrusActions::GetProjects.value(),
@@ -70,7 +405,21 @@ async fn projects_get(
         return Err(AppError::Forbidden);
     }
 
-    let mut page = state.cedrus.projects_find(query_params.into()).await?;
+    let mut query: cedrus_core::Query = query_params.into();
+    let labels: HashMap<String, Selector> = params
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let key = key.strip_prefix(LABEL_PARAM_PREFIX)?;
+            Some((
+                format!("labels.{key}"),
+                Selector::Eq(Box::new(Selector::String(value))),
+            ))
+        })
+        .collect();
+    if !labels.is_empty() {
+        query.and_selector(Selector::Record(labels));
+    }
+    let mut page = state.cedrus.projects_find(query.clone()).await?;
 
     page.items.retain(|p| {
         state.cedrus.is_allow(
@@ -80,12 +429,20 @@ async fn projects_get(
         )
     });
 
-    Ok(AppJson(page))
+    let mut items = Vec::with_capacity(page.items.len());
+    for project in page.items {
+        items.push(query.select_fields(
+            serde_json::to_value(project.redacted()).map_err(AppError::SerdeJsonError)?,
+        ));
+    }
+
+    Ok(AppJson(PageList::new(items, page.last_key)))
 }
 
 #[utoipa::path(
     post,
     path = "/v1/projects",
+    params(DryRunParams),
     request_body = Project,
     responses(
         (status = 200, description = "Project", body = Project)
@@ -95,12 +452,13 @@ async fn projects_get(
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_post", skip(principal, state, project))]
+#[tracing::instrument(name = "projects_post", skip(principal, state, dry_run, project))]
 async fn projects_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
+    Query(dry_run): Query<DryRunParams>,
     Json(mut project): Json<Project>,
-) -> Result<AppJson<Project>, AppError> {
+) -> Result<Mutation<AppJson<Project>>, AppError> {
     if !state.cedrus.is_allow(
         principal.clone(),
         CedrusActions::PostProject.value(),
@@ -110,9 +468,13 @@ async fn projects_post(
     }
 
     project.id = Uuid::now_v7();
-    let project = state.cedrus.project_create(project, principal).await?;
+    project.environment = None;
+    project.candidate = None;
+    let project = dry_run
+        .mutate(state.cedrus.project_create(project, principal))
+        .await?;
 
-    Ok(AppJson(project))
+    Ok(project.map(|project| AppJson(project.redacted())))
 }
 
 #[utoipa::path(
@@ -147,7 +509,7 @@ async fn projects_id_get(
         return Err(AppError::NotFound);
     };
 
-    Ok(AppJson(project))
+    Ok(AppJson(project.redacted()))
 }
 
 #[utoipa::path(
@@ -155,7 +517,8 @@ async fn projects_id_get(
     path = "/v1/projects/{id}",
     request_body = Project,
     params(
-        ("id" = Uuid, Path, description = "Project id")
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
     responses(
         (status = 200, description = "Project", body = Project)
@@ -165,13 +528,14 @@ async fn projects_id_get(
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_put", skip(principal, state, project), fields(project_id = %id))]
+#[tracing::instrument(name = "projects_id_put", skip(principal, state, dry_run, project), fields(project_id = %id))]
 async fn projects_id_put(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
     Json(project): Json<Project>,
-) -> Result<AppJson<Project>, AppError> {
+) -> Result<Mutation<AppJson<Project>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
         CedrusActions::PutProject.value(),
@@ -180,16 +544,19 @@ async fn projects_id_put(
         return Err(AppError::Forbidden);
     }
 
-    let project = state.cedrus.project_update(id, project).await?;
+    let project = dry_run
+        .mutate(state.cedrus.project_update(id, project))
+        .await?;
 
-    Ok(AppJson(project))
+    Ok(project.map(|project| AppJson(project.redacted())))
 }
 
 #[utoipa::path(
-    delete,
-    path = "/v1/projects/{id}",
+    put,
+    path = "/v1/projects/{id}/read-only",
+    request_body = ReadOnly,
     params(
-        ("id" = Uuid, Path, description = "Project id")
+        ("id" = Uuid, Path, description = "Project id, the admin project toggles the whole server")
     ),
     responses(
         (status = 200, description = "Project", body = Project)
@@ -199,577 +566,617 @@ async fn projects_id_put(
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_delete", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_delete(
+#[tracing::instrument(name = "projects_id_read_only_put", skip(principal, state, read_only), fields(project_id = %id))]
+async fn projects_id_read_only_put(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    Json(read_only): Json<ReadOnly>,
 ) -> Result<AppJson<Project>, AppError> {
-    if id.is_nil() {
-        return Err(AppError::Forbidden);
-    }
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::DeleteProject.value(),
+        CedrusActions::PutProject.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let project = state.cedrus.project_remove(id).await?;
+    let project = state
+        .cedrus
+        .project_read_only_set(id, read_only.read_only)
+        .await?;
 
-    Ok(AppJson(project))
+    Ok(AppJson(project.redacted()))
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/identity-source",
+    delete,
+    path = "/v1/projects/{id}",
     params(
         ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
     responses(
-        (status = 200, description = "Identity source", body = Option<IdentitySource>)
+        (status = 200, description = "Project", body = Project)
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_identity_source_get", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_identity_source_get(
+#[tracing::instrument(name = "projects_id_delete", skip(principal, state, dry_run), fields(project_id = %id))]
+async fn projects_id_delete(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<AppJson<Option<IdentitySource>>, AppError> {
+    Query(dry_run): Query<DryRunParams>,
+) -> Result<Mutation<AppJson<Project>>, AppError> {
+    if id.is_nil() {
+        return Err(AppError::Forbidden);
+    }
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectIdentitySource.value(),
+        CedrusActions::DeleteProject.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let identity_source = state.cedrus.project_identity_source_find(id).await?;
+    let project = dry_run.mutate(state.cedrus.project_remove(id)).await?;
 
-    Ok(AppJson(identity_source))
+    Ok(project.map(|project| AppJson(project.redacted())))
 }
 
 #[utoipa::path(
-    put,
-    path = "/v1/projects/{id}/identity-source",
+    get,
+    path = "/v1/projects/{id}/stats",
     params(
         ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = IdentitySource,
     responses(
-        (status = 200, description = "Identity source", body = IdentitySource)
+        (status = 200, description = "Project statistics", body = ProjectStats),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_identity_source_put", skip(principal, state, identity_source), fields(project_id = %id))]
-async fn projects_id_identity_source_put(
+#[tracing::instrument(name = "projects_id_stats_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_stats_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(identity_source): Json<IdentitySource>,
-) -> Result<(), AppError> {
+) -> Result<AppJson<ProjectStats>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PutProjectIdentitySource.value(),
+        CedrusActions::GetProjectStats.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state
-        .cedrus
-        .project_identity_source_update(id, identity_source)
-        .await?;
+    let stats = state.cedrus.project_stats(id).await?;
 
-    Ok(())
+    Ok(AppJson(stats))
 }
 
 #[utoipa::path(
-    delete,
-    path = "/v1/projects/{id}/identity-source",
+    get,
+    path = "/v1/projects/{id}/consistency",
     params(
         ("id" = Uuid, Path, description = "Project id")
     ),
     responses(
-        (status = 200, description = "Identity source deleted")
+        (status = 200, description = "Consistency report between Database, Cache and memory", body = ConsistencyReport),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_identity_source_delete", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_identity_source_delete(
+#[tracing::instrument(name = "projects_id_consistency_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_consistency_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<(), AppError> {
+) -> Result<AppJson<ConsistencyReport>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::DeleteProjectIdentitySource.value(),
-        Project::entity_uid(id),
+        CedrusActions::GetProjectConsistency.value(),
+        CedrusEntities::ApplicationCedrus.value(),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state.cedrus.project_identity_source_remove(id).await?;
+    let report = state.cedrus.project_consistency_check(id, false).await?;
 
-    Ok(())
+    Ok(AppJson(report))
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/schema",
+    post,
+    path = "/v1/projects/{id}/consistency",
     params(
         ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
     responses(
-        (status = 200, description = "Schema", body = Option<Schema>)
+        (status = 200, description = "Consistency report after repairing drift", body = ConsistencyReport),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_schema_get", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_schema_get(
+#[tracing::instrument(name = "projects_id_consistency_post", skip(principal, state, dry_run), fields(project_id = %id))]
+async fn projects_id_consistency_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<AppJson<Option<Schema>>, AppError> {
+    Query(dry_run): Query<DryRunParams>,
+) -> Result<AppJson<ConsistencyReport>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectSchema.value(),
-        Project::entity_uid(id),
+        CedrusActions::PostProjectConsistency.value(),
+        CedrusEntities::ApplicationCedrus.value(),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let schema = state.cedrus.project_schema_find(id).await?;
+    let report = state
+        .cedrus
+        .project_consistency_check(id, !dry_run.is_dry_run())
+        .await?;
 
-    Ok(AppJson(schema))
+    Ok(AppJson(report))
 }
 
 #[utoipa::path(
     put,
-    path = "/v1/projects/{id}/schema",
+    path = "/v1/projects/{id}/state",
     params(
-        ("id" = Uuid, Path, description = "Project id")
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
-    request_body = Schema,
+    request_body = ProjectState,
     responses(
-        (status = 200, description = "Schema created", body = Schema)
+        (status = 200, description = "Changes made for the project to match the state", body = StatePlan),
+        (status = 400, description = "Invalid schema or policies"),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_schema_put", skip(principal, state, schema), fields(project_id = %id))]
-async fn projects_id_schema_put(
+#[tracing::instrument(name = "projects_id_state_put", skip(principal, state, dry_run, project_state), fields(project_id = %id))]
+async fn projects_id_state_put(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(schema): Json<Schema>,
-) -> Result<(), AppError> {
-    if !state.cedrus.is_allow(
-        principal,
-        CedrusActions::PutProjectSchema.value(),
-        Project::entity_uid(id),
-    ) {
+    Query(dry_run): Query<DryRunParams>,
+    Json(project_state): Json<ProjectState>,
+) -> Result<Mutation<AppJson<StatePlan>>, AppError> {
+    if !STATE_ACTIONS.iter().all(|action| {
+        state
+            .cedrus
+            .is_allow(principal.clone(), action.value(), Project::entity_uid(id))
+    }) {
         return Err(AppError::Forbidden);
     }
 
-    state.cedrus.project_schema_update(id, schema).await?;
+    // The state is planned as a whole, not step by step as applied
+    let plan = state
+        .cedrus
+        .project_state_apply(id, project_state, !dry_run.is_dry_run())
+        .await?;
 
-    Ok(())
+    Ok(match dry_run.is_dry_run() {
+        true => Mutation::DryRun(Box::new(DryRunReport {
+            plan,
+            ..Default::default()
+        })),
+        false => Mutation::Applied(AppJson(plan)),
+    })
 }
 
 #[utoipa::path(
-    delete,
-    path = "/v1/projects/{id}/schema",
+    get,
+    path = "/v1/projects/{id}/gitops",
     params(
         ("id" = Uuid, Path, description = "Project id")
     ),
     responses(
-        (status = 200, description = "Schema deleted")
+        (status = 200, description = "Drift between the project and its Git repository", body = GitOpsReport),
+        (status = 400, description = "Project not synced from Git or repository unreadable"),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_schema_delete", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_schema_delete(
+#[tracing::instrument(name = "projects_id_gitops_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_gitops_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<(), AppError> {
+) -> Result<AppJson<GitOpsReport>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::DeleteProjectSchema.value(),
+        CedrusActions::GetProjectPolicies.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state.cedrus.project_schema_remove(id).await?;
+    let report = state.cedrus.project_gitops_sync(id, false).await?;
 
-    Ok(())
+    Ok(AppJson(report))
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/schema/cedar",
+    post,
+    path = "/v1/projects/{id}/gitops/sync",
     params(
         ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
     responses(
-        (status = 200, description = "Schema in Cedar syntax", body = CedarSyntax)
+        (status = 200, description = "Drift between the project and its Git repository, reconciled", body = GitOpsReport),
+        (status = 400, description = "Project not synced from Git or repository unreadable"),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_schema_cedar_get", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_schema_cedar_get(
+#[tracing::instrument(name = "projects_id_gitops_sync_post", skip(principal, state, dry_run), fields(project_id = %id))]
+async fn projects_id_gitops_sync_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<AppJson<CedarSyntax>, AppError> {
-    if !state.cedrus.is_allow(
-        principal,
-        CedrusActions::GetProjectSchema.value(),
-        Project::entity_uid(id),
-    ) {
+    Query(dry_run): Query<DryRunParams>,
+) -> Result<AppJson<GitOpsReport>, AppError> {
+    // The sync removes the template links of removed templates, as a state would
+    if !STATE_ACTIONS.iter().all(|action| {
+        state
+            .cedrus
+            .is_allow(principal.clone(), action.value(), Project::entity_uid(id))
+    }) {
         return Err(AppError::Forbidden);
     }
 
-    let schema = state.cedrus.project_schema_find(id).await?;
-    let schema = match schema {
-        Some(schema) => {
-            let value = serde_json::to_value(&schema).map_err(AppError::SerdeJsonError)?;
-            let cedar_schema = cedar_policy::SchemaFragment::from_json_value(value)
-                .map_err(AppError::SchemaError)?;
-            let schema = cedar_schema
-                .to_cedarschema()
-                .map_err(AppError::ToCedarSchemaError)?;
-            CedarSyntax {
-                cedar: Some(schema),
-            }
-        }
-        None => return Ok(AppJson(CedarSyntax { cedar: None })),
-    };
+    let report = state
+        .cedrus
+        .project_gitops_sync(id, !dry_run.is_dry_run())
+        .await?;
 
-    Ok(AppJson(schema))
+    Ok(AppJson(report))
 }
 
 #[utoipa::path(
-    put,
-    path = "/v1/projects/{id}/schema/cedar",
+    get,
+    path = "/v1/projects/{id}/environments",
     params(
         ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = CedarSyntax,
     responses(
-        (status = 200, description = "Schema in Cedar syntax")
+        (status = 200, description = "Environments of the project, sorted by name", body = Vec<Project>),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_schema_cedar_put", skip(principal, state, syntax), fields(project_id = %id))]
-async fn projects_id_schema_cedar_put(
+#[tracing::instrument(name = "projects_id_environments_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_environments_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(syntax): Json<CedarSyntax>,
-) -> Result<AppJson<Schema>, AppError> {
+) -> Result<AppJson<Vec<Project>>, AppError> {
     if !state.cedrus.is_allow(
-        principal,
-        CedrusActions::PutProjectSchema.value(),
+        principal.clone(),
+        CedrusActions::GetProject.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let schema = match syntax.cedar {
-        Some(str) => {
-            let (cedar_schema, _warnings) =
-                cedar_policy::SchemaFragment::from_cedarschema_str(&str)
-                    .map_err(AppError::CedarSchemaError)?;
-            let json = cedar_schema
-                .to_json_value()
-                .map_err(AppError::SchemaError)?;
-            let schema: Schema = serde_json::from_value(json).map_err(AppError::SerdeJsonError)?;
-            schema
-        }
-        None => return Err(AppError::BadRequest),
-    };
-
-    state
-        .cedrus
-        .project_schema_update(id, schema.clone())
-        .await?;
+    let mut environments = state.cedrus.project_environments_find(id).await?;
+    environments.retain(|p| {
+        state.cedrus.is_allow(
+            principal.clone(),
+            CedrusActions::GetProject.value(),
+            Project::entity_uid(p.id),
+        )
+    });
 
-    Ok(AppJson(schema))
+    Ok(AppJson(
+        environments.into_iter().map(Project::redacted).collect(),
+    ))
 }
 
 #[utoipa::path(
     post,
-    path = "/v1/projects/{id}/schema/validate/cedar",
+    path = "/v1/projects/{id}/environments",
+    request_body = NewEnvironment,
     params(
         ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = CedarSyntax,
     responses(
-        (status = 200, description = "Schema", body = Schema)
+        (status = 201, description = "Project of the environment", body = Project,
+            headers(("Location" = String, description = "URL of the project of the environment"))),
+        (status = 400, description = "Invalid name, or the project is an environment itself"),
+        (status = 404, description = "Project not found"),
+        (status = 409, description = "The project has an environment of that name")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_schema_validate_cedar_post", skip(principal, state, syntax), fields(project_id = %id))]
-async fn projects_id_schema_validate_cedar_post(
+#[tracing::instrument(name = "projects_id_environments_post", skip(principal, state, environment), fields(project_id = %id))]
+async fn projects_id_environments_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(syntax): Json<CedarSyntax>,
-) -> Result<AppJson<Schema>, AppError> {
+    Json(environment): Json<NewEnvironment>,
+) -> Result<(StatusCode, HeaderMap, AppJson<Project>), AppError> {
     if !state.cedrus.is_allow(
-        principal,
-        CedrusActions::PutProjectSchema.value(),
+        principal.clone(),
+        CedrusActions::PostProject.value(),
+        CedrusEntities::ApplicationCedrus.value(),
+    ) || !state.cedrus.is_allow(
+        principal.clone(),
+        CedrusActions::PutProject.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let Some(str) = syntax.cedar else {
-        return Err(AppError::BadRequest);
-    };
-
-    let (cedar_schema, _warnings) = cedar_policy::SchemaFragment::from_cedarschema_str(&str)
-        .map_err(|_| AppError::BadRequest)?;
-    let json = cedar_schema
-        .to_json_value()
-        .map_err(AppError::SchemaError)?;
-    let schema: Schema = serde_json::from_value(json).map_err(AppError::SerdeJsonError)?;
+    let project = state
+        .cedrus
+        .project_environment_create(id, environment.name, principal)
+        .await?;
 
-    Ok(AppJson(schema))
+    Ok((
+        StatusCode::CREATED,
+        location_headers(&format!("/v1/projects/{}", project.id)),
+        AppJson(project.redacted()),
+    ))
 }
 
 #[utoipa::path(
     post,
-    path = "/v1/projects/{id}/schema/validate/json",
+    path = "/v1/projects/{id}/environments/{name}/promote",
+    request_body = Promotion,
     params(
-        ("id" = Uuid, Path, description = "Project id")
+        ("id" = Uuid, Path, description = "Project id"),
+        ("name" = String, Path, description = "Environment the policy set is copied from")
     ),
-    request_body = Schema,
     responses(
-        (status = 200, description = "CedarSyntax", body = CedarSyntax)
+        (status = 200, description = "Changes made to the target environment", body = PromotionReport),
+        (status = 400, description = "Policies failing validation against the schema"),
+        (status = 404, description = "Project or environment not found"),
+        (status = 409, description = "Policies of the source environment changed since the tested version"),
+        (status = 423, description = "Target environment read-only or synced from Git")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_schema_validate_json_post", skip(principal, state, schema), fields(project_id = %id))]
-async fn projects_id_schema_validate_json_post(
+#[tracing::instrument(name = "projects_id_environments_name_promote_post", skip(principal, state, promotion), fields(project_id = %id))]
+async fn projects_id_environments_name_promote_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path(id): Path<Uuid>,
-    Json(schema): Json<Schema>,
-) -> Result<AppJson<CedarSyntax>, AppError> {
+    Path((id, name)): Path<(Uuid, String)>,
+    Json(promotion): Json<Promotion>,
+) -> Result<AppJson<PromotionReport>, AppError> {
+    let (Some(from_id), Some(to_id)) = (
+        state.cedrus.project_environment_id(&id, &name),
+        state.cedrus.project_environment_id(&id, &promotion.to),
+    ) else {
+        return Err(AppError::NotFound);
+    };
+
+    let actions = [
+        CedrusActions::PostProjectTemplates,
+        CedrusActions::DeleteProjectTemplates,
+        CedrusActions::PostProjectPolicies,
+        CedrusActions::DeleteProjectPolicies,
+        CedrusActions::PostProjectTemplateLinks,
+        CedrusActions::DeleteProjectTemplateLinks,
+    ];
     if !state.cedrus.is_allow(
-        principal,
-        CedrusActions::PutProjectSchema.value(),
-        Project::entity_uid(id),
-    ) {
+        principal.clone(),
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(from_id),
+    ) || !actions.iter().all(|action| {
+        state.cedrus.is_allow(
+            principal.clone(),
+            action.value(),
+            Project::entity_uid(to_id),
+        )
+    }) {
         return Err(AppError::Forbidden);
     }
+    if state.cedrus.is_project_read_only(&to_id) || state.cedrus.is_project_gitops(&to_id) {
+        return Err(AppError::Locked);
+    }
 
-    let value = serde_json::to_value(&schema).map_err(|_| AppError::BadRequest)?;
-    let cedar_schema =
-        cedar_policy::SchemaFragment::from_json_value(value).map_err(|_| AppError::BadRequest)?;
-    let cedar = cedar_schema
-        .to_cedarschema()
-        .map_err(|_| AppError::BadRequest)?;
+    let report = state.cedrus.project_promote(id, &name, promotion).await?;
 
-    Ok(AppJson(CedarSyntax { cedar: Some(cedar) }))
+    Ok(AppJson(report))
 }
 
 #[utoipa::path(
     get,
-    path = "/v1/projects/{id}/entities",
+    path = "/v1/projects/{id}/candidate",
     params(
-        ("id" = Uuid, Path, description = "Project id"),
-        QueryParams
+        ("id" = Uuid, Path, description = "Project id")
     ),
     responses(
-        (status = 200, description = "Entities page", body = PageList<Entity>)
+        (status = 200, description = "Decisions of the candidate compared to the live ones on this node", body = CandidateReport),
+        (status = 404, description = "Project without candidate")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_entities_get", skip(principal, state, query_params), fields(project_id = %id))]
-async fn projects_id_entities_get(
+#[tracing::instrument(name = "projects_id_candidate_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_candidate_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Query(query_params): Query<QueryParams>,
-) -> Result<AppJson<PageList<Entity>>, AppError> {
+) -> Result<AppJson<CandidateReport>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectEntities.value(),
+        CedrusActions::GetProjectPolicies.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let page = state
-        .cedrus
-        .project_entities_find(id, query_params.into())
-        .await?;
-
-    Ok(AppJson(page))
+    Ok(AppJson(state.cedrus.project_candidate_report(&id)?))
 }
 
 #[utoipa::path(
-    post,
-    path = "/v1/projects/{id}/entities",
+    put,
+    path = "/v1/projects/{id}/candidate",
+    request_body = ProjectCandidate,
     params(
         ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = Vec<Entity>,
     responses(
-        (status = 200, description = "Entities added")
+        (status = 200, description = "Candidate attached, replacing the previous one", body = CandidateReport),
+        (status = 400, description = "Candidate failing to build or to validate against the schema"),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_entities_post", skip(principal, state, entities), fields(project_id = %id))]
-async fn projects_id_entities_post(
+#[tracing::instrument(name = "projects_id_candidate_put", skip(principal, state, candidate), fields(project_id = %id))]
+async fn projects_id_candidate_put(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(entities): Json<Vec<Entity>>,
-) -> Result<(), AppError> {
+    Json(candidate): Json<ProjectCandidate>,
+) -> Result<AppJson<CandidateReport>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectEntities.value(),
+        CedrusActions::PostProjectPolicies.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state.cedrus.project_entities_add(id, entities).await?;
+    state
+        .cedrus
+        .project_candidate_set(id, Some(candidate))
+        .await?;
 
-    Ok(())
+    Ok(AppJson(state.cedrus.project_candidate_report(&id)?))
 }
 
 #[utoipa::path(
     delete,
-    path = "/v1/projects/{id}/entities",
+    path = "/v1/projects/{id}/candidate",
     params(
         ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = Vec<EntityUid>,
     responses(
-        (status = 200, description = "Entities deleted")
+        (status = 200, description = "Candidate detached"),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_entities_delete", skip(principal, state, project_ids), fields(project_id = %id))]
-async fn projects_id_entities_delete(
+#[tracing::instrument(name = "projects_id_candidate_delete", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_candidate_delete(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(project_ids): Json<Vec<EntityUid>>,
 ) -> Result<(), AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::DeleteProjectEntities.value(),
+        CedrusActions::DeleteProjectPolicies.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state
-        .cedrus
-        .project_entities_remove(id, project_ids)
-        .await?;
+    state.cedrus.project_candidate_set(id, None).await?;
 
     Ok(())
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/policies",
+    post,
+    path = "/v1/projects/{id}/candidate/promote",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        QueryParams
+        ("id" = Uuid, Path, description = "Project id")
     ),
     responses(
-        (status = 200, description = "Get Policies", body = PageHash<PolicyId, Policy>),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Changes made to the live policies, the candidate detached", body = StatePlan),
+        (status = 400, description = "Policies failing validation against the schema"),
+        (status = 404, description = "Project without candidate"),
+        (status = 423, description = "Project read-only or synced from Git")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policies_get", skip(principal, state, query_params), fields(project_id = %id))]
-async fn projects_id_policies_get(
+#[tracing::instrument(name = "projects_id_candidate_promote_post", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_candidate_promote_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Query(query_params): Query<QueryParams>,
-) -> Result<AppJson<PageHash<PolicyId, Policy>>, AppError> {
-    if !state.cedrus.is_allow(
-        principal,
-        CedrusActions::GetProjectPolicies.value(),
-        Project::entity_uid(id),
-    ) {
+) -> Result<AppJson<StatePlan>, AppError> {
+    let actions = [
+        CedrusActions::PostProjectTemplates,
+        CedrusActions::DeleteProjectTemplates,
+        CedrusActions::PostProjectPolicies,
+        CedrusActions::DeleteProjectPolicies,
+        CedrusActions::PostProjectTemplateLinks,
+        CedrusActions::DeleteProjectTemplateLinks,
+    ];
+    if !actions.iter().all(|action| {
+        state
+            .cedrus
+            .is_allow(principal.clone(), action.value(), Project::entity_uid(id))
+    }) {
         return Err(AppError::Forbidden);
     }
+    if state.cedrus.is_project_read_only(&id) || state.cedrus.is_project_gitops(&id) {
+        return Err(AppError::Locked);
+    }
 
-    let page = state
-        .cedrus
-        .project_policies_find(id, query_params.into())
-        .await?;
+    let plan = state.cedrus.project_candidate_promote(id).await?;
 
-    Ok(AppJson(page))
+    Ok(AppJson(plan))
 }
 
 #[utoipa::path(
     post,
-    path = "/v1/projects/{id}/policies/validate/cedar",
+    path = "/v1/projects/{id}/resync",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
+        ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = CedarSyntax,
     responses(
-        (status = 200, description = "Get Policy Cedar", body = CedarSyntax),
-        (status = 400, description = "Bad request"),
+        (status = 200, description = "Project reloaded from the Database"),
         (status = 404, description = "Project not found")
     ),
     security(
@@ -777,670 +1184,3476 @@ async fn projects_id_policies_get(
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policies_validate_cedar_post", skip(principal, state, syntax), fields(project_id = %id))]
-async fn projects_id_policies_validate_cedar_post(
+#[tracing::instrument(name = "projects_id_resync_post", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_resync_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(syntax): Json<CedarSyntax>,
-) -> Result<AppJson<Policy>, AppError> {
+) -> Result<(), AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectPolicies.value(),
-        Project::entity_uid(id),
+        CedrusActions::PostProjectResync.value(),
+        CedrusEntities::ApplicationCedrus.value(),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let Some(cedar) = syntax.cedar else {
-        return Err(AppError::BadRequest);
-    };
+    state.cedrus.project_resync(id).await?;
 
-    let policy = cedar_policy::Policy::parse(None, cedar)?;
-    Ok(AppJson(policy.try_into()?))
+    Ok(())
 }
 
 #[utoipa::path(
-    post,
-    path = "/v1/projects/{id}/policies/validate/json",
+    get,
+    path = "/v1/projects/{id}/identity-source",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
+        ("id" = Uuid, Path, description = "Project id"),
     ),
-    request_body = Policy,
     responses(
-        (status = 200, description = "validate json policy", body = CedarSyntax),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Identity source", body = Option<IdentitySource>)
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policies_validate_json_post", skip(principal, state, policy), fields(project_id = %id))]
-async fn projects_id_policies_validate_json_post(
+#[tracing::instrument(name = "projects_id_identity_source_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_identity_source_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(policy): Json<Policy>,
-) -> Result<AppJson<CedarSyntax>, AppError> {
+) -> Result<AppJson<Option<IdentitySource>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectPolicies.value(),
+        CedrusActions::GetProjectIdentitySource.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let policy_id = PolicyId::from("policy0".to_string());
-    let cedar_policy = policy.to_cedar(policy_id)?;
-    let cedar = cedar_policy.to_cedar();
+    let identity_source = state.cedrus.project_identity_source_find(id).await?;
 
-    Ok(AppJson(CedarSyntax { cedar }))
+    Ok(AppJson(identity_source))
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/policies/{policyId}/cedar",
+    put,
+    path = "/v1/projects/{id}/identity-source",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        ("policyId" = String, Path, description = "Policy Id"),
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
+    request_body = IdentitySource,
     responses(
-        (status = 200, description = "Get Policy Cedar", body = CedarSyntax),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Identity source", body = IdentitySource)
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policies_policy_id_cedar_get", skip(principal, state), fields(project_id = %id, policy_id = %policy_id))]
-async fn projects_id_policies_policy_id_cedar_get(
+#[tracing::instrument(name = "projects_id_identity_source_put", skip(principal, state, dry_run, identity_source), fields(project_id = %id))]
+async fn projects_id_identity_source_put(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path((id, policy_id)): Path<(Uuid, String)>,
-) -> Result<AppJson<CedarSyntax>, AppError> {
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(identity_source): Json<IdentitySource>,
+) -> Result<Mutation<()>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectPolicies.value(),
+        CedrusActions::PutProjectIdentitySource.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let selector = Selector::Eq(Box::new(Selector::String(policy_id.clone())));
-    let map = HashMap::from([("policyId".to_string(), selector)]);
-    let query = cedrus_core::Query {
-        selector: Some(Selector::Record(map)),
-        ..Default::default()
-    };
-
-    let items = state.cedrus.project_policies_find(id, query).await?.items;
-    let (_, mut policy) = items.into_iter().next().ok_or(AppError::NotFound)?;
-    policy.annotations.insert("id".to_string(), Some(policy_id));
-    let json = serde_json::to_value(policy).map_err(AppError::SerdeJsonError)?;
-    let cedar_policy =
-        cedar_policy::Policy::from_json(None, json).map_err(AppError::PolicyFromJsonError)?;
-
-    let cedar = cedar_policy
-        .to_cedar()
-        .ok_or(AppError::InternalServerError)?;
+    let mutation = dry_run
+        .mutate(
+            state
+                .cedrus
+                .project_identity_source_update(id, identity_source),
+        )
+        .await?;
 
-    Ok(AppJson(CedarSyntax { cedar: Some(cedar) }))
+    Ok(mutation.map(|_| ()))
 }
 
 #[utoipa::path(
-    put,
-    path = "/v1/projects/{id}/policies/{policyId}/cedar",
+    delete,
+    path = "/v1/projects/{id}/identity-source",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        ("policyId" = String, Path, description = "Policy Id"),
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
-    request_body = CedarSyntax,
     responses(
-        (status = 200, description = "Get Policy Cedar"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Identity source deleted")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policies_policy_id_cedar_put", skip(principal, state, syntax), fields(project_id = %id, policy_id = %policy_id))]
-async fn projects_id_policies_policy_id_cedar_put(
+#[tracing::instrument(name = "projects_id_identity_source_delete", skip(principal, state, dry_run), fields(project_id = %id))]
+async fn projects_id_identity_source_delete(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path((id, policy_id)): Path<(Uuid, String)>,
-    Json(syntax): Json<CedarSyntax>,
-) -> Result<(), AppError> {
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+) -> Result<Mutation<()>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectPolicies.value(),
+        CedrusActions::DeleteProjectIdentitySource.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let Some(cedar) = syntax.cedar else {
-        return Err(AppError::BadRequest);
-    };
-
-    let cedar_policy_id = cedar_policy::PolicyId::new(policy_id.clone());
-    let cedar_policy = cedar_policy::Policy::parse(Some(cedar_policy_id), cedar)?;
-
-    let policy: Policy = cedar_policy.try_into()?;
-
-    state
-        .cedrus
-        .project_policies_add(id, HashMap::from([(policy_id.into(), policy)]))
+    let mutation = dry_run
+        .mutate(state.cedrus.project_identity_source_remove(id))
         .await?;
 
-    Ok(())
+    Ok(mutation.map(|_| ()))
 }
 
 #[utoipa::path(
-    post,
-    path = "/v1/projects/{id}/policies",
+    get,
+    path = "/v1/projects/{id}/schema",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
+        ("id" = Uuid, Path, description = "Project id"),
     ),
-    request_body = HashMap<PolicyId, Policy>,
     responses(
-        (status = 200, description = "add policies"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Schema", body = Option<Schema>,
+            headers(("etag" = String), ("last-modified" = String), ("cache-control" = String))),
+        (status = 304, description = "Schema unchanged since `If-None-Match` or `If-Modified-Since`")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policies_post", skip(principal, state, policies), fields(project_id = %id))]
-async fn projects_id_policies_post(
+#[tracing::instrument(name = "projects_id_schema_get", skip(principal, state, headers), fields(project_id = %id))]
+async fn projects_id_schema_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(policies): Json<HashMap<PolicyId, Policy>>,
-) -> Result<(), AppError> {
+    headers: HeaderMap,
+) -> Result<HttpResponse, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectPolicies.value(),
+        CedrusActions::GetProjectSchema.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state.cedrus.project_policies_add(id, policies).await?;
-
-    Ok(())
+    let schema = state.cedrus.project_schema_find(id).await?;
+    let schema = serde_json::to_value(schema).map_err(AppError::SerdeJsonError)?;
+
+    Ok(cached_response(
+        &headers,
+        state
+            .cedrus
+            .project_modified
+            .get(&id, ProjectResource::Schema),
+        schema,
+    ))
 }
 
 #[utoipa::path(
-    delete,
-    path = "/v1/projects/{id}/policies",
+    put,
+    path = "/v1/projects/{id}/schema",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
-    request_body = Vec<PolicyId>,
+    request_body = Schema,
     responses(
-        (status = 200, description = "delete policies"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Schema created", body = Schema)
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policies_delete", skip(principal, state, policy_ids), fields(project_id = %id))]
-async fn projects_id_policies_delete(
+#[tracing::instrument(name = "projects_id_schema_put", skip(principal, state, dry_run, schema), fields(project_id = %id))]
+async fn projects_id_schema_put(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(policy_ids): Json<Vec<PolicyId>>,
-) -> Result<(), AppError> {
+    Query(dry_run): Query<DryRunParams>,
+    Json(schema): Json<Schema>,
+) -> Result<Mutation<()>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::DeleteProjectPolicies.value(),
+        CedrusActions::PutProjectSchema.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state.cedrus.project_policies_remove(id, policy_ids).await?;
-
-    Ok(())
+    Ok(dry_run
+        .mutate(state.cedrus.project_schema_update(id, schema))
+        .await?)
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/templates",
+    delete,
+    path = "/v1/projects/{id}/schema",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        QueryParams
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
     responses(
-        (status = 200, description = "get templates", body = PageHash<PolicyId, Template>),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Schema deleted")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_templates_get", skip(principal, state, query_params), fields(project_id = %id))]
-async fn projects_id_templates_get(
+#[tracing::instrument(name = "projects_id_schema_delete", skip(principal, state, dry_run), fields(project_id = %id))]
+async fn projects_id_schema_delete(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Query(query_params): Query<QueryParams>,
-) -> Result<AppJson<PageHash<PolicyId, Template>>, AppError> {
+    Query(dry_run): Query<DryRunParams>,
+) -> Result<Mutation<()>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectTemplates.value(),
+        CedrusActions::DeleteProjectSchema.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let page = state
-        .cedrus
-        .project_templates_find(id, query_params.into())
-        .await?;
-
-    Ok(AppJson(page))
+    Ok(dry_run
+        .mutate(state.cedrus.project_schema_remove(id))
+        .await?)
 }
 
 #[utoipa::path(
-    post,
-    path = "/v1/projects/{id}/templates",
+    get,
+    path = "/v1/projects/{id}/schema/cedar",
     params(
-        ("id" = Uuid, Path, description = "Project Id")
+        ("id" = Uuid, Path, description = "Project id"),
     ),
-    request_body = HashMap<PolicyId, Template>,
     responses(
-        (status = 200, description = "add templates"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Schema in Cedar syntax", body = CedarSyntax)
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_templates_post", skip(principal, state, templates), fields(project_id = %id))]
-async fn projects_id_templates_post(
+#[tracing::instrument(name = "projects_id_schema_cedar_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_schema_cedar_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(templates): Json<HashMap<PolicyId, Template>>,
-) -> Result<(), AppError> {
+) -> Result<AppJson<CedarSyntax>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectTemplates.value(),
+        CedrusActions::GetProjectSchema.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state.cedrus.project_templates_add(id, templates).await?;
+    let cedar = match state.cedrus.project_schema_find(id).await? {
+        Some(schema) => Some(schema_to_cedar(&schema).map_err(AppError::CedarDiagnostics)?),
+        None => None,
+    };
 
-    Ok(())
+    Ok(AppJson(CedarSyntax { cedar }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/projects/{id}/schema/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams,
+        WarningsParams
+    ),
+    request_body = CedarSyntax,
+    responses(
+        (status = 200, description = "Schema, along with the parser warnings when `warnings=true`", body = ParsedSchema),
+        (status = 400, description = "Schema syntax errors")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_schema_cedar_put", skip(principal, state, dry_run, warnings, syntax), fields(project_id = %id))]
+async fn projects_id_schema_cedar_put(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Query(warnings): Query<WarningsParams>,
+    Json(syntax): Json<CedarSyntax>,
+) -> Result<Mutation<AppJson<ParsedSchema>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PutProjectSchema.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let Some(src) = syntax.cedar else {
+        return Err(AppError::BadRequest);
+    };
+    let cedar_schema = CedarSchema::parse(&src).map_err(AppError::CedarDiagnostics)?;
+
+    let mutation = dry_run
+        .mutate(
+            state
+                .cedrus
+                .project_schema_update(id, cedar_schema.schema.clone()),
+        )
+        .await?;
+
+    Ok(mutation.map(|()| AppJson(ParsedSchema::new(cedar_schema, &warnings))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/schema/validate/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        WarningsParams
+    ),
+    request_body = CedarSyntax,
+    responses(
+        (status = 200, description = "Schema, along with the parser warnings when `warnings=true`", body = ParsedSchema),
+        (status = 400, description = "Schema syntax errors")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_schema_validate_cedar_post", skip(principal, state, warnings, syntax), fields(project_id = %id))]
+async fn projects_id_schema_validate_cedar_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(warnings): Query<WarningsParams>,
+    Json(syntax): Json<CedarSyntax>,
+) -> Result<AppJson<ParsedSchema>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PutProjectSchema.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let Some(src) = syntax.cedar else {
+        return Err(AppError::BadRequest);
+    };
+
+    let cedar_schema = CedarSchema::parse(&src).map_err(AppError::CedarDiagnostics)?;
+
+    Ok(AppJson(ParsedSchema::new(cedar_schema, &warnings)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/schema/validate/json",
+    params(
+        ("id" = Uuid, Path, description = "Project id")
+    ),
+    request_body = Schema,
+    responses(
+        (status = 200, description = "CedarSyntax", body = CedarSyntax)
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_schema_validate_json_post", skip(principal, state, schema), fields(project_id = %id))]
+async fn projects_id_schema_validate_json_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(schema): Json<Schema>,
+) -> Result<AppJson<CedarSyntax>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PutProjectSchema.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let cedar = schema_to_cedar(&schema).map_err(AppError::CedarDiagnostics)?;
+
+    Ok(AppJson(CedarSyntax { cedar: Some(cedar) }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/schema/lint",
+    params(
+        ("id" = Uuid, Path, description = "Project id")
+    ),
+    request_body(content = Option<Schema>, description = "Schema to lint, the stored schema of the project when there is no body"),
+    responses(
+        (status = 200, description = "Schema findings", body = SchemaLintReport),
+        (status = 400, description = "No schema to lint")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_schema_lint_post", skip(principal, state, schema), fields(project_id = %id))]
+async fn projects_id_schema_lint_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    schema: Option<Json<Schema>>,
+) -> Result<AppJson<SchemaLintReport>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectSchema.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let report = state
+        .cedrus
+        .project_schema_lint(id, schema.map(|Json(schema)| schema))
+        .await?;
+
+    Ok(AppJson(report))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/schema/sdk",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        SdkParams
+    ),
+    responses(
+        (status = 200, description = "Typed helpers for the actions, entity types and contexts of the schema", content(
+            (String = "text/plain"),
+            (Value = "application/json")
+        )),
+        (status = 400, description = "The project has no schema")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_schema_sdk_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_schema_sdk_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<SdkParams>,
+) -> Result<HttpResponse, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectSchema.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let sdk = state.cedrus.project_schema_sdk(id, params.lang).await?;
+
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(params.lang.content_type()),
+        )],
+        sdk,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/entities",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        QueryParams,
+        AsOfParams
+    ),
+    responses(
+        (status = 200, description = "Entities page, or all matching entities (up to `limit`) as one JSON object per line when `Accept` is `application/x-ndjson`", content(
+            (PageList<Entity> = "application/json"),
+            (Entity = "application/x-ndjson")
+        ), headers(("etag" = String), ("last-modified" = String), ("cache-control" = String))),
+        (status = 304, description = "Entities page unchanged since `If-None-Match` or `If-Modified-Since`"),
+        (status = 400, description = "No history of the project covers `asOf`")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_entities_get", skip(principal, entity_types, state, headers, query_params), fields(project_id = %id))]
+async fn projects_id_entities_get(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Query(query_params): Query<QueryParams>,
+    Query(as_of): Query<AsOfParams>,
+) -> Result<HttpResponse, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let query: cedrus_core::Query = query_params.into();
+    if let Some(as_of) = as_of.as_of {
+        let mut entities = state.cedrus.project_entities_as_of(id, as_of).await?;
+        entities.retain(|e| entity_types.allows(e.uid().type_name()));
+        let entities = entities
+            .iter()
+            .map(|e| select_fields(&query, e))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(AppError::SerdeJsonError)?;
+        return Ok(AppJson(PageList::new(entities, None)).into_response());
+    }
+
+    if accepts_ndjson(&headers) {
+        let Some(_) = state.cedrus.project_find(id).await? else {
+            return Err(AppError::NotFound);
+        };
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(NDJSON_CHANNEL_CAPACITY);
+        let fields = query.clone();
+        tokio::spawn(async move {
+            state
+                .cedrus
+                .project_entities_stream(id, query, sender)
+                .await
+        });
+
+        let lines = ReceiverStream::new(receiver)
+            .filter(move |entity| {
+                entity
+                    .as_ref()
+                    .map_or(true, |e| entity_types.allows(e.uid().type_name()))
+            })
+            .map(move |entity| {
+                let entity = entity.map_err(|e| std::io::Error::other(e.to_string()))?;
+                let mut line =
+                    serde_json::to_vec(&fields.select_fields(serde_json::to_value(entity)?))?;
+                line.push(b'\n');
+                Ok::<_, std::io::Error>(line)
+            });
+        return Ok((
+            [(header::CONTENT_TYPE, HeaderValue::from_static(NDJSON))],
+            Body::from_stream(lines),
+        )
+            .into_response());
+    }
+
+    let mut page = state
+        .cedrus
+        .project_entities_find(id, query.clone())
+        .await?;
+    page.items
+        .retain(|e| entity_types.allows(e.uid().type_name()));
+    let entities = page
+        .items
+        .iter()
+        .map(|e| select_fields(&query, e))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AppError::SerdeJsonError)?;
+    let page = serde_json::to_value(PageList::new(entities, page.last_key))
+        .map_err(AppError::SerdeJsonError)?;
+
+    Ok(cached_response(
+        &headers,
+        state
+            .cedrus
+            .project_modified
+            .get(&id, ProjectResource::Entities),
+        page,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/entities",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
+    ),
+    request_body = Vec<Entity>,
+    responses(
+        (status = 201, description = "Entities added", body = Vec<Entity>)
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_entities_post", skip(principal, entity_types, state, dry_run, entities), fields(project_id = %id))]
+async fn projects_id_entities_post(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(entities): Json<Vec<Entity>>,
+) -> Result<Mutation<Created<Vec<Entity>>>, AppError> {
+    if !entity_types.allows_all(entities.iter().map(|e| e.uid())) {
+        return Err(AppError::Forbidden);
+    }
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let entities = dry_run
+        .mutate(state.cedrus.project_entities_add(id, entities))
+        .await?;
+
+    Ok(entities.map(|entities| {
+        (
+            StatusCode::CREATED,
+            created_location_headers(
+                &format!("/v1/projects/{id}/entities"),
+                entities.iter().map(|e| e.uid()),
+            ),
+            AppJson(entities),
+        )
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/entities/paths",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
+    ),
+    request_body = Vec<ResourcePath>,
+    responses(
+        (status = 201, description = "Resources and their containers added", body = Vec<Entity>),
+        (status = 400, description = "Path without segment")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_entities_paths_post", skip(principal, entity_types, state, dry_run, paths), fields(project_id = %id))]
+async fn projects_id_entities_paths_post(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(paths): Json<Vec<ResourcePath>>,
+) -> Result<Mutation<Created<Vec<Entity>>>, AppError> {
+    let entity_uids: Vec<EntityUid> = paths
+        .iter()
+        .flat_map(|path| path.entities())
+        .map(|entity| entity.uid().clone())
+        .collect();
+    if !entity_types.allows_all(entity_uids.iter()) {
+        return Err(AppError::Forbidden);
+    }
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let entities = dry_run
+        .mutate(state.cedrus.project_entities_paths_add(id, paths))
+        .await?;
+
+    Ok(entities.map(|entities| {
+        (
+            StatusCode::CREATED,
+            created_location_headers(
+                &format!("/v1/projects/{id}/entities"),
+                entities.iter().map(|e| e.uid()),
+            ),
+            AppJson(entities),
+        )
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/entities/{uid}",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        ("uid" = String, Path, description = "Entity uid, e.g. `App::User::alice`")
+    ),
+    responses(
+        (status = 200, description = "Get Entity", body = Entity),
+        (status = 404, description = "Project or entity not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_entities_uid_get", skip(principal, entity_types, state), fields(project_id = %id))]
+async fn projects_id_entities_uid_get(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path((id, uid)): Path<(Uuid, String)>,
+) -> Result<AppJson<Entity>, AppError> {
+    let entity = EntityUid::from(uid.as_str());
+    if !entity_types.allows(entity.type_name()) {
+        return Err(AppError::Forbidden);
+    }
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let eq = |value: &str| Selector::Eq(Box::new(Selector::String(value.to_string())));
+    let map = HashMap::from([
+        ("uid.type".to_string(), eq(entity.type_name())),
+        ("uid.id".to_string(), eq(entity.id())),
+    ]);
+    let query = cedrus_core::Query {
+        selector: Some(Selector::Record(map)),
+        ..Default::default()
+    };
+
+    let items = state.cedrus.project_entities_find(id, query).await?.items;
+    let entity = items
+        .into_iter()
+        .find(|e| e.uid() == &entity)
+        .ok_or(AppError::NotFound)?;
+
+    Ok(AppJson(entity))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/entities/{uid}/references",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        ("uid" = String, Path, description = "Entity uid, e.g. `App::User::alice`")
+    ),
+    responses(
+        (status = 200, description = "Static policies and template links naming the entity", body = EntityReferences),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_entities_uid_references_get", skip(principal, entity_types, state), fields(project_id = %id))]
+async fn projects_id_entities_uid_references_get(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path((id, uid)): Path<(Uuid, String)>,
+) -> Result<AppJson<EntityReferences>, AppError> {
+    let entity = EntityUid::from(uid.as_str());
+    if !entity_types.allows(entity.type_name()) {
+        return Err(AppError::Forbidden);
+    }
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let references = state.cedrus.project_entity_references(id, entity).await?;
+
+    Ok(AppJson(references))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/relations",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        RelationParams
+    ),
+    responses(
+        (status = 200, description = "Relations of the object, or to the subject", body = Vec<Relation>),
+        (status = 400, description = "Neither object nor subject")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_relations_get", skip(principal, entity_types, state), fields(project_id = %id))]
+async fn projects_id_relations_get(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<RelationParams>,
+) -> Result<AppJson<Vec<Relation>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let mut relations = state
+        .cedrus
+        .project_relations_find(
+            id,
+            params.object.as_deref().map(EntityUid::from),
+            params.subject.as_deref().map(EntityUid::from),
+            params.relation,
+        )
+        .await?;
+    relations.retain(|r| entity_types.allows(r.object.type_name()));
+
+    Ok(AppJson(relations))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/relations",
+    params(
+        ("id" = Uuid, Path, description = "Project id")
+    ),
+    request_body = Vec<Relation>,
+    responses(
+        (status = 200, description = "Objects updated", body = Vec<Entity>),
+        (status = 404, description = "Project or object not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_relations_post", skip(principal, entity_types, state, relations), fields(project_id = %id))]
+async fn projects_id_relations_post(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(relations): Json<Vec<Relation>>,
+) -> Result<AppJson<Vec<Entity>>, AppError> {
+    if !entity_types.allows_all(relations.iter().map(|r| &r.object)) {
+        return Err(AppError::Forbidden);
+    }
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let entities = state.cedrus.project_relations_add(id, relations).await?;
+
+    Ok(AppJson(entities))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/projects/{id}/relations",
+    params(
+        ("id" = Uuid, Path, description = "Project id")
+    ),
+    request_body = Vec<Relation>,
+    responses(
+        (status = 200, description = "Objects updated", body = Vec<Entity>),
+        (status = 404, description = "Project or object not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_relations_delete", skip(principal, entity_types, state, relations), fields(project_id = %id))]
+async fn projects_id_relations_delete(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(relations): Json<Vec<Relation>>,
+) -> Result<AppJson<Vec<Entity>>, AppError> {
+    if !entity_types.allows_all(relations.iter().map(|r| &r.object)) {
+        return Err(AppError::Forbidden);
+    }
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let entities = state.cedrus.project_relations_remove(id, relations).await?;
+
+    Ok(AppJson(entities))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/entities/sync",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
+    ),
+    request_body = EntitiesSync,
+    responses(
+        (status = 200, description = "Entities synchronized", body = EntitiesSyncReport),
+        (status = 400, description = "Duplicated or out of scope entities"),
+        (status = 404, description = "Project not found"),
+        (status = 409, description = "Removed entities named by template links, unless the project cascades")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_entities_sync_post", skip(principal, entity_types, state, dry_run, sync), fields(project_id = %id))]
+async fn projects_id_entities_sync_post(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(sync): Json<EntitiesSync>,
+) -> Result<Mutation<AppJson<EntitiesSyncReport>>, AppError> {
+    // A sync without `entityTypes` removes the entities of every type
+    if entity_types.is_restricted()
+        && (sync.entity_types.is_empty()
+            || !sync.entity_types.iter().all(|t| entity_types.allows(t)))
+    {
+        return Err(AppError::Forbidden);
+    }
+    if !entity_types.allows_all(sync.entities.iter().map(|e| e.uid())) {
+        return Err(AppError::Forbidden);
+    }
+    let allowed = [
+        CedrusActions::PostProjectEntities,
+        CedrusActions::DeleteProjectEntities,
+    ]
+    .iter()
+    .all(|action| {
+        state
+            .cedrus
+            .is_allow(principal.clone(), action.value(), Project::entity_uid(id))
+    });
+    if !allowed {
+        return Err(AppError::Forbidden);
+    }
+
+    let report = dry_run
+        .mutate(state.cedrus.project_entities_sync(id, sync))
+        .await?;
+
+    Ok(report.map(AppJson))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/projects/{id}/entities",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
+    ),
+    request_body = Vec<EntityUid>,
+    responses(
+        (status = 200, description = "Entities deleted"),
+        (status = 409, description = "Entities named by template links, unless the project cascades")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_entities_delete", skip(principal, entity_types, state, dry_run, project_ids), fields(project_id = %id))]
+async fn projects_id_entities_delete(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(project_ids): Json<Vec<EntityUid>>,
+) -> Result<Mutation<()>, AppError> {
+    if !entity_types.allows_all(&project_ids) {
+        return Err(AppError::Forbidden);
+    }
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(dry_run
+        .mutate(state.cedrus.project_entities_remove(id, project_ids))
+        .await?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/entities:batchDelete",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
+    ),
+    request_body = Vec<EntityUid>,
+    responses(
+        (status = 200, description = "Outcome per entity", body = [BatchDeleteResult<EntityUid>]),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found"),
+        (status = 409, description = "Entities named by template links, unless the project cascades")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_entities_batch_delete_post", skip(principal, entity_types, state, dry_run, entity_uids), fields(project_id = %id))]
+async fn projects_id_entities_batch_delete_post(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(entity_uids): Json<Vec<EntityUid>>,
+) -> Result<Mutation<AppJson<Vec<BatchDeleteResult<EntityUid>>>>, AppError> {
+    if !entity_types.allows_all(&entity_uids) {
+        return Err(AppError::Forbidden);
+    }
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectEntities.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let results = dry_run
+        .mutate(state.cedrus.project_entities_batch_remove(id, entity_uids))
+        .await?;
+
+    Ok(results.map(AppJson))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policies",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("annotation.{key}" = Option<String>, Query, description = "Filter by policy annotation value, e.g. annotation.owner=team-x"),
+        QueryParams,
+        AsOfParams
+    ),
+    responses(
+        (status = 200, description = "Get Policies", body = PageHash<PolicyId, Policy>,
+            headers(("etag" = String), ("last-modified" = String), ("cache-control" = String))),
+        (status = 304, description = "Policies unchanged since `If-None-Match` or `If-Modified-Since`"),
+        (status = 400, description = "Bad request, or no history of the project covers `asOf`"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_get", skip(principal, state, headers, query_params, params), fields(project_id = %id))]
+async fn projects_id_policies_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Query(query_params): Query<QueryParams>,
+    Query(as_of): Query<AsOfParams>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<HttpResponse, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let mut query: cedrus_core::Query = query_params.into();
+    if let Some(as_of) = as_of.as_of {
+        let policies = state.cedrus.project_policies_as_of(id, as_of).await?;
+        let policies = policies
+            .iter()
+            .map(|(policy_id, p)| Ok((policy_id.clone(), select_fields(&query, p)?)))
+            .collect::<Result<HashMap<_, _>, serde_json::Error>>()
+            .map_err(AppError::SerdeJsonError)?;
+        return Ok(AppJson(PageHash::new(policies, None)).into_response());
+    }
+
+    query.annotations = annotation_params(&params);
+    let fields = query.clone();
+
+    let page = state.cedrus.project_policies_find(id, query).await?;
+    let policies = page
+        .items
+        .iter()
+        .map(|(policy_id, p)| Ok((policy_id.clone(), select_fields(&fields, p)?)))
+        .collect::<Result<HashMap<_, _>, serde_json::Error>>()
+        .map_err(AppError::SerdeJsonError)?;
+    let page = serde_json::to_value(PageHash::new(policies, page.last_key))
+        .map_err(AppError::SerdeJsonError)?;
+
+    Ok(cached_response(
+        &headers,
+        state
+            .cedrus
+            .project_modified
+            .get(&id, ProjectResource::Policies),
+        page,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/revisions/{from}/diff/{to}",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("from" = chrono::DateTime<chrono::Utc>, Path, description = "Time of the policies before the changes"),
+        ("to" = chrono::DateTime<chrono::Utc>, Path, description = "Time of the policies after the changes"),
+        DiffParams
+    ),
+    responses(
+        (status = 200, description = "Static policies added, removed or modified between the two times", body = RevisionDiff),
+        (status = 400, description = "Bad request, or no history of the project covers `from` or `to`"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_revisions_diff_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_revisions_diff_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, from, to)): Path<(
+        Uuid,
+        chrono::DateTime<chrono::Utc>,
+        chrono::DateTime<chrono::Utc>,
+    )>,
+    Query(params): Query<DiffParams>,
+) -> Result<AppJson<RevisionDiff>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let diff = state
+        .cedrus
+        .project_revisions_diff(id, from, to, params.format)
+        .await?;
+
+    Ok(AppJson(diff))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policies/version",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    responses(
+        (status = 200, description = "Current policy set version", body = PolicyVersion,
+            headers(("x-policy-version" = String), ("etag" = String))),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_version_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_policies_version_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<(HeaderMap, AppJson<PolicyVersion>), AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let version = state.cedrus.project_policy_version(&id)?;
+
+    Ok((
+        policy_version_headers(&version),
+        AppJson(PolicyVersion {
+            project_id: id,
+            version,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policies/coverage",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    responses(
+        (status = 200, description = "Policy coverage of the schema", body = PolicyCoverageReport),
+        (status = 400, description = "Project has no schema"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_coverage_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_policies_coverage_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<AppJson<PolicyCoverageReport>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let report = state.cedrus.project_policy_coverage(&id)?;
+
+    Ok(AppJson(report))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/policies/validate/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    request_body = CedarSyntax,
+    responses(
+        (status = 200, description = "Get Policy Cedar", body = CedarSyntax),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_validate_cedar_post", skip(principal, state, syntax), fields(project_id = %id))]
+async fn projects_id_policies_validate_cedar_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(syntax): Json<CedarSyntax>,
+) -> Result<AppJson<Policy>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let Some(cedar) = syntax.cedar else {
+        return Err(AppError::BadRequest);
+    };
+
+    let policy = cedar_policy::Policy::parse(None, cedar)?;
+    Ok(AppJson(policy.try_into()?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/policies/validate/json",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    request_body = Policy,
+    responses(
+        (status = 200, description = "validate json policy", body = CedarSyntax),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_validate_json_post", skip(principal, state, policy), fields(project_id = %id))]
+async fn projects_id_policies_validate_json_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(policy): Json<Policy>,
+) -> Result<AppJson<CedarSyntax>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let policy_id = PolicyId::from("policy0".to_string());
+    let cedar_policy = policy.to_cedar(policy_id)?;
+    let cedar = cedar_policy.to_cedar();
+
+    Ok(AppJson(CedarSyntax { cedar }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policies/{policyId}",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("policyId" = String, Path, description = "Policy Id"),
+    ),
+    responses(
+        (status = 200, description = "Get Policy", body = Policy),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project or policy not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_policy_id_get", skip(principal, state), fields(project_id = %id, policy_id = %policy_id))]
+async fn projects_id_policies_policy_id_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, policy_id)): Path<(Uuid, String)>,
+) -> Result<AppJson<Policy>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let selector = Selector::Eq(Box::new(Selector::String(policy_id)));
+    let map = HashMap::from([("policyId".to_string(), selector)]);
+    let query = cedrus_core::Query {
+        selector: Some(Selector::Record(map)),
+        ..Default::default()
+    };
+
+    let items = state.cedrus.project_policies_find(id, query).await?.items;
+    let (_, policy) = items.into_iter().next().ok_or(AppError::NotFound)?;
+
+    Ok(AppJson(policy))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policies/{policyId}/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("policyId" = String, Path, description = "Policy Id"),
+    ),
+    responses(
+        (status = 200, description = "Get Policy Cedar", body = CedarSyntax),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_policy_id_cedar_get", skip(principal, state), fields(project_id = %id, policy_id = %policy_id))]
+async fn projects_id_policies_policy_id_cedar_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, policy_id)): Path<(Uuid, String)>,
+) -> Result<AppJson<CedarSyntax>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let selector = Selector::Eq(Box::new(Selector::String(policy_id.clone())));
+    let map = HashMap::from([("policyId".to_string(), selector)]);
+    let query = cedrus_core::Query {
+        selector: Some(Selector::Record(map)),
+        ..Default::default()
+    };
+
+    let items = state.cedrus.project_policies_find(id, query).await?.items;
+    let (_, mut policy) = items.into_iter().next().ok_or(AppError::NotFound)?;
+    policy.annotations.insert("id".to_string(), Some(policy_id));
+    let json = serde_json::to_value(policy).map_err(AppError::SerdeJsonError)?;
+    let cedar_policy =
+        cedar_policy::Policy::from_json(None, json).map_err(AppError::PolicyFromJsonError)?;
+
+    let cedar = cedar_policy
+        .to_cedar()
+        .ok_or(AppError::InternalServerError)?;
+
+    Ok(AppJson(CedarSyntax { cedar: Some(cedar) }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policies/{policyId}/references",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("policyId" = String, Path, description = "Policy Id"),
+    ),
+    responses(
+        (status = 200, description = "Entity attributes, tags and context keys the conditions of the policy read", body = AttributeReferences),
+        (status = 404, description = "Project or policy not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_policy_id_references_get", skip(principal, state), fields(project_id = %id, policy_id = %policy_id))]
+async fn projects_id_policies_policy_id_references_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, policy_id)): Path<(Uuid, String)>,
+) -> Result<AppJson<AttributeReferences>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let selector = Selector::Eq(Box::new(Selector::String(policy_id)));
+    let map = HashMap::from([("policyId".to_string(), selector)]);
+    let query = cedrus_core::Query {
+        selector: Some(Selector::Record(map)),
+        ..Default::default()
+    };
+
+    let items = state.cedrus.project_policies_find(id, query).await?.items;
+    let (_, policy) = items.into_iter().next().ok_or(AppError::NotFound)?;
+
+    Ok(AppJson(policy.references()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/projects/{id}/policies/{policyId}/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("policyId" = String, Path, description = "Policy Id"),
+        DryRunParams
+    ),
+    request_body = CedarSyntax,
+    responses(
+        (status = 200, description = "Get Policy Cedar"),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_policy_id_cedar_put", skip(principal, state, dry_run, syntax), fields(project_id = %id, policy_id = %policy_id))]
+async fn projects_id_policies_policy_id_cedar_put(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, policy_id)): Path<(Uuid, String)>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(syntax): Json<CedarSyntax>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let Some(cedar) = syntax.cedar else {
+        return Err(AppError::BadRequest);
+    };
+
+    let cedar_policy_id = cedar_policy::PolicyId::new(policy_id.clone());
+    let cedar_policy = cedar_policy::Policy::parse(Some(cedar_policy_id), cedar)?;
+
+    let policy: Policy = cedar_policy.try_into()?;
+
+    let policies = HashMap::from([(policy_id.into(), policy)]);
+    let mutation = dry_run
+        .mutate(state.cedrus.project_policies_add(id, policies))
+        .await?;
+
+    Ok(mutation.map(|_| ()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/policies",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        DryRunParams
+    ),
+    request_body = HashMap<PolicyId, Policy>,
+    responses(
+        (status = 201, description = "add policies", body = HashMap<PolicyId, Policy>),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_post", skip(principal, state, dry_run, policies), fields(project_id = %id))]
+async fn projects_id_policies_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(policies): Json<HashMap<PolicyId, Policy>>,
+) -> Result<Mutation<Created<HashMap<PolicyId, Policy>>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let policies = dry_run
+        .mutate(state.cedrus.project_policies_add(id, policies))
+        .await?;
+
+    Ok(policies.map(|policies| {
+        (
+            StatusCode::CREATED,
+            created_location_headers(&format!("/v1/projects/{id}/policies"), policies.keys()),
+            AppJson(policies),
+        )
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/projects/{id}/policies",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        DryRunParams
+    ),
+    request_body = Vec<PolicyId>,
+    responses(
+        (status = 200, description = "delete policies"),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_delete", skip(principal, state, dry_run, policy_ids), fields(project_id = %id))]
+async fn projects_id_policies_delete(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(policy_ids): Json<Vec<PolicyId>>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(dry_run
+        .mutate(state.cedrus.project_policies_remove(id, policy_ids))
+        .await?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/policies:batchDelete",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        DryRunParams
+    ),
+    request_body = Vec<PolicyId>,
+    responses(
+        (status = 200, description = "Outcome per policy", body = [BatchDeleteResult<PolicyId>]),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policies_batch_delete_post", skip(principal, state, dry_run, policy_ids), fields(project_id = %id))]
+async fn projects_id_policies_batch_delete_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(policy_ids): Json<Vec<PolicyId>>,
+) -> Result<Mutation<AppJson<Vec<BatchDeleteResult<PolicyId>>>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectPolicies.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let results = dry_run
+        .mutate(state.cedrus.project_policies_batch_remove(id, policy_ids))
+        .await?;
+
+    Ok(results.map(AppJson))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/templates",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        QueryParams
+    ),
+    responses(
+        (status = 200, description = "get templates", body = PageHash<PolicyId, Template>),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_get", skip(principal, state, query_params), fields(project_id = %id))]
+async fn projects_id_templates_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query_params): Query<QueryParams>,
+) -> Result<AppJson<PageHash<PolicyId, Template>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let page = state
+        .cedrus
+        .project_templates_find(id, query_params.into())
+        .await?;
+
+    Ok(AppJson(page))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/templates",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        DryRunParams
+    ),
+    request_body = HashMap<PolicyId, Template>,
+    responses(
+        (status = 201, description = "add templates", body = HashMap<PolicyId, Template>),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_post", skip(principal, state, dry_run, templates), fields(project_id = %id))]
+async fn projects_id_templates_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(templates): Json<HashMap<PolicyId, Template>>,
+) -> Result<Mutation<Created<HashMap<PolicyId, Template>>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let templates = dry_run
+        .mutate(state.cedrus.project_templates_add(id, templates))
+        .await?;
+
+    Ok(templates.map(|templates| {
+        (
+            StatusCode::CREATED,
+            created_location_headers(&format!("/v1/projects/{id}/templates"), templates.keys()),
+            AppJson(templates),
+        )
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/projects/{id}/templates",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ForceParams,
+        DryRunParams
+    ),
+    request_body = Vec<PolicyId>,
+    responses(
+        (status = 200, description = "delete templates"),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found"),
+        (status = 409, description = "Templates still referenced by template links")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_delete", skip(principal, state, dry_run, params, template_ids), fields(project_id = %id))]
+async fn projects_id_templates_delete(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Query(params): Query<ForceParams>,
+    Json(template_ids): Json<Vec<PolicyId>>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(dry_run
+        .mutate(state.cedrus.project_templates_remove(
+            id,
+            template_ids,
+            params.force.unwrap_or(false),
+        ))
+        .await?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/templates:batchDelete",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ForceParams,
+        DryRunParams
+    ),
+    request_body = Vec<PolicyId>,
+    responses(
+        (status = 200, description = "Outcome per template", body = [BatchDeleteResult<PolicyId>]),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_batch_delete_post", skip(principal, state, dry_run, params, template_ids), fields(project_id = %id))]
+async fn projects_id_templates_batch_delete_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Query(params): Query<ForceParams>,
+    Json(template_ids): Json<Vec<PolicyId>>,
+) -> Result<Mutation<AppJson<Vec<BatchDeleteResult<PolicyId>>>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let results = dry_run
+        .mutate(state.cedrus.project_templates_batch_remove(
+            id,
+            template_ids,
+            params.force.unwrap_or(false),
+        ))
+        .await?;
+
+    Ok(results.map(AppJson))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/templates/{templateId}",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("templateId" = String, Path, description = "Template Id"),
+    ),
+    responses(
+        (status = 200, description = "Get Template", body = Template),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project or template not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_template_id_get", skip(principal, state), fields(project_id = %id, template_id = %template_id))]
+async fn projects_id_templates_template_id_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, template_id)): Path<(Uuid, String)>,
+) -> Result<AppJson<Template>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let selector = Selector::Eq(Box::new(Selector::String(template_id)));
+    let map = HashMap::from([("policyId".to_string(), selector)]);
+    let query = cedrus_core::Query {
+        selector: Some(Selector::Record(map)),
+        ..Default::default()
+    };
+
+    let items = state.cedrus.project_templates_find(id, query).await?.items;
+    let (_, template) = items.into_iter().next().ok_or(AppError::NotFound)?;
+
+    Ok(AppJson(template))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/projects/{id}/templates/{templateId}",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("templateId" = String, Path, description = "Template Id"),
+        DryRunParams
+    ),
+    request_body = Template,
+    responses(
+        (status = 200, description = "update template"),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_template_id_put", skip(principal, state, dry_run, template), fields(project_id = %id, template_id = %template_id))]
+async fn projects_id_templates_template_id_put(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, template_id)): Path<(Uuid, String)>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(template): Json<Template>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let templates = HashMap::from([(template_id.into(), template)]);
+    let mutation = dry_run
+        .mutate(state.cedrus.project_templates_add(id, templates))
+        .await?;
+
+    Ok(mutation.map(|_| ()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/projects/{id}/templates/{templateId}",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("templateId" = String, Path, description = "Template Id"),
+        ForceParams,
+        DryRunParams
+    ),
+    responses(
+        (status = 200, description = "delete template"),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found"),
+        (status = 409, description = "Template still referenced by template links")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_template_id_delete", skip(principal, state, dry_run, params), fields(project_id = %id, template_id = %template_id))]
+async fn projects_id_templates_template_id_delete(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, template_id)): Path<(Uuid, String)>,
+    Query(dry_run): Query<DryRunParams>,
+    Query(params): Query<ForceParams>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(dry_run
+        .mutate(state.cedrus.project_templates_remove(
+            id,
+            vec![template_id.into()],
+            params.force.unwrap_or(false),
+        ))
+        .await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/templates/{templateId}/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("templateId" = String, Path, description = "Template Id"),
+    ),
+    responses(
+        (status = 200, description = "Get Template Cedar", body = CedarSyntax),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_template_id_cedar_get", skip(principal, state), fields(project_id = %id, template_id = %template_id))]
+async fn projects_id_templates_template_id_cedar_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, template_id)): Path<(Uuid, String)>,
+) -> Result<AppJson<CedarSyntax>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let selector = Selector::Eq(Box::new(Selector::String(template_id)));
+    let map = HashMap::from([("policyId".to_string(), selector)]);
+    let query = cedrus_core::Query {
+        selector: Some(Selector::Record(map)),
+        ..Default::default()
+    };
+
+    let items = state.cedrus.project_templates_find(id, query).await?.items;
+    let (_, template) = items.into_iter().next().ok_or(AppError::NotFound)?;
+    let json = serde_json::to_value(template).map_err(AppError::SerdeJsonError)?;
+    let cedar_template = cedar_policy::Template::from_json(None, json)?;
+    let cedar = cedar_template.to_cedar();
+
+    Ok(AppJson(CedarSyntax { cedar: Some(cedar) }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/projects/{id}/templates/{templateId}/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("templateId" = String, Path, description = "Template Id"),
+        DryRunParams
+    ),
+    request_body = CedarSyntax,
+    responses(
+        (status = 200, description = "update template cedar"),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_templates_template_id_cedar_put", skip(principal, state, dry_run, syntax), fields(project_id = %id, template_id = %template_id))]
+async fn projects_id_templates_template_id_cedar_put(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, template_id)): Path<(Uuid, String)>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(syntax): Json<CedarSyntax>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectTemplates.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let Some(cedar) = syntax.cedar else {
+        return Err(AppError::BadRequest);
+    };
+
+    let cedar_template_id = cedar_policy::PolicyId::new(template_id.clone());
+    let cedar_template = cedar_policy::Template::parse(Some(cedar_template_id), cedar)?;
+
+    let template: Template = cedar_template.try_into()?;
+
+    let templates = HashMap::from([(template_id.into(), template)]);
+    let mutation = dry_run
+        .mutate(state.cedrus.project_templates_add(id, templates))
+        .await?;
+
+    Ok(mutation.map(|_| ()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/template-links",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        QueryParams
+    ),
+    responses(
+        (status = 200, description = "get template links", body = PageList<TemplateLink>),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_template_links_get", skip(principal, state, query_params), fields(project_id = %id))]
+async fn projects_id_template_links_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query_params): Query<QueryParams>,
+) -> Result<AppJson<PageList<TemplateLink>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let page = state
+        .cedrus
+        .project_template_links_find(id, query_params.into())
+        .await?;
+
+    Ok(AppJson(page))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/template-links",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        DryRunParams
+    ),
+    request_body = Vec<TemplateLink>,
+    responses(
+        (status = 201, description = "add template links", body = Vec<TemplateLink>),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_template_links_post", skip(principal, state, dry_run, template_links), fields(project_id = %id))]
+async fn projects_id_template_links_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(template_links): Json<Vec<TemplateLink>>,
+) -> Result<Mutation<Created<Vec<TemplateLink>>>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let template_links = dry_run
+        .mutate(state.cedrus.project_template_links_add(id, template_links))
+        .await?;
+
+    Ok(template_links.map(|template_links| {
+        (
+            StatusCode::CREATED,
+            created_location_headers(
+                &format!("/v1/projects/{id}/template-links"),
+                template_links.iter().map(|link| &link.new_id),
+            ),
+            AppJson(template_links),
+        )
+    }))
+}
+
+// Principals come as JSON, or as CSV rows with the template and resource in the query
+fn template_link_batch(
+    headers: &HeaderMap,
+    params: TemplateLinkBatchParams,
+    body: &str,
+) -> Option<TemplateLinkBatch> {
+    let is_csv = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/csv"));
+    if !is_csv {
+        return serde_json::from_str(body).ok();
+    }
+
+    Some(TemplateLinkBatch {
+        template_id: PolicyId::from(params.template_id?),
+        principals: TemplateLinkBatch::principals_from_csv(body)?,
+        selector: None,
+        resource: params.resource.as_deref().map(EntityUid::from),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/template-links:batchCreate",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        TemplateLinkBatchParams
+    ),
+    request_body(content(
+        (TemplateLinkBatch = "application/json"),
+        (String = "text/csv")
+    ), description = "Template and principals to link, or CSV rows of principals `type,id`"),
+    responses(
+        (status = 201, description = "Template links stored, one per principal, with deterministic ids", body = Vec<TemplateLink>),
+        (status = 400, description = "Bad request, or the template needs a resource"),
+        (status = 404, description = "Project or template not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_template_links_batch_create_post", skip(principal, state, params, headers, body), fields(project_id = %id))]
+async fn projects_id_template_links_batch_create_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<TemplateLinkBatchParams>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<(StatusCode, HeaderMap, AppJson<Vec<TemplateLink>>), AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let batch = template_link_batch(&headers, params, &body).ok_or(AppError::BadRequest)?;
+    let template_links = state
+        .cedrus
+        .project_template_links_batch_add(id, batch)
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        created_location_headers(
+            &format!("/v1/projects/{id}/template-links"),
+            template_links.iter().map(|link| &link.new_id),
+        ),
+        AppJson(template_links),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/projects/{id}/template-links",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        DryRunParams
+    ),
+    request_body = Vec<(PolicyId, PolicyId)>,
+    responses(
+        (status = 200, description = "delete template links"),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_template_links_delete", skip(principal, state, dry_run, template_link_ids), fields(project_id = %id))]
+async fn projects_id_template_links_delete(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(template_link_ids): Json<Vec<PolicyId>>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(dry_run
+        .mutate(
+            state
+                .cedrus
+                .project_template_links_remove(id, template_link_ids),
+        )
+        .await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/template-links/{policyId}",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("policyId" = String, Path, description = "Id of the linked policy"),
+    ),
+    responses(
+        (status = 200, description = "Get Template Link", body = TemplateLink),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project or template link not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_template_links_policy_id_get", skip(principal, state), fields(project_id = %id, policy_id = %policy_id))]
+async fn projects_id_template_links_policy_id_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, policy_id)): Path<(Uuid, String)>,
+) -> Result<AppJson<TemplateLink>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let selector = Selector::Eq(Box::new(Selector::String(policy_id)));
+    let map = HashMap::from([("newId".to_string(), selector)]);
+    let query = cedrus_core::Query {
+        selector: Some(Selector::Record(map)),
+        ..Default::default()
+    };
+
+    let items = state
+        .cedrus
+        .project_template_links_find(id, query)
+        .await?
+        .items;
+    let template_link = items.into_iter().next().ok_or(AppError::NotFound)?;
+
+    Ok(AppJson(template_link))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/template-links/{templateId}/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("templateId" = String, Path, description = "Template Id"),
+    ),
+    responses(
+        (status = 200, description = "Get Template Link Cedar", body = CedarSyntax),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_template_links_policy_id_cedar_get", skip(principal, state), fields(project_id = %id, template_id = %template_id))]
+async fn projects_id_template_links_policy_id_cedar_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, template_id)): Path<(Uuid, String)>,
+) -> Result<AppJson<CedarSyntax>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let selector = Selector::Eq(Box::new(Selector::String(template_id)));
+    let map = HashMap::from([("newId".to_string(), selector)]);
+    let query = cedrus_core::Query {
+        selector: Some(Selector::Record(map)),
+        ..Default::default()
+    };
+
+    let items = state.cedrus.project_templates_find(id, query).await?.items;
+    let (_, template) = items.into_iter().next().ok_or(AppError::NotFound)?;
+    let json = serde_json::to_value(template).map_err(AppError::SerdeJsonError)?;
+    let cedar_template = cedar_policy::Template::from_json(None, json)?;
+    let cedar = cedar_template.to_cedar();
+
+    Ok(AppJson(CedarSyntax { cedar: Some(cedar) }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/projects/{id}/template-links/{templateId}/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("templateId" = String, Path, description = "Template Id"),
+        DryRunParams
+    ),
+    request_body = CedarSyntax,
+    responses(
+        (status = 200, description = "update template link cedar"),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_template_links_policy_id_cedar_put", skip(principal, state, dry_run, syntax), fields(project_id = %id, template_id = %template_id))]
+async fn projects_id_template_links_policy_id_cedar_put(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, template_id)): Path<(Uuid, String)>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(syntax): Json<CedarSyntax>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let Some(cedar) = syntax.cedar else {
+        return Err(AppError::BadRequest);
+    };
+
+    let cedar_template_id = cedar_policy::PolicyId::new(template_id.clone());
+    let cedar_template = cedar_policy::Template::parse(Some(cedar_template_id), cedar)?;
+
+    let template: Template = cedar_template.try_into()?;
+
+    let templates = HashMap::from([(template_id.into(), template)]);
+    let mutation = dry_run
+        .mutate(state.cedrus.project_templates_add(id, templates))
+        .await?;
+
+    Ok(mutation.map(|_| ()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policy-set",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    responses(
+        (status = 200, description = "Get Policy Set Cedar Syntax", body = PolicySet),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policy_set_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_policy_set_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<AppJson<PolicySet>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let query = cedrus_core::Query::new();
+    let static_policies = state
+        .cedrus
+        .project_policies_find(id, query.clone())
+        .await?
+        .items;
+    let templates = state
+        .cedrus
+        .project_templates_find(id, query.clone())
+        .await?
+        .items;
+    let template_links = state
+        .cedrus
+        .project_template_links_find(id, query)
+        .await?
+        .items;
+
+    let policy_set = PolicySet {
+        static_policies,
+        templates,
+        template_links,
+    };
+
+    Ok(AppJson(policy_set))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policy-set/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    responses(
+        (status = 200, description = "Get Policy Set Cedar Syntax", body = CedarSyntax),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policy_set_cedar_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_policy_set_cedar_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<AppJson<CedarSyntax>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectTemplateLinks.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let query = cedrus_core::Query::new();
+    let static_policies = state
+        .cedrus
+        .project_policies_find(id, query.clone())
+        .await?
+        .items;
+    let templates = state
+        .cedrus
+        .project_templates_find(id, query.clone())
+        .await?
+        .items;
+    let template_links = state
+        .cedrus
+        .project_template_links_find(id, query)
+        .await?
+        .items;
+
+    let policy_set = PolicySet {
+        static_policies,
+        templates,
+        template_links,
+    };
+
+    let cedar_policy_set: cedar_policy::PolicySet = policy_set.try_into()?;
+    let cedar = cedar_policy_set.to_cedar();
+
+    Ok(AppJson(CedarSyntax { cedar }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/policy-set/cedar",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        DryRunParams
+    ),
+    request_body = CedarSyntax,
+    responses(
+        (status = 201, description = "Policies and templates of the Cedar text added, by their @id", body = PolicySet),
+        (status = 400, description = "Syntax errors, or policies without or with a duplicated @id"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policy_set_cedar_post", skip(principal, state, dry_run, syntax), fields(project_id = %id))]
+async fn projects_id_policy_set_cedar_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(syntax): Json<CedarSyntax>,
+) -> Result<Mutation<Created<PolicySet>>, AppError> {
+    let actions = [
+        CedrusActions::PostProjectTemplates,
+        CedrusActions::PostProjectPolicies,
+    ];
+    if !actions.iter().all(|action| {
+        state
+            .cedrus
+            .is_allow(principal.clone(), action.value(), Project::entity_uid(id))
+    }) {
+        return Err(AppError::Forbidden);
+    }
+
+    let Some(cedar) = syntax.cedar else {
+        return Err(AppError::BadRequest);
+    };
+    let policy_set = policy_set_from_cedar(&cedar).map_err(AppError::CedarDiagnostics)?;
+
+    let policy_set = dry_run
+        .mutate(state.cedrus.project_policy_set_add(id, policy_set))
+        .await?;
+
+    Ok(policy_set.map(|policy_set| {
+        (
+            StatusCode::CREATED,
+            location_headers(&format!("/v1/projects/{id}/policy-set")),
+            AppJson(policy_set),
+        )
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/projects/{id}/policy-set/bundle",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    responses(
+        (status = 200, description = "Get the policy set signed as a bundle", body = PolicyBundle),
+        (status = 400, description = "No bundle private key configured"),
+        (status = 403, description = "Not allowed to read the policies, templates and template links of the project"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policy_set_bundle_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_policy_set_bundle_get(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<AppJson<PolicyBundle>, AppError> {
+    let actions = [
+        CedrusActions::GetProjectPolicies,
+        CedrusActions::GetProjectTemplates,
+        CedrusActions::GetProjectTemplateLinks,
+    ];
+    if !actions.iter().all(|action| {
+        state
+            .cedrus
+            .is_allow(principal.clone(), action.value(), Project::entity_uid(id))
+    }) {
+        return Err(AppError::Forbidden);
+    }
+
+    let bundle = state.cedrus.project_bundle_export(id).await?;
+
+    Ok(AppJson(bundle))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/policy-set/bundle",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        DryRunParams
+    ),
+    request_body = PolicyBundle,
+    responses(
+        (status = 201, description = "Bundle verified and its policy set added", body = PolicySet),
+        (status = 400, description = "Bundle not signed by a trusted key, exported from another project, or invalid for the project"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_policy_set_bundle_post", skip(principal, state, dry_run, bundle), fields(project_id = %id))]
+async fn projects_id_policy_set_bundle_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(bundle): Json<PolicyBundle>,
+) -> Result<Mutation<Created<PolicySet>>, AppError> {
+    let actions = [
+        CedrusActions::PostProjectTemplates,
+        CedrusActions::PostProjectPolicies,
+        CedrusActions::PostProjectTemplateLinks,
+    ];
+    if !actions.iter().all(|action| {
+        state
+            .cedrus
+            .is_allow(principal.clone(), action.value(), Project::entity_uid(id))
+    }) {
+        return Err(AppError::Forbidden);
+    }
+
+    let policy_set = dry_run
+        .mutate(state.cedrus.project_bundle_import(id, bundle))
+        .await?;
+
+    Ok(policy_set.map(|policy_set| {
+        (
+            StatusCode::CREATED,
+            location_headers(&format!("/v1/projects/{id}/policy-set")),
+            AppJson(policy_set),
+        )
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/is-authorized",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("x-policy-version" = Option<String>, Header, description = "Expected policy set version"),
+    ),
+    request_body = IsAuthorizedRequest,
+    responses(
+        (status = 200, description = "is authorized", body = Response,
+            headers(("x-policy-version" = String), ("etag" = String))),
+        (status = 400, description = "Bad request, or the action or entity types are outside the schema of a strict project"),
+        (status = 404, description = "Project not found"),
+        (status = 412, description = "Policy set version is stale"),
+        (status = 503, description = "Evaluation timed out, or the evaluation queue of the project is full")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_is_authorized_post", skip(principal, state, request_headers, request), fields(project_id = %id))]
+async fn projects_id_is_authorized_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    request_headers: HeaderMap,
+    Json(request): Json<IsAuthorizedRequest>,
+) -> Result<(HeaderMap, AppJson<Response>), AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectIsAuthorized.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+    if request.sync {
+        state.cedrus.project_sync(&id).await?;
+    }
+
+    // Evaluated against the policies whose version is checked and answered
+    let policies = state.cedrus.project_policies(&id)?;
+    let version = policies.version.clone();
+    if is_policy_version_stale(&version, &request_headers) {
+        return Err(AppError::PreconditionFailed);
+    }
+
+    let principal_entity = match &request.token {
+        Some(token) => Some(state.cedrus.project_token_principal(&id, token).await?),
+        None => None,
+    };
+    let principal = principal_entity
+        .as_ref()
+        .map_or(request.principal, |entity| entity.uid().clone());
+    let sampled = state.sampler.as_ref().map(|_| Request {
+        principal: principal.clone(),
+        action: request.action.clone(),
+        resource: request.resource.clone(),
+        context: request.context.clone(),
+    });
+    let action = state
+        .cedrus
+        .deny_rates
+        .is_enabled()
+        .then(|| request.action.clone());
+    let shadowed = state.cedrus.project_candidates.contains_key(&id).then(|| {
+        let request = Request {
+            principal: principal.clone(),
+            action: request.action.clone(),
+            resource: request.resource.clone(),
+            context: request.context.clone(),
+        };
+        (vec![request], principal_entity.clone())
+    });
+
+    let shared = state.clone();
+    let answer = state
+        .cedrus
+        .evaluate(&[id], move || {
+            shared.cedrus.is_authorized(
+                &id,
+                principal,
+                request.action,
+                request.resource,
+                request.context,
+                principal_entity,
+                Some(policies),
+            )
+        })
+        .await?;
+
+    state
+        .cedrus
+        .decisions_record(&id, action.as_slice(), std::slice::from_ref(&answer));
+    if let Some((requests, principal_entity)) = shadowed {
+        candidate_shadow(&state, id, requests, principal_entity, vec![answer.clone()]);
+    }
+    if let (Some(sampler), Some(sampled)) = (&state.sampler, sampled) {
+        sampler.record(
+            &state.cedrus,
+            &id,
+            &version,
+            [sampled],
+            std::slice::from_ref(&answer),
+        );
+    }
+
+    Ok((policy_version_headers(&version), AppJson(answer)))
+}
+
+// Evaluates requests against the candidate policy set of the project off the response path,
+// the live decisions being already answered, or drops them when no evaluation slot is free
+fn candidate_shadow(
+    state: &Arc<AppState>,
+    id: Uuid,
+    requests: Vec<Request>,
+    principal_entity: Option<Entity>,
+    answers: Vec<Response>,
+) {
+    let Some(permits) = state.cedrus.project_candidate_slots(&id, requests.len()) else {
+        return;
+    };
+    let shared = state.clone();
+    tokio::task::spawn_blocking(move || {
+        let _permits = permits;
+        if let Err(e) =
+            shared
+                .cedrus
+                .project_candidate_evaluate(&id, requests, principal_entity, &answers)
+        {
+            tracing::debug!("Candidate evaluation of project {} failed: {:?}", id, e);
+        }
+    });
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/replay",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    request_body = ReplayRequest,
+    responses(
+        (status = 200, description = "Decision as of the replayed time", body = Response),
+        (status = 400, description = "Bad request, or no history of the project covers `asOf`"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_replay_post", skip(principal, state, replay), fields(project_id = %id))]
+async fn projects_id_replay_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(replay): Json<ReplayRequest>,
+) -> Result<AppJson<Response>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectReplay.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let answer = state
+        .cedrus
+        .project_replay(id, replay.as_of, replay.request)
+        .await?;
+
+    Ok(AppJson(answer))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/benchmark",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    request_body = Benchmark,
+    responses(
+        (status = 200, description = "Outcomes and latency percentiles of the authorizations", body = BenchmarkReport),
+        (status = 400, description = "Iterations out of range, or no requests and no schema to synthesize them from"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_benchmark_post", skip(principal, state, benchmark), fields(project_id = %id))]
+async fn projects_id_benchmark_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(benchmark): Json<Benchmark>,
+) -> Result<AppJson<BenchmarkReport>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectBenchmark.value(),
+        CedrusEntities::ApplicationCedrus.value(),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    // Thousands of evaluations in a row: they hold an evaluation slot of the project and run
+    // on a blocking thread, off the async workers
+    let permits = state.cedrus.evaluation_slots.acquire(&[id]).await?;
+    let shared = state.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        let _permits = permits;
+        shared.cedrus.project_benchmark(&id, benchmark)
+    })
+    .await
+    .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))?;
+
+    Ok(AppJson(report))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/generate",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+    ),
+    request_body = GeneratorOptions,
+    responses(
+        (status = 200, description = "Random entities and requests conforming to the schema", body = GeneratedData),
+        (status = 400, description = "Options out of range, or the project has no schema"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_generate_post", skip(principal, state, options), fields(project_id = %id))]
+async fn projects_id_generate_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(options): Json<GeneratorOptions>,
+) -> Result<AppJson<GeneratedData>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::GetProjectSchema.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let data = state.cedrus.project_generate(&id, options)?;
+
+    Ok(AppJson(data))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/is-authorized",
+    request_body = CombinedRequest,
+    responses(
+        (status = 200, description = "Combined decision and the decision of each project", body = CombinedResponse),
+        (status = 400, description = "No project, or the request is outside the schema of a strict project"),
+        (status = 404, description = "Project not found"),
+        (status = 503, description = "Evaluation of a project timed out, or its evaluation queue is full")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_is_authorized_post", skip(principal, state, combined))]
+async fn projects_is_authorized_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Json(combined): Json<CombinedRequest>,
+) -> Result<AppJson<CombinedResponse>, AppError> {
+    let allowed = combined.projects.iter().all(|id| {
+        state.cedrus.is_allow(
+            principal.clone(),
+            CedrusActions::PostProjectIsAuthorized.value(),
+            Project::entity_uid(*id),
+        )
+    });
+    if !allowed {
+        return Err(AppError::Forbidden);
+    }
+    for id in &combined.projects {
+        if combined.sync {
+            state.cedrus.project_sync(id).await?;
+        } else {
+            state.cedrus.project_refresh(id).await?;
+        }
+    }
+
+    let shared = state.clone();
+    let project_ids = combined.projects.clone();
+    let answer = state
+        .cedrus
+        .evaluate(&project_ids, move || {
+            shared.cedrus.is_authorized_combined(
+                &combined.projects,
+                combined.request,
+                combined.strategy,
+            )
+        })
+        .await?;
+
+    Ok(AppJson(answer))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/is-authorized-batch",
+    params(
+        ("id" = Uuid, Path, description = "Project Id"),
+        ("x-policy-version" = Option<String>, Header, description = "Expected policy set version"),
+    ),
+    request_body = IsAuthorizedRequests,
+    responses(
+        (status = 200, description = "is authorized", body = Vec<Response>,
+            headers(("x-policy-version" = String), ("etag" = String))),
+        (status = 400, description = "Bad request, or the action or entity types of a request are outside the schema of a strict project"),
+        (status = 404, description = "Project not found"),
+        (status = 412, description = "Policy set version is stale"),
+        (status = 503, description = "Evaluation timed out, or the evaluation queue of the project is full")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_is_authorized_batch_post", skip(principal, state, request_headers, request), fields(project_id = %id))]
+async fn projects_id_is_authorized_batch_post(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    request_headers: HeaderMap,
+    Json(request): Json<IsAuthorizedRequests>,
+) -> Result<(HeaderMap, AppJson<Vec<Response>>), AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::PostProjectIsAuthorized.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+    if request.sync {
+        state.cedrus.project_sync(&id).await?;
+    }
+
+    // Evaluated against the policies whose version is checked and answered
+    let policies = state.cedrus.project_policies(&id)?;
+    let version = policies.version.clone();
+    if is_policy_version_stale(&version, &request_headers) {
+        return Err(AppError::PreconditionFailed);
+    }
+
+    let principal_entity = match &request.token {
+        Some(token) => Some(state.cedrus.project_token_principal(&id, token).await?),
+        None => None,
+    };
+
+    let sampled = state.sampler.as_ref().map(|_| {
+        let principal = principal_entity.as_ref().map(|entity| entity.uid());
+        request
+            .requests
+            .iter()
+            .map(|request| sampled_request(request, principal))
+            .collect::<Vec<_>>()
+    });
+    let actions: Vec<EntityUid> = match state.cedrus.deny_rates.is_enabled() {
+        true => request
+            .requests
+            .iter()
+            .map(|request| request.action.clone())
+            .collect(),
+        false => Vec::new(),
+    };
+    let shadowed = state
+        .cedrus
+        .project_candidates
+        .contains_key(&id)
+        .then(|| (request.requests.clone(), principal_entity.clone()));
+
+    let answers = state
+        .cedrus
+        .is_authorized_batch(
+            &id,
+            request.requests,
+            principal_entity,
+            request.timings,
+            Some(policies),
+        )
+        .await?;
+
+    state.cedrus.decisions_record(&id, &actions, &answers);
+    if let Some((requests, principal_entity)) = shadowed {
+        candidate_shadow(&state, id, requests, principal_entity, answers.clone());
+    }
+    if let (Some(sampler), Some(sampled)) = (&state.sampler, sampled) {
+        sampler.record(&state.cedrus, &id, &version, sampled, &answers);
+    }
+
+    Ok((policy_version_headers(&version), AppJson(answers)))
 }
 
 #[utoipa::path(
-    delete,
-    path = "/v1/projects/{id}/templates",
+    get,
+    path = "/v1/projects/{id}/api-keys",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
+        ("id" = Uuid, Path, description = "Project id"),
+        QueryParams,
     ),
-    request_body = Vec<PolicyId>,
     responses(
-        (status = 200, description = "delete templates"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "API Keys list", body = PageList<ApiKey>)
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_templates_delete", skip(principal, state, template_ids), fields(project_id = %id))]
-async fn projects_id_templates_delete(
+#[tracing::instrument(name = "projects_id_apikeys_get", skip(principal, state), fields(project_id = %id))]
+async fn projects_id_apikeys_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(template_ids): Json<Vec<PolicyId>>,
-) -> Result<(), AppError> {
+    Query(query_params): Query<QueryParams>,
+) -> Result<AppJson<PageList<ApiKey>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::DeleteProjectTemplates.value(),
+        CedrusActions::GetProjectApiKey.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state
-        .cedrus
-        .project_templates_remove(id, template_ids)
-        .await?;
+    let query = query_params.into();
+    let apikeys = state.cedrus.project_apikeys_find(id, query).await?;
 
-    Ok(())
+    Ok(AppJson(apikeys))
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/templates/{templateId}/cedar",
+    post,
+    path = "/v1/projects/{id}/api-keys",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        ("templateId" = String, Path, description = "Template Id"),
+        ("id" = Uuid, Path, description = "Project id"),
+        DryRunParams
     ),
+    request_body = ApiKey,
     responses(
-        (status = 200, description = "Get Template Cedar", body = CedarSyntax),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "API Key added", body = ApiKey)
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_templates_template_id_cedar_get", skip(principal, state), fields(project_id = %id, template_id = %template_id))]
-async fn projects_id_templates_template_id_cedar_get(
+#[tracing::instrument(name = "projects_id_apikeys_post", skip(principal, state, dry_run, apikey), fields(project_id = %id))]
+async fn projects_id_apikeys_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path((id, template_id)): Path<(Uuid, String)>,
-) -> Result<AppJson<CedarSyntax>, AppError> {
+    Path(id): Path<Uuid>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(apikey): Json<ApiKey>,
+) -> Result<Mutation<AppJson<ApiKey>>, AppError> {
     if !state.cedrus.is_allow(
-        principal,
-        CedrusActions::GetProjectTemplates.value(),
+        principal.clone(),
+        CedrusActions::PostProjectApiKey.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let selector = Selector::Eq(Box::new(Selector::String(template_id)));
-    let map = HashMap::from([("policyId".to_string(), selector)]);
-    let query = cedrus_core::Query {
-        selector: Some(Selector::Record(map)),
-        ..Default::default()
-    };
-
-    let items = state.cedrus.project_templates_find(id, query).await?.items;
-    let (_, template) = items.into_iter().next().ok_or(AppError::NotFound)?;
-    let json = serde_json::to_value(template).map_err(AppError::SerdeJsonError)?;
-    let cedar_template = cedar_policy::Template::from_json(None, json)?;
-    let cedar = cedar_template.to_cedar();
+    // Keys may act as an entity of the project, such as a service, but never as another
+    // principal of the admin project
+    let mut apikey = apikey;
+    if apikey.owner.type_name().is_empty() {
+        apikey.owner = principal;
+    } else if apikey.owner != principal
+        && state.cedrus.is_admin_entity_type(apikey.owner.type_name())
+    {
+        return Err(AppError::Forbidden);
+    }
+    let apikey = dry_run
+        .mutate(state.cedrus.project_apikeys_add(id, apikey))
+        .await?;
 
-    Ok(AppJson(CedarSyntax { cedar: Some(cedar) }))
+    Ok(apikey.map(AppJson))
 }
 
 #[utoipa::path(
     put,
-    path = "/v1/projects/{id}/templates/{templateId}/cedar",
+    path = "/v1/projects/{id}/api-keys/{key}",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        ("templateId" = String, Path, description = "Template Id"),
+        ("id" = Uuid, Path, description = "Project id"),
+        ("key" = Uuid, Path, description = "API key id"),
+        DryRunParams
     ),
-    request_body = CedarSyntax,
+    request_body = ApiKey,
     responses(
-        (status = 200, description = "update template cedar"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "API Key updated", body = ApiKey)
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_templates_template_id_cedar_put", skip(principal, state, syntax), fields(project_id = %id, template_id = %template_id))]
-async fn projects_id_templates_template_id_cedar_put(
+#[tracing::instrument(name = "projects_id_apikeys_key_put", skip(principal, state, dry_run, apikey), fields(project_id = %id, key = %key))]
+async fn projects_id_apikeys_key_put(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path((id, template_id)): Path<(Uuid, String)>,
-    Json(syntax): Json<CedarSyntax>,
-) -> Result<(), AppError> {
+    Path((id, key)): Path<(Uuid, Uuid)>,
+    Query(dry_run): Query<DryRunParams>,
+    Json(apikey): Json<ApiKey>,
+) -> Result<Mutation<AppJson<ApiKey>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectTemplates.value(),
+        CedrusActions::PutProjectApiKey.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let Some(cedar) = syntax.cedar else {
+    if key != apikey.id {
         return Err(AppError::BadRequest);
-    };
+    }
 
-    let cedar_template_id = cedar_policy::PolicyId::new(template_id.clone());
-    let cedar_template = cedar_policy::Template::parse(Some(cedar_template_id), cedar)?;
+    let apikey = dry_run
+        .mutate(state.cedrus.project_apikeys_update(id, apikey))
+        .await?;
 
-    let template: Template = cedar_template.try_into()?;
+    Ok(apikey.map(AppJson))
+}
 
-    state
-        .cedrus
-        .project_templates_add(id, HashMap::from([(template_id.into(), template)]))
+#[utoipa::path(
+    delete,
+    path = "/v1/projects/{id}/api-keys/{key}",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        ("key" = Uuid, Path, description = "API key id"),
+        DryRunParams
+    ),
+    responses(
+        (status = 200, description = "API Key deleted")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_apikeys_key_delete", skip(principal, state, dry_run), fields(project_id = %id, key = %key))]
+async fn projects_id_apikeys_key_delete(
+    Extension(principal): Extension<EntityUid>,
+    State(state): State<Arc<AppState>>,
+    Path((id, key)): Path<(Uuid, Uuid)>,
+    Query(dry_run): Query<DryRunParams>,
+) -> Result<Mutation<()>, AppError> {
+    if !state.cedrus.is_allow(
+        principal,
+        CedrusActions::DeleteProjectApiKey.value(),
+        Project::entity_uid(id),
+    ) {
+        return Err(AppError::Forbidden);
+    }
+
+    let mutation = dry_run
+        .mutate(state.cedrus.project_apikeys_remove(id, key))
         .await?;
 
-    Ok(())
+    Ok(mutation.map(|_| ()))
 }
 
 #[utoipa::path(
     get,
-    path = "/v1/projects/{id}/template-links",
+    path = "/v1/projects/{id}/roles",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        QueryParams
+        ("id" = Uuid, Path, description = "Project id, roles are only defined in the nil project"),
+        QueryParams,
     ),
     responses(
-        (status = 200, description = "get template links", body = PageList<TemplateLink>),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Roles list", body = PageList<Role>),
+        (status = 400, description = "Bad request")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_template_links_get", skip(principal, state, query_params), fields(project_id = %id))]
-async fn projects_id_template_links_get(
+#[tracing::instrument(name = "projects_id_roles_get", skip(principal, state, query_params), fields(project_id = %id))]
+async fn projects_id_roles_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     Query(query_params): Query<QueryParams>,
-) -> Result<AppJson<PageList<TemplateLink>>, AppError> {
+) -> Result<AppJson<PageList<Role>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectTemplateLinks.value(),
+        CedrusActions::GetProjectRoles.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let page = state
+    let roles = state
         .cedrus
-        .project_template_links_find(id, query_params.into())
+        .project_roles_find(id, query_params.into())
         .await?;
 
-    Ok(AppJson(page))
+    Ok(AppJson(roles))
 }
 
 #[utoipa::path(
     post,
-    path = "/v1/projects/{id}/template-links",
+    path = "/v1/projects/{id}/roles",
     params(
-        ("id" = Uuid, Path, description = "Project Id")
+        ("id" = Uuid, Path, description = "Project id, roles are only defined in the nil project")
     ),
-    request_body = Vec<TemplateLink>,
+    request_body = Vec<Role>,
     responses(
-        (status = 200, description = "add template links"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Roles added", body = Vec<Role>),
+        (status = 400, description = "Bad request")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_template_links_post", skip(principal, state, template_links), fields(project_id = %id))]
-async fn projects_id_template_links_post(
+#[tracing::instrument(name = "projects_id_roles_post", skip(principal, state, roles), fields(project_id = %id))]
+async fn projects_id_roles_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(template_links): Json<Vec<TemplateLink>>,
-) -> Result<(), AppError> {
+    Json(roles): Json<Vec<Role>>,
+) -> Result<AppJson<Vec<Role>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectTemplateLinks.value(),
+        CedrusActions::PostProjectRoles.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state
-        .cedrus
-        .project_template_links_add(id, template_links)
-        .await?;
+    let roles = state.cedrus.project_roles_add(id, roles).await?;
 
-    Ok(())
+    Ok(AppJson(roles))
 }
 
 #[utoipa::path(
     delete,
-    path = "/v1/projects/{id}/template-links",
+    path = "/v1/projects/{id}/roles",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
+        ("id" = Uuid, Path, description = "Project id, roles are only defined in the nil project")
     ),
-    request_body = Vec<(PolicyId, PolicyId)>,
+    request_body = Vec<String>,
     responses(
-        (status = 200, description = "delete template links"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Roles deleted"),
+        (status = 400, description = "Bad request")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_template_links_delete", skip(principal, state, template_link_ids), fields(project_id = %id))]
-async fn projects_id_template_links_delete(
+#[tracing::instrument(name = "projects_id_roles_delete", skip(principal, state, role_ids), fields(project_id = %id))]
+async fn projects_id_roles_delete(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(template_link_ids): Json<Vec<PolicyId>>,
+    Json(role_ids): Json<Vec<String>>,
 ) -> Result<(), AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::DeleteProjectTemplateLinks.value(),
+        CedrusActions::DeleteProjectRoles.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state
-        .cedrus
-        .project_template_links_remove(id, template_link_ids)
-        .await?;
+    state.cedrus.project_roles_remove(id, role_ids).await?;
 
     Ok(())
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/template-links/{templateId}/cedar",
+    post,
+    path = "/v1/projects/{id}/roles/{roleId}/principals",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        ("templateId" = String, Path, description = "Template Id"),
+        ("id" = Uuid, Path, description = "Project id the role is instantiated for"),
+        ("roleId" = String, Path, description = "Role id")
     ),
+    request_body = EntityUid,
     responses(
-        (status = 200, description = "Get Template Link Cedar", body = CedarSyntax),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Role assigned", body = Vec<TemplateLink>),
+        (status = 400, description = "Nil project, or a role template without a ?resource slot"),
+        (status = 404, description = "Project or role not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_template_links_policy_id_cedar_get", skip(principal, state), fields(project_id = %id, template_id = %template_id))]
-async fn projects_id_template_links_policy_id_cedar_get(
+#[tracing::instrument(name = "projects_id_roles_role_id_principals_post", skip(principal, state, assignee), fields(project_id = %id, role_id = %role_id))]
+async fn projects_id_roles_role_id_principals_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path((id, template_id)): Path<(Uuid, String)>,
-) -> Result<AppJson<CedarSyntax>, AppError> {
+    Path((id, role_id)): Path<(Uuid, String)>,
+    Json(assignee): Json<EntityUid>,
+) -> Result<AppJson<Vec<TemplateLink>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectTemplateLinks.value(),
+        CedrusActions::PostProjectRolePrincipals.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let selector = Selector::Eq(Box::new(Selector::String(template_id)));
-    let map = HashMap::from([("newId".to_string(), selector)]);
-    let query = cedrus_core::Query {
-        selector: Some(Selector::Record(map)),
-        ..Default::default()
-    };
-
-    let items = state.cedrus.project_templates_find(id, query).await?.items;
-    let (_, template) = items.into_iter().next().ok_or(AppError::NotFound)?;
-    let json = serde_json::to_value(template).map_err(AppError::SerdeJsonError)?;
-    let cedar_template = cedar_policy::Template::from_json(None, json)?;
-    let cedar = cedar_template.to_cedar();
+    let template_links = state
+        .cedrus
+        .project_role_assign(id, role_id, assignee)
+        .await?;
 
-    Ok(AppJson(CedarSyntax { cedar: Some(cedar) }))
+    Ok(AppJson(template_links))
 }
 
 #[utoipa::path(
-    put,
-    path = "/v1/projects/{id}/template-links/{templateId}/cedar",
+    delete,
+    path = "/v1/projects/{id}/roles/{roleId}/principals",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
-        ("templateId" = String, Path, description = "Template Id"),
+        ("id" = Uuid, Path, description = "Project id the role was instantiated for"),
+        ("roleId" = String, Path, description = "Role id")
     ),
-    request_body = CedarSyntax,
+    request_body = EntityUid,
     responses(
-        (status = 200, description = "update template link cedar"),
-        (status = 400, description = "Bad request"),
-        (status = 404, description = "Project not found")
+        (status = 200, description = "Role revoked"),
+        (status = 404, description = "Project or role assignment not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_template_links_policy_id_cedar_put", skip(principal, state, syntax), fields(project_id = %id, template_id = %template_id))]
-async fn projects_id_template_links_policy_id_cedar_put(
+#[tracing::instrument(name = "projects_id_roles_role_id_principals_delete", skip(principal, state, assignee), fields(project_id = %id, role_id = %role_id))]
+async fn projects_id_roles_role_id_principals_delete(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path((id, template_id)): Path<(Uuid, String)>,
-    Json(syntax): Json<CedarSyntax>,
+    Path((id, role_id)): Path<(Uuid, String)>,
+    Json(assignee): Json<EntityUid>,
 ) -> Result<(), AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectTemplateLinks.value(),
+        CedrusActions::DeleteProjectRolePrincipals.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let Some(cedar) = syntax.cedar else {
-        return Err(AppError::BadRequest);
-    };
-
-    let cedar_template_id = cedar_policy::PolicyId::new(template_id.clone());
-    let cedar_template = cedar_policy::Template::parse(Some(cedar_template_id), cedar)?;
-
-    let template: Template = cedar_template.try_into()?;
-
     state
         .cedrus
-        .project_templates_add(id, HashMap::from([(template_id.into(), template)]))
+        .project_role_revoke(id, role_id, assignee)
         .await?;
 
     Ok(())
@@ -1448,13 +4661,13 @@ async fn projects_id_template_links_policy_id_cedar_put(
 
 #[utoipa::path(
     get,
-    path = "/v1/projects/{id}/policy-set",
+    path = "/v1/projects/{id}/delegations",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
+        ("id" = Uuid, Path, description = "Project id"),
+        QueryParams,
     ),
     responses(
-        (status = 200, description = "Get Policy Set Cedar Syntax", body = PolicySet),
-        (status = 400, description = "Bad request"),
+        (status = 200, description = "Delegation policies of the project", body = PageHash<PolicyId, Policy>),
         (status = 404, description = "Project not found")
     ),
     security(
@@ -1462,54 +4675,38 @@ async fn projects_id_template_links_policy_id_cedar_put(
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policy_set_get", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_policy_set_get(
+#[tracing::instrument(name = "projects_id_delegations_get", skip(principal, state, query_params), fields(project_id = %id))]
+async fn projects_id_delegations_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<AppJson<PolicySet>, AppError> {
+    Query(query_params): Query<QueryParams>,
+) -> Result<AppJson<PageHash<PolicyId, Policy>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectTemplateLinks.value(),
+        CedrusActions::GetProjectDelegations.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let query = cedrus_core::Query::new();
-    let static_policies = state
-        .cedrus
-        .project_policies_find(id, query.clone())
-        .await?
-        .items;
-    let templates = state
-        .cedrus
-        .project_templates_find(id, query.clone())
-        .await?
-        .items;
-    let template_links = state
+    let delegations = state
         .cedrus
-        .project_template_links_find(id, query)
-        .await?
-        .items;
-
-    let policy_set = PolicySet {
-        static_policies,
-        templates,
-        template_links,
-    };
+        .project_delegations_find(id, query_params.into())
+        .await?;
 
-    Ok(AppJson(policy_set))
+    Ok(AppJson(delegations))
 }
 
 #[utoipa::path(
-    get,
-    path = "/v1/projects/{id}/policy-set/cedar",
+    post,
+    path = "/v1/projects/{id}/delegations",
     params(
-        ("id" = Uuid, Path, description = "Project Id"),
+        ("id" = Uuid, Path, description = "Project id")
     ),
+    request_body = Delegation,
     responses(
-        (status = 200, description = "Get Policy Set Cedar Syntax", body = CedarSyntax),
+        (status = 200, description = "Delegation policy added", body = HashMap<PolicyId, Policy>),
         (status = 400, description = "Bad request"),
         (status = 404, description = "Project not found")
     ),
@@ -1518,59 +4715,50 @@ async fn projects_id_policy_set_get(
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_policy_set_cedar_get", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_policy_set_cedar_get(
+#[tracing::instrument(name = "projects_id_delegations_post", skip(principal, state, delegation), fields(project_id = %id))]
+async fn projects_id_delegations_post(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<AppJson<CedarSyntax>, AppError> {
+    Json(delegation): Json<Delegation>,
+) -> Result<AppJson<HashMap<PolicyId, Policy>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectTemplateLinks.value(),
+        CedrusActions::PostProjectDelegations.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let query = cedrus_core::Query::new();
-    let static_policies = state
-        .cedrus
-        .project_policies_find(id, query.clone())
-        .await?
-        .items;
-    let templates = state
-        .cedrus
-        .project_templates_find(id, query.clone())
-        .await?
-        .items;
-    let template_links = state
+    let actions = delegation
+        .capability
+        .actions()
+        .iter()
+        .map(|action| action.value())
+        .collect();
+    let (policy_id, policy) = state
         .cedrus
-        .project_template_links_find(id, query)
-        .await?
-        .items;
-
-    let policy_set = PolicySet {
-        static_policies,
-        templates,
-        template_links,
-    };
-
-    let cedar_policy_set: cedar_policy::PolicySet = policy_set.try_into()?;
-    let cedar = cedar_policy_set.to_cedar();
+        .project_delegation_grant(
+            id,
+            delegation.capability.name(),
+            delegation.effect,
+            delegation.principal,
+            actions,
+        )
+        .await?;
 
-    Ok(AppJson(CedarSyntax { cedar }))
+    Ok(AppJson(HashMap::from([(policy_id, policy)])))
 }
 
 #[utoipa::path(
-    post,
-    path = "/v1/projects/{id}/is-authorized",
+    delete,
+    path = "/v1/projects/{id}/delegations",
     params(
-        ("id" = Uuid, Path, description = "Project Id")
+        ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = IsAuthorizedRequest,
+    request_body = Delegation,
     responses(
-        (status = 200, description = "is authorized", body = Response),
-        (status = 400, description = "Bad request"),
+        (status = 200, description = "Delegation policy removed"),
         (status = 404, description = "Project not found")
     ),
     security(
@@ -1578,42 +4766,117 @@ async fn projects_id_policy_set_cedar_get(
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_is_authorized_post", skip(principal, state, request), fields(project_id = %id))]
-async fn projects_id_is_authorized_post(
+#[tracing::instrument(name = "projects_id_delegations_delete", skip(principal, state, delegation), fields(project_id = %id))]
+async fn projects_id_delegations_delete(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(request): Json<IsAuthorizedRequest>,
-) -> Result<AppJson<Response>, AppError> {
+    Json(delegation): Json<Delegation>,
+) -> Result<(), AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectIsAuthorized.value(),
+        CedrusActions::DeleteProjectDelegations.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let answer = state.cedrus.is_authorized(
-        &id,
-        request.principal,
-        request.action,
-        request.resource,
-        request.context,
-    )?;
+    state
+        .cedrus
+        .project_delegation_revoke(
+            id,
+            delegation.capability.name(),
+            delegation.effect,
+            delegation.principal,
+        )
+        .await?;
 
-    Ok(AppJson(answer))
+    Ok(())
+}
+
+// A job reads or changes what the synchronous routes do, so it needs their actions and the
+// entity types of an API key, on top of posting jobs
+fn is_job_allowed(
+    state: &AppState,
+    principal: EntityUid,
+    entity_types: &EntityTypeScope,
+    id: Uuid,
+    task: &JobTask,
+) -> bool {
+    let mut actions = vec![CedrusActions::PostProjectJobs];
+    let in_scope = match task {
+        JobTask::Import(data) => {
+            if data.schema.is_some() {
+                actions.push(CedrusActions::PutProjectSchema);
+            }
+            if !data.entities.is_empty() {
+                actions.push(CedrusActions::PostProjectEntities);
+            }
+            if !data.policies.is_empty() {
+                actions.push(CedrusActions::PostProjectPolicies);
+            }
+            if !data.templates.is_empty() {
+                actions.push(CedrusActions::PostProjectTemplates);
+            }
+            if !data.template_links.is_empty() {
+                actions.push(CedrusActions::PostProjectTemplateLinks);
+            }
+            entity_types.allows_all(data.entities.iter().map(|e| e.uid()))
+        }
+        // Every entity is exported, whatever its type
+        JobTask::Export => {
+            actions.extend([
+                CedrusActions::GetProjectSchema,
+                CedrusActions::GetProjectEntities,
+                CedrusActions::GetProjectPolicies,
+                CedrusActions::GetProjectTemplates,
+                CedrusActions::GetProjectTemplateLinks,
+            ]);
+            !entity_types.is_restricted()
+        }
+        JobTask::Cleanup(cleanup) => {
+            if cleanup.entities {
+                actions.push(CedrusActions::DeleteProjectEntities);
+            }
+            if cleanup.policies {
+                actions.push(CedrusActions::DeleteProjectPolicies);
+            }
+            if cleanup.templates {
+                actions.push(CedrusActions::DeleteProjectTemplates);
+            }
+            if cleanup.template_links {
+                actions.push(CedrusActions::DeleteProjectTemplateLinks);
+            }
+            !(cleanup.entities && entity_types.is_restricted())
+        }
+    };
+
+    in_scope
+        && actions.iter().all(|action| {
+            state
+                .cedrus
+                .is_allow(principal.clone(), action.value(), Project::entity_uid(id))
+        })
+}
+
+fn spawn_job(state: Arc<AppState>, job: Job, task: JobTask) {
+    tokio::spawn(async move {
+        let job_id = job.id;
+        if let Err(e) = state.cedrus.project_job_run(job, task).await {
+            tracing::error!("Job {} not completed: {}", job_id, e);
+        }
+    });
 }
 
 #[utoipa::path(
-    post,
-    path = "/v1/projects/{id}/is-authorized-batch",
+    get,
+    path = "/v1/projects/{id}/jobs",
     params(
-        ("id" = Uuid, Path, description = "Project Id")
+        ("id" = Uuid, Path, description = "Project id"),
+        QueryParams,
     ),
-    request_body = IsAuthorizedRequests,
     responses(
-        (status = 200, description = "is authorized", body = Vec<Response>),
-        (status = 400, description = "Bad request"),
+        (status = 200, description = "Jobs list", body = PageList<Job>),
         (status = 404, description = "Project not found")
     ),
     security(
@@ -1621,180 +4884,234 @@ async fn projects_id_is_authorized_post(
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_is_authorized_batch_post", skip(principal, state, request), fields(project_id = %id))]
-async fn projects_id_is_authorized_batch_post(
+#[tracing::instrument(name = "projects_id_jobs_get", skip(principal, state, query_params), fields(project_id = %id))]
+async fn projects_id_jobs_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(request): Json<IsAuthorizedRequests>,
-) -> Result<AppJson<Vec<Response>>, AppError> {
+    Query(query_params): Query<QueryParams>,
+) -> Result<AppJson<PageList<Job>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::PostProjectIsAuthorized.value(),
+        CedrusActions::GetProjectJobs.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let answers = state.cedrus.is_authorized_batch(&id, request.requests)?;
+    let jobs = state
+        .cedrus
+        .project_jobs_find(id, query_params.into())
+        .await?;
 
-    Ok(AppJson(answers))
+    Ok(AppJson(jobs))
 }
 
 #[utoipa::path(
     get,
-    path = "/v1/projects/{id}/apikeys",
+    path = "/v1/projects/{id}/jobs/{jobId}",
     params(
         ("id" = Uuid, Path, description = "Project id"),
-        QueryParams,
+        ("jobId" = Uuid, Path, description = "Job id")
     ),
     responses(
-        (status = 200, description = "API Keys list", body = PageList<ApiKey>)
+        (status = 200, description = "Job status and progress", body = Job),
+        (status = 404, description = "Job not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_apikeys_get", skip(principal, state), fields(project_id = %id))]
-async fn projects_id_apikeys_get(
+#[tracing::instrument(name = "projects_id_jobs_job_id_get", skip(principal, state), fields(project_id = %id, job_id = %job_id))]
+async fn projects_id_jobs_job_id_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path(id): Path<Uuid>,
-    Query(query_params): Query<QueryParams>,
-) -> Result<AppJson<PageList<ApiKey>>, AppError> {
+    Path((id, job_id)): Path<(Uuid, Uuid)>,
+) -> Result<AppJson<Job>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::GetProjectApiKey.value(),
+        CedrusActions::GetProjectJobs.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    let query = query_params.into();
-    let apikeys = state.cedrus.project_apikeys_find(id, query).await?;
+    let job = state.cedrus.project_job_find(id, job_id).await?;
 
-    Ok(AppJson(apikeys))
+    Ok(AppJson(job))
 }
 
 #[utoipa::path(
     post,
-    path = "/v1/projects/{id}/apikeys",
+    path = "/v1/projects/{id}/jobs/import",
     params(
         ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = ApiKey,
+    request_body = ProjectData,
     responses(
-        (status = 200, description = "API Key added", body = ApiKey)
+        (status = 202, description = "Import job accepted", body = Job),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_apikeys_post", skip(principal, state, apikey), fields(project_id = %id))]
-async fn projects_id_apikeys_post(
+#[tracing::instrument(name = "projects_id_jobs_import_post", skip(principal, entity_types, state, data), fields(project_id = %id))]
+async fn projects_id_jobs_import_post(
     Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(apikey): Json<ApiKey>,
-) -> Result<AppJson<ApiKey>, AppError> {
-    if !state.cedrus.is_allow(
-        principal.clone(),
-        CedrusActions::PostProjectApiKey.value(),
-        Project::entity_uid(id),
-    ) {
+    Json(data): Json<ProjectData>,
+) -> Result<(StatusCode, AppJson<Job>), AppError> {
+    let task = JobTask::Import(data);
+    if !is_job_allowed(&state, principal, &entity_types, id, &task) {
         return Err(AppError::Forbidden);
     }
 
-    let mut apikey = apikey;
-    apikey.owner = principal;
-    let apikey = state.cedrus.project_apikeys_add(id, apikey).await?;
+    let job = state.cedrus.project_job_create(id, &task).await?;
+    spawn_job(state.clone(), job.clone(), task);
 
-    Ok(AppJson(apikey))
+    Ok((StatusCode::ACCEPTED, AppJson(job)))
 }
 
 #[utoipa::path(
-    put,
-    path = "/v1/projects/{id}/apikeys/{key}",
+    post,
+    path = "/v1/projects/{id}/jobs/export",
     params(
-        ("id" = Uuid, Path, description = "Project id"),
-        ("key" = Uuid, Path, description = "API key id")
+        ("id" = Uuid, Path, description = "Project id")
     ),
-    request_body = ApiKey,
     responses(
-        (status = 200, description = "API Key updated", body = ApiKey)
+        (status = 202, description = "Export job accepted, the job result holds the project data", body = Job),
+        (status = 404, description = "Project not found")
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_apikeys_key_put", skip(principal, state, apikey), fields(project_id = %id, key = %key))]
-async fn projects_id_apikeys_key_put(
+#[tracing::instrument(name = "projects_id_jobs_export_post", skip(principal, entity_types, state), fields(project_id = %id))]
+async fn projects_id_jobs_export_post(
     Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
     State(state): State<Arc<AppState>>,
-    Path((id, key)): Path<(Uuid, Uuid)>,
-    Json(apikey): Json<ApiKey>,
-) -> Result<AppJson<ApiKey>, AppError> {
-    if !state.cedrus.is_allow(
-        principal,
-        CedrusActions::PutProjectApiKey.value(),
-        Project::entity_uid(id),
-    ) {
+    Path(id): Path<Uuid>,
+) -> Result<(StatusCode, AppJson<Job>), AppError> {
+    let task = JobTask::Export;
+    if !is_job_allowed(&state, principal, &entity_types, id, &task) {
         return Err(AppError::Forbidden);
     }
 
-    if key != apikey.id {
-        return Err(AppError::BadRequest);
+    let job = state.cedrus.project_job_create(id, &task).await?;
+    spawn_job(state.clone(), job.clone(), task);
+
+    Ok((StatusCode::ACCEPTED, AppJson(job)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/projects/{id}/jobs/cleanup",
+    params(
+        ("id" = Uuid, Path, description = "Project id")
+    ),
+    request_body = ProjectCleanup,
+    responses(
+        (status = 202, description = "Cleanup job accepted", body = Job),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearerAuth" = []),
+        ("apiKey" = []),
+    )
+)]
+#[tracing::instrument(name = "projects_id_jobs_cleanup_post", skip(principal, entity_types, state, cleanup), fields(project_id = %id))]
+async fn projects_id_jobs_cleanup_post(
+    Extension(principal): Extension<EntityUid>,
+    Extension(entity_types): Extension<EntityTypeScope>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(cleanup): Json<ProjectCleanup>,
+) -> Result<(StatusCode, AppJson<Job>), AppError> {
+    let task = JobTask::Cleanup(cleanup);
+    if !is_job_allowed(&state, principal, &entity_types, id, &task) {
+        return Err(AppError::Forbidden);
     }
 
-    let apikey = state.cedrus.project_apikeys_update(id, apikey).await?;
+    let job = state.cedrus.project_job_create(id, &task).await?;
+    spawn_job(state.clone(), job.clone(), task);
 
-    Ok(AppJson(apikey))
+    Ok((StatusCode::ACCEPTED, AppJson(job)))
 }
 
 #[utoipa::path(
-    delete,
-    path = "/v1/projects/{id}/apikeys/{key}",
+    get,
+    path = "/v1/projects/{id}/audit",
     params(
         ("id" = Uuid, Path, description = "Project id"),
-        ("key" = Uuid, Path, description = "API key id")
+        QueryParams,
     ),
     responses(
-        (status = 200, description = "API Key deleted")
+        (status = 200, description = "Management changes of the project, oldest first", body = PageList<AuditRecord>),
     ),
     security(
         ("bearerAuth" = []),
         ("apiKey" = []),
     )
 )]
-#[tracing::instrument(name = "projects_id_apikeys_key_delete", skip(principal, state), fields(project_id = %id, key = %key))]
-async fn projects_id_apikeys_key_delete(
+#[tracing::instrument(name = "projects_id_audit_get", skip(principal, state, query_params), fields(project_id = %id))]
+async fn projects_id_audit_get(
     Extension(principal): Extension<EntityUid>,
     State(state): State<Arc<AppState>>,
-    Path((id, key)): Path<(Uuid, Uuid)>,
-) -> Result<(), AppError> {
+    Path(id): Path<Uuid>,
+    Query(query_params): Query<QueryParams>,
+) -> Result<AppJson<PageList<AuditRecord>>, AppError> {
     if !state.cedrus.is_allow(
         principal,
-        CedrusActions::DeleteProjectApiKey.value(),
+        CedrusActions::GetProjectAudit.value(),
         Project::entity_uid(id),
     ) {
         return Err(AppError::Forbidden);
     }
 
-    state.cedrus.project_apikeys_remove(id, key).await?;
+    let records = state
+        .cedrus
+        .project_audit_find(id, query_params.into())
+        .await?;
 
-    Ok(())
+    Ok(AppJson(records))
 }
 
-pub fn routes() -> Router<Arc<AppState>> {
+/// Management CRUD routes.
+pub fn management_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(projects_get))
         .route("/", post(projects_post))
         .route("/{id}", get(projects_id_get))
         .route("/{id}", put(projects_id_put))
         .route("/{id}", delete(projects_id_delete))
+        .route("/{id}/read-only", put(projects_id_read_only_put))
+        .route("/{id}/stats", get(projects_id_stats_get))
+        .route("/{id}/consistency", get(projects_id_consistency_get))
+        .route("/{id}/consistency", post(projects_id_consistency_post))
+        .route("/{id}/state", put(projects_id_state_put))
+        .route("/{id}/gitops", get(projects_id_gitops_get))
+        .route("/{id}/gitops/sync", post(projects_id_gitops_sync_post))
+        .route("/{id}/environments", get(projects_id_environments_get))
+        .route("/{id}/environments", post(projects_id_environments_post))
+        .route(
+            "/{id}/environments/{name}/promote",
+            post(projects_id_environments_name_promote_post),
+        )
+        .route("/{id}/candidate", get(projects_id_candidate_get))
+        .route("/{id}/candidate", put(projects_id_candidate_put))
+        .route("/{id}/candidate", delete(projects_id_candidate_delete))
+        .route(
+            "/{id}/candidate/promote",
+            post(projects_id_candidate_promote_post),
+        )
+        .route("/{id}/resync", post(projects_id_resync_post))
         .route(
             "/{id}/identity-source",
             get(projects_id_identity_source_get),
@@ -1807,6 +5124,14 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/{id}/identity-source",
             delete(projects_id_identity_source_delete),
         )
+        .route("/{id}/api-keys", get(projects_id_apikeys_get))
+        .route("/{id}/api-keys", post(projects_id_apikeys_post))
+        .route("/{id}/api-keys/{key}", put(projects_id_apikeys_key_put))
+        .route(
+            "/{id}/api-keys/{key}",
+            delete(projects_id_apikeys_key_delete),
+        )
+        // Former paths of the API key routes
         .route("/{id}/apikeys", get(projects_id_apikeys_get))
         .route("/{id}/apikeys", post(projects_id_apikeys_post))
         .route("/{id}/apikeys/{key}", put(projects_id_apikeys_key_put))
@@ -1814,6 +5139,26 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/{id}/apikeys/{key}",
             delete(projects_id_apikeys_key_delete),
         )
+        .route("/{id}/roles", get(projects_id_roles_get))
+        .route("/{id}/roles", post(projects_id_roles_post))
+        .route("/{id}/roles", delete(projects_id_roles_delete))
+        .route(
+            "/{id}/roles/{roleId}/principals",
+            post(projects_id_roles_role_id_principals_post),
+        )
+        .route(
+            "/{id}/roles/{roleId}/principals",
+            delete(projects_id_roles_role_id_principals_delete),
+        )
+        .route("/{id}/delegations", get(projects_id_delegations_get))
+        .route("/{id}/delegations", post(projects_id_delegations_post))
+        .route("/{id}/delegations", delete(projects_id_delegations_delete))
+        .route("/{id}/jobs", get(projects_id_jobs_get))
+        .route("/{id}/jobs/{jobId}", get(projects_id_jobs_job_id_get))
+        .route("/{id}/jobs/import", post(projects_id_jobs_import_post))
+        .route("/{id}/jobs/export", post(projects_id_jobs_export_post))
+        .route("/{id}/jobs/cleanup", post(projects_id_jobs_cleanup_post))
+        .route("/{id}/audit", get(projects_id_audit_get))
         .route("/{id}/schema", get(projects_id_schema_get))
         .route("/{id}/schema", put(projects_id_schema_put))
         .route("/{id}/schema", delete(projects_id_schema_delete))
@@ -1827,12 +5172,47 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/{id}/schema/validate/json",
             post(projects_id_schema_validate_json_post),
         )
+        .route("/{id}/schema/lint", post(projects_id_schema_lint_post))
+        .route("/{id}/schema/sdk", get(projects_id_schema_sdk_get))
         .route("/{id}/entities", get(projects_id_entities_get))
         .route("/{id}/entities", post(projects_id_entities_post))
+        .route(
+            "/{id}/entities/paths",
+            post(projects_id_entities_paths_post),
+        )
         .route("/{id}/entities", delete(projects_id_entities_delete))
+        .route(
+            "/{id}/entities:batchDelete",
+            post(projects_id_entities_batch_delete_post),
+        )
+        .route("/{id}/entities/sync", post(projects_id_entities_sync_post))
+        .route("/{id}/entities/{uid}", get(projects_id_entities_uid_get))
+        .route(
+            "/{id}/entities/{uid}/references",
+            get(projects_id_entities_uid_references_get),
+        )
+        .route("/{id}/relations", get(projects_id_relations_get))
+        .route("/{id}/relations", post(projects_id_relations_post))
+        .route("/{id}/relations", delete(projects_id_relations_delete))
         .route("/{id}/policies", get(projects_id_policies_get))
         .route("/{id}/policies", post(projects_id_policies_post))
         .route("/{id}/policies", delete(projects_id_policies_delete))
+        .route(
+            "/{id}/policies:batchDelete",
+            post(projects_id_policies_batch_delete_post),
+        )
+        .route(
+            "/{id}/revisions/{from}/diff/{to}",
+            get(projects_id_revisions_diff_get),
+        )
+        .route(
+            "/{id}/policies/version",
+            get(projects_id_policies_version_get),
+        )
+        .route(
+            "/{id}/policies/coverage",
+            get(projects_id_policies_coverage_get),
+        )
         .route(
             "/{id}/policies/validate/cedar",
             post(projects_id_policies_validate_cedar_post),
@@ -1841,6 +5221,10 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/{id}/policies/validate/json",
             post(projects_id_policies_validate_json_post),
         )
+        .route(
+            "/{id}/policies/{policyId}",
+            get(projects_id_policies_policy_id_get),
+        )
         .route(
             "/{id}/policies/{policyId}/cedar",
             get(projects_id_policies_policy_id_cedar_get),
@@ -1849,9 +5233,29 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/{id}/policies/{policyId}/cedar",
             put(projects_id_policies_policy_id_cedar_put),
         )
+        .route(
+            "/{id}/policies/{policyId}/references",
+            get(projects_id_policies_policy_id_references_get),
+        )
         .route("/{id}/templates", get(projects_id_templates_get))
         .route("/{id}/templates", post(projects_id_templates_post))
         .route("/{id}/templates", delete(projects_id_templates_delete))
+        .route(
+            "/{id}/templates:batchDelete",
+            post(projects_id_templates_batch_delete_post),
+        )
+        .route(
+            "/{id}/templates/{templateId}",
+            get(projects_id_templates_template_id_get),
+        )
+        .route(
+            "/{id}/templates/{templateId}",
+            put(projects_id_templates_template_id_put),
+        )
+        .route(
+            "/{id}/templates/{templateId}",
+            delete(projects_id_templates_template_id_delete),
+        )
         .route(
             "/{id}/templates/{templateId}/cedar",
             get(projects_id_templates_template_id_cedar_get),
@@ -1865,10 +5269,18 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/{id}/template-links",
             post(projects_id_template_links_post),
         )
+        .route(
+            "/{id}/template-links:batchCreate",
+            post(projects_id_template_links_batch_create_post),
+        )
         .route(
             "/{id}/template-links",
             delete(projects_id_template_links_delete),
         )
+        .route(
+            "/{id}/template-links/{policyId}",
+            get(projects_id_template_links_policy_id_get),
+        )
         .route(
             "/{id}/template-links/{policyId}/cedar",
             get(projects_id_template_links_policy_id_cedar_get),
@@ -1882,9 +5294,712 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/{id}/policy-set/cedar",
             get(projects_id_policy_set_cedar_get),
         )
+        .route(
+            "/{id}/policy-set/cedar",
+            post(projects_id_policy_set_cedar_post),
+        )
+        .route(
+            "/{id}/policy-set/bundle",
+            get(projects_id_policy_set_bundle_get),
+        )
+        .route(
+            "/{id}/policy-set/bundle",
+            post(projects_id_policy_set_bundle_post),
+        )
+        .route("/{id}/replay", post(projects_id_replay_post))
+        .route("/{id}/benchmark", post(projects_id_benchmark_post))
+}
+
+/// Authorization routes, served on their own listener when a data plane is configured.
+pub fn data_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/is-authorized", post(projects_is_authorized_post))
         .route("/{id}/is-authorized", post(projects_id_is_authorized_post))
         .route(
             "/{id}/is-authorized-batch",
             post(projects_id_is_authorized_batch_post),
         )
 }
+
+/// Development routes, only served with `devRoutes` set.
+pub fn dev_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/{id}/generate", post(projects_id_generate_post))
+}
+
+pub fn routes() -> Router<Arc<AppState>> {
+    management_routes().merge(data_routes())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::to_bytes, http::Method};
+    use cedrus_cedar::{EntityValue, PolicyEffect, SlotId};
+    use cedrus_core::{
+        cache::dashmap::DashMapCache,
+        core::{
+            BundleConfig, CedrusConfig, batch::BatchDeleteStatus, bundle::BundleKeys,
+            cedrus::Cedrus,
+        },
+        db::memory::MemoryDb,
+        pubsub::dummy::DummyPubSub,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::AdminCapability;
+
+    fn admin() -> EntityUid {
+        EntityUid::new("User".to_string(), Uuid::nil().to_string())
+    }
+
+    async fn app(principal: EntityUid) -> (Router, Arc<AppState>, Uuid) {
+        let mut cedrus = Cedrus::new(
+            Box::new(MemoryDb::default()),
+            Box::new(DashMapCache::default()),
+            Box::new(DummyPubSub::new()),
+            None,
+            false,
+            false,
+        )
+        .await;
+        let key = openssl::pkey::PKey::generate_ed25519().unwrap();
+        cedrus.bundle_keys = BundleKeys::new(&BundleConfig {
+            private_key: Some(String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap()),
+            trusted_keys: Vec::new(),
+        })
+        .unwrap();
+        cedrus
+            .init_admin_project(&CedrusConfig::default(), "admin".to_string())
+            .await
+            .unwrap();
+        cedrus.init_cache().await.unwrap();
+        cedrus.load_cache().await.unwrap();
+        let project = Project {
+            id: Uuid::now_v7(),
+            name: "test".to_string(),
+            ..Default::default()
+        };
+        let project_id = cedrus.project_create(project, admin()).await.unwrap().id;
+
+        let state = Arc::new(AppState::new(cedrus));
+        let app = routes()
+            .with_state(state.clone())
+            .layer(Extension(principal))
+            .layer(Extension(EntityTypeScope::default()));
+        (app, state, project_id)
+    }
+
+    fn bundle_post(project_id: Uuid, bundle: &PolicyBundle) -> axum::http::Request<Body> {
+        axum::http::Request::post(format!("/{project_id}/policy-set/bundle"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(bundle).unwrap()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bundle_forbidden() {
+        let (app, state, project_id) = app(EntityUid::from("User::nobody")).await;
+
+        let req = axum::http::Request::get(format!("/{project_id}/policy-set/bundle"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let bundle = state
+            .cedrus
+            .bundle_keys
+            .sign(project_id, PolicySet::default())
+            .unwrap();
+        let response = app.oneshot(bundle_post(project_id, &bundle)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_round_trip() {
+        let (app, state, project_id) = app(admin()).await;
+
+        let req = axum::http::Request::get(format!("/{project_id}/policy-set/bundle"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let bundle: PolicyBundle = serde_json::from_slice(&body).unwrap();
+        assert_eq!(bundle.metadata.project_id, project_id);
+
+        // A bundle exported from another project is refused
+        let other = state
+            .cedrus
+            .bundle_keys
+            .sign(Uuid::now_v7(), PolicySet::default())
+            .unwrap();
+        let response = app
+            .clone()
+            .oneshot(bundle_post(project_id, &other))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app.oneshot(bundle_post(project_id, &bundle)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    fn json_request(
+        method: Method,
+        uri: String,
+        body: &impl Serialize,
+    ) -> axum::http::Request<Body> {
+        axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap()
+    }
+
+    async fn dry_run_report(response: HttpResponse) -> DryRunReport {
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn test_cedar_schema_parse() {
+        let cedar_schema = CedarSchema::parse("entity User;").unwrap();
+        assert!(cedar_schema.warnings.is_empty());
+
+        let errors = CedarSchema::parse("entity User;\nentity Photo in [User];;\n")
+            .err()
+            .unwrap();
+        assert!(!errors.is_empty());
+        assert!(
+            errors
+                .iter()
+                .all(|e| e.severity == DiagnosticSeverity::Error)
+        );
+        assert_eq!(errors[0].line, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_schema_validate_cedar() {
+        let (app, _, project_id) = app(admin()).await;
+        let syntax = CedarSyntax {
+            cedar: Some("entity User;".to_string()),
+        };
+
+        // The schema alone unless the warnings are asked for
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                Method::POST,
+                format!("/{project_id}/schema/validate/cedar"),
+                &syntax,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert!(value.get("schema").is_none());
+        serde_json::from_value::<Schema>(value).unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                Method::POST,
+                format!("/{project_id}/schema/validate/cedar?warnings=true"),
+                &syntax,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let cedar_schema: CedarSchema = serde_json::from_slice(&body).unwrap();
+        assert!(cedar_schema.warnings.is_empty());
+
+        let syntax = CedarSyntax {
+            cedar: Some("entity User".to_string()),
+        };
+        let response = app
+            .oneshot(json_request(
+                Method::POST,
+                format!("/{project_id}/schema/validate/cedar"),
+                &syntax,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_benchmark() {
+        let (app, _, project_id) = app(admin()).await;
+        let benchmark = Benchmark {
+            iterations: Some(10),
+            requests: vec![Request {
+                principal: EntityUid::from("User::alice"),
+                action: EntityUid::from("Action::view"),
+                resource: EntityUid::from("Photo::a"),
+                context: None,
+            }],
+        };
+
+        let response = app
+            .oneshot(json_request(
+                Method::POST,
+                format!("/{project_id}/benchmark"),
+                &benchmark,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report: BenchmarkReport = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report.iterations, 10);
+        assert_eq!(report.denied + report.errors, 10);
+    }
+
+    #[tokio::test]
+    async fn test_policies_dry_run() {
+        let (app, state, project_id) = app(admin()).await;
+        let policy = cedar_policy::Policy::parse(
+            Some(cedar_policy::PolicyId::new("all")),
+            "permit(principal, action, resource);",
+        )
+        .unwrap();
+        let policies: HashMap<PolicyId, Policy> = HashMap::from([(
+            PolicyId::from("all".to_string()),
+            policy.try_into().unwrap(),
+        )]);
+
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                Method::POST,
+                format!("/{project_id}/policies?dryRun=true"),
+                &policies,
+            ))
+            .await
+            .unwrap();
+        let report = dry_run_report(response).await;
+        assert_eq!(
+            report.plan.policies_changed,
+            vec![PolicyId::from("all".to_string())]
+        );
+        let found = state
+            .cedrus
+            .project_policies_find(project_id, cedrus_core::Query::default())
+            .await
+            .unwrap();
+        assert!(found.items.is_empty());
+
+        let response = app
+            .oneshot(json_request(
+                Method::POST,
+                format!("/{project_id}/policies"),
+                &policies,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let found = state
+            .cedrus
+            .project_policies_find(project_id, cedrus_core::Query::default())
+            .await
+            .unwrap();
+        assert_eq!(found.items.len(), 1);
+    }
+
+    async fn template_linked(state: &AppState, project_id: Uuid) -> PolicyId {
+        let template = cedar_policy::Template::parse(
+            Some(cedar_policy::PolicyId::new("viewer")),
+            "permit(principal == ?principal, action, resource);",
+        )
+        .unwrap();
+        let template_id = PolicyId::from("viewer".to_string());
+        state
+            .cedrus
+            .project_templates_add(
+                project_id,
+                HashMap::from([(template_id.clone(), template.try_into().unwrap())]),
+            )
+            .await
+            .unwrap();
+        let link = TemplateLink::new(
+            template_id.clone(),
+            PolicyId::from("viewer:alice".to_string()),
+            HashMap::from([(
+                SlotId::Principal,
+                EntityValue::EntityUid(EntityUid::from("User::alice")),
+            )]),
+        );
+        state
+            .cedrus
+            .project_template_links_add(project_id, vec![link])
+            .await
+            .unwrap();
+        template_id
+    }
+
+    #[tokio::test]
+    async fn test_templates_template_id() {
+        let (app, state, project_id) = app(admin()).await;
+        let template_id = template_linked(&state, project_id).await;
+        let uri = format!("/{project_id}/templates/{template_id}");
+
+        let req = axum::http::Request::get(&uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let template: Template = serde_json::from_slice(&body).unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(json_request(Method::PUT, uri.clone(), &template))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The link still referencing the template keeps it unless forced
+        let req = axum::http::Request::delete(&uri)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let req = axum::http::Request::delete(format!("{uri}?force=true"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let links = state
+            .cedrus
+            .project_template_links_find(project_id, cedrus_core::Query::default())
+            .await
+            .unwrap();
+        assert!(links.items.is_empty());
+
+        let req = axum::http::Request::get(&uri).body(Body::empty()).unwrap();
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_batch_delete() {
+        let (app, state, project_id) = app(admin()).await;
+        let alice = EntityUid::from("User::alice");
+        let bob = EntityUid::from("User::bob");
+        state
+            .cedrus
+            .project_entities_add(
+                project_id,
+                vec![Entity::new_no_attrs(alice.clone(), Default::default())],
+            )
+            .await
+            .unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                Method::POST,
+                format!("/{project_id}/entities:batchDelete"),
+                &vec![alice.clone(), bob.clone()],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results: Vec<BatchDeleteResult<EntityUid>> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            results,
+            vec![
+                BatchDeleteResult::new(alice, BatchDeleteStatus::Deleted),
+                BatchDeleteResult::new(bob, BatchDeleteStatus::NotFound),
+            ]
+        );
+
+        // A template still linked is kept unless forced, the rest of the batch goes on
+        let template_id = template_linked(&state, project_id).await;
+        let missing = PolicyId::from("missing".to_string());
+        let template_ids = vec![template_id.clone(), missing.clone()];
+        let batch_delete = |force: bool| {
+            json_request(
+                Method::POST,
+                format!("/{project_id}/templates:batchDelete?force={force}"),
+                &template_ids,
+            )
+        };
+        let response = app.clone().oneshot(batch_delete(false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results: Vec<BatchDeleteResult<PolicyId>> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            results,
+            vec![
+                BatchDeleteResult::new(template_id.clone(), BatchDeleteStatus::Conflict),
+                BatchDeleteResult::new(missing.clone(), BatchDeleteStatus::NotFound),
+            ]
+        );
+
+        let response = app.oneshot(batch_delete(true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results: Vec<BatchDeleteResult<PolicyId>> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            results,
+            vec![
+                BatchDeleteResult::new(template_id, BatchDeleteStatus::Deleted),
+                BatchDeleteResult::new(missing, BatchDeleteStatus::NotFound),
+            ]
+        );
+        let links = state
+            .cedrus
+            .project_template_links_find(project_id, cedrus_core::Query::default())
+            .await
+            .unwrap();
+        assert!(links.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delegations() {
+        let (app, state, project_id) = app(admin()).await;
+        let bob = EntityUid::from("User::bob");
+        let delegation = |effect: PolicyEffect| Delegation {
+            principal: bob.clone(),
+            capability: AdminCapability::PolicyEditor,
+            effect,
+        };
+        let uri = format!("/{project_id}/delegations");
+        let is_allow = |action: CedrusActions| {
+            state
+                .cedrus
+                .is_allow(bob.clone(), action.value(), Project::entity_uid(project_id))
+        };
+
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                Method::POST,
+                uri.clone(),
+                &delegation(PolicyEffect::Permit),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let granted: HashMap<PolicyId, Policy> = serde_json::from_slice(&body).unwrap();
+        assert!(is_allow(CedrusActions::PostProjectPolicies));
+        assert!(!is_allow(CedrusActions::PutProjectSchema));
+
+        let req = axum::http::Request::get(&uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let found: PageHash<PolicyId, Policy> = serde_json::from_slice(&body).unwrap();
+        assert!(granted.keys().all(|id| found.items.contains_key(id)));
+
+        // A forbid delegation restricts the capability over the permit one
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                Method::POST,
+                uri.clone(),
+                &delegation(PolicyEffect::Forbid),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!is_allow(CedrusActions::PostProjectPolicies));
+
+        for effect in [PolicyEffect::Forbid, PolicyEffect::Permit] {
+            let response = app
+                .clone()
+                .oneshot(json_request(
+                    Method::DELETE,
+                    uri.clone(),
+                    &delegation(effect),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert!(!is_allow(CedrusActions::GetProjectPolicies));
+    }
+
+    #[tokio::test]
+    async fn test_gitops_sync_forbidden() {
+        let (_, state, project_id) = app(admin()).await;
+        let bob = EntityUid::from("User::bob");
+        let grant = |actions: Vec<&CedrusActions>| {
+            state.cedrus.project_delegation_grant(
+                project_id,
+                "gitops",
+                PolicyEffect::Permit,
+                bob.clone(),
+                actions.into_iter().map(|action| action.value()).collect(),
+            )
+        };
+        let app = routes()
+            .with_state(state.clone())
+            .layer(Extension(bob.clone()));
+        let sync = || {
+            axum::http::Request::post(format!("/{project_id}/gitops/sync"))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // Without removing template links the sync is refused
+        let actions = STATE_ACTIONS
+            .iter()
+            .filter(|action| !matches!(action, CedrusActions::DeleteProjectTemplateLinks))
+            .collect();
+        grant(actions).await.unwrap();
+        let response = app.clone().oneshot(sync()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The project is not synced from Git
+        grant(STATE_ACTIONS.iter().collect()).await.unwrap();
+        let response = app.oneshot(sync()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_forbidden() {
+        let (_, state, project_id) = app(admin()).await;
+        let bob = EntityUid::from("User::bob");
+        let app = |entity_types: EntityTypeScope| {
+            routes()
+                .with_state(state.clone())
+                .layer(Extension(bob.clone()))
+                .layer(Extension(entity_types))
+        };
+        let photos = EntityTypeScope(Some(HashSet::from(["Photo".to_string()])));
+        let cleanup = ProjectCleanup {
+            entities: true,
+            ..Default::default()
+        };
+        let data = ProjectData {
+            entities: vec![Entity::new_no_attrs(
+                EntityUid::from("User::alice"),
+                Default::default(),
+            )],
+            ..Default::default()
+        };
+        let post = |task: &str, body: &Value| {
+            json_request(Method::POST, format!("/{project_id}/jobs/{task}"), body)
+        };
+        let export = || {
+            axum::http::Request::post(format!("/{project_id}/jobs/export"))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // Posting jobs alone reads and changes nothing
+        state
+            .cedrus
+            .project_delegation_grant(
+                project_id,
+                "jobs",
+                PolicyEffect::Permit,
+                bob.clone(),
+                vec![CedrusActions::PostProjectJobs.value()],
+            )
+            .await
+            .unwrap();
+        let response = app(EntityTypeScope::default())
+            .oneshot(export())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app(EntityTypeScope::default())
+            .oneshot(post("cleanup", &serde_json::to_value(&cleanup).unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        state
+            .cedrus
+            .project_delegation_grant(
+                project_id,
+                "jobs",
+                PolicyEffect::Permit,
+                bob.clone(),
+                [
+                    CedrusActions::PostProjectJobs,
+                    CedrusActions::GetProjectSchema,
+                    CedrusActions::GetProjectEntities,
+                    CedrusActions::GetProjectPolicies,
+                    CedrusActions::GetProjectTemplates,
+                    CedrusActions::GetProjectTemplateLinks,
+                    CedrusActions::PostProjectEntities,
+                    CedrusActions::DeleteProjectEntities,
+                ]
+                .iter()
+                .map(|action| action.value())
+                .collect(),
+            )
+            .await
+            .unwrap();
+        let response = app(EntityTypeScope::default())
+            .oneshot(export())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // An API key limited to some entity types reaches no other through a job
+        let response = app(photos.clone()).oneshot(export()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app(photos.clone())
+            .oneshot(post("cleanup", &serde_json::to_value(&cleanup).unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app(photos)
+            .oneshot(post("import", &serde_json::to_value(&data).unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_project_dry_run() {
+        let (app, state, project_id) = app(admin()).await;
+
+        let apikey = ApiKey {
+            name: "ci".to_string(),
+            ..Default::default()
+        };
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                Method::POST,
+                format!("/{project_id}/api-keys?dryRun=true"),
+                &apikey,
+            ))
+            .await
+            .unwrap();
+        let report = dry_run_report(response).await;
+        assert_eq!(report.api_keys_changed.len(), 1);
+        let found = state
+            .cedrus
+            .project_apikeys_find(project_id, cedrus_core::Query::default())
+            .await
+            .unwrap();
+        assert!(found.items.is_empty());
+
+        let req = axum::http::Request::delete(format!("/{project_id}?dryRun=true"))
+            .body(Body::empty())
+            .unwrap();
+        let report = dry_run_report(app.oneshot(req).await.unwrap()).await;
+        assert_eq!(report.projects_removed, vec![project_id]);
+        assert!(
+            state
+                .cedrus
+                .project_find(project_id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, OriginalUri, Query, Request, State},
+    http::{Method, Response},
+    middleware::Next,
+};
+
+use crate::{AppError, AppState, DryRunParams, routes::log::project_id};
+
+// Routes taking a body that evaluate or validate without changing anything, plus the
+// route lifting the read-only mode itself
+const READ_ROUTES: [&str; 12] = [
+    "/read-only",
+    "/benchmark",
+    "/generate",
+    "/is-authorized",
+    "/is-authorized-batch",
+    "/replay",
+    "/schema/lint",
+    "/schema/validate/cedar",
+    "/schema/validate/json",
+    "/policies/validate/cedar",
+    "/policies/validate/json",
+    "/jobs/export",
+];
+
+// Mutation routes that can run dry, validating without persisting anything
+const DRY_RUN_ROUTES: [&str; 27] = [
+    "/projects",
+    "/projects/{id}",
+    "/{id}/identity-source",
+    "/{id}/api-keys",
+    "/{id}/api-keys/{key}",
+    "/{id}/apikeys",
+    "/{id}/apikeys/{key}",
+    "/{id}/consistency",
+    "/{id}/state",
+    "/{id}/gitops/sync",
+    "/{id}/schema",
+    "/{id}/schema/cedar",
+    "/{id}/entities",
+    "/{id}/entities:batchDelete",
+    "/{id}/entities/sync",
+    "/{id}/entities/paths",
+    "/{id}/policies",
+    "/{id}/policies:batchDelete",
+    "/{id}/policies/{policyId}/cedar",
+    "/{id}/templates",
+    "/{id}/templates:batchDelete",
+    "/{id}/templates/{templateId}",
+    "/{id}/templates/{templateId}/cedar",
+    "/{id}/template-links",
+    "/{id}/template-links/{policyId}/cedar",
+    "/{id}/policy-set/cedar",
+    "/{id}/policy-set/bundle",
+];
+
+// Routes below the project of the data a Git repository is the source of truth for
+const GITOPS_ROUTES: [&str; 6] = [
+    "schema",
+    "policies",
+    "templates",
+    "policy-set",
+    "state",
+    "jobs/import",
+];
+
+pub(crate) fn route(req: &Request) -> &str {
+    req.extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str())
+        .unwrap_or_else(|| req.uri().path())
+}
+
+pub(crate) fn is_mutation(req: &Request) -> bool {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return false;
+    }
+    let route = route(req);
+    !READ_ROUTES.iter().any(|r| route.ends_with(r))
+}
+
+pub(crate) fn is_dry_run(req: &Request) -> bool {
+    Query::<DryRunParams>::try_from_uri(req.uri()).is_ok_and(|Query(params)| params.is_dry_run())
+}
+
+fn is_gitops_route(req: &Request) -> bool {
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| req.uri().path(), |uri| uri.path());
+    let Some(route) = path.splitn(5, '/').nth(4) else {
+        return false;
+    };
+    // Custom methods such as `policies:batchDelete` act on their collection
+    let (collection, rest) = route.split_once('/').unwrap_or((route, ""));
+    let collection = collection.split(':').next().unwrap_or_default();
+    let route = format!("{collection}/{rest}/");
+    GITOPS_ROUTES
+        .iter()
+        .any(|r| route.starts_with(&format!("{r}/")))
+}
+
+/// Rejects mutation routes with 503 while the server is read-only, and with 423 while
+/// the targeted project is, or while its policies are synced from Git. Authorization
+/// requests and dry runs keep being served, dry runs of other mutations are rejected.
+pub async fn guard(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response<Body>, AppError> {
+    if is_mutation(&req) {
+        if is_dry_run(&req) {
+            let route = route(&req);
+            if !DRY_RUN_ROUTES.iter().any(|r| route.ends_with(r)) {
+                return Err(AppError::BadRequest);
+            }
+            return Ok(next.run(req).await);
+        }
+        if state.cedrus.is_read_only() {
+            return Err(AppError::ServiceUnavailable);
+        }
+        if let Some(id) = project_id(&req) {
+            if state.cedrus.is_project_read_only(&id) {
+                return Err(AppError::Locked);
+            }
+            if state.cedrus.is_project_gitops(&id) && is_gitops_route(&req) {
+                return Err(AppError::Locked);
+            }
+        }
+    }
+
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str) -> Request {
+        Request::post(format!("/v1/projects/{}{path}", uuid::Uuid::nil()))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_is_gitops_route() {
+        for path in [
+            "/schema",
+            "/schema/cedar",
+            "/policies",
+            "/policies:batchDelete",
+            "/policies/p1/cedar",
+            "/templates:batchDelete",
+            "/policy-set/bundle",
+            "/state",
+            "/jobs/import",
+        ] {
+            assert!(is_gitops_route(&request(path)), "{path}");
+        }
+        for path in [
+            "",
+            "/entities",
+            "/entities:batchDelete",
+            "/template-links",
+            "/schemas",
+            "/jobs/export",
+            "/gitops/sync",
+        ] {
+            assert!(!is_gitops_route(&request(path)), "{path}");
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{Method, Response, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{AppState, routes::log::project_id};
+
+/// Path of the combined decisions, naming their projects in the body.
+const COMBINED_PATH: &str = "/v1/projects/is-authorized";
+/// Largest body read for the projects of a combined decision, the payload limit of Lambda.
+const MAX_COMBINED_BODY_SIZE: usize = 6 * 1024 * 1024;
+
+// Projects of a combined decision request, the rest of the body being left to the route
+#[derive(Deserialize)]
+struct CombinedProjects {
+    #[serde(default)]
+    projects: Vec<Uuid>,
+}
+
+/// Reloads the admin project and the projects of a request from the Database before it is
+/// authenticated, for nodes receiving no events such as serverless ones, then flushes the
+/// writes it deferred. A failed reload answers 503, the node having no other way to catch up.
+pub async fn reload(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    let mut project_ids = vec![Uuid::nil()];
+    project_ids.extend(project_id(&req).filter(|id| !id.is_nil()));
+
+    let req = if req.method() == Method::POST && req.uri().path() == COMBINED_PATH {
+        let (parts, body) = req.into_parts();
+        let bytes = match to_bytes(body, MAX_COMBINED_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+        };
+        // A body the route rejects names no project to reload
+        if let Ok(combined) = serde_json::from_slice::<CombinedProjects>(&bytes) {
+            for project_id in combined.projects {
+                if !project_ids.contains(&project_id) {
+                    project_ids.push(project_id);
+                }
+            }
+        }
+        Request::from_parts(parts, Body::from(bytes))
+    } else {
+        req
+    };
+
+    for project_id in &project_ids {
+        if let Err(e) = state.cedrus.project_reload(project_id).await {
+            tracing::error!("Failed to reload project {}: {}", project_id, e);
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+    }
+
+    let response = next.run(req).await;
+
+    if let Err(e) = state.cedrus.write_behind_flush().await {
+        tracing::error!("Write-behind flush failed: {:?}", e);
+    }
+
+    response
+}
@@ -0,0 +1,27 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::Response,
+    middleware::Next,
+};
+
+use crate::{AppState, routes::log::project_id};
+
+/// Rebuilds the project of a request when events changed it on a shard of another node, before
+/// the route reads its compiled entities and policies. A failed rebuild is logged and retried
+/// on the next request, the route answering from the state it has.
+pub async fn refresh(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    if let Some(project_id) = project_id(&req)
+        && let Err(e) = state.cedrus.project_refresh(&project_id).await
+    {
+        tracing::error!("Failed to refresh project {}: {}", project_id, e);
+    }
+
+    next.run(req).await
+}
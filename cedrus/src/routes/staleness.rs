@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderValue, Response},
+    middleware::Next,
+};
+
+use crate::{AppState, routes::log::project_id};
+
+/// Seconds the data of the project may have been stale on the node when the request was
+/// served.
+pub const X_POLICY_STALENESS: &str = "x-policy-staleness";
+
+/// Tells the callers how stale the project of a request may be on this node, since its last
+/// applied event or the node last knew it received the events of every other node, so they
+/// can detect decisions made on outdated policies.
+pub async fn header(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    let staleness = project_id(&req)
+        .and_then(|project_id| state.cedrus.project_staleness(&project_id))
+        .map(|staleness| format!("{:.3}", staleness.num_milliseconds() as f64 / 1000.0));
+
+    let mut response = next.run(req).await;
+    if let Some(staleness) = staleness
+        && let Ok(value) = HeaderValue::from_str(&staleness)
+    {
+        response.headers_mut().insert(X_POLICY_STALENESS, value);
+    }
+
+    response
+}
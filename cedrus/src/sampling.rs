@@ -0,0 +1,269 @@
+use std::time::{Duration, SystemTime};
+
+use aws_credential_types::provider::ProvideCredentials;
+use aws_sigv4::{
+    http_request::{PayloadChecksumKind, SignableBody, SignableRequest, SigningSettings, sign},
+    sign::v4,
+};
+use cedrus_cedar::{Decision, EntityUid, Request, Response};
+use cedrus_core::core::{SamplingConfig, SamplingSinkConfig, cedrus::Cedrus};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const SAMPLING_TARGET: &str = "cedrus::sampling";
+
+/// Authorization decision sampled for offline analysis, one JSON line in the sink.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecisionSample {
+    pub project_id: Uuid,
+    /// Milliseconds since the Unix epoch
+    pub evaluated_at: i64,
+    pub principal: String,
+    pub action: String,
+    pub resource: String,
+    /// The context as a JSON string, so every sink stores it in one column
+    pub context: String,
+    pub decision: Decision,
+    pub reason: Vec<String>,
+    pub errors: Vec<String>,
+    pub policy_version: String,
+    /// Digest of the principal and resource entities and their ancestors
+    pub entity_digest: String,
+}
+
+/// Samples authorization decisions at the configured rates, handing them to a background
+/// writer so evaluation never waits on the sink.
+pub struct DecisionSampler {
+    allow_rate: f64,
+    deny_rate: f64,
+    sender: mpsc::Sender<DecisionSample>,
+}
+
+impl DecisionSampler {
+    /// Starts the writer of the samples and returns the sampler feeding it.
+    pub fn start(config: SamplingConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.buffer_size.max(1));
+        let sampler = DecisionSampler {
+            allow_rate: config.allow_rate,
+            deny_rate: config.deny_rate,
+            sender,
+        };
+        tokio::spawn(write_samples(config, receiver));
+
+        sampler
+    }
+
+    fn is_sampled(&self, decision: &Decision) -> bool {
+        let rate = match decision {
+            Decision::Allow => self.allow_rate,
+            Decision::Deny => self.deny_rate,
+        };
+        rate >= 1.0 || (rate > 0.0 && rand::random::<f64>() < rate)
+    }
+
+    /// Records the decisions drawn by the sampling rates. Samples are dropped while the
+    /// writer is behind by more than the buffer size.
+    pub fn record(
+        &self,
+        cedrus: &Cedrus,
+        project_id: &Uuid,
+        policy_version: &str,
+        requests: impl IntoIterator<Item = Request>,
+        responses: &[Response],
+    ) {
+        let evaluated_at = chrono::Utc::now().timestamp_millis();
+        for (request, response) in requests.into_iter().zip(responses) {
+            if !self.is_sampled(&response.decision) {
+                continue;
+            }
+            let entity_digest = cedrus
+                .project_entity_slice_digest(
+                    project_id,
+                    &[request.principal.clone(), request.resource.clone()],
+                )
+                .unwrap_or_default();
+            let sample = DecisionSample {
+                project_id: *project_id,
+                evaluated_at,
+                principal: request.principal.to_string(),
+                action: request.action.to_string(),
+                resource: request.resource.to_string(),
+                context: request
+                    .context
+                    .map(|context| serde_json::to_string(&context).unwrap_or_default())
+                    .unwrap_or_else(|| "{}".to_string()),
+                decision: response.decision.clone(),
+                reason: response.reason.clone(),
+                errors: response.errors.clone(),
+                policy_version: policy_version.to_string(),
+                entity_digest,
+            };
+            if self.sender.try_send(sample).is_err() {
+                tracing::debug!(target: SAMPLING_TARGET, "Sampling buffer full, sample dropped");
+            }
+        }
+    }
+}
+
+/// Principal a request is evaluated for, the token principal when one was materialized.
+pub fn sampled_request(request: &Request, principal: Option<&EntityUid>) -> Request {
+    Request {
+        principal: principal.unwrap_or(&request.principal).clone(),
+        ..request.clone()
+    }
+}
+
+async fn write_samples(config: SamplingConfig, mut receiver: mpsc::Receiver<DecisionSample>) {
+    let batch_size = config.batch_size.max(1);
+    let mut interval = tokio::time::interval(Duration::from_secs(config.flush_interval.max(1)));
+    let mut batch = Vec::with_capacity(batch_size);
+
+    loop {
+        let closed = tokio::select! {
+            sample = receiver.recv() => match sample {
+                Some(sample) => {
+                    batch.push(sample);
+                    if batch.len() < batch_size {
+                        continue;
+                    }
+                    false
+                }
+                None => true,
+            },
+            _ = interval.tick() => false,
+        };
+
+        if !batch.is_empty() {
+            let samples = std::mem::take(&mut batch);
+            if let Err(e) = write(&config.sink, &samples).await {
+                tracing::warn!(target: SAMPLING_TARGET, "{} samples lost: {}", samples.len(), e);
+            }
+        }
+        if closed {
+            return;
+        }
+    }
+}
+
+async fn write(sink: &SamplingSinkConfig, samples: &[DecisionSample]) -> Result<(), String> {
+    let mut lines = Vec::new();
+    for sample in samples {
+        serde_json::to_writer(&mut lines, sample).map_err(|e| e.to_string())?;
+        lines.push(b'\n');
+    }
+
+    match sink {
+        SamplingSinkConfig::Log => {
+            for sample in samples {
+                let sample = serde_json::to_value(sample).unwrap_or(Value::Null);
+                tracing::info!(target: SAMPLING_TARGET, sample = %sample);
+            }
+            Ok(())
+        }
+        SamplingSinkConfig::ClickHouse {
+            url,
+            table,
+            user,
+            password,
+        } => {
+            let mut request = reqwest::Client::new()
+                .post(url)
+                .query(&[("query", format!("INSERT INTO {table} FORMAT JSONEachRow"))]);
+            if let Some(user) = user {
+                request = request.header("X-ClickHouse-User", user);
+            }
+            if let Some(password) = password {
+                request = request.header("X-ClickHouse-Key", password);
+            }
+            request
+                .body(lines)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        }
+        SamplingSinkConfig::S3 {
+            bucket,
+            prefix,
+            region,
+        } => put_s3_object(bucket, prefix, region.as_deref(), lines).await,
+    }
+}
+
+// Writes a batch to `{prefix}{yyyy}/{mm}/{dd}/{uuid}.jsonl` with a SigV4 signed PUT
+async fn put_s3_object(
+    bucket: &str,
+    prefix: &str,
+    region: Option<&str>,
+    body: Vec<u8>,
+) -> Result<(), String> {
+    let config = aws_config::load_from_env().await;
+    let region = region
+        .map(str::to_string)
+        .or_else(|| config.region().map(|r| r.to_string()))
+        .ok_or("missing region")?;
+    let credentials = config
+        .credentials_provider()
+        .ok_or("missing credentials")?
+        .provide_credentials()
+        .await
+        .map_err(|e| e.to_string())?;
+    let identity = credentials.into();
+
+    let host = format!("{bucket}.s3.{region}.amazonaws.com");
+    let key = format!(
+        "{prefix}{}/{}.jsonl",
+        chrono::Utc::now().format("%Y/%m/%d"),
+        Uuid::now_v7()
+    );
+    let uri = format!("https://{host}/{key}");
+
+    let mut request = axum::http::Request::builder()
+        .method("PUT")
+        .uri(&uri)
+        .header("host", &host)
+        .header("content-type", "application/x-ndjson")
+        .body(())
+        .map_err(|e| e.to_string())?;
+
+    let mut settings = SigningSettings::default();
+    settings.payload_checksum_kind = PayloadChecksumKind::XAmzSha256;
+    let params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(&region)
+        .name("s3")
+        .time(SystemTime::now())
+        .settings(settings)
+        .build()
+        .map_err(|e| e.to_string())?
+        .into();
+    let signable = SignableRequest::new(
+        "PUT",
+        &uri,
+        request
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?))),
+        SignableBody::Bytes(&body),
+    )
+    .map_err(|e| e.to_string())?;
+    let (instructions, _) = sign(signable, &params)
+        .map_err(|e| e.to_string())?
+        .into_parts();
+    instructions.apply_to_request_http1x(&mut request);
+
+    reqwest::Client::new()
+        .put(&uri)
+        .headers(request.headers().clone())
+        .body(body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+
+use aws_sdk_secretsmanager::{Client, error::DisplayErrorContext};
+use serde_json::Value;
+
+/// Prefix of config values resolved from a secret store, as
+/// `secretRef:<provider>:<name>[#<key>]` where the provider is `env`, `file`, `aws`
+/// (Secrets Manager) or `vault` (KV engine at `VAULT_ADDR`, with `VAULT_TOKEN`). The
+/// optional key selects a field of a JSON secret.
+pub const SECRET_REF_PREFIX: &str = "secretRef:";
+
+const VAULT_ADDR_ENV: &str = "VAULT_ADDR";
+const VAULT_TOKEN_ENV: &str = "VAULT_TOKEN";
+
+/// Secrets Manager client shared by every resolution, built from the environment on first use.
+static AWS_CLIENT: tokio::sync::OnceCell<Client> = tokio::sync::OnceCell::const_new();
+
+#[derive(Debug)]
+pub enum SecretError {
+    InvalidRef(String),
+    NotFound(String),
+    Io(std::io::Error),
+    Http(reqwest::Error),
+    Json(serde_json::Error),
+    Aws(String),
+}
+
+impl std::error::Error for SecretError {}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SecretError::InvalidRef(r) => write!(f, "invalid secret reference: {}", r),
+            SecretError::NotFound(r) => write!(f, "secret not found: {}", r),
+            SecretError::Io(e) => write!(f, "secret io error: {}", e),
+            SecretError::Http(e) => write!(f, "secret http error: {}", e),
+            SecretError::Json(e) => write!(f, "secret json error: {}", e),
+            SecretError::Aws(e) => write!(f, "secret aws error: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for SecretError {
+    fn from(e: std::io::Error) -> Self {
+        SecretError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for SecretError {
+    fn from(e: reqwest::Error) -> Self {
+        SecretError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for SecretError {
+    fn from(e: serde_json::Error) -> Self {
+        SecretError::Json(e)
+    }
+}
+
+pub fn is_secret_ref(value: &str) -> bool {
+    value.starts_with(SECRET_REF_PREFIX)
+}
+
+/// Resolves `value` when it is a secret reference, returns it unchanged otherwise.
+pub async fn resolve(value: &str) -> Result<String, SecretError> {
+    let Some(reference) = value.strip_prefix(SECRET_REF_PREFIX) else {
+        return Ok(value.to_string());
+    };
+    let Some((provider, name)) = reference.split_once(':') else {
+        return Err(SecretError::InvalidRef(value.to_string()));
+    };
+    let (name, key) = match name.split_once('#') {
+        Some((name, key)) => (name, Some(key)),
+        None => (name, None),
+    };
+
+    let secret = match provider {
+        "env" => std::env::var(name).map_err(|_| SecretError::NotFound(value.to_string()))?,
+        "file" => tokio::fs::read_to_string(name)
+            .await?
+            .trim_end_matches(['\r', '\n'])
+            .to_string(),
+        "aws" => aws_secret(name).await?,
+        "vault" => vault_secret(name).await?,
+        _ => return Err(SecretError::InvalidRef(value.to_string())),
+    };
+
+    match key {
+        Some(key) => select(&serde_json::from_str(&secret)?, key)
+            .ok_or_else(|| SecretError::NotFound(value.to_string())),
+        None => Ok(secret),
+    }
+}
+
+/// Replaces every secret reference found in the strings of a JSON document.
+pub async fn resolve_value(value: &mut Value) -> Result<(), SecretError> {
+    match value {
+        Value::String(s) if is_secret_ref(s) => *s = resolve(s).await?,
+        Value::Array(values) => {
+            for value in values {
+                Box::pin(resolve_value(value)).await?;
+            }
+        }
+        Value::Object(map) => {
+            for value in map.values_mut() {
+                Box::pin(resolve_value(value)).await?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn select(value: &Value, key: &str) -> Option<String> {
+    match value.get(key)? {
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+async fn aws_client() -> &'static Client {
+    AWS_CLIENT
+        .get_or_init(|| async { Client::new(&aws_config::load_from_env().await) })
+        .await
+}
+
+async fn aws_secret(secret_id: &str) -> Result<String, SecretError> {
+    let response = aws_client()
+        .await
+        .get_secret_value()
+        .secret_id(secret_id)
+        .send()
+        .await
+        .map_err(|e| SecretError::Aws(DisplayErrorContext(e).to_string()))?;
+
+    response
+        .secret_string()
+        .map(str::to_string)
+        .ok_or_else(|| SecretError::NotFound(secret_id.to_string()))
+}
+
+async fn vault_secret(path: &str) -> Result<String, SecretError> {
+    let addr = std::env::var(VAULT_ADDR_ENV)
+        .map_err(|_| SecretError::NotFound(VAULT_ADDR_ENV.to_string()))?;
+    let token = std::env::var(VAULT_TOKEN_ENV)
+        .map_err(|_| SecretError::NotFound(VAULT_TOKEN_ENV.to_string()))?;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/v1/{}", addr.trim_end_matches('/'), path))
+        .header("X-Vault-Token", token)
+        .send()
+        .await?
+        .error_for_status()?;
+    let response: Value = serde_json::from_str(&response.text().await?)?;
+
+    // KV version 2 nests the secret one level deeper than version 1
+    let data = response
+        .pointer("/data/data")
+        .or_else(|| response.get("data"))
+        .ok_or_else(|| SecretError::NotFound(path.to_string()))?;
+    Ok(data.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use axum::{
+        Json, Router,
+        http::{HeaderMap, header},
+        routing::{get, post},
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    // Serves `router` on a local port, returning its base URL
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        assert_eq!(resolve("plain").await.unwrap(), "plain");
+
+        unsafe {
+            std::env::set_var("CEDRUS_TEST_SECRET", r#"{"user":"cedrus","port":5984}"#);
+        }
+        assert_eq!(
+            resolve("secretRef:env:CEDRUS_TEST_SECRET#user")
+                .await
+                .unwrap(),
+            "cedrus"
+        );
+        assert_eq!(
+            resolve("secretRef:env:CEDRUS_TEST_SECRET#port")
+                .await
+                .unwrap(),
+            "5984"
+        );
+        assert!(matches!(
+            resolve("secretRef:env:CEDRUS_TEST_SECRET#password").await,
+            Err(SecretError::NotFound(_))
+        ));
+        assert!(matches!(
+            resolve("secretRef:env:CEDRUS_TEST_MISSING").await,
+            Err(SecretError::NotFound(_))
+        ));
+        assert!(matches!(
+            resolve("secretRef:env").await,
+            Err(SecretError::InvalidRef(_))
+        ));
+        assert!(matches!(
+            resolve("secretRef:s3:bucket").await,
+            Err(SecretError::InvalidRef(_))
+        ));
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "s3cr3t").unwrap();
+        let reference = format!("secretRef:file:{}", file.path().display());
+        assert_eq!(resolve(&reference).await.unwrap(), "s3cr3t");
+
+        let mut value = json!({
+            "db": { "password": reference },
+            "keys": ["plain", "secretRef:env:CEDRUS_TEST_SECRET#user"],
+        });
+        resolve_value(&mut value).await.unwrap();
+        assert_eq!(
+            value,
+            json!({ "db": { "password": "s3cr3t" }, "keys": ["plain", "cedrus"] })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_vault() {
+        let router = Router::new().route(
+            "/v1/secret/data/cedrus",
+            get(|headers: HeaderMap| async move {
+                assert_eq!(headers["x-vault-token"], "token");
+                Json(json!({ "data": { "data": { "password": "s3cr3t" } } }))
+            }),
+        );
+        let addr = serve(router).await;
+        unsafe {
+            std::env::set_var(VAULT_ADDR_ENV, addr);
+            std::env::set_var(VAULT_TOKEN_ENV, "token");
+        }
+
+        assert_eq!(
+            resolve("secretRef:vault:secret/data/cedrus#password")
+                .await
+                .unwrap(),
+            "s3cr3t"
+        );
+        assert!(
+            resolve("secretRef:vault:secret/data/missing")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_aws() {
+        let router = Router::new().route(
+            "/",
+            post(|headers: HeaderMap, body: String| async move {
+                assert_eq!(headers["x-amz-target"], "secretsmanager.GetSecretValue");
+                let request: Value = serde_json::from_str(&body).unwrap();
+                (
+                    [(header::CONTENT_TYPE, "application/x-amz-json-1.1")],
+                    json!({
+                        "Name": request["SecretId"],
+                        "SecretString": r#"{"password":"s3cr3t"}"#,
+                    })
+                    .to_string(),
+                )
+            }),
+        );
+        let addr = serve(router).await;
+        unsafe {
+            std::env::set_var("AWS_ENDPOINT_URL", addr);
+            std::env::set_var("AWS_REGION", "us-east-1");
+            std::env::set_var("AWS_ACCESS_KEY_ID", "local");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "local");
+        }
+
+        // Both resolutions go through the one client
+        assert_eq!(
+            resolve("secretRef:aws:cedrus#password").await.unwrap(),
+            "s3cr3t"
+        );
+        assert_eq!(
+            resolve("secretRef:aws:cedrus").await.unwrap(),
+            r#"{"password":"s3cr3t"}"#
+        );
+        assert!(AWS_CLIENT.initialized());
+    }
+}
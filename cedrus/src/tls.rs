@@ -0,0 +1,206 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use cedrus_core::core::TlsConfig;
+use rustls::{
+    RootCertStore, ServerConfig,
+    crypto::aws_lc_rs,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::WebPkiClientVerifier,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const PENDING_CONNECTIONS: usize = 1024;
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(std::io::Error),
+    Pem(String, rustls::pki_types::pem::Error),
+    Rustls(rustls::Error),
+    ClientVerifier(rustls::server::VerifierBuilderError),
+}
+
+impl std::error::Error for TlsError {}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TlsError::Io(e) => write!(f, "tls io error: {}", e),
+            TlsError::Pem(file, e) => write!(f, "tls pem error in {}: {}", file, e),
+            TlsError::Rustls(e) => write!(f, "tls error: {}", e),
+            TlsError::ClientVerifier(e) => write!(f, "tls client verifier error: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for TlsError {
+    fn from(e: std::io::Error) -> Self {
+        TlsError::Io(e)
+    }
+}
+
+impl From<rustls::Error> for TlsError {
+    fn from(e: rustls::Error) -> Self {
+        TlsError::Rustls(e)
+    }
+}
+
+fn certificates(file: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    CertificateDer::pem_file_iter(file)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| TlsError::Pem(file.to_string(), e))
+}
+
+/// Builds the rustls configuration from the PEM files of `config`.
+pub fn server_config(config: &TlsConfig) -> Result<ServerConfig, TlsError> {
+    let provider = Arc::new(aws_lc_rs::default_provider());
+
+    let certs = certificates(&config.cert)?;
+    let key = PrivateKeyDer::from_pem_file(&config.key)
+        .map_err(|e| TlsError::Pem(config.key.clone(), e))?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+
+    let builder = match &config.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in certificates(client_ca)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if config.client_auth_optional {
+                verifier.allow_unauthenticated()
+            } else {
+                verifier
+            };
+            builder.with_client_cert_verifier(verifier.build().map_err(TlsError::ClientVerifier)?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder.with_single_cert(certs, key)?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(server_config)
+}
+
+// Modification times of the files the configuration is built from
+fn modified(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [
+        Some(&config.cert),
+        Some(&config.key),
+        config.client_ca.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+    .collect()
+}
+
+/// HTTPS listener for `axum::serve`. Handshakes run off the accept loop, and the
+/// certificates are reloaded when their files change if `reloadInterval` is set.
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    pub async fn bind(addr: &str, config: &TlsConfig) -> Result<Self, TlsError> {
+        let current = Arc::new(RwLock::new(Arc::new(server_config(config)?)));
+
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+
+        if let Some(interval) = config.reload_interval.filter(|interval| *interval > 0) {
+            tokio::spawn(reload_loop(
+                config.clone(),
+                current.clone(),
+                Duration::from_secs(interval),
+            ));
+        }
+
+        let (sender, incoming) = mpsc::channel(PENDING_CONNECTIONS);
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!("tls: accept failed: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+
+                let acceptor = TlsAcceptor::from(current.read().unwrap().clone());
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = sender.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => tracing::debug!("tls: handshake with {} failed: {}", addr, e),
+                        Err(_) => tracing::debug!("tls: handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            incoming,
+            local_addr,
+        })
+    }
+}
+
+async fn reload_loop(
+    config: TlsConfig,
+    current: Arc<RwLock<Arc<ServerConfig>>>,
+    interval: Duration,
+) {
+    let mut last = modified(&config);
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let now = modified(&config);
+        if now == last {
+            continue;
+        }
+
+        match server_config(&config) {
+            Ok(server_config) => {
+                *current.write().unwrap() = Arc::new(server_config);
+                last = now;
+                tracing::info!("tls: certificates reloaded");
+            }
+            // Files may be half written, keep serving the previous certificates
+            Err(e) => tracing::warn!("tls: unable to reload certificates: {}", e),
+        }
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(accepted) => accepted,
+            // The accept loop only ends with the runtime
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
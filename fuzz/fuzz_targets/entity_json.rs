@@ -0,0 +1,14 @@
+#![no_main]
+
+use cedrus_cedar::{Entity, proto};
+use libfuzzer_sys::fuzz_target;
+
+// Entities as posted to the entity routes, then compiled and cached
+fuzz_target!(|data: &[u8]| {
+    if let Ok(entities) = serde_json::from_slice::<Vec<Entity>>(data) {
+        for entity in entities {
+            let _ = entity.to_cedar_entity(None);
+            let _ = Entity::from(proto::Entity::from(entity));
+        }
+    }
+});
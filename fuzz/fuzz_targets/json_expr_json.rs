@@ -0,0 +1,13 @@
+#![no_main]
+
+use cedrus_cedar::{JsonExpr, expr::AttributeReferences, proto};
+use libfuzzer_sys::fuzz_target;
+
+// Condition bodies of the policies, walked for their references and cached
+fuzz_target!(|data: &[u8]| {
+    if let Ok(expr) = serde_json::from_slice::<JsonExpr>(data) {
+        let mut references = AttributeReferences::default();
+        expr.accept(&mut references);
+        let _ = JsonExpr::from(proto::JsonExpr::from(expr));
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use cedrus_cedar::{Policy, Template, proto};
+use libfuzzer_sys::fuzz_target;
+
+// Policies and templates as posted to the policy routes, then compiled and cached
+fuzz_target!(|data: &[u8]| {
+    if let Ok(policy) = serde_json::from_slice::<Policy>(data) {
+        let _: Result<cedar_policy::Policy, _> = policy.clone().try_into();
+        let _ = policy.references();
+        let _ = Policy::from(proto::Policy::from(policy));
+    }
+    if let Ok(template) = serde_json::from_slice::<Template>(data) {
+        let _: Result<cedar_policy::Template, _> = template.clone().try_into();
+        let _ = Template::from(proto::Template::from(template));
+    }
+});
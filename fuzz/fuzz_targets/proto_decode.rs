@@ -0,0 +1,24 @@
+#![no_main]
+
+use cedrus_cedar::{Entity, JsonExpr, Policy, Schema, Template, proto};
+use libfuzzer_sys::fuzz_target;
+use prost::Message;
+
+// Protobuf values read back from the cache
+fuzz_target!(|data: &[u8]| {
+    if let Ok(entity) = proto::Entity::decode(data) {
+        let _ = proto::Entity::from(Entity::from(entity));
+    }
+    if let Ok(policy) = proto::Policy::decode(data) {
+        let _ = proto::Policy::from(Policy::from(policy));
+    }
+    if let Ok(template) = proto::Template::decode(data) {
+        let _ = proto::Template::from(Template::from(template));
+    }
+    if let Ok(schema) = proto::Schema::decode(data) {
+        let _ = proto::Schema::from(Schema::from(schema));
+    }
+    if let Ok(expr) = proto::JsonExpr::decode(data) {
+        let _ = proto::JsonExpr::from(JsonExpr::from(expr));
+    }
+});
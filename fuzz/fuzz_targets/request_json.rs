@@ -0,0 +1,16 @@
+#![no_main]
+
+use cedrus_cedar::Request;
+use libfuzzer_sys::fuzz_target;
+
+// Authorization requests as posted to the is-authorized routes, then converted for Cedar
+fuzz_target!(|data: &[u8]| {
+    if let Ok(request) = serde_json::from_slice::<Request>(data) {
+        let _ = cedar_policy::EntityUid::try_from(request.principal);
+        let _ = cedar_policy::EntityUid::try_from(request.action);
+        let _ = cedar_policy::EntityUid::try_from(request.resource);
+        if let Some(context) = request.context {
+            let _ = context.to_cedar_context(None);
+        }
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use cedrus_cedar::{Schema, proto};
+use libfuzzer_sys::fuzz_target;
+
+// Schemas as put to the schema routes, then compiled and cached
+fuzz_target!(|data: &[u8]| {
+    if let Ok(schema) = serde_json::from_slice::<Schema>(data) {
+        let _: Result<cedar_policy::Schema, _> = schema.clone().try_into();
+        let _ = Schema::from(proto::Schema::from(schema));
+    }
+});
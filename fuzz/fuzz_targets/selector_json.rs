@@ -0,0 +1,15 @@
+#![no_main]
+
+use cedrus_core::{Query, Selector, db::dynamodb::QueryFilter};
+use libfuzzer_sys::fuzz_target;
+
+// Selectors of the list routes, translated into database filters
+fuzz_target!(|data: &[u8]| {
+    if let Ok(selector) = serde_json::from_slice::<Selector>(data) {
+        let query = Query {
+            selector: Some(selector),
+            ..Default::default()
+        };
+        let _ = QueryFilter::new_with_query(&query, "#PK = :PK");
+    }
+});